@@ -0,0 +1,158 @@
+use std::{hint::black_box, io::{BufRead, BufReader}, path::PathBuf, process::{Child, Command, Stdio}};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use nix::unistd::Pid;
+use memory::{Endianness, PairDistance, ScanOptions, ScanStats, Snapshot, StringSearch, StructPattern, TypedValue, find_masked, find_pair, find_string, find_struct, find_value, find_value_by_predicate, find_value_generic, get_possible_memory_ranges, read_many, read_scalar, reduce_found_values};
+
+// Must match the constants in benches/support/bench_target.rs
+const PLANTED_I32: i32 = 0x5eed_1234;
+const PLANTED_F32: f32 = 4321.5;
+const TARGET_MEGABYTES: usize = 256;
+const REDUCE_ADDRESSES: usize = 1_000_000;
+const READ_MANY_ADDRESSES: usize = 10_000;
+
+struct Target {
+    child: Child,
+    pid: Pid,
+    base: usize,
+    len: usize,
+}
+
+impl Target {
+    fn spawn(megabytes: usize) -> Target {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_bench_target"))
+            .arg(megabytes.to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Expected to be able to spawn the benchmark target");
+        let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+        let mut pid = None;
+        let mut allocation = None;
+        // Wait for the target to report that its memory is in place before benchmarking
+        while allocation.is_none() {
+            let line = lines.next().expect("Expected the benchmark target to report its allocation").unwrap();
+            let words = line.split_whitespace().collect::<Vec<&str>>();
+            match words.as_slice() {
+                ["pid", x] => pid = Some(Pid::from_raw(x.parse().unwrap())),
+                ["allocated", base, len] => allocation = Some((usize::from_str_radix(base.trim_start_matches("0x"), 16).unwrap(), len.parse().unwrap())),
+                _ => {}
+            }
+        }
+        let (base, len) = allocation.unwrap();
+        Target { child, pid: pid.expect("Expected the benchmark target to report its pid"), base, len }
+    }
+
+    fn readable_bytes(&self) -> u64 {
+        get_possible_memory_ranges(self.pid).unwrap().iter().map(|x| (x.1 - x.0) as u64).sum()
+    }
+}
+
+impl Drop for Target {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn first_scan(c: &mut Criterion) {
+    let target = Target::spawn(TARGET_MEGABYTES);
+    let options = ScanOptions::default();
+    let mut group = c.benchmark_group("first_scan");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(target.readable_bytes()));
+    group.bench_function("i32", |b| b.iter(|| find_value(target.pid, black_box(PLANTED_I32), &options).unwrap()));
+    group.bench_function("f32", |b| b.iter(|| find_value(target.pid, black_box(PLANTED_F32), &options).unwrap()));
+    group.bench_function("generic_i32", |b| b.iter(|| find_value_generic(target.pid, black_box(PLANTED_I32), &options).unwrap()));
+    group.bench_function("predicate_i32", |b| b.iter(|| find_value_by_predicate::<i32>(target.pid, |x| *x == PLANTED_I32, &options).unwrap()));
+    group.finish();
+}
+
+// The scans that match more than one fixed value: the planted i32 and the f32 half a page after it
+// as a struct and as a pair, part of the i32 under a mask, and strings, which the filler never holds
+fn pattern_scan(c: &mut Criterion) {
+    let target = Target::spawn(TARGET_MEGABYTES);
+    let options = ScanOptions::default();
+    let pattern = format!("+0 i32 {}, +0x800 f32 {}", PLANTED_I32, PLANTED_F32).parse::<StructPattern>().unwrap();
+    let glob = StringSearch { glob: true, ignore_case: true, ..StringSearch::default() };
+    let mut group = c.benchmark_group("pattern_scan");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(target.readable_bytes()));
+    group.bench_function("struct_i32_f32", |b| b.iter(|| find_struct(target.pid, black_box(&pattern), &options).unwrap()));
+    group.bench_function("pair_i32_f32", |b| b.iter(|| find_pair(target.pid, &TypedValue::I32(PLANTED_I32), &TypedValue::F32(PLANTED_F32), PairDistance::Exactly(0x800), &options).unwrap()));
+    group.bench_function("masked_i32", |b| b.iter(|| find_masked(target.pid, PLANTED_I32 as u64 & 0xffff_0000, 0xffff_0000, 4, &options).unwrap()));
+    group.bench_function("string", |b| b.iter(|| find_string(target.pid, black_box("player"), &StringSearch::default(), &options).unwrap()));
+    group.bench_function("string_glob", |b| b.iter(|| find_string(target.pid, black_box("pl?yer*name"), &glob, &options).unwrap()));
+    group.finish();
+}
+
+fn reduce(c: &mut Criterion) {
+    let target = Target::spawn(TARGET_MEGABYTES);
+    let addresses = (0..REDUCE_ADDRESSES).map(|x| target.base + (x * 4) % target.len).collect::<Vec<usize>>();
+    let mut group = c.benchmark_group("reduce");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(REDUCE_ADDRESSES as u64));
+    group.bench_function("i32_1m", |b| b.iter_batched_ref(|| addresses.clone(), |x| reduce_found_values(target.pid, x, PLANTED_I32, &ScanOptions::default(), &mut ScanStats::default()).unwrap(), BatchSize::LargeInput));
+    group.finish();
+}
+
+// The same scattered addresses read one call each and gathered into as few calls as possible
+fn read_many_addresses(c: &mut Criterion) {
+    let target = Target::spawn(TARGET_MEGABYTES);
+    let addresses = (0..READ_MANY_ADDRESSES).map(|x| target.base + (x * 4096 + 12) % target.len).collect::<Vec<usize>>();
+    let mut group = c.benchmark_group("read_many");
+    group.throughput(Throughput::Elements(READ_MANY_ADDRESSES as u64));
+    group.bench_function("individual_i32_10k", |b| b.iter(|| addresses.iter().map(|x| read_scalar::<i32>(target.pid, *x, Endianness::Native)).collect::<Vec<_>>()));
+    group.bench_function("gathered_i32_10k", |b| b.iter(|| read_many::<i32>(target.pid, black_box(&addresses))));
+    group.finish();
+}
+
+fn snapshot_compare(c: &mut Criterion) {
+    let target = Target::spawn(TARGET_MEGABYTES);
+    let mut group = c.benchmark_group("snapshot_compare");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(target.readable_bytes()));
+    for compress_snapshots in [false, true] {
+        let options = ScanOptions { compress_snapshots, ..Default::default() };
+        let snapshot = Snapshot::capture(target.pid, &options).unwrap();
+        let name = if compress_snapshots { "changed_i32_lz4" } else { "changed_i32_raw" };
+        group.bench_function(name, |b| b.iter(|| snapshot.compare::<i32>(target.pid, |old, new| old != new).unwrap()));
+    }
+    group.finish();
+}
+
+// examples/victim, built next to bench_target if cargo bench has not built it already
+fn spawn_victim() -> (Child, Pid, usize) {
+    let path = PathBuf::from(env!("CARGO_BIN_EXE_bench_target")).parent().unwrap().join("examples/victim");
+    if !path.exists() {
+        let built = Command::new(env!("CARGO")).args(["build", "-q", "--release", "--example", "victim"]).status().unwrap();
+        assert!(built.success(), "Expected to be able to build examples/victim");
+    }
+    // An interval of 0 keeps its hp at 100 for as long as nothing is written to it
+    let mut child = Command::new(path).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().expect("Expected to be able to spawn the victim");
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+    let pid = Pid::from_raw(lines.next().unwrap().unwrap().strip_prefix("pid ").unwrap().parse().unwrap());
+    let player = lines.next().unwrap().unwrap();
+    let player = usize::from_str_radix(player.strip_prefix("player 0x").unwrap().split_once(' ').unwrap().0, 16).unwrap();
+    // Read the last line too, so that it is not written into a closed pipe
+    lines.next().unwrap().unwrap();
+    (child, pid, player)
+}
+
+// The walkthrough's first steps against a process of ordinary size rather than a planted one
+fn victim(c: &mut Criterion) {
+    let (mut child, pid, player) = spawn_victim();
+    let options = ScanOptions::default();
+    let mut group = c.benchmark_group("victim");
+    group.throughput(Throughput::Bytes(get_possible_memory_ranges(pid).unwrap().iter().map(|x| (x.1 - x.0) as u64).sum()));
+    group.bench_function("scan_hp", |b| b.iter(|| find_value(pid, black_box(100i32), &options).unwrap()));
+    let (found, _) = find_value(pid, 100i32, &options).unwrap();
+    assert!(found.contains(&player), "Expected the victim's hp among the results");
+    group.throughput(Throughput::Elements(found.len() as u64));
+    group.bench_function("rescan_hp", |b| b.iter_batched_ref(|| found.clone(), |x| reduce_found_values(pid, x, 100i32, &options, &mut ScanStats::default()).unwrap(), BatchSize::SmallInput));
+    group.finish();
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+criterion_group!(benches, first_scan, pattern_scan, reduce, read_many_addresses, snapshot_compare, victim);
+criterion_main!(benches);
@@ -0,0 +1,64 @@
+// Synthetic target for the benchmarks: allocates a block of memory with known values planted
+// at fixed strides, reports where it is, and idles until its stdin is closed. Given "tick" after
+// the size, a second thread also counts player.hp up every few milliseconds through tick, for the
+// watchpoint and breakpoint tests to catch. Given "threads" instead, it keeps starting threads that
+// live for a fraction of a second, for the ptrace session tests to see brought in
+use std::io::{Read, Write};
+
+// Must match the constants in benches/scan.rs
+const PLANTED_I32: i32 = 0x5eed_1234;
+const PLANTED_F32: f32 = 4321.5;
+const STRIDE: usize = 4096;
+
+// Read back by examples/read_struct.rs
+#[repr(C)]
+struct Player {
+    hp: i32,
+    mp: i32,
+    pos: [f32; 3],
+}
+
+// Called for every tick, so there is a function to put a breakpoint on
+#[inline(never)]
+fn tick(hp: *mut i32) {
+    unsafe { hp.write_volatile(hp.read_volatile() + 1) };
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let megabytes = std::env::args().nth(1).unwrap_or("256".to_string()).parse::<usize>()?;
+    // Fill with a non-zero byte so every page is resident and nothing matches by accident
+    let mut block: Vec<u8> = vec![0x11; megabytes << 20];
+    for chunk in block.chunks_exact_mut(STRIDE) {
+        chunk[..4].copy_from_slice(&PLANTED_I32.to_ne_bytes());
+        chunk[STRIDE / 2..STRIDE / 2 + 4].copy_from_slice(&PLANTED_F32.to_ne_bytes());
+    }
+    // Only ever reached through the raw pointer, since the tick thread writes it
+    let player = Box::into_raw(Box::new(Player { hp: 100, mp: 50, pos: [1.0, 2.0, 3.0] }));
+    let ticking = std::env::args().nth(2).as_deref() == Some("tick");
+    if ticking {
+        let hp = unsafe { &raw mut (*player).hp } as usize;
+        std::thread::spawn(move || loop {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+            tick(hp as *mut i32);
+        });
+    }
+    if std::env::args().nth(2).as_deref() == Some("threads") {
+        std::thread::spawn(|| loop {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            std::thread::spawn(|| std::thread::sleep(std::time::Duration::from_millis(200)));
+        });
+    }
+    let mut stdout = std::io::stdout();
+    writeln!(stdout, "pid {}", std::process::id())?;
+    writeln!(stdout, "player 0x{:x}", player as usize)?;
+    if ticking {
+        writeln!(stdout, "tick 0x{:x}", tick as *const () as usize)?;
+    }
+    writeln!(stdout, "allocated 0x{:x} {}", block.as_ptr() as usize, block.len())?;
+    stdout.flush()?;
+    // Returns once the benchmark closes the pipe (or dies), so the target never outlives it
+    let _ = std::io::stdin().read_to_end(&mut Vec::new());
+    std::hint::black_box(&block);
+    std::hint::black_box(player);
+    Ok(())
+}
@@ -0,0 +1,39 @@
+// Reads a struct out of another process and edits one of its fields. Start the benchmark target
+// with `cargo run --bin bench_target 1`, then pass the pid and player address it prints:
+//     cargo run --example read_struct -- <pid> <player address>
+use memory::{Endianness, FromBytes, Immutable, IntoBytes, KnownLayout, field_address, read_struct, write_scalar, write_struct};
+use nix::unistd::Pid;
+
+// Laid out exactly as in the target. Deriving FromBytes is what makes read_struct safe: the
+// compiler checks that every field accepts any bit pattern and that there is no padding
+#[repr(C)]
+#[derive(Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
+struct Player {
+    hp: i32,
+    mp: i32,
+    pos: [f32; 3],
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = std::env::args().collect::<Vec<String>>();
+    let (Some(pid), Some(address)) = (args.get(1), args.get(2)) else {
+        return Err("Usage: read_struct <pid> <player address>".into());
+    };
+    let pid = Pid::from_raw(pid.parse()?);
+    let address = usize::from_str_radix(address.trim_start_matches("0x"), 16)?;
+
+    let player = read_struct::<Player>(pid, address)?;
+    println!("read {:?}", player);
+
+    // A single field can be written, locked or watched through its own address
+    let hp = field_address!(address, Player, hp);
+    write_scalar(pid, hp, player.hp + 1, Endianness::Native)?;
+    println!("hp at 0x{:x} is now {}", hp, read_struct::<Player>(pid, address)?.hp);
+
+    // Or the whole struct read, changed and written back in one go
+    let mut player = read_struct::<Player>(pid, address)?;
+    player.pos[1] += 10.0;
+    write_struct(pid, address, &player)?;
+    println!("moved to {:?}, pos starts at 0x{:x}", read_struct::<Player>(pid, address)?.pos, field_address!(address, Player, pos));
+    Ok(())
+}
@@ -0,0 +1,77 @@
+// A harmless target to try every feature on, laid out the same way on every run. Start it with
+//     cargo run --example victim
+// and it prints its pid, where the player is, and where the static pointing at the player is:
+//     pid 4242
+//     player 0x55d5c3f1a2b0 (94376603787952)
+//     pointer 0x55d0c2b1e048
+// Every second the player loses 1 hp, coming back at 100 after reaching 1, gains 25 gold, and
+// switches between walking and running speed. Then, in another terminal:
+//     cargo run -- <pid>
+//     > scan i32 100                            every i32 that is 100, the player's hp among them
+//     > rescan 99                               once it has dropped, which leaves only the hp
+//     > write #0 500                            until the next tick takes 1 off it again
+//     > lock #0 set 500                         kept there whatever the ticks do
+//     > scan u64 94376603787952                 the player's address, finding the static among others
+//     > lock victim+<offset>->0x8 i64 set 9999  the gold, through the pointer, wherever it was put
+// The offset is the pointer's address minus where the victim's first mapping starts, as `maps`
+// shows it. The benchmarks and tests/victim.rs start it with an interval of 0, which only ticks
+// once for each line read from standard input and answers with "ticked"; either way it exits once
+// its standard input is closed
+use std::{io::{BufRead, Write}, ptr::{addr_of_mut, read_volatile, write_volatile}, sync::atomic::{AtomicPtr, Ordering}, time::Duration};
+
+// At offsets 0x0, 0x8, 0x10 and 0x14, 0x28 bytes in all with padding after hp and after name
+#[repr(C)]
+struct Player {
+    hp: i32,
+    gold: i64,
+    speed: f32,
+    name: [u8; 16],
+}
+
+const WALKING: f32 = 1.5;
+const RUNNING: f32 = 3.0;
+
+// A pointer to the player that is always at the same offset into the executable, for a pointer
+// chain to start from and a pointer scan to find
+static PLAYER: AtomicPtr<Player> = AtomicPtr::new(std::ptr::null_mut());
+
+// Volatile, as whatever is attached may have changed any of it since the last tick
+fn tick() {
+    let player = PLAYER.load(Ordering::SeqCst);
+    unsafe {
+        let hp = read_volatile(addr_of_mut!((*player).hp));
+        write_volatile(addr_of_mut!((*player).hp), if hp <= 1 { 100 } else { hp - 1 });
+        let gold = read_volatile(addr_of_mut!((*player).gold));
+        write_volatile(addr_of_mut!((*player).gold), gold.wrapping_add(25));
+        let speed = read_volatile(addr_of_mut!((*player).speed));
+        write_volatile(addr_of_mut!((*player).speed), if speed == WALKING { RUNNING } else { WALKING });
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let interval = std::env::args().nth(1).map(|x| x.parse::<u64>()).transpose()?.unwrap_or(1000);
+    let mut name = [0u8; 16];
+    name[..10].copy_from_slice(b"Player One");
+    let player = Box::into_raw(Box::new(Player { hp: 100, gold: 1000, speed: WALKING, name }));
+    PLAYER.store(player, Ordering::SeqCst);
+    let mut stdout = std::io::stdout();
+    writeln!(stdout, "pid {}", std::process::id())?;
+    writeln!(stdout, "player 0x{:x} ({})", player as usize, player as usize)?;
+    writeln!(stdout, "pointer 0x{:x}", &PLAYER as *const AtomicPtr<Player> as usize)?;
+    stdout.flush()?;
+    if interval > 0 {
+        std::thread::spawn(move || loop {
+            std::thread::sleep(Duration::from_millis(interval));
+            tick();
+        });
+    }
+    for line in std::io::stdin().lock().lines() {
+        line?;
+        if interval == 0 {
+            tick();
+            writeln!(stdout, "ticked")?;
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,34 @@
+#![no_main]
+use std::sync::{Arc, OnceLock};
+use libfuzzer_sys::fuzz_target;
+use memory::{MockProcess, PointerChain, RegionCache, SavedAddress, resolve_address};
+
+// A module and a heap with pointers between them, so that chains the parser accepts are also
+// followed, into unmapped memory and off the ends of regions as often as not
+fn process() -> &'static Arc<MockProcess> {
+    static PROCESS: OnceLock<Arc<MockProcess>> = OnceLock::new();
+    PROCESS.get_or_init(|| {
+        let mock = MockProcess::new(100);
+        mock.map("400000-401000 r--p 00000000 08:01 1234 /usr/bin/game").unwrap();
+        mock.map("401000-402000 rw-p 00001000 08:01 1234 /usr/bin/game").unwrap();
+        mock.map("100000-102000 rw-p 00000000 00:00 0 [heap]").unwrap();
+        for offset in (0..0x1000).step_by(8) {
+            mock.plant_value(0x401000 + offset, 0x100000 + offset * 2).unwrap();
+            mock.plant_value(0x100000 + offset, 0x101000usize.wrapping_sub(offset * 3)).unwrap();
+        }
+        Arc::new(mock)
+    })
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else { return };
+    if let Ok(address) = text.parse::<SavedAddress>() {
+        assert_eq!(address.to_string().parse::<SavedAddress>().unwrap(), address);
+    }
+    if let Ok(chain) = text.parse::<PointerChain>() {
+        assert_eq!(chain.to_string().parse::<PointerChain>().unwrap(), chain);
+    }
+    let process = process();
+    let mut regions = RegionCache::from_process(process.clone()).unwrap();
+    let _ = resolve_address(&**process, &mut regions, text);
+});
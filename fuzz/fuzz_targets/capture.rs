@@ -0,0 +1,20 @@
+#![no_main]
+use std::io::Write;
+use libfuzzer_sys::fuzz_target;
+use memory::{Capture, OfflineCapture, ProcessMemory, read_bytes_from_process};
+
+// Captures are read from a file, so each input is written to one first. Everything that opens is
+// then read back a little at the start and end of every region
+fuzz_target!(|data: &[u8]| {
+    let path = std::env::temp_dir().join(format!("memory-fuzz-capture-{}", std::process::id()));
+    std::fs::File::create(&path).unwrap().write_all(data).unwrap();
+    if let Ok(capture) = Capture::open(&path) {
+        let _ = capture.len();
+    }
+    if let Ok(capture) = OfflineCapture::open(&path) {
+        for region in capture.memory_regions().unwrap() {
+            let _ = read_bytes_from_process(&capture, 64, region.start);
+            let _ = read_bytes_from_process(&capture, 64, region.end.saturating_sub(64));
+        }
+    }
+});
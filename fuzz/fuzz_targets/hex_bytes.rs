@@ -0,0 +1,10 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use memory::parse_hex_bytes;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else { return };
+    if let Ok(bytes) = parse_hex_bytes(text) {
+        assert!(!bytes.is_empty() && bytes.len() * 2 <= text.len());
+    }
+});
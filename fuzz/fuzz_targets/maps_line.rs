@@ -0,0 +1,14 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use memory::{MemoryRegion, RegionFilter, modules_from_regions};
+
+// Lines one at a time, as the maps are read, and then all of them together as modules
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else { return };
+    let regions = text.lines().filter_map(|x| x.parse::<MemoryRegion>().ok()).collect::<Vec<MemoryRegion>>();
+    for region in &regions {
+        assert!(region.end > region.start);
+    }
+    let _ = modules_from_regions(&regions);
+    let _ = text.parse::<RegionFilter>();
+});
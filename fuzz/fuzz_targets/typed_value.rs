@@ -0,0 +1,17 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use memory::{Endianness, TypedValue, ValueType};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else { return };
+    let _ = text.parse::<Endianness>();
+    if let Ok(value_type) = text.parse::<ValueType>() {
+        let _ = value_type.parse_value(text);
+        let _ = value_type.value_from_bytes(data);
+    }
+    // Whatever parses prints back as something that parses too, NaN aside
+    if let Ok(value) = text.parse::<TypedValue>() {
+        let _ = value.value_type().value_from_bytes(&value.to_bytes());
+        let _ = value.to_string().parse::<TypedValue>().unwrap();
+    }
+});
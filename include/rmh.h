@@ -0,0 +1,46 @@
+/* Built into libmemory.so with `cargo build --release --features ffi`.
+ *
+ * Functions that can fail return a negative RMH_ERR_* code; rmh_read, rmh_write and
+ * rmh_scan_i32 otherwise return a count. A handle may be used from several threads at once, but
+ * rmh_detach must come after every other call on it has returned. */
+
+#ifndef RMH_H
+#define RMH_H
+
+/* Generated by cbindgen from src/ffi.rs with cbindgen.toml; do not edit */
+
+#include <stdint.h>
+
+#define RMH_OK 0
+
+#define RMH_ERR_INVALID -1
+
+#define RMH_ERR_ACCESS -2
+
+#define RMH_ERR_NO_PROCESS -3
+
+#define RMH_ERR_BAD_ADDRESS -4
+
+#define RMH_ERR_NOT_LOCKED -5
+
+#define RMH_ERR_PANIC -6
+
+#define RMH_ERR_OTHER -7
+
+typedef struct RmhHandle RmhHandle;
+
+RmhHandle *rmh_attach(int32_t pid, int32_t *error);
+
+intptr_t rmh_read(const RmhHandle *handle, uintptr_t address, uint8_t *buf, uintptr_t len);
+
+intptr_t rmh_write(const RmhHandle *handle, uintptr_t address, const uint8_t *buf, uintptr_t len);
+
+intptr_t rmh_scan_i32(const RmhHandle *handle, int32_t value, uintptr_t *out_addrs, uintptr_t cap);
+
+int32_t rmh_lock(const RmhHandle *handle, uintptr_t address, const uint8_t *value, uintptr_t len, uint32_t interval_ms);
+
+int32_t rmh_unlock(const RmhHandle *handle, uintptr_t address);
+
+void rmh_detach(RmhHandle *handle);
+
+#endif  /* RMH_H */
@@ -0,0 +1,348 @@
+use std::{fmt, str::FromStr};
+use crate::{expr::Operator, maps::RegionCache, pointer::{follow_pointers, read_pointer}, process::ProcessMemory, session::SavedAddress, value::ParseValueError};
+
+// Brackets nested deeper than this are refused rather than parsed on the stack
+const MAX_DEPTH: usize = 64;
+
+// An address as every command takes one. The terms are numbers, hex as everywhere else in the tool
+// ("0x7f31c2a0" or "7f31c2a0") unless written "0n100" for decimal; names, a tag if there is one by
+// that name and otherwise a module's base ("player_base", "libgame.so"); tags as "@hp" and results
+// as "#12". Between them go + - * and brackets: "[x]" is the pointer read at x, so
+// "[player_base + 0x10] + 0x4" pokes along a pointer path without saving it as a chain first, and
+// "a->0x10->-0x8" is a chain as PointerChain writes one. A '-' between two letters is part of a
+// name, as in ld-linux-x86-64.so.2, so subtracting from a name takes spaces around the '-'
+#[derive(Debug, Clone, PartialEq)]
+pub enum AddressExpression {
+    Number(i128),
+    Name(String),
+    Tag(String),
+    Result(usize),
+    Negate(Box<AddressExpression>),
+    Binary(Operator, Box<AddressExpression>, Box<AddressExpression>),
+    Deref(Box<AddressExpression>),
+    // Reads the pointer at the base and adds the offset, for each offset in turn, checking every hop
+    // as resolve_pointer_chain does
+    Chain(Box<AddressExpression>, Vec<AddressExpression>),
+}
+
+// What results and tags stand for, which only a session knows
+pub trait AddressNames {
+    fn result(&self, index: usize) -> Result<usize, String>;
+    fn tag(&self, name: &str) -> Option<usize>;
+}
+
+// Outside a session nothing is named
+impl AddressNames for () {
+    fn result(&self, index: usize) -> Result<usize, String> {
+        Err(format!("#{} names a session's result, and there are none here", index))
+    }
+
+    fn tag(&self, _: &str) -> Option<usize> {
+        None
+    }
+}
+
+impl AddressExpression {
+    // Works it out against the modules loaded now and the pointers the process holds now
+    pub fn evaluate(&self, process: impl ProcessMemory, regions: &mut RegionCache, names: &dyn AddressNames) -> Result<usize, Box<dyn std::error::Error>> {
+        let value = self.value(&process, regions, names)?;
+        to_address(value, self)
+    }
+
+    fn value<P: ProcessMemory>(&self, process: &P, regions: &mut RegionCache, names: &dyn AddressNames) -> Result<i128, Box<dyn std::error::Error>> {
+        let value = match self {
+            AddressExpression::Number(x) => *x,
+            AddressExpression::Result(index) => names.result(*index)? as i128,
+            AddressExpression::Tag(tag) => names.tag(tag).ok_or_else(|| format!("No result is tagged {}; tag <address> {} tags one", tag, tag))? as i128,
+            AddressExpression::Name(name) => match names.tag(name) {
+                Some(address) => address as i128,
+                None => module_address(regions, names, name, 0)? as i128,
+            },
+            AddressExpression::Negate(x) => x.value(process, regions, names)?.checked_neg().ok_or_else(|| overflow(self))?,
+            // As "libgame.so+0x2a10" always has been, an offset into a module has to be inside it
+            AddressExpression::Binary(Operator::Add, a, b) if matches!((&**a, &**b), (AddressExpression::Name(name), AddressExpression::Number(_)) if names.tag(name).is_none()) => {
+                let (AddressExpression::Name(name), AddressExpression::Number(offset)) = (&**a, &**b) else { unreachable!() };
+                module_address(regions, names, name, usize::try_from(*offset).map_err(|_| overflow(self))?)? as i128
+            }
+            AddressExpression::Binary(operator, a, b) => {
+                let (a, b) = (a.value(process, regions, names)?, b.value(process, regions, names)?);
+                match operator {
+                    Operator::Add => a.checked_add(b),
+                    Operator::Sub => a.checked_sub(b),
+                    Operator::Mul => a.checked_mul(b),
+                    Operator::Div => a.checked_div(b),
+                }.ok_or_else(|| overflow(self))?
+            }
+            AddressExpression::Deref(x) => {
+                let address = to_address(x.value(process, regions, names)?, x)?;
+                read_pointer(process, regions, address).map_err(|e| format!("{}: {}", self, e))? as i128
+            }
+            AddressExpression::Chain(base, offsets) => {
+                let address = base.value(process, regions, names).and_then(|x| to_address(x, base)).map_err(|e| format!("hop 0 ({}): {}", base, e))?;
+                let offsets = offsets.iter().map(|x| isize::try_from(x.value(process, regions, names)?).map_err(|_| format!("{} is too far to be an offset", x).into())).collect::<Result<Vec<isize>, Box<dyn std::error::Error>>>()?;
+                follow_pointers(process, regions, address, &offsets)? as i128
+            }
+        };
+        Ok(value)
+    }
+}
+
+// A name that is not a tag is a module, and a miss says which loaded ones it could have meant, or
+// that a '-' joined the name to what was meant to be subtracted from it
+fn module_address(regions: &mut RegionCache, names: &dyn AddressNames, name: &str, offset: usize) -> Result<usize, Box<dyn std::error::Error>> {
+    regions.resolve(&SavedAddress::Module { name: name.to_string(), offset }).map_err(|e| match name.rsplit_once('-') {
+        Some((before, _)) if names.tag(before).is_some() || regions.find_module(before).is_some() => format!("{}; to subtract from {}, put spaces around the '-'", e, before).into(),
+        _ => e,
+    })
+}
+
+fn to_address(value: i128, expression: &AddressExpression) -> Result<usize, Box<dyn std::error::Error>> {
+    usize::try_from(value).map_err(|_| match value < 0 {
+        true => format!("{} comes to -0x{:x}, which is not an address", expression, value.unsigned_abs()).into(),
+        false => format!("{} comes to 0x{:x}, past the last address", expression, value).into(),
+    })
+}
+
+fn overflow(expression: &AddressExpression) -> Box<dyn std::error::Error> {
+    format!("{} overflows", expression).into()
+}
+
+impl FromStr for AddressExpression {
+    type Err = ParseValueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { text: s, position: 0, depth: 0 };
+        let expression = parser.chain()?;
+        parser.skip_spaces();
+        if parser.position < s.len() {
+            return Err(parser.error("an operator"));
+        }
+        Ok(expression)
+    }
+}
+
+fn symbol(operator: Operator) -> char {
+    match operator {
+        Operator::Add => '+',
+        Operator::Sub => '-',
+        Operator::Mul => '*',
+        Operator::Div => '/',
+    }
+}
+
+fn precedence(expression: &AddressExpression) -> u8 {
+    match expression {
+        AddressExpression::Chain(..) => 0,
+        AddressExpression::Binary(Operator::Add | Operator::Sub, ..) => 1,
+        AddressExpression::Binary(..) => 2,
+        _ => 3,
+    }
+}
+
+// Written so that it parses back to the same expression, with brackets only where they are needed
+impl fmt::Display for AddressExpression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bracketed = |f: &mut fmt::Formatter<'_>, x: &AddressExpression, below: u8| match precedence(x) < below {
+            true => write!(f, "({})", x),
+            false => write!(f, "{}", x),
+        };
+        match self {
+            AddressExpression::Number(x) if *x < 0 => write!(f, "-0x{:x}", x.unsigned_abs()),
+            AddressExpression::Number(x) => write!(f, "0x{:x}", x),
+            AddressExpression::Name(name) => write!(f, "{}", name),
+            AddressExpression::Tag(tag) => write!(f, "@{}", tag),
+            AddressExpression::Result(index) => write!(f, "#{}", index),
+            AddressExpression::Negate(x) => {
+                write!(f, "-")?;
+                bracketed(f, x, 3)
+            }
+            AddressExpression::Binary(operator, a, b) => {
+                let level = precedence(self);
+                bracketed(f, a, level)?;
+                write!(f, " {} ", symbol(*operator))?;
+                bracketed(f, b, level + 1)
+            }
+            AddressExpression::Deref(x) => write!(f, "[{}]", x),
+            AddressExpression::Chain(base, offsets) => {
+                bracketed(f, base, 1)?;
+                for offset in offsets {
+                    write!(f, "->")?;
+                    bracketed(f, offset, 1)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// What can be in a name besides the '-' and '+' that join one
+fn name_character(c: char) -> bool {
+    !c.is_whitespace() && !"+-*()[]#@>".contains(c)
+}
+
+// How a number is written: hex with or without 0x, or decimal after 0n
+fn parse_number(word: &str) -> Option<i128> {
+    let value = match word.strip_prefix("0n") {
+        Some(digits) => digits.parse::<u64>().ok()?,
+        None => u64::from_str_radix(word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")).unwrap_or(word), 16).ok()?,
+    };
+    // from_str_radix takes a sign, which a word never starts with
+    Some(value as i128)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn rest(&self) -> &str {
+        &self.text[self.position..]
+    }
+
+    fn skip_spaces(&mut self) {
+        self.position = self.text.len() - self.rest().trim_start().len();
+    }
+
+    // Takes the symbol if it is next, after any spaces
+    fn take(&mut self, symbol: &str) -> bool {
+        self.skip_spaces();
+        let taken = self.rest().starts_with(symbol);
+        if taken {
+            self.position += symbol.len();
+        }
+        taken
+    }
+
+    // Names the token the parser stopped at, a whole word if it is one
+    fn error(&self, expected: &str) -> ParseValueError {
+        let rest = self.rest().trim_start();
+        let word = rest.find(|x: char| !name_character(x)).unwrap_or(rest.len());
+        let token = match word {
+            0 => rest.chars().next().map(String::from),
+            length => Some(rest[..length].to_string()),
+        };
+        let column = self.text[..self.text.len() - rest.len()].chars().count() + 1;
+        ParseValueError(match token {
+            Some(token) => format!("Expected {} at '{}' (column {}) in '{}'", expected, token, column, self.text),
+            None => format!("Expected {} at the end of '{}'", expected, self.text),
+        })
+    }
+
+    fn chain(&mut self) -> Result<AddressExpression, ParseValueError> {
+        let base = self.sum()?;
+        let mut offsets = Vec::new();
+        while self.take("->") {
+            offsets.push(self.sum()?);
+        }
+        Ok(match offsets.is_empty() {
+            true => base,
+            false => AddressExpression::Chain(Box::new(base), offsets),
+        })
+    }
+
+    fn sum(&mut self) -> Result<AddressExpression, ParseValueError> {
+        let mut expression = self.product()?;
+        loop {
+            self.skip_spaces();
+            let operator = match self.rest() {
+                x if x.starts_with("->") => return Ok(expression),
+                x if x.starts_with('+') => Operator::Add,
+                x if x.starts_with('-') => Operator::Sub,
+                _ => return Ok(expression),
+            };
+            self.position += 1;
+            expression = AddressExpression::Binary(operator, Box::new(expression), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<AddressExpression, ParseValueError> {
+        let mut expression = self.unary()?;
+        while self.take("*") {
+            expression = AddressExpression::Binary(Operator::Mul, Box::new(expression), Box::new(self.unary()?));
+        }
+        Ok(expression)
+    }
+
+    fn unary(&mut self) -> Result<AddressExpression, ParseValueError> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error(&format!("at most {} levels of brackets", MAX_DEPTH)));
+        }
+        self.depth += 1;
+        let expression = match self.take("-") {
+            true => AddressExpression::Negate(Box::new(self.unary()?)),
+            false => self.term()?,
+        };
+        self.depth -= 1;
+        Ok(expression)
+    }
+
+    fn term(&mut self) -> Result<AddressExpression, ParseValueError> {
+        for (open, close) in [("(", ")"), ("[", "]")] {
+            if self.take(open) {
+                let expression = self.chain()?;
+                if !self.take(close) {
+                    return Err(self.error(&format!("'{}'", close)));
+                }
+                return Ok(match open {
+                    "[" => AddressExpression::Deref(Box::new(expression)),
+                    _ => expression,
+                });
+            }
+        }
+        if self.take("#") {
+            let length = self.rest().find(|x: char| !x.is_ascii_digit()).unwrap_or(self.rest().len());
+            let index = self.rest()[..length].parse::<usize>().map_err(|_| self.error("a result's index after #"))?;
+            self.position += length;
+            return Ok(AddressExpression::Result(index));
+        }
+        if self.take("@") {
+            let tag = self.name();
+            if tag.is_empty() {
+                return Err(self.error("a tag after @"));
+            }
+            return Ok(AddressExpression::Tag(tag));
+        }
+        // A word of letters and digits that starts with a digit can only be a number, where one with
+        // a dot in it, such as 7z.so, is a name
+        if self.rest().starts_with(|x: char| x.is_ascii_digit()) {
+            let length = self.rest().find(|x: char| !x.is_ascii_alphanumeric()).unwrap_or(self.rest().len());
+            if !self.rest()[length..].starts_with(name_character) {
+                let number = parse_number(&self.rest()[..length]).ok_or_else(|| self.error("a hex number, or a decimal one after 0n, that fits in 64 bits"))?;
+                self.position += length;
+                return Ok(AddressExpression::Number(number));
+            }
+        }
+        let name = self.name();
+        if name.is_empty() {
+            return Err(self.error("a number, a name, #<index>, @<tag>, '(' or '['"));
+        }
+        // Hex digits alone are a number, as they always were; a tag spelled that way takes an @
+        Ok(match name.chars().all(|x| x.is_ascii_hexdigit()) {
+            true => AddressExpression::Number(parse_number(&name).ok_or_else(|| ParseValueError(format!("{} is too large for an address in '{}'", name, self.text)))?),
+            false => AddressExpression::Name(name),
+        })
+    }
+
+    // A '-' between name characters joins them, as does a run of '+' as in libstdc++.so.6
+    fn name(&mut self) -> String {
+        let rest = &self.text[self.position..];
+        let mut length = 0;
+        let mut characters = rest.char_indices().peekable();
+        while let Some((at, c)) = characters.next() {
+            let next = characters.peek().map(|x| x.1);
+            let joins = match c {
+                '-' => length > 0 && next.is_some_and(name_character),
+                '+' => length > 0 && (next == Some('+') || rest[..at].ends_with('+')),
+                c => name_character(c),
+            };
+            if !joins {
+                break;
+            }
+            length = at + c.len_utf8();
+        }
+        self.position += length;
+        rest[..length].to_string()
+    }
+}
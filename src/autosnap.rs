@@ -0,0 +1,188 @@
+use std::{collections::VecDeque, path::PathBuf, sync::{Arc, Condvar, Mutex, MutexGuard}, thread::JoinHandle, time::{Duration, Instant, SystemTime}};
+use crate::{platform::Pid, process::ProcessMemory, scan::ScanOptions, snapshot::snapshot_to_file};
+
+// Snapshots taken to files on a timer, so that once something interesting happens there are
+// captures from just before it to diff against. The files form a ring in one directory: past keep
+// of them, or past the budget, the oldest are deleted. A cycle that comes round while the snapshot
+// before it is still being taken is skipped rather than queued
+
+#[derive(Debug, Clone)]
+pub struct AutoSnapshotOptions {
+    pub interval: Duration,
+    pub keep: usize,
+    pub directory: PathBuf,
+    // Most bytes the files may take up together, None for no limit
+    pub budget: Option<u64>,
+    // Stop the target for each snapshot and continue it after, unless it was stopped already.
+    // Consistent captures, but a game stopping every few seconds is noticeable
+    pub stop: bool,
+    // Which regions are captured
+    pub scan: ScanOptions,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutoSnapshot {
+    pub path: PathBuf,
+    pub time: SystemTime,
+    pub took: Duration,
+    pub size: u64,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    // Oldest first
+    snapshots: VecDeque<AutoSnapshot>,
+    taken: usize,
+    skipped: usize,
+    // Why the last snapshot failed, until one succeeds
+    error: Option<String>,
+    shutdown: bool,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+#[derive(Debug)]
+pub struct AutoSnapshotter {
+    options: AutoSnapshotOptions,
+    shared: Arc<Shared>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl AutoSnapshotter {
+    // Takes the first snapshot straight away
+    pub fn start(process: impl ProcessMemory + 'static, mut options: AutoSnapshotOptions) -> Result<AutoSnapshotter, Box<dyn std::error::Error>> {
+        if options.interval.is_zero() || options.keep == 0 {
+            return Err("Autosnapshots need an interval above zero and at least one to keep".into());
+        }
+        if options.stop && !cfg!(any(target_os = "linux", target_os = "macos")) {
+            return Err("Stopping the target for snapshots needs SIGSTOP, which this platform does not have".into());
+        }
+        std::fs::create_dir_all(&options.directory)?;
+        // Nobody is watching a background snapshot's progress
+        options.scan.progress = None;
+        let shared = Arc::new(Shared::default());
+        let (threads_shared, threads_options) = (shared.clone(), options.clone());
+        let handle = std::thread::spawn(move || service(process, &threads_options, &threads_shared));
+        Ok(AutoSnapshotter { options, shared, handle: Some(handle) })
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.shared.state.lock().unwrap()
+    }
+
+    pub fn options(&self) -> &AutoSnapshotOptions {
+        &self.options
+    }
+
+    // Oldest first
+    pub fn snapshots(&self) -> Vec<AutoSnapshot> {
+        self.state().snapshots.iter().cloned().collect()
+    }
+
+    pub fn taken(&self) -> usize {
+        self.state().taken
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.state().skipped
+    }
+
+    pub fn error(&self) -> Option<String> {
+        self.state().error.clone()
+    }
+
+    pub fn running(&self) -> bool {
+        self.handle.is_some()
+    }
+
+    // Ends the timer, cancelling a snapshot in progress. The snapshots taken stay on disk and listed
+    pub fn stop(&mut self) {
+        self.state().shutdown = true;
+        self.shared.changed.notify_all();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for AutoSnapshotter {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// Whether this stopped the target, to be continued once the snapshot is taken
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn stop_target(pid: Pid) -> bool {
+    !crate::control::is_stopped(pid) && crate::control::stop_process(pid).is_ok()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn stop_target(_: Pid) -> bool {
+    false
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn continue_target(pid: Pid) {
+    let _ = crate::control::continue_process(pid);
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn continue_target(_: Pid) {}
+
+// Deletes the oldest snapshots until at most keep are left and, with room for extra more bytes,
+// they fit the budget
+fn trim(state: &mut State, options: &AutoSnapshotOptions, extra: u64) {
+    let over_budget = |state: &State| options.budget.is_some_and(|x| state.snapshots.iter().map(|x| x.size).sum::<u64>() + extra > x);
+    while state.snapshots.len() > options.keep || (!state.snapshots.is_empty() && over_budget(state)) {
+        let oldest = state.snapshots.pop_front().unwrap();
+        let _ = std::fs::remove_file(oldest.path);
+    }
+}
+
+fn service(process: impl ProcessMemory, options: &AutoSnapshotOptions, shared: &Shared) {
+    let mut due = Instant::now();
+    loop {
+        let mut state = shared.state.lock().unwrap();
+        while !state.shutdown && Instant::now() < due {
+            state = shared.changed.wait_timeout(state, due.saturating_duration_since(Instant::now())).unwrap().0;
+        }
+        if state.shutdown {
+            return;
+        }
+        // Room is made for one more the size of the last, so the ring never goes over the budget
+        let expected = state.snapshots.back().map(|x| x.size).unwrap_or(0);
+        trim(&mut state, options, expected);
+        let path = options.directory.join(format!("autosnap-{}.cap", state.taken));
+        drop(state);
+        let (time, started) = (SystemTime::now(), Instant::now());
+        let stopped = options.stop && stop_target(process.pid());
+        let written = snapshot_to_file(&process, &path, &options.scan, || shared.state.lock().unwrap().shutdown);
+        if stopped {
+            continue_target(process.pid());
+        }
+        let mut state = shared.state.lock().unwrap();
+        match written.and_then(|_| Ok(std::fs::metadata(&path)?.len())) {
+            Ok(size) if options.budget.is_some_and(|x| size > x) => {
+                let _ = std::fs::remove_file(&path);
+                state.error = Some(format!("A snapshot takes {} bytes, more than the whole budget", size));
+            }
+            Ok(size) => {
+                state.snapshots.push_back(AutoSnapshot { path, time, took: started.elapsed(), size });
+                state.taken += 1;
+                state.error = None;
+                trim(&mut state, options, 0);
+            }
+            Err(e) => state.error = Some(e.to_string()),
+        }
+        due += options.interval;
+        while due <= Instant::now() {
+            due += options.interval;
+            state.skipped += 1;
+        }
+    }
+}
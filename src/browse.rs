@@ -0,0 +1,255 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+use crate::{process::{ProcessMemory, read_many_bytes}, value::{DisplayFormat, Endianness, ValueType}};
+
+// How often browse reads the window again unless told otherwise
+pub const DEFAULT_BROWSE_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+pub const DEFAULT_BROWSE_ROWS: usize = 16;
+pub const DEFAULT_BROWSE_LAYOUT: &str = "offset 4xi32 ascii";
+
+// One column of a browse row. Values take the row's bytes one after another, so "4xi32 2xf32" is a
+// 24-byte row of four i32s followed by two f32s, while offset and ascii take none
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BrowseColumn {
+    // How far the row starts from the address browsed
+    Offset,
+    Values { value_type: ValueType, count: usize },
+    // The whole row's bytes as text, with dots for those that are not printable
+    Ascii,
+}
+
+// The columns of each row, written as e.g. "offset 4xi32 2xf32 ascii", spaces or commas between
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrowseLayout {
+    pub columns: Vec<BrowseColumn>,
+}
+
+impl BrowseLayout {
+    // The bytes each row covers
+    pub fn stride(&self) -> usize {
+        self.cells().iter().map(|x| x.1.size().unwrap()).sum()
+    }
+
+    // Where each value starts in the row, and its type, left to right
+    pub fn cells(&self) -> Vec<(usize, ValueType)> {
+        let mut cells = Vec::new();
+        let mut offset = 0;
+        for column in &self.columns {
+            if let BrowseColumn::Values { value_type, count } = column {
+                for _ in 0..*count {
+                    cells.push((offset, *value_type));
+                    offset += value_type.size().unwrap();
+                }
+            }
+        }
+        cells
+    }
+}
+
+impl FromStr for BrowseLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut columns = Vec::new();
+        for word in s.split([' ', ',']).filter(|x| !x.is_empty()) {
+            columns.push(match word {
+                "offset" => BrowseColumn::Offset,
+                "ascii" => BrowseColumn::Ascii,
+                word => {
+                    let (count, value_type) = match word.split_once(['x', '*']) {
+                        Some((count, value_type)) if count.parse::<usize>().is_ok() => (count.parse::<usize>().unwrap(), value_type),
+                        _ => (1, word),
+                    };
+                    let value_type = value_type.parse::<ValueType>().map_err(|_| format!("Unknown column '{}', expected offset, ascii or a count and numeric type like 4xi32", word))?;
+                    if !value_type.is_numeric() || count == 0 {
+                        return Err(format!("A value column is a count of at least 1 and a numeric type like 4xi32, not '{}'", word));
+                    }
+                    BrowseColumn::Values { value_type, count }
+                }
+            });
+        }
+        let layout = BrowseLayout { columns };
+        if layout.stride() == 0 {
+            return Err(format!("'{}' shows no values; a layout needs a column like 4xi32", s));
+        }
+        Ok(layout)
+    }
+}
+
+impl fmt::Display for BrowseLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let words = self.columns.iter().map(|x| match x {
+            BrowseColumn::Offset => "offset".to_string(),
+            BrowseColumn::Ascii => "ascii".to_string(),
+            BrowseColumn::Values { value_type, count } => format!("{}x{}", count, value_type),
+        }).collect::<Vec<String>>();
+        write!(f, "{}", words.join(" "))
+    }
+}
+
+// A value as the browser last read it
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrowseCell {
+    pub address: usize,
+    pub value_type: ValueType,
+    // None where the row could not be read
+    pub text: Option<String>,
+    // Its bytes differ from the read before, which saw the same address
+    pub changed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrowseRow {
+    pub address: usize,
+    // Bytes from the address browsed, negative above it
+    pub offset: i64,
+    pub cells: Vec<BrowseCell>,
+    // The row's bytes, or None if it could not be read
+    pub bytes: Option<Vec<u8>>,
+}
+
+// A moving window of rows around an address and a cursor on one of its values. It keeps what it
+// read last, so a refresh can tell which values changed since
+#[derive(Debug, Clone)]
+pub struct Browser {
+    pub anchor: usize,
+    pub layout: BrowseLayout,
+    pub rows: usize,
+    // The first row shown, in rows from the anchor's, which starts a quarter of the way down
+    pub top: i64,
+    // The row in the window, and the value in the row
+    pub cursor: (usize, usize),
+    previous: BTreeMap<usize, Vec<u8>>,
+}
+
+// A move of the cursor, with the window scrolling to keep it in view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrowseMove {
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+}
+
+impl Browser {
+    pub fn new(anchor: usize, layout: BrowseLayout, rows: usize) -> Browser {
+        let rows = rows.max(1);
+        let top = -(rows as i64 / 4).min(anchor as i64 / layout.stride() as i64);
+        Browser { anchor, layout, rows, top, cursor: ((-top) as usize, 0), previous: BTreeMap::new() }
+    }
+
+    // The address the row of the window starts at, if it is one
+    pub fn row_address(&self, row: usize) -> Option<usize> {
+        self.address_with_top(self.top, row)
+    }
+
+    fn address_with_top(&self, top: i64, row: usize) -> Option<usize> {
+        let offset = top.checked_add(row as i64)?.checked_mul(self.layout.stride() as i64)?;
+        self.anchor.checked_add_signed(offset.try_into().ok()?)
+    }
+
+    // The address and type of the value under the cursor
+    pub fn cursor_cell(&self) -> Option<(usize, ValueType)> {
+        let (offset, value_type) = self.layout.cells()[self.cursor.1];
+        Some((self.row_address(self.cursor.0)?.checked_add(offset)?, value_type))
+    }
+
+    pub fn move_cursor(&mut self, movement: BrowseMove) {
+        let (row, cell) = self.cursor;
+        let last_cell = self.layout.cells().len() - 1;
+        match movement {
+            BrowseMove::Left if cell > 0 => self.cursor.1 -= 1,
+            BrowseMove::Right if cell < last_cell => self.cursor.1 += 1,
+            BrowseMove::Left | BrowseMove::Right => {}
+            BrowseMove::Up if row > 0 => self.cursor.0 -= 1,
+            BrowseMove::Down if row + 1 < self.rows => self.cursor.0 += 1,
+            BrowseMove::Up => self.scroll(-1),
+            BrowseMove::Down => self.scroll(1),
+            BrowseMove::PageUp => self.scroll(-(self.rows as i64)),
+            BrowseMove::PageDown => self.scroll(self.rows as i64),
+        }
+    }
+
+    // Going past either end of the address space stops there
+    fn scroll(&mut self, rows: i64) {
+        let top = self.top.saturating_add(rows);
+        if self.address_with_top(top, 0).is_some() && self.address_with_top(top, self.rows - 1).is_some() {
+            self.top = top;
+        }
+    }
+
+    // Reads the window again, one read per row so that an unmapped row leaves the others, and marks
+    // the values that changed since they were last read
+    pub fn refresh(&mut self, process: impl ProcessMemory, endianness: Endianness, format: DisplayFormat) -> Vec<BrowseRow> {
+        let stride = self.layout.stride();
+        let addresses = (0..self.rows).filter_map(|x| self.row_address(x)).collect::<Vec<usize>>();
+        let requests = addresses.iter().map(|x| (*x, stride)).collect::<Vec<(usize, usize)>>();
+        let mut previous = BTreeMap::new();
+        let mut rows = Vec::with_capacity(addresses.len());
+        for (address, bytes) in addresses.into_iter().zip(read_many_bytes(&process, &requests)) {
+            let bytes = bytes.ok().filter(|x| x.len() == stride);
+            let before = self.previous.get(&address);
+            let cells = self.layout.cells().into_iter().map(|(offset, value_type)| {
+                let size = value_type.size().unwrap();
+                let value = bytes.as_ref().map(|x| &x[offset..offset + size]);
+                BrowseCell {
+                    address: address + offset,
+                    value_type,
+                    text: value.and_then(|x| value_type.value_from_bytes_in(x, endianness).ok()).map(|x| x.format_as(format)),
+                    changed: matches!((value, before), (Some(value), Some(before)) if *value != before[offset..offset + size]),
+                }
+            }).collect();
+            if let Some(bytes) = &bytes {
+                previous.insert(address, bytes.clone());
+            }
+            rows.push(BrowseRow { address, offset: address as i64 - self.anchor as i64, cells, bytes });
+        }
+        self.previous = previous;
+        rows
+    }
+}
+
+// A keystroke read from a terminal out of canonical mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Move(BrowseMove),
+    Enter,
+    Backspace,
+    Escape,
+    Char(char),
+}
+
+// Splits what one read gave into keys, decoding the escape sequences terminals send for the arrow
+// and page keys. Sequences it does not know are dropped whole
+pub fn decode_keys(bytes: &[u8]) -> Vec<Key> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut keys = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        keys.push(match c {
+            '\x1b' if chars.peek() == Some(&'[') => {
+                chars.next();
+                let mut sequence = String::new();
+                while let Some(c) = chars.next_if(|x| !x.is_ascii_alphabetic() && *x != '~') {
+                    sequence.push(c);
+                }
+                let movement = match (sequence.as_str(), chars.next()) {
+                    ("", Some('A')) => BrowseMove::Up,
+                    ("", Some('B')) => BrowseMove::Down,
+                    ("", Some('C')) => BrowseMove::Right,
+                    ("", Some('D')) => BrowseMove::Left,
+                    ("5", Some('~')) => BrowseMove::PageUp,
+                    ("6", Some('~')) => BrowseMove::PageDown,
+                    _ => continue,
+                };
+                Key::Move(movement)
+            }
+            '\x1b' => Key::Escape,
+            '\r' | '\n' => Key::Enter,
+            '\x7f' | '\x08' => Key::Backspace,
+            c => Key::Char(c),
+        });
+    }
+    keys
+}
@@ -0,0 +1,107 @@
+use std::{fs::File, path::PathBuf, sync::{Arc, Mutex, atomic::{AtomicU64, AtomicUsize, Ordering}}};
+use crate::platform::FileExt;
+
+// A scan with a memory budget keeps the memory it allocates under it by changing how it works as
+// it goes, rather than growing until the system runs out. The accounting is rough: it covers what
+// grows with the target, i.e. the workers' chunk buffers, the results and a snapshot's chunks, and
+// not the fixed costs of the session around it. A quarter of the budget is kept for the buffers,
+// which shrink to fit it, and the rest for the results or snapshot. Scans stop collecting results
+// once they would go over, and snapshots keep the chunks that would in a file on disk instead
+
+// The smallest chunks a budget shrinks scan buffers to, below which reads cost more than they save
+pub const MIN_BUDGET_CHUNK: usize = 64 << 10;
+
+// What a scan, snapshot or narrow did to keep within its memory budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetFallback {
+    // Read in chunks of this many bytes rather than the usual, so every worker's buffer fit
+    SmallerChunks(usize),
+    // Stopped collecting results at this many, leaving the scan partial
+    TruncatedResults(usize),
+    // Kept this many bytes of the snapshot in a file on disk rather than in memory
+    Spilled(usize),
+}
+
+// The memory the workers' buffers take, and the largest chunk size up to `chunk_size` that keeps
+// them within a quarter of the budget, along with the fallback when that is smaller
+pub(crate) fn budget_chunk_size(budget: Option<usize>, chunk_size: usize, slack: usize) -> (usize, usize, Option<BudgetFallback>) {
+    let threads = rayon::current_num_threads().max(1);
+    let Some(budget) = budget else {
+        return (chunk_size, threads * (chunk_size + slack), None);
+    };
+    let fitted = (budget / 4 / threads).saturating_sub(slack) / 4096 * 4096;
+    if fitted >= chunk_size {
+        return (chunk_size, threads * (chunk_size + slack), None);
+    }
+    let fitted = fitted.max(MIN_BUDGET_CHUNK);
+    (fitted, threads * (fitted + slack), Some(BudgetFallback::SmallerChunks(fitted)))
+}
+
+// A file in the temporary directory holding spilled chunks, removed once nothing refers to it
+#[derive(Debug)]
+pub struct SpillFile {
+    file: File,
+    path: PathBuf,
+}
+
+impl SpillFile {
+    pub fn read(&self, offset: u64, len: usize) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut bytes = vec![0u8; len];
+        self.file.read_exact_at(&mut bytes, offset)?;
+        Ok(bytes)
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+// Hands out memory from a budget to the chunks of a snapshot, and a place in a spill file to those
+// it cannot, the file being made for the first of them
+#[derive(Debug)]
+pub(crate) struct Spiller {
+    // Bytes chunks may still take in memory, None without a budget
+    room: Option<AtomicUsize>,
+    file: Mutex<Option<Arc<SpillFile>>>,
+    end: AtomicU64,
+}
+
+impl Spiller {
+    pub(crate) fn new(budget: Option<usize>, buffers: usize) -> Spiller {
+        Spiller { room: budget.map(|x| AtomicUsize::new(x.saturating_sub(buffers))), file: Mutex::new(None), end: AtomicU64::new(0) }
+    }
+
+    // Takes len bytes from the budget if they fit
+    pub(crate) fn fits(&self, len: usize) -> bool {
+        let Some(room) = &self.room else { return true };
+        room.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |x| x.checked_sub(len)).is_ok()
+    }
+
+    // Writes the bytes to the spill file, returning it and where they went
+    pub(crate) fn spill(&self, bytes: &[u8]) -> Result<(Arc<SpillFile>, u64), Box<dyn std::error::Error>> {
+        let file = {
+            let mut file = self.file.lock().unwrap();
+            match &*file {
+                Some(file) => file.clone(),
+                None => file.insert(Arc::new(create_spill_file()?)).clone(),
+            }
+        };
+        let offset = self.end.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+        file.file.write_all_at(bytes, offset)?;
+        Ok((file, offset))
+    }
+
+    // The fallback taken, if anything was spilled
+    pub(crate) fn fallback(&self) -> Option<BudgetFallback> {
+        Some(self.end.load(Ordering::Relaxed) as usize).filter(|x| *x > 0).map(BudgetFallback::Spilled)
+    }
+}
+
+fn create_spill_file() -> Result<SpillFile, Box<dyn std::error::Error>> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!("rmh-spill-{}-{}", std::process::id(), COUNT.fetch_add(1, Ordering::Relaxed)));
+    let file = File::options().read(true).write(true).create_new(true).open(&path).map_err(|e| format!("Could not create a spill file at {}: {}", path.display(), e))?;
+    Ok(SpillFile { file, path })
+}
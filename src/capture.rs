@@ -0,0 +1,437 @@
+use std::{fs::File, io::{BufWriter, Seek, SeekFrom, Write}, path::Path, sync::{Arc, Mutex}, time::{SystemTime, UNIX_EPOCH}};
+use crate::{elf::Arch, maps::MemoryRegion, platform::FileExt, process::ProcessMemory, snapshot::SNAPSHOT_CHUNK_SIZE};
+
+// The file dump all writes, snapshots are saved as and offline mode reads. Integers are
+// little-endian and addresses are u64 whatever the target's pointer width. The file starts with
+//     magic          8 bytes, "RMHCAPT\0"
+//     version        u16, only raised for changes an older reader could not skip over
+//     table offset   u64, where the region table starts
+//     header length  u32, then that many bytes of header fields, each a u16 tag, a u32 length and
+//                    the value. Readers skip tags they do not know
+//                        1  pid, i32
+//                        2  when it was captured, u64 seconds since the Unix epoch
+//                        3  the target's pointer width, u8 bytes
+//                        4  the executable's path, UTF-8
+//                        5  a thread id (i32) and an address in its stack (u64), once per thread
+//                        6  a mapping that was not captured, as a region table entry with no
+//                           payload, once per mapping
+//                        7  the target's architecture, its executable's ELF e_machine as a u16.
+//                           Captures without it are taken to be of this machine's architecture,
+//                           or its 32-bit or 64-bit counterpart for their pointer width
+// followed by each region's payload: its bytes in blocks of CAPTURE_BLOCK_SIZE, the last one
+// shorter, each block written as a u32 length and the stored bytes. The region table at the end
+// is a u32 count and then per region a u32 length followed by
+//     start u64, end u64, permissions u8 (1 read, 2 write, 4 execute, 8 shared), file offset u64,
+//     inode u64, deleted u8, compression u8 (0 none, 1 lz4 per block), payload offset u64, payload
+//     length u64, device and pathname (each a u16 length and UTF-8), hole count u32 and the holes
+//     (start u64, end u64)
+// Readers skip whatever follows the fields they know in an entry, so fields can be appended. Holes
+// are ranges that could not be read, stored as zeroes
+
+pub const CAPTURE_MAGIC: [u8; 8] = *b"RMHCAPT\0";
+pub const CAPTURE_VERSION: u16 = 1;
+// The same as snapshot chunks, so a snapshot's chunks are saved as blocks one for one
+pub const CAPTURE_BLOCK_SIZE: usize = SNAPSHOT_CHUNK_SIZE;
+
+// Decompressed blocks kept around, enough for every scan thread reading a block of its own
+const CACHED_BLOCKS: usize = 32;
+
+// A decompressed block with its region and block numbers
+type CachedBlock = (usize, usize, Arc<Vec<u8>>);
+
+const TAG_PID: u16 = 1;
+const TAG_TIME: u16 = 2;
+const TAG_POINTER_WIDTH: u16 = 3;
+const TAG_EXECUTABLE: u16 = 4;
+const TAG_STACK: u16 = 5;
+const TAG_UNCAPTURED: u16 = 6;
+const TAG_ARCH: u16 = 7;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    Lz4,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureHeader {
+    // CAPTURE_VERSION when writing, whatever the file says when read
+    pub version: u16,
+    pub pid: i32,
+    pub time: SystemTime,
+    // Stored as its pointer width and its ELF machine
+    pub arch: Arch,
+    pub executable: Option<String>,
+    // Thread ids with an address inside each thread's stack
+    pub stacks: Vec<(i32, usize)>,
+    // The mappings left out, unreadable or not selected, so the maps and the modules made of them
+    // are whole when read back
+    pub uncaptured: Vec<MemoryRegion>,
+}
+
+impl CaptureHeader {
+    // Describes the process as it is now
+    pub fn of(process: impl ProcessMemory) -> Result<CaptureHeader, Box<dyn std::error::Error>> {
+        Ok(CaptureHeader {
+            version: CAPTURE_VERSION,
+            pid: process.pid().as_raw(),
+            time: SystemTime::now(),
+            arch: process.arch(),
+            executable: process.executable_path(),
+            stacks: process.thread_stacks().into_iter().map(|x| (x.0.as_raw(), x.1)).collect(),
+            uncaptured: Vec::new(),
+        })
+    }
+}
+
+// The mappings that do not overlap any of the captured regions
+pub(crate) fn uncaptured(mapped: &[MemoryRegion], captured: &[MemoryRegion]) -> Vec<MemoryRegion> {
+    mapped.iter().filter(|x| !captured.iter().any(|y| y.start < x.end && y.end > x.start)).cloned().collect()
+}
+
+// One entry of the region table
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedRegion {
+    pub region: MemoryRegion,
+    pub holes: Vec<(usize, usize)>,
+    pub compression: Compression,
+    pub payload_offset: u64,
+    // Stored bytes, block lengths included
+    pub payload_len: u64,
+}
+
+#[derive(Debug)]
+pub struct Capture {
+    pub header: CaptureHeader,
+    pub regions: Vec<CapturedRegion>,
+    file: File,
+    // Per region, where each block's stored bytes start and how many there are
+    blocks: Vec<Vec<(u64, u32)>>,
+    // Recently decompressed blocks, the newest last
+    cache: Mutex<Vec<CachedBlock>>,
+}
+
+impl Capture {
+    // Writes the regions in order, calling fill with each block's address and a buffer to fill
+    // with its bytes. fill returns the ranges it could not read, which it leaves as zeroes
+    pub fn write(path: &Path, header: &CaptureHeader, compression: Compression, regions: Vec<MemoryRegion>, mut fill: impl FnMut(usize, &mut [u8]) -> Result<Vec<(usize, usize)>, Box<dyn std::error::Error>>) -> Result<Vec<CapturedRegion>, Box<dyn std::error::Error>> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let fields = header_fields(header);
+        file.write_all(&CAPTURE_MAGIC)?;
+        file.write_all(&CAPTURE_VERSION.to_le_bytes())?;
+        file.write_all(&0u64.to_le_bytes())?;
+        file.write_all(&(fields.len() as u32).to_le_bytes())?;
+        file.write_all(&fields)?;
+        let mut position = (CAPTURE_MAGIC.len() + 2 + 8 + 4 + fields.len()) as u64;
+        let mut buffer = vec![0u8; CAPTURE_BLOCK_SIZE];
+        let mut captured = Vec::with_capacity(regions.len());
+        for region in regions {
+            let payload_offset = position;
+            let mut holes = Vec::new();
+            for address in (region.start..region.end).step_by(CAPTURE_BLOCK_SIZE) {
+                let block = &mut buffer[..CAPTURE_BLOCK_SIZE.min(region.end - address)];
+                block.fill(0);
+                holes.extend(fill(address, block)?);
+                let compressed;
+                let stored = match compression {
+                    Compression::None => &*block,
+                    Compression::Lz4 => {
+                        compressed = lz4_flex::compress(block);
+                        &compressed
+                    }
+                };
+                file.write_all(&(stored.len() as u32).to_le_bytes())?;
+                file.write_all(stored)?;
+                position += 4 + stored.len() as u64;
+            }
+            captured.push(CapturedRegion { region, holes: merge_holes(holes), compression, payload_offset, payload_len: position - payload_offset });
+        }
+        let mut table = (captured.len() as u32).to_le_bytes().to_vec();
+        for entry in &captured {
+            let entry = table_entry(entry);
+            table.extend((entry.len() as u32).to_le_bytes());
+            table.extend(entry);
+        }
+        file.write_all(&table)?;
+        file.seek(SeekFrom::Start(CAPTURE_MAGIC.len() as u64 + 2))?;
+        file.write_all(&position.to_le_bytes())?;
+        file.flush()?;
+        Ok(captured)
+    }
+
+    pub fn open(path: &Path) -> Result<Capture, Box<dyn std::error::Error>> {
+        let file = File::open(path).map_err(|e| format!("Could not open {}: {}", path.display(), e))?;
+        let len = file.metadata()?.len();
+        let mut start = [0u8; 22];
+        file.read_exact_at(&mut start, 0).map_err(|_| format!("{} is not a capture", path.display()))?;
+        if start[..8] != CAPTURE_MAGIC {
+            return Err(format!("{} is not a capture", path.display()).into());
+        }
+        let mut fixed = Fields(&start[8..]);
+        let version = fixed.u16()?;
+        if version > CAPTURE_VERSION {
+            return Err(format!("{} is a version {} capture, but only versions up to {} can be read", path.display(), version, CAPTURE_VERSION).into());
+        }
+        let table_offset = fixed.u64()?;
+        let header_len = fixed.u32()? as u64;
+        if 22 + header_len > table_offset || table_offset > len {
+            return Err(format!("{} is truncated or corrupt", path.display()).into());
+        }
+        let mut bytes = vec![0u8; header_len as usize];
+        file.read_exact_at(&mut bytes, 22)?;
+        let header = read_header_fields(version, &bytes)?;
+        let mut bytes = vec![0u8; (len - table_offset) as usize];
+        file.read_exact_at(&mut bytes, table_offset)?;
+        let mut table = Fields(&bytes);
+        let mut capture = Capture { header, regions: Vec::new(), file, blocks: Vec::new(), cache: Mutex::new(Vec::new()) };
+        for _ in 0..table.u32()? {
+            let len = table.u32()? as usize;
+            let entry = read_table_entry(&mut Fields(table.take(len)?))?;
+            if entry.payload_offset.checked_add(entry.payload_len).is_none_or(|x| x > table_offset) {
+                return Err(format!("{} is truncated or corrupt", path.display()).into());
+            }
+            capture.blocks.push(capture.block_index(&entry)?);
+            capture.regions.push(entry);
+        }
+        Ok(capture)
+    }
+
+    // Walks the region's block lengths, making sure they add up to its payload
+    fn block_index(&self, entry: &CapturedRegion) -> Result<Vec<(u64, u32)>, Box<dyn std::error::Error>> {
+        let mut blocks = Vec::new();
+        let mut position = entry.payload_offset;
+        let mut len = [0u8; 4];
+        for address in (entry.region.start..entry.region.end).step_by(CAPTURE_BLOCK_SIZE) {
+            self.file.read_exact_at(&mut len, position)?;
+            let stored = u32::from_le_bytes(len);
+            let expected = CAPTURE_BLOCK_SIZE.min(entry.region.end - address);
+            if entry.compression == Compression::None && stored as usize != expected {
+                return Err(format!("The block at 0x{:x} holds {} bytes instead of {}", address, stored, expected).into());
+            }
+            blocks.push((position + 4, stored));
+            position += 4 + stored as u64;
+        }
+        if position != entry.payload_offset + entry.payload_len {
+            return Err(format!("The blocks of 0x{:x}-0x{:x} do not add up to its payload", entry.region.start, entry.region.end).into());
+        }
+        Ok(blocks)
+    }
+
+    // Fills the buffer from the index'th region, offset bytes in. Holes read as zeroes
+    pub fn read_region(&self, index: usize, offset: usize, buffer: &mut [u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let entry = &self.regions[index];
+        if offset + buffer.len() > entry.region.len() {
+            return Err(format!("Read past the end of 0x{:x}-0x{:x}", entry.region.start, entry.region.end).into());
+        }
+        let mut done = 0;
+        while done < buffer.len() {
+            let (block, within) = ((offset + done) / CAPTURE_BLOCK_SIZE, (offset + done) % CAPTURE_BLOCK_SIZE);
+            let len = (CAPTURE_BLOCK_SIZE - within).min(buffer.len() - done);
+            match entry.compression {
+                Compression::None => self.file.read_exact_at(&mut buffer[done..done + len], self.blocks[index][block].0 + within as u64)?,
+                Compression::Lz4 => buffer[done..done + len].copy_from_slice(&self.block(index, block)?[within..within + len]),
+            }
+            done += len;
+        }
+        Ok(())
+    }
+
+    fn block(&self, index: usize, block: usize) -> Result<Arc<Vec<u8>>, Box<dyn std::error::Error>> {
+        if let Some(cached) = self.cache.lock().unwrap().iter().find(|x| x.0 == index && x.1 == block) {
+            return Ok(cached.2.clone());
+        }
+        let (position, stored) = self.blocks[index][block];
+        let mut compressed = vec![0u8; stored as usize];
+        self.file.read_exact_at(&mut compressed, position)?;
+        let region = &self.regions[index].region;
+        let expected = CAPTURE_BLOCK_SIZE.min(region.len() - block * CAPTURE_BLOCK_SIZE);
+        let data = Arc::new(lz4_flex::decompress(&compressed, expected)?);
+        if data.len() != expected {
+            return Err(format!("The block at 0x{:x} holds {} bytes instead of {}", region.start + block * CAPTURE_BLOCK_SIZE, data.len(), expected).into());
+        }
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() == CACHED_BLOCKS {
+            cache.remove(0);
+        }
+        cache.push((index, block, data.clone()));
+        Ok(data)
+    }
+
+    // The bytes of the whole capture, and what they take up in the file
+    pub fn len(&self) -> usize {
+        self.regions.iter().map(|x| x.region.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    pub fn stored_len(&self) -> u64 {
+        self.regions.iter().map(|x| x.payload_len).sum()
+    }
+}
+
+// The header's tagged fields, as a capture stores them and a pointer map does too
+pub(crate) fn header_fields(header: &CaptureHeader) -> Vec<u8> {
+    let mut fields = Vec::new();
+    field(&mut fields, TAG_PID, &header.pid.to_le_bytes());
+    field(&mut fields, TAG_TIME, &header.time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs().to_le_bytes());
+    field(&mut fields, TAG_POINTER_WIDTH, &[header.arch.pointer_width() as u8]);
+    field(&mut fields, TAG_ARCH, &header.arch.machine().to_le_bytes());
+    if let Some(executable) = &header.executable {
+        field(&mut fields, TAG_EXECUTABLE, executable.as_bytes());
+    }
+    for (tid, address) in &header.stacks {
+        field(&mut fields, TAG_STACK, &[tid.to_le_bytes().as_slice(), &(*address as u64).to_le_bytes()].concat());
+    }
+    for region in &header.uncaptured {
+        field(&mut fields, TAG_UNCAPTURED, &table_entry(&CapturedRegion { region: region.clone(), holes: Vec::new(), compression: Compression::None, payload_offset: 0, payload_len: 0 }));
+    }
+    fields
+}
+
+pub(crate) fn read_header_fields(version: u16, bytes: &[u8]) -> Result<CaptureHeader, Box<dyn std::error::Error>> {
+    let mut header = CaptureHeader { version, pid: 0, time: UNIX_EPOCH, arch: Arch::native(), executable: None, stacks: Vec::new(), uncaptured: Vec::new() };
+    let (mut pointer_width, mut machine) = (header.arch.pointer_width() as u8, None);
+    let mut fields = Fields(bytes);
+    while !fields.0.is_empty() {
+        let tag = fields.u16()?;
+        let len = fields.u32()? as usize;
+        let mut value = Fields(fields.take(len)?);
+        match tag {
+            TAG_PID => header.pid = value.u32()? as i32,
+            TAG_TIME => header.time = UNIX_EPOCH.checked_add(std::time::Duration::from_secs(value.u64()?)).ok_or("The capture's time is out of range")?,
+            TAG_POINTER_WIDTH => pointer_width = value.u8()?,
+            TAG_ARCH => machine = Some(value.u16()?),
+            TAG_EXECUTABLE => header.executable = Some(String::from_utf8(value.0.to_vec())?),
+            TAG_STACK => header.stacks.push((value.u32()? as i32, value.u64()? as usize)),
+            TAG_UNCAPTURED => header.uncaptured.push(read_table_entry(&mut value)?.region),
+            _ => {}
+        }
+    }
+    header.arch = match machine {
+        Some(machine) => Arch::from_machine(machine, pointer_width),
+        None => header.arch.with_pointer_width(pointer_width),
+    };
+    Ok(header)
+}
+
+fn field(fields: &mut Vec<u8>, tag: u16, value: &[u8]) {
+    fields.extend(tag.to_le_bytes());
+    fields.extend((value.len() as u32).to_le_bytes());
+    fields.extend(value);
+}
+
+fn table_entry(entry: &CapturedRegion) -> Vec<u8> {
+    let region = &entry.region;
+    let permissions = region.readable as u8 | (region.writable as u8) << 1 | (region.executable as u8) << 2 | (region.shared as u8) << 3;
+    let mut bytes = Vec::new();
+    bytes.extend((region.start as u64).to_le_bytes());
+    bytes.extend((region.end as u64).to_le_bytes());
+    bytes.push(permissions);
+    bytes.extend((region.offset as u64).to_le_bytes());
+    bytes.extend(region.inode.to_le_bytes());
+    bytes.push(region.deleted as u8);
+    bytes.push(match entry.compression {
+        Compression::None => 0,
+        Compression::Lz4 => 1,
+    });
+    bytes.extend(entry.payload_offset.to_le_bytes());
+    bytes.extend(entry.payload_len.to_le_bytes());
+    for text in [&region.device, &region.pathname] {
+        bytes.extend((text.len() as u16).to_le_bytes());
+        bytes.extend(text.as_bytes());
+    }
+    bytes.extend((entry.holes.len() as u32).to_le_bytes());
+    for (start, end) in &entry.holes {
+        bytes.extend((*start as u64).to_le_bytes());
+        bytes.extend((*end as u64).to_le_bytes());
+    }
+    bytes
+}
+
+fn read_table_entry(fields: &mut Fields) -> Result<CapturedRegion, Box<dyn std::error::Error>> {
+    let start = fields.u64()? as usize;
+    let end = fields.u64()? as usize;
+    let permissions = fields.u8()?;
+    let offset = fields.u64()? as usize;
+    let inode = fields.u64()?;
+    let deleted = fields.u8()? != 0;
+    let compression = match fields.u8()? {
+        0 => Compression::None,
+        1 => Compression::Lz4,
+        x => return Err(format!("Unknown compression {} for 0x{:x}-0x{:x}", x, start, end).into()),
+    };
+    let payload_offset = fields.u64()?;
+    let payload_len = fields.u64()?;
+    let device = fields.string()?;
+    let pathname = fields.string()?;
+    let holes = (0..fields.u32()?).map(|_| Ok((fields.u64()? as usize, fields.u64()? as usize))).collect::<Result<Vec<(usize, usize)>, Box<dyn std::error::Error>>>()?;
+    if end < start {
+        return Err(format!("Region 0x{:x}-0x{:x} ends before it starts", start, end).into());
+    }
+    let region = MemoryRegion {
+        start,
+        end,
+        readable: permissions & 1 != 0,
+        writable: permissions & 2 != 0,
+        executable: permissions & 4 != 0,
+        shared: permissions & 8 != 0,
+        offset,
+        device,
+        inode,
+        pathname,
+        deleted,
+        // Not recorded, as nothing read from a capture can fail a huge page at a time
+        kernel_page_size: None,
+    };
+    Ok(CapturedRegion { region, holes, compression, payload_offset, payload_len })
+}
+
+// Joins holes that touch, as those of neighbouring blocks do
+fn merge_holes(holes: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for hole in holes {
+        match merged.last_mut() {
+            Some(last) if last.1 == hole.0 => last.1 = hole.1,
+            _ => merged.push(hole),
+        }
+    }
+    merged
+}
+
+// Little-endian fields read off the front of a byte slice
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Fields<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Box<dyn std::error::Error>> {
+        if len > self.0.len() {
+            return Err("The capture is truncated or corrupt".into());
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, Box<dyn std::error::Error>> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Box<dyn std::error::Error>> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, Box<dyn std::error::Error>> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, Box<dyn std::error::Error>> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn string(&mut self) -> Result<String, Box<dyn std::error::Error>> {
+        let len = self.u16()? as usize;
+        Ok(String::from_utf8(self.take(len)?.to_vec())?)
+    }
+}
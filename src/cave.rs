@@ -0,0 +1,139 @@
+use nix::unistd::Pid;
+use crate::{inject::{remote_alloc, remote_alloc_near, remote_free}, maps::get_memory_regions, patch::{NOP, Patch, patch_bytes}, process::{MemBackend, Process, read_bytes_from_process, write_bytes_to_process}, x86::decode_instruction};
+
+// Code put into memory allocated in the target, optionally with a hook: a jmp written over
+// whole instructions somewhere else, which runs the cave's code, then the instructions it moved,
+// then jumps back to carry on after them. Both are written through /proc/<pid>/mem, which writes
+// into code mapped without write permission. Everything is undone by remove, the hook first.
+// Nothing stops the target while the jmp is written, so a thread running the hooked instructions
+// at that moment can see half of it; code run now and then is safer to hook than a hot loop
+
+// What the hook writes over the start of the hooked code, and what jumps back from the cave
+const JMP_LEN: usize = 5;
+
+#[derive(Debug)]
+pub struct Injection {
+    pub pid: Pid,
+    pub cave: usize,
+    // The code given, then with a hook the moved instructions and the jmp back
+    pub cave_code: Vec<u8>,
+    pub hook: Option<Patch<Process>>,
+    pub remove_on_drop: bool,
+    removed: bool,
+}
+
+impl Injection {
+    pub fn is_removed(&self) -> bool {
+        self.removed
+    }
+
+    // The hooked code is put back before the cave goes, so nothing new jumps into it. A thread that
+    // is inside the cave at that moment still crashes, which there is no telling from here
+    pub fn remove(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.removed {
+            return Ok(());
+        }
+        if let Some(hook) = &mut self.hook {
+            hook.restore()?;
+        }
+        remote_free(self.pid, self.cave, self.cave_code.len())?;
+        self.removed = true;
+        Ok(())
+    }
+}
+
+impl Drop for Injection {
+    fn drop(&mut self) {
+        if self.remove_on_drop {
+            let _ = self.remove();
+        }
+    }
+}
+
+// The displacement of a rel32 or [rip + disp32] at from_end pointing to to, if it reaches
+fn rel32(from_end: usize, to: usize) -> Option<i32> {
+    i32::try_from(to as i64 - from_end as i64).ok()
+}
+
+fn jmp(from: usize, to: usize) -> Result<[u8; JMP_LEN], Box<dyn std::error::Error>> {
+    let offset = rel32(from + JMP_LEN, to).ok_or(format!("0x{:x} is too far from 0x{:x} for a jmp rel32", to, from))?;
+    let mut jmp = [0xe9, 0, 0, 0, 0];
+    jmp[1..].copy_from_slice(&offset.to_le_bytes());
+    Ok(jmp)
+}
+
+// Writes code into a new read-and-execute mapping in the target. With hook_at, enough whole
+// instructions there to fit a jmp rel32 are moved to after the code, followed by a jmp back, and
+// the jmp to the cave goes over them, padded out with NOPs. Instructions that cannot run anywhere
+// else, such as relative jumps, are refused, as is anything the decoder does not know, rather
+// than cutting an instruction in two
+pub fn inject_code(pid: Pid, code: &[u8], hook_at: Option<usize>) -> Result<Injection, Box<dyn std::error::Error>> {
+    if code.is_empty() && hook_at.is_none() {
+        return Err("Nothing to inject".into());
+    }
+    let process = Process::with_backend(pid, MemBackend::ProcMem)?;
+    let Some(site) = hook_at else {
+        let cave = remote_alloc(pid, code.len(), libc::PROT_READ | libc::PROT_EXEC)?;
+        return write_cave(pid, &process, cave, code.to_vec(), None);
+    };
+    let region = get_memory_regions(pid)?.into_iter().find(|x| x.contains(site)).ok_or(format!("0x{:x} is not mapped", site))?;
+    if !region.executable {
+        return Err(format!("0x{:x} is not in executable memory", site).into());
+    }
+    let original = read_bytes_from_process(&process, (region.end - site).min(JMP_LEN + 15), site)?;
+    let mut moved = Vec::new();
+    let mut len = 0;
+    while len < JMP_LEN {
+        let instruction = decode_instruction(&original[len..]).map_err(|e| format!("Could not decode the instruction at 0x{:x}: {}", site + len, e))?;
+        if instruction.relative_branch {
+            return Err(format!("The instruction at 0x{:x} jumps relative to where it is, so it would go somewhere else once moved", site + len).into());
+        }
+        if instruction.ends_flow && len + instruction.len < JMP_LEN {
+            return Err(format!("The code at 0x{:x} does not carry on past 0x{:x}, so the jmp would run over whatever is after it", site, site + len).into());
+        }
+        moved.push((len, instruction));
+        len += instruction.len;
+    }
+    let cave = remote_alloc_near(pid, site, code.len() + len + JMP_LEN, libc::PROT_READ | libc::PROT_EXEC)?;
+    let built = (|| {
+        let mut cave_code = code.to_vec();
+        for (offset, instruction) in moved {
+            let mut bytes = original[offset..offset + instruction.len].to_vec();
+            if let Some(at) = instruction.rip_relative {
+                let target = (site + offset + instruction.len).wrapping_add_signed(i32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as isize);
+                let moved_to = cave + cave_code.len() + instruction.len;
+                let displacement = rel32(moved_to, target).ok_or(format!("The instruction at 0x{:x} refers to 0x{:x}, which the cave is too far from", site + offset, target))?;
+                bytes[at..at + 4].copy_from_slice(&displacement.to_le_bytes());
+            }
+            cave_code.extend(bytes);
+        }
+        cave_code.extend(jmp(cave + cave_code.len(), site + len)?);
+        let mut hook = jmp(site, cave)?.to_vec();
+        hook.resize(len, NOP);
+        Ok::<_, Box<dyn std::error::Error>>((cave_code, hook))
+    })();
+    match built {
+        Ok((cave_code, hook)) => write_cave(pid, &process, cave, cave_code, Some((site, hook))),
+        Err(e) => {
+            let _ = remote_free(pid, cave, code.len() + len + JMP_LEN);
+            Err(e)
+        }
+    }
+}
+
+// The cave is written in full before the hook goes in, and freed again if either fails
+fn write_cave(pid: Pid, process: &Process, cave: usize, cave_code: Vec<u8>, hook: Option<(usize, Vec<u8>)>) -> Result<Injection, Box<dyn std::error::Error>> {
+    let written = (|| {
+        if write_bytes_to_process(process, cave, &cave_code)? < cave_code.len() {
+            return Err(format!("Could not write all {} bytes of code at 0x{:x}", cave_code.len(), cave).into());
+        }
+        hook.map(|(site, bytes)| patch_bytes(process.clone(), site, &bytes)).transpose()
+    })();
+    match written {
+        Ok(hook) => Ok(Injection { pid, cave, cave_code, hook, remove_on_drop: false, removed: false }),
+        Err(e) => {
+            let _ = remote_free(pid, cave, cave_code.len());
+            Err(e)
+        }
+    }
+}
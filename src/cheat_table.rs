@@ -0,0 +1,128 @@
+use crate::{session::SavedAddress, value::{Encoding, Endianness, ValueType}};
+
+// A Cheat Engine table (.CT), for handing addresses found here to someone using Cheat Engine.
+// Each group becomes a group header with its entries nested under it
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheatEntry {
+    pub description: String,
+    pub address: SavedAddress,
+    pub value_type: ValueType,
+    // How many bytes a byte buffer or string covers; ignored for the numeric types
+    pub len: usize,
+    // The value the entry is frozen at, formatted like TypedValue::format_value
+    pub frozen: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheatGroup {
+    pub name: String,
+    pub entries: Vec<CheatEntry>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CheatTable {
+    pub groups: Vec<CheatGroup>,
+    // The byte order of the numeric values. Cheat Engine runs on little-endian machines and only
+    // has big-endian types for 2 and 4 byte integers and floats, so other big-endian values are
+    // left as they are, with a comment saying so
+    pub endianness: Endianness,
+}
+
+impl CheatTable {
+    pub fn to_xml(&self) -> String {
+        let mut xml = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<CheatTable CheatEngineTableVersion=\"45\">\n  <CheatEntries>\n".to_string();
+        let mut id = 0;
+        for group in self.groups.iter().filter(|x| !x.entries.is_empty()) {
+            xml += &format!("    <CheatEntry>\n      <ID>{}</ID>\n      <Description>\"{}\"</Description>\n      <GroupHeader>1</GroupHeader>\n      <CheatEntries>\n", id, escape(&group.name));
+            id += 1;
+            for entry in &group.entries {
+                xml += &self.entry_xml(entry, id);
+                id += 1;
+            }
+            xml += "      </CheatEntries>\n    </CheatEntry>\n";
+        }
+        xml += "  </CheatEntries>\n  <UserdefinedSymbols/>\n</CheatTable>\n";
+        xml
+    }
+
+    fn entry_xml(&self, entry: &CheatEntry, id: usize) -> String {
+        let indent = "        ";
+        let mut xml = format!("{}<CheatEntry>\n", indent);
+        let mut line = |x: String| xml += &format!("{}  {}\n", indent, x);
+        line(format!("<ID>{}</ID>", id));
+        line(format!("<Description>\"{}\"</Description>", escape(&entry.description)));
+        if let SavedAddress::Absolute(_) = entry.address {
+            line("<!-- Not inside any module, so this address will not survive a restart of the process -->".to_string());
+        }
+        if let Some(value) = &entry.frozen {
+            line(format!("<LastState Value=\"{}\" Activated=\"1\"/>", escape(value)));
+        }
+        if matches!(entry.value_type, ValueType::I8 | ValueType::I16 | ValueType::I32 | ValueType::I64) {
+            line("<ShowAsSigned>1</ShowAsSigned>".to_string());
+        }
+        match (entry.value_type, self.endianness == Endianness::Big) {
+            (ValueType::Bytes, _) => {
+                line("<VariableType>Array of byte</VariableType>".to_string());
+                line(format!("<ByteLength>{}</ByteLength>", entry.len));
+            }
+            (ValueType::Str { encoding, null_terminate }, _) => {
+                line("<VariableType>String</VariableType>".to_string());
+                match encoding {
+                    Encoding::Utf8 => line(format!("<Length>{}</Length>", entry.len)),
+                    Encoding::Utf16 => {
+                        line(format!("<Length>{}</Length>", entry.len / 2));
+                        line("<Unicode>1</Unicode>".to_string());
+                    }
+                }
+                if null_terminate {
+                    line("<ZeroTerminate>1</ZeroTerminate>".to_string());
+                }
+            }
+            (value_type, true) if let Some(custom) = big_endian_type(value_type) => {
+                line("<VariableType>Custom</VariableType>".to_string());
+                line(format!("<CustomType>{}</CustomType>", custom));
+            }
+            (value_type, big_endian) => {
+                if big_endian && value_type.size() != Some(1) {
+                    line("<!-- Big-endian in the target, but Cheat Engine has no big-endian type of this size -->".to_string());
+                }
+                line(format!("<VariableType>{}</VariableType>", variable_type(value_type)));
+            }
+        }
+        line(format!("<Address>{}</Address>", match &entry.address {
+            SavedAddress::Absolute(address) => format!("{:X}", address),
+            SavedAddress::Module { name, offset } => format!("\"{}\"+{:X}", escape(name), offset),
+        }));
+        xml += &format!("{}</CheatEntry>\n", indent);
+        xml
+    }
+}
+
+// Cheat Engine's name for a numeric type, which does not tell signed from unsigned
+fn variable_type(value_type: ValueType) -> &'static str {
+    match value_type {
+        ValueType::I8 | ValueType::U8 => "Byte",
+        ValueType::I16 | ValueType::U16 => "2 Bytes",
+        ValueType::I32 | ValueType::U32 => "4 Bytes",
+        ValueType::I64 | ValueType::U64 => "8 Bytes",
+        ValueType::F32 => "Float",
+        ValueType::F64 => "Double",
+        ValueType::Bytes => "Array of byte",
+        ValueType::Str { .. } => "String",
+    }
+}
+
+// The custom types Cheat Engine comes with for big-endian values
+fn big_endian_type(value_type: ValueType) -> Option<&'static str> {
+    match value_type {
+        ValueType::I16 | ValueType::U16 => Some("2 Byte Big Endian"),
+        ValueType::I32 | ValueType::U32 => Some("4 Byte Big Endian"),
+        ValueType::F32 => Some("Float Big Endian"),
+        _ => None,
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
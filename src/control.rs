@@ -0,0 +1,49 @@
+use std::time::{Duration, Instant};
+use nix::{sys::signal::{Signal, kill}, unistd::Pid};
+
+// Stopping, continuing and killing the target with the signals a shell's kill would send
+
+// How long stop_process waits to see the target stopped
+const STOP_WAIT: Duration = Duration::from_millis(500);
+
+fn signal(pid: Pid, signal: Signal) -> Result<(), Box<dyn std::error::Error>> {
+    kill(pid, signal).map_err(|e| format!("Could not send {:?} to process {}: {}", signal, pid, e).into())
+}
+
+// SIGSTOP cannot be caught or ignored, but the threads stop as each next runs, so this waits for
+// the kernel to say the target is stopped, for whatever is read next to be from the stopped target.
+// A target being traced shows as in a tracing stop instead, which is not waited for
+pub fn stop_process(pid: Pid) -> Result<(), Box<dyn std::error::Error>> {
+    signal(pid, Signal::SIGSTOP)?;
+    let started = Instant::now();
+    while !is_stopped(pid) && started.elapsed() < STOP_WAIT {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    Ok(())
+}
+
+pub fn continue_process(pid: Pid) -> Result<(), Box<dyn std::error::Error>> {
+    signal(pid, Signal::SIGCONT)
+}
+
+// SIGKILL, which the target gets no say in
+pub fn kill_process(pid: Pid) -> Result<(), Box<dyn std::error::Error>> {
+    signal(pid, Signal::SIGKILL)
+}
+
+// Whether the target is stopped by a signal, from the T state in its status
+#[cfg(target_os = "linux")]
+pub fn is_stopped(pid: Pid) -> bool {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).unwrap_or_default();
+    status.lines().find_map(|x| x.strip_prefix("State:")).is_some_and(|x| x.trim_start().starts_with('T'))
+}
+
+// From the process status proc_pidinfo gives, SSTOP in sys/proc.h
+#[cfg(target_os = "macos")]
+pub fn is_stopped(pid: Pid) -> bool {
+    const SSTOP: u32 = 4;
+    let mut info: libc::proc_bsdinfo = unsafe { std::mem::zeroed() };
+    let size = size_of::<libc::proc_bsdinfo>() as libc::c_int;
+    let got = unsafe { libc::proc_pidinfo(pid.as_raw(), libc::PROC_PIDTBSDINFO, 0, &mut info as *mut libc::proc_bsdinfo as *mut libc::c_void, size) };
+    got == size && info.pbi_status == SSTOP
+}
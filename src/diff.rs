@@ -0,0 +1,291 @@
+use std::{collections::{HashMap, HashSet}, path::Path};
+use crate::{capture::{CAPTURE_BLOCK_SIZE, Capture}, filter::RegionFilter, maps::{MemoryRegion, modules_from_regions}, process::{ProcessMemory, read_bytes_into}, scan::SCAN_CHUNK_SIZE, value::{Endianness, Scalar}};
+
+// Comparing two captures of the same process, e.g. snapshots from before and after something
+// happened in the game. Regions are matched by address and read a block at a time from each, so
+// neither capture has to fit in memory. Bytes in a hole in either capture are not compared
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangedRegion {
+    // As the later capture has it
+    pub region: MemoryRegion,
+    // In order, each a run of changed bytes, or one value for a typed diff
+    pub ranges: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CaptureDiff {
+    // Regions in both captures where something changed
+    pub changed: Vec<ChangedRegion>,
+    // Regions overlapping none in the other capture, mapped or unmapped in between
+    pub only_before: Vec<MemoryRegion>,
+    pub only_after: Vec<MemoryRegion>,
+    pub bytes_compared: usize,
+}
+
+impl CaptureDiff {
+    pub fn addresses(&self) -> impl Iterator<Item = usize> + '_ {
+        self.changed.iter().flat_map(|x| x.ranges.iter().map(|x| x.0))
+    }
+}
+
+// Runs of bytes or values, as start and end
+type Ranges = Vec<(usize, usize)>;
+
+fn overlaps(a: &MemoryRegion, b: &MemoryRegion) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+// The parts of start..end outside every hole
+fn outside_holes(start: usize, end: usize, holes: &mut [(usize, usize)]) -> Vec<(usize, usize)> {
+    holes.sort();
+    let mut ranges = Vec::new();
+    let mut position = start;
+    for (hole_start, hole_end) in holes.iter().copied() {
+        if hole_end <= position || hole_start >= end {
+            continue;
+        }
+        if hole_start > position {
+            ranges.push((position, hole_start));
+        }
+        position = hole_end;
+    }
+    if position < end {
+        ranges.push((position, end));
+    }
+    ranges
+}
+
+// Hands compare each block where the regions of the two captures overlap, as the address, the old
+// and new bytes and how many of them the block owns. Up to slack bytes more are read past them,
+// without leaving the overlap, for values that straddle the next block
+fn walk(before: &Capture, after: &Capture, slack: usize, mut compare: impl FnMut(usize, &[u8], &[u8], usize, &mut Ranges)) -> Result<CaptureDiff, Box<dyn std::error::Error>> {
+    let only = |of: &Capture, other: &Capture| of.regions.iter().filter(|x| !other.regions.iter().any(|y| overlaps(&x.region, &y.region))).map(|x| x.region.clone()).collect();
+    let mut diff = CaptureDiff { only_before: only(before, after), only_after: only(after, before), ..CaptureDiff::default() };
+    let (mut old, mut new) = (Vec::new(), Vec::new());
+    for (index, entry) in after.regions.iter().enumerate() {
+        let mut changed = Vec::new();
+        for (earlier, earlier_entry) in before.regions.iter().enumerate().filter(|x| overlaps(&x.1.region, &entry.region)) {
+            let (start, end) = (entry.region.start.max(earlier_entry.region.start), entry.region.end.min(earlier_entry.region.end));
+            for (start, end) in outside_holes(start, end, &mut [entry.holes.as_slice(), &earlier_entry.holes].concat()) {
+                for address in (start..end).step_by(CAPTURE_BLOCK_SIZE) {
+                    let owned = CAPTURE_BLOCK_SIZE.min(end - address);
+                    let len = (owned + slack).min(end - address);
+                    old.resize(len, 0);
+                    new.resize(len, 0);
+                    before.read_region(earlier, address - earlier_entry.region.start, &mut old)?;
+                    after.read_region(index, address - entry.region.start, &mut new)?;
+                    compare(address, &old, &new, owned, &mut changed);
+                    diff.bytes_compared += owned;
+                }
+            }
+        }
+        if !changed.is_empty() {
+            diff.changed.push(ChangedRegion { region: entry.region.clone(), ranges: changed });
+        }
+    }
+    Ok(diff)
+}
+
+// Adds each run of bytes that differs, a run crossing from one block into the next kept whole
+fn byte_runs(address: usize, old: &[u8], new: &[u8], owned: usize, changed: &mut Ranges) {
+    if old == new {
+        return;
+    }
+    let mut offset = 0;
+    while offset < owned {
+        if old[offset] == new[offset] {
+            offset += 1;
+            continue;
+        }
+        let start = offset;
+        while offset < owned && old[offset] != new[offset] {
+            offset += 1;
+        }
+        match changed.last_mut() {
+            Some(last) if last.1 == address + start => last.1 = address + offset,
+            _ => changed.push((address + start, address + offset)),
+        }
+    }
+}
+
+// Adds each value of type T, at a multiple of alignment, whose bytes changed and whose old and new
+// values satisfy the predicate. Reads T::SIZE - 1 bytes of slack past what the block owns
+fn typed_values<T: Scalar>(alignment: usize, endianness: Endianness, predicate: impl Fn(T, T) -> bool) -> impl FnMut(usize, &[u8], &[u8], usize, &mut Ranges) {
+    let alignment = alignment.max(1);
+    move |address, old, new, owned, changed| {
+        if old == new {
+            return;
+        }
+        let first = (alignment - address % alignment) % alignment;
+        for offset in (first..owned).step_by(alignment).take_while(|x| x + T::SIZE <= old.len()) {
+            let (was, now) = (&old[offset..offset + T::SIZE], &new[offset..offset + T::SIZE]);
+            if was != now && predicate(T::from_bytes_in(was, endianness), T::from_bytes_in(now, endianness)) {
+                changed.push((address + offset, address + offset + T::SIZE));
+            }
+        }
+    }
+}
+
+pub fn diff_captures(before: &Path, after: &Path) -> Result<CaptureDiff, Box<dyn std::error::Error>> {
+    walk(&Capture::open(before)?, &Capture::open(after)?, 0, byte_runs)
+}
+
+// The values of type T, at multiples of alignment, whose bytes changed and whose old and new
+// values satisfy the predicate
+pub fn diff_captures_by<T: Scalar>(before: &Path, after: &Path, alignment: usize, endianness: Endianness, predicate: impl Fn(T, T) -> bool) -> Result<CaptureDiff, Box<dyn std::error::Error>> {
+    walk(&Capture::open(before)?, &Capture::open(after)?, T::SIZE - 1, typed_values(alignment, endianness, predicate))
+}
+
+// Comparing two processes, e.g. two instances of a game with one setting changed, to find where
+// the setting lives. Their regions are paired up by what they hold rather than by address, as the
+// same module or heap is mapped somewhere else in each, and found differences are offsets into a
+// pair. A pair whose regions differ in size is compared up to the shorter
+
+// One region of each process taken to hold the same thing
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionPair {
+    // The module both regions are part of, their pseudo-path such as [heap], or for other
+    // anonymous memory its permissions and the module it follows
+    pub name: String,
+    // Where the regions start relative to the module, and 0 for the rest
+    pub offset: usize,
+    pub first: MemoryRegion,
+    pub second: MemoryRegion,
+}
+
+impl RegionPair {
+    pub fn common_len(&self) -> usize {
+        self.first.len().min(self.second.len())
+    }
+
+    // The offset into the pair as the name plus an offset, module-relative for a module
+    pub fn label(&self, offset: usize) -> String {
+        format!("{}+0x{:x}", self.name, self.offset + offset)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangedPair {
+    pub pair: RegionPair,
+    // As offsets from the start of both regions
+    pub ranges: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProcessDiff {
+    pub changed: Vec<ChangedPair>,
+    pub pairs: usize,
+    // Pairs whose regions differ in size
+    pub resized: Vec<RegionPair>,
+    // Readable regions with nothing to pair them with
+    pub only_first: Vec<MemoryRegion>,
+    pub only_second: Vec<MemoryRegion>,
+    pub bytes_compared: usize,
+}
+
+impl ProcessDiff {
+    // Where each found difference starts in the second process if second, else in the first
+    pub fn addresses(&self, second: bool) -> impl Iterator<Item = usize> + '_ {
+        self.changed.iter().flat_map(move |x| {
+            let start = if second { x.pair.second.start } else { x.pair.first.start };
+            x.ranges.iter().map(move |x| start + x.0)
+        })
+    }
+}
+
+// Names the readable regions the same way in any process: a module's, and the anonymous memory
+// right after it that holds its .bss, by the module and offset; pseudo-paths as they are; and any
+// other mapping by its permissions and the module before it. A name several regions share is
+// numbered in order from the second on
+fn region_names(regions: &[MemoryRegion]) -> Vec<(String, usize, MemoryRegion)> {
+    let modules = modules_from_regions(regions);
+    let mut counts: HashMap<(String, usize), usize> = HashMap::new();
+    let mut previous = "the first module".to_string();
+    let mut names = Vec::new();
+    for region in regions {
+        let (name, offset) = match modules.iter().find(|x| region.start >= x.base && region.start < x.base + x.size) {
+            Some(module) => {
+                previous.clone_from(&module.name);
+                (module.name.clone(), region.start - module.base)
+            }
+            None if region.pathname.starts_with('[') => (region.pathname.clone(), 0),
+            None => (format!("[{} {} after {}]", if region.pathname.is_empty() { "anonymous" } else { region.pathname.as_str() }, region.permissions(), previous), 0),
+        };
+        if !region.readable {
+            continue;
+        }
+        let count = counts.entry((name.clone(), offset)).or_default();
+        *count += 1;
+        let name = match name.strip_suffix(']') {
+            _ if *count == 1 => name,
+            Some(name) => format!("{} #{}]", name, count),
+            None => format!("{} #{}", name, count),
+        };
+        names.push((name, offset, region.clone()));
+    }
+    names
+}
+
+// Pairs up the readable regions of two processes by name, returning the pairs and what each
+// process has that the other does not
+pub fn pair_regions(first: &[MemoryRegion], second: &[MemoryRegion]) -> (Vec<RegionPair>, Vec<MemoryRegion>, Vec<MemoryRegion>) {
+    let mut others = region_names(second).into_iter().map(|x| ((x.0, x.1), x.2)).collect::<HashMap<(String, usize), MemoryRegion>>();
+    let mut pairs = Vec::new();
+    let mut only_first = Vec::new();
+    for (name, offset, region) in region_names(first) {
+        match others.remove(&(name.clone(), offset)) {
+            Some(other) => pairs.push(RegionPair { name, offset, first: region, second: other }),
+            None => only_first.push(region),
+        }
+    }
+    let mut only_second = others.into_values().collect::<Vec<MemoryRegion>>();
+    only_second.sort_by_key(|x| x.start);
+    (pairs, only_first, only_second)
+}
+
+// Like walk, with each block read from both processes at the same offset into a pair. Only what
+// both reads returned is compared
+fn walk_processes(first: impl ProcessMemory, second: impl ProcessMemory, filter: Option<&RegionFilter>, slack: usize, mut compare: impl FnMut(usize, &[u8], &[u8], usize, &mut Ranges)) -> Result<ProcessDiff, Box<dyn std::error::Error>> {
+    let (first_regions, second_regions) = (first.memory_regions()?, second.memory_regions()?);
+    let (mut pairs, mut only_first, mut only_second) = pair_regions(&first_regions, &second_regions);
+    // Filtered after pairing, so that the regions are named with all of them around
+    if let Some(filter) = filter {
+        let selected = |process: &dyn ProcessMemory, regions: &[MemoryRegion]| filter.select(&process, regions).into_iter().map(|x| x.start).collect::<HashSet<usize>>();
+        let (in_first, in_second) = (selected(&first, &first_regions), selected(&second, &second_regions));
+        pairs.retain(|x| in_first.contains(&x.first.start) && in_second.contains(&x.second.start));
+        only_first.retain(|x| in_first.contains(&x.start));
+        only_second.retain(|x| in_second.contains(&x.start));
+    }
+    let mut diff = ProcessDiff { pairs: pairs.len(), only_first, only_second, ..ProcessDiff::default() };
+    let (mut old, mut new) = (vec![0u8; SCAN_CHUNK_SIZE + slack], vec![0u8; SCAN_CHUNK_SIZE + slack]);
+    for pair in pairs {
+        let len = pair.common_len();
+        let mut changed = Vec::new();
+        for offset in (0..len).step_by(SCAN_CHUNK_SIZE) {
+            let wanted = (SCAN_CHUNK_SIZE + slack).min(len - offset);
+            let read = read_bytes_into(&first, pair.first.start + offset, &mut old[..wanted]).unwrap_or(0);
+            let read = read.min(read_bytes_into(&second, pair.second.start + offset, &mut new[..wanted]).unwrap_or(0));
+            let owned = SCAN_CHUNK_SIZE.min(read);
+            compare(offset, &old[..read], &new[..read], owned, &mut changed);
+            diff.bytes_compared += owned;
+        }
+        if pair.first.len() != pair.second.len() {
+            diff.resized.push(pair.clone());
+        }
+        if !changed.is_empty() {
+            diff.changed.push(ChangedPair { pair, ranges: changed });
+        }
+    }
+    Ok(diff)
+}
+
+// Every run of bytes that differs, in the pairs whose regions the filter selects in both if given
+pub fn diff_processes(first: impl ProcessMemory, second: impl ProcessMemory, filter: Option<&RegionFilter>) -> Result<ProcessDiff, Box<dyn std::error::Error>> {
+    walk_processes(first, second, filter, 0, byte_runs)
+}
+
+// As diff_captures_by, the alignment counted from the start of each pair
+pub fn diff_processes_by<T: Scalar>(first: impl ProcessMemory, second: impl ProcessMemory, filter: Option<&RegionFilter>, alignment: usize, endianness: Endianness, predicate: impl Fn(T, T) -> bool) -> Result<ProcessDiff, Box<dyn std::error::Error>> {
+    walk_processes(first, second, filter, T::SIZE - 1, typed_values(alignment, endianness, predicate))
+}
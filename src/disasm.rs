@@ -0,0 +1,633 @@
+use crate::x86::decode_instruction;
+
+// Turning x86-64 machine code back into Intel-syntax text, on top of the length decoder in x86.
+// The lengths come from there, so whatever it cannot measure ends a listing. The common integer
+// instructions and the SSE moves and arithmetic compilers emit for scalar code are named with
+// their operands; anything else it can measure is shown as (unknown), so a listing carries on
+// past it with the instructions after it still in step
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disassembled {
+    pub address: usize,
+    pub bytes: Vec<u8>,
+    // e.g. mov eax, dword ptr [rip+0x2f1a]
+    pub text: String,
+    // Where a relative call, jmp, jcc or loop goes, or the address a [rip + disp32] operand is at
+    pub target: Option<usize>,
+}
+
+const CONDITIONS: [&str; 16] = ["o", "no", "b", "ae", "e", "ne", "be", "a", "s", "ns", "p", "np", "l", "ge", "le", "g"];
+const ARITHMETIC: [&str; 8] = ["add", "or", "adc", "sbb", "and", "sub", "xor", "cmp"];
+const SHIFTS: [&str; 8] = ["rol", "ror", "rcl", "rcr", "shl", "shr", "sal", "sar"];
+const SEGMENTS: [&str; 6] = ["es", "cs", "ss", "ds", "fs", "gs"];
+const QWORDS: [&str; 16] = ["rax", "rcx", "rdx", "rbx", "rsp", "rbp", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15"];
+const DWORDS: [&str; 16] = ["eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi", "r8d", "r9d", "r10d", "r11d", "r12d", "r13d", "r14d", "r15d"];
+const WORDS: [&str; 16] = ["ax", "cx", "dx", "bx", "sp", "bp", "si", "di", "r8w", "r9w", "r10w", "r11w", "r12w", "r13w", "r14w", "r15w"];
+const BYTES: [&str; 16] = ["al", "cl", "dl", "bl", "spl", "bpl", "sil", "dil", "r8b", "r9b", "r10b", "r11b", "r12b", "r13b", "r14b", "r15b"];
+// Without a REX prefix, 4 to 7 are the high bytes of the first four instead
+const HIGH_BYTES: [&str; 8] = ["al", "cl", "dl", "bl", "ah", "ch", "dh", "bh"];
+
+// Decodes one instruction. Only an instruction the length decoder rejects, or one cut short by
+// the end of the code, is an error
+pub fn disassemble(code: &[u8], address: usize) -> Result<Disassembled, Box<dyn std::error::Error>> {
+    let len = decode_instruction(code)?.len;
+    let mut decoder = Decoder { code: &code[..len], at: 0, next: address.wrapping_add(len), operand16: false, address32: false, rep: false, repne: false, lock: false, rex: 0, segment: "", target: None };
+    let (text, target) = match decoder.instruction() {
+        Some(text) if decoder.lock => (format!("lock {}", text), decoder.target),
+        Some(text) => (text, decoder.target),
+        None => ("(unknown)".to_string(), None),
+    };
+    Ok(Disassembled { address, bytes: code[..len].to_vec(), text, target })
+}
+
+// The instruction that ends at an address, from code read from code_start. Decoding forward from
+// the wrong byte falls into step with the real instructions within a few of them, so of the
+// starts whose instructions land exactly on the address, the earliest is the likeliest
+pub fn instruction_before(code: &[u8], code_start: usize, address: usize) -> Option<Disassembled> {
+    let end = address.checked_sub(code_start).filter(|&x| x <= code.len())?;
+    for start in 0..end {
+        let (mut at, mut last) = (start, None);
+        while at < end && let Ok(instruction) = disassemble(&code[at..end], code_start + at) {
+            at += instruction.bytes.len();
+            last = Some(instruction);
+        }
+        if at == end {
+            return last;
+        }
+    }
+    None
+}
+
+struct Decoder<'a> {
+    // Exactly the instruction
+    code: &'a [u8],
+    at: usize,
+    // The address after it, which relative operands count from
+    next: usize,
+    operand16: bool,
+    address32: bool,
+    // f3 and f2, which are also what select the scalar forms of SSE instructions
+    rep: bool,
+    repne: bool,
+    lock: bool,
+    rex: u8,
+    // fs: or gs:, the only overrides that do anything in 64-bit mode
+    segment: &'static str,
+    target: Option<usize>,
+}
+
+struct ModRm {
+    // With REX.R
+    reg: u8,
+    rm: Rm,
+}
+
+enum Rm {
+    Register(u8),
+    // Without its size, e.g. [rbp-0x8]
+    Memory(String),
+}
+
+fn hex(value: i64, size: usize) -> String {
+    match size {
+        8.. => format!("0x{:x}", value as u64),
+        _ => format!("0x{:x}", value as u64 & ((1 << (size * 8)) - 1)),
+    }
+}
+
+fn signed(value: i64) -> String {
+    match value < 0 {
+        true => format!("-0x{:x}", value.unsigned_abs()),
+        false => format!("+0x{:x}", value),
+    }
+}
+
+fn pointer(size: usize) -> &'static str {
+    match size {
+        1 => "byte",
+        2 => "word",
+        4 => "dword",
+        8 => "qword",
+        _ => "xmmword",
+    }
+}
+
+impl Decoder<'_> {
+    fn byte(&mut self) -> Option<u8> {
+        let byte = *self.code.get(self.at)?;
+        self.at += 1;
+        Some(byte)
+    }
+
+    // Little-endian and sign-extended
+    fn immediate(&mut self, len: usize) -> Option<i64> {
+        let bytes = self.code.get(self.at..self.at + len)?;
+        self.at += len;
+        let value = bytes.iter().rev().fold(0u64, |x, y| x << 8 | *y as u64);
+        let unused = 64 - 8 * len as u32;
+        Some(((value << unused) as i64) >> unused)
+    }
+
+    // A z immediate, a word with a 66 prefix and a dword otherwise, even with REX.W
+    fn immediate_z(&mut self) -> Option<i64> {
+        self.immediate(if self.size() == 2 { 2 } else { 4 })
+    }
+
+    fn relative(&mut self, len: usize) -> Option<String> {
+        let target = self.next.wrapping_add_signed(self.immediate(len)? as isize);
+        self.target = Some(target);
+        Some(format!("0x{:x}", target))
+    }
+
+    // The operand size of most instructions
+    fn size(&self) -> usize {
+        match (self.rex & 8 != 0, self.operand16) {
+            (true, _) => 8,
+            (false, true) => 2,
+            (false, false) => 4,
+        }
+    }
+
+    // push, pop and indirect call and jmp default to 64 bits
+    fn stack_size(&self) -> usize {
+        if self.operand16 { 2 } else { 8 }
+    }
+
+    fn register(&self, n: u8, size: usize) -> String {
+        let n = n as usize;
+        match size {
+            8 => QWORDS[n].to_string(),
+            4 => DWORDS[n].to_string(),
+            2 => WORDS[n].to_string(),
+            1 if self.rex == 0 && n < 8 => HIGH_BYTES[n].to_string(),
+            1 => BYTES[n].to_string(),
+            _ => format!("xmm{}", n),
+        }
+    }
+
+    fn modrm(&mut self) -> Option<ModRm> {
+        let byte = self.byte()?;
+        let (mode, reg, rm) = (byte >> 6, (byte >> 3 & 7) | (self.rex & 4) << 1, byte & 7);
+        if mode == 3 {
+            return Some(ModRm { reg, rm: Rm::Register(rm | (self.rex & 1) << 3) });
+        }
+        let width = if self.address32 { 4 } else { 8 };
+        let (mut base, mut index, mut displacement) = (None, None, 0);
+        if rm == 4 {
+            let sib = self.byte()?;
+            let (scale, index_n, base_n) = (1 << (sib >> 6), (sib >> 3 & 7) | (self.rex & 2) << 2, sib & 7);
+            if index_n != 4 {
+                index = Some((self.register(index_n, width), scale));
+            }
+            if base_n == 5 && mode == 0 {
+                displacement = self.immediate(4)?;
+            }
+            else {
+                base = Some(self.register(base_n | (self.rex & 1) << 3, width));
+            }
+        }
+        else if rm == 5 && mode == 0 {
+            let displacement = self.immediate(4)?;
+            self.target = Some(self.next.wrapping_add_signed(displacement as isize));
+            let rip = if self.address32 { "eip" } else { "rip" };
+            return Some(ModRm { reg, rm: Rm::Memory(format!("{}[{}{}]", self.segment, rip, signed(displacement))) });
+        }
+        else {
+            base = Some(self.register(rm | (self.rex & 1) << 3, width));
+        }
+        displacement = match mode {
+            1 => self.immediate(1)?,
+            2 => self.immediate(4)?,
+            _ => displacement,
+        };
+        let mut text = base.unwrap_or_default();
+        if let Some((index, scale)) = index {
+            if !text.is_empty() {
+                text.push('+');
+            }
+            text += &match scale {
+                1 => index,
+                _ => format!("{}*{}", index, scale),
+            };
+        }
+        if text.is_empty() {
+            text = hex(displacement, width);
+        }
+        else if displacement != 0 {
+            text += &signed(displacement);
+        }
+        Some(ModRm { reg, rm: Rm::Memory(format!("{}[{}]", self.segment, text)) })
+    }
+
+    // The r/m operand as a register or memory of the size
+    fn rm(&self, modrm: &ModRm, size: usize) -> String {
+        match &modrm.rm {
+            Rm::Register(n) => self.register(*n, size),
+            Rm::Memory(memory) => format!("{} ptr {}", pointer(size), memory),
+        }
+    }
+
+    // The common r/m, reg and reg, r/m pairs
+    fn pair(&mut self, name: &str, size: usize, reg_first: bool) -> Option<String> {
+        let modrm = self.modrm()?;
+        let (rm, reg) = (self.rm(&modrm, size), self.register(modrm.reg, size));
+        Some(match reg_first {
+            true => format!("{} {}, {}", name, reg, rm),
+            false => format!("{} {}, {}", name, rm, reg),
+        })
+    }
+
+    fn instruction(&mut self) -> Option<String> {
+        loop {
+            match self.code.get(self.at)? {
+                0x66 => self.operand16 = true,
+                0x67 => self.address32 = true,
+                0xf3 => self.rep = true,
+                0xf2 => self.repne = true,
+                0xf0 => self.lock = true,
+                0x64 => self.segment = "fs:",
+                0x65 => self.segment = "gs:",
+                0x26 | 0x2e | 0x36 | 0x3e => {}
+                0x40..=0x4f => self.rex = self.code[self.at],
+                _ => break,
+            }
+            // Only a REX right before the opcode counts
+            if !(0x40..=0x4f).contains(&self.code[self.at]) {
+                self.rex = 0;
+            }
+            self.at += 1;
+        }
+        let opcode = self.byte()?;
+        let size = self.size();
+        Some(match opcode {
+            0x0f => return self.escaped(),
+            0x00..=0x3f => {
+                let name = ARITHMETIC[opcode as usize >> 3];
+                match opcode & 7 {
+                    0 => self.pair(name, 1, false)?,
+                    1 => self.pair(name, size, false)?,
+                    2 => self.pair(name, 1, true)?,
+                    3 => self.pair(name, size, true)?,
+                    4 => format!("{} al, {}", name, hex(self.immediate(1)?, 1)),
+                    5 => format!("{} {}, {}", name, self.register(0, size), hex(self.immediate_z()?, size)),
+                    _ => return None,
+                }
+            }
+            0x50..=0x57 => format!("push {}", self.register(opcode & 7 | (self.rex & 1) << 3, self.stack_size())),
+            0x58..=0x5f => format!("pop {}", self.register(opcode & 7 | (self.rex & 1) << 3, self.stack_size())),
+            0x63 => {
+                let modrm = self.modrm()?;
+                format!("movsxd {}, {}", self.register(modrm.reg, size), self.rm(&modrm, 4))
+            }
+            0x68 => format!("push {}", hex(self.immediate_z()?, self.stack_size())),
+            0x6a => format!("push {}", hex(self.immediate(1)?, self.stack_size())),
+            0x69 | 0x6b => {
+                let modrm = self.modrm()?;
+                let immediate = if opcode == 0x69 { self.immediate_z()? } else { self.immediate(1)? };
+                format!("imul {}, {}, {}", self.register(modrm.reg, size), self.rm(&modrm, size), hex(immediate, size))
+            }
+            0x6c => "insb".to_string(),
+            0x6d => format!("ins{}", if size == 2 { "w" } else { "d" }),
+            0x6e => "outsb".to_string(),
+            0x6f => format!("outs{}", if size == 2 { "w" } else { "d" }),
+            0x70..=0x7f => format!("j{} {}", CONDITIONS[opcode as usize & 15], self.relative(1)?),
+            0x80..=0x83 => {
+                let modrm = self.modrm()?;
+                let size = if opcode == 0x80 { 1 } else { size };
+                let immediate = if opcode == 0x81 { self.immediate_z()? } else { self.immediate(1)? };
+                format!("{} {}, {}", ARITHMETIC[modrm.reg as usize & 7], self.rm(&modrm, size), hex(immediate, size))
+            }
+            0x84 => self.pair("test", 1, false)?,
+            0x85 => self.pair("test", size, false)?,
+            0x86 => self.pair("xchg", 1, false)?,
+            0x87 => self.pair("xchg", size, false)?,
+            0x88 => self.pair("mov", 1, false)?,
+            0x89 => self.pair("mov", size, false)?,
+            0x8a => self.pair("mov", 1, true)?,
+            0x8b => self.pair("mov", size, true)?,
+            0x8c | 0x8e => {
+                let modrm = self.modrm()?;
+                let segment = SEGMENTS.get(modrm.reg as usize & 7)?;
+                match opcode {
+                    0x8c => format!("mov {}, {}", self.rm(&modrm, 2), segment),
+                    _ => format!("mov {}, {}", segment, self.rm(&modrm, 2)),
+                }
+            }
+            0x8d => {
+                let modrm = self.modrm()?;
+                let Rm::Memory(memory) = &modrm.rm else { return None };
+                format!("lea {}, {}", self.register(modrm.reg, size), memory)
+            }
+            0x8f => {
+                let modrm = self.modrm()?;
+                if modrm.reg & 7 != 0 {
+                    return None;
+                }
+                format!("pop {}", self.rm(&modrm, self.stack_size()))
+            }
+            0x90 if self.rep => "pause".to_string(),
+            0x90 if self.rex & 1 == 0 => "nop".to_string(),
+            0x90..=0x97 => format!("xchg {}, {}", self.register(opcode & 7 | (self.rex & 1) << 3, size), self.register(0, size)),
+            0x98 => match size { 2 => "cbw", 4 => "cwde", _ => "cdqe" }.to_string(),
+            0x99 => match size { 2 => "cwd", 4 => "cdq", _ => "cqo" }.to_string(),
+            0x9b => "fwait".to_string(),
+            0x9c => (if self.operand16 { "pushf" } else { "pushfq" }).to_string(),
+            0x9d => (if self.operand16 { "popf" } else { "popfq" }).to_string(),
+            0x9e => "sahf".to_string(),
+            0x9f => "lahf".to_string(),
+            0xa0..=0xa3 => {
+                let address = self.immediate(if self.address32 { 4 } else { 8 })?;
+                let size = if opcode & 1 == 0 { 1 } else { size };
+                let memory = format!("{} ptr {}[{}]", pointer(size), self.segment, hex(address, if self.address32 { 4 } else { 8 }));
+                match opcode {
+                    0xa0 | 0xa1 => format!("mov {}, {}", self.register(0, size), memory),
+                    _ => format!("mov {}, {}", memory, self.register(0, size)),
+                }
+            }
+            0xa8 => format!("test al, {}", hex(self.immediate(1)?, 1)),
+            0xa9 => format!("test {}, {}", self.register(0, size), hex(self.immediate_z()?, size)),
+            0xa4..=0xa7 | 0xaa..=0xaf => {
+                let name = ["movs", "cmps", "", "stos", "lods", "scas"][(opcode as usize - 0xa4) / 2];
+                let suffix = match (opcode & 1, size) {
+                    (0, _) => "b",
+                    (_, 2) => "w",
+                    (_, 4) => "d",
+                    _ => "q",
+                };
+                // cmps and scas stop on a difference or a match, so their rep is repe
+                let compares = matches!(opcode, 0xa6 | 0xa7 | 0xae | 0xaf);
+                let rep = match (self.rep, self.repne) {
+                    (true, _) if compares => "repe ",
+                    (true, _) => "rep ",
+                    (_, true) if compares => "repne ",
+                    _ => "",
+                };
+                format!("{}{}{}", rep, name, suffix)
+            }
+            0xb0..=0xb7 => format!("mov {}, {}", self.register(opcode & 7 | (self.rex & 1) << 3, 1), hex(self.immediate(1)?, 1)),
+            0xb8..=0xbf => {
+                let register = self.register(opcode & 7 | (self.rex & 1) << 3, size);
+                match size {
+                    8 => format!("movabs {}, {}", register, hex(self.immediate(8)?, 8)),
+                    _ => format!("mov {}, {}", register, hex(self.immediate(size)?, size)),
+                }
+            }
+            0xc0 | 0xc1 | 0xd0..=0xd3 => {
+                let modrm = self.modrm()?;
+                let size = if opcode & 1 == 0 { 1 } else { size };
+                let count = match opcode {
+                    0xc0 | 0xc1 => hex(self.immediate(1)?, 1),
+                    0xd0 | 0xd1 => "1".to_string(),
+                    _ => "cl".to_string(),
+                };
+                format!("{} {}, {}", SHIFTS[modrm.reg as usize & 7], self.rm(&modrm, size), count)
+            }
+            0xc2 => format!("ret {}", hex(self.immediate(2)?, 2)),
+            0xc3 => "ret".to_string(),
+            0xc6 | 0xc7 => {
+                let modrm = self.modrm()?;
+                let size = if opcode == 0xc6 { 1 } else { size };
+                match (modrm.reg & 7, &modrm.rm) {
+                    (0, _) => {
+                        let immediate = if size == 1 { self.immediate(1)? } else { self.immediate_z()? };
+                        format!("mov {}, {}", self.rm(&modrm, size), hex(immediate, size))
+                    }
+                    (7, Rm::Register(0)) if size == 1 => format!("xabort {}", hex(self.immediate(1)?, 1)),
+                    (7, Rm::Register(0)) => format!("xbegin {}", self.relative(if size == 2 { 2 } else { 4 })?),
+                    _ => return None,
+                }
+            }
+            0xc8 => {
+                let (frame, level) = (self.immediate(2)?, self.immediate(1)?);
+                format!("enter {}, {}", hex(frame, 2), hex(level, 1))
+            }
+            0xc9 => "leave".to_string(),
+            0xca => format!("retf {}", hex(self.immediate(2)?, 2)),
+            0xcb => "retf".to_string(),
+            0xcc => "int3".to_string(),
+            0xcd => format!("int {}", hex(self.immediate(1)?, 1)),
+            0xcf => "iretq".to_string(),
+            0xd7 => "xlatb".to_string(),
+            0xe0 => format!("loopne {}", self.relative(1)?),
+            0xe1 => format!("loope {}", self.relative(1)?),
+            0xe2 => format!("loop {}", self.relative(1)?),
+            0xe3 => format!("{} {}", if self.address32 { "jecxz" } else { "jrcxz" }, self.relative(1)?),
+            0xe4 => format!("in al, {}", hex(self.immediate(1)?, 1)),
+            0xe5 => format!("in eax, {}", hex(self.immediate(1)?, 1)),
+            0xe6 => format!("out {}, al", hex(self.immediate(1)?, 1)),
+            0xe7 => format!("out {}, eax", hex(self.immediate(1)?, 1)),
+            0xe8 => format!("call {}", self.relative(4)?),
+            0xe9 => format!("jmp {}", self.relative(4)?),
+            0xeb => format!("jmp {}", self.relative(1)?),
+            0xec => "in al, dx".to_string(),
+            0xed => "in eax, dx".to_string(),
+            0xee => "out dx, al".to_string(),
+            0xef => "out dx, eax".to_string(),
+            0xf1 => "int1".to_string(),
+            0xf4 => "hlt".to_string(),
+            0xf5 => "cmc".to_string(),
+            0xf6 | 0xf7 => {
+                let modrm = self.modrm()?;
+                let size = if opcode == 0xf6 { 1 } else { size };
+                let name = ["test", "test", "not", "neg", "mul", "imul", "div", "idiv"][modrm.reg as usize & 7];
+                match modrm.reg & 7 {
+                    0 | 1 => {
+                        let immediate = if size == 1 { self.immediate(1)? } else { self.immediate_z()? };
+                        format!("test {}, {}", self.rm(&modrm, size), hex(immediate, size))
+                    }
+                    _ => format!("{} {}", name, self.rm(&modrm, size)),
+                }
+            }
+            0xf8 => "clc".to_string(),
+            0xf9 => "stc".to_string(),
+            0xfa => "cli".to_string(),
+            0xfb => "sti".to_string(),
+            0xfc => "cld".to_string(),
+            0xfd => "std".to_string(),
+            0xfe => {
+                let modrm = self.modrm()?;
+                let name = ["inc", "dec"].get(modrm.reg as usize & 7)?;
+                format!("{} {}", name, self.rm(&modrm, 1))
+            }
+            0xff => {
+                let modrm = self.modrm()?;
+                match modrm.reg & 7 {
+                    0 => format!("inc {}", self.rm(&modrm, size)),
+                    1 => format!("dec {}", self.rm(&modrm, size)),
+                    2 => format!("call {}", self.rm(&modrm, 8)),
+                    4 => format!("jmp {}", self.rm(&modrm, 8)),
+                    6 => format!("push {}", self.rm(&modrm, self.stack_size())),
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        })
+    }
+
+    // The two-byte opcodes after 0f. The 0f 38 and 0f 3a maps, x87 and VEX are not named
+    fn escaped(&mut self) -> Option<String> {
+        let opcode = self.byte()?;
+        let size = self.size();
+        // The SSE forms by prefix: packed single, packed double, scalar single, scalar double,
+        // with how much memory each reads
+        let (suffix, memory) = match (self.rep, self.repne, self.operand16) {
+            (true, _, _) => ("ss", 4),
+            (_, true, _) => ("sd", 8),
+            (_, _, true) => ("pd", 16),
+            _ => ("ps", 16),
+        };
+        let packed = suffix.starts_with('p');
+        Some(match opcode {
+            0x01 => match self.byte()? {
+                0xd0 => "xgetbv".to_string(),
+                0xf9 => "rdtscp".to_string(),
+                _ => return None,
+            },
+            0x05 => "syscall".to_string(),
+            0x0b => "ud2".to_string(),
+            0x10 | 0x11 => {
+                let name = if packed { format!("movu{}", suffix) } else { format!("mov{}", suffix) };
+                self.xmm(&name, memory, opcode == 0x11)?
+            }
+            0x18 => {
+                let modrm = self.modrm()?;
+                let name = ["prefetchnta", "prefetcht0", "prefetcht1", "prefetcht2"].get(modrm.reg as usize & 7)?;
+                format!("{} {}", name, self.rm(&modrm, 1))
+            }
+            0x1e if self.rep => match self.byte()? {
+                0xfa => "endbr64".to_string(),
+                0xfb => "endbr32".to_string(),
+                _ => return None,
+            },
+            0x1f => {
+                let modrm = self.modrm()?;
+                format!("nop {}", self.rm(&modrm, size))
+            }
+            0x28 | 0x29 if packed => self.xmm(&format!("mova{}", suffix), memory, opcode == 0x29)?,
+            0x2a if !packed => {
+                let modrm = self.modrm()?;
+                let integer = if self.rex & 8 != 0 { 8 } else { 4 };
+                format!("cvtsi2{} {}, {}", &suffix[1..], self.register(modrm.reg, 16), self.rm(&modrm, integer))
+            }
+            0x2c | 0x2d if !packed => {
+                let modrm = self.modrm()?;
+                let name = if opcode == 0x2c { "cvtt" } else { "cvt" };
+                format!("{}{}2si {}, {}", name, &suffix[1..], self.register(modrm.reg, size.max(4)), self.xmm_rm(&modrm, memory))
+            }
+            0x2e | 0x2f if !self.rep && !self.repne => {
+                let name = if opcode == 0x2e { "ucomis" } else { "comis" };
+                let (name, memory) = if self.operand16 { (format!("{}d", name), 8) } else { (format!("{}s", name), 4) };
+                self.xmm(&name, memory, false)?
+            }
+            0x31 => "rdtsc".to_string(),
+            0x40..=0x4f => self.pair(&format!("cmov{}", CONDITIONS[opcode as usize & 15]), size, true)?,
+            0x51 | 0x58 | 0x59 | 0x5c..=0x5f => {
+                let name = match opcode { 0x51 => "sqrt", 0x58 => "add", 0x59 => "mul", 0x5c => "sub", 0x5d => "min", 0x5e => "div", _ => "max" };
+                self.xmm(&format!("{}{}", name, suffix), memory, false)?
+            }
+            0x54..=0x57 if packed => {
+                let name = ["and", "andn", "or", "xor"][opcode as usize - 0x54];
+                self.xmm(&format!("{}{}", name, suffix), memory, false)?
+            }
+            0x6e | 0x7e if self.operand16 => {
+                let modrm = self.modrm()?;
+                let (name, integer) = if self.rex & 8 != 0 { ("movq", 8) } else { ("movd", 4) };
+                let (xmm, rm) = (self.register(modrm.reg, 16), self.rm(&modrm, integer));
+                match opcode {
+                    0x6e => format!("{} {}, {}", name, xmm, rm),
+                    _ => format!("{} {}, {}", name, rm, xmm),
+                }
+            }
+            0x7e if self.rep => self.xmm("movq", 8, false)?,
+            0xd6 if self.operand16 => self.xmm("movq", 8, true)?,
+            0x6f | 0x7f if self.operand16 || self.rep => {
+                let name = if self.rep { "movdqu" } else { "movdqa" };
+                self.xmm(name, 16, opcode == 0x7f)?
+            }
+            0x70 if self.operand16 => {
+                let instruction = self.xmm("pshufd", 16, false)?;
+                format!("{}, {}", instruction, hex(self.immediate(1)?, 1))
+            }
+            0x74..=0x76 | 0xd4 | 0xdb | 0xdf | 0xeb | 0xef | 0xfa | 0xfb | 0xfe if self.operand16 => {
+                let name = match opcode {
+                    0x74 => "pcmpeqb",
+                    0x75 => "pcmpeqw",
+                    0x76 => "pcmpeqd",
+                    0xd4 => "paddq",
+                    0xdb => "pand",
+                    0xdf => "pandn",
+                    0xeb => "por",
+                    0xef => "pxor",
+                    0xfa => "psubd",
+                    0xfb => "psubq",
+                    _ => "paddd",
+                };
+                self.xmm(name, 16, false)?
+            }
+            0xd7 if self.operand16 => {
+                let modrm = self.modrm()?;
+                let Rm::Register(n) = modrm.rm else { return None };
+                format!("pmovmskb {}, {}", self.register(modrm.reg, 4), self.register(n, 16))
+            }
+            0x80..=0x8f => format!("j{} {}", CONDITIONS[opcode as usize & 15], self.relative(4)?),
+            0x90..=0x9f => {
+                let modrm = self.modrm()?;
+                format!("set{} {}", CONDITIONS[opcode as usize & 15], self.rm(&modrm, 1))
+            }
+            0xa0 => "push fs".to_string(),
+            0xa1 => "pop fs".to_string(),
+            0xa8 => "push gs".to_string(),
+            0xa9 => "pop gs".to_string(),
+            0xa2 => "cpuid".to_string(),
+            0xa3 => self.pair("bt", size, false)?,
+            0xab => self.pair("bts", size, false)?,
+            0xb3 => self.pair("btr", size, false)?,
+            0xbb => self.pair("btc", size, false)?,
+            0xa4 | 0xa5 | 0xac | 0xad => {
+                let instruction = self.pair(if opcode < 0xa8 { "shld" } else { "shrd" }, size, false)?;
+                match opcode & 1 {
+                    0 => format!("{}, {}", instruction, hex(self.immediate(1)?, 1)),
+                    _ => format!("{}, cl", instruction),
+                }
+            }
+            0xaf => self.pair("imul", size, true)?,
+            0xb0 => self.pair("cmpxchg", 1, false)?,
+            0xb1 => self.pair("cmpxchg", size, false)?,
+            0xb6 | 0xb7 | 0xbe | 0xbf => {
+                let modrm = self.modrm()?;
+                let name = if opcode < 0xb8 { "movzx" } else { "movsx" };
+                format!("{} {}, {}", name, self.register(modrm.reg, size), self.rm(&modrm, if opcode & 1 == 0 { 1 } else { 2 }))
+            }
+            0xb8 if self.rep => self.pair("popcnt", size, true)?,
+            0xba => {
+                let modrm = self.modrm()?;
+                let name = ["bt", "bts", "btr", "btc"].get((modrm.reg as usize & 7).checked_sub(4)?)?;
+                format!("{} {}, {}", name, self.rm(&modrm, size), hex(self.immediate(1)?, 1))
+            }
+            0xbc => self.pair(if self.rep { "tzcnt" } else { "bsf" }, size, true)?,
+            0xbd => self.pair(if self.rep { "lzcnt" } else { "bsr" }, size, true)?,
+            0xc0 => self.pair("xadd", 1, false)?,
+            0xc1 => self.pair("xadd", size, false)?,
+            0xc8..=0xcf => format!("bswap {}", self.register(opcode & 7 | (self.rex & 1) << 3, size.max(4))),
+            _ => return None,
+        })
+    }
+
+    // The r/m operand as an xmm register or memory of the size
+    fn xmm_rm(&self, modrm: &ModRm, memory: usize) -> String {
+        match modrm.rm {
+            Rm::Register(n) => self.register(n, 16),
+            Rm::Memory(_) => self.rm(modrm, memory),
+        }
+    }
+
+    // An SSE instruction between an xmm register and an xmm register or memory of the size
+    fn xmm(&mut self, name: &str, memory: usize, store: bool) -> Option<String> {
+        let modrm = self.modrm()?;
+        let (xmm, rm) = (self.register(modrm.reg, 16), self.xmm_rm(&modrm, memory));
+        Some(match store {
+            true => format!("{} {}, {}", name, rm, xmm),
+            false => format!("{} {}, {}", name, xmm, rm),
+        })
+    }
+}
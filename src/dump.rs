@@ -0,0 +1,87 @@
+use std::{io::Write, path::Path};
+use serde::{Deserialize, Serialize};
+use crate::{capture::{Capture, CaptureHeader, CapturedRegion, Compression, uncaptured}, maps::MemoryRegion, process::{ProcessMemory, read_bytes_into}, scan::{SCAN_CHUNK_SIZE, split_into_chunks}};
+
+// The name of the file describing the regions in a dump directory, which is what dump all wrote
+// before captures. Offline mode still reads them
+pub const DUMP_INDEX: &str = "index.json";
+
+// Unreadable parts of a dumped range are found and zero-filled at this granularity
+const DUMP_PAGE_SIZE: usize = 4096;
+
+// One region of a dump directory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DumpedRegion {
+    #[serde(flatten)]
+    pub region: MemoryRegion,
+    // Relative to the dump directory
+    pub file: String,
+    // Ranges that could not be read and were written as zeroes
+    pub holes: Vec<(usize, usize)>,
+}
+
+// Everything about the process besides its memory that is needed to scan the capture later as if
+// it were the live process
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DumpIndex {
+    pub pid: i32,
+    pub executable: Option<String>,
+    // Thread ids with an address inside each thread's stack
+    pub stacks: Vec<(i32, usize)>,
+    pub regions: Vec<DumpedRegion>,
+}
+
+// Writes the range chunk by chunk, so the output has exactly one byte per address. A chunk that
+// cannot be read in full is retried page by page, a huge page at a time in regions of them, and
+// whatever still fails is written as zeroes and returned as a hole
+pub fn dump_range(process: impl ProcessMemory, start: usize, end: usize, output: &mut impl Write) -> Result<Vec<(usize, usize)>, Box<dyn std::error::Error>> {
+    let mut holes: Vec<(usize, usize)> = Vec::new();
+    let mut buffer = vec![0u8; SCAN_CHUNK_SIZE];
+    // Only needed once a read fails
+    let mut regions = None;
+    for chunk in split_into_chunks(&[(start, end)], SCAN_CHUNK_SIZE) {
+        let data = &mut buffer[..chunk.len];
+        if read_bytes_into(&process, chunk.address, data).is_ok_and(|x| x == chunk.len) {
+            output.write_all(data)?;
+            continue;
+        }
+        let regions: &Vec<MemoryRegion> = regions.get_or_insert_with(|| process.memory_regions().unwrap_or_default());
+        let page_size = regions.iter().find(|x| x.contains(chunk.address)).and_then(|x| x.kernel_page_size).unwrap_or(DUMP_PAGE_SIZE);
+        // Cut at page boundaries rather than a page from the start, which for a range starting
+        // mid-page would make the readable start of the page after a hole part of it
+        let (mut address, chunk_end) = (chunk.address, chunk.address + chunk.len);
+        while address < chunk_end {
+            let end = (address / page_size + 1).saturating_mul(page_size).min(chunk_end);
+            let data = &mut buffer[..end - address];
+            let read = read_bytes_into(&process, address, data).unwrap_or(0);
+            data[read..].fill(0);
+            if address + read < end {
+                match holes.last_mut() {
+                    Some(last) if last.1 == address + read => last.1 = end,
+                    _ => holes.push((address + read, end)),
+                }
+            }
+            output.write_all(data)?;
+            address = end;
+        }
+    }
+    Ok(holes)
+}
+
+pub fn dump_to_file(process: impl ProcessMemory, start: usize, end: usize, path: &Path) -> Result<Vec<(usize, usize)>, Box<dyn std::error::Error>> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let holes = dump_range(process, start, end, &mut file)?;
+    file.flush()?;
+    Ok(holes)
+}
+
+// Writes every readable region to a capture at path, see capture.rs
+pub fn dump_all(process: impl ProcessMemory, path: &Path) -> Result<Vec<CapturedRegion>, Box<dyn std::error::Error>> {
+    let mapped = process.memory_regions()?;
+    let regions = mapped.iter().filter(|x| x.readable).cloned().collect::<Vec<MemoryRegion>>();
+    let header = CaptureHeader { uncaptured: uncaptured(&mapped, &regions), ..CaptureHeader::of(&process)? };
+    Capture::write(path, &header, Compression::None, regions, |address, mut block| {
+        let end = address + block.len();
+        dump_range(&process, address, end, &mut block)
+    })
+}
@@ -0,0 +1,334 @@
+// Just enough of a 64-bit little-endian ELF file to name the functions in a backtrace and to find
+// the call frame information for unwinding through them: its sections, its function symbols, and
+// where its image starts, which is what its base address once loaded corresponds to
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    // Addresses in the file, as its own headers have them rather than where it is loaded
+    pub address: usize,
+    pub size: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Section {
+    name: String,
+    kind: usize,
+    address: usize,
+    offset: usize,
+    size: usize,
+    // The section of names for a symbol table
+    link: usize,
+}
+
+const SHT_SYMTAB: usize = 2;
+const SHT_NOBITS: usize = 8;
+const SHT_DYNSYM: usize = 11;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+const STT_GNU_IFUNC: u8 = 10;
+
+#[derive(Debug)]
+pub struct ElfFile {
+    data: Vec<u8>,
+    sections: Vec<Section>,
+    // Functions and data by address, from .symtab, or from .dynsym when the file is stripped
+    symbols: Vec<Symbol>,
+    // The start of the first PT_LOAD segment, rounded down to its page
+    pub image_start: usize,
+}
+
+// A little-endian field, or None past the end of the bytes
+fn field(bytes: &[u8], offset: usize, len: usize) -> Option<usize> {
+    Some(bytes.get(offset..offset.checked_add(len)?)?.iter().rev().fold(0usize, |x, y| x << 8 | *y as usize))
+}
+
+// The NUL-terminated string at offset in a table of them
+fn string_at(table: &[u8], offset: usize) -> Option<String> {
+    let bytes = table.get(offset..)?;
+    Some(String::from_utf8_lossy(&bytes[..bytes.iter().position(|&x| x == 0)?]).into_owned())
+}
+
+impl ElfFile {
+    pub fn read(path: &str) -> Result<ElfFile, Box<dyn std::error::Error>> {
+        ElfFile::parse(std::fs::read(path).map_err(|e| format!("Could not read {}: {}", path, e))?)
+    }
+
+    pub fn parse(data: Vec<u8>) -> Result<ElfFile, Box<dyn std::error::Error>> {
+        if data.get(..4) != Some(b"\x7fELF") || data.get(4) != Some(&2) || data.get(5) != Some(&1) {
+            return Err("Not a 64-bit little-endian ELF file".into());
+        }
+        let header = |offset: usize, len: usize| field(&data, offset, len).ok_or("The ELF header is cut short");
+        let (segments, segment_size, segment_count) = (header(0x20, 8)?, header(0x36, 2)?, header(0x38, 2)?);
+        let (table, entry_size, count, names) = (header(0x28, 8)?, header(0x3a, 2)?, header(0x3c, 2)?, header(0x3e, 2)?);
+        let image_start = (0..segment_count).filter_map(|n| {
+            let at = segments + n * segment_size;
+            (field(&data, at, 4)? == 1).then(|| field(&data, at + 0x10, 8))?
+        }).min().unwrap_or(0) / 4096 * 4096;
+        // No section is cut short, so the rest can slice without checking
+        let mut sections = Vec::new();
+        let mut name_offsets = Vec::new();
+        for n in 0..count {
+            let at = table + n * entry_size;
+            let entry = |offset: usize, len: usize| field(&data, at + offset, len).ok_or("A section header is cut short");
+            let section = Section { name: String::new(), kind: entry(4, 4)?, address: entry(0x10, 8)?, offset: entry(0x18, 8)?, size: entry(0x20, 8)?, link: entry(0x28, 4)? };
+            if section.kind != SHT_NOBITS && section.offset.checked_add(section.size).is_none_or(|x| x > data.len()) {
+                return Err(format!("Section {} runs past the end of the file", n).into());
+            }
+            name_offsets.push(entry(0, 4)?);
+            sections.push(section);
+        }
+        if let Some(names) = sections.get(names).map(|x| data[x.offset..x.offset + x.size].to_vec()) {
+            for (section, offset) in sections.iter_mut().zip(name_offsets) {
+                section.name = string_at(&names, offset).unwrap_or_default();
+            }
+        }
+        let mut elf = ElfFile { data, sections, symbols: Vec::new(), image_start };
+        elf.symbols = elf.read_symbols(SHT_SYMTAB);
+        if elf.symbols.is_empty() {
+            elf.symbols = elf.read_symbols(SHT_DYNSYM);
+        }
+        Ok(elf)
+    }
+
+    // Defined functions and data from every symbol table of the kind, one per address. Data
+    // without a size is left out, being markers such as the start of .init_array rather than data
+    fn read_symbols(&self, kind: usize) -> Vec<Symbol> {
+        let mut symbols = Vec::new();
+        for table in self.sections.iter().filter(|x| x.kind == kind) {
+            let Some(names) = self.sections.get(table.link) else { continue };
+            let names = &self.data[names.offset..names.offset + names.size];
+            for entry in self.data[table.offset..table.offset + table.size].chunks_exact(24) {
+                let (kind, section, address, size) = (entry[4] & 0xf, field(entry, 6, 2).unwrap(), field(entry, 8, 8).unwrap(), field(entry, 16, 8).unwrap());
+                if (matches!(kind, STT_FUNC | STT_GNU_IFUNC) || kind == STT_OBJECT && size != 0) && section != 0 && address != 0 && let Some(name) = string_at(names, field(entry, 0, 4).unwrap()) {
+                    symbols.push(Symbol { name, address, size });
+                }
+            }
+        }
+        symbols.sort_by_key(|x| (x.address, std::cmp::Reverse(x.size)));
+        symbols.dedup_by_key(|x| x.address);
+        symbols
+    }
+
+    // A section's address and contents, if the file has it
+    pub fn section(&self, name: &str) -> Option<(usize, &[u8])> {
+        let section = self.sections.iter().find(|x| x.name == name && x.kind != SHT_NOBITS)?;
+        Some((section.address, &self.data[section.offset..section.offset + section.size]))
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    // The function or data an address in the file is in, with how far into it. One without a size is
+    // taken to reach as far as the next
+    pub fn symbol_at(&self, address: usize) -> Option<(&Symbol, usize)> {
+        let symbol = &self.symbols[self.symbols.partition_point(|x| x.address <= address).checked_sub(1)?];
+        if symbol.size != 0 && address >= symbol.address + symbol.size {
+            return None;
+        }
+        Some((symbol, address - symbol.address))
+    }
+}
+
+// What an ELF file's header says it runs on, read from the identification bytes and e_machine of
+// any ELF file, 32-bit or big-endian ones included. A known machine with the other class, such as
+// x86-64 code built for the x32 ABI, is kept as Other, as its pointers are not the machine's own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arch {
+    X86_64,
+    X86,
+    Aarch64,
+    Arm,
+    Riscv64,
+    Other { machine: u16, pointer_width: u8 },
+}
+
+const EM_386: u16 = 3;
+const EM_ARM: u16 = 40;
+const EM_X86_64: u16 = 62;
+const EM_AARCH64: u16 = 183;
+const EM_RISCV: u16 = 243;
+
+// Magic, class, data and e_machine, the last at 0x12 whatever the class
+pub const ELF_IDENT_LEN: usize = 0x14;
+
+impl Arch {
+    // Whatever this build of the crate runs on
+    pub fn native() -> Arch {
+        match std::env::consts::ARCH {
+            "x86_64" => Arch::X86_64,
+            "x86" => Arch::X86,
+            "aarch64" => Arch::Aarch64,
+            "arm" => Arch::Arm,
+            "riscv64" => Arch::Riscv64,
+            _ => Arch::Other { machine: 0, pointer_width: std::mem::size_of::<usize>() as u8 },
+        }
+    }
+
+    pub fn from_machine(machine: u16, pointer_width: u8) -> Arch {
+        match (machine, pointer_width) {
+            (EM_X86_64, 8) => Arch::X86_64,
+            (EM_386, 4) => Arch::X86,
+            (EM_AARCH64, 8) => Arch::Aarch64,
+            (EM_ARM, 4) => Arch::Arm,
+            (EM_RISCV, 8) => Arch::Riscv64,
+            (machine, pointer_width) => Arch::Other { machine, pointer_width },
+        }
+    }
+
+    // From the first ELF_IDENT_LEN bytes of a file or of its image in memory
+    pub fn from_elf_header(header: &[u8]) -> Result<Arch, Box<dyn std::error::Error>> {
+        if header.get(..4) != Some(b"\x7fELF") {
+            return Err("Not an ELF file".into());
+        }
+        let pointer_width = match header.get(4) {
+            Some(1) => 4,
+            Some(2) => 8,
+            _ => return Err("The ELF header has an unknown class".into()),
+        };
+        let machine = header.get(0x12..0x14).ok_or("The ELF header is cut short")?;
+        let machine = match header[5] {
+            2 => u16::from_be_bytes([machine[0], machine[1]]),
+            _ => u16::from_le_bytes([machine[0], machine[1]]),
+        };
+        Ok(Arch::from_machine(machine, pointer_width))
+    }
+
+    pub fn read(path: &str) -> Result<Arch, Box<dyn std::error::Error>> {
+        let mut header = [0u8; ELF_IDENT_LEN];
+        std::io::Read::read_exact(&mut std::fs::File::open(path).map_err(|e| format!("Could not read {}: {}", path, e))?, &mut header).map_err(|e| format!("Could not read the ELF header of {}: {}", path, e))?;
+        Arch::from_elf_header(&header)
+    }
+
+    // The 32-bit architecture beside a 64-bit one or the other way round, for a capture that only
+    // recorded its pointer width
+    pub fn with_pointer_width(self, pointer_width: u8) -> Arch {
+        match (self, pointer_width) {
+            (arch, width) if arch.pointer_width() == width as usize => arch,
+            (Arch::X86_64, 4) => Arch::X86,
+            (Arch::X86, 8) => Arch::X86_64,
+            (Arch::Aarch64, 4) => Arch::Arm,
+            (Arch::Arm, 8) => Arch::Aarch64,
+            (arch, pointer_width) => Arch::Other { machine: arch.machine(), pointer_width },
+        }
+    }
+
+    pub fn machine(self) -> u16 {
+        match self {
+            Arch::X86_64 => EM_X86_64,
+            Arch::X86 => EM_386,
+            Arch::Aarch64 => EM_AARCH64,
+            Arch::Arm => EM_ARM,
+            Arch::Riscv64 => EM_RISCV,
+            Arch::Other { machine, .. } => machine,
+        }
+    }
+
+    pub fn pointer_width(self) -> usize {
+        match self {
+            Arch::X86_64 | Arch::Aarch64 | Arch::Riscv64 => 8,
+            Arch::X86 | Arch::Arm => 4,
+            Arch::Other { pointer_width, .. } => pointer_width as usize,
+        }
+    }
+
+    // The disassembler, the register layout and the debug registers watchpoints are set with only
+    // know x86-64, so what needs them is refused on anything else rather than getting it wrong
+    pub fn require_x86_64(self, feature: &str) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Arch::X86_64 => Ok(()),
+            arch => Err(format!("{} only supports x86-64 targets, and this one is {}", feature, arch).into()),
+        }
+    }
+}
+
+impl std::fmt::Display for Arch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Arch::X86_64 => write!(f, "x86-64"),
+            Arch::X86 => write!(f, "x86"),
+            Arch::Aarch64 => write!(f, "aarch64"),
+            Arch::Arm => write!(f, "arm"),
+            Arch::Riscv64 => write!(f, "riscv64"),
+            Arch::Other { machine, pointer_width } => write!(f, "ELF machine {} ({}-bit)", machine, *pointer_width as usize * 8),
+        }
+    }
+}
+
+// Rust's legacy mangling: _ZN, then each path segment prefixed by its length, then E, with the last
+// segment a hash. The v0 mangling std is built with is only taken apart as far as plain paths go.
+// Anything else, including C++ names with their parameter types after the E, is given back as it is
+pub fn demangle(name: &str) -> String {
+    if let Some(rest) = name.strip_prefix("_R") {
+        return v0_path(rest).map(|x| x.0).unwrap_or(name.to_string());
+    }
+    let Some(mut rest) = name.strip_prefix("_ZN") else {
+        return name.to_string();
+    };
+    let mut segments = Vec::new();
+    while !rest.starts_with('E') {
+        let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        let Some(len) = rest[..digits].parse::<usize>().ok().filter(|&x| x <= rest.len() - digits) else {
+            return name.to_string();
+        };
+        let Some(segment) = rest.get(digits..digits + len) else {
+            return name.to_string();
+        };
+        segments.push(segment);
+        rest = &rest[digits + len..];
+    }
+    if rest != "E" || segments.is_empty() {
+        return name.to_string();
+    }
+    if segments.len() > 1 && segments.last().is_some_and(|x| x.len() == 17 && x.starts_with('h') && x[1..].bytes().all(|x| x.is_ascii_hexdigit())) {
+        segments.pop();
+    }
+    let escapes = [("$LT$", "<"), ("$GT$", ">"), ("$RF$", "&"), ("$BP$", "*"), ("$C$", ","), ("$SP$", "@"), ("$u20$", " "), ("$u27$", "'"), ("$u5b$", "["), ("$u5d$", "]"), ("$u7b$", "{"), ("$u7d$", "}"), ("$u7e$", "~"), ("$LP$", "("), ("$RP$", ")"), ("..", "::")];
+    segments.iter().map(|segment| {
+        let segment = segment.strip_prefix("_$").map(|x| format!("${}", x)).unwrap_or(segment.to_string());
+        escapes.iter().fold(segment, |x, (from, to)| x.replace(from, to))
+    }).collect::<Vec<String>>().join("::")
+}
+
+// A v0 path of crate roots and nested names, with what follows it. Generic arguments, impls and
+// back references, which need the types demangled too, are not, and give None
+fn v0_path(mangled: &str) -> Option<(String, &str)> {
+    let (tag, rest) = (mangled.as_bytes().first()?, &mangled[1..]);
+    match tag {
+        b'C' => {
+            let (name, rest) = v0_identifier(v0_disambiguator(rest))?;
+            Some((name.to_string(), rest))
+        }
+        b'N' => {
+            let namespace = *rest.as_bytes().first()?;
+            let (parent, rest) = v0_path(&rest[1..])?;
+            let (name, rest) = v0_identifier(v0_disambiguator(rest))?;
+            let name = match (name, namespace) {
+                ("", b'C') => "{{closure}}".to_string(),
+                ("", b'S') => "{{shim}}".to_string(),
+                (name, _) => name.to_string(),
+            };
+            Some((format!("{}::{}", parent, name), rest))
+        }
+        _ => None,
+    }
+}
+
+// s, then a base-62 number, then _
+fn v0_disambiguator(mangled: &str) -> &str {
+    match mangled.strip_prefix('s').and_then(|x| Some(&x[x.find('_')? + 1..])) {
+        Some(rest) => rest,
+        None => mangled,
+    }
+}
+
+// Its length in decimal, then a _ if the name starts with a digit or _, then the name. Punycode
+// names, which start with u, are not decoded
+fn v0_identifier(mangled: &str) -> Option<(&str, &str)> {
+    let digits = mangled.bytes().take_while(u8::is_ascii_digit).count();
+    let len = mangled[..digits].parse::<usize>().ok()?;
+    let rest = &mangled[digits..];
+    let rest = rest.strip_prefix('_').unwrap_or(rest);
+    Some((rest.get(..len)?, &rest[len..]))
+}
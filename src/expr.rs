@@ -0,0 +1,409 @@
+use crate::{scan::INITIAL_FLOAT_ULPS, value::{Endianness, Scalar, ValueType}, with_scan_type};
+
+// An expression in old, the value a result held when last read, working out what it should hold
+// now, as rescan expr "old * 2" does. Integer types work it out exactly in i128, dividing by
+// truncating towards zero, so "old / 2" of 7 is 3 and of -7 is -3; a result whose expression
+// overflows or divides by zero matches nothing. Floats work it out in f64
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Old,
+    Integer(i128),
+    Float(f64),
+    Negate(Box<Expression>),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+    // The value of a Derived's read with that index
+    Read(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl Expression {
+    // Parses it for values of type T, whose literals have to be whole numbers if T is an integer
+    pub fn parse<T: Scalar>(text: &str) -> Result<Expression, String> {
+        let mut parser = Parser { text, position: 0, float: T::default().to_i128().is_none(), name: T::NAME, reads: None };
+        parser.whole()
+    }
+
+    // None where it overflows or divides by zero
+    pub fn evaluate_integer(&self, old: i128) -> Option<i128> {
+        self.integer(old, &[])
+    }
+
+    pub fn evaluate_float(&self, old: f64) -> f64 {
+        self.float(old, &[])
+    }
+
+    fn integer(&self, old: i128, reads: &[i128]) -> Option<i128> {
+        match self {
+            Expression::Old => Some(old),
+            Expression::Integer(x) => Some(*x),
+            Expression::Float(x) => Some(*x as i128),
+            Expression::Read(index) => reads.get(*index).copied(),
+            Expression::Negate(x) => x.integer(old, reads)?.checked_neg(),
+            Expression::Binary(operator, a, b) => {
+                let (a, b) = (a.integer(old, reads)?, b.integer(old, reads)?);
+                match operator {
+                    Operator::Add => a.checked_add(b),
+                    Operator::Sub => a.checked_sub(b),
+                    Operator::Mul => a.checked_mul(b),
+                    Operator::Div => a.checked_div(b),
+                }
+            }
+        }
+    }
+
+    fn float(&self, old: f64, reads: &[f64]) -> f64 {
+        match self {
+            Expression::Old => old,
+            Expression::Integer(x) => *x as f64,
+            Expression::Float(x) => *x,
+            Expression::Read(index) => reads.get(*index).copied().unwrap_or(f64::NAN),
+            Expression::Negate(x) => -x.float(old, reads),
+            Expression::Binary(operator, a, b) => {
+                let (a, b) = (a.float(old, reads), b.float(old, reads));
+                match operator {
+                    Operator::Add => a + b,
+                    Operator::Sub => a - b,
+                    Operator::Mul => a * b,
+                    Operator::Div => a / b,
+                }
+            }
+        }
+    }
+
+    // Whether new is what the expression makes of old: exactly for integers, and for floats within
+    // a few units in the last place of it, as rescan initial compares them
+    pub fn matches<T: Scalar>(&self, old: T, new: T) -> bool {
+        if let (Some(old), Some(new)) = (old.to_i128(), new.to_i128()) {
+            return self.evaluate_integer(old) == Some(new);
+        }
+        let expected = self.evaluate_float(old.to_f64());
+        let tolerance = T::epsilon().to_f64() * INITIAL_FLOAT_ULPS;
+        new.to_f64() == expected || expected.is_finite() && (new.to_f64() - expected).abs() <= tolerance * expected.abs()
+    }
+}
+
+// A value worked out from typed reads of addresses rather than from old, as
+// watchexpr hp_pct "(read i32 @hp) * 100 / (read i32 @max_hp)" watches. It is worked out as
+// integers, as rescan expr does, if every read is of an integer and every literal is whole, and in
+// f64 otherwise. The addresses are kept as typed, for whatever reads them to resolve
+#[derive(Debug, Clone, PartialEq)]
+pub struct Derived {
+    pub text: String,
+    pub reads: Vec<DerivedRead>,
+    expression: Expression,
+    integer: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivedRead {
+    pub value_type: ValueType,
+    pub address: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DerivedValue {
+    Integer(i128),
+    Float(f64),
+}
+
+impl DerivedValue {
+    pub fn to_f64(self) -> f64 {
+        match self {
+            DerivedValue::Integer(x) => x as f64,
+            DerivedValue::Float(x) => x,
+        }
+    }
+
+    // As equality, but a NaN that stays NaN is the same
+    pub fn same(self, other: DerivedValue) -> bool {
+        match (self, other) {
+            (DerivedValue::Float(a), DerivedValue::Float(b)) => a.to_bits() == b.to_bits(),
+            _ => self == other,
+        }
+    }
+}
+
+impl std::fmt::Display for DerivedValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DerivedValue::Integer(x) => write!(f, "{}", x),
+            DerivedValue::Float(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+impl Derived {
+    pub fn parse(text: &str) -> Result<Derived, String> {
+        let mut parser = Parser { text, position: 0, float: true, name: "derived", reads: Some(Vec::new()) };
+        let expression = parser.whole()?;
+        let reads = parser.reads.unwrap_or_default();
+        if reads.is_empty() {
+            return Err(format!("'{}' reads nothing; read <type> <address> reads a value, e.g. read i32 @hp", text));
+        }
+        let integer = reads.iter().all(|x| !matches!(x.value_type, ValueType::F32 | ValueType::F64)) && !expression.has_float();
+        Ok(Derived { text: text.to_string(), reads, expression, integer })
+    }
+
+    // From the bytes read for each read, in its order. None if any is missing, or the integers
+    // overflow or divide by zero
+    pub fn evaluate(&self, bytes: &[Option<Vec<u8>>], endianness: Endianness) -> Option<DerivedValue> {
+        let values = self.reads.iter().zip(bytes).map(|(read, bytes)| numeric_value(read.value_type, bytes.as_deref()?, endianness).ok()).collect::<Option<Vec<(Option<i128>, f64)>>>()?;
+        if values.len() < self.reads.len() {
+            return None;
+        }
+        if self.integer {
+            let integers = values.iter().map(|x| x.0).collect::<Option<Vec<i128>>>()?;
+            return self.expression.integer(0, &integers).map(DerivedValue::Integer);
+        }
+        let floats = values.iter().map(|x| x.1).collect::<Vec<f64>>();
+        Some(DerivedValue::Float(self.expression.float(0.0, &floats)))
+    }
+}
+
+fn numeric_value(value_type: ValueType, bytes: &[u8], endianness: Endianness) -> Result<(Option<i128>, f64), Box<dyn std::error::Error>> {
+    with_scan_type!(value_type, T, {
+        if bytes.len() != T::SIZE {
+            return Err(format!("Expected {} bytes for {}, got {}", T::SIZE, T::NAME, bytes.len()).into());
+        }
+        let value = T::from_bytes_in(bytes, endianness);
+        Ok((value.to_i128(), value.to_f64()))
+    })
+}
+
+impl Expression {
+    fn has_float(&self) -> bool {
+        match self {
+            Expression::Float(_) => true,
+            Expression::Negate(x) => x.has_float(),
+            Expression::Binary(_, a, b) => a.has_float() || b.has_float(),
+            _ => false,
+        }
+    }
+}
+
+// A test of a derived value, as monitor start --when "hp_pct < 20" alerts on
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertCondition {
+    pub name: String,
+    pub comparison: Comparison,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+// Longest first, so <= is not taken as <
+const COMPARISONS: [(&str, Comparison); 6] = [("<=", Comparison::LessOrEqual), (">=", Comparison::GreaterOrEqual), ("==", Comparison::Equal), ("!=", Comparison::NotEqual), ("<", Comparison::Less), (">", Comparison::Greater)];
+
+impl AlertCondition {
+    // "<name> <comparison> <number>", the comparison one of < <= > >= == !=
+    pub fn parse(text: &str) -> Result<AlertCondition, String> {
+        let usage = || format!("Expected <name> <comparison> <number>, e.g. \"hp_pct < 20\", got '{}'", text);
+        let (position, symbol, comparison) = COMPARISONS.iter().filter_map(|(symbol, comparison)| Some((text.find(symbol)?, *symbol, *comparison))).min_by_key(|x| (x.0, std::cmp::Reverse(x.1.len()))).ok_or_else(usage)?;
+        let name = text[..position].trim();
+        let value = text[position + symbol.len()..].trim().parse::<f64>().map_err(|_| usage())?;
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(usage());
+        }
+        Ok(AlertCondition { name: name.to_string(), comparison, value })
+    }
+
+    pub fn holds(&self, value: DerivedValue) -> bool {
+        let value = value.to_f64();
+        match self.comparison {
+            Comparison::Less => value < self.value,
+            Comparison::LessOrEqual => value <= self.value,
+            Comparison::Greater => value > self.value,
+            Comparison::GreaterOrEqual => value >= self.value,
+            Comparison::Equal => value == self.value,
+            Comparison::NotEqual => value != self.value,
+        }
+    }
+}
+
+impl std::fmt::Display for AlertCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let symbol = COMPARISONS.iter().find(|x| x.1 == self.comparison).unwrap().0;
+        write!(f, "{} {} {}", self.name, symbol, self.value)
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+    float: bool,
+    name: &'static str,
+    // Those of a Derived so far, and None for a rescan's expression in old
+    reads: Option<Vec<DerivedRead>>,
+}
+
+impl Parser<'_> {
+    fn whole(&mut self) -> Result<Expression, String> {
+        let expression = self.sum()?;
+        self.skip_spaces();
+        if self.position < self.text.len() {
+            return Err(self.error("an operator"));
+        }
+        Ok(expression)
+    }
+
+    // The word next, after any spaces, up to a space or bracket
+    fn word(&mut self) -> &str {
+        self.skip_spaces();
+        let length = self.rest().find(|x: char| x.is_whitespace() || x == '(' || x == ')').unwrap_or(self.rest().len());
+        self.position += length;
+        &self.text[self.position - length..self.position]
+    }
+
+    // A word, or a bracketed address expression through its closing bracket, spaces and all
+    fn address(&mut self) -> &str {
+        self.skip_spaces();
+        if !self.rest().starts_with('[') {
+            return self.word();
+        }
+        let mut depth = 0;
+        let length = self.rest().char_indices().find(|(_, x)| {
+            depth += match x {
+                '[' => 1,
+                ']' => -1,
+                _ => 0,
+            };
+            depth == 0
+        }).map_or(self.rest().len(), |x| x.0 + 1);
+        self.position += length;
+        &self.text[self.position - length..self.position]
+    }
+
+    // After "read": a numeric type and an address
+    fn read(&mut self) -> Result<Expression, String> {
+        let text = self.text;
+        let value_type = self.word().to_string();
+        let value_type = match value_type.parse::<ValueType>() {
+            Ok(x) if x.is_numeric() && x.size().is_some() => x,
+            _ => return Err(format!("read needs a numeric type such as i32 or f32, rather than '{}' in '{}'", value_type, text)),
+        };
+        let address = self.address().to_string();
+        if address.is_empty() {
+            return Err(format!("read {} needs an address after it in '{}'", value_type, text));
+        }
+        let reads = self.reads.as_mut().unwrap();
+        reads.push(DerivedRead { value_type, address });
+        Ok(Expression::Read(reads.len() - 1))
+    }
+
+    // Whether the keyword is next, as a whole word
+    fn keyword(&self, keyword: &str) -> bool {
+        self.rest().strip_prefix(keyword).is_some_and(|x| !x.starts_with(|x: char| x.is_alphanumeric() || x == '_'))
+    }
+
+    fn skip_spaces(&mut self) {
+        self.position = self.text.len() - self.rest().trim_start().len();
+    }
+
+    fn rest(&self) -> &str {
+        &self.text[self.position..]
+    }
+
+    // Takes the character if it is next, after any spaces
+    fn take(&mut self, character: char) -> bool {
+        self.skip_spaces();
+        let taken = self.rest().starts_with(character);
+        if taken {
+            self.position += character.len_utf8();
+        }
+        taken
+    }
+
+    fn take_operator(&mut self, operators: &[(char, Operator)]) -> Option<Operator> {
+        operators.iter().find(|x| self.take(x.0)).map(|x| x.1)
+    }
+
+    fn error(&self, expected: &str) -> String {
+        match self.rest().chars().next() {
+            Some(found) => format!("Expected {} at '{}' in '{}'", expected, found, self.text),
+            None => format!("Expected {} at the end of '{}'", expected, self.text),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expression, String> {
+        let mut expression = self.product()?;
+        loop {
+            let Some(operator) = self.take_operator(&[('+', Operator::Add), ('-', Operator::Sub)]) else {
+                return Ok(expression);
+            };
+            expression = Expression::Binary(operator, Box::new(expression), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expression, String> {
+        let mut expression = self.unary()?;
+        loop {
+            let Some(operator) = self.take_operator(&[('*', Operator::Mul), ('/', Operator::Div)]) else {
+                return Ok(expression);
+            };
+            expression = Expression::Binary(operator, Box::new(expression), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expression, String> {
+        if self.take('-') {
+            return Ok(Expression::Negate(Box::new(self.unary()?)));
+        }
+        if self.take('(') {
+            let expression = self.sum()?;
+            if !self.take(')') {
+                return Err(self.error("')'"));
+            }
+            return Ok(expression);
+        }
+        self.skip_spaces();
+        if self.reads.is_some() && self.keyword("read") {
+            self.position += 4;
+            return self.read();
+        }
+        if self.keyword("old") {
+            if self.reads.is_some() {
+                return Err(format!("old is what rescan expr compares with, so means nothing in '{}'; read <type> <address> reads a value", self.text));
+            }
+            self.position += 3;
+            return Ok(Expression::Old);
+        }
+        self.literal()
+    }
+
+    fn literal(&mut self) -> Result<Expression, String> {
+        let length = self.rest().find(|x: char| !(x.is_ascii_alphanumeric() || x == '.' || x == '_')).unwrap_or(self.rest().len());
+        let literal = &self.rest()[..length];
+        let expected = if self.reads.is_some() { "read <type> <address>, a number or '('" } else { "old, a number or '('" };
+        if literal.is_empty() {
+            return Err(self.error(expected));
+        }
+        let hex = literal.strip_prefix("0x").or_else(|| literal.strip_prefix("0X"));
+        let integer = match hex {
+            Some(digits) => i128::from_str_radix(digits, 16).ok(),
+            None => literal.parse::<i128>().ok(),
+        };
+        let expression = match (integer, literal.parse::<f64>()) {
+            (Some(x), _) => Expression::Integer(x),
+            (None, Ok(x)) if self.float => Expression::Float(x),
+            (None, Ok(_)) => return Err(format!("{} is not a whole number, which an expression for {} values needs, as they are worked out as integers", literal, self.name)),
+            (None, Err(_)) => return Err(format!("Expected {} rather than '{}' in '{}'", expected, literal, self.text)),
+        };
+        self.position += length;
+        Ok(expression)
+    }
+}
@@ -0,0 +1,172 @@
+// What each function needs from its caller is in the comment above it, for C callers rather than
+// as rustdoc
+#![allow(clippy::missing_safety_doc)]
+use std::{panic::AssertUnwindSafe, sync::Mutex, time::Duration};
+use nix::{errno::Errno, unistd::Pid};
+use crate::{lock::LockManager, process::{Process, ProcessMemory, write_bytes_to_process}, scan::{ScanOptions, find_value}};
+
+// A C interface to the library, built into the cdylib with the ffi feature; include/rmh.h
+// declares it. Functions that can fail return one of the negative codes below, or for reads,
+// writes and scans a count that is never negative. A panic is caught before it reaches the
+// caller and returned as RMH_ERR_PANIC.
+// A handle may be used from several threads at once, except that rmh_detach must come after
+// every other call on it has returned, and nothing may use the handle afterwards
+
+pub const RMH_OK: i32 = 0;
+// A null handle or buffer, or a length that does not fit
+pub const RMH_ERR_INVALID: i32 = -1;
+// The process exists but its memory could not be opened, or the write landed on a read-only page
+pub const RMH_ERR_ACCESS: i32 = -2;
+pub const RMH_ERR_NO_PROCESS: i32 = -3;
+// Nothing is mapped at the address
+pub const RMH_ERR_BAD_ADDRESS: i32 = -4;
+// rmh_unlock on an address with no lock
+pub const RMH_ERR_NOT_LOCKED: i32 = -5;
+pub const RMH_ERR_PANIC: i32 = -6;
+pub const RMH_ERR_OTHER: i32 = -7;
+
+pub struct RmhHandle {
+    process: Process,
+    locks: Mutex<LockManager<Process>>,
+}
+
+fn error_code(error: &(dyn std::error::Error + 'static)) -> i32 {
+    match error.downcast_ref::<Errno>() {
+        Some(Errno::EPERM | Errno::EACCES) => RMH_ERR_ACCESS,
+        Some(Errno::ESRCH) => RMH_ERR_NO_PROCESS,
+        Some(Errno::EFAULT | Errno::EIO) => RMH_ERR_BAD_ADDRESS,
+        _ => RMH_ERR_OTHER,
+    }
+}
+
+// Runs f, turning a panic into the given result rather than unwinding into C
+fn guard<R>(panicked: R, f: impl FnOnce() -> R) -> R {
+    std::panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(panicked)
+}
+
+fn count(result: Result<usize, Box<dyn std::error::Error>>) -> isize {
+    match result {
+        Ok(count) => count as isize,
+        Err(e) => error_code(e.as_ref()) as isize,
+    }
+}
+
+// Attaches like Process::attach. Returns null on failure, with the reason in *error if error is
+// not null
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rmh_attach(pid: i32, error: *mut i32) -> *mut RmhHandle {
+    let (handle, code) = guard((std::ptr::null_mut(), RMH_ERR_PANIC), || {
+        let pid = Pid::from_raw(pid);
+        if pid.as_raw() <= 0 {
+            return (std::ptr::null_mut(), RMH_ERR_INVALID);
+        }
+        if unsafe { libc::kill(pid.as_raw(), 0) } == -1 && Errno::last() == Errno::ESRCH {
+            return (std::ptr::null_mut(), RMH_ERR_NO_PROCESS);
+        }
+        match Process::attach(pid) {
+            Ok(process) => (Box::into_raw(Box::new(RmhHandle { locks: Mutex::new(LockManager::new(process.clone())), process })), RMH_OK),
+            // Every backend failed on a process that exists
+            Err(_) => (std::ptr::null_mut(), RMH_ERR_ACCESS),
+        }
+    });
+    if !error.is_null() {
+        unsafe { *error = code };
+    }
+    handle
+}
+
+// Reads up to len bytes at address into buf, returning how many were read. Like read(2) this can
+// come up short, at the end of a mapping
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rmh_read(handle: *const RmhHandle, address: usize, buf: *mut u8, len: usize) -> isize {
+    guard(RMH_ERR_PANIC as isize, || {
+        let (Some(handle), Some(buffer)) = (unsafe { handle.as_ref() }, unsafe { slice_mut(buf, len) }) else {
+            return RMH_ERR_INVALID as isize;
+        };
+        count(handle.process.read_at(address, buffer))
+    })
+}
+
+// Writes len bytes from buf to address, returning how many were written
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rmh_write(handle: *const RmhHandle, address: usize, buf: *const u8, len: usize) -> isize {
+    guard(RMH_ERR_PANIC as isize, || {
+        let (Some(handle), Some(data)) = (unsafe { handle.as_ref() }, unsafe { slice(buf, len) }) else {
+            return RMH_ERR_INVALID as isize;
+        };
+        count(write_bytes_to_process(&handle.process, address, data))
+    })
+}
+
+// Scans the process's memory for value, writing the first cap addresses found to out_addrs and
+// returning how many there were in all, which may be more than cap. out_addrs may be null if cap
+// is 0, to count them alone
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rmh_scan_i32(handle: *const RmhHandle, value: i32, out_addrs: *mut usize, cap: usize) -> isize {
+    guard(RMH_ERR_PANIC as isize, || {
+        let (Some(handle), Some(out)) = (unsafe { handle.as_ref() }, unsafe { slice_mut(out_addrs, cap) }) else {
+            return RMH_ERR_INVALID as isize;
+        };
+        count(find_value(&handle.process, value, &ScanOptions::default()).map(|(found, _)| {
+            let copied = found.len().min(out.len());
+            out[..copied].copy_from_slice(&found[..copied]);
+            found.len()
+        }))
+    })
+}
+
+// Writes the len bytes at value to address every interval_ms until unlocked or detached,
+// replacing any lock already there
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rmh_lock(handle: *const RmhHandle, address: usize, value: *const u8, len: usize, interval_ms: u32) -> i32 {
+    guard(RMH_ERR_PANIC, || {
+        let (Some(handle), Some(bytes)) = (unsafe { handle.as_ref() }, unsafe { slice(value, len) }) else {
+            return RMH_ERR_INVALID;
+        };
+        if bytes.is_empty() || interval_ms == 0 {
+            return RMH_ERR_INVALID;
+        }
+        handle.locks.lock().unwrap().lock_bytes(bytes.to_vec(), address, Duration::from_millis(interval_ms as u64));
+        RMH_OK
+    })
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rmh_unlock(handle: *const RmhHandle, address: usize) -> i32 {
+    guard(RMH_ERR_PANIC, || {
+        let Some(handle) = (unsafe { handle.as_ref() }) else {
+            return RMH_ERR_INVALID;
+        };
+        match handle.locks.lock().unwrap().unlock_value(address) {
+            true => RMH_OK,
+            false => RMH_ERR_NOT_LOCKED,
+        }
+    })
+}
+
+// Stops the handle's locks and frees it. A null handle is ignored
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rmh_detach(handle: *mut RmhHandle) {
+    if !handle.is_null() {
+        guard((), || drop(unsafe { Box::from_raw(handle) }));
+    }
+}
+
+// A C buffer as a slice; null is only allowed with a length of 0
+unsafe fn slice<'a>(buf: *const u8, len: usize) -> Option<&'a [u8]> {
+    match (buf.is_null(), len) {
+        (true, 0) => Some(&[]),
+        (true, _) => None,
+        _ if len > isize::MAX as usize => None,
+        _ => Some(unsafe { std::slice::from_raw_parts(buf, len) }),
+    }
+}
+
+unsafe fn slice_mut<'a, T>(buf: *mut T, len: usize) -> Option<&'a mut [T]> {
+    match (buf.is_null(), len) {
+        (true, 0) => Some(&mut []),
+        (true, _) => None,
+        _ if len.checked_mul(size_of::<T>()).is_none_or(|x| x > isize::MAX as usize) => None,
+        _ => Some(unsafe { std::slice::from_raw_parts_mut(buf, len) }),
+    }
+}
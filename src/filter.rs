@@ -0,0 +1,295 @@
+use std::{collections::{HashMap, HashSet}, str::FromStr};
+use crate::{maps::{MemoryRegion, get_memory_regions, modules_from_regions}, platform::Pid, process::ProcessMemory};
+
+// Times a running thread's syscall file is read before giving up on its stack pointer
+const SYSCALL_ATTEMPTS: usize = 5;
+
+// Selects regions by what they hold rather than by address. Parsed from expressions like
+// "heap or stack", "module:libgame.so and writable" or "not (stack or module:libc.so.6)"
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegionFilter {
+    // [heap], plus anonymous read-write mappings contiguous with it
+    Heap,
+    // [stack] and the stacks of other threads
+    Stack,
+    // Any mapping whose pathname's basename is the name
+    Module(String),
+    Writable,
+    // Any mapping overlapping the addresses from start up to end, as in "0x7f1000-0x7f3000"
+    Range(usize, usize),
+    And(Box<RegionFilter>, Box<RegionFilter>),
+    Or(Box<RegionFilter>, Box<RegionFilter>),
+    Not(Box<RegionFilter>),
+}
+
+// What kind of memory a region holds. Every region is in exactly one, taken in this order, so the
+// .bss of the main executable counts as main executable rather than anonymous
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RegionCategory {
+    MainExecutable,
+    Heap,
+    Stack,
+    FileBacked,
+    // Everything else, including special mappings like [vdso]
+    Anonymous,
+}
+
+impl std::fmt::Display for RegionCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegionCategory::MainExecutable => write!(f, "main executable"),
+            RegionCategory::Heap => write!(f, "heap"),
+            RegionCategory::Stack => write!(f, "stack"),
+            RegionCategory::FileBacked => write!(f, "file-backed"),
+            RegionCategory::Anonymous => write!(f, "anonymous"),
+        }
+    }
+}
+
+// The category of each region, in the same order
+pub fn classify_regions(process: &impl ProcessMemory, regions: &[MemoryRegion]) -> Vec<RegionCategory> {
+    let heap = heap_regions(regions);
+    let stacks = stack_regions(process.pid(), regions, &process.thread_stacks());
+    let executable = process.executable_path();
+    let main = modules_from_regions(regions).into_iter().find(|x| Some(&x.path) == executable.as_ref());
+    regions.iter().enumerate().map(|(index, region)| {
+        if main.as_ref().is_some_and(|x| x.contains(region.start)) {
+            RegionCategory::MainExecutable
+        }
+        else if heap.contains(&index) {
+            RegionCategory::Heap
+        }
+        else if stacks.contains_key(&index) {
+            RegionCategory::Stack
+        }
+        else if region.pathname.starts_with('/') {
+            RegionCategory::FileBacked
+        }
+        else {
+            RegionCategory::Anonymous
+        }
+    }).collect()
+}
+
+// What the filters need to know about the whole layout rather than a single region
+struct FilterContext {
+    heap: HashSet<usize>,
+    stacks: HashMap<usize, Option<Pid>>,
+}
+
+impl RegionFilter {
+    // The regions the filter selects, in their original order
+    pub fn select<'a>(&self, process: &impl ProcessMemory, regions: &'a [MemoryRegion]) -> Vec<&'a MemoryRegion> {
+        let context = FilterContext { heap: heap_regions(regions), stacks: stack_regions(process.pid(), regions, &process.thread_stacks()) };
+        regions.iter().enumerate().filter(|(index, region)| self.matches(*index, region, &context)).map(|x| x.1).collect()
+    }
+
+    fn matches(&self, index: usize, region: &MemoryRegion, context: &FilterContext) -> bool {
+        match self {
+            RegionFilter::Heap => context.heap.contains(&index),
+            RegionFilter::Stack => context.stacks.contains_key(&index),
+            RegionFilter::Module(name) => !region.pathname.is_empty() && region.pathname.rsplit('/').next() == Some(name.as_str()),
+            RegionFilter::Writable => region.writable,
+            RegionFilter::Range(start, end) => region.start < *end && *start < region.end,
+            RegionFilter::And(a, b) => a.matches(index, region, context) && b.matches(index, region, context),
+            RegionFilter::Or(a, b) => a.matches(index, region, context) || b.matches(index, region, context),
+            RegionFilter::Not(a) => !a.matches(index, region, context),
+        }
+    }
+}
+
+// Whether the address is in one of the regions, which must be in address order as select returns
+// them, so that a filter over a million results looks each one up rather than going through them all
+pub fn within_regions(regions: &[&MemoryRegion], address: usize) -> bool {
+    let index = regions.partition_point(|x| x.end <= address);
+    regions.get(index).is_some_and(|x| x.contains(address))
+}
+
+fn is_anonymous_rw(region: &MemoryRegion) -> bool {
+    region.pathname.is_empty() && region.readable && region.writable
+}
+
+// Indices of [heap] and of the anonymous read-write mappings chained to either end of it, which is
+// where large malloc arenas usually end up
+pub(crate) fn heap_regions(regions: &[MemoryRegion]) -> HashSet<usize> {
+    let mut heap = HashSet::new();
+    for (index, region) in regions.iter().enumerate().filter(|x| x.1.pathname == "[heap]") {
+        heap.insert(index);
+        let mut end = region.end;
+        for (next, x) in regions.iter().enumerate().skip(index + 1) {
+            if x.start != end || !is_anonymous_rw(x) {
+                break;
+            }
+            heap.insert(next);
+            end = x.end;
+        }
+        let mut start = region.start;
+        for (previous, x) in regions.iter().enumerate().take(index).rev() {
+            if x.end != start || !is_anonymous_rw(x) {
+                break;
+            }
+            heap.insert(previous);
+            start = x.start;
+        }
+    }
+    heap
+}
+
+// Indices of [stack] (or [stack:<tid>] on old kernels) and of the mappings holding any of the
+// thread stacks, each with the thread it belongs to where that is known. Where nothing identifies
+// another thread's stack, an anonymous read-write mapping directly above an inaccessible guard
+// mapping is taken to be one, which is how pthreads lays them out
+pub(crate) fn stack_regions(pid: Pid, regions: &[MemoryRegion], thread_stacks: &[(Pid, usize)]) -> HashMap<usize, Option<Pid>> {
+    let mut stacks: HashMap<usize, Option<Pid>> = regions.iter().enumerate().filter_map(|(index, region)| {
+        let tid = region.pathname.strip_prefix("[stack")?.strip_suffix(']')?;
+        Some((index, tid.strip_prefix(':').and_then(|x| x.parse().ok()).map(Pid::from_raw).or(Some(pid))))
+    }).collect();
+    let main_stack = regions.iter().position(|x| x.pathname == "[stack]");
+    let mut found_threads = false;
+    for (tid, address) in thread_stacks {
+        // Kernels that do not label each thread's own stack show every thread the main [stack]
+        if let Some(index) = regions.iter().position(|x| x.contains(*address)) && (Some(index) != main_stack || *tid == pid) {
+            found_threads |= Some(index) != main_stack;
+            stacks.insert(index, Some(*tid));
+        }
+    }
+    if !found_threads {
+        for (index, pair) in regions.windows(2).enumerate() {
+            let (guard, stack) = (&pair[0], &pair[1]);
+            if guard.end == stack.start && guard.pathname.is_empty() && !guard.readable && !guard.writable && is_anonymous_rw(stack) {
+                stacks.entry(index + 1).or_insert(None);
+            }
+        }
+    }
+    stacks
+}
+
+// The mapping holding each thread's stack that could be found, by tid. A thread that was running
+// on every look, and so never showed its stack pointer, is missing, as are the stacks only the
+// guard mapping heuristic found, which belong to no known thread
+pub fn thread_stack_regions(pid: Pid) -> Vec<(Pid, MemoryRegion)> {
+    let Ok(regions) = get_memory_regions(pid) else {
+        return Vec::new();
+    };
+    let mut stacks = stack_regions(pid, &regions, &thread_stacks(pid)).into_iter().filter_map(|(index, tid)| Some((tid?, regions[index].clone()))).collect::<Vec<(Pid, MemoryRegion)>>();
+    stacks.sort_by_key(|x| (x.0, x.1.start));
+    stacks
+}
+
+// Each thread with an address inside its stack, from /proc/<pid>/task/<tid>: the start of the
+// mapping the thread's own maps file labels [stack], which some kernels do, the stack pointer
+// from syscall (its second to last field) for threads blocked in a system call, and kstkesp from
+// stat, which kernels since 4.9 leave at 0 for anything but a thread dumping core
+pub fn thread_stacks(pid: Pid) -> Vec<(Pid, usize)> {
+    let Ok(tasks) = std::fs::read_dir(format!("/proc/{}/task", pid)) else {
+        return Vec::new();
+    };
+    let mut stacks = Vec::new();
+    for task in tasks.filter_map(|x| x.ok()) {
+        let Some(tid) = task.file_name().to_str().and_then(|x| x.parse().ok()).map(Pid::from_raw) else {
+            continue;
+        };
+        let labelled = std::fs::read_to_string(task.path().join("maps")).ok().and_then(|maps| {
+            let line = maps.lines().find(|x| x.ends_with("[stack]"))?;
+            usize::from_str_radix(line.split('-').next()?, 16).ok()
+        });
+        // A thread caught running has no stack pointer to show, so it is looked at again a few times
+        // in case it was only briefly awake
+        let read_pointer = || std::fs::read_to_string(task.path().join("syscall")).ok().and_then(|syscall| {
+            let fields = syscall.split_whitespace().collect::<Vec<&str>>();
+            let pointer = fields.len().checked_sub(2).map(|x| fields[x])?;
+            usize::from_str_radix(pointer.trim_start_matches("0x"), 16).ok()
+        });
+        let pointer = (0..SYSCALL_ATTEMPTS).find_map(|attempt| {
+            if attempt > 0 {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+            read_pointer()
+        });
+        let saved = std::fs::read_to_string(task.path().join("stat")).ok().and_then(|stat| {
+            stat.rsplit_once(')')?.1.split_whitespace().nth(26)?.parse::<usize>().ok().filter(|x| *x != 0)
+        });
+        stacks.extend(labelled.into_iter().chain(pointer).chain(saved).map(|x| (tid, x)));
+    }
+    stacks
+}
+
+impl std::fmt::Display for RegionFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegionFilter::Heap => write!(f, "heap"),
+            RegionFilter::Stack => write!(f, "stack"),
+            RegionFilter::Module(name) => write!(f, "module:{}", name),
+            RegionFilter::Writable => write!(f, "writable"),
+            RegionFilter::Range(start, end) => write!(f, "0x{:x}-0x{:x}", start, end),
+            RegionFilter::And(a, b) => write!(f, "({} and {})", a, b),
+            RegionFilter::Or(a, b) => write!(f, "({} or {})", a, b),
+            RegionFilter::Not(a) => write!(f, "not {}", a),
+        }
+    }
+}
+
+impl FromStr for RegionFilter {
+    type Err = String;
+
+    // Precedence from loosest to tightest is or, and, not; parentheses group
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let spaced = s.replace('(', " ( ").replace(')', " ) ");
+        let tokens = spaced.split_whitespace().collect::<Vec<&str>>();
+        let mut position = 0;
+        let filter = parse_or(&tokens, &mut position)?;
+        match tokens.get(position) {
+            None => Ok(filter),
+            Some(token) => Err(format!("Unexpected '{}' in region filter", token)),
+        }
+    }
+}
+
+fn parse_or(tokens: &[&str], position: &mut usize) -> Result<RegionFilter, String> {
+    let mut filter = parse_and(tokens, position)?;
+    while tokens.get(*position) == Some(&"or") {
+        *position += 1;
+        filter = RegionFilter::Or(Box::new(filter), Box::new(parse_and(tokens, position)?));
+    }
+    Ok(filter)
+}
+
+fn parse_and(tokens: &[&str], position: &mut usize) -> Result<RegionFilter, String> {
+    let mut filter = parse_not(tokens, position)?;
+    while tokens.get(*position) == Some(&"and") {
+        *position += 1;
+        filter = RegionFilter::And(Box::new(filter), Box::new(parse_not(tokens, position)?));
+    }
+    Ok(filter)
+}
+
+fn parse_not(tokens: &[&str], position: &mut usize) -> Result<RegionFilter, String> {
+    let token = *tokens.get(*position).ok_or("Expected a region filter, e.g. heap, stack or module:<name>")?;
+    *position += 1;
+    match token {
+        "not" => Ok(RegionFilter::Not(Box::new(parse_not(tokens, position)?))),
+        "(" => {
+            let filter = parse_or(tokens, position)?;
+            if tokens.get(*position) != Some(&")") {
+                return Err("Expected ')' in region filter".to_string());
+            }
+            *position += 1;
+            Ok(filter)
+        }
+        "heap" => Ok(RegionFilter::Heap),
+        "stack" => Ok(RegionFilter::Stack),
+        "writable" => Ok(RegionFilter::Writable),
+        _ => match (token.strip_prefix("module:"), parse_range(token)) {
+            (Some(name), _) if !name.is_empty() => Ok(RegionFilter::Module(name.to_string())),
+            (_, Some((start, end))) if start < end => Ok(RegionFilter::Range(start, end)),
+            _ => Err(format!("Unknown region filter '{}', expected heap, stack, writable, module:<name> or <start>-<end>", token)),
+        },
+    }
+}
+
+// Hex addresses as maps shows them, with or without 0x
+fn parse_range(token: &str) -> Option<(usize, usize)> {
+    let (start, end) = token.split_once('-')?;
+    let parse = |x: &str| usize::from_str_radix(x.trim_start_matches("0x"), 16).ok();
+    Some((parse(start)?, parse(end)?))
+}
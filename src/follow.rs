@@ -0,0 +1,163 @@
+use std::{collections::BTreeSet, path::Path};
+use nix::unistd::Pid;
+use crate::maps::{Module, executable_path, modules};
+
+// Telling when the attached process has exited or exec'd, and which process looks like it took
+// its place, for following a game that restarts itself or is started through a launcher that
+// forks it. A process is taken to be the same program if its executable has the same basename
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessIdentity {
+    pub pid: Pid,
+    pub parent: Pid,
+    // The basename of its executable
+    pub name: String,
+    pub path: String,
+    // In clock ticks since boot, which tells a reused pid from the process that had it before
+    pub start_time: u64,
+}
+
+// The state, parent and start time fields of /proc/<pid>/stat, after the name, which can hold
+// anything, spaces and parentheses included
+fn stat(pid: i32) -> Option<(char, i32, u64)> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    let fields = stat.rsplit_once(')')?.1.split_whitespace().collect::<Vec<&str>>();
+    Some((fields.first()?.chars().next()?, fields.get(1)?.parse().ok()?, fields.get(19)?.parse().ok()?))
+}
+
+impl ProcessIdentity {
+    // Fails for a process that has exited, zombies included, or whose executable cannot be read
+    pub fn of(pid: Pid) -> Result<ProcessIdentity, Box<dyn std::error::Error>> {
+        let (state, parent, start_time) = stat(pid.as_raw()).ok_or(format!("Process {} does not exist", pid))?;
+        if matches!(state, 'Z' | 'X') {
+            return Err(format!("Process {} has exited", pid).into());
+        }
+        let path = executable_path(pid)?;
+        let name = Path::new(&path).file_name().map(|x| x.to_string_lossy().into_owned()).unwrap_or_else(|| path.clone());
+        Ok(ProcessIdentity { pid, parent: Pid::from_raw(parent), name, path, start_time })
+    }
+}
+
+impl std::fmt::Display for ProcessIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.pid, self.name)
+    }
+}
+
+// Every process this user can see the executable of with the name
+fn processes_named(name: &str) -> Vec<ProcessIdentity> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries.filter_map(|x| x.ok()?.file_name().to_str()?.parse::<i32>().ok()).filter_map(|pid| ProcessIdentity::of(Pid::from_raw(pid)).ok()).filter(|x| x.name == name).collect()
+}
+
+// Once a process exits its children are handed to init or a subreaper, so only those of a
+// process still running are known to be its descendants
+fn is_descendant(pid: Pid, ancestor: Pid) -> bool {
+    let mut pid = pid.as_raw();
+    // Bounded, in case a pid is reused into a loop while this walks
+    for _ in 0..64 {
+        match stat(pid) {
+            Some((_, parent, _)) if parent == ancestor.as_raw() => return true,
+            Some((_, parent, _)) if parent > 1 => pid = parent,
+            _ => return false,
+        }
+    }
+    false
+}
+
+// An exec maps the new executable afresh, so its base moves unless ASLR is off and it is the same
+// file, which then cannot be told from no exec at all
+fn image_base(modules: &[Module], path: &str) -> Option<usize> {
+    modules.iter().find(|x| x.path == path).map(|x| x.base)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TargetChange {
+    Exited,
+    // The same pid running a new image, of the same executable or not
+    Execed(ProcessIdentity),
+    // A process of the same name started after the target, and whether the target, still
+    // running, is its parent or further up its ancestry
+    Appeared { process: ProcessIdentity, descendant: bool },
+}
+
+pub struct TargetMonitor {
+    target: ProcessIdentity,
+    // As of the last poll that found the target unchanged, for resolving what was found in it
+    modules: Vec<Module>,
+    changed: bool,
+    // What it exec'd into, once it has
+    execed: Option<ProcessIdentity>,
+    // Those already reported, or running when it started, so each is only reported once
+    seen: BTreeSet<i32>,
+}
+
+impl TargetMonitor {
+    pub fn new(pid: Pid) -> Result<TargetMonitor, Box<dyn std::error::Error>> {
+        let target = ProcessIdentity::of(pid)?;
+        let modules = modules(pid)?;
+        let seen = processes_named(&target.name).into_iter().map(|x| x.pid.as_raw()).collect();
+        Ok(TargetMonitor { target, modules, changed: false, execed: None, seen })
+    }
+
+    pub fn target(&self) -> &ProcessIdentity {
+        &self.target
+    }
+
+    pub fn modules(&self) -> &[Module] {
+        &self.modules
+    }
+
+    // Whether it has exited or exec'd, after which nothing more is learned about it
+    pub fn changed(&self) -> bool {
+        self.changed
+    }
+
+    // What happened since the last poll, an exit or exec only once
+    pub fn poll(&mut self) -> Vec<TargetChange> {
+        let mut changes = Vec::new();
+        if !self.changed {
+            let now = ProcessIdentity::of(self.target.pid).ok().filter(|x| x.start_time == self.target.start_time);
+            match now.map(|x| (modules(x.pid), x)) {
+                None => {
+                    self.changed = true;
+                    changes.push(TargetChange::Exited);
+                }
+                // Partway through an exec its maps can already be the new image's, or empty, while
+                // its exe still names the old one, so it is left to the next poll
+                Some((Ok(modules), now)) if image_base(&modules, &now.path).is_none() => {}
+                // Nothing to go by when it was still being mapped in as the monitor started
+                Some((Ok(modules), now)) if now.path != self.target.path || image_base(&self.modules, &self.target.path).is_some_and(|x| image_base(&modules, &now.path) != Some(x)) => {
+                    self.changed = true;
+                    self.execed = Some(now.clone());
+                    changes.push(TargetChange::Execed(now));
+                }
+                Some((Ok(modules), _)) => self.modules = modules,
+                // Its maps go just before it does, so this is left to the next poll
+                Some((Err(_), _)) => {}
+            }
+        }
+        for process in self.candidates() {
+            if self.seen.insert(process.pid.as_raw()) {
+                let descendant = !self.changed && is_descendant(process.pid, self.target.pid);
+                changes.push(TargetChange::Appeared { process, descendant });
+            }
+        }
+        changes
+    }
+
+    // Where to follow the target to: itself once it has exec'd, and otherwise the first candidate
+    pub fn successor(&self) -> Option<ProcessIdentity> {
+        self.execed.clone().or_else(|| self.candidates().into_iter().next())
+    }
+
+    // The processes that may have taken the target's place, those of the same name started no
+    // earlier than it, its descendants first and then the newest
+    pub fn candidates(&self) -> Vec<ProcessIdentity> {
+        let mut candidates = processes_named(&self.target.name).into_iter().filter(|x| x.pid != self.target.pid && x.start_time >= self.target.start_time).map(|x| (!self.changed && is_descendant(x.pid, self.target.pid), x)).collect::<Vec<(bool, ProcessIdentity)>>();
+        candidates.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.start_time.cmp(&a.1.start_time)).then(b.1.pid.cmp(&a.1.pid)));
+        candidates.into_iter().map(|x| x.1).collect()
+    }
+}
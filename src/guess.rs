@@ -0,0 +1,108 @@
+use crate::{maps::MemoryRegion, value::{Endianness, Scalar, TypedValue, ValueType}};
+
+// What the bytes at an address could be
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reading {
+    Value(TypedValue),
+    // Mapped when a readable region holds the address it points to
+    Pointer { target: usize, mapped: bool },
+    // The printable ASCII the bytes start with, up to a NUL or the end of what was read
+    Text(String),
+}
+
+// A reading with how sensible it looks, from 0 for something no program would store to 10, and why
+// it looks unlikely where that is worth saying
+#[derive(Debug, Clone, PartialEq)]
+pub struct Guess {
+    pub reading: Reading,
+    pub score: u8,
+    pub note: Option<&'static str>,
+}
+
+// Every numeric type, a pointer and a string read from the start of the bytes, the most sensible
+// first. Small integers, floats of an everyday size, pointers into mapped memory and runs of text
+// score higher than huge integers, NaNs, denormals and pointers to nothing
+pub fn guess_types(bytes: &[u8], regions: &[MemoryRegion], endianness: Endianness) -> Vec<Guess> {
+    let mut guesses = Vec::new();
+    for value_type in ValueType::NUMERIC {
+        let size = value_type.size().unwrap_or(0);
+        let Some(value) = bytes.get(..size).and_then(|x| value_type.value_from_bytes_in(x, endianness).ok()) else {
+            continue;
+        };
+        let (score, note) = match value {
+            TypedValue::F32(x) => score_float(x as f64, x.is_normal()),
+            TypedValue::F64(x) => score_float(x, x.is_normal()),
+            _ => score_integer(&value, &bytes[size..]),
+        };
+        // 32 bits being what most values in a game are kept in
+        let score = if size == 4 && note.is_none() { score + 1 } else { score };
+        guesses.push(Guess { reading: Reading::Value(value), score, note });
+    }
+    if let Some(pointer) = bytes.get(..usize::SIZE) {
+        let target = if endianness.is_native() { usize::from_bytes(pointer) } else { usize::from_bytes_in(pointer, endianness) };
+        let mapped = regions.iter().any(|x| x.readable && x.contains(target));
+        let (score, note) = match (target, mapped) {
+            (0, _) => (2, Some("null")),
+            (_, true) => (9, None),
+            (_, false) => (0, Some("points to nothing mapped")),
+        };
+        guesses.push(Guess { reading: Reading::Pointer { target, mapped }, score, note });
+    }
+    let text = bytes.iter().take_while(|x| x.is_ascii_graphic() || **x == b' ').map(|x| *x as char).collect::<String>();
+    // Text that stops at something other than a NUL is more likely bytes that happen to be printable
+    let terminated = matches!(bytes.get(text.len()), Some(0) | None);
+    let score = match text.len() {
+        0 => 0,
+        length if length >= 8 && terminated => 9,
+        length if length >= 4 && terminated => 7,
+        length if length >= 4 => 4,
+        _ => 1,
+    };
+    guesses.push(Guess { reading: Reading::Text(text), score, note: (score <= 1).then_some("too short to be text") });
+    guesses.sort_by_key(|x| std::cmp::Reverse(x.score));
+    guesses
+}
+
+// By how far the value is from 0. An 8- or 16-bit value followed by bytes that are not zero (or all
+// 0xff for a negative one) is more likely the low bytes of something wider
+fn score_integer(value: &TypedValue, after: &[u8]) -> (u8, Option<&'static str>) {
+    let (magnitude, negative) = match *value {
+        TypedValue::I8(x) => ((x as i64).unsigned_abs(), x < 0),
+        TypedValue::I16(x) => ((x as i64).unsigned_abs(), x < 0),
+        TypedValue::I32(x) => ((x as i64).unsigned_abs(), x < 0),
+        TypedValue::I64(x) => (x.unsigned_abs(), x < 0),
+        TypedValue::U8(x) => (x as u64, false),
+        TypedValue::U16(x) => (x as u64, false),
+        TypedValue::U32(x) => (x as u64, false),
+        TypedValue::U64(x) => (x, false),
+        _ => (u64::MAX, false),
+    };
+    let (score, note) = match magnitude {
+        0 => (3, Some("0 reads the same as every type")),
+        1..=1000 => (8, None),
+        1001..=1_000_000 => (6, None),
+        1_000_001..=0xffff_ffff => (3, None),
+        _ => (1, Some("too large to be likely")),
+    };
+    let padding = if negative { 0xff } else { 0 };
+    let size = value.to_bytes().len();
+    if after.iter().take(4usize.saturating_sub(size)).all(|x| *x == padding) {
+        (score, note)
+    }
+    else {
+        (score.saturating_sub(3), Some("the bytes after it are not zero"))
+    }
+}
+
+fn score_float(value: f64, normal: bool) -> (u8, Option<&'static str>) {
+    match value {
+        x if x.is_nan() => (0, Some("NaN, unlikely")),
+        x if x.is_infinite() => (0, Some("infinite, unlikely")),
+        0.0 => (3, Some("0 reads the same as every type")),
+        _ if !normal => (0, Some("denormal, unlikely")),
+        // Values like 1.5 or 100.25 that a person would have typed are likelier than 1.4013e-3
+        x if (1e-3..=1e7).contains(&x.abs()) && (x * 100.0).fract() == 0.0 => (9, None),
+        x if (1e-3..=1e7).contains(&x.abs()) => (6, None),
+        _ => (1, Some("too large or small to be likely")),
+    }
+}
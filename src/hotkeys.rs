@@ -0,0 +1,54 @@
+use std::sync::Arc;
+use evdev::{Device, EventSummary, KeyCode};
+
+// Global key presses read straight from /dev/input, so they arrive whichever window has focus.
+// Reading those devices normally needs root or membership of the input group
+
+// Key names are evdev's without the KEY_ prefix, e.g. "F7" or "KP1"
+pub fn is_key(name: &str) -> bool {
+    format!("KEY_{}", name.to_ascii_uppercase()).parse::<KeyCode>().is_ok()
+}
+
+// Starts a thread per keyboard calling on_press with the name of each key pressed, returning how
+// many keyboards are being listened to. Fails if no keyboard could be opened
+pub fn listen(on_press: impl Fn(&str) + Send + Sync + 'static) -> Result<usize, Box<dyn std::error::Error>> {
+    let on_press = Arc::new(on_press);
+    let mut keyboards = 0;
+    let mut last_error: Option<String> = None;
+    for entry in std::fs::read_dir("/dev/input").map_err(|e| format!("Could not read /dev/input: {}", e))? {
+        let path = entry?.path();
+        if !path.file_name().and_then(|x| x.to_str()).is_some_and(|x| x.starts_with("event")) {
+            continue;
+        }
+        let mut device = match Device::open(&path) {
+            Ok(device) => device,
+            Err(e) => {
+                last_error = Some(format!("{}: {}", path.display(), e));
+                continue;
+            }
+        };
+        if !device.supported_keys().is_some_and(|x| x.contains(KeyCode::KEY_ENTER)) {
+            continue;
+        }
+        keyboards += 1;
+        let on_press = on_press.clone();
+        std::thread::spawn(move || {
+            while let Ok(events) = device.fetch_events() {
+                for event in events {
+                    // A value of 1 is a press, as opposed to a release (0) or autorepeat (2)
+                    if let EventSummary::Key(_, code, 1) = event.destructure() {
+                        let name = format!("{:?}", code);
+                        on_press(name.strip_prefix("KEY_").unwrap_or(&name));
+                    }
+                }
+            }
+        });
+    }
+    if keyboards == 0 {
+        return Err(match last_error {
+            Some(e) => format!("Could not open any keyboard ({}); reading /dev/input usually needs the input group", e),
+            None => "Found no keyboards under /dev/input".to_string(),
+        }.into());
+    }
+    Ok(keyboards)
+}
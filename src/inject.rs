@@ -0,0 +1,296 @@
+use nix::{errno::Errno, sys::{ptrace, signal::Signal, wait::{WaitPidFlag, WaitStatus, waitpid}}, unistd::Pid};
+use crate::{maps::{MemoryRegion, get_memory_regions}, process::{MemBackend, Process, read_bytes_from_process}, resident::page_size, tracer::{PtraceSession, Resume, Traced}};
+
+// Making the target make syscalls of ours: mprotect, for pages the kernel will not let
+// process_vm_writev write to, and mmap and munmap, for memory of its own to put code or data in.
+// Its main thread is seized and interrupted, its registers saved, and it is sent to a
+// syscall instruction already somewhere in its code with the arguments set up, stepped over it,
+// and put back as it was. Nothing is written to its code, since other threads may be running it.
+// Everything here is x86-64 and can still crash the target, e.g. if a signal handler it relies on
+// runs at the wrong moment, so the session only allows it with --dangerous
+
+// What the target makes writable is set back to this, region by region
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Protection {
+    pub start: usize,
+    pub end: usize,
+    // PROT_READ, PROT_WRITE and PROT_EXEC
+    pub prot: i32,
+}
+
+// A range made writable, with how each region of it was protected before. Nothing is restored when
+// the handle is dropped unless restore_on_drop is set
+#[derive(Debug)]
+pub struct Reprotection {
+    pub pid: Pid,
+    pub start: usize,
+    pub end: usize,
+    pub original: Vec<Protection>,
+    pub restore_on_drop: bool,
+    restored: bool,
+}
+
+impl Reprotection {
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn is_restored(&self) -> bool {
+        self.restored
+    }
+
+    // Has the target call mprotect again with the protection each region had
+    pub fn restore(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.restored {
+            return Ok(());
+        }
+        inject_mprotect(self.pid, &self.original)?;
+        self.restored = true;
+        Ok(())
+    }
+}
+
+impl Drop for Reprotection {
+    fn drop(&mut self) {
+        if self.restore_on_drop {
+            let _ = self.restore();
+        }
+    }
+}
+
+// A syscall number with its six arguments, in the order of rdi, rsi, rdx, r10, r8 and r9
+#[derive(Clone)]
+struct Syscall {
+    number: libc::c_long,
+    arguments: [u64; 6],
+}
+
+fn mprotect(protection: &Protection) -> Syscall {
+    Syscall { number: libc::SYS_mprotect, arguments: [protection.start as u64, (protection.end - protection.start) as u64, protection.prot as u64, 0, 0, 0] }
+}
+
+// All of them are made before any is checked, so a failure leaves the others applied
+fn inject_mprotect(pid: Pid, calls: &[Protection]) -> Result<(), Box<dyn std::error::Error>> {
+    let returned = inject_syscalls(pid, &calls.iter().map(mprotect).collect::<Vec<Syscall>>())?;
+    for (call, returned) in calls.iter().zip(returned) {
+        check(&format!("mprotect(0x{:x}, 0x{:x}, {})", call.start, call.end - call.start, call.prot), returned)?;
+    }
+    Ok(())
+}
+
+fn prot(region: &MemoryRegion) -> i32 {
+    let mut prot = libc::PROT_NONE;
+    if region.readable {
+        prot |= libc::PROT_READ;
+    }
+    if region.writable {
+        prot |= libc::PROT_WRITE;
+    }
+    if region.executable {
+        prot |= libc::PROT_EXEC;
+    }
+    prot
+}
+
+// Adds PROT_WRITE to every page from address to address + len, keeping the rest of each region's
+// protection, and checks the maps afterwards to be sure it took
+pub fn make_writable(pid: Pid, address: usize, len: usize) -> Result<Reprotection, Box<dyn std::error::Error>> {
+    if len == 0 {
+        return Err("Nothing to make writable".into());
+    }
+    let page = page_size();
+    let start = address - address % page;
+    let end = address.checked_add(len).and_then(|x| x.checked_next_multiple_of(page)).ok_or("The range runs past the end of the address space")?;
+    let regions = get_memory_regions(pid)?;
+    let original = covering(&regions, start, end)?;
+    if original.iter().all(|x| x.prot & libc::PROT_WRITE != 0) {
+        return Err(format!("0x{:x}-0x{:x} is already writable", start, end).into());
+    }
+    let writable = original.iter().map(|x| Protection { prot: x.prot | libc::PROT_WRITE, ..*x }).collect::<Vec<Protection>>();
+    inject_mprotect(pid, &writable)?;
+    let after = covering(&get_memory_regions(pid)?, start, end)?;
+    if let Some(refused) = after.iter().find(|x| x.prot & libc::PROT_WRITE == 0) {
+        return Err(format!("mprotect succeeded, but the maps still show 0x{:x}-0x{:x} as not writable", refused.start, refused.end).into());
+    }
+    Ok(Reprotection { pid, start, end, original, restore_on_drop: false, restored: false })
+}
+
+// Maps len bytes, rounded up to whole pages, of fresh zeroed memory into the target with the given
+// PROT_* flags, returning where the kernel put it
+pub fn remote_alloc(pid: Pid, len: usize, prot: i32) -> Result<usize, Box<dyn std::error::Error>> {
+    remote_mmap(pid, None, len, prot)
+}
+
+// The user half of the x86-64 address space with 4-level paging, which is where a gap can be
+const USER_END: usize = 0x7fff_ffff_f000;
+// Below this mmap_min_addr usually refuses even a fixed mapping
+const USER_START: usize = 0x10000;
+
+// Like remote_alloc, but within a rel32 of near either way, for code that near jumps to and that
+// jumps back. The gap chosen keeps a page from its neighbours, so it is not merged into them, and
+// MAP_FIXED_NOREPLACE has the kernel refuse rather than put it elsewhere or over something mapped
+// in the meantime
+pub fn remote_alloc_near(pid: Pid, near: usize, len: usize, prot: i32) -> Result<usize, Box<dyn std::error::Error>> {
+    if len == 0 {
+        return Err("Nothing to allocate".into());
+    }
+    let page = page_size();
+    let len = len.checked_next_multiple_of(page).ok_or("Too large to allocate")?;
+    let reach = (i32::MAX as usize).checked_sub(len).ok_or(format!("0x{:x} bytes cannot all be within 2 GiB of 0x{:x}", len, near))?;
+    let regions = get_memory_regions(pid)?;
+    let mut gaps = Vec::new();
+    let mut previous = USER_START - page;
+    for region in regions.iter().filter(|x| x.start < USER_END) {
+        gaps.push((previous + page, region.start.saturating_sub(page)));
+        previous = region.end;
+    }
+    gaps.push((previous + page, USER_END));
+    let at = gaps.into_iter().filter(|&(start, end)| end >= start + len).map(|(start, end)| (near - near % page).clamp(start, end - len)).filter(|x| x.abs_diff(near) < reach).min_by_key(|x| x.abs_diff(near));
+    let at = at.ok_or(format!("No gap of 0x{:x} bytes is within 2 GiB of 0x{:x}", len, near))?;
+    remote_mmap(pid, Some(at), len, prot)
+}
+
+// Kernels before 4.17 take MAP_FIXED_NOREPLACE as a hint, so a mapping anywhere else is undone
+fn remote_mmap(pid: Pid, at: Option<usize>, len: usize, prot: i32) -> Result<usize, Box<dyn std::error::Error>> {
+    if len == 0 {
+        return Err("Nothing to allocate".into());
+    }
+    let len = len.checked_next_multiple_of(page_size()).ok_or("Too large to allocate")?;
+    let mut flags = libc::MAP_PRIVATE | libc::MAP_ANONYMOUS;
+    if at.is_some() {
+        flags |= libc::MAP_FIXED_NOREPLACE;
+    }
+    let mmap = Syscall { number: libc::SYS_mmap, arguments: [at.unwrap_or(0) as u64, len as u64, prot as u64, flags as u64, u64::MAX, 0] };
+    let address = check(&format!("mmap of 0x{:x} bytes", len), inject_syscalls(pid, &[mmap])?[0])? as usize;
+    if let Some(at) = at && address != at {
+        let _ = remote_free(pid, address, len);
+        return Err(format!("mmap put the memory at 0x{:x} rather than 0x{:x}", address, at).into());
+    }
+    if covering(&get_memory_regions(pid)?, address, address + len).is_err() {
+        return Err(format!("mmap returned 0x{:x}, but the maps do not show 0x{:x} bytes there", address, len).into());
+    }
+    Ok(address)
+}
+
+// Unmaps what remote_alloc gave back. All of it has to be mapped, since munmap quietly succeeds
+// over holes and a wrong address would go unnoticed
+pub fn remote_free(pid: Pid, address: usize, len: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let page = page_size();
+    if !address.is_multiple_of(page) {
+        return Err(format!("0x{:x} is not the start of a page", address).into());
+    }
+    let end = address.checked_add(len).and_then(|x| x.checked_next_multiple_of(page)).ok_or("The range runs past the end of the address space")?;
+    covering(&get_memory_regions(pid)?, address, end)?;
+    let munmap = Syscall { number: libc::SYS_munmap, arguments: [address as u64, (end - address) as u64, 0, 0, 0, 0] };
+    check(&format!("munmap(0x{:x}, 0x{:x})", address, end - address), inject_syscalls(pid, &[munmap])?[0])?;
+    Ok(())
+}
+
+// Each region's part of start..end, failing if any of it is not mapped
+fn covering(regions: &[MemoryRegion], start: usize, end: usize) -> Result<Vec<Protection>, Box<dyn std::error::Error>> {
+    let mut parts = Vec::new();
+    let mut at = start;
+    while at < end {
+        let region = regions.iter().find(|x| x.contains(at)).ok_or(format!("0x{:x} is not mapped", at))?;
+        parts.push(Protection { start: at, end: region.end.min(end), prot: prot(region) });
+        at = region.end;
+    }
+    Ok(parts)
+}
+
+// Any 0f 05 will do, as the thread is sent straight to it and stepped over just that. The vdso is
+// small and always there, so it is looked in first. [vsyscall] is left out, since running code
+// there faults on current kernels
+fn find_syscall(pid: Pid, regions: &[MemoryRegion]) -> Result<usize, Box<dyn std::error::Error>> {
+    let process = Process::with_backend(pid, MemBackend::ProcessVmReadv).or_else(|_| Process::attach(pid))?;
+    let mut code = regions.iter().filter(|x| x.executable && x.readable && x.pathname != "[vsyscall]").collect::<Vec<&MemoryRegion>>();
+    code.sort_by_key(|x| x.pathname != "[vdso]");
+    for region in code {
+        let Ok(bytes) = read_bytes_from_process(&process, region.len(), region.start) else { continue };
+        if let Some(offset) = bytes.windows(2).position(|x| x == [0x0f, 0x05]) {
+            return Ok(region.start + offset);
+        }
+    }
+    Err("Found no syscall instruction in the target's code to borrow".into())
+}
+
+// One stop for every call, so the main thread is only taken over once, returning what each gave
+// back in rax. It goes through the target's PtraceSession, so it works alongside a watcher or a
+// seize. The registers are put back whatever happens, and if even that fails the error says so,
+// since the target will not survive it
+fn inject_syscalls(pid: Pid, calls: &[Syscall]) -> Result<Vec<i64>, Box<dyn std::error::Error>> {
+    let gadget = find_syscall(pid, &get_memory_regions(pid)?)?;
+    let calls = calls.to_vec();
+    Ok(PtraceSession::seize(pid)?.run(move |traced| take_over(traced, gadget, &calls))??)
+}
+
+// A signal the thread was stopped for is held, and handed on when it is let go
+fn take_over(traced: &mut Traced, gadget: usize, calls: &[Syscall]) -> Result<Vec<i64>, String> {
+    let pid = traced.pid;
+    let resume = traced.stop(pid).ok_or(format!("Process {} exited", pid))?;
+    if let Resume::Listen(signal) = resume {
+        resume.resume(pid);
+        return Err(format!("Process {} is stopped by {:?}; continue it first", pid, signal));
+    }
+    let mut held = match resume {
+        Resume::Continue(signal) => signal,
+        _ => None,
+    };
+    let saved = match ptrace::getregs(pid) {
+        Ok(saved) => saved,
+        Err(e) => {
+            resume.resume(pid);
+            return Err(format!("Could not read the registers of process {}: {}", pid, e));
+        }
+    };
+    let result = calls.iter().map(|call| run_syscall(pid, gadget, saved, call, &mut held).map_err(|e| e.to_string())).collect::<Result<Vec<i64>, _>>();
+    let put_back = ptrace::setregs(pid, saved);
+    match resume {
+        Resume::Continue(_) => Resume::Continue(held).resume(pid),
+        _ => resume.resume(pid),
+    }
+    match put_back {
+        Ok(()) => result,
+        Err(e) => Err(format!("Could not put back the registers of process {} ({}), which will most likely crash", pid, e)),
+    }
+}
+
+// The kernel returns -errno in rax, which is never a valid address or count
+fn check(name: &str, returned: i64) -> Result<u64, Box<dyn std::error::Error>> {
+    match returned {
+        -4095..=-1 => Err(format!("{} failed in the target: {}", name, Errno::from_raw(-returned as i32)).into()),
+        _ => Ok(returned as u64),
+    }
+}
+
+// orig_rax is set to -1 so that a thread stopped inside a syscall of its own is not restarted into
+// it: its own registers, put back afterwards, make the kernel restart that one as it would have
+fn run_syscall(pid: Pid, gadget: usize, saved: libc::user_regs_struct, call: &Syscall, held: &mut Option<Signal>) -> Result<i64, Box<dyn std::error::Error>> {
+    let mut regs = saved;
+    regs.rip = gadget as u64;
+    regs.rax = call.number as u64;
+    regs.orig_rax = u64::MAX;
+    [regs.rdi, regs.rsi, regs.rdx, regs.r10, regs.r8, regs.r9] = call.arguments;
+    ptrace::setregs(pid, regs)?;
+    // A signal arriving first stops the thread before the step, and is held for later
+    for _ in 0..8 {
+        ptrace::step(pid, None)?;
+        match waitpid(pid, Some(WaitPidFlag::__WALL))? {
+            WaitStatus::Stopped(_, Signal::SIGTRAP) => {
+                let after = ptrace::getregs(pid)?;
+                if after.rip != gadget as u64 + 2 {
+                    return Err(format!("Process {} did not come back from the syscall at 0x{:x}, but at 0x{:x}", pid, gadget, after.rip).into());
+                }
+                return Ok(after.rax as i64);
+            }
+            WaitStatus::Stopped(_, signal) => *held = Some(signal),
+            WaitStatus::PtraceEvent(..) => {}
+            _ => return Err(format!("Process {} exited during the syscall", pid).into()),
+        }
+    }
+    Err(format!("Process {} kept being stopped by signals instead of stepping over the syscall", pid).into())
+}
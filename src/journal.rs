@@ -0,0 +1,184 @@
+use std::{fs::File, io::Write, path::{Path, PathBuf}, time::SystemTime};
+use serde::{Deserialize, Serialize};
+use crate::{process::{ProcessMemory, read_bytes_from_process, write_bytes_to_process}, session::{SavedAddress, SavedLock, hex}};
+
+// One write, with the bytes it replaced so it can be undone
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournalEntry {
+    // Numbered from 0 in the order the writes were journaled, and kept through undo and redo, so
+    // `undo #7` means the same write however many others were undone before it
+    pub id: usize,
+    pub address: usize,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+    pub time: SystemTime,
+    // What made the write, e.g. "write", "poke" or "lock"
+    pub kind: String,
+}
+
+// Every write made through it, oldest first
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Journal {
+    pub entries: Vec<JournalEntry>,
+    // Those undone, most recently undone last, until a new write makes redoing them meaningless
+    pub undone: Vec<JournalEntry>,
+    next_id: usize,
+}
+
+impl Journal {
+    // Saves the bytes about to be overwritten, then writes like write_bytes_to_process. Only the
+    // part that was actually written is recorded
+    pub fn write(&mut self, process: impl ProcessMemory, address: usize, data: &[u8], kind: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let old = read_bytes_from_process(&process, data.len(), address)?;
+        let written = write_bytes_to_process(&process, address, data)?.min(old.len());
+        if written > 0 {
+            self.record(address, old[..written].to_vec(), data[..written].to_vec(), kind);
+        }
+        Ok(written)
+    }
+
+    // For writes made some other way, such as the first write of a lock
+    pub fn record(&mut self, address: usize, old: Vec<u8>, new: Vec<u8>, kind: &str) {
+        self.entries.push(JournalEntry { id: self.next_id, address, old, new, time: SystemTime::now(), kind: kind.to_string() });
+        self.next_id += 1;
+        self.undone.clear();
+    }
+
+    // Puts writes made before this journal was started, such as a loaded session's, ahead of its
+    // own, numbering them after the ids it has handed out so far
+    pub fn prepend(&mut self, mut entries: Vec<JournalEntry>) {
+        for entry in &mut entries {
+            entry.id = self.next_id;
+            self.next_id += 1;
+        }
+        entries.append(&mut self.entries);
+        self.entries = entries;
+    }
+
+    // Writes back the bytes the most recent write replaced. The entry stays in the journal if that
+    // fails, so it can be tried again
+    pub fn undo(&mut self, process: impl ProcessMemory) -> Option<Result<JournalEntry, Box<dyn std::error::Error>>> {
+        let id = self.entries.last()?.id;
+        self.undo_id(process, id)
+    }
+
+    // As undo, for any entry still in the journal rather than only the most recent. A lock's entry
+    // is not kept to be redone, as redoing it would write the value once without the lock
+    pub fn undo_id(&mut self, process: impl ProcessMemory, id: usize) -> Option<Result<JournalEntry, Box<dyn std::error::Error>>> {
+        let index = self.entries.iter().position(|x| x.id == id)?;
+        let entry = &self.entries[index];
+        Some(restore(&process, entry.address, &entry.old).map(|_| {
+            let entry = self.entries.remove(index);
+            if entry.kind != "lock" {
+                self.undone.push(entry.clone());
+            }
+            entry
+        }))
+    }
+
+    // Writes the bytes of the most recently undone write again, returning it to the journal under
+    // its own id
+    pub fn redo(&mut self, process: impl ProcessMemory) -> Option<Result<JournalEntry, Box<dyn std::error::Error>>> {
+        let entry = self.undone.last()?;
+        Some(restore(&process, entry.address, &entry.new).map(|_| {
+            let entry = self.undone.pop().unwrap();
+            // By time rather than id, as a loaded session's writes are numbered after but made before
+            let index = self.entries.partition_point(|x| x.time <= entry.time);
+            self.entries.insert(index, entry.clone());
+            entry
+        }))
+    }
+
+    pub fn get(&self, id: usize) -> Option<&JournalEntry> {
+        self.entries.iter().find(|x| x.id == id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+fn restore(process: impl ProcessMemory, address: usize, bytes: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    let written = write_bytes_to_process(&process, address, bytes)?;
+    if written < bytes.len() {
+        return Err(format!("Only {} of {} bytes at 0x{:x} could be restored", written, bytes.len(), address).into());
+    }
+    Ok(())
+}
+
+// One change made to a process, as appended to a journal file: writes and pokes, undos and redos, locks
+// created and removed, patches made and restored, and code injected and removed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalRecord {
+    pub time: SystemTime,
+    pub pid: i32,
+    // write, poke, undo, redo, lock, unlock, patch, restore, inject or uninject
+    pub action: String,
+    // Module-relative where it can be, so that the record can be replayed in a new instance
+    pub address: SavedAddress,
+    // The address in the process the record was made in
+    pub absolute: usize,
+    // For an injection, the bytes the hook replaced and the code injected
+    #[serde(with = "hex")]
+    pub old: Vec<u8>,
+    #[serde(with = "hex")]
+    pub new: Vec<u8>,
+    // The command line that made the change
+    pub command: String,
+    // For a lock, enough to create it again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock: Option<SavedLock>,
+}
+
+// An append-only file of JSON records, one per line. Each record goes out in a single write with
+// nothing buffered, so a crash loses at most the record being written, and that only in part
+#[derive(Debug)]
+pub struct JournalFile {
+    path: PathBuf,
+    file: File,
+}
+
+impl JournalFile {
+    // Appends to the file if there is one already
+    pub fn open(path: impl AsRef<Path>) -> Result<JournalFile, Box<dyn std::error::Error>> {
+        let path = path.as_ref().to_path_buf();
+        let file = std::fs::OpenOptions::new().create(true).append(true).open(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(JournalFile { path, file })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&mut self, record: &JournalRecord) -> Result<(), Box<dyn std::error::Error>> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+        Ok(self.file.flush()?)
+    }
+
+    // Each append is already written through, but on its way out the session makes sure it is on disk
+    pub fn sync(&self) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(self.file.sync_all().map_err(|e| format!("{}: {}", self.path.display(), e))?)
+    }
+
+    // Oldest first. A last line cut short, as a crash would leave it, is left out
+    pub fn read(path: impl AsRef<Path>) -> Result<Vec<JournalRecord>, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut records = Vec::new();
+        let lines = contents.split_inclusive('\n').collect::<Vec<&str>>();
+        for (number, line) in lines.iter().enumerate().filter(|x| !x.1.trim().is_empty()) {
+            match serde_json::from_str::<JournalRecord>(line) {
+                Ok(record) => records.push(record),
+                Err(_) if number == lines.len() - 1 && !line.ends_with('\n') => {}
+                Err(e) => return Err(format!("{} line {}: {}", path.display(), number + 1, e).into()),
+            }
+        }
+        Ok(records)
+    }
+}
@@ -0,0 +1,129 @@
+pub mod process;
+pub mod maps;
+pub mod filter;
+pub mod resident;
+pub mod scan;
+pub mod pattern;
+pub mod guess;
+pub mod lock;
+pub mod snapshot;
+pub mod autosnap;
+pub mod monitor;
+pub mod dump;
+pub mod capture;
+pub mod offline;
+pub mod mock;
+pub mod diff;
+pub mod session;
+pub mod value;
+pub mod expr;
+pub mod pointer;
+pub mod address;
+pub mod pointermap;
+pub mod priority;
+pub mod budget;
+pub mod retry;
+pub mod patch;
+pub mod journal;
+pub mod browse;
+#[cfg(target_os = "linux")]
+pub mod tracer;
+#[cfg(target_os = "linux")]
+pub mod registers;
+#[cfg(target_os = "linux")]
+pub mod status;
+#[cfg(target_os = "linux")]
+pub mod preflight;
+#[cfg(target_os = "linux")]
+pub mod follow;
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub mod watch;
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub mod unwind;
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub mod inject;
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub mod cave;
+pub mod x86;
+pub mod disasm;
+pub mod elf;
+pub mod symbols;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub mod control;
+pub mod cheat_table;
+pub mod scanmem;
+pub mod server;
+pub mod machine;
+pub mod platform;
+#[cfg(windows)]
+pub mod windows;
+#[cfg(target_os = "macos")]
+pub mod macos;
+#[cfg(feature = "hotkeys")]
+pub mod hotkeys;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "scripting")]
+pub mod script;
+
+pub use process::{MemBackend, Process, ProcessMemory, StringSlot, WRITE_CHUNK_SIZE, read_from_process, read_from_process_with_retry, read_bits, read_bytes_from_process, read_bytes_into, read_cstring, read_into, read_many, read_many_bytes, read_many_in, read_scalar, read_string_lossy, read_string_slot, read_struct, write_bits, write_bytes_to_process, write_many, write_to_process, write_scalar, write_string, write_struct, write_to_process_checked, write_to_process_forced, write_typed};
+pub use filter::{RegionCategory, RegionFilter, classify_regions, thread_stack_regions, thread_stacks, within_regions};
+pub use resident::{Residency, resident_ranges};
+pub use maps::{AddressDescription, MapsChange, MapsFingerprint, MemoryRegion, Module, RegionCache, apply_page_sizes, describe_address, executable_path, find_module, get_memory_regions, get_possible_memory_ranges, module_for_address, modules, modules_from_regions};
+pub use scanmem::ScanmemSession;
+pub use scan::{CategorizedRange, FlagConvention, InitialValues, ProgressCallback, ReadFailure, SCAN_CHUNK_SIZE, ScanOptions, ScanProgress, ScanStats, categorized_ranges, check_rounded, filtered_ranges, find_bit, find_flags, find_masked, find_rounded, find_value, find_value_by_predicate, find_value_generic, read_error_name, reduce_by_expression, reduce_found_bits, reduce_found_flags, reduce_found_masked, reduce_found_rounded, reduce_found_values, reduce_found_values_by_predicate, reduce_rounded_change, reduce_to_initial, rounds_to, slow_scan_bytes};
+pub use pattern::{DEFAULT_GLOB_RUN, DEFAULT_PAIR_DISTANCE, FieldConstraint, Pair, PairDistance, StringSearch, StructPattern, find_pair, find_string, find_struct, reduce_found_structs};
+pub use guess::{Guess, Reading, guess_types};
+pub use lock::{DEFAULT_LOCK_INTERVAL, DEFAULT_RANGE_LOCK_LIMIT, LOCK_DEAD_AFTER, LOCK_FAILING_AFTER, LockAction, LockEntry, LockFailureCallback, LockManager, LockStatus};
+pub use snapshot::{Candidates, HEATMAP_PAGE_SIZE, Heatmap, RegionHeat, Snapshot, UNKNOWN_LIST_LIMIT, UnknownScan, reduce_decreased_by_percent, reduce_increased_by_percent, snapshot_to_file};
+pub use autosnap::{AutoSnapshot, AutoSnapshotOptions, AutoSnapshotter};
+pub use monitor::{DEFAULT_MONITOR_INTERVAL, ExpressionCallback, ExpressionChange, ExpressionMonitor, MonitorCallback, MonitorChange, MonitorEntry, ValueMonitor, WatchedExpression};
+pub use offline::OfflineCapture;
+pub use mock::{MOCK_PAGE_SIZE, MockProcess};
+pub use diff::{CaptureDiff, ChangedPair, ChangedRegion, ProcessDiff, RegionPair, diff_captures, diff_captures_by, diff_processes, diff_processes_by, pair_regions};
+pub use capture::{CAPTURE_BLOCK_SIZE, CAPTURE_MAGIC, CAPTURE_VERSION, Capture, CaptureHeader, CapturedRegion, Compression};
+pub use cheat_table::{CheatEntry, CheatGroup, CheatTable};
+pub use dump::{DUMP_INDEX, DumpIndex, DumpedRegion, dump_all, dump_range, dump_to_file};
+pub use session::{SAVED_VALUE_LIMIT, SESSION_VERSION, SavedAddress, SavedLock, SavedMark, SavedPatch, SavedResult, SavedWrite, SessionFile};
+pub use address::{AddressExpression, AddressNames};
+pub use pointer::{DEFAULT_POINTER_OFFSET, PointerChain, PointerHit, PointerSearch, find_pointers_to, parse_offset, read_chain, resolve_address, resolve_pointer_chain, write_chain};
+pub use pointermap::{ChainSearch, DEFAULT_CHAIN_DEPTH, POINTER_MAP_BLOCK, POINTER_MAP_MAGIC, PointerMap, intersect_chains};
+pub use priority::{ScanPriority, priority_error};
+pub use budget::{BudgetFallback, MIN_BUDGET_CHUNK, SpillFile};
+pub use journal::{Journal, JournalEntry, JournalFile, JournalRecord};
+pub use browse::{BrowseCell, BrowseColumn, BrowseLayout, BrowseMove, BrowseRow, Browser, DEFAULT_BROWSE_INTERVAL, DEFAULT_BROWSE_LAYOUT, DEFAULT_BROWSE_ROWS, Key, decode_keys};
+pub use patch::{NOP, Patch, patch_bytes, patch_nop};
+pub use retry::{Retry, is_transient};
+pub use server::{ServeAddress, Server};
+pub use machine::{ErrorCode, MACHINE_VERSION, MachineError, MachineMessage, ResultStatus};
+pub use platform::{Errno, Pid};
+#[cfg(target_os = "linux")]
+pub use tracer::{AlreadyTraced, PtraceSession, Tracer, check_not_traced, tracer_of};
+#[cfg(target_os = "linux")]
+pub use registers::{Registers, ThreadInfo, list_threads, read_registers};
+#[cfg(target_os = "linux")]
+pub use status::{TargetStatus, target_status};
+#[cfg(target_os = "linux")]
+pub use preflight::{PermissionFix, PreflightCheck, find_in_path, permission_fixes, preflight};
+#[cfg(target_os = "linux")]
+pub use follow::{ProcessIdentity, TargetChange, TargetMonitor};
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub use watch::{Access, BreakHit, Breakpoint, CODE_WINDOW, DEFAULT_HISTORY_LIMIT, MAX_WATCHPOINTS, ValueChange, Watch, WatchCallback, WatchEvent, WatchHit, WatchKind, Watcher};
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub use unwind::{DEFAULT_BACKTRACE_DEPTH, Unwinder, backtrace};
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub use inject::{Protection, Reprotection, make_writable, remote_alloc, remote_alloc_near, remote_free};
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub use cave::{Injection, inject_code};
+pub use x86::{Instruction, decode_instruction};
+pub use disasm::{Disassembled, disassemble, instruction_before};
+pub use elf::{Arch, ELF_IDENT_LEN, ElfFile, Symbol, demangle};
+pub use symbols::{Location, SymbolTable, find_symbol, locate, symbol_table};
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub use control::{continue_process, is_stopped, kill_process, stop_process};
+pub use expr::{AlertCondition, Comparison, Derived, DerivedRead, DerivedValue, Expression, Operator};
+pub use value::{Arithmetic, DisplayFormat, Encoding, Endianness, Pad, ParseValueError, Radix, Scalar, StringWriteOptions, TypedValue, ValueType, encode_string, parse_hex_bytes, parse_scalar, strip_endianness};
+// Structs passed to read_struct and write_struct derive these
+pub use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
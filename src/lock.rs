@@ -0,0 +1,528 @@
+use std::{collections::BTreeMap, sync::{Arc, Condvar, Mutex, MutexGuard}, thread::JoinHandle, time::{Duration, Instant}};
+use zerocopy::{FromBytes, Immutable, IntoBytes};
+use crate::{platform::Errno, process::{ProcessMemory, explain_failed_writes, read_bytes_into, read_from_process, read_scalar}, value::{Endianness, Scalar}};
+
+// Rewriting every millisecond is enough for values that are reset every frame. Shorter intervals,
+// including sub-millisecond ones, are allowed but keep the servicing thread busy
+pub const DEFAULT_LOCK_INTERVAL: Duration = Duration::from_millis(1);
+// Consecutive failed ticks before a lock is reported as failing, and before it is given up on
+pub const LOCK_FAILING_AFTER: u64 = 10;
+pub const LOCK_DEAD_AFTER: u64 = 10_000;
+// The longest range lock_range is given by the CLI without being forced. Rewriting a whole block
+// every millisecond races whatever the target is doing to it, and the larger it is the more
+// visibly the two fight
+pub const DEFAULT_RANGE_LOCK_LIMIT: usize = 4096;
+
+// What a lock does to the value on every tick. Everything but Set reads the current value first
+// and skips the tick if that read fails
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LockAction<T> {
+    Set(T),
+    Add(T),
+    Sub(T),
+    // Never lets the value rise above the operand
+    Min(T),
+    // Never lets the value fall below the operand
+    Max(T),
+    // Keeps the value within whichever bounds are given, writing only once it is further than the
+    // tolerance past one, and then writing the bound itself. Within them, a tick is only a read
+    Clamp { min: Option<T>, max: Option<T>, tolerance: T },
+    // Writes the value only when the current one is further than the tolerance from it, so an
+    // undisturbed value costs a read per tick instead of a write
+    Hold(T, T),
+}
+
+impl<T: Scalar> LockAction<T> {
+    pub fn apply(&self, current: T) -> T {
+        match *self {
+            LockAction::Set(x) => x,
+            LockAction::Add(x) => current.saturating_add(x),
+            LockAction::Sub(x) => current.saturating_sub(x),
+            LockAction::Min(x) => if current > x { x } else { current },
+            LockAction::Max(x) => if current < x { x } else { current },
+            LockAction::Clamp { min: Some(min), .. } if current < min => min,
+            LockAction::Clamp { max: Some(max), .. } if current > max => max,
+            LockAction::Clamp { min, max, .. } if current.is_nan() => min.or(max).unwrap_or(current),
+            LockAction::Clamp { .. } => current,
+            LockAction::Hold(x, _) => if self.needs_write(current) { x } else { current },
+        }
+    }
+
+    // Whether a tick needs to write at all given the current value
+    pub fn needs_write(&self, current: T) -> bool {
+        match *self {
+            // A NaN on either side is incomparable and so counts as drifted
+            LockAction::Hold(x, tolerance) => !matches!(current.abs_diff(x).partial_cmp(&tolerance), Some(std::cmp::Ordering::Less | std::cmp::Ordering::Equal)),
+            // A NaN is within no band, so is put back on a bound
+            LockAction::Clamp { min, max, tolerance } => current.is_nan() || min.is_some_and(|x| current < x && x.abs_diff(current) > tolerance) || max.is_some_and(|x| current > x && current.abs_diff(x) > tolerance),
+            _ => true,
+        }
+    }
+
+    // The operand written as-is by Set, or the first operand of the other actions
+    pub fn operand(&self) -> T {
+        match *self {
+            LockAction::Set(x) | LockAction::Add(x) | LockAction::Sub(x) | LockAction::Min(x) | LockAction::Max(x) | LockAction::Hold(x, _) => x,
+            LockAction::Clamp { min, max, .. } => min.or(max).unwrap_or_default(),
+        }
+    }
+}
+
+impl<T: Scalar> std::fmt::Display for LockAction<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LockAction::Set(x) => write!(f, "set {}", x),
+            LockAction::Add(x) => write!(f, "add {}", x),
+            LockAction::Sub(x) => write!(f, "sub {}", x),
+            LockAction::Min(x) => write!(f, "min {}", x),
+            LockAction::Max(x) => write!(f, "max {}", x),
+            LockAction::Clamp { min, max, tolerance } => {
+                match (min, max) {
+                    (Some(min), Some(max)) => write!(f, "clamp {} {}", min, max)?,
+                    (Some(min), None) => write!(f, "clamp min {}", min)?,
+                    (None, Some(max)) => write!(f, "clamp max {}", max)?,
+                    (None, None) => write!(f, "clamp")?,
+                }
+                if *tolerance != T::default() {
+                    write!(f, " {}", tolerance)?;
+                }
+                Ok(())
+            }
+            LockAction::Hold(x, tolerance) => write!(f, "hold {} {}", x, tolerance),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LockStatus {
+    Running,
+    Paused,
+    // At least LOCK_FAILING_AFTER ticks in a row have failed, but the lock keeps trying
+    Failing,
+    // LOCK_DEAD_AFTER ticks in a row failed and the lock is no longer serviced
+    Dead,
+    // The lock's deadline has passed; it stays registered so it can be renewed
+    Expired,
+}
+
+impl std::fmt::Display for LockStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LockStatus::Running => write!(f, "running"),
+            LockStatus::Paused => write!(f, "paused"),
+            LockStatus::Failing => write!(f, "failing"),
+            LockStatus::Dead => write!(f, "dead"),
+            LockStatus::Expired => write!(f, "expired"),
+        }
+    }
+}
+
+// Called from the servicing thread when a lock becomes Failing and again if it becomes Dead, rather
+// than on every failed tick. It runs with the registry locked, so it must not call back into the
+// LockManager
+#[derive(Clone)]
+pub struct LockFailureCallback(pub Arc<dyn Fn(usize, LockStatus, Option<Errno>) + Send + Sync>);
+
+impl std::fmt::Debug for LockFailureCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LockFailureCallback")
+    }
+}
+
+// A copy of one lock's state as the servicing thread last left it
+#[derive(Debug, Clone)]
+pub struct LockEntry {
+    pub address: usize,
+    pub type_name: &'static str,
+    // A copy of what the lock writes, kept so that listings can show the frozen value
+    pub value_bytes: Arc<[u8]>,
+    // How the lock treats the value each tick, e.g. "set 100" or "add 10"
+    pub action: String,
+    pub created: Instant,
+    pub interval: Duration,
+    // Disabled locks stay registered but are skipped by the servicing thread
+    pub enabled: bool,
+    // Writes actually made since the lock was created, which for Hold and Clamp locks are only the corrective ones
+    pub writes: u64,
+    pub failures: u64,
+    pub consecutive_failures: u64,
+    pub last_errno: Option<Errno>,
+    // How long the lock lasts each time it is armed, and when the current run ends
+    pub duration: Option<Duration>,
+    pub deadline: Option<Instant>,
+}
+
+impl LockEntry {
+    pub fn status(&self) -> LockStatus {
+        if self.consecutive_failures >= LOCK_DEAD_AFTER {
+            LockStatus::Dead
+        }
+        else if self.deadline.is_some_and(|x| x <= Instant::now()) {
+            LockStatus::Expired
+        }
+        else if !self.enabled {
+            LockStatus::Paused
+        }
+        else if self.consecutive_failures >= LOCK_FAILING_AFTER {
+            LockStatus::Failing
+        }
+        else {
+            LockStatus::Running
+        }
+    }
+
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline.map(|x| x.saturating_duration_since(Instant::now()))
+    }
+
+    pub fn writes_per_second(&self) -> f64 {
+        self.writes as f64 / self.created.elapsed().as_secs_f64().max(f64::EPSILON)
+    }
+
+    // The frozen value, if the lock was created with type T
+    pub fn value<T: Scalar>(&self) -> Option<T> {
+        (self.type_name == std::any::type_name::<T>()).then(|| T::from_bytes(&self.value_bytes))
+    }
+}
+
+// Returns whether the tick wrote anything
+type CustomTick<P> = Box<dyn FnMut(&P) -> Result<bool, Box<dyn std::error::Error>> + Send>;
+
+// Constant writes are gathered from every due lock into one scattered write. Anything else runs
+// on its own
+enum Tick<P> {
+    Write(Arc<[u8]>),
+    Custom(CustomTick<P>),
+}
+
+struct RegisteredLock<P> {
+    entry: LockEntry,
+    tick: Tick<P>,
+    due: Instant,
+}
+
+struct Registry<P> {
+    locks: BTreeMap<usize, RegisteredLock<P>>,
+    on_failure: Option<LockFailureCallback>,
+    shutdown: bool,
+}
+
+// The registry and the condition variable that wakes the servicing thread whenever it changes
+struct Shared<P> {
+    registry: Mutex<Registry<P>>,
+    changed: Condvar,
+}
+
+// Owns every lock on one process. A single background thread services all of them; adding,
+// pausing, editing or removing a lock is a change to the shared registry that the thread picks up
+// on its next tick. Since ticks run with the registry locked, once one of these calls returns no
+// write from the old state of the lock is still in flight
+pub struct LockManager<P: ProcessMemory + Clone + 'static> {
+    process: P,
+    shared: Arc<Shared<P>>,
+    handle: Option<JoinHandle<()>>,
+    // Whether locks start out enabled; only cleared inside create_disabled
+    new_locks_enabled: bool,
+}
+
+impl<P: ProcessMemory + Clone + 'static> std::fmt::Debug for LockManager<P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LockManager").field("locks", &self.list()).finish()
+    }
+}
+
+impl<P: ProcessMemory + Clone + 'static> LockManager<P> {
+    pub fn new(process: P) -> LockManager<P> {
+        let shared = Arc::new(Shared {
+            registry: Mutex::new(Registry { locks: BTreeMap::new(), on_failure: None, shutdown: false }),
+            changed: Condvar::new(),
+        });
+        let threads_shared = shared.clone();
+        let threads_process = process.clone();
+        let handle = std::thread::spawn(move || service(threads_process, &threads_shared));
+        LockManager { process, shared, handle: Some(handle), new_locks_enabled: true }
+    }
+
+    fn registry(&self) -> MutexGuard<'_, Registry<P>> {
+        self.shared.registry.lock().unwrap()
+    }
+
+    // Runs an edit on the registry and wakes the servicing thread so it notices
+    fn edit<R>(&self, edit: impl FnOnce(&mut Registry<P>) -> R) -> R {
+        let result = edit(&mut self.registry());
+        self.shared.changed.notify_one();
+        result
+    }
+
+    pub fn set_failure_callback(&mut self, callback: Option<LockFailureCallback>) {
+        self.edit(|registry| registry.on_failure = callback);
+    }
+
+    // Adds a lock calling tick every interval, replacing any existing lock on the same address
+    fn insert(&self, address: usize, type_name: &'static str, value_bytes: Arc<[u8]>, action: String, interval: Duration, tick: Tick<P>) {
+        let now = Instant::now();
+        let entry = LockEntry {
+            address,
+            type_name,
+            value_bytes,
+            action,
+            created: now,
+            interval,
+            enabled: self.new_locks_enabled,
+            writes: 0,
+            failures: 0,
+            consecutive_failures: 0,
+            last_errno: None,
+            duration: None,
+            deadline: None,
+        };
+        self.edit(|registry| registry.locks.insert(address, RegisteredLock { entry, tick, due: now }));
+    }
+
+    pub fn lock_value<T: IntoBytes + Immutable + Send + Sync + 'static>(&mut self, value: T, address: usize, interval: Duration) {
+        let value_bytes: Arc<[u8]> = value.as_bytes().into();
+        let ticks_bytes = value_bytes.clone();
+        self.insert(address, std::any::type_name::<T>(), value_bytes, "set".to_string(), interval, Tick::Write(ticks_bytes));
+    }
+
+    // Writes the whole buffer every tick in a single call, so the target never sees it half updated
+    pub fn lock_bytes(&mut self, bytes: Vec<u8>, address: usize, interval: Duration) {
+        let value_bytes: Arc<[u8]> = bytes.into();
+        let ticks_bytes = value_bytes.clone();
+        self.insert(address, "bytes", value_bytes, "set".to_string(), interval, Tick::Write(ticks_bytes));
+    }
+
+    // Freezes a whole block, such as a struct, at the bytes given, which are usually a snapshot of
+    // it: every tick rewrites all of them in one write alongside the other constant locks, so the
+    // target never sees it half restored. With only_changed a tick reads the range first and
+    // writes only if anything in it differs, trading a read for a write while it is undisturbed.
+    // Listed as bytes, with "range" or "range if changed" as the action
+    pub fn lock_range(&mut self, bytes: Vec<u8>, address: usize, interval: Duration, only_changed: bool) {
+        let value_bytes: Arc<[u8]> = bytes.into();
+        if !only_changed {
+            let ticks_bytes = value_bytes.clone();
+            return self.insert(address, "bytes", value_bytes, "range".to_string(), interval, Tick::Write(ticks_bytes));
+        }
+        let (ticks_bytes, mut current) = (value_bytes.clone(), vec![0u8; value_bytes.len()]);
+        self.insert(address, "bytes", value_bytes, "range if changed".to_string(), interval, Tick::Custom(Box::new(move |process: &P| {
+            let read = read_bytes_into(process, address, &mut current)?;
+            if read == current.len() && current[..] == ticks_bytes[..] {
+                return Ok(false);
+            }
+            write_all(process, address, &ticks_bytes).map(|_| true)
+        })));
+    }
+
+    // Applies the action to the current value on every tick instead of writing a constant. A
+    // tick whose read fails is skipped and counts as a failure. The value is read and written in
+    // the given byte order, though the entry's value_bytes stay native so listings can show it
+    pub fn lock_with_action<T: Scalar>(&mut self, action: LockAction<T>, address: usize, interval: Duration, endianness: Endianness) {
+        if let LockAction::Set(value) = action {
+            if endianness.is_native() {
+                return self.lock_value(value, address, interval);
+            }
+            let bytes = value.to_bytes_in(endianness);
+            return self.insert(address, std::any::type_name::<T>(), value.to_bytes().into(), "set".to_string(), interval, Tick::Write(bytes.into()));
+        }
+        self.insert(address, std::any::type_name::<T>(), action.operand().to_bytes().into(), action.to_string(), interval, Tick::Custom(Box::new(move |process: &P| {
+            let current = read_scalar::<T>(process, address, endianness)?;
+            if !action.needs_write(current) {
+                return Ok(false);
+            }
+            write_all(process, address, &action.apply(current).to_bytes_in(endianness)).map(|_| true)
+        })));
+    }
+
+    // Freezes whatever value is currently at the address, failing without creating a lock if it cannot be read
+    pub fn lock_current_value<T: FromBytes + IntoBytes + Immutable + Send + Sync + 'static>(&mut self, address: usize, interval: Duration) -> Result<(), Box<dyn std::error::Error>> {
+        let value: T = read_from_process(&self.process, address)?;
+        self.lock_value(value, address, interval);
+        Ok(())
+    }
+
+    // Runs create with every lock it makes starting out disabled, so that not even one write goes out
+    // before the caller has checked the address, e.g. when restoring a lock whose address may have moved
+    pub fn create_disabled<R>(&mut self, create: impl FnOnce(&mut Self) -> R) -> R {
+        self.new_locks_enabled = false;
+        let result = create(self);
+        self.new_locks_enabled = true;
+        result
+    }
+
+    // Returns whether there was a lock
+    pub fn unlock_value(&mut self, address: usize) -> bool {
+        self.edit(|registry| registry.locks.remove(&address).is_some())
+    }
+
+    pub fn remove_all(&mut self) {
+        self.edit(|registry| registry.locks.clear());
+    }
+
+    // Returns whether there was a lock to enable or disable
+    pub fn set_enabled(&self, address: usize, enabled: bool) -> bool {
+        self.edit(|registry| registry.locks.get_mut(&address).map(|x| set_enabled(x, enabled)).is_some())
+    }
+
+    // Keeps the lock registered but skips its writes until it is resumed
+    pub fn pause(&self, address: usize) -> bool {
+        self.set_enabled(address, false)
+    }
+
+    // Also gives a dead lock another try
+    pub fn resume(&self, address: usize) -> bool {
+        self.set_enabled(address, true)
+    }
+
+    pub fn pause_all(&self) {
+        self.edit(|registry| registry.locks.values_mut().for_each(|x| set_enabled(x, false)));
+    }
+
+    pub fn resume_all(&self) {
+        self.edit(|registry| registry.locks.values_mut().for_each(|x| set_enabled(x, true)));
+    }
+
+    // Takes effect from the lock's next tick. Returns whether there was a lock
+    pub fn set_interval(&self, address: usize, interval: Duration) -> bool {
+        self.edit(|registry| match registry.locks.get_mut(&address) {
+            Some(lock) => {
+                lock.entry.interval = interval;
+                lock.due = lock.due.min(Instant::now() + interval);
+                true
+            }
+            None => false,
+        })
+    }
+
+    // Makes the lock expire once the duration has passed from now, or never with None. Returns
+    // whether there was a lock
+    pub fn set_duration(&self, address: usize, duration: Option<Duration>) -> bool {
+        self.edit(|registry| match registry.locks.get_mut(&address) {
+            Some(lock) => {
+                lock.entry.duration = duration;
+                lock.entry.deadline = duration.map(|x| Instant::now() + x);
+                true
+            }
+            None => false,
+        })
+    }
+
+    // Re-arms a lock with a duration for another run of that duration, whether or not it has
+    // expired yet. Returns whether there was such a lock
+    pub fn renew(&self, address: usize) -> bool {
+        match self.get(address).and_then(|x| x.duration) {
+            Some(duration) => self.set_duration(address, Some(duration)),
+            None => false,
+        }
+    }
+
+    pub fn get(&self, address: usize) -> Option<LockEntry> {
+        self.registry().locks.get(&address).map(|x| x.entry.clone())
+    }
+
+    pub fn list(&self) -> Vec<LockEntry> {
+        self.registry().locks.values().map(|x| x.entry.clone()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.registry().locks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.registry().locks.is_empty()
+    }
+}
+
+fn set_enabled<P>(lock: &mut RegisteredLock<P>, enabled: bool) {
+    if enabled && lock.entry.status() == LockStatus::Dead {
+        lock.entry.consecutive_failures = 0;
+    }
+    lock.entry.enabled = enabled;
+    lock.due = Instant::now();
+}
+
+// Runs every due tick, then sleeps until the next one is due or the registry changes
+fn service<P: ProcessMemory>(process: P, shared: &Shared<P>) {
+    let mut registry = shared.registry.lock().unwrap();
+    while !registry.shutdown {
+        let now = Instant::now();
+        let on_failure = registry.on_failure.clone();
+        let mut next_due: Option<Instant> = None;
+        let mut writing = Vec::new();
+        for lock in registry.locks.values_mut() {
+            if !lock.entry.enabled || matches!(lock.entry.status(), LockStatus::Dead | LockStatus::Expired) {
+                continue;
+            }
+            if lock.due <= now {
+                // Falling behind skips the missed ticks rather than running them back to back
+                lock.due = (lock.due + lock.entry.interval).max(now);
+                match &mut lock.tick {
+                    Tick::Write(_) => writing.push(lock),
+                    Tick::Custom(tick) => {
+                        let result = tick(&process);
+                        record(&mut lock.entry, result, on_failure.as_ref());
+                        next_due = Some(next_due.map_or(lock.due, |x| x.min(lock.due)));
+                    }
+                }
+                continue;
+            }
+            next_due = Some(next_due.map_or(lock.due, |x| x.min(lock.due)));
+        }
+        let writes = writing.iter().filter_map(|x| match &x.tick {
+            Tick::Write(bytes) => Some((x.entry.address, bytes.clone())),
+            Tick::Custom(_) => None,
+        }).collect::<Vec<(usize, Arc<[u8]>)>>();
+        let writes = writes.iter().map(|x| (x.0, &*x.1)).collect::<Vec<(usize, &[u8])>>();
+        let mut results = process.write_many_at(&writes);
+        // Only a lock's first failure in a row has the maps read to explain it, or one that keeps
+        // failing would have them parsed on every tick. Those after are put down to the same cause
+        explain_failed_writes(&process, &writes, &mut results, |index| writing[index].entry.consecutive_failures == 0);
+        for (lock, result) in writing.into_iter().zip(results) {
+            let result = match result {
+                Err(_) if lock.entry.consecutive_failures > 0 && lock.entry.last_errno == Some(Errno::EACCES) => Err(Errno::EACCES.into()),
+                result => result,
+            };
+            record(&mut lock.entry, result.map(|_| true), on_failure.as_ref());
+            next_due = Some(next_due.map_or(lock.due, |x| x.min(lock.due)));
+        }
+        registry = match next_due {
+            Some(due) => shared.changed.wait_timeout(registry, due.saturating_duration_since(Instant::now())).unwrap().0,
+            None => shared.changed.wait(registry).unwrap(),
+        };
+    }
+}
+
+// Counts the outcome of one tick
+fn record(entry: &mut LockEntry, result: Result<bool, Box<dyn std::error::Error>>, on_failure: Option<&LockFailureCallback>) {
+    match result {
+        Ok(wrote) => {
+            entry.writes += wrote as u64;
+            entry.consecutive_failures = 0;
+        }
+        Err(e) => {
+            let errno = e.downcast_ref::<Errno>().copied();
+            entry.last_errno = errno.or(entry.last_errno);
+            entry.failures += 1;
+            entry.consecutive_failures += 1;
+            if let (LOCK_FAILING_AFTER | LOCK_DEAD_AFTER, Some(on_failure)) = (entry.consecutive_failures, on_failure) {
+                (on_failure.0)(entry.address, entry.status(), errno);
+            }
+        }
+    }
+}
+
+fn write_all(process: impl ProcessMemory, address: usize, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    let written = process.write_at(address, data)?;
+    if written < data.len() {
+        return Err(format!("Short write at 0x{:x}: wrote {} of {} bytes", address, written, data.len()).into());
+    }
+    Ok(())
+}
+
+impl<P: ProcessMemory + Clone + 'static> Drop for LockManager<P> {
+    fn drop(&mut self) {
+        self.edit(|registry| registry.shutdown = true);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::{platform::Errno, value::ParseValueError};
+
+// What `memory <pid> --machine` writes to standard output instead of the prompt, one JSON object
+// per line for frontends that would rather pipe the binary than talk to the socket server.
+// Commands are read as typed, one per line, and each is answered by a result, in order:
+//     {"type": "result", "command": "scan i32 100", "status": "ok", "output": ["scanned ..."]}
+//     {"type": "result", "command": "frobnicate", "status": "error", "output": [],
+//      "error": {"code": "invalid", "message": "Unknown command 'frobnicate'"}}
+// Between results come events, which are everything not printed by a command:
+//     {"type": "attached", "pid": 1234, "backend": "process_vm", "version": 1}, before anything else
+//     {"type": "progress", "bytes_scanned": 1024, "bytes_total": 4096, "rate": 2048.0}
+//     {"type": "lock_failure", "address": "0x7f31c2a0", "status": "failing", "error": "..."}
+//     {"type": "watch_hit", "address": "0x55d1...", "thread": 1235, "rip": "0x55d1...", ...}
+//     {"type": "break_hit", "address": "0x55d1...", "thread": 1235, "hits": 3, "registers": {...}}
+//     {"type": "message", "text": "..."}, e.g. a script's log or a hotkey's output
+// Fields are only ever added, so a frontend should ignore ones it does not know.
+// MACHINE_VERSION goes up if an existing one changes meaning
+pub const MACHINE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MachineMessage {
+    Attached {
+        pid: i32,
+        backend: String,
+        version: u32,
+    },
+    Result {
+        command: String,
+        status: ResultStatus,
+        // The lines the command printed for a person to read
+        output: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<MachineError>,
+    },
+    Progress {
+        bytes_scanned: usize,
+        bytes_total: usize,
+        // Bytes per second
+        rate: f64,
+    },
+    LockFailure {
+        address: String,
+        status: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    // The first access caught by watchwrite or watchaccess from an instruction. rip is the
+    // instruction after it, location where that is as module+offset, and value the watched bytes
+    // afterwards in hex
+    WatchHit {
+        address: String,
+        thread: i32,
+        rip: String,
+        location: String,
+        value: String,
+        // read or write
+        access: String,
+        // The instruction that made the access, where the code before rip could be decoded
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        instruction: Option<String>,
+        // From rip, then each caller, each as module+offset with the function where known
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        backtrace: Vec<String>,
+    },
+    // A thread reaching a breakpoint set with break, hits counting this one, and the registers
+    // by name in hex
+    BreakHit {
+        address: String,
+        thread: i32,
+        location: String,
+        hits: u64,
+        registers: BTreeMap<String, String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        backtrace: Vec<String>,
+    },
+    Message {
+        text: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResultStatus {
+    Ok,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    // The system refused, e.g. EPERM reading the process or EFAULT writing an unmapped address
+    Os,
+    // A file could not be read or written
+    Io,
+    // The command or one of its values could not be understood
+    Invalid,
+    // Another process is tracing the target, so nothing needing ptrace can be used on it
+    Traced,
+    // Anything else, e.g. no scan to rescan or a lock on an address that is not locked
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MachineError {
+    pub code: ErrorCode,
+    pub message: String,
+    // For os errors, and io errors that came from the system, e.g. 2 for a missing file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub errno: Option<i32>,
+}
+
+impl MachineError {
+    // The code is worked out from the error's type, so most errors that only have a message are
+    // failed
+    pub fn from_error(error: &(dyn std::error::Error + 'static)) -> MachineError {
+        let (code, errno) = if is_already_traced(error) {
+            (ErrorCode::Traced, None)
+        }
+        else if let Some(errno) = error.downcast_ref::<Errno>() {
+            (ErrorCode::Os, Some(*errno as i32))
+        }
+        else if let Some(e) = error.downcast_ref::<std::io::Error>() {
+            (ErrorCode::Io, e.raw_os_error())
+        }
+        else if error.is::<std::num::ParseIntError>() || error.is::<std::num::ParseFloatError>() || error.is::<ParseValueError>() || error.to_string().starts_with("Unknown command") {
+            (ErrorCode::Invalid, None)
+        }
+        else {
+            (ErrorCode::Failed, None)
+        };
+        MachineError { code, message: error.to_string(), errno }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn is_already_traced(error: &(dyn std::error::Error + 'static)) -> bool {
+    error.is::<crate::tracer::AlreadyTraced>()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_already_traced(_: &(dyn std::error::Error + 'static)) -> bool {
+    false
+}
+
+impl MachineMessage {
+    pub fn result(command: &str, output: Vec<String>, result: Result<(), &(dyn std::error::Error + 'static)>) -> MachineMessage {
+        let (status, error) = match result {
+            Ok(()) => (ResultStatus::Ok, None),
+            Err(e) => (ResultStatus::Error, Some(MachineError::from_error(e))),
+        };
+        MachineMessage::Result { command: command.to_string(), status, output, error }
+    }
+
+    // Written in one go so lines from different threads never interleave
+    pub fn emit(&self) {
+        if let Ok(line) = serde_json::to_string(self) {
+            println!("{}", line);
+        }
+    }
+}
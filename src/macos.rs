@@ -0,0 +1,239 @@
+use std::{ffi::c_void, path::Path, sync::Arc};
+use mach2::{kern_return::{KERN_INVALID_ADDRESS, KERN_INVALID_NAME, KERN_INVALID_TASK, KERN_NO_ACCESS, KERN_PROTECTION_FAILURE, KERN_SUCCESS, KERN_TERMINATED, kern_return_t}, mach_port::mach_port_deallocate, message::mach_msg_type_number_t, port::{MACH_PORT_NULL, mach_port_t}, traps::{mach_task_self, task_for_pid}, vm::{mach_vm_read_overwrite, mach_vm_region, mach_vm_write}, vm_prot::{VM_PROT_EXECUTE, VM_PROT_READ, VM_PROT_WRITE}, vm_region::{VM_REGION_BASIC_INFO_64, vm_region_basic_info_64, vm_region_info_t}, vm_types::{mach_vm_address_t, mach_vm_size_t}};
+use crate::{maps::MemoryRegion, offline::OfflineCapture, platform::{Errno, Pid}, process::{MemBackend, ProcessMemory}, resident::page_size};
+
+// The macOS backend: mach_vm_read_overwrite and mach_vm_write on the target's task port, with the
+// regions from mach_vm_region. task_for_pid only hands out another process's task port to root or
+// to a binary signed with the com.apple.security.cs.debugger entitlement, and even then never for
+// one System Integrity Protection covers, nor for a hardened runtime app without
+// com.apple.security.get-task-allow. A process's own task port needs neither
+
+// Longest path proc_pidpath and proc_regionfilename are asked for
+const PATH_LIMIT: usize = libc::PROC_PIDPATHINFO_MAXSIZE as usize;
+
+#[derive(Debug)]
+struct Task {
+    port: mach_port_t,
+}
+
+impl Drop for Task {
+    fn drop(&mut self) {
+        if self.port != unsafe { mach_task_self() } {
+            unsafe { mach_port_deallocate(mach_task_self(), self.port) };
+        }
+    }
+}
+
+// The errnos the rest of the library tells failures apart by, so that retries and lock failures
+// work the same as on Linux
+fn errno(kern: kern_return_t) -> Errno {
+    match kern {
+        KERN_INVALID_ADDRESS | KERN_PROTECTION_FAILURE => Errno::EFAULT,
+        KERN_INVALID_TASK | KERN_INVALID_NAME | KERN_TERMINATED => Errno::ESRCH,
+        KERN_NO_ACCESS => Errno::EACCES,
+        _ => Errno::EIO,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Process {
+    pid: Pid,
+    task: Option<Arc<Task>>,
+    offline: Option<Arc<OfflineCapture>>,
+}
+
+impl Process {
+    pub fn attach(pid: Pid) -> Result<Process, Box<dyn std::error::Error>> {
+        let port = match pid == Pid::this() {
+            true => unsafe { mach_task_self() },
+            false => {
+                let mut port = MACH_PORT_NULL;
+                let kern = unsafe { task_for_pid(mach_task_self(), pid.as_raw(), &mut port) };
+                if kern != KERN_SUCCESS {
+                    return Err(refused(pid, kern));
+                }
+                port
+            }
+        };
+        Ok(Process { pid, task: Some(Arc::new(Task { port })), offline: None })
+    }
+
+    // The calling process itself, which a process may always open
+    pub fn current() -> Result<Process, Box<dyn std::error::Error>> {
+        Process::attach(Pid::this())
+    }
+
+    pub fn with_backend(pid: Pid, backend: MemBackend) -> Result<Process, Box<dyn std::error::Error>> {
+        match backend {
+            MemBackend::Mach => Process::attach(pid),
+            _ => Err(backend.unavailable()),
+        }
+    }
+
+    pub fn seize(_pid: Pid) -> Result<Process, Box<dyn std::error::Error>> {
+        Err("Seizing a process needs Linux's ptrace".into())
+    }
+
+    // Reads a capture written by dump all, possibly on another machine, instead of a live process
+    pub fn offline(path: &Path) -> Result<Process, Box<dyn std::error::Error>> {
+        let capture = OfflineCapture::open(path)?;
+        Ok(Process { pid: capture.pid(), task: None, offline: Some(Arc::new(capture)) })
+    }
+
+    pub fn backend(&self) -> MemBackend {
+        match self.offline {
+            Some(_) => MemBackend::Offline,
+            None => MemBackend::Mach,
+        }
+    }
+
+    pub fn is_seized(&self) -> bool {
+        false
+    }
+
+    // A task port allows writing whenever it allows reading
+    pub fn is_read_only(&self) -> bool {
+        self.task.is_none()
+    }
+
+    fn port(&self) -> Result<mach_port_t, Box<dyn std::error::Error>> {
+        Ok(self.task.as_ref().ok_or("Expected a task port")?.port)
+    }
+}
+
+// task_for_pid fails with KERN_FAILURE whatever the reason, so what is likely is told apart by
+// whether the process exists and whether this one runs as root
+fn refused(pid: Pid, kern: kern_return_t) -> Box<dyn std::error::Error> {
+    if unsafe { libc::kill(pid.as_raw(), 0) } != 0 && Errno::last() == Errno::ESRCH {
+        return format!("Could not attach to process {}: {}", pid, Errno::ESRCH).into();
+    }
+    let reason = match unsafe { libc::geteuid() } {
+        0 => "Running as root, so the process is most likely protected by System Integrity Protection, or uses the hardened runtime without the com.apple.security.get-task-allow entitlement",
+        _ => "Run memory as root, or sign it with the com.apple.security.cs.debugger entitlement. Processes protected by System Integrity Protection, and hardened runtime ones without the com.apple.security.get-task-allow entitlement, refuse even then",
+    };
+    format!("task_for_pid refused process {} (kern_return_t {}). {}", pid, kern, reason).into()
+}
+
+impl ProcessMemory for Process {
+    fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    // mach_vm_read_overwrite reads all or nothing, so a read that fails is tried again a page at
+    // a time to return what it got before the fault
+    fn read_at(&self, address: usize, buffer: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        if let Some(offline) = &self.offline {
+            return offline.read_at(address, buffer);
+        }
+        let port = self.port()?;
+        let error = match read(port, address, buffer) {
+            Ok(()) => return Ok(buffer.len()),
+            Err(e) => e,
+        };
+        let page = page_size();
+        let mut read_so_far = 0;
+        while read_so_far < buffer.len() {
+            let at = address + read_so_far;
+            let len = (page - at % page).min(buffer.len() - read_so_far);
+            if read(port, at, &mut buffer[read_so_far..read_so_far + len]).is_err() {
+                break;
+            }
+            read_so_far += len;
+        }
+        match read_so_far {
+            0 => Err(error.into()),
+            _ => Ok(read_so_far),
+        }
+    }
+
+    fn write_at(&self, address: usize, data: &[u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        if let Some(offline) = &self.offline {
+            return offline.write_at(address, data);
+        }
+        let len = data.len().min(mach_msg_type_number_t::MAX as usize);
+        let kern = unsafe { mach_vm_write(self.port()?, address as mach_vm_address_t, data.as_ptr() as usize, len as mach_msg_type_number_t) };
+        if kern != KERN_SUCCESS {
+            return Err(errno(kern).into());
+        }
+        Ok(len)
+    }
+
+    fn memory_regions(&self) -> Result<Vec<MemoryRegion>, Box<dyn std::error::Error>> {
+        match &self.offline {
+            Some(offline) => offline.memory_regions(),
+            None => memory_regions(self.pid, self.port()?),
+        }
+    }
+
+    fn executable_path(&self) -> Option<String> {
+        if let Some(offline) = &self.offline {
+            return offline.executable_path();
+        }
+        let mut path = vec![0u8; PATH_LIMIT];
+        match unsafe { libc::proc_pidpath(self.pid.as_raw(), path.as_mut_ptr() as *mut c_void, path.len() as u32) } {
+            len if len > 0 => Some(String::from_utf8_lossy(&path[..len as usize]).into_owned()),
+            _ => None,
+        }
+    }
+
+    // Not found out on macOS yet
+    fn thread_stacks(&self) -> Vec<(Pid, usize)> {
+        match &self.offline {
+            Some(offline) => offline.thread_stacks(),
+            None => Vec::new(),
+        }
+    }
+}
+
+fn read(port: mach_port_t, address: usize, buffer: &mut [u8]) -> Result<(), Errno> {
+    let mut read = 0;
+    match unsafe { mach_vm_read_overwrite(port, address as mach_vm_address_t, buffer.len() as mach_vm_size_t, buffer.as_mut_ptr() as mach_vm_address_t, &mut read) } {
+        KERN_SUCCESS => Ok(()),
+        kern => Err(errno(kern)),
+    }
+}
+
+// Every mapped region, in address order. Submaps such as the dyld shared cache come back as one
+// region each rather than recursed into. A region backed by a file gets its path from
+// proc_regionfilename, so modules are found the same way as on Linux
+fn memory_regions(pid: Pid, port: mach_port_t) -> Result<Vec<MemoryRegion>, Box<dyn std::error::Error>> {
+    let mut regions = Vec::new();
+    let mut address: mach_vm_address_t = 0;
+    let mut path = vec![0u8; PATH_LIMIT];
+    loop {
+        let mut size: mach_vm_size_t = 0;
+        let mut info = vm_region_basic_info_64::default();
+        let mut count = vm_region_basic_info_64::count();
+        let mut object = MACH_PORT_NULL;
+        // Finds the first region at or above the address, and fails past the last one
+        match unsafe { mach_vm_region(port, &mut address, &mut size, VM_REGION_BASIC_INFO_64, &mut info as *mut vm_region_basic_info_64 as vm_region_info_t, &mut count, &mut object) } {
+            KERN_SUCCESS => {}
+            KERN_INVALID_ADDRESS => break,
+            kern => return Err(errno(kern).into()),
+        }
+        let start = address as usize;
+        let end = start.saturating_add(size as usize);
+        let protection = info.protection;
+        let len = unsafe { libc::proc_regionfilename(pid.as_raw(), address, path.as_mut_ptr() as *mut c_void, path.len() as u32) };
+        let pathname = match len {
+            len if len > 0 => String::from_utf8_lossy(&path[..len as usize]).into_owned(),
+            _ => String::new(),
+        };
+        regions.push(MemoryRegion {
+            start,
+            end,
+            readable: protection & VM_PROT_READ != 0,
+            writable: protection & VM_PROT_WRITE != 0,
+            executable: protection & VM_PROT_EXECUTE != 0,
+            shared: info.shared != 0,
+            offset: if pathname.is_empty() { 0 } else { info.offset as usize },
+            pathname,
+            ..MemoryRegion::default()
+        });
+        if end <= start {
+            break;
+        }
+        address = end as mach_vm_address_t;
+    }
+    Ok(regions)
+}
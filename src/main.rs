@@ -1,196 +1,5872 @@
-use std::{collections::HashMap, fmt::Display, fs::File, io::{IoSlice, IoSliceMut, Read}, sync::{atomic::AtomicBool, Arc, RwLock}, thread::JoinHandle};
-use nix::{sys::uio::{process_vm_readv, RemoteIoVec, process_vm_writev}, unistd::Pid};
-use rayon::prelude::*;
+use std::{cell::RefCell, collections::BTreeMap, io::{IsTerminal, Write}, path::{Path, PathBuf}, str::FromStr, sync::{Arc, atomic::{AtomicBool, AtomicI32, Ordering}, mpsc::{Receiver, Sender}}, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+use memory::{AddressDescription, AddressExpression, AddressNames, AlertCondition, Arithmetic, AutoSnapshotOptions, AutoSnapshotter, BrowseColumn, BrowseLayout, BrowseMove, Browser, BudgetFallback, Candidates, Capture, ChainSearch, check_rounded, CheatEntry, CheatGroup, CheatTable, Compression, DEFAULT_CHAIN_DEPTH, DEFAULT_LOCK_INTERVAL, DEFAULT_MONITOR_INTERVAL, DEFAULT_GLOB_RUN, DEFAULT_PAIR_DISTANCE, DEFAULT_POINTER_OFFSET, DEFAULT_RANGE_LOCK_LIMIT, DEFAULT_BROWSE_INTERVAL, DEFAULT_BROWSE_LAYOUT, DEFAULT_BROWSE_ROWS, Derived, DerivedValue, Disassembled, DisplayFormat, Encoding, Endianness, Errno, Expression, ExpressionCallback, ExpressionChange, ExpressionMonitor, FieldConstraint, FlagConvention, InitialValues, Reading, Journal, JournalEntry, JournalFile, JournalRecord, Key, LOCK_DEAD_AFTER, LockAction, LockEntry, LockFailureCallback, LockManager, LockStatus, MachineMessage, MapsChange, MemBackend, MemoryRegion, Module, MonitorCallback, MonitorChange, MonitorEntry, NOP, Pad, PairDistance, Patch, Pid, PointerChain, PointerMap, PointerSearch, Process, ProcessMemory, ProgressCallback, RegionCache, RegionFilter, Residency, Retry, SAVED_VALUE_LIMIT, SCAN_CHUNK_SIZE, SavedAddress, SavedLock, SavedMark, SavedPatch, SavedResult, SavedWrite, Scalar, ScanOptions, ScanPriority, ScanProgress, ScanStats, ScanmemSession, ServeAddress, Server, SessionFile, Snapshot, StringWriteOptions, StringSearch, StructPattern, TypedValue, UNKNOWN_LIST_LIMIT, ValueMonitor, ValueType, WatchedExpression, diff_captures, diff_captures_by, diff_processes, diff_processes_by, disassemble, dump_all, dump_to_file, encode_string, find_bit, find_flags, find_masked, find_module, find_pair, find_pointers_to, find_rounded, find_string, find_struct, find_value, guess_types, decode_keys, intersect_chains, parse_hex_bytes, parse_offset, parse_scalar, patch_bytes, priority_error, read_bytes_from_process, read_bytes_into, read_many_bytes, read_many_in, read_scalar, read_string_lossy, read_string_slot, reduce_by_expression, reduce_decreased_by_percent, reduce_found_bits, reduce_found_flags, reduce_found_masked, reduce_found_rounded, reduce_found_structs, reduce_found_values, reduce_increased_by_percent, reduce_rounded_change, reduce_to_initial, resolve_pointer_chain, rounds_to, slow_scan_bytes, strip_endianness, with_scan_type, within_regions, write_bits, write_many, write_to_process};
 
-fn read_from_process<T: Default>(pid: Pid, address: usize) -> Result<T, Box<dyn std::error::Error>> {
-    let mut output: T = T::default();
-    let buffer: &mut [u8] = unsafe {
-        std::slice::from_raw_parts_mut((&mut output as *mut T) as *mut u8, std::mem::size_of::<T>())
-    };
-    let local_binding = IoSliceMut::new(buffer);
-    let remote_binding = RemoteIoVec{ base: address, len: std::mem::size_of::<T>() };
-    process_vm_readv(pid, &mut [local_binding], &[remote_binding])?;
-    Ok(output)
+// Regions above this size are worth pointing out, since they usually dominate scan time
+const LARGE_REGION_HINT: usize = 4 << 30;
+const LIST_LIMIT: usize = 100;
+const PREVIEW_LIMIT: usize = 16;
+// Changed ranges shown per region by snapshot diff
+const DIFF_LIMIT: usize = 16;
+// Snapshots autosnap start keeps unless given --keep
+const AUTOSNAP_KEEP: usize = 10;
+// Regions heatmap lists unless given --top, how long it may take, and how wide its bars are
+const HEATMAP_LIMIT: usize = 20;
+const HEATMAP_TIME: Duration = Duration::from_secs(5);
+const HEATMAP_BAR_WIDTH: usize = 32;
+// Longest string shown when listing results as strings
+const STRING_LIMIT: usize = 256;
+// Most scan results put in an exported cheat table
+const EXPORT_LIMIT: usize = 1000;
+// Percentage points either side a rescan by percent allows unless told otherwise
+const PERCENT_TOLERANCE: f64 = 1.0;
+// How often autorescan rescans, and the count it stops at once a pass leaves it unchanged, unless
+// given --every and --below
+const AUTORESCAN_INTERVAL: Duration = Duration::from_secs(2);
+const AUTORESCAN_BELOW: usize = 10;
+// Bytes guess reads, enough for every numeric type and a short string
+const GUESS_BYTES: usize = 32;
+// A pointer scan stops once it has found this many chains
+const CHAIN_LIMIT: usize = 10000;
+// How far either side of when the monitor saw a change a journaled write over it may be, beyond
+// the monitor's interval, for the change to be put down to the write and not alerted on
+const OWN_WRITE_WINDOW: Duration = Duration::from_millis(250);
+// How often the target is checked for having exited or exec'd
+#[cfg(target_os = "linux")]
+const MONITOR_INTERVAL: Duration = Duration::from_millis(500);
+// How long the session has to save and clean up after a SIGTERM or SIGHUP before it exits anyway,
+// so a target that hangs in a detach or a restore cannot keep it from exiting
+#[cfg(unix)]
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Set by --machine, where everything goes to standard output as MachineMessage lines
+static MACHINE: AtomicBool = AtomicBool::new(false);
+// Set while browse has the terminal, whose input then arrives as keys rather than lines
+static BROWSE_KEYS: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    // What the command running on this thread has printed, while --machine collects it
+    static CAPTURED: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
 }
 
-fn read_bytes_from_process(pid: Pid, bytes: usize, address: usize) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    let mut output: Vec<u8> = Vec::with_capacity(bytes);
-    output.resize(bytes, 0);
-    let local_binding = IoSliceMut::new(&mut output);
-    let remote_binding = RemoteIoVec{ base: address, len: bytes };
-    process_vm_readv(pid, &mut [local_binding], &[remote_binding])?;
-    Ok(output)
+// Everything the REPL prints goes through say!, so --machine can turn it into a command's output
+// or, when no command is running, a message event
+macro_rules! say {
+    ($($arg:tt)*) => { say(format!($($arg)*)) };
 }
 
-fn write_to_process<T>(pid: Pid, address: usize, to_write: &mut T) -> Result<(), Box<dyn std::error::Error>> {
-    let local_binding = IoSlice::new(unsafe {
-        std::slice::from_raw_parts((to_write as *mut T) as *mut u8, std::mem::size_of::<T>())
+fn say(text: String) {
+    let uncaptured = CAPTURED.with_borrow_mut(|captured| match captured {
+        Some(lines) => {
+            lines.extend(text.lines().map(|x| x.to_string()));
+            None
+        }
+        None => Some(text),
     });
-    let remote_binding = RemoteIoVec{ base: address, len: std::mem::size_of::<T>() };
-    process_vm_writev(pid, &[local_binding], &[remote_binding])?;
-    Ok(())
-}
-
-fn get_possible_memory_ranges(pid: Pid) -> Result<Vec<(usize, usize)>, Box<dyn std::error::Error>> {
-    let mut mem_maps_file = File::open(format!("/proc/{}/maps", pid))?;
-    let mut ranges: Vec<(usize, usize)> = Vec::new();
-    let mut mem_maps: String = String::new();
-    mem_maps_file.read_to_string(&mut mem_maps)?;
-    const MEMORY_EMPTY_ERR: &'static str = "Expected no line in memory map to be empty";
-    const MEMORY_RANGE_ERR: &'static str = "Expected each memory region to have address ranges";
-    for line in mem_maps.lines() {
-        let label = line.split_whitespace().last().ok_or(MEMORY_EMPTY_ERR)?;
-        let flags: _;
-        {
-            let mut iter = line.split_whitespace();
-            iter.next().ok_or(MEMORY_EMPTY_ERR)?;
-            flags = iter.next().ok_or::<&str>("Expected each line in memory map to contain memory flags".into())?;
-        }
-        if flags.contains('r') {
-            let range = line.split_whitespace().next().ok_or(MEMORY_EMPTY_ERR)?.split_once('-').ok_or(MEMORY_RANGE_ERR)?;
-            let lower = usize::from_str_radix(range.0, 16)?;
-            let higher = usize::from_str_radix(range.1, 16)?;
-            ranges.push((lower, higher));
-        }
-    }
-    Ok(ranges)
-}
-
-fn find_value<T: PartialEq + Send + Sync>(pid: Pid, value: T) -> Result<Vec<usize>, Box<dyn std::error::Error>> {
-    let ranges = get_possible_memory_ranges(pid)?;
-    let found: Arc<RwLock<Vec<usize>>> = Arc::new(RwLock::new(Vec::new()));
-    ranges.par_iter().for_each(|x| {
-        let base_address = x.0;
-        let num_bytes = x.1 - x.0;
-        // Copy the entire memory region, and then iterate over it
-        let data: Result<Vec<u8>, Box<dyn std::error::Error>> = read_bytes_from_process(pid, num_bytes, base_address);
-        if data.is_err() {
-            // TODO: error report maybe?
+    match uncaptured {
+        Some(text) if MACHINE.load(Ordering::Relaxed) => MachineMessage::Message { text }.emit(),
+        // After a hangup there is no terminal to print to, and that must not stop the clean-up
+        Some(text) => {
+            let _ = writeln!(std::io::stdout(), "{}", text);
         }
-        else {
-            data.unwrap().par_iter().enumerate().for_each(|(offset, x)| {
-                let address = base_address + offset;
-                // If we cannot read the required number of bytes, then do not attempt to
-                if offset + std::mem::size_of::<T>() >= num_bytes {}
-                else {
-                    let pointer = (x as *const u8) as *const T;
-                    unsafe {
-                        let data = &*pointer;
-                        if *data == value {
-                            found.write().unwrap().push(address);
-                        }
-                    }
-                }
-            });
+        None => {}
+    }
+}
+
+// Runs the command, and with --machine answers it with a result carrying what it printed
+fn run_line(session: &mut Session, line: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    if !MACHINE.load(Ordering::Relaxed) {
+        return run_command(session, line);
+    }
+    CAPTURED.set(Some(Vec::new()));
+    let result = run_command(session, line);
+    let output = CAPTURED.take().unwrap_or_default();
+    MachineMessage::result(line.trim(), output, result.as_ref().map(|_| ()).map_err(|e| e.as_ref())).emit();
+    // The error is in the result, so there is nothing left for the caller to report
+    Ok(result.unwrap_or(true))
+}
+
+// Everything the REPL acts on arrives through one channel, whether typed or from a hotkey
+enum Input {
+    Line(String),
+    #[cfg_attr(not(feature = "hotkeys"), allow(dead_code))]
+    Key(String),
+    // Requests from running scripts, answered between commands
+    #[cfg(feature = "scripting")]
+    Script(memory::script::ScriptRequest),
+    // Ctrl-C while something is watched
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    Interrupted,
+    // The target exited or exec'd, or something that may replace it started
+    #[cfg(target_os = "linux")]
+    Target(memory::TargetChange),
+    // Time for autorescan's next pass
+    AutoRescan,
+    // A value the monitor saw change
+    Monitor(MonitorChange),
+    // A watched expression that now works out to something else
+    Expression(ExpressionChange),
+    // Time for browse to read its window again
+    Browse,
+    // Keys pressed while browse has the terminal
+    BrowseKey(Key),
+    // SIGTERM or SIGHUP, which end the session as quit does
+    #[cfg(unix)]
+    Terminated,
+    Closed,
+}
+
+struct Session {
+    process: Process,
+    scan_type: ValueType,
+    results: Vec<usize>,
+    // The byte order the results were found in, which rescans and listings keep using
+    scan_endianness: Endianness,
+    // Of the scan that found the results, updated by each rescan
+    stats: ScanStats,
+    // Started by scan unknown, which the results come from once there are few enough candidates
+    unknown: Option<memory::UnknownScan>,
+    // The results' values when they were first found or listed, for rescan initial
+    initial: Option<InitialValues>,
+    // Their values when a scan or rescan last read them, for rescan expr
+    last: Option<InitialValues>,
+    autorescan: Option<AutoRescan>,
+    // Started by monitor start over the marked results and the locks
+    value_monitor: Option<Monitoring>,
+    // Every expression watchexpr has been given, polled together
+    expressions: Option<ExpressionMonitor>,
+    browsing: Option<Browsing>,
+    // The columns browse shows unless given --layout
+    browse_layout: BrowseLayout,
+    // What each filter left out, most recent last, for filter undo
+    unfiltered: Vec<Unfiltered>,
+    // Tagged and starred results by address, so they stay marked as long as rescans keep them
+    marks: BTreeMap<usize, Mark>,
+    // What on was stored as for the last scan flag, which rescan flag goes on with
+    flag_convention: FlagConvention,
+    // The last scan struct's, for rescan struct
+    struct_pattern: Option<StructPattern>,
+    // The last scan masked's, which rescan masked checks with unless given another
+    scan_mask: Option<u64>,
+    // The chain each lock made through one was made through, by the address it led to
+    lock_chains: BTreeMap<usize, PointerChain>,
+    // The session file last loaded and where from, which resolve all finds again in the process
+    saved: Option<(SessionFile, String)>,
+    // Taken by snapshot, for heatmap to compare with
+    snapshot: Option<(Snapshot, SystemTime)>,
+    // Snapshots taken on a timer by autosnap start, listed until the next start even once stopped
+    autosnap: Option<memory::AutoSnapshotter>,
+    options: ScanOptions,
+    locks: LockManager<Process>,
+    lock_interval: Duration,
+    // The longest block lock range freezes without --force
+    range_lock_limit: usize,
+    // Active patches in the order they were made, all restored on exit
+    patches: Vec<Patch<Process>>,
+    // Every write and poke, and the value each lock replaced when it was created
+    journal: Journal,
+    // Given --journal or journal file, where every change to the target is appended as it is made
+    journal_file: Option<JournalFile>,
+    // The line being run, which the journal file records each change against
+    command: String,
+    regions: RegionCache,
+    // Whether addresses inside modules are shown as module+offset
+    relative_addresses: bool,
+    // How list, locks and history show numbers unless given --fmt or --precision
+    display: DisplayFormat,
+    // Key name to the command it runs
+    bindings: BTreeMap<String, String>,
+    input: Sender<Input>,
+    hotkeys_started: bool,
+    #[cfg(feature = "scripting")]
+    scripts: Vec<RunningScript>,
+    #[cfg(feature = "scripting")]
+    next_script: usize,
+    // Started by the first watchwrite or break and dropped, detaching, once nothing is left armed
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    watcher: Option<memory::Watcher>,
+    // Frames unwound for each hit, and shown by bt
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    backtrace_depth: usize,
+    // Changes each watch keeps, 0 for none
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    watch_history: usize,
+    // Given --dangerous, which make_writable, alloc and free need
+    dangerous: bool,
+    // Pages make_writable changed with --restore, put back on exit
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    protections: Vec<memory::Reprotection>,
+    // Memory alloc mapped into the target, which maps marks
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    allocations: Vec<Allocation>,
+    // Code caves and the hooks into them, all removed on exit
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    injections: Vec<memory::Injection>,
+    // Set by stop, since a traced target shows as in a tracing stop rather than stopped
+    #[cfg_attr(windows, allow(dead_code))]
+    stopped: bool,
+    // Set by kill until the next line, which kills only if it is yes
+    kill_pending: bool,
+    // Given quit --leave-stopped, which keeps a stopped target stopped after the session
+    leave_stopped: bool,
+    // Watching the target for the monitor thread, swapped for a new one on reattaching
+    #[cfg(target_os = "linux")]
+    monitor: Arc<std::sync::Mutex<Option<memory::TargetMonitor>>>,
+    // Given --follow or follow on, which reattaches to whatever replaces the target without asking
+    #[cfg(target_os = "linux")]
+    follow: bool,
+    // Set when a process is offered until the next line, which reattaches to it if empty
+    #[cfg(target_os = "linux")]
+    reattach_pending: Option<Pid>,
+    // The targets attach added besides the one in use, by pid
+    targets: BTreeMap<Pid, Target>,
+}
+
+// Everything of the session that belongs to one target, for those attached alongside the one in
+// use, which use swaps in for it. Their locks and watches go on meanwhile; autorescan and the value
+// monitor follow the prompt, so they stop once another target is used
+struct Target {
+    process: Process,
+    scan_type: ValueType,
+    results: Vec<usize>,
+    scan_endianness: Endianness,
+    stats: ScanStats,
+    unknown: Option<memory::UnknownScan>,
+    initial: Option<InitialValues>,
+    last: Option<InitialValues>,
+    unfiltered: Vec<Unfiltered>,
+    marks: BTreeMap<usize, Mark>,
+    flag_convention: FlagConvention,
+    struct_pattern: Option<StructPattern>,
+    scan_mask: Option<u64>,
+    lock_chains: BTreeMap<usize, PointerChain>,
+    saved: Option<(SessionFile, String)>,
+    snapshot: Option<(Snapshot, SystemTime)>,
+    autosnap: Option<memory::AutoSnapshotter>,
+    locks: LockManager<Process>,
+    patches: Vec<Patch<Process>>,
+    journal: Journal,
+    regions: RegionCache,
+    stopped: bool,
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    watcher: Option<memory::Watcher>,
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    protections: Vec<memory::Reprotection>,
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    allocations: Vec<Allocation>,
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    injections: Vec<memory::Injection>,
+}
+
+// Started by monitor start, with what it does when a value changes that this session did not change
+// or a watched expression comes to meet one of its conditions
+struct Monitoring {
+    // None where it was only given conditions
+    monitor: Option<ValueMonitor>,
+    conditions: Vec<AlertCondition>,
+    beep: bool,
+    // A command of this session run for each alert, as a key binding runs one
+    run: Option<String>,
+    alerts: usize,
+    suppressed: usize,
+}
+
+// Started by autorescan, whose timer asks for a pass at most once until it has been run, so
+// passes slower than the interval never queue up behind one another
+struct AutoRescan {
+    // The rescan's arguments, e.g. "increased" or "decreased 25%"
+    comparison: String,
+    below: usize,
+    passes: usize,
+    // Left after the last pass
+    last: Option<usize>,
+    due: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+struct Mark {
+    tag: Option<String>,
+    starred: bool,
+}
+
+// The results before a filter, and after it so that filter undo can tell whether anything has
+// replaced them since
+struct Unfiltered {
+    results: Vec<usize>,
+    after: Vec<usize>,
+    candidates: Option<Candidates>,
+}
+
+impl Drop for AutoRescan {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+// Started by browse, with a timer like autorescan's
+struct Browsing {
+    browser: Browser,
+    every: Duration,
+    // Keys and a redrawn screen, rather than lines in and out
+    terminal: bool,
+    // The command a key started and what has been typed for it so far
+    prompt: Option<(&'static str, String)>,
+    // What the last command said, shown under the window
+    message: Option<String>,
+    due: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+    // The terminal's settings before browse took it over
+    #[cfg(unix)]
+    saved: Option<libc::termios>,
+}
+
+// Hands the terminal back as it was however browsing ends
+impl Drop for Browsing {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if self.terminal {
+            BROWSE_KEYS.store(false, Ordering::SeqCst);
+            #[cfg(unix)]
+            if let Some(saved) = &self.saved {
+                unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, saved) };
+            }
+            print!("\x1b[?1049l");
+            let _ = std::io::stdout().flush();
         }
-    });
-    Ok(Arc::into_inner(found).unwrap().into_inner().unwrap())
+    }
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+struct Allocation {
+    address: usize,
+    len: usize,
+    prot: i32,
+    free_on_exit: bool,
+}
+
+#[cfg(feature = "scripting")]
+struct RunningScript {
+    id: usize,
+    name: String,
+    stop: Arc<AtomicBool>,
+    thread: std::thread::JoinHandle<()>,
+}
+
+// "bit5" is bit 5, counted LSB-first as in read_bits
+fn parse_bit(s: &str) -> Option<u32> {
+    s.strip_prefix("bit")?.parse().ok()
+}
+
+fn parse_size(s: &str) -> Result<usize, Box<dyn std::error::Error>> {
+    let s = s.trim();
+    let (digits, multiplier) = match s.chars().last().map(|x| x.to_ascii_uppercase()) {
+        Some('K') => (&s[..s.len() - 1], 1usize << 10),
+        Some('M') => (&s[..s.len() - 1], 1 << 20),
+        Some('G') => (&s[..s.len() - 1], 1 << 30),
+        Some('T') => (&s[..s.len() - 1], 1 << 40),
+        _ => (s, 1),
+    };
+    Ok(digits.parse::<usize>()?.checked_mul(multiplier).ok_or("Size is too large")?)
+}
+
+// MB a second, MB being 1 << 20 bytes as format_bytes has it, for ScanOptions::throttle: 0 is
+// unthrottled
+fn parse_throttle(s: &str) -> Result<u64, Box<dyn std::error::Error>> {
+    let rate = s.strip_suffix("MB/s").unwrap_or(s).parse::<f64>().ok().filter(|x| x.is_finite() && *x >= 0.0);
+    Ok((rate.ok_or(format!("Expected a rate in MB/s, not '{}'", s))? * (1u64 << 20) as f64) as u64)
+}
+
+// Accepts ns, us, ms, s and m suffixes, e.g. "16ms" or "1.5s"
+fn parse_duration(s: &str) -> Result<Duration, Box<dyn std::error::Error>> {
+    let s = s.trim();
+    let split = s.find(|x: char| !x.is_ascii_digit() && x != '.').ok_or(format!("Expected a unit (ns, us, ms, s or m) in duration '{}'", s))?;
+    let (number, unit) = s.split_at(split);
+    let number = number.parse::<f64>()?;
+    let seconds = match unit {
+        "ns" => number / 1e9,
+        "us" => number / 1e6,
+        "ms" => number / 1e3,
+        "s" => number,
+        "m" => number * 60.0,
+        _ => return Err(format!("Unknown duration unit '{}', expected ns, us, ms, s or m", unit).into()),
+    };
+    Ok(Duration::try_from_secs_f64(seconds)?)
+}
+
+fn parse_toggle(s: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    match s {
+        "on" | "true" | "yes" => Ok(true),
+        "off" | "false" | "no" => Ok(false),
+        _ => Err(format!("Expected on or off, got '{}'", s).into()),
+    }
+}
+
+// Counts too large to read at a glance, as 38.2M
+fn format_count(count: usize) -> String {
+    match count {
+        0..1_000 => count.to_string(),
+        1_000..1_000_000 => format!("{:.1}K", count as f64 / 1e3),
+        1_000_000..1_000_000_000 => format!("{:.1}M", count as f64 / 1e6),
+        _ => format!("{:.1}G", count as f64 / 1e9),
+    }
+}
+
+fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[unit])
+    }
+    else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+// Results and tags as the address expressions a command is given name them
+struct SessionNames<'a> {
+    results: &'a [usize],
+    marks: &'a BTreeMap<usize, Mark>,
 }
 
-fn find_value_by_predicate<T>(pid: Pid, predicate: fn(&T) -> bool) -> Result<Vec<usize>, Box<dyn std::error::Error>> {
-    let ranges = get_possible_memory_ranges(pid)?;
-    let found: Arc<RwLock<Vec<usize>>> = Arc::new(RwLock::new(Vec::new()));
-    ranges.par_iter().for_each(|x| {
-        let base_address = x.0;
-        let num_bytes = x.1 - x.0;
-        // Copy the entire memory region, and then iterate over it
-        let data: Result<Vec<u8>, Box<dyn std::error::Error>> = read_bytes_from_process(pid, num_bytes, base_address);
-        if data.is_err() {
-            // TODO: error report maybe?
+impl AddressNames for SessionNames<'_> {
+    fn result(&self, index: usize) -> Result<usize, String> {
+        self.results.get(index).copied().ok_or(format!("No result with index {}", index))
+    }
+
+    fn tag(&self, name: &str) -> Option<usize> {
+        self.marks.iter().find(|x| x.1.tag.as_deref() == Some(name)).map(|x| *x.0)
+    }
+}
+
+// Any address expression: a result index ("#3"), a tagged result ("@hp"), an absolute address, a
+// module-relative one ("libgame.so+0x2a10"), a pointer chain, or arithmetic on them with brackets
+// to follow a pointer ("[@player + 0x10] + 0x4")
+fn parse_address(session: &mut Session, s: &str) -> Result<usize, Box<dyn std::error::Error>> {
+    let expression = s.parse::<AddressExpression>()?;
+    let names = SessionNames { results: &session.results, marks: &session.marks };
+    expression.evaluate(&session.process, &mut session.regions, &names)
+}
+
+// A value with an optional "be:" or "le:" prefix, which overrides the session's byte order
+fn parse_value<T: Scalar + FromStr>(session: &Session, s: &str) -> Result<(T, Endianness), Box<dyn std::error::Error>> where T::Err: std::fmt::Display {
+    let (endianness, value) = strip_endianness(s);
+    Ok((parse_scalar::<T>(value)?, endianness.unwrap_or(session.options.endianness)))
+}
+
+// "~100" for the floats a UI would show as 100, which can be given a byte order as "be:~100". None
+// for any other value
+fn parse_shown(s: &str) -> Result<Option<(i64, Option<Endianness>)>, String> {
+    let (endianness, value) = strip_endianness(s);
+    let Some(shown) = value.strip_prefix('~') else {
+        return Ok(None);
+    };
+    let shown = shown.parse::<i64>().map_err(|_| format!("Expected the integer shown after ~, got '{}'", shown))?;
+    Ok(Some((shown, endianness)))
+}
+
+// An address inside a module is shown module-relative, the form that can be typed back in a later
+// run, followed by where it is now. Anything else is shown absolute, followed by what it points into
+fn format_address(session: &mut Session, address: usize) -> String {
+    match session.regions.describe(address) {
+        AddressDescription::Module { name, offset } if session.relative_addresses => format!("{}+0x{:x} (0x{:x})", name, offset, address),
+        description => format!("0x{:x} ({})", address, description),
+    }
+}
+
+// Inside a module, the symbol the address is in where the module has symbols, as
+// libgame.so!Player::take_damage+0x24. Anything else is described as it is everywhere else
+fn describe_symbol(regions: &mut RegionCache, address: usize) -> String {
+    match regions.describe(address) {
+        AddressDescription::Module { .. } => memory::locate(regions.modules(), address).to_string(),
+        description => description.to_string(),
+    }
+}
+
+// Its address, bytes and text, then what a call or jump target or rip-relative operand is in
+fn format_instruction(regions: &mut RegionCache, instruction: &Disassembled) -> String {
+    let line = format!("0x{:x}  {:<29} {}", instruction.address, format_hex(&instruction.bytes), instruction.text);
+    match instruction.target {
+        Some(target) => format!("{}  ; {}", line, describe_symbol(regions, target)),
+        None => line,
+    }
+}
+
+// Every way of reading the bytes at the address, the most sensible first, with what is unlikely
+// about the rest. Reads less than GUESS_BYTES where the mapping ends sooner
+fn guess(session: &mut Session, address: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let mut bytes = vec![0; GUESS_BYTES];
+    let read = read_bytes_into(&session.process, address, &mut bytes).map_err(|e| format!("Could not read 0x{:x}: {}", address, e))?;
+    bytes.truncate(read);
+    session.regions.refresh()?;
+    say!("{}: {}", format_address(session, address), format_hex(&bytes));
+    for guess in guess_types(&bytes, session.regions.regions(), session.options.endianness) {
+        let (value_type, shown) = match &guess.reading {
+            // Denormals and the like would otherwise run to hundreds of digits
+            Reading::Value(TypedValue::F32(x)) if guess.note.is_some() => ("f32".to_string(), format!("{:e}", x)),
+            Reading::Value(TypedValue::F64(x)) if guess.note.is_some() => ("f64".to_string(), format!("{:e}", x)),
+            Reading::Value(value) => (value.value_type().to_string(), value.format_value()),
+            Reading::Pointer { target, .. } => ("ptr".to_string(), format_address(session, *target)),
+            Reading::Text(text) => ("str".to_string(), format!("{:?}", text)),
+        };
+        match guess.note {
+            Some(note) => say!("{:>3}  {:<4} {}  ({})", guess.score, value_type, shown, note),
+            None => say!("{:>3}  {:<4} {}", guess.score, value_type, shown),
         }
-        else {
-            data.unwrap().par_iter().enumerate().for_each(|(offset, x)| {
-                let address = base_address + offset;
-                // If we cannot read the required number of bytes, then do not attempt to
-                if offset + std::mem::size_of::<T>() >= num_bytes {}
-                else {
-                    let pointer = (x as *const u8) as *const T;
-                    unsafe {
-                        let data = &*pointer;
-                        if predicate(data) {
-                            found.write().unwrap().push(address);
-                        }
-                    }
-                }
-            });
+    }
+    Ok(())
+}
+
+// pointers <address> [--min-offset <offset>] [--max-offset <offset>] [--aligned] [--static]
+// Lists what points near the address, each with the chain from the pointer to it. The offsets are
+// signed hex as in a chain, 0 to DEFAULT_POINTER_OFFSET by default
+fn find_pointers(session: &mut Session, arguments: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut arguments = arguments.to_vec();
+    let min_offset = take_option(&mut arguments, "--min-offset")?.map(parse_offset).transpose()?;
+    let max_offset = take_option(&mut arguments, "--max-offset")?.map(parse_offset).transpose()?;
+    let aligned = take_flag(&mut arguments, "--aligned");
+    let static_only = take_flag(&mut arguments, "--static");
+    let [address] = arguments[..] else {
+        return Err("Usage: pointers <address> [--min-offset <offset>] [--max-offset <offset>] [--aligned] [--static]".into());
+    };
+    let target = parse_address(session, address)?;
+    let search = PointerSearch { min_offset: min_offset.unwrap_or(0), max_offset: max_offset.unwrap_or(DEFAULT_POINTER_OFFSET), aligned, static_only };
+    warn_slow_scan(session)?;
+    let (hits, stats) = find_pointers_to(&session.process, target, &search, &session.options)?;
+    session.regions.refresh()?;
+    for hit in hits.iter().take(LIST_LIMIT) {
+        let chain = hit.chain(session.regions.modules());
+        let offset = if hit.offset < 0 { format!("-0x{:x}", hit.offset.unsigned_abs()) } else { format!("+0x{:x}", hit.offset) };
+        say!("{} -> 0x{:x} {}  {}", format_address(session, hit.location), hit.pointer, offset, chain);
+    }
+    if hits.len() > LIST_LIMIT {
+        say!("... {} more", hits.len() - LIST_LIMIT);
+    }
+    say!("{} pointers to {} in {:.2}s", hits.len(), format_address(session, target), stats.elapsed.as_secs_f64());
+    Ok(())
+}
+
+fn build_pointer_map(session: &mut Session, file: &str) -> Result<(), Box<dyn std::error::Error>> {
+    warn_slow_scan(session)?;
+    let (map, stats) = PointerMap::build(&session.process, &session.options)?;
+    map.save(Path::new(file))?;
+    say!("{} pointers in {} scanned, saved to {} with the layout of {} modules ({:.2}s)", format_count(map.pointers.len()), format_bytes(stats.bytes_scanned), file, map.modules().len(), stats.elapsed.as_secs_f64());
+    Ok(())
+}
+
+// A saved map moved to where the target's modules are now, so addresses typed in the session say
+// the same thing to it whichever run it was built in
+fn open_pointer_map(session: &mut Session, file: &str) -> Result<PointerMap, Box<dyn std::error::Error>> {
+    let mut map = PointerMap::open(Path::new(file))?;
+    session.regions.refresh()?;
+    map.rebase(session.regions.modules());
+    Ok(map)
+}
+
+// pointerscan <address> [<map> [<other map> <address there>]] [--depth <n>] [--min-offset <offset>] [--max-offset <offset>]
+// Lists the chains from a pointer stored in a module to the address, shortest first. With no map
+// one is built of the target as it is now. Given a second map, from another run of the target,
+// only the chains that also lead to the address there are kept
+fn pointer_scan(session: &mut Session, arguments: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut arguments = arguments.to_vec();
+    let depth = take_option(&mut arguments, "--depth")?.map(|x| x.parse::<usize>()).transpose()?.unwrap_or(DEFAULT_CHAIN_DEPTH);
+    let min_offset = take_option(&mut arguments, "--min-offset")?.map(parse_offset).transpose()?.unwrap_or(0);
+    let max_offset = take_option(&mut arguments, "--max-offset")?.map(parse_offset).transpose()?.unwrap_or(DEFAULT_POINTER_OFFSET);
+    if min_offset > max_offset {
+        return Err(format!("The lowest offset, {}, is above the highest, {}", min_offset, max_offset).into());
+    }
+    let (address, map, other) = match arguments[..] {
+        [address] => (address, None, None),
+        [address, map] => (address, Some(map), None),
+        [address, map, other, other_address] => (address, Some(map), Some((other, other_address))),
+        _ => return Err("Usage: pointerscan <address> [<map> [<other map> <address there>]] [--depth <n>] [--min-offset <offset>] [--max-offset <offset>]".into()),
+    };
+    let target = parse_address(session, address)?;
+    let map = match map {
+        Some(file) => open_pointer_map(session, file)?,
+        None => {
+            warn_slow_scan(session)?;
+            PointerMap::build(&session.process, &session.options)?.0
         }
-    });
-    Ok(Arc::into_inner(found).unwrap().into_inner().unwrap())
+    };
+    let start = Instant::now();
+    let mut chains = map.find_chains(target, &ChainSearch { min_offset, max_offset, depth, limit: CHAIN_LIMIT });
+    let found = chains.len();
+    if let Some((file, other_address)) = other {
+        let other_target = parse_address(session, other_address)?;
+        chains = intersect_chains(chains, &open_pointer_map(session, file)?, other_target);
+    }
+    for chain in chains.iter().take(LIST_LIMIT) {
+        say!("{}", chain);
+    }
+    if chains.len() > LIST_LIMIT {
+        say!("... {} more", chains.len() - LIST_LIMIT);
+    }
+    let limited = if found == CHAIN_LIMIT { format!(", stopping at {}", CHAIN_LIMIT) } else { String::new() };
+    match other {
+        Some((file, _)) => say!("{} of {} chains at most {} deep also lead there in {}{} ({:.2}s)", chains.len(), found, depth, file, limited, start.elapsed().as_secs_f64()),
+        None => say!("{} chains at most {} deep{} ({:.2}s)", chains.len(), depth, limited, start.elapsed().as_secs_f64()),
+    }
+    Ok(())
 }
 
-fn reduce_found_values<T: Default + PartialEq + Send + Sync>(pid: Pid, found_values: &mut Vec<usize>, value: T) -> Result<(), Box<dyn std::error::Error>> {
-    let to_remove: Arc<RwLock<Vec<usize>>> = Arc::new(RwLock::new(Vec::with_capacity(found_values.len())));
-    found_values.par_iter().enumerate().for_each(|(index, address)| {
-        let read_value: Result<T, _> = read_from_process(pid, *address);
-        match read_value {
-            Ok(x) => {
-                if x != value {
-                    to_remove.write().unwrap().push(index);
-                }
+// Stops early at memory that cannot be read or at bytes that are not an instruction, after
+// printing what came before
+fn print_disassembly(session: &mut Session, address: usize, count: usize) -> Result<(), Box<dyn std::error::Error>> {
+    session.process.arch().require_x86_64("disasm")?;
+    // No instruction is longer than 15 bytes
+    let mut code = vec![0; count.saturating_mul(15).min(1 << 20)];
+    let wanted = code.len();
+    let read = read_bytes_into(&session.process, address, &mut code).map_err(|e| format!("Could not read 0x{:x}: {}", address, e))?;
+    code.truncate(read);
+    let mut at = 0;
+    for _ in 0..count {
+        match disassemble(&code[at..], address + at) {
+            Ok(instruction) => {
+                say!("{}", format_instruction(&mut session.regions, &instruction));
+                at += instruction.bytes.len();
+            }
+            Err(_) if read < wanted && code.len() - at < 15 => {
+                say!("0x{:x}  cannot be read", address + at);
+                break;
+            }
+            Err(e) => {
+                say!("0x{:x}  {:<29} (bad: {})", address + at, format_hex(&code[at..(at + 1).min(code.len())]), e);
+                break;
             }
-            Err(_) => {}
         }
-    });
-    to_remove.write().unwrap().par_sort();
-    for i in to_remove.read().unwrap().iter().rev() {
-        found_values.remove(*i);
     }
     Ok(())
 }
 
-fn reduce_found_values_by_predicate<T: Default>(pid: Pid, found_values: &mut Vec<usize>, predicate: fn(&T) -> bool) -> Result<(), Box<dyn std::error::Error>> {
-    let to_remove: Arc<RwLock<Vec<usize>>> = Arc::new(RwLock::new(Vec::with_capacity(found_values.len())));
-    found_values.par_iter().enumerate().for_each(|(index, address)| {
-        let read_value: Result<T, _> = read_from_process(pid, *address);
-        match read_value {
-            Ok(x) => {
-                if !predicate(&x) {
-                    to_remove.write().unwrap().push(index);
-                }
-            }
-            Err(_) => {}
+// Splits on whitespace, except that a double-quoted word may contain spaces. The quotes are dropped
+fn split_words(line: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut rest = line.trim_start();
+    while !rest.is_empty() {
+        let (word, remainder) = match rest.strip_prefix('"') {
+            Some(quoted) => match quoted.find('"') {
+                Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                None => (quoted, ""),
+            },
+            None => rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len())),
+        };
+        words.push(word);
+        rest = remainder.trim_start();
+    }
+    words
+}
+
+fn format_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|x| format!("{:02x}", x)).collect::<Vec<String>>().join(" ")
+}
+
+// Hex followed by printable ASCII, cut off after PREVIEW_LIMIT bytes
+fn format_bytes_preview(bytes: &[u8]) -> String {
+    let shown = &bytes[..bytes.len().min(PREVIEW_LIMIT)];
+    let hex = format_hex(shown);
+    let ascii = shown.iter().map(|x| if x.is_ascii_graphic() || *x == b' ' { *x as char } else { '.' }).collect::<String>();
+    let ellipsis = if bytes.len() > PREVIEW_LIMIT { " ..." } else { "" };
+    format!("{}{} |{}|", hex, ellipsis, ascii)
+}
+
+fn format_lock_value(lock: &LockEntry, format: DisplayFormat) -> String {
+    match lock.type_name.parse::<ValueType>() {
+        Ok(value_type) if value_type.is_numeric() => value_type.value_from_bytes(&lock.value_bytes).map(|x| x.format_as(format)).unwrap_or_default(),
+        _ => format!("{} bytes {}", lock.value_bytes.len(), format_bytes_preview(&lock.value_bytes)),
+    }
+}
+
+fn format_lock_status(lock: &LockEntry) -> String {
+    match (lock.status(), lock.last_errno, lock.remaining()) {
+        (status @ (LockStatus::Failing | LockStatus::Dead), Some(errno), _) => format!("{}: {}", status, errno),
+        (status @ (LockStatus::Running | LockStatus::Paused), _, Some(remaining)) => format!("{}, {:.1}s left", status, remaining.as_secs_f64()),
+        (status, _, _) => status.to_string(),
+    }
+}
+
+fn print_lock_failure(address: usize, status: LockStatus, errno: Option<Errno>) {
+    if MACHINE.load(Ordering::Relaxed) {
+        return MachineMessage::LockFailure { address: format!("0x{:x}", address), status: status.to_string(), error: errno.map(|x| x.desc().to_string()) }.emit();
+    }
+    let cause = errno.map(|x| format!(" ({})", x.desc())).unwrap_or_default();
+    match status {
+        LockStatus::Dead => eprintln!("\nwarning: lock at 0x{:x} failed {} times in a row{} and has stopped", address, LOCK_DEAD_AFTER, cause),
+        _ => eprintln!("\nwarning: lock at 0x{:x} is failing{}", address, cause),
+    }
+}
+
+fn print_progress(progress: &ScanProgress) {
+    if MACHINE.load(Ordering::Relaxed) {
+        return MachineMessage::Progress { bytes_scanned: progress.bytes_scanned, bytes_total: progress.bytes_total, rate: progress.rate }.emit();
+    }
+    let percent = if progress.bytes_total == 0 { 100.0 } else { progress.bytes_scanned as f64 * 100.0 / progress.bytes_total as f64 };
+    let throttle = progress.throttle.map(|x| format!(", throttled to {}/s", format_bytes(x as usize))).unwrap_or_default();
+    eprint!("\r\x1b[Kscanning {:.1}% ({} / {}) at {}/s{}", percent, format_bytes(progress.bytes_scanned), format_bytes(progress.bytes_total), format_bytes(progress.rate as usize), throttle);
+}
+
+fn describe_backend(process: &Process) -> String {
+    match process.is_seized() {
+        true if process.backend() == MemBackend::Ptrace => "ptrace, peeking and poking a word at a time as its tracer (seized; it keeps running and is detached on exit)".to_string(),
+        true => format!("{} as its tracer (seized with ptrace; it keeps running and is detached on exit)", process.backend()),
+        false => process.backend().to_string(),
+    }
+}
+
+// Before a scan that has to go a word at a time through ptrace
+fn warn_slow_scan(session: &Session) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(bytes) = slow_scan_bytes(&session.process, &session.options)? {
+        say!("warning: the {} backend reads a word per system call, so scanning {} will take a long time; narrow it down first with e.g. `set filter heap` or `set filter module:<name> and writable`", session.process.backend(), format_bytes(bytes));
+    }
+    Ok(())
+}
+
+fn print_rescan_summary(session: &Session) {
+    if let Some(change) = &session.stats.maps_change {
+        print_maps_change(change);
+        if session.options.drop_unmapped {
+            say!("dropped {} results no longer in a readable region", session.stats.dropped_unmapped);
         }
-    });
-    to_remove.write().unwrap().par_sort();
-    for i in to_remove.read().unwrap().iter().rev() {
-        found_values.remove(*i);
+        else {
+            say!("note: results may now point somewhere else; `set drop_unmapped on` drops those left unmapped");
+        }
+    }
+    if session.stats.retried > 0 {
+        say!("{} results could only be read after retrying", session.stats.retried);
+    }
+    print_read_failures(&session.stats);
+    say!("{} matches", session.results.len());
+    if session.stats.partial {
+        say!("note: these results came from a partial scan and may be missing addresses");
+    }
+}
+
+fn print_scan_summary(session: &Session, stats: &ScanStats) {
+    if session.options.progress.is_some() && !MACHINE.load(Ordering::Relaxed) {
+        eprint!("\r\x1b[K");
+    }
+    say!("scanned {} in {:.1} s ({}/s), {} matches", format_bytes(stats.bytes_scanned), stats.elapsed.as_secs_f64(), format_bytes(stats.throughput() as usize), stats.matches);
+    print_read_failures(stats);
+    if !stats.bytes_by_category.is_empty() {
+        let categories = stats.bytes_by_category.iter().map(|(category, bytes)| format!("{} {}", category, format_bytes(*bytes))).collect::<Vec<String>>();
+        say!("by category: {}", categories.join(", "));
+    }
+    match stats.residency {
+        Some(Residency::Unavailable) => say!("note: could not read pagemap or smaps, so non-resident pages were scanned too"),
+        Some(residency) => say!("skipped {} not resident in RAM (from {})", format_bytes(stats.bytes_not_resident), residency),
+        None => {}
+    }
+    print_fallbacks(&stats.fallbacks);
+    if stats.partial {
+        say!("stopped after {} matches having covered {:.1}% of memory; results are partial", session.results.len(), stats.coverage() * 100.0);
+    }
+    if !stats.skipped_regions.is_empty() {
+        say!("skipped {} regions ({}) above max_region_size", stats.skipped_regions.len(), format_bytes(stats.bytes_skipped()));
+    }
+    else if session.options.max_region_size.is_none() && stats.largest_region > LARGE_REGION_HINT {
+        say!("note: the largest region is {}; `set max_region_size 4G` (or --max-region-size) skips regions like it", format_bytes(stats.largest_region));
     }
+}
+
+// The regions the last scan or rescan could not read, by how many failed with each error
+fn print_read_failures(stats: &ScanStats) {
+    if stats.read_failures.is_empty() {
+        return;
+    }
+    let mut errors: Vec<(&str, usize)> = Vec::new();
+    for failure in &stats.read_failures {
+        match errors.iter_mut().find(|x| x.0 == failure.error) {
+            Some(error) => error.1 += 1,
+            None => errors.push((&failure.error, 1)),
+        }
+    }
+    errors.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    let mut regions = stats.read_failures.iter().map(|x| x.region).collect::<Vec<(usize, usize)>>();
+    regions.dedup();
+    let errors = errors.iter().map(|(error, count)| format!("{}x {}", count, error)).collect::<Vec<String>>();
+    say!("skipped {} regions / {}: {}; run `errors` for details", regions.len(), format_bytes(stats.bytes_lost()), errors.join(", "));
+}
+
+// errors
+// Lists what the last scan or rescan could not read, a line per region and error
+fn list_read_failures(session: &Session) {
+    if session.stats.read_failures.is_empty() {
+        say!("the last scan or rescan read everything it tried to");
+        return;
+    }
+    let regions = session.process.memory_regions().unwrap_or_default();
+    for failure in &session.stats.read_failures {
+        let (start, end) = failure.region;
+        let name = regions.iter().find(|x| x.contains(start) && !x.pathname.is_empty()).map(|x| format!(" {}", x.pathname)).unwrap_or_default();
+        say!("0x{:x}-0x{:x}{}: {}, {} lost", start, end, name, failure.error, format_bytes(failure.bytes_lost));
+    }
+    say!("{} lost in all", format_bytes(session.stats.bytes_lost()));
+}
+
+// What a scan or snapshot did to keep within --mem-budget
+fn print_fallbacks(fallbacks: &[BudgetFallback]) {
+    for fallback in fallbacks {
+        match fallback {
+            BudgetFallback::SmallerChunks(size) => say!("warning: read in {} chunks rather than {} to keep the scan's buffers within the memory budget", format_bytes(*size), format_bytes(SCAN_CHUNK_SIZE)),
+            BudgetFallback::TruncatedResults(kept) => say!("warning: stopped collecting results at {} to stay within the memory budget, so they are partial", format_count(*kept)),
+            BudgetFallback::Spilled(bytes) => say!("warning: kept {} of the snapshot on disk to stay within the memory budget", format_bytes(*bytes)),
+        }
+    }
+}
+
+// scan unknown [<type>] [--stacks-only|--exclude-stacks]
+// Stores every value of the type, the session's if none is given, for rescans to compare with
+fn scan_unknown(session: &mut Session, arguments: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let (scan_type, scope) = match arguments.split_first().map(|x| (x.0.parse::<ValueType>(), x.1)) {
+        Some((Ok(scan_type), scope)) => (scan_type, scope),
+        _ => (session.scan_type, arguments),
+    };
+    if scope.len() > 1 {
+        return Err("Expected scan unknown [<type>] [--stacks-only|--exclude-stacks]".into());
+    }
+    let options = scan_scope(&session.options, scope)?;
+    let size = with_scan_type!(scan_type, T, { T::SIZE });
+    warn_slow_scan(session)?;
+    let unknown = memory::UnknownScan::start(&session.process, size, &options)?;
+    let snapshot = unknown.snapshot();
+    say!("stored {} in {} chunks as {}", format_bytes(snapshot.len()), snapshot.chunks.len(), format_bytes(snapshot.stored_len()));
+    print_fallbacks(&snapshot.fallbacks);
+    if !snapshot.failed_regions.is_empty() {
+        say!("{} regions could not be read", snapshot.failed_regions.len());
+    }
+    say!("candidates: ~{} (region-level)", format_count(unknown.candidates()));
+    session.scan_type = scan_type;
+    session.scan_endianness = options.endianness;
+    session.results.clear();
+    session.initial = None;
+    session.stats = ScanStats::default();
+    session.unknown = Some(unknown);
     Ok(())
 }
 
-fn lock_value<T: Send + Sync + 'static>(value: T, address: usize, pid: Pid, locks: &mut HashMap<usize, Arc<AtomicBool>>) {
-    let atomic_bool = Arc::new(AtomicBool::new(true));
-    let threads_bool = atomic_bool.clone();
-    std::thread::spawn(move || {
-        let local_binding = IoSlice::new(unsafe {
-            std::slice::from_raw_parts((&value as *const T) as *const u8, std::mem::size_of::<T>())
-        });
-        let remote_binding = RemoteIoVec{ base: address, len: std::mem::size_of::<T>() };
-        while threads_bool.load(std::sync::atomic::Ordering::Relaxed) {
-            let _ = process_vm_writev(pid, &[local_binding], &[remote_binding]);
-            std::thread::sleep(std::time::Duration::from_millis(1));
+// "25%" or "25", with a tolerance of percentage points either side, 1 if none is given
+fn parse_percent_change(percent: &str, tolerance: Option<&str>) -> Result<(f64, f64), Box<dyn std::error::Error>> {
+    let percent = percent.trim_end_matches('%').parse::<f64>()?;
+    let tolerance = tolerance.map(|x| x.trim_end_matches('%').parse::<f64>()).transpose()?.unwrap_or(PERCENT_TOLERANCE);
+    Ok((percent, tolerance / 100.0))
+}
+
+// autorescan <rescan arguments> [--every <duration>] [--below <count>]: the rescan, once every
+// interval until it leaves nothing, or leaves the same count below the threshold twice in a row, or
+// is stopped by an empty line or autorescan stop
+fn start_autorescan(session: &mut Session, arguments: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut arguments = arguments.to_vec();
+    let every = take_option(&mut arguments, "--every")?.map(parse_duration).transpose()?.unwrap_or(AUTORESCAN_INTERVAL);
+    let below = take_option(&mut arguments, "--below")?.map(|x| x.parse::<usize>()).transpose()?.unwrap_or(AUTORESCAN_BELOW);
+    if arguments.is_empty() {
+        return Err("Usage: autorescan <rescan arguments> [--every <duration>] [--below <count>]".into());
+    }
+    if session.results.is_empty() && session.unknown.is_none() {
+        return Err("There is nothing to rescan, start with a scan".into());
+    }
+    if every.is_zero() {
+        return Err("Expected an interval longer than 0".into());
+    }
+    let (due, stop) = (Arc::new(AtomicBool::new(false)), Arc::new(AtomicBool::new(false)));
+    let (timer_due, timer_stop, input) = (due.clone(), stop.clone(), session.input.clone());
+    std::thread::spawn(move || loop {
+        std::thread::sleep(every);
+        if timer_stop.load(Ordering::SeqCst) {
+            break;
+        }
+        if !timer_due.swap(true, Ordering::SeqCst) && input.send(Input::AutoRescan).is_err() {
+            break;
+        }
+    });
+    let comparison = arguments.join(" ");
+    say!("rescanning {} every {:?} until fewer than {} stop changing, or an empty line", comparison, every, below);
+    // Starting another replaces the one running, whose timer stops as it is dropped
+    session.autorescan = Some(AutoRescan { comparison, below, passes: 0, last: None, due, stop });
+    Ok(())
+}
+
+fn stop_autorescan(session: &mut Session, why: &str) {
+    if let Some(autorescan) = session.autorescan.take() {
+        say!("autorescan stopped at pass {}, {}", autorescan.passes, why);
+    }
+}
+
+// One pass, run as if the rescan had been typed
+fn autorescan_pass(session: &mut Session) {
+    let Some(autorescan) = session.autorescan.as_mut() else {
+        return;
+    };
+    autorescan.passes += 1;
+    let command = format!("rescan {}", autorescan.comparison);
+    say!("[autorescan {}] {}", autorescan.passes, command);
+    if let Err(e) = run_command(session, &command) {
+        say!("error: {}", e);
+        stop_autorescan(session, "as the rescan failed");
+        return;
+    }
+    let left = match (&session.unknown, session.results.is_empty()) {
+        (Some(unknown), true) => unknown.candidates(),
+        _ => session.results.len(),
+    };
+    let autorescan = session.autorescan.as_mut().unwrap();
+    let stable = autorescan.last == Some(left) && left < autorescan.below;
+    autorescan.last = Some(left);
+    // Passes due while this one ran are dropped rather than run back to back
+    autorescan.due.store(false, Ordering::SeqCst);
+    if left == 0 {
+        stop_autorescan(session, "with nothing left");
+    }
+    else if stable {
+        stop_autorescan(session, &format!("with {} left twice in a row", left));
+    }
+}
+
+// browse <address> [--layout "<columns>"] [--rows <count>] [--every <duration>]: a window of memory
+// around the address, read again every interval. In a terminal it takes keystrokes, and otherwise
+// lines naming them, so that it can be driven from a script or a pipe
+fn start_browse(session: &mut Session, arguments: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut arguments = arguments.to_vec();
+    let layout = take_option(&mut arguments, "--layout")?.map(|x| x.parse::<BrowseLayout>()).transpose()?.unwrap_or_else(|| session.browse_layout.clone());
+    let rows = take_option(&mut arguments, "--rows")?.map(|x| x.parse::<usize>()).transpose()?.unwrap_or(DEFAULT_BROWSE_ROWS);
+    let every = take_option(&mut arguments, "--every")?.map(parse_duration).transpose()?.unwrap_or(DEFAULT_BROWSE_INTERVAL);
+    let [address] = arguments[..] else {
+        return Err("Usage: browse <address> [--layout \"offset 4xi32 2xf32 ascii\"] [--rows <count>] [--every <duration>]".into());
+    };
+    if rows == 0 || every.is_zero() {
+        return Err("browse needs at least one row and an interval longer than 0".into());
+    }
+    let address = parse_address(session, address)?;
+    let (due, stop) = (Arc::new(AtomicBool::new(false)), Arc::new(AtomicBool::new(false)));
+    let (timer_due, timer_stop, input) = (due.clone(), stop.clone(), session.input.clone());
+    std::thread::spawn(move || loop {
+        std::thread::sleep(every);
+        if timer_stop.load(Ordering::SeqCst) {
+            break;
+        }
+        if !timer_due.swap(true, Ordering::SeqCst) && input.send(Input::Browse).is_err() {
+            break;
         }
     });
-    locks.insert(address, atomic_bool);
+    // Keys need the terminal out of canonical mode, which is done through termios
+    let terminal = cfg!(unix) && std::io::stdin().is_terminal() && std::io::stdout().is_terminal() && !MACHINE.load(Ordering::Relaxed);
+    #[cfg(unix)]
+    let saved = if terminal { keystroke_mode() } else { None };
+    let browser = Browser::new(address, layout, rows);
+    session.browsing = Some(Browsing {
+        browser,
+        every,
+        terminal,
+        prompt: None,
+        message: None,
+        due,
+        stop,
+        #[cfg(unix)]
+        saved,
+    });
+    if terminal {
+        BROWSE_KEYS.store(true, Ordering::SeqCst);
+        print!("\x1b[?1049h");
+    }
+    else {
+        say!("browsing {} every {:?}; up, down, left, right, pgup and pgdn move, w <value> writes, t <name> tags, lock locks and q leaves", format_address(session, address), every);
+    }
+    draw_browser(session, true);
+    Ok(())
 }
 
-fn unlock_value(address: usize, locks: &mut HashMap<usize, Arc<AtomicBool>>) {
-    let atomic_bool = locks.get(&address);
-    match atomic_bool {
-        Some(x) => {
-            x.store(false, std::sync::atomic::Ordering::Relaxed);
-            locks.remove(&address);
+// Takes the terminal out of canonical mode and echo, so keys arrive as they are pressed, returning
+// how it was to put it back
+#[cfg(unix)]
+fn keystroke_mode() -> Option<libc::termios> {
+    let mut termios = unsafe { std::mem::zeroed::<libc::termios>() };
+    if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) } != 0 {
+        return None;
+    }
+    let saved = termios;
+    termios.c_lflag &= !(libc::ICANON | libc::ECHO);
+    termios.c_cc[libc::VMIN] = 1;
+    termios.c_cc[libc::VTIME] = 0;
+    unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) };
+    Some(saved)
+}
+
+fn stop_browse(session: &mut Session) {
+    if let Some(browsing) = session.browsing.take() && !browsing.terminal {
+        say!("stopped browsing");
+    }
+}
+
+// Reads the window again on the timer. Without a terminal to redraw, it is only printed again if
+// something in it changed
+fn browse_refresh(session: &mut Session) -> bool {
+    let Some(browsing) = session.browsing.as_mut() else {
+        return false;
+    };
+    browsing.due.store(false, Ordering::SeqCst);
+    let terminal = browsing.terminal;
+    draw_browser(session, terminal) && !terminal
+}
+
+// A keystroke while browsing in a terminal. w and t start a prompt for the value or tag, which
+// Enter finishes and Escape abandons
+fn browse_key(session: &mut Session, key: Key) -> bool {
+    let Some(browsing) = session.browsing.as_mut() else {
+        return false;
+    };
+    let command = match (browsing.prompt.as_mut(), key) {
+        (Some((_, text)), Key::Char(c)) => {
+            text.push(c);
+            None
         }
-        None => {}
+        (Some((_, text)), Key::Backspace) => {
+            text.pop();
+            None
+        }
+        (Some(_), Key::Escape) => {
+            browsing.prompt = None;
+            None
+        }
+        (Some(_), Key::Enter) => browsing.prompt.take().map(|(command, text)| format!("{} {}", command, text)),
+        (Some(_), _) => None,
+        (None, Key::Char('w')) => {
+            browsing.prompt = Some(("w", String::new()));
+            None
+        }
+        (None, Key::Char('t')) => {
+            browsing.prompt = Some(("t", String::new()));
+            None
+        }
+        (None, Key::Char('L')) => Some("lock".to_string()),
+        (None, Key::Char('q') | Key::Escape) => Some("q".to_string()),
+        (None, Key::Move(movement)) => {
+            browsing.browser.move_cursor(movement);
+            None
+        }
+        (None, _) => None,
+    };
+    if let Some(command) = command {
+        browse_command(session, &command);
+    }
+    if session.browsing.is_some() {
+        draw_browser(session, true);
     }
+    false
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = std::env::args().collect::<Vec<String>>();
-    let pid = Pid::from_raw(args[1].parse::<i32>()?);
-    let mut buffer: String = String::new();
-    let stdin = std::io::stdin();
-    stdin.read_line(&mut buffer)?;
-    let mut locks: HashMap<usize, Arc<AtomicBool>> = HashMap::new();
+// A line while browsing, or a key turned into one
+fn browse_command(session: &mut Session, line: &str) {
+    let words = split_words(line);
+    let browsing = session.browsing.as_mut().unwrap();
+    let movement = match words[..] {
+        ["up"] => Some(BrowseMove::Up),
+        ["down"] => Some(BrowseMove::Down),
+        ["left"] => Some(BrowseMove::Left),
+        ["right"] => Some(BrowseMove::Right),
+        ["pgup"] => Some(BrowseMove::PageUp),
+        ["pgdn"] => Some(BrowseMove::PageDown),
+        _ => None,
+    };
+    if let Some(movement) = movement {
+        browsing.browser.move_cursor(movement);
+        return;
+    }
+    if let ["q" | "quit"] = words[..] {
+        return stop_browse(session);
+    }
+    let Some((address, value_type)) = browsing.browser.cursor_cell() else {
+        return;
+    };
+    let result = match words[..] {
+        ["w", value] => run_command(session, &format!("write 0x{:x} {}:{}", address, value_type, value)).map(|_| format!("wrote {} {} at 0x{:x}", value_type, value, address)),
+        ["t", tag] => {
+            // Only results can be tagged, so the value becomes one
+            if let Err(index) = session.results.binary_search(&address) {
+                session.results.insert(index, address);
+            }
+            session.marks.entry(address).or_default().tag = Some(tag.to_string());
+            Ok(format!("tagged 0x{:x} as {}, which @{} now names", address, tag, tag))
+        }
+        ["lock"] => run_command(session, &format!("lock 0x{:x} {}", address, value_type)).map(|_| format!("locked 0x{:x} as the {} it holds", address, value_type)),
+        _ => Err("While browsing: up, down, left, right, pgup, pgdn, w <value>, t <name>, lock or q".into()),
+    };
+    let message = result.unwrap_or_else(|e| format!("error: {}", e));
+    match session.browsing.as_mut() {
+        Some(browsing) if browsing.terminal => browsing.message = Some(message),
+        _ => say!("{}", message),
+    }
+}
+
+// Reads the window and shows it, returning whether anything in it changed since the last read.
+// A terminal is redrawn in place with the cursor's value reversed and changed ones in bold; lines
+// printed instead put the cursor's in brackets and a star after each changed one
+fn draw_browser(session: &mut Session, always: bool) -> bool {
+    let (endianness, format) = (session.options.endianness, session.display);
+    let browsing = session.browsing.as_mut().unwrap();
+    let rows = browsing.browser.refresh(&session.process, endianness, format);
+    let changed = rows.iter().any(|x| x.cells.iter().any(|x| x.changed));
+    if !always && !changed {
+        return false;
+    }
+    let (browser, terminal) = (&browsing.browser, browsing.terminal);
+    let cursor = browser.cursor_cell().map(|x| x.0);
+    let cells = browser.layout.cells().len();
+    let widths = (0..cells).map(|x| rows.iter().map(|row| row.cells[x].text.as_ref().map_or(2, |x| x.len())).max().unwrap_or(0)).collect::<Vec<usize>>();
+    let mut lines = Vec::with_capacity(rows.len() + 2);
+    for row in &rows {
+        let mut line = format!("0x{:x}", row.address);
+        let mut cell = 0;
+        for column in &browser.layout.columns {
+            match column {
+                BrowseColumn::Offset => line.push_str(&format!(" {}", format_offset(row.offset))),
+                BrowseColumn::Ascii => line.push_str(&format!(" |{}|", row.bytes.as_ref().map_or_else(|| "?".repeat(browser.layout.stride()), |x| x.iter().map(|x| if x.is_ascii_graphic() || *x == b' ' { *x as char } else { '.' }).collect()))),
+                BrowseColumn::Values { count, .. } => {
+                    for value in &row.cells[cell..cell + count] {
+                        let text = format!("{:>width$}", value.text.as_deref().unwrap_or("??"), width = widths[cell]);
+                        line.push_str(&match (terminal, cursor == Some(value.address), value.changed) {
+                            (true, true, _) => format!(" \x1b[7m{}\x1b[0m", text),
+                            (true, false, true) => format!(" \x1b[1m{}\x1b[0m", text),
+                            (true, false, false) => format!(" {}", text),
+                            (false, under, changed) => format!("{}{}{}{}", if under { "[" } else { " " }, text, if under { "]" } else { " " }, if changed { "*" } else { " " }),
+                        });
+                        cell += 1;
+                    }
+                }
+            }
+        }
+        lines.push(line);
+    }
+    if let (false, Some(address)) = (terminal, cursor) {
+        lines.push(format!("cursor at 0x{:x} ({})", address, format_offset(address as i64 - browser.anchor as i64)));
+    }
+    if !terminal {
+        lines.iter().for_each(|x| say!("{}", x));
+        return changed;
+    }
+    let status = match (&browsing.prompt, &browsing.message) {
+        (Some((command, text)), _) => format!("{}: {}", if *command == "w" { "write" } else { "tag" }, text),
+        (None, Some(message)) => message.clone(),
+        (None, None) => String::new(),
+    };
+    let header = format!("0x{:x} as {}, every {:?}; arrows and page keys move, w writes, t tags, L locks, q leaves", browser.anchor, browser.layout, browsing.every);
+    print!("\x1b[H\x1b[2J{}\r\n\r\n{}\r\n\r\n{}", header, lines.join("\r\n"), status);
+    let _ = std::io::stdout().flush();
+    changed
+}
+
+// How far from the address browsed, e.g. +0x0010 or -0x0008
+fn format_offset(offset: i64) -> String {
+    format!("{}0x{:04x}", if offset < 0 { "-" } else { "+" }, offset.unsigned_abs())
+}
+
+// How a result is marked in listings, e.g. ` * "maybe hp"`
+fn format_mark(session: &Session, address: usize) -> String {
+    let Some(mark) = session.marks.get(&address) else {
+        return String::new();
+    };
+    let star = if mark.starred { " *" } else { "" };
+    let tag = mark.tag.as_ref().map(|x| format!(" {:?}", x)).unwrap_or_default();
+    format!("{}{}", star, tag)
+}
+
+// Only results can be marked, and a mark left with neither a tag nor a star is dropped
+fn mark(session: &mut Session, address: &str, change: impl FnOnce(&mut Mark)) -> Result<(), Box<dyn std::error::Error>> {
+    let address = parse_address(session, address)?;
+    if !session.results.contains(&address) {
+        return Err(format!("{} is not one of the results", format_address(session, address)).into());
+    }
+    let mark = session.marks.entry(address).or_default();
+    change(mark);
+    if mark.tag.is_none() && !mark.starred {
+        session.marks.remove(&address);
+    }
+    Ok(())
+}
+
+// After each command, so that a rescan dropping a marked result says so
+fn drop_lost_marks(session: &mut Session) {
+    if session.marks.is_empty() {
+        return;
+    }
+    let lost = session.marks.keys().copied().filter(|x| !session.results.contains(x)).collect::<Vec<usize>>();
+    for address in lost {
+        let mark = format_mark(session, address);
+        session.marks.remove(&address);
+        say!("note: marked result {}{} is no longer a result", format_address(session, address), mark);
+    }
+}
+
+// Keeps the results, and the candidates of an unknown scan, at the addresses keep accepts. Nothing
+// is read from the target, so however many there are this takes no time
+fn filter_results(session: &mut Session, keep: impl Fn(usize) -> bool) -> Result<(), Box<dyn std::error::Error>> {
+    if session.results.is_empty() && session.unknown.is_none() {
+        return Err("There are no results to filter, start with a scan".into());
+    }
+    let results = session.results.clone();
+    session.results.retain(|x| keep(*x));
+    let candidates = match session.unknown.as_mut() {
+        Some(unknown) if results.is_empty() => {
+            let before = unknown.candidates();
+            let candidates = unknown.retain(&keep);
+            // Filtered down far enough to be listed, as a rescan would have
+            if unknown.candidates() <= UNKNOWN_LIST_LIMIT {
+                session.results = unknown.addresses();
+            }
+            say!("kept {} of {} candidates", format_count(unknown.candidates()), format_count(before));
+            Some(candidates)
+        }
+        Some(unknown) => Some(unknown.retain(&keep)),
+        None => None,
+    };
+    if !results.is_empty() {
+        say!("kept {} of {} results", format_count(session.results.len()), format_count(results.len()));
+    }
+    session.unfiltered.push(Unfiltered { results, after: session.results.clone(), candidates });
+    Ok(())
+}
+
+fn unfilter_results(session: &mut Session) -> Result<(), Box<dyn std::error::Error>> {
+    let unfiltered = session.unfiltered.pop().ok_or("There is no filter to undo")?;
+    if session.results != unfiltered.after {
+        session.unfiltered.clear();
+        return Err("The results have changed since the last filter, which can no longer be undone".into());
+    }
+    if let Some(candidates) = unfiltered.candidates {
+        session.unknown.as_mut().ok_or("The unknown scan the filter narrowed is gone")?.restore(candidates)?;
+    }
+    session.results = unfiltered.results;
+    match &session.unknown {
+        Some(unknown) if session.results.is_empty() => say!("back to {} candidates", format_count(unknown.candidates())),
+        _ => say!("back to {} results", format_count(session.results.len())),
+    }
+    Ok(())
+}
+
+// The regions the filter selects, as they are now
+fn filter_regions(session: &mut Session, filter: &RegionFilter) -> Result<Vec<MemoryRegion>, Box<dyn std::error::Error>> {
+    session.regions.refresh()?;
+    Ok(filter.select(&session.process, session.regions.regions()).into_iter().cloned().collect())
+}
+
+fn filter_by_regions(session: &mut Session, regions: &[MemoryRegion]) -> Result<(), Box<dyn std::error::Error>> {
+    let regions = regions.iter().collect::<Vec<&MemoryRegion>>();
+    filter_results(session, |x| within_regions(&regions, x))
+}
+
+// The results' values now, for a new set of them to go back to; bytes and strings have none
+fn initial_values(session: &Session) -> Result<InitialValues, Box<dyn std::error::Error>> {
+    Ok(with_scan_type!(session.scan_type, T, {
+        InitialValues::record::<T>(&session.process, &session.results, session.scan_endianness)
+    }))
+}
+
+fn print_rescan_help() {
+    say!("rescan <value>: keep the results now holding the value");
+    say!("rescan changed|unchanged|increased|decreased: keep scan unknown's candidates that did so since it or the last rescan");
+    say!("  after `set soft_dirty on`, scan unknown clears the target's soft-dirty bits and rescans only read the pages it wrote since, where the kernel tracks them; anything else using the bits, such as CRIU, sees them cleared");
+    say!("rescan increased|decreased <percent>% [<tolerance>%]: keep those that changed by about that much");
+    say!("rescan ~<integer>: keep the floats that round to the integer, as a UI showing them would: 99.5 up to but not including 100.5 for ~100, halves rounding away from zero");
+    say!("  after scan f32|f64 ~<integer>, rescan changed|unchanged|increased|decreased compares the integers shown rather than the floats");
+    say!("rescan initial: keep the results holding what they did when first found or listed");
+    say!("rescan expr \"<expression>\": keep the results now holding what the expression makes of old, their value at the last scan or rescan, e.g. \"old * 2\" or \"(old - 7) / 2\"");
+    say!("  expressions take old, numbers, + - * / and parentheses; for integer types they are worked out exactly, dividing by truncating towards zero (7 / 2 is 3, and -7 / 2 is -3), and a result whose expression overflows or divides by zero is dropped; floats match within a few units in the last place");
+    say!("rescan struct, rescan masked <value> [mask <mask>], rescan flag on|off, rescan <bit> set|clear: check the results of the scan of that kind again");
+}
+
+// The values the results had at the last scan or rescan, for the rescan that works from them
+fn last_values<'a>(last: &'a Option<InitialValues>, rescan: &str) -> Result<&'a InitialValues, String> {
+    last.as_ref().ok_or_else(|| format!("{} works from the values the results had at the last scan or rescan, which are only kept for at most {} results", rescan, format_count(UNKNOWN_LIST_LIMIT)))
+}
+
+// After a scan for the integer a UI shows, keeps the results whose shown integer changed as asked
+// since the last scan or rescan, rather than the float behind it: 99.7 going to 100.2 is unchanged
+fn rescan_rounded_change(session: &mut Session, change: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let keep: fn(f64, f64) -> bool = match change {
+        "changed" => |old, new| new != old && !old.is_nan() && !new.is_nan(),
+        "unchanged" => |old, new| new == old,
+        "increased" => |old, new| new > old,
+        _ => |old, new| new < old,
+    };
+    let last = last_values(&session.last, &format!("rescan {}", change))?;
+    with_scan_type!(session.scan_type, T, {
+        let options = ScanOptions { endianness: session.scan_endianness, ..session.options.clone() };
+        reduce_rounded_change::<T>(&session.process, &mut session.results, last, keep, &options, &mut session.stats)?;
+    });
+    print_rescan_summary(session);
+    Ok(())
+}
+
+// What the results hold once a scan or rescan is done with them, for rescan expr to work from. An
+// unknown scan's candidates keep their own, and past the listing limit none are kept, as reading
+// them all again would double what every rescan of them takes
+fn remember_last_values(session: &mut Session) {
+    session.last = match session.unknown.is_none() && session.results.len() <= UNKNOWN_LIST_LIMIT {
+        true => initial_values(session).ok(),
+        false => None,
+    };
+}
+
+// Narrows scan unknown's candidates by how they changed since it or the last rescan, increased or
+// decreased by a percentage among them, or to those now holding a value, listing them as the
+// results once there are few enough
+fn rescan_unknown(session: &mut Session, comparison: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(unknown) = session.unknown.as_mut() else {
+        return Err(format!("rescan {} compares with the values scan unknown stored, so start with scan unknown", comparison.join(" ")).into());
+    };
+    let unreadable = with_scan_type!(session.scan_type, T, {
+        match *comparison {
+            // A NaN is unequal to everything, but has not changed for that
+            ["changed"] => unknown.narrow::<T>(&session.process, |old, new| new != old && !old.is_nan() && !new.is_nan())?,
+            ["unchanged"] => unknown.narrow::<T>(&session.process, |old, new| new == old)?,
+            ["increased"] => unknown.narrow::<T>(&session.process, |old, new| new > old)?,
+            ["decreased"] => unknown.narrow::<T>(&session.process, |old, new| new < old)?,
+            ["increased", percent, ref tolerance @ ..] if tolerance.len() <= 1 => {
+                let (percent, tolerance) = parse_percent_change(percent, tolerance.first().copied())?;
+                reduce_increased_by_percent::<T>(&session.process, unknown, percent, tolerance)?
+            }
+            ["decreased", percent, ref tolerance @ ..] if tolerance.len() <= 1 => {
+                let (percent, tolerance) = parse_percent_change(percent, tolerance.first().copied())?;
+                reduce_decreased_by_percent::<T>(&session.process, unknown, percent, tolerance)?
+            }
+            [value] if parse_shown(value)?.is_some() => {
+                let (shown, endianness) = parse_shown(value)?.unwrap();
+                if endianness.is_some_and(|x| x != unknown.endianness) {
+                    return Err(format!("The candidates were stored as {} values", unknown.endianness).into());
+                }
+                check_rounded::<T>()?;
+                unknown.narrow::<T>(&session.process, |_, new| rounds_to(new, shown))?
+            }
+            ["expr", expression] => {
+                let expression = Expression::parse::<T>(expression)?;
+                unknown.narrow::<T>(&session.process, |old, new| expression.matches(old, new))?
+            }
+            [value] => {
+                let (endianness, value) = strip_endianness(value);
+                if endianness.is_some_and(|x| x != unknown.endianness) {
+                    return Err(format!("The candidates were stored as {} values", unknown.endianness).into());
+                }
+                let value = parse_scalar::<T>(value)?;
+                unknown.narrow::<T>(&session.process, |_, new| new == value)?
+            }
+            _ => return Err("Usage: rescan changed|unchanged|increased|decreased|initial|<value>, rescan increased|decreased <percent>% [<tolerance>%], or rescan expr \"<expression>\"".into()),
+        }
+    });
+    if unreadable > 0 {
+        say!("{} chunks could no longer be read, and their candidates were dropped", unreadable);
+    }
+    if unknown.unread > 0 {
+        say!("{} on pages not written since were not read again", format_bytes(unknown.unread));
+    }
+    print_fallbacks(&unknown.snapshot().fallbacks);
+    let candidates = unknown.candidates();
+    if candidates > UNKNOWN_LIST_LIMIT {
+        session.results.clear();
+        say!("candidates: {} in {}, listed once there are at most {}", format_count(candidates), format_bytes(unknown.stored_len()), format_count(UNKNOWN_LIST_LIMIT));
+        return Ok(());
+    }
+    session.results = unknown.addresses();
+    if session.initial.is_none() {
+        session.initial = initial_values(session).ok();
+    }
+    say!("{} matches", session.results.len());
+    Ok(())
+}
+
+// A scan given --stacks-only looks at nothing but the threads' stacks, whatever else is left out,
+// and one given --exclude-stacks at everything else, as with --no-stack
+// scan flag [on|off] [--as 1|255|nonzero] [<scope>]: single bytes holding a boolean, in either
+// state unless one is given
+fn scan_flags(session: &mut Session, arguments: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut arguments = arguments.to_vec();
+    let convention = take_option(&mut arguments, "--as")?.map(|x| x.parse::<FlagConvention>()).transpose()?.unwrap_or_default();
+    let state = match arguments.first() {
+        Some(&"on") => Some(true),
+        Some(&"off") => Some(false),
+        _ => None,
+    };
+    if state.is_some() {
+        arguments.remove(0);
+    }
+    if arguments.len() > 1 {
+        return Err("Usage: scan flag [on|off] [--as 1|255|nonzero] [--stacks-only|--exclude-stacks]".into());
+    }
+    let options = scan_scope(&session.options, &arguments)?;
+    warn_slow_scan(session)?;
+    let (results, stats) = find_flags(&session.process, convention, state, &options)?;
+    session.scan_type = ValueType::U8;
+    session.scan_endianness = session.options.endianness;
+    session.flag_convention = convention;
+    session.results = results;
+    session.unknown = None;
+    session.initial = initial_values(session).ok();
+    print_scan_summary(session, &stats);
+    session.stats = stats;
+    Ok(())
+}
+
+// A value or mask in hex, as 0x00ffff00, or decimal
+fn parse_bits(s: &str) -> Result<u64, Box<dyn std::error::Error>> {
+    let parsed = match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse::<u64>(),
+    };
+    Ok(parsed.map_err(|_| format!("Expected a number like 0x123400 or 1193984, got '{}'", s))?)
+}
+
+// The bytes an integer type covers, which is what a masked scan or rescan compares
+fn masked_width(value_type: ValueType) -> Result<usize, Box<dyn std::error::Error>> {
+    match value_type {
+        ValueType::F32 | ValueType::F64 => Err(format!("A mask is over the bits of an integer, not an {}", value_type).into()),
+        value_type => value_type.size().ok_or_else(|| format!("A mask is over the bits of an integer, not {}", value_type).into()),
+    }
+}
+
+// scan masked <type> <value> mask <mask> [--stacks-only|--exclude-stacks]: integers equal to the
+// value in the bits the mask has set, at multiples of the type's size
+fn scan_masked(session: &mut Session, arguments: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let [value_type, value, "mask", mask, scope @ ..] = arguments else {
+        return Err("Usage: scan masked <type> <value> mask <mask> [--stacks-only|--exclude-stacks]".into());
+    };
+    let value_type = value_type.parse::<ValueType>()?;
+    let width = masked_width(value_type)?;
+    let (value, mask) = (parse_bits(value)?, parse_bits(mask)?);
+    let options = scan_scope(&session.options, scope)?;
+    warn_slow_scan(session)?;
+    let (results, stats) = find_masked(&session.process, value, mask, width, &options)?;
+    session.scan_type = value_type;
+    session.scan_endianness = session.options.endianness;
+    session.scan_mask = Some(mask);
+    session.results = results;
+    session.unknown = None;
+    session.initial = initial_values(session).ok();
+    print_scan_summary(session, &stats);
+    session.stats = stats;
+    Ok(())
+}
+
+// scan struct <offset> <type> [<value>|<min>..<max>|*], ... [--align <alignment>]: the addresses
+// of structs whose fields all hold what they should, stepping by the widest field unless told
+// otherwise. The results are listed as the field at offset 0, if there is one
+fn scan_struct(session: &mut Session, arguments: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut arguments = arguments.to_vec();
+    let alignment = take_option(&mut arguments, "--align")?.map(|x| x.parse::<usize>()).transpose()?;
+    if arguments.is_empty() {
+        return Err("Usage: scan struct <offset> <type> [<value>|<min>..<max>|*], ... [--align <alignment>], where <type> can also be ptr".into());
+    }
+    let pattern = arguments.join(" ").parse::<StructPattern>()?;
+    let alignment = alignment.unwrap_or(pattern.alignment()).max(1);
+    let options = ScanOptions { alignment: Some(alignment), ..session.options.clone() };
+    warn_slow_scan(session)?;
+    let (results, stats) = find_struct(&session.process, &pattern, &options)?;
+    session.scan_type = match pattern.fields.iter().find(|x| x.0 == 0).map(|x| &x.1) {
+        Some(FieldConstraint::Equals(value) | FieldConstraint::Range(value, _)) if value.value_type().size().is_some() => value.value_type(),
+        Some(FieldConstraint::Any(value_type)) => *value_type,
+        Some(FieldConstraint::Pointer) => ValueType::U64,
+        _ => ValueType::U8,
+    };
+    session.scan_endianness = session.options.endianness;
+    session.results = results;
+    session.unknown = None;
+    session.initial = initial_values(session).ok();
+    session.struct_pattern = Some(pattern);
+    print_scan_summary(session, &stats);
+    session.stats = stats;
+    Ok(())
+}
+
+// scan pair <type> <value> <type> <value> [--within <bytes>] [--delta <offset>] [--align <alignment>]:
+// the first values with the second near enough to be in the same object. The results are the
+// firsts, or with one delta throughout the lower of each pair, with the two as a struct pattern
+// that rescan struct can check and add the remaining fields to
+fn scan_pair(session: &mut Session, arguments: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut arguments = arguments.to_vec();
+    let alignment = take_option(&mut arguments, "--align")?.map(|x| x.parse::<usize>()).transpose()?;
+    let within = take_option(&mut arguments, "--within")?.map(parse_size).transpose()?;
+    let delta = take_option(&mut arguments, "--delta")?.map(parse_offset).transpose()?;
+    let [first_type, first, second_type, second] = arguments[..] else {
+        return Err("Usage: scan pair <type> <value> <type> <value> [--within <bytes>] [--delta <offset>] [--align <alignment>]".into());
+    };
+    let distance = match (within, delta) {
+        (Some(_), Some(_)) => return Err("--within and --delta both say how far apart the pair is; give one".into()),
+        (_, Some(delta)) => PairDistance::Exactly(delta),
+        (within, None) => PairDistance::Within(within.unwrap_or(DEFAULT_PAIR_DISTANCE)),
+    };
+    let (first_type, second_type) = (first_type.parse::<ValueType>()?, second_type.parse::<ValueType>()?);
+    let (first, second) = (first_type.parse_value(first)?, second_type.parse_value(second)?);
+    let options = ScanOptions { alignment: alignment.or(session.options.alignment), ..session.options.clone() };
+    warn_slow_scan(session)?;
+    let (pairs, stats) = find_pair(&session.process, &first, &second, distance, &options)?;
+    let mut deltas = BTreeMap::<isize, usize>::new();
+    for (_, _, delta) in &pairs {
+        *deltas.entry(*delta).or_default() += 1;
+    }
+    let pattern = |at: (usize, &TypedValue), other: (usize, &TypedValue)| StructPattern { fields: vec![(at.0, FieldConstraint::Equals(at.1.clone())), (other.0, FieldConstraint::Equals(other.1.clone()))] };
+    let (mut results, scan_type, struct_pattern) = match deltas.keys().collect::<Vec<&isize>>()[..] {
+        [delta] if *delta < 0 => (pairs.iter().map(|x| x.1).collect::<Vec<usize>>(), second_type, Some(pattern((0, &second), (delta.unsigned_abs(), &first)))),
+        [delta] => (pairs.iter().map(|x| x.0).collect(), first_type, Some(pattern((0, &first), (*delta as usize, &second)))),
+        _ => (pairs.iter().map(|x| x.0).collect(), first_type, None),
+    };
+    results.dedup();
+    session.scan_type = scan_type;
+    session.scan_endianness = session.options.endianness;
+    session.results = results;
+    session.unknown = None;
+    session.initial = initial_values(session).ok();
+    session.struct_pattern = struct_pattern;
+    print_scan_summary(session, &stats);
+    let mut common = deltas.into_iter().collect::<Vec<(isize, usize)>>();
+    common.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.unsigned_abs().cmp(&b.0.unsigned_abs())));
+    if !common.is_empty() {
+        let shown = common.iter().take(5).map(|(delta, count)| format!("{} ({})", format_offset(*delta as i64), count)).collect::<Vec<String>>();
+        let more = if common.len() > 5 { format!(" and {} more", common.len() - 5) } else { String::new() };
+        say!("{} results; the second is at {}{} from the first", session.results.len(), shown.join(", "), more);
+    }
+    match (session.struct_pattern.is_some(), common.len()) {
+        (true, _) => say!("rescan struct checks both, and rescan struct <offset> <type> <value>, ... adds fields to them"),
+        (false, 0) => {}
+        (false, _) => say!("the results are the first values; with --delta <offset> only pairs that far apart are kept, which rescan struct can then add fields to"),
+    }
+    session.stats = stats;
+    Ok(())
+}
+
+// scan string "<text>" [--glob] [--utf16] [--ignore-case] [--after-nul] [--max-run <chars>] [--align <alignment>]
+fn scan_string(session: &mut Session, arguments: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut arguments = arguments.to_vec();
+    let alignment = take_option(&mut arguments, "--align")?.map(|x| x.parse::<usize>()).transpose()?;
+    let max_run = take_option(&mut arguments, "--max-run")?.map(|x| x.parse::<usize>()).transpose()?;
+    let glob = take_flag(&mut arguments, "--glob");
+    let search = StringSearch {
+        encoding: if take_flag(&mut arguments, "--utf16") { Encoding::Utf16 } else { Encoding::Utf8 },
+        ignore_case: take_flag(&mut arguments, "--ignore-case"),
+        glob,
+        max_run: max_run.unwrap_or(DEFAULT_GLOB_RUN),
+        after_nul: take_flag(&mut arguments, "--after-nul"),
+    };
+    let [needle] = arguments[..] else {
+        return Err("Usage: scan string \"<text>\" [--glob] [--utf16] [--ignore-case] [--after-nul] [--max-run <chars>] [--align <alignment>]".into());
+    };
+    if max_run.is_some() && !glob {
+        return Err("--max-run is how far a * reaches, which only --glob has".into());
+    }
+    let options = ScanOptions { alignment: alignment.or(session.options.alignment), ..session.options.clone() };
+    warn_slow_scan(session)?;
+    let (results, stats) = find_string(&session.process, needle, &search, &options)?;
+    session.scan_type = ValueType::Str { encoding: search.encoding, null_terminate: false };
+    session.scan_endianness = session.options.endianness;
+    session.results = results;
+    session.unknown = None;
+    session.initial = None;
+    print_scan_summary(session, &stats);
+    session.stats = stats;
+    Ok(())
+}
+
+fn scan_scope(options: &ScanOptions, scope: &[&str]) -> Result<ScanOptions, Box<dyn std::error::Error>> {
+    let mut options = options.clone();
+    match scope {
+        [] => {}
+        ["--stacks-only"] => {
+            options.filter = Some(match options.filter.take() {
+                Some(filter) => RegionFilter::And(Box::new(filter), Box::new(RegionFilter::Stack)),
+                None => RegionFilter::Stack,
+            });
+            options.include_stack = true;
+        }
+        ["--exclude-stacks"] => options.include_stack = false,
+        _ => return Err(format!("Unknown scan option '{}', expected --stacks-only or --exclude-stacks", scope.join(" ")).into()),
+    }
+    Ok(options)
+}
+
+// The option including one category of region, by its name in `set include_<name>` and `--no-<name>`
+fn category_toggle<'a>(options: &'a mut ScanOptions, name: &str) -> Option<&'a mut bool> {
+    match name {
+        "heap" => Some(&mut options.include_heap),
+        "stack" => Some(&mut options.include_stack),
+        "anonymous" => Some(&mut options.include_anonymous),
+        "file_backed" => Some(&mut options.include_file_backed),
+        "main_executable" => Some(&mut options.include_main_executable),
+        _ => None,
+    }
+}
+
+fn print_dump(holes: Vec<(usize, usize)>, start: usize, end: usize, file: &str) {
+    say!("dumped 0x{:x}-0x{:x} ({}) to {}", start, end, format_bytes(end - start), file);
+    for (start, end) in holes {
+        say!("    unreadable 0x{:x}-0x{:x}, written as zeroes", start, end);
+    }
+}
+
+// Civil time from days since the epoch, after Howard Hinnant's days_from_civil
+fn format_time(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let days = (seconds / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{}-{:02}-{:02} {:02}:{:02}:{:02} UTC", year, month, day, seconds % 86400 / 3600, seconds % 3600 / 60, seconds % 60)
+}
+
+fn print_capture_info(capture: &Capture) {
+    let header = &capture.header;
+    say!("version {} capture of {} ({}), {}", header.version, header.pid, header.executable.as_deref().unwrap_or("unknown executable"), header.arch);
+    say!("captured {} with {} threads", format_time(header.time), header.stacks.len());
+    let holes = capture.regions.iter().filter(|x| !x.holes.is_empty()).count();
+    say!("{} regions, {} stored as {}, {} with unreadable holes", capture.regions.len(), format_bytes(capture.len()), format_bytes(capture.stored_len() as usize), holes);
+    if !header.uncaptured.is_empty() {
+        say!("{} more mappings recorded without their contents", header.uncaptured.len());
+    }
+    for entry in &capture.regions {
+        let compression = match entry.compression {
+            Compression::None => "",
+            Compression::Lz4 => " lz4",
+        };
+        let holes = if entry.holes.is_empty() { String::new() } else { format!(" ({} holes)", entry.holes.len()) };
+        say!("0x{:x}-0x{:x} {} {:>10}{}{} {}", entry.region.start, entry.region.end, entry.region.permissions(), format_bytes(entry.region.len()), compression, holes, entry.region.pathname);
+    }
+}
+
+// Streams what a scan would read into a capture, which offline mode and Snapshot::load read back.
+// With --stop the target is stopped throughout, for an image of a single moment rather than of
+// regions read one after another as it ran, and continued after unless it was stopped already.
+// Ctrl-C cancels it
+fn snapshot_to_file(session: &mut Session, file: &str, stop_first: bool) -> Result<(), Box<dyn std::error::Error>> {
+    warn_slow_scan(session)?;
+    let resume = stop_first && !target_stopped(session);
+    if resume {
+        stop(session)?;
+    }
+    #[cfg(unix)]
+    catch_interrupts();
+    SNAPSHOTTING.store(true, Ordering::SeqCst);
+    let written = memory::snapshot_to_file(&session.process, Path::new(file), &session.options, || SNAPSHOT_INTERRUPTED.load(Ordering::SeqCst));
+    SNAPSHOTTING.store(false, Ordering::SeqCst);
+    SNAPSHOT_INTERRUPTED.store(false, Ordering::SeqCst);
+    if session.options.progress.is_some() && !MACHINE.load(Ordering::Relaxed) {
+        eprint!("\r\x1b[K");
+    }
+    if resume {
+        cont(session)?;
+    }
+    let written = written?;
+    let holes = written.iter().filter(|x| !x.holes.is_empty()).count();
+    say!("captured {} regions ({}, stored as {}) to {}, {} with unreadable holes", written.len(), format_bytes(written.iter().map(|x| x.region.len()).sum()), format_bytes(written.iter().map(|x| x.payload_len as usize).sum()), file, holes);
+    Ok(())
+}
+
+// Whether a typed diff keeps a value, given its old and new values
+type DiffCondition<T> = Box<dyn Fn(T, T) -> bool>;
+
+// autosnap start <interval> [--keep <n>] [--budget <size>] [--stop] [--dir <directory>]
+// Snapshots on a timer into a ring of files, for autosnap diff to compare two of once something
+// has happened. --stop stops the target for each, which shows at short intervals
+// heatmap [--bars] [--top <n>]: the regions where the most pages changed since the last snapshot,
+// and the filter that restricts the next scan to the hottest of them
+fn heatmap(session: &mut Session, arguments: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut arguments = arguments.to_vec();
+    let bars = take_flag(&mut arguments, "--bars");
+    let top = take_option(&mut arguments, "--top")?.map(|x| x.parse::<usize>()).transpose()?.unwrap_or(HEATMAP_LIMIT);
+    if let Some(argument) = arguments.first() {
+        return Err(format!("Unexpected '{}'; expected heatmap [--bars] [--top <n>]", argument).into());
+    }
+    let (snapshot, time) = session.snapshot.as_ref().ok_or("No snapshot to compare with; take one with `snapshot` first")?;
+    let started = std::time::Instant::now();
+    let heatmap = snapshot.heatmap(&session.process, Some(HEATMAP_TIME))?;
+    let hot = heatmap.regions.iter().filter(|x| x.changed() > 0).collect::<Vec<&memory::RegionHeat>>();
+    let age = time.elapsed().unwrap_or_default();
+    say!("compared {} pages with the snapshot from {:.1}s ago in {:.2}s: {} changed, in {} of {} regions", heatmap.pages(), age.as_secs_f64(), started.elapsed().as_secs_f64(), heatmap.changed(), hot.len(), heatmap.regions.len());
+    for heat in hot.iter().take(top) {
+        let region = &heat.region;
+        let name = if region.pathname.is_empty() { "[anonymous]" } else { region.pathname.as_str() };
+        let bar = if bars { format!(" [{}]", heat.bar(HEATMAP_BAR_WIDTH)) } else { String::new() };
+        say!("{:>6.1}% {:>6} of {:<6} pages 0x{:x}-0x{:x} {} {}{}", heat.fraction() * 100.0, heat.changed(), heat.pages.len(), region.start, region.end, region.permissions(), name, bar);
+    }
+    if hot.len() > top {
+        say!("... {} more regions changed", hot.len() - top);
+    }
+    if heatmap.unread > 0 {
+        say!("{} pages could not be read again or are no longer mapped", heatmap.unread);
+    }
+    if heatmap.skipped > 0 {
+        say!("{} pages were left uncompared after {}s", heatmap.skipped, HEATMAP_TIME.as_secs());
+    }
+    if !hot.is_empty() {
+        let ranges = hot.iter().take(3).map(|x| format!("0x{:x}-0x{:x}", x.region.start, x.region.end)).collect::<Vec<String>>();
+        say!("`set filter {}` scans only the hottest", ranges.join(" or "));
+    }
+    Ok(())
+}
+
+fn start_autosnap(session: &mut Session, arguments: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    if session.process.backend() == MemBackend::Offline {
+        return Err("Autosnapshots are taken of a live target".into());
+    }
+    if session.autosnap.as_ref().is_some_and(|x| x.running()) {
+        return Err("autosnap is already running, autosnap stop it first".into());
+    }
+    let mut arguments = arguments.to_vec();
+    let stop = take_flag(&mut arguments, "--stop");
+    let keep = take_option(&mut arguments, "--keep")?.map(|x| x.parse::<usize>()).transpose()?.unwrap_or(AUTOSNAP_KEEP);
+    let budget = take_option(&mut arguments, "--budget")?.map(parse_size).transpose()?;
+    let directory = take_option(&mut arguments, "--dir")?.map(PathBuf::from).unwrap_or_else(|| std::env::temp_dir().join(format!("rmh-autosnap-{}", session.process.pid())));
+    let [interval] = arguments[..] else {
+        return Err("Expected autosnap start <interval> [--keep <n>] [--budget <size>] [--stop] [--dir <directory>]".into());
+    };
+    let interval = parse_duration(interval)?;
+    let options = AutoSnapshotOptions { interval, keep, directory: directory.clone(), budget: budget.map(|x| x as u64), stop, scan: session.options.clone() };
+    session.autosnap = Some(AutoSnapshotter::start(session.process.clone(), options)?);
+    let budget = budget.map(|x| format!(" within {}", format_bytes(x))).unwrap_or_default();
+    let stopping = if stop { ", stopping the target for each" } else { "" };
+    say!("taking a snapshot every {:?} into {}, keeping the last {}{}{}", interval, directory.display(), keep, budget, stopping);
+    Ok(())
+}
+
+// monitor start [--every <duration>] [--beep] [--run <command>] [--when "<name> <comparison> <number>"]...
+// Polls every marked result, as the scan type, and every lock in one batched read each interval,
+// alerting when any of them changes with what it was and what it is now. What is monitored is the
+// table as it was when started; monitor start again picks up what has been marked or locked since.
+// Each --when alerts whenever the expression watchexpr watches by that name comes to meet it
+fn start_value_monitor(session: &mut Session, arguments: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut arguments = arguments.to_vec();
+    let interval = take_option(&mut arguments, "--every")?.map(parse_duration).transpose()?.unwrap_or(DEFAULT_MONITOR_INTERVAL);
+    let beep = take_flag(&mut arguments, "--beep");
+    let run = take_option(&mut arguments, "--run")?.map(|x| x.to_string());
+    let mut conditions = Vec::new();
+    while let Some(condition) = take_option(&mut arguments, "--when")? {
+        conditions.push(AlertCondition::parse(condition)?);
+    }
+    if !arguments.is_empty() {
+        return Err("Usage: monitor start [--every <duration>] [--beep] [--run <command>] [--when \"<name> <comparison> <number>\"]...".into());
+    }
+    for condition in &conditions {
+        if !session.expressions.as_ref().is_some_and(|x| x.watched().iter().any(|x| x.name == condition.name)) {
+            return Err(format!("No expression is watched as {}; watchexpr {} \"<expression>\" watches one", condition.name, condition.name).into());
+        }
+    }
+    let mut entries = Vec::new();
+    // Results scanned for as a string have no size to read them by
+    if let Some(size) = session.scan_type.size() {
+        for (address, tag) in session.marks.iter().map(|x| (*x.0, x.1.tag.clone())).collect::<Vec<(usize, Option<String>)>>() {
+            let label = tag.unwrap_or_else(|| format_address(session, address));
+            entries.push(MonitorEntry { label, address, size, value_type: session.scan_type });
+        }
+    }
+    for lock in session.locks.list() {
+        let Ok(value_type) = lock.type_name.parse::<ValueType>() else {
+            continue;
+        };
+        if !session.marks.contains_key(&lock.address) {
+            let label = format!("lock {}", format_address(session, lock.address));
+            entries.push(MonitorEntry { label, address: lock.address, size: lock.value_bytes.len(), value_type });
+        }
+    }
+    if entries.is_empty() && conditions.is_empty() {
+        return Err("Nothing to monitor: star or tag results, lock values, or give --when for a watched expression, first".into());
+    }
+    if let Some(monitor) = session.value_monitor.as_mut().and_then(|x| x.monitor.as_mut()) {
+        monitor.stop();
+    }
+    let input = session.input.clone();
+    let callback = MonitorCallback(Arc::new(move |change| { let _ = input.send(Input::Monitor(change)); }));
+    let count = entries.len();
+    let monitor = match entries.is_empty() {
+        true => None,
+        false => Some(ValueMonitor::start(session.process.clone(), entries, interval, callback)?),
+    };
+    let watching = match conditions.is_empty() {
+        true => String::new(),
+        false => format!(" and alerting when {}", conditions.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(" or ")),
+    };
+    session.value_monitor = Some(Monitoring { monitor, conditions, beep, run, alerts: 0, suppressed: 0 });
+    say!("monitoring {} entries every {:?}{}", count, interval, watching);
+    Ok(())
+}
+
+// Whether this session made the change itself: a journaled write over the entry close enough to
+// when the change was seen, or a lock putting its value back
+fn own_change(session: &Session, change: &MonitorChange, interval: Duration) -> bool {
+    let (start, end) = (change.entry.address, change.entry.address + change.entry.size);
+    let earliest = change.time.checked_sub(interval + OWN_WRITE_WINDOW).unwrap_or(UNIX_EPOCH);
+    let written = session.journal.entries.iter().rev().any(|x| x.address < end && start < x.address + x.new.len() && earliest <= x.time && x.time <= change.time + OWN_WRITE_WINDOW);
+    written || session.locks.get(start).is_some_and(|x| x.value_bytes[..] == change.new[..])
+}
+
+// Returns whether anything was shown, for the prompt to be drawn again
+fn monitor_alert(session: &mut Session, change: MonitorChange) -> bool {
+    // Sent before the monitor it came from was stopped
+    let Some(monitor) = session.value_monitor.as_ref().and_then(|x| x.monitor.as_ref()).filter(|x| x.entries().contains(&change.entry)) else {
+        return false;
+    };
+    if own_change(session, &change, monitor.interval()) {
+        session.value_monitor.as_mut().unwrap().suppressed += 1;
+        return false;
+    }
+    let (old, new) = (format_bytes_as(session, &change.old, change.entry.value_type), format_bytes_as(session, &change.new, change.entry.value_type));
+    alert(session, format!("{}: {} -> {} at {}", change.entry.label, old, new, format_time(change.time)));
+    true
+}
+
+// Shown with the monitor's beep, then followed by its command if it was given one
+fn alert(session: &mut Session, text: String) {
+    let monitoring = session.value_monitor.as_mut().unwrap();
+    monitoring.alerts += 1;
+    let (beep, run) = (monitoring.beep, monitoring.run.clone());
+    say!("{}[monitor] {}", if beep { "\x07" } else { "" }, text);
+    if let Some(command) = run {
+        say!("[monitor] {}", command);
+        if let Err(e) = run_line(session, &command) {
+            say!("error: {}", e);
+        }
+    }
+}
+
+fn monitor_status(session: &Session) -> Result<(), Box<dyn std::error::Error>> {
+    let monitoring = session.value_monitor.as_ref().ok_or("The monitor is not running, start it with monitor start")?;
+    let (count, interval, reads, changes) = match &monitoring.monitor {
+        Some(monitor) => (monitor.entries().len(), monitor.interval(), monitor.reads(), monitor.changes()),
+        None => (0, Duration::ZERO, 0, 0),
+    };
+    say!("monitoring {} entries every {:?}: {} reads, {} changes, {} alerted, {} put down to this session's own writes", count, interval, reads, changes, monitoring.alerts, monitoring.suppressed);
+    for entry in monitoring.monitor.iter().flat_map(|x| x.entries()) {
+        say!("  {} 0x{:x} {}", entry.label, entry.address, entry.value_type);
+    }
+    for condition in &monitoring.conditions {
+        say!("  when {}", condition);
+    }
+    Ok(())
+}
+
+fn format_derived(value: Option<DerivedValue>) -> String {
+    value.map(|x| x.to_string()).unwrap_or_else(|| "unavailable".to_string())
+}
+
+// watchexpr <name> "<expression>" [--every <duration>]
+// Watches a value worked out from typed reads, as
+//     watchexpr hp_pct "(read i32 @hp) * 100 / (read i32 @max_hp)"
+// showing it whenever it changes. The addresses are resolved once, here; every address of every
+// expression watched is then read in one batched read each interval. One that cannot be read shows
+// the expression as unavailable until it can be again
+fn watch_expression(session: &mut Session, arguments: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut arguments = arguments.to_vec();
+    let interval = take_option(&mut arguments, "--every")?.map(parse_duration).transpose()?;
+    let [name, text] = arguments[..] else {
+        return Err("Usage: watchexpr <name> \"<expression>\" [--every <duration>], e.g. watchexpr hp_pct \"(read i32 @hp) * 100 / (read i32 @max_hp)\"".into());
+    };
+    let derived = Derived::parse(text)?;
+    let addresses = derived.reads.iter().map(|x| parse_address(session, &x.address)).collect::<Result<Vec<usize>, Box<dyn std::error::Error>>>()?;
+    let expression = WatchedExpression { name: name.to_string(), derived, addresses };
+    let value = expression.read(&session.process, session.options.endianness);
+    let (mut watched, mut values, before) = match session.expressions.take() {
+        Some(mut expressions) => {
+            expressions.stop();
+            (expressions.watched().to_vec(), expressions.values(), Some(expressions.interval()))
+        }
+        None => (Vec::new(), Vec::new(), None),
+    };
+    match watched.iter().position(|x| x.name == name) {
+        Some(index) => (watched[index], values[index]) = (expression, value),
+        None => {
+            watched.push(expression);
+            values.push(value);
+        }
+    }
+    let interval = interval.or(before).unwrap_or(DEFAULT_MONITOR_INTERVAL);
+    start_expressions(session, watched, values, interval)?;
+    say!("{} = {}, watched every {:?}", name, format_derived(value), interval);
+    Ok(())
+}
+
+fn start_expressions(session: &mut Session, watched: Vec<WatchedExpression>, values: Vec<Option<DerivedValue>>, interval: Duration) -> Result<(), Box<dyn std::error::Error>> {
+    session.expressions = None;
+    if watched.is_empty() {
+        return Ok(());
+    }
+    let input = session.input.clone();
+    let callback = ExpressionCallback(Arc::new(move |change| { let _ = input.send(Input::Expression(change)); }));
+    session.expressions = Some(ExpressionMonitor::start(session.process.clone(), watched, values, interval, session.options.endianness, callback)?);
+    Ok(())
+}
+
+fn unwatch_expression(session: &mut Session, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut expressions = session.expressions.take().ok_or("No expressions are watched")?;
+    expressions.stop();
+    let (mut watched, mut values, interval) = (expressions.watched().to_vec(), expressions.values(), expressions.interval());
+    let Some(index) = watched.iter().position(|x| x.name == name) else {
+        session.expressions = Some(expressions);
+        return Err(format!("No expression is watched as {}", name).into());
+    };
+    watched.remove(index);
+    values.remove(index);
+    start_expressions(session, watched, values, interval)?;
+    if let Some(monitoring) = session.value_monitor.as_mut() {
+        monitoring.conditions.retain(|x| x.name != name);
+    }
+    say!("unwatched {}", name);
+    Ok(())
+}
+
+fn list_expressions(session: &Session) {
+    let Some(expressions) = session.expressions.as_ref() else {
+        say!("no expressions are watched");
+        return;
+    };
+    for (expression, value) in expressions.watched().iter().zip(expressions.values()) {
+        say!("{} = {}  {}", expression.name, format_derived(value), expression.derived.text);
+    }
+}
+
+// Returns whether anything was shown, for the prompt to be drawn again
+fn expression_changed(session: &mut Session, change: ExpressionChange) -> bool {
+    // Sent before the expression was unwatched
+    if !session.expressions.as_ref().is_some_and(|x| x.watched().iter().any(|x| x.name == change.name)) {
+        return false;
+    }
+    say!("[watchexpr] {}: {} -> {}", change.name, format_derived(change.old), format_derived(change.new));
+    // Only on coming to meet a condition, so a value that stays low alerts once
+    let meets = |value: Option<DerivedValue>, condition: &AlertCondition| value.is_some_and(|x| condition.holds(x));
+    let met = session.value_monitor.as_ref().and_then(|x| x.conditions.iter().find(|x| x.name == change.name && !meets(change.old, x) && meets(change.new, x))).cloned();
+    if let Some(condition) = met {
+        alert(session, format!("{}: {} at {}", condition, format_derived(change.new), format_time(change.time)));
+    }
+    true
+}
+
+fn list_autosnaps(session: &Session) -> Result<(), Box<dyn std::error::Error>> {
+    let autosnap = session.autosnap.as_ref().ok_or("No autosnapshots, start taking them with autosnap start <interval>")?;
+    let snapshots = autosnap.snapshots();
+    for (index, snapshot) in snapshots.iter().enumerate() {
+        say!("{:>3} {} {:>10} in {:.1?} {}", index as isize - snapshots.len() as isize, format_time(snapshot.time), format_bytes(snapshot.size as usize), snapshot.took, snapshot.path.display());
+    }
+    let options = autosnap.options();
+    let state = if autosnap.running() { format!("every {:?}", options.interval) } else { "stopped".to_string() };
+    say!("{} of the last {} kept, {} taken, {} cycles skipped ({})", snapshots.len(), options.keep, autosnap.taken(), autosnap.skipped(), state);
+    if let Some(error) = autosnap.error() {
+        say!("the last snapshot failed: {}", error);
+    }
+    Ok(())
+}
+
+// autosnap diff <from> <to> [<type> ...] [--load] compares two of the snapshots as snapshot diff
+// would, each given as in the list: -1 for the latest, -2 for the one before and so on
+fn diff_autosnaps(session: &mut Session, from: &str, to: &str, arguments: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let snapshots = session.autosnap.as_ref().ok_or("No autosnapshots, start taking them with autosnap start <interval>")?.snapshots();
+    let path = |index: &str| -> Result<String, Box<dyn std::error::Error>> {
+        let back = index.strip_prefix('-').and_then(|x| x.parse::<usize>().ok()).filter(|x| *x > 0).ok_or(format!("Expected a snapshot as -1 for the latest, -2 for the one before and so on, got '{}'", index))?;
+        let snapshot = snapshots.len().checked_sub(back).map(|x| &snapshots[x]).ok_or(format!("There are only {} snapshots", snapshots.len()))?;
+        Ok(snapshot.path.display().to_string())
+    };
+    let (from, to) = (path(from)?, path(to)?);
+    diff_snapshots(session, &from, &to, arguments)
+}
+
+// What a typed diff keeps, from changed|increased|decreased|by <delta>, changed if none is given
+fn diff_condition<T: Scalar + FromStr>(condition: &[&str], usage: &str) -> Result<DiffCondition<T>, Box<dyn std::error::Error>> where T::Err: std::error::Error + 'static {
+    Ok(match *condition {
+        [] | ["changed"] => Box::new(|old, new| !old.is_nan() && !new.is_nan()),
+        ["increased"] => Box::new(|old, new| new > old),
+        ["decreased"] => Box::new(|old, new| new < old),
+        ["by", delta] => {
+            let delta = parse_scalar::<T>(delta)?;
+            Box::new(move |old: T, new: T| Scalar::abs_diff(new.saturating_sub(old), delta) <= T::epsilon())
+        }
+        _ => return Err(format!("Expected {}", usage).into()),
+    })
+}
+
+// snapshot diff <before> <after> [<type> [changed|increased|decreased|by <delta>]] [--load]
+// What changed between two captures, region by region. Given a type, only its values at multiples
+// of its size, or of the alignment if one is set, are compared. --load makes what was found the
+// results, to be narrowed down from there with rescans of the live target
+fn diff_snapshots(session: &mut Session, before: &str, after: &str, arguments: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut arguments = arguments.to_vec();
+    let load = take_flag(&mut arguments, "--load");
+    let (before_path, after_path) = (Path::new(before), Path::new(after));
+    let (earlier, later) = (Capture::open(before_path)?.header, Capture::open(after_path)?.header);
+    if earlier.pid != later.pid || earlier.executable != later.executable {
+        say!("note: {} and {} were captured from different processes", before, after);
+    }
+    let endianness = session.options.endianness;
+    // Typed values are shown as they were and are, and bytes only by where they changed
+    let (diff, value_type, mut values) = match arguments[..] {
+        [] => (diff_captures(before_path, after_path)?, ValueType::U8, BTreeMap::new()),
+        [value_type, ref condition @ ..] => {
+            let value_type = value_type.parse::<ValueType>()?;
+            with_scan_type!(value_type, T, {
+                let alignment = session.options.alignment.unwrap_or(T::SIZE);
+                let predicate = diff_condition::<T>(condition, "snapshot diff <before> <after> [<type> [changed|increased|decreased|by <delta>]] [--load]")?;
+                let diff = diff_captures_by::<T>(before_path, after_path, alignment, endianness, predicate)?;
+                let shown = diff.changed.iter().flat_map(|x| x.ranges.iter().take(DIFF_LIMIT).map(|x| x.0)).collect::<Vec<usize>>();
+                let old = read_many_in::<T>(&Process::offline(before_path)?, &shown, endianness);
+                let new = read_many_in::<T>(&Process::offline(after_path)?, &shown, endianness);
+                let values = shown.into_iter().zip(old.into_iter().zip(new)).filter_map(|(address, (old, new))| Some((address, format!("{} -> {}", old.ok()?, new.ok()?)))).collect::<BTreeMap<usize, String>>();
+                (diff, value_type, values)
+            })
+        }
+    };
+    let found = diff.changed.iter().map(|x| x.ranges.len()).sum::<usize>();
+    let kind = if arguments.is_empty() { "changed ranges" } else { "values" };
+    say!("compared {}, {} {} in {} regions", format_bytes(diff.bytes_compared), found, kind, diff.changed.len());
+    for changed in &diff.changed {
+        let region = &changed.region;
+        let bytes = changed.ranges.iter().map(|x| x.1 - x.0).sum::<usize>();
+        say!("0x{:x}-0x{:x} {} {}: {} {} ({} changed)", region.start, region.end, region.permissions(), region.pathname, changed.ranges.len(), kind, format_bytes(bytes));
+        for (start, end) in changed.ranges.iter().take(DIFF_LIMIT) {
+            match values.remove(start) {
+                Some(value) => say!("  {} {}", format_address(session, *start), value),
+                None => say!("  {} {} bytes", format_address(session, *start), end - start),
+            }
+        }
+        if changed.ranges.len() > DIFF_LIMIT {
+            say!("  ... {} more", changed.ranges.len() - DIFF_LIMIT);
+        }
+    }
+    for (which, regions) in [(before, &diff.only_before), (after, &diff.only_after)] {
+        for region in regions {
+            say!("only in {}: 0x{:x}-0x{:x} {} {:>10} {}", which, region.start, region.end, region.permissions(), format_bytes(region.len()), region.pathname);
+        }
+    }
+    if load {
+        session.results = match arguments.is_empty() {
+            true => diff.changed.iter().flat_map(|x| x.ranges.iter().flat_map(|x| x.0..x.1)).collect(),
+            false => diff.addresses().collect(),
+        };
+        session.scan_type = value_type;
+        session.scan_endianness = endianness;
+        session.stats = ScanStats::default();
+        session.unknown = None;
+        session.initial = initial_values(session).ok();
+        say!("loaded {} results as {}", session.results.len(), value_type);
+    }
+    Ok(())
+}
+
+// crosscompare <pidA> <pidB> [<type> [changed|increased|decreased|by <delta>]] [--load]
+// Where two processes differ, e.g. two instances of a game with one setting changed. Regions are
+// paired by module and offset, or for the heap and other anonymous memory by what they follow,
+// and differences are shown relative to that. Only pairs the region filter selects in both are
+// compared. --load makes them the results in whichever of the two the session is attached to
+fn cross_compare(session: &mut Session, first: &str, second: &str, arguments: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut arguments = arguments.to_vec();
+    let load = take_flag(&mut arguments, "--load");
+    let pid = |x: &str| -> Result<Pid, Box<dyn std::error::Error>> { Ok(Pid::from_raw(x.parse().map_err(|_| format!("Not a pid: {}", x))?)) };
+    let (first_pid, second_pid) = (pid(first)?, pid(second)?);
+    // Checked first, so as not to compare only to find nowhere to load the results
+    let in_second = match session.process.pid() {
+        _ if !load => false,
+        x if x == first_pid => false,
+        x if x == second_pid => true,
+        x => return Err(format!("crosscompare --load makes results in the session's process, and {} is neither {} nor {}", x, first_pid, second_pid).into()),
+    };
+    let (a, b) = (Process::attach(first_pid)?, Process::attach(second_pid)?);
+    let endianness = session.options.endianness;
+    let (diff, value_type, mut values) = match arguments[..] {
+        [] => (diff_processes(a, b, session.options.filter.as_ref())?, ValueType::U8, BTreeMap::new()),
+        [value_type, ref condition @ ..] => {
+            let value_type = value_type.parse::<ValueType>()?;
+            with_scan_type!(value_type, T, {
+                let alignment = session.options.alignment.unwrap_or(T::SIZE);
+                let predicate = diff_condition::<T>(condition, "crosscompare <pidA> <pidB> [<type> [changed|increased|decreased|by <delta>]] [--load]")?;
+                let diff = diff_processes_by::<T>(a.clone(), b.clone(), session.options.filter.as_ref(), alignment, endianness, predicate)?;
+                let shown = diff.changed.iter().flat_map(|x| x.ranges.iter().take(DIFF_LIMIT).map(move |y| (x.pair.first.start + y.0, x.pair.second.start + y.0))).collect::<Vec<(usize, usize)>>();
+                let old = read_many_in::<T>(&a, &shown.iter().map(|x| x.0).collect::<Vec<usize>>(), endianness);
+                let new = read_many_in::<T>(&b, &shown.iter().map(|x| x.1).collect::<Vec<usize>>(), endianness);
+                let values = shown.into_iter().zip(old.into_iter().zip(new)).filter_map(|(address, (old, new))| Some((address.0, format!("{} vs {}", old.ok()?, new.ok()?)))).collect::<BTreeMap<usize, String>>();
+                (diff, value_type, values)
+            })
+        }
+    };
+    let found = diff.changed.iter().map(|x| x.ranges.len()).sum::<usize>();
+    let kind = if arguments.is_empty() { "differing ranges" } else { "values" };
+    say!("compared {} in {} region pairs, {} {} in {} pairs", format_bytes(diff.bytes_compared), diff.pairs, found, kind, diff.changed.len());
+    for changed in &diff.changed {
+        let pair = &changed.pair;
+        let bytes = changed.ranges.iter().map(|x| x.1 - x.0).sum::<usize>();
+        say!("{} {}: 0x{:x} in {}, 0x{:x} in {}: {} {} ({} differ)", pair.label(0), pair.first.permissions(), pair.first.start, first_pid, pair.second.start, second_pid, changed.ranges.len(), kind, format_bytes(bytes));
+        for (start, end) in changed.ranges.iter().take(DIFF_LIMIT) {
+            match values.remove(&(pair.first.start + start)) {
+                Some(value) => say!("  {} {}", pair.label(*start), value),
+                None => say!("  {} {} bytes", pair.label(*start), end - start),
+            }
+        }
+        if changed.ranges.len() > DIFF_LIMIT {
+            say!("  ... {} more", changed.ranges.len() - DIFF_LIMIT);
+        }
+    }
+    for pair in &diff.resized {
+        say!("note: {} is {} in {} and {} in {}, compared up to the shorter", pair.label(0), format_bytes(pair.first.len()), first_pid, format_bytes(pair.second.len()), second_pid);
+    }
+    for (which, regions) in [(first_pid, &diff.only_first), (second_pid, &diff.only_second)] {
+        for region in regions {
+            say!("only in {}: 0x{:x}-0x{:x} {} {:>10} {}", which, region.start, region.end, region.permissions(), format_bytes(region.len()), region.pathname);
+        }
+    }
+    if load {
+        session.results = match arguments.is_empty() {
+            true => diff.changed.iter().flat_map(|x| {
+                let start = if in_second { x.pair.second.start } else { x.pair.first.start };
+                x.ranges.iter().flat_map(move |x| start + x.0..start + x.1)
+            }).collect(),
+            false => diff.addresses(in_second).collect(),
+        };
+        session.scan_type = value_type;
+        session.scan_endianness = endianness;
+        session.stats = ScanStats::default();
+        session.unknown = None;
+        session.initial = initial_values(session).ok();
+        say!("loaded {} results as {} in process {}", session.results.len(), value_type, session.process.pid());
+    }
+    Ok(())
+}
+
+fn module_json(module: &Module, main: bool) -> serde_json::Value {
+    serde_json::json!({
+        "name": module.name,
+        "path": module.path,
+        "base": module.base,
+        "size": module.size,
+        "executable": module.executable,
+        "writable": module.writable,
+        "deleted": module.deleted,
+        "main": main,
+    })
+}
+
+fn print_maps_change(change: &MapsChange) {
+    say!("warning: the memory map changed since the last scan");
+    for (start, end) in &change.appeared {
+        say!("    appeared    0x{:x}-0x{:x}", start, end);
+    }
+    for (start, end) in &change.disappeared {
+        say!("    disappeared 0x{:x}-0x{:x}", start, end);
+    }
+    for ((old_start, old_end), (start, end)) in &change.moved {
+        say!("    moved       0x{:x}-0x{:x} to 0x{:x}-0x{:x}", old_start, old_end, start, end);
+    }
+}
+
+// Removes "<name> <value>" from the words if present, returning the value
+fn take_option<'a>(words: &mut Vec<&'a str>, name: &str) -> Result<Option<&'a str>, Box<dyn std::error::Error>> {
+    match words.iter().position(|x| *x == name) {
+        Some(index) => {
+            let value = *words.get(index + 1).ok_or(format!("Expected a value after {}", name))?;
+            words.drain(index..index + 2);
+            Ok(Some(value))
+        }
+        None => Ok(None),
+    }
+}
+
+// --fmt <format> and --precision <digits>, each in place of the session's own
+fn take_display_format(session: &Session, words: &mut Vec<&str>) -> Result<DisplayFormat, Box<dyn std::error::Error>> {
+    let mut format = session.display;
+    if let Some(radix) = take_option(words, "--fmt")? {
+        format.radix = radix.parse()?;
+    }
+    if let Some(digits) = take_option(words, "--precision")? {
+        format.precision = Some(digits.parse()?);
+    }
+    Ok(format)
+}
+
+// Removes the flag from the words if present, returning whether it was
+fn take_flag(words: &mut Vec<&str>, name: &str) -> bool {
+    let before = words.len();
+    words.retain(|x| *x != name);
+    words.len() != before
+}
+
+// Writes into mappings without the w flag are refused unless forced
+fn check_writable(session: &mut Session, address: usize, len: usize, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if session.process.backend() == MemBackend::Offline {
+        return Err("Offline captures are read-only".into());
+    }
+    if force {
+        return Ok(());
+    }
+    session.regions.check_writable(address, len).map_err(|e| format!("{} (add --force to try anyway)", e).into())
+}
+
+// Fails if the write fell short, having journaled whatever part of it landed
+fn journaled_write(session: &mut Session, address: usize, bytes: &[u8], kind: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let written = session.journal.write(&session.process, address, bytes, kind)?;
+    if let Some(entry) = session.journal.entries.last().cloned().filter(|_| written > 0) {
+        log_change(session, kind, address, &entry.old, &entry.new, None);
+    }
+    if written < bytes.len() {
+        return Err(format!("Only {} of {} bytes could be written, stopping at 0x{:x}", written, bytes.len(), address + written).into());
+    }
+    Ok(())
+}
+
+// Reads the value as the session's type, applies the operation and writes the result back as one
+// journaled write, so a single undo puts back what was read
+fn write_arithmetic(session: &mut Session, address: usize, operation: Arithmetic, operand: &str, wrapping: bool, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    with_scan_type!(session.scan_type, T, {
+        let (operand, endianness) = parse_value::<T>(session, operand)?;
+        check_writable(session, address, T::SIZE, force)?;
+        let old = read_scalar::<T>(&session.process, address, endianness).map_err(|e| format!("Could not read 0x{:x}: {}", address, e))?;
+        let new = operation.apply(old, operand, wrapping);
+        journaled_write(session, address, &new.to_bytes_in(endianness), "write")?;
+        // Wrapping gives something else exactly when the result went past the type's bounds, and
+        // floats, NaN included, come out the same either way
+        let saturated = if !wrapping && operation.apply(old, operand, true).to_bytes() != new.to_bytes() { " (saturated, --wrapping wraps around instead)" } else { "" };
+        say!("{} {} {} {}: {} -> {}{}", format_address(session, address), T::NAME, operation, operand, old, new, saturated);
+    });
+    Ok(())
+}
+
+// Appends the change to the journal file, if there is one. Failing to is said, but leaves the
+// command that made the change to carry on
+fn log_change(session: &mut Session, action: &str, address: usize, old: &[u8], new: &[u8], lock: Option<SavedLock>) {
+    if session.journal_file.is_none() {
+        return;
+    }
+    let record = JournalRecord {
+        time: SystemTime::now(),
+        pid: session.process.pid().as_raw(),
+        action: action.to_string(),
+        address: SavedAddress::from_address(address, session.regions.modules()),
+        absolute: address,
+        old: old.to_vec(),
+        new: new.to_vec(),
+        command: session.command.clone(),
+        lock,
+    };
+    let file = session.journal_file.as_mut().unwrap();
+    if let Err(e) = file.append(&record) {
+        say!("could not journal the {} at 0x{:x} to {}: {}", action, address, file.path().display(), e);
+    }
+}
+
+// journal replay <path> [--dry-run] [--keep-going]. Stops at the first entry that cannot be found
+// in this process or fails to replay, unless kept going, having replayed those before it
+fn replay_journal(session: &mut Session, arguments: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut arguments = arguments.to_vec();
+    let dry_run = take_flag(&mut arguments, "--dry-run");
+    let keep_going = take_flag(&mut arguments, "--keep-going");
+    let path = match arguments[..] {
+        [path] => path,
+        _ => return Err("Usage: journal replay <path> [--dry-run] [--keep-going]".into()),
+    };
+    let records = JournalFile::read(path)?;
+    session.regions.refresh()?;
+    let modules = session.regions.modules().to_vec();
+    // Caves in the journaled process to those the replayed injections went into
+    let mut moved = BTreeMap::new();
+    let mut replayed = 0;
+    for (index, record) in records.iter().enumerate() {
+        let result = resolve_record(session, record, &modules, &moved).and_then(|address| {
+            let now = read_bytes_from_process(&session.process, record.old.len(), address).unwrap_or_default();
+            let holding = if now != record.old { format!(" (holding {} now)", format_hex(&now)) } else { String::new() };
+            let bytes = match record.old.is_empty() && record.new.is_empty() {
+                true => String::new(),
+                false => format!(": {} -> {}{}", format_hex(&record.old), format_hex(&record.new), holding),
+            };
+            say!("#{} {}{} {}{}", index, if dry_run { "would " } else { "" }, record.action, format_address(session, address), bytes);
+            match dry_run {
+                true => Ok(()),
+                false => replay_record(session, record, address, &mut moved),
+            }
+        });
+        match result {
+            Ok(()) => replayed += 1,
+            Err(e) if keep_going => say!("skipped #{} {} at {}: {}", index, record.action, record.address, e),
+            Err(e) => return Err(format!("Stopped at #{} {} at {} (from `{}`) after {} of {} entries: {}; --keep-going skips what cannot be replayed", index, record.action, record.address, record.command, replayed, records.len(), e).into()),
+        }
+    }
+    say!("{} {} of {} entries from {}", if dry_run { "would replay" } else { "replayed" }, replayed, records.len(), path);
+    Ok(())
+}
+
+// Module-relative addresses are found afresh. Absolute ones only stand in the process they were
+// journaled in, or in a cave a replayed injection moved
+fn resolve_record(session: &Session, record: &JournalRecord, modules: &[Module], moved: &BTreeMap<usize, usize>) -> Result<usize, Box<dyn std::error::Error>> {
+    // Code without a hook goes in whatever cave is found for it
+    if record.action == "inject" && record.old.is_empty() {
+        return Ok(record.absolute);
+    }
+    match &record.address {
+        SavedAddress::Absolute(address) => match moved.get(address) {
+            Some(address) => Ok(*address),
+            None if record.pid == session.process.pid().as_raw() => Ok(*address),
+            None => Err(format!("0x{:x} is outside any module of process {}, so cannot be found in this one", address, record.pid).into()),
+        },
+        address => address.resolve(modules),
+    }
+}
+
+fn replay_record(session: &mut Session, record: &JournalRecord, address: usize, moved: &mut BTreeMap<usize, usize>) -> Result<(), Box<dyn std::error::Error>> {
+    match record.action.as_str() {
+        "write" | "undo" | "redo" => journaled_write(session, address, &record.new, "write"),
+        "poke" => journaled_write(session, address, &record.new, "poke"),
+        "lock" => {
+            let lock = record.lock.as_ref().ok_or("The entry has nothing to create the lock from")?;
+            let old = record_lock(session, address, &record.new);
+            let endianness = session.options.endianness;
+            match lock.enabled {
+                true => restore_lock(&mut session.locks, lock, address, endianness)?,
+                false => session.locks.create_disabled(|locks| restore_lock(locks, lock, address, endianness))?,
+            }
+            log_lock(session, address, &old);
+            Ok(())
+        }
+        "unlock" => {
+            if !session.locks.unlock_value(address) {
+                return Err(format!("No lock at 0x{:x}", address).into());
+            }
+            log_change(session, "unlock", address, &[], &[], None);
+            Ok(())
+        }
+        "patch" => {
+            if read_bytes_from_process(&session.process, record.old.len(), address)? != record.old {
+                return Err("the bytes there are no longer the ones it replaced".into());
+            }
+            let patch = patch(session, address, &record.new, true)?;
+            session.patches.push(patch);
+            Ok(())
+        }
+        "restore" => restore_patch(session, address),
+        "inject" => {
+            require_dangerous(session, "inject")?;
+            let hooked = !record.old.is_empty();
+            let cave = inject(session, &record.new, hooked.then_some(address))?;
+            if !hooked {
+                moved.insert(record.absolute, cave);
+            }
+            Ok(())
+        }
+        "uninject" => uninject(session, address),
+        action => Err(format!("Unknown action '{}'", action).into()),
+    }
+}
+
+// Reverts the journaled write with the id, or else the most recent, returning false if there was
+// none. A lock's entry is undone by removing the lock too, since the lock would otherwise write
+// straight over the old value
+fn undo(session: &mut Session, id: Option<usize>) -> Result<bool, Box<dyn std::error::Error>> {
+    let entry = match id {
+        Some(id) => session.journal.get(id).ok_or_else(|| format!("No journaled write #{}; `journal` lists them", id))?,
+        None => match session.journal.entries.last() {
+            Some(entry) => entry,
+            None => return Ok(false),
+        },
+    }.clone();
+    // Later writes to the same bytes replaced what this one wrote, so putting back what it replaced
+    // undercuts them
+    let later = session.journal.entries.iter().filter(|x| x.id > entry.id).collect::<Vec<&JournalEntry>>();
+    if !later.is_empty() {
+        let overlapping = later.iter().filter(|x| x.address < entry.address + entry.old.len() && entry.address < x.address + x.new.len()).count();
+        match overlapping {
+            0 => say!("warning: #{} is not the most recent write, and undoing writes out of order may conflict", entry.id),
+            _ => say!("warning: #{} is not the most recent write, and {} later writes overlap it, so undoing it out of order conflicts with them", entry.id, overlapping),
+        }
+    }
+    if entry.kind == "lock" {
+        if session.locks.unlock_value(entry.address) {
+            log_change(session, "unlock", entry.address, &[], &[], None);
+            say!("unlocked 0x{:x}", entry.address);
+        }
+    }
+    else {
+        warn_changed_since(session, entry.address, &entry.new);
+    }
+    let entry = session.journal.undo_id(&session.process, entry.id).unwrap()?;
+    log_change(session, "undo", entry.address, &entry.new, &entry.old, None);
+    say!("restored {} at {} (undoing #{} {} {})", format_hex(&entry.old), format_address(session, entry.address), entry.id, entry.kind, format_hex(&entry.new));
+    Ok(true)
+}
+
+// Writes the most recently undone write again, returning false if there was none
+fn redo(session: &mut Session) -> Result<bool, Box<dyn std::error::Error>> {
+    let Some(entry) = session.journal.undone.last().cloned() else {
+        return Ok(false);
+    };
+    warn_changed_since(session, entry.address, &entry.old);
+    let entry = session.journal.redo(&session.process).unwrap()?;
+    log_change(session, "redo", entry.address, &entry.old, &entry.new, None);
+    say!("wrote {} at {} again (redoing #{} {})", format_hex(&entry.new), format_address(session, entry.address), entry.id, entry.kind);
+    Ok(true)
+}
+
+// Undo and redo expect the bytes to be as they were left, which the target may not have kept them
+fn warn_changed_since(session: &mut Session, address: usize, expected: &[u8]) {
+    if let Ok(found) = read_bytes_from_process(&session.process, expected.len(), address) && found != expected {
+        say!("warning: {} holds {} rather than the {} expected, so the target may have changed it since", format_address(session, address), format_hex(&found), format_hex(expected));
+    }
+}
+
+// Puts the bytes at the address, for nop or a replayed patch. Code is mapped without write
+// permission, which process_vm_writev respects but /proc/<pid>/mem does not, so a forced patch of a
+// read-only region goes through the latter whichever backend the session uses
+fn patch(session: &mut Session, address: usize, bytes: &[u8], force: bool) -> Result<Patch<Process>, Box<dyn std::error::Error>> {
+    let len = bytes.len();
+    if let Some(patch) = session.patches.iter().find(|x| x.contains(address) || (address <= x.address && x.address < address + len)) {
+        return Err(format!("{} is already patched; restore it first", format_address(session, patch.address)).into());
+    }
+    check_writable(session, address, len, force)?;
+    let process = match session.regions.check_writable(address, len) {
+        Err(_) if session.process.backend() == MemBackend::ProcessVmReadv => Process::with_backend(session.process.pid(), MemBackend::ProcMem)?,
+        _ => session.process.clone(),
+    };
+    let mut patch = patch_bytes(process, address, bytes)?;
+    patch.restore_on_drop = true;
+    log_change(session, "patch", address, &patch.original, &patch.patched, None);
+    Ok(patch)
+}
+
+fn restore_patch(session: &mut Session, address: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let index = session.patches.iter().position(|x| x.contains(address)).ok_or(format!("No patch at 0x{:x}", address))?;
+    session.patches[index].restore()?;
+    let patch = session.patches.remove(index);
+    log_change(session, "restore", patch.address, &patch.patched, &patch.original, None);
+    Ok(())
+}
+
+fn restore_patches(session: &mut Session) {
+    for mut patch in std::mem::take(&mut session.patches).into_iter().rev() {
+        match patch.restore() {
+            Ok(_) => {
+                log_change(session, "restore", patch.address, &patch.patched, &patch.original, None);
+                say!("restored {} bytes at {}", patch.len(), format_address(session, patch.address));
+            }
+            Err(e) => say!("could not restore {}: {}", format_address(session, patch.address), e),
+        }
+    }
+}
+
+// The target runs a syscall it never asked for, from wherever it was stopped
+fn require_dangerous(session: &Session, command: &str) -> Result<(), Box<dyn std::error::Error>> {
+    match session.dangerous {
+        true => Ok(()),
+        false => Err(format!("{} has the target make a syscall from wherever it was stopped, which can crash it; start with --dangerous to allow it", command).into()),
+    }
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn make_writable(session: &mut Session, address: usize, len: usize, restore: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let mut protection = memory::make_writable(session.process.pid(), address, len)?;
+    let was = protection.original.iter().map(|x| format!("0x{:x}-0x{:x} {}", x.start, x.end, format_prot(x.prot))).collect::<Vec<String>>();
+    say!("made {} bytes at {} writable (was {}){}", protection.len(), format_address(session, protection.start), was.join(", "), if restore { ", restored on exit" } else { "" });
+    session.regions.refresh()?;
+    if restore {
+        protection.restore_on_drop = true;
+        session.protections.push(protection);
+    }
+    Ok(())
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn format_prot(prot: i32) -> String {
+    [(libc::PROT_READ, 'r'), (libc::PROT_WRITE, 'w'), (libc::PROT_EXEC, 'x')].iter().map(|&(bit, c)| if prot & bit != 0 { c } else { '-' }).collect()
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn restore_protections(session: &mut Session) {
+    for mut protection in std::mem::take(&mut session.protections).into_iter().rev() {
+        match protection.restore() {
+            Ok(_) => say!("restored the protection of {} bytes at {}", protection.len(), format_address(session, protection.start)),
+            Err(e) => say!("could not restore the protection at {}: {}", format_address(session, protection.start), e),
+        }
+    }
+}
+
+// "rw-", "rwx" or "rw" as in maps, to PROT_* flags
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn parse_prot(s: &str) -> Result<i32, Box<dyn std::error::Error>> {
+    s.chars().try_fold(libc::PROT_NONE, |prot, c| match c {
+        'r' => Ok(prot | libc::PROT_READ),
+        'w' => Ok(prot | libc::PROT_WRITE),
+        'x' => Ok(prot | libc::PROT_EXEC),
+        '-' => Ok(prot),
+        _ => Err(format!("Unknown protection '{}', expected something like rw- or rwx", s).into()),
+    })
+}
+
+// alloc <len> [<protection>] [--free-on-exit]
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn alloc(session: &mut Session, arguments: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut arguments = arguments.to_vec();
+    let free_on_exit = take_flag(&mut arguments, "--free-on-exit");
+    let (len, prot) = match arguments[..] {
+        [len] => (parse_size(len)?, libc::PROT_READ | libc::PROT_WRITE),
+        [len, prot] => (parse_size(len)?, parse_prot(prot)?),
+        _ => return Err("Usage: alloc <len> [<protection>] [--free-on-exit]".into()),
+    };
+    let address = memory::remote_alloc(session.process.pid(), len, prot)?;
+    session.allocations.push(Allocation { address, len, prot, free_on_exit });
+    session.regions.refresh()?;
+    say!("allocated {} bytes {} at 0x{:x}{}", len, format_prot(prot), address, if free_on_exit { ", freed on exit" } else { "" });
+    Ok(())
+}
+
+// Only what alloc made, so the length is known and nothing of the target's own goes
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn free(session: &mut Session, address: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let index = session.allocations.iter().position(|x| x.address == address).ok_or(format!("Nothing was allocated at 0x{:x}", address))?;
+    let allocation = &session.allocations[index];
+    memory::remote_free(session.process.pid(), allocation.address, allocation.len)?;
+    say!("freed {} bytes at 0x{:x}", allocation.len, allocation.address);
+    session.allocations.remove(index);
+    session.regions.refresh()?;
+    Ok(())
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn list_allocations(session: &mut Session) {
+    if session.allocations.is_empty() {
+        return say!("nothing allocated");
+    }
+    for allocation in &session.allocations {
+        say!("0x{:x} {} bytes {}{}", allocation.address, allocation.len, format_prot(allocation.prot), if allocation.free_on_exit { ", freed on exit" } else { "" });
+    }
+}
+
+// The command that mapped the region, if the session did. mmap may have merged an allocation into
+// the anonymous region next to it, so any overlap counts
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn allocated_by(session: &Session, region: &MemoryRegion) -> Option<&'static str> {
+    let overlaps = |start: usize, len: usize| start < region.end && region.start < start + len;
+    if session.allocations.iter().any(|x| overlaps(x.address, x.len)) {
+        return Some("alloc");
+    }
+    session.injections.iter().any(|x| overlaps(x.cave, x.cave_code.len())).then_some("inject")
+}
+
+// inject "<hex>" [--at <address>], returning the cave. The hook may not overlap a patch or another
+// hook, since restoring either would then put back the wrong bytes
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn inject(session: &mut Session, code: &[u8], at: Option<usize>) -> Result<usize, Box<dyn std::error::Error>> {
+    if let Some(site) = at {
+        // The most a hook can cover, five bytes of jmp ending inside a 15-byte instruction
+        let end = site + 19;
+        let mut patched = session.patches.iter().map(|x| (x.address, x.len())).chain(session.injections.iter().filter_map(|x| x.hook.as_ref()).map(|x| (x.address, x.len())));
+        if let Some((address, _)) = patched.find(|&(address, len)| address < end && site < address + len) {
+            return Err(format!("{} is already patched or hooked; restore it first", format_address(session, address)).into());
+        }
+    }
+    let mut injection = memory::inject_code(session.process.pid(), code, at)?;
+    injection.remove_on_drop = true;
+    session.regions.refresh()?;
+    match &injection.hook {
+        Some(hook) => {
+            let (site, moved) = (hook.address, hook.len());
+            say!("hooked {} to {} bytes of code at 0x{:x}, moving {} bytes ({}) after it", format_address(session, site), code.len(), injection.cave, moved, format_hex(&injection.cave_code[code.len()..code.len() + moved]));
+        }
+        None => say!("injected {} bytes of code at 0x{:x}", code.len(), injection.cave),
+    }
+    // The journal has the code itself as the new bytes, rather than the jmp to it
+    let (address, original) = injection.hook.as_ref().map(|x| (x.address, x.original.clone())).unwrap_or((injection.cave, Vec::new()));
+    log_change(session, "inject", address, &original, code, None);
+    let cave = injection.cave;
+    session.injections.push(injection);
+    Ok(cave)
+}
+
+// As inject journals it, by the hooked address or else the cave
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn log_uninject(session: &mut Session, injection: &memory::Injection) {
+    match &injection.hook {
+        Some(hook) => log_change(session, "uninject", hook.address, &hook.patched, &hook.original, None),
+        None => log_change(session, "uninject", injection.cave, &[], &[], None),
+    }
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn list_injections(session: &mut Session) {
+    if session.injections.is_empty() {
+        return say!("nothing injected");
+    }
+    let injections = session.injections.iter().map(|x| (x.cave, x.cave_code.len(), x.hook.as_ref().map(|x| x.address))).collect::<Vec<_>>();
+    for (cave, len, site) in injections {
+        match site {
+            Some(site) => say!("0x{:x} {} bytes, hooked from {}", cave, len, format_address(session, site)),
+            None => say!("0x{:x} {} bytes", cave, len),
+        }
+    }
+}
+
+// By the cave or the hooked address. One that fails to come out is kept, to be tried again
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn uninject(session: &mut Session, address: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let index = session.injections.iter().position(|x| (x.cave..x.cave + x.cave_code.len()).contains(&address) || x.hook.as_ref().is_some_and(|x| x.contains(address))).ok_or(format!("Nothing was injected at 0x{:x}", address))?;
+    session.injections[index].remove()?;
+    let injection = session.injections.remove(index);
+    log_uninject(session, &injection);
+    say!("removed the {} bytes of code at 0x{:x}", injection.cave_code.len(), injection.cave);
+    session.regions.refresh()?;
+    Ok(())
+}
+
+// Newest first, in case a later hook went over code an earlier one moved. Any that fail stay
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn remove_injections(session: &mut Session) {
+    let mut kept = Vec::new();
+    for mut injection in std::mem::take(&mut session.injections).into_iter().rev() {
+        match injection.remove() {
+            Ok(()) => {
+                log_uninject(session, &injection);
+                say!("removed the {} bytes of code at 0x{:x}", injection.cave_code.len(), injection.cave);
+            }
+            Err(e) => {
+                say!("could not remove the code at 0x{:x}: {}", injection.cave, e);
+                kept.insert(0, injection);
+            }
+        }
+    }
+    session.injections = kept;
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn free_allocations(session: &mut Session) {
+    let pid = session.process.pid();
+    for allocation in std::mem::take(&mut session.allocations).into_iter().filter(|x| x.free_on_exit) {
+        match memory::remote_free(pid, allocation.address, allocation.len) {
+            Ok(()) => say!("freed {} bytes at 0x{:x}", allocation.len, allocation.address),
+            Err(e) => say!("could not free 0x{:x}: {}", allocation.address, e),
+        }
+    }
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn alloc(_: &mut Session, _: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    Err("alloc injects an mmap through ptrace, so only works on x86-64 Linux".into())
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn free(_: &mut Session, _: usize) -> Result<(), Box<dyn std::error::Error>> {
+    Err("Nothing was allocated".into())
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn list_allocations(_: &mut Session) {
+    say!("nothing allocated");
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn allocated_by(_: &Session, _: &MemoryRegion) -> Option<&'static str> {
+    None
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn inject(_: &mut Session, _: &[u8], _: Option<usize>) -> Result<usize, Box<dyn std::error::Error>> {
+    Err("inject maps its code cave through ptrace, so only works on x86-64 Linux".into())
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn list_injections(_: &mut Session) {
+    say!("nothing injected");
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn uninject(_: &mut Session, _: usize) -> Result<(), Box<dyn std::error::Error>> {
+    Err("Nothing was injected".into())
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn remove_injections(_: &mut Session) {}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn free_allocations(_: &mut Session) {}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn make_writable(_: &mut Session, _: usize, _: usize, _: bool) -> Result<(), Box<dyn std::error::Error>> {
+    Err("make_writable injects an mprotect through ptrace, so only works on x86-64 Linux".into())
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn restore_protections(_: &mut Session) {}
+
+// The pid to signal, which an offline capture does not have
+fn signalled_pid(session: &Session) -> Result<Pid, Box<dyn std::error::Error>> {
+    match session.process.backend() {
+        MemBackend::Offline => Err("An offline capture has no process to signal".into()),
+        _ => Ok(session.process.pid()),
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn target_stopped(session: &Session) -> bool {
+    session.process.backend() != MemBackend::Offline && (session.stopped || memory::is_stopped(session.process.pid()))
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn stop(session: &mut Session) -> Result<(), Box<dyn std::error::Error>> {
+    let pid = signalled_pid(session)?;
+    memory::stop_process(pid)?;
+    session.stopped = true;
+    say!("stopped process {}", pid);
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn cont(session: &mut Session) -> Result<(), Box<dyn std::error::Error>> {
+    let pid = signalled_pid(session)?;
+    memory::continue_process(pid)?;
+    session.stopped = false;
+    say!("continued process {}", pid);
+    Ok(())
+}
+
+// Asks first unless confirmed, as nothing brings the target back. What the session changed in it
+// went with it, so nothing is left to undo on exit, and the session ends
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn kill(session: &mut Session, confirmed: bool) -> Result<bool, Box<dyn std::error::Error>> {
+    let pid = signalled_pid(session)?;
+    if !confirmed {
+        let name = session.process.executable_path().and_then(|x| Some(std::path::Path::new(&x).file_name()?.to_string_lossy().into_owned()));
+        say!("kill process {}{} with SIGKILL? type yes to confirm", pid, name.map(|x| format!(" ({})", x)).unwrap_or_default());
+        session.kill_pending = true;
+        return Ok(true);
+    }
+    memory::kill_process(pid)?;
+    say!("killed process {}", pid);
+    session.stopped = false;
+    forget_changes(session);
+    Ok(false)
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn forget_changes(session: &mut Session) {
+    session.patches.iter_mut().for_each(|x| x.restore_on_drop = false);
+    session.patches.clear();
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    {
+        session.protections.iter_mut().for_each(|x| x.restore_on_drop = false);
+        session.protections.clear();
+        session.injections.iter_mut().for_each(|x| x.remove_on_drop = false);
+        session.injections.clear();
+        session.allocations.clear();
+    }
+}
+
+// A target left stopped would otherwise hang for good once nobody is around to continue it
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn continue_on_exit(session: &mut Session) {
+    if session.leave_stopped || !target_stopped(session) {
+        return;
+    }
+    match memory::continue_process(session.process.pid()) {
+        Ok(()) => say!("continued process {}, which was stopped (quit --leave-stopped leaves it stopped)", session.process.pid()),
+        Err(e) => say!("could not continue process {}: {}", session.process.pid(), e),
+    }
+}
+
+// Only those of threads that were blocked in a system call at some point can be told apart, and a
+// stack found by its guard page alone has no thread to go with it
+fn list_stacks(session: &mut Session) -> Result<(), Box<dyn std::error::Error>> {
+    session.regions.refresh()?;
+    for (tid, region) in session.regions.stacks() {
+        let owner = tid.map(|x| format!("tid {}", x)).unwrap_or("unknown thread".to_string());
+        say!("0x{:x}-0x{:x} {:>10} {}", region.start, region.end, format_bytes(region.len()), owner);
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn list_threads(session: &mut Session) -> Result<(), Box<dyn std::error::Error>> {
+    if session.process.backend() == MemBackend::Offline {
+        return Err("An offline capture has no threads to list".into());
+    }
+    for thread in memory::list_threads(session.process.pid())? {
+        say!("{:>7} {} {}", thread.tid, thread.state, thread.name);
+    }
+    Ok(())
+}
+
+// The main thread unless a tid is given. Where the thread is and its stack pointer are always
+// described, and any other register holding an address inside a mapping says which
+#[cfg(target_os = "linux")]
+fn show_registers(session: &mut Session, thread: Option<Pid>) -> Result<(), Box<dyn std::error::Error>> {
+    if session.process.backend() == MemBackend::Offline {
+        return Err("An offline capture has no registers to read".into());
+    }
+    session.process.arch().require_x86_64("regs")?;
+    let pid = session.process.pid();
+    let registers = memory::read_registers(pid, thread.unwrap_or(pid))?;
+    session.regions.refresh()?;
+    for &(name, value) in &registers.values {
+        let pointer = value == registers.pc || value == registers.sp || session.regions.find(value as usize).is_some();
+        match pointer {
+            true => say!("{:<8} 0x{:016x} {}", name, value, session.regions.describe(value as usize)),
+            false => say!("{:<8} 0x{:016x}", name, value),
+        }
+    }
+    Ok(())
+}
+
+// Everything that decides whether attaching works, for telling why it did not
+#[cfg(target_os = "linux")]
+fn show_status(session: &mut Session) -> Result<(), Box<dyn std::error::Error>> {
+    if session.process.backend() == MemBackend::Offline {
+        return Err("An offline capture has no process to show the status of".into());
+    }
+    let status = memory::target_status(session.process.pid())?;
+    let tracer = match &status.tracer {
+        None => "none".to_string(),
+        Some(tracer) if tracer.is_us() => format!("{} (this session)", tracer),
+        Some(tracer) => format!("{}, so watch, break, regs, bt, alloc and inject cannot be used until it detaches", tracer),
+    };
+    let dumpable = match status.dumpable {
+        Some(true) => "yes",
+        Some(false) => "no, so only root can read its memory",
+        None => "unknown, as it runs as root",
+    };
+    let seccomp = match status.seccomp {
+        2 => format!("filter ({} filters)", status.seccomp_filters),
+        _ => status.seccomp_mode().to_string(),
+    };
+    let ptrace_scope = match status.ptrace_scope {
+        Some(0) => "0 (any process of the same user can be traced)",
+        Some(1) => "1 (only descendants, or processes that allow it, can be traced without CAP_SYS_PTRACE)",
+        Some(2) => "2 (only with CAP_SYS_PTRACE)",
+        Some(3) => "3 (nothing can be traced)",
+        Some(_) => "unknown",
+        None => "no Yama",
+    };
+    say!("process      {} ({})", status.pid, status.name);
+    say!("state        {}", status.state);
+    say!("tracer       {}", tracer);
+    say!("uid          {} (effective {})", status.uid, status.effective_uid);
+    say!("dumpable     {}", dumpable);
+    say!("seccomp      {}", seccomp);
+    say!("no_new_privs {}", if status.no_new_privs { "yes" } else { "no" });
+    say!("ptrace_scope {}", ptrace_scope);
+    say!("backend      {}", describe_backend(&session.process));
+    say!("arch         {}", session.process.arch());
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn show_status(session: &mut Session) -> Result<(), Box<dyn std::error::Error>> {
+    if session.process.backend() == MemBackend::Offline {
+        return Err("An offline capture has no process to show the status of".into());
+    }
+    say!("process      {}", session.process.pid());
+    say!("backend      {}", describe_backend(&session.process));
+    say!("arch         {}", session.process.arch());
+    Ok(())
+}
+
+// One line for each check, and under any that failed, what to do about it. Returns how many failed
+#[cfg(target_os = "linux")]
+fn print_preflight(checks: &[memory::PreflightCheck]) -> usize {
+    for check in checks {
+        say!("{}  {}: {}", if check.passed { "pass" } else { "FAIL" }, check.name, check.detail);
+        if let Some(suggestion) = &check.suggestion {
+            say!("      fix: {}", suggestion);
+        }
+    }
+    checks.iter().filter(|x| !x.passed).count()
+}
+
+// Everything that decides whether the process can be attached to, each with how to fix it
+#[cfg(target_os = "linux")]
+fn doctor(pid: Pid) -> Result<(), Box<dyn std::error::Error>> {
+    let checks = memory::preflight(pid);
+    match print_preflight(&checks) {
+        0 => Ok(()),
+        failed => Err(format!("{} of {} preflight checks failed", failed, checks.len()).into()),
+    }
+}
+
+// --fix-perms: when the preflight checks fail, lists what would get past them and takes the one
+// chosen. Running again as root replaces this process, keeping its terminal; setcap is confirmed a
+// second time, then checked with --doctor before running again with the capability. Nothing is
+// run without being chosen, and checks that pass leave the attach to go ahead as usual
+#[cfg(target_os = "linux")]
+fn fix_permissions(pid: Pid, args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::process::CommandExt;
+    let checks = memory::preflight(pid);
+    if checks.iter().all(|x| x.passed) {
+        say!("the preflight checks all pass, so there is nothing to fix");
+        return Ok(());
+    }
+    print_preflight(&checks);
+    let executable = std::env::current_exe()?;
+    let root = unsafe { libc::geteuid() } == 0;
+    let fixes = memory::permission_fixes(&checks, &executable, &args[1..], &std::env::current_dir()?, root, std::env::var_os("PATH").as_deref())?;
+    let shown = |program: &Path, arguments: &[String]| format!("{} {}", program.display(), arguments.join(" "));
+    say!("--fix-perms can:");
+    for (index, fix) in fixes.iter().enumerate() {
+        match fix {
+            memory::PermissionFix::Reexec { program, arguments } => say!("  {}) run this again as root with `{}`", index + 1, shown(program, arguments)),
+            memory::PermissionFix::Setcap { program, arguments } => say!("  {}) give the executable CAP_SYS_PTRACE with `{}`", index + 1, shown(program, arguments)),
+        }
+    }
+    say!("  {}) change nothing", fixes.len() + 1);
+    let mut answer = String::new();
+    say!("choose 1 to {}:", fixes.len() + 1);
+    std::io::stdin().read_line(&mut answer)?;
+    let Some(fix) = answer.trim().parse::<usize>().ok().and_then(|x| fixes.get(x.wrapping_sub(1))) else {
+        return Err("Nothing was changed".into());
+    };
+    match fix {
+        memory::PermissionFix::Reexec { program, arguments } => {
+            say!("running {}", shown(program, arguments));
+            Err(format!("Could not run {}: {}", program.display(), std::process::Command::new(program).args(arguments).exec()).into())
+        }
+        memory::PermissionFix::Setcap { program, arguments } => {
+            say!("warning: with CAP_SYS_PTRACE, anyone who can run {} can read and write the memory of any process on this machine, root's included, until `setcap -r` takes it away or the file is replaced", executable.display());
+            say!("type yes to go ahead:");
+            answer.clear();
+            std::io::stdin().read_line(&mut answer)?;
+            if answer.trim() != "yes" {
+                return Err("Nothing was changed".into());
+            }
+            if !std::process::Command::new(program).args(arguments).status()?.success() {
+                return Err(format!("{} failed, so nothing was changed", shown(program, arguments)).into());
+            }
+            say!("checking the attach with the capability:");
+            if !std::process::Command::new(&executable).args(["--doctor", &pid.to_string()]).status()?.success() {
+                return Err("The capability is set, but the checks still fail; `setcap -r` takes it away again".into());
+            }
+            let arguments = args[1..].iter().filter(|x| *x != "--fix-perms").collect::<Vec<&String>>();
+            Err(format!("Could not run {}: {}", executable.display(), std::process::Command::new(&executable).args(arguments).exec()).into())
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn fix_permissions(_: Pid, _: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    Err("--fix-perms fixes Linux's ptrace policy, so only works on Linux".into())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn doctor(_: Pid) -> Result<(), Box<dyn std::error::Error>> {
+    Err("doctor checks Linux's ptrace policy and /proc, so only works on Linux".into())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn list_threads(_: &mut Session) -> Result<(), Box<dyn std::error::Error>> {
+    Err("threads reads /proc/<pid>/task, so only works on Linux".into())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn show_registers(_: &mut Session, _: Option<Pid>) -> Result<(), Box<dyn std::error::Error>> {
+    Err("regs reads registers through ptrace, so only works on Linux".into())
+}
+
+#[cfg(windows)]
+fn target_stopped(_: &Session) -> bool {
+    false
+}
+
+#[cfg(windows)]
+fn stop(session: &mut Session) -> Result<(), Box<dyn std::error::Error>> {
+    signalled_pid(session)?;
+    Err("stop sends SIGSTOP, which Windows does not have".into())
+}
+
+#[cfg(windows)]
+fn cont(session: &mut Session) -> Result<(), Box<dyn std::error::Error>> {
+    signalled_pid(session)?;
+    Err("cont sends SIGCONT, which Windows does not have".into())
+}
+
+#[cfg(windows)]
+fn kill(session: &mut Session, _: bool) -> Result<bool, Box<dyn std::error::Error>> {
+    signalled_pid(session)?;
+    Err("kill sends SIGKILL, which Windows does not have".into())
+}
+
+#[cfg(windows)]
+fn continue_on_exit(_: &mut Session) {}
+
+// Polls for the target exiting or exec'ing and for processes of the same name starting, passing
+// each on to the session as input. The monitor is swapped for a new one on reattaching, so this
+// outlives any one target
+#[cfg(target_os = "linux")]
+fn start_monitor(session: &Session) {
+    *session.monitor.lock().unwrap() = memory::TargetMonitor::new(session.process.pid()).ok();
+    let (monitor, input) = (session.monitor.clone(), session.input.clone());
+    std::thread::spawn(move || loop {
+        std::thread::sleep(MONITOR_INTERVAL);
+        let changes = monitor.lock().unwrap().as_mut().map(|x| x.poll()).unwrap_or_default();
+        for change in changes {
+            if input.send(Input::Target(change)).is_err() {
+                return;
+            }
+        }
+    });
+}
+
+// Says what became of the target and offers the process that looks like it replaced it, which
+// with follow on is reattached to there and then. Another copy of the program started alongside
+// a target that is still running is left alone
+#[cfg(target_os = "linux")]
+fn target_changed(session: &mut Session, change: memory::TargetChange) {
+    let Some((target, changed)) = session.monitor.lock().unwrap().as_ref().map(|x| (x.target().clone(), x.changed())) else {
+        return;
+    };
+    let offered = match change {
+        memory::TargetChange::Exited => {
+            session.stopped = false;
+            say!("process {} exited; the next {} to start will be offered to reattach to", target, target.name);
+            return;
+        }
+        memory::TargetChange::Execed(now) => {
+            session.locks.pause_all();
+            say!("process {} exec'd {}, so its locks were paused, as nothing found in it is where it was", target, now.path);
+            now.pid
+        }
+        memory::TargetChange::Appeared { process, descendant } if descendant || changed => {
+            say!("{} started as process {}{}", process.name, process.pid, if descendant { format!(", a child of process {}", target.pid) } else { String::new() });
+            process.pid
+        }
+        memory::TargetChange::Appeared { .. } => return,
+    };
+    if session.follow {
+        if let Err(e) = reattach(session, Some(offered)) {
+            say!("error: could not follow it: {}", e);
+        }
+        return;
+    }
+    say!("press Enter to reattach to process {}, or `reattach {}` later", offered, offered);
+    session.reattach_pending = Some(offered);
+}
+
+// Moves the session to another process, by default whatever replaced the target, carrying its
+// results, locks, patches and journal over as save and load would, so module-relative addresses
+// are resolved afresh in the new process. Watches, allocations and injections belong to the old
+// process, and are taken out of it as on exit if it is still running as it was
+#[cfg(target_os = "linux")]
+fn reattach(session: &mut Session, pid: Option<Pid>) -> Result<(), Box<dyn std::error::Error>> {
+    if session.process.backend() == MemBackend::Offline {
+        return Err("An offline capture has no process to reattach from".into());
+    }
+    let old = session.process.pid();
+    let (successor, modules) = match session.monitor.lock().unwrap().as_ref() {
+        // Once the target has gone or exec'd, its modules as last seen are what everything found in it is relative to
+        Some(monitor) => (monitor.successor().map(|x| x.pid), monitor.changed().then(|| monitor.modules().to_vec())),
+        None => (None, None),
+    };
+    let pid = pid.or(successor).ok_or(format!("Nothing has replaced process {}, so give the pid to reattach to", old))?;
+    let changed = modules.is_some();
+    let modules = match modules {
+        Some(modules) => modules,
+        None => {
+            let _ = session.regions.refresh();
+            session.regions.modules().to_vec()
+        }
+    };
+    // Attached first, so that failing leaves the session as it was
+    let process = match session.process.backend() {
+        MemBackend::ProcMem if session.process.is_seized() => Process::seize(pid)?,
+        MemBackend::ProcessVmReadv => Process::attach(pid)?,
+        backend => Process::with_backend(pid, backend)?,
+    };
+    let regions = RegionCache::from_process(Arc::new(process.clone()))?;
+    let mut file = session_file(session, &modules)?;
+    // The values are from the old process, and the bindings never went anywhere
+    file.results.iter_mut().for_each(|x| x.value.clear());
+    file.bindings.clear();
+    #[cfg(target_arch = "x86_64")]
+    if session.watcher.take().is_some() {
+        WATCHING.store(false, Ordering::SeqCst);
+        say!("stopped watching, as the watches were on process {}", old);
+    }
+    if let Some(autosnap) = session.autosnap.as_mut().filter(|x| x.running()) {
+        autosnap.stop();
+        say!("stopped autosnap, as the snapshots were of process {}", old);
+    }
+    if changed {
+        forget_changes(session);
+    }
+    else {
+        remove_injections(session);
+        restore_patches(session);
+        restore_protections(session);
+        free_allocations(session);
+        continue_on_exit(session);
+    }
+    session.results.clear();
+    session.unknown = None;
+    session.initial = None;
+    session.snapshot = None;
+    // Its entries are addresses in the old process, as are the expressions'
+    session.value_monitor = None;
+    session.expressions = None;
+    session.browsing = None;
+    session.journal = Journal::default();
+    session.locks = LockManager::new(process.clone());
+    session.locks.set_failure_callback(Some(LockFailureCallback(Arc::new(print_lock_failure))));
+    session.regions = regions;
+    session.process = process;
+    session.stopped = false;
+    *session.monitor.lock().unwrap() = memory::TargetMonitor::new(pid).ok();
+    match MACHINE.load(Ordering::Relaxed) {
+        true => MachineMessage::Attached { pid: pid.as_raw(), backend: session.process.backend().to_string(), version: memory::MACHINE_VERSION }.emit(),
+        false => say!("reattached from process {} to {} using {}", old, pid, describe_backend(&session.process)),
+    }
+    restore_session(session, file, &format!("process {}", old))?;
+    // What was loaded from a session file is found again in the new instance too
+    if session.saved.is_some() {
+        resolve_all(session, false)?;
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn set_follow(session: &mut Session, follow: Option<bool>) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(follow) = follow {
+        session.follow = follow;
+    }
+    say!("follow is {}", if session.follow { "on, so whatever replaces the target is reattached to without asking" } else { "off" });
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reattach(_: &mut Session, _: Option<Pid>) -> Result<(), Box<dyn std::error::Error>> {
+    Err("reattach looks for the target's replacement in /proc, so only works on Linux".into())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_follow(_: &mut Session, _: Option<bool>) -> Result<(), Box<dyn std::error::Error>> {
+    Err("follow watches /proc for the target, so only works on Linux".into())
+}
+
+// lock <address> [<type>] [[set|add|sub|min|max] <value> | clamp <min> <max> | hold <value> [<tolerance>] | bytes "<hex>" | string "<text>"] [--interval <duration>] [--for <duration>] [--force]
+fn run_lock(session: &mut Session, arguments: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut arguments = arguments.to_vec();
+    let force = take_flag(&mut arguments, "--force");
+    let interval = match take_option(&mut arguments, "--interval")? {
+        Some(interval) => parse_duration(interval)?,
+        None => session.lock_interval,
+    };
+    let duration = take_option(&mut arguments, "--for")?.map(parse_duration).transpose()?;
+    let clamp = [take_option(&mut arguments, "--min")?, take_option(&mut arguments, "--max")?];
+    let tolerance = take_option(&mut arguments, "--tolerance")?;
+    if arguments.first() == Some(&"range") {
+        let only_changed = take_flag(&mut arguments, "--if-changed");
+        if duration.is_some() || clamp != [None, None] || tolerance.is_some() {
+            return Err("A range lock rewrites the block as it was, so takes only --interval, --if-changed and --force".into());
+        }
+        return match arguments[1..] {
+            [address, len] => lock_range(session, address, parse_size(len)?, interval, only_changed, force),
+            _ => Err("Expected lock range <address> <len> [--if-changed] [--interval <duration>] [--force]".into()),
+        };
+    }
+    let address = parse_address(session, arguments.first().ok_or("Expected an address to lock")?)?;
+    // An explicit type overrides the one from the last scan
+    let scan_type = match arguments.get(1).and_then(|x| x.parse::<ValueType>().ok()).filter(|x| x.is_numeric()) {
+        Some(scan_type) => {
+            arguments.remove(1);
+            scan_type
+        }
+        None => session.scan_type,
+    };
+    // --min and --max are written the way clamp locks are listed, and parsed from there
+    if clamp != [None, None] {
+        if arguments.len() > 1 {
+            return Err("--min and --max make a clamp lock, which takes no other action".into());
+        }
+        match clamp {
+            [Some(min), Some(max)] => arguments.extend(["clamp", min, max]),
+            [Some(min), None] => arguments.extend(["clamp", "min", min]),
+            [None, max] => arguments.extend(["clamp", "max", max.unwrap_or_default()]),
+        }
+        arguments.extend(tolerance);
+    }
+    else if tolerance.is_some() {
+        return Err("--tolerance is how far past --min or --max a clamp lock lets the value go, so needs one of them".into());
+    }
+    // What the lock replaced, for the journal file
+    let old = match arguments[1..] {
+        ["bytes" | "string", text] => {
+            let bytes = if arguments[1] == "bytes" { parse_hex_bytes(text)? } else { text.as_bytes().to_vec() };
+            check_writable(session, address, bytes.len(), force)?;
+            let old = record_lock(session, address, &bytes);
+            session.locks.lock_bytes(bytes, address, interval);
+            old
+        }
+        // Freezing the current value changes nothing, so there is nothing to undo
+        [] => with_scan_type!(scan_type, T, {
+            check_writable(session, address, std::mem::size_of::<T>(), force)?;
+            let old = read_bytes_from_process(&session.process, T::SIZE, address).unwrap_or_default();
+            session.locks.lock_current_value::<T>(address, interval)?;
+            old
+        }),
+        _ => with_scan_type!(scan_type, T, {
+            let action = parse_lock_action::<T>(&arguments[1..])?;
+            check_writable(session, address, std::mem::size_of::<T>(), force)?;
+            // Other actions write something that depends on the value at the time, so only the old
+            // value is known; it is journaled as both
+            let new = match action {
+                LockAction::Set(value) => Some(value.to_bytes_in(session.options.endianness)),
+                _ => read_bytes_from_process(&session.process, T::SIZE, address).ok(),
+            };
+            let old = new.map(|x| record_lock(session, address, &x)).unwrap_or_default();
+            session.locks.lock_with_action(action, address, interval, session.options.endianness);
+            old
+        }),
+    };
+    if duration.is_some() {
+        session.locks.set_duration(address, duration);
+    }
+    // A chain is followed again each time the lock is restored; any other expression is where it
+    // led this once
+    match arguments[0].contains("->").then(|| arguments[0].parse::<PointerChain>().ok()).flatten() {
+        Some(chain) => session.lock_chains.insert(address, chain),
+        None => session.lock_chains.remove(&address),
+    };
+    log_lock(session, address, &old);
+    Ok(())
+}
+
+// lock range <address> <len> [--if-changed] [--interval <duration>] [--force]
+// Snapshots the block once and puts all of it back every tick. Past range_lock_limit it takes
+// --force, as rewriting that much that often fights the target for it
+fn lock_range(session: &mut Session, address: &str, len: usize, interval: Duration, only_changed: bool, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if len == 0 {
+        return Err("A range lock needs at least one byte".into());
+    }
+    if len > session.range_lock_limit && !force {
+        return Err(format!("{} bytes is more than range_lock_limit ({}) freezes; --force locks it anyway", len, session.range_lock_limit).into());
+    }
+    let chain = address.contains("->").then(|| address.parse::<PointerChain>().ok()).flatten();
+    let address = parse_address(session, address)?;
+    check_writable(session, address, len, force)?;
+    let bytes = read_bytes_from_process(&session.process, len, address)?;
+    if bytes.len() != len {
+        return Err(format!("Only {} of the {} bytes at 0x{:x} could be read, so there is no whole block to freeze", bytes.len(), len, address).into());
+    }
+    if len > session.range_lock_limit {
+        say!("warning: rewriting {} bytes every {:?} may fight the target for them", len, interval);
+    }
+    session.locks.lock_range(bytes, address, interval, only_changed);
+    match chain {
+        Some(chain) => session.lock_chains.insert(address, chain),
+        None => session.lock_chains.remove(&address),
+    };
+    // The block is frozen as it already is, so there is nothing to undo
+    log_lock(session, address, &[]);
+    Ok(())
+}
+
+// write <address> string|utf16 "<text>" [--max <len>] [--pad null|space|none] [--terminator] [--force]
+// With --max the text must fit in that many bytes and the rest of them is padded, with NULs unless
+// told otherwise, so a shorter name fully replaces a longer one
+fn write_string_command(session: &mut Session, address: &str, encoding: Encoding, text: &str, flags: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut flags = flags.to_vec();
+    let force = take_flag(&mut flags, "--force");
+    let add_terminator = take_flag(&mut flags, "--terminator");
+    let max_len = take_option(&mut flags, "--max")?.map(parse_size).transpose()?;
+    let pad = match take_option(&mut flags, "--pad")? {
+        None | Some("null") => Pad::Null,
+        Some("space") => Pad::Space,
+        Some("none") => Pad::None,
+        Some(pad) => return Err(format!("Unknown padding '{}', expected null, space or none", pad).into()),
+    };
+    if let Some(flag) = flags.first() {
+        return Err(format!("Unexpected '{}'", flag).into());
+    }
+    let address = parse_address(session, address)?;
+    let bytes = encode_string(text, &StringWriteOptions { encoding, endianness: session.options.endianness, max_len, pad, add_terminator })?;
+    check_writable(session, address, bytes.len(), force)?;
+    journaled_write(session, address, &bytes, "write")
+}
+
+// replace-string <address> "<text>" [--utf16] [--pad null|space|none] [--force]
+// Reads the string there first, to clear what a shorter text leaves of it and to refuse a longer
+// one that would run past the NULs after it, which are likely the end of its buffer. --pad none
+// writes the text alone, without a terminator, leaving the old tail for strings whose length is
+// kept before them. The write is read back to check it landed
+fn replace_string(session: &mut Session, address: &str, text: &str, flags: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut flags = flags.to_vec();
+    let force = take_flag(&mut flags, "--force");
+    let encoding = if take_flag(&mut flags, "--utf16") { Encoding::Utf16 } else { Encoding::Utf8 };
+    let pad = match take_option(&mut flags, "--pad")? {
+        None | Some("null") => Pad::Null,
+        Some("space") => Pad::Space,
+        Some("none") => Pad::None,
+        Some(pad) => return Err(format!("Unknown padding '{}', expected null, space or none", pad).into()),
+    };
+    if let Some(flag) = flags.first() {
+        return Err(format!("Unexpected '{}'", flag).into());
+    }
+    let address = parse_address(session, address)?;
+    let endianness = session.options.endianness;
+    let slot = read_string_slot(&session.process, &mut session.regions, address, encoding, endianness, STRING_LIMIT)?;
+    let old = if slot.terminated { slot.len + encoding.unit_size() } else { slot.len };
+    let add_terminator = pad != Pad::None;
+    let needed = encode_string(text, &StringWriteOptions { encoding, endianness, add_terminator, ..StringWriteOptions::default() })?.len();
+    if needed > slot.room() && !force {
+        return Err(format!("{:?} takes {} bytes, more than the {} the string there ({:?}) likely has room for before other data; --force writes it anyway", text, needed, slot.room(), slot.text).into());
+    }
+    let bytes = encode_string(text, &StringWriteOptions { encoding, endianness, max_len: Some(needed.max(old)), pad, add_terminator })?;
+    check_writable(session, address, bytes.len(), force)?;
+    journaled_write(session, address, &bytes, "write")?;
+    if read_bytes_from_process(&session.process, bytes.len(), address)? != bytes {
+        return Err(format!("Read back 0x{:x} and it does not hold what was written; something else may have written it since", address).into());
+    }
+    say!("{}: {:?} -> {:?}, {} bytes written", format_address(session, address), slot.text, text, bytes.len());
+    if needed > slot.room() {
+        say!("warning: wrote past the {} bytes the old string likely had room for, as forced", slot.room());
+    }
+    else if needed > old {
+        say!("note: wrote {} bytes past the old terminator, over NULs that are likely the rest of its buffer", needed - old);
+    }
+    if pad == Pad::None {
+        say!("note: only the text was written, so a length kept before the string has to be set to {} to match", needed / encoding.unit_size());
+    }
+    Ok(())
+}
+
+// Journals the value a new lock is about to replace, once, rather than every write the lock makes,
+// and returns it
+fn record_lock(session: &mut Session, address: usize, new: &[u8]) -> Vec<u8> {
+    match read_bytes_from_process(&session.process, new.len(), address) {
+        Ok(old) if old.len() == new.len() => {
+            session.journal.record(address, old.clone(), new.to_vec(), "lock");
+            old
+        }
+        _ => Vec::new(),
+    }
+}
+
+// Appends a lock just created to the journal file, with what replaying it needs
+fn log_lock(session: &mut Session, address: usize, old: &[u8]) {
+    if let Some(lock) = session.locks.get(address) && let Ok(saved) = saved_lock(&lock, session.regions.modules(), session.lock_chains.get(&address)) {
+        log_change(session, "lock", address, old, &lock.value_bytes, Some(saved));
+    }
+}
+
+fn parse_lock_action<T: Scalar + FromStr>(words: &[&str]) -> Result<LockAction<T>, Box<dyn std::error::Error>> where T::Err: std::error::Error + 'static {
+    Ok(match *words {
+        [value] | ["set", value] => LockAction::Set(parse_scalar::<T>(value)?),
+        ["add", value] => LockAction::Add(parse_scalar::<T>(value)?),
+        ["sub", value] => LockAction::Sub(parse_scalar::<T>(value)?),
+        ["min", value] => LockAction::Min(parse_scalar::<T>(value)?),
+        ["max", value] => LockAction::Max(parse_scalar::<T>(value)?),
+        ["hold", value] => LockAction::Hold(parse_scalar::<T>(value)?, T::epsilon()),
+        ["hold", value, tolerance] => LockAction::Hold(parse_scalar::<T>(value)?, parse_scalar::<T>(tolerance)?),
+        // As clamp locks are listed and saved, with a tolerance when there is one
+        ["clamp", bound @ ("min" | "max"), value, ref tolerance @ ..] if tolerance.len() <= 1 => {
+            let value = Some(parse_scalar::<T>(value)?);
+            let tolerance = tolerance.first().map(|x| parse_scalar::<T>(x)).transpose()?.unwrap_or_default();
+            match bound {
+                "min" => LockAction::Clamp { min: value, max: None, tolerance },
+                _ => LockAction::Clamp { min: None, max: value, tolerance },
+            }
+        }
+        ["clamp", min, max, ref tolerance @ ..] if tolerance.len() <= 1 => {
+            let (min, max) = (parse_scalar::<T>(min)?, parse_scalar::<T>(max)?);
+            if min > max {
+                return Err(format!("Expected clamp's minimum {} to be at most its maximum {}", min, max).into());
+            }
+            let tolerance = tolerance.first().map(|x| parse_scalar::<T>(x)).transpose()?.unwrap_or_default();
+            LockAction::Clamp { min: Some(min), max: Some(max), tolerance }
+        }
+        _ => return Err("Expected lock <address> [[set|add|sub|min|max] <value> | clamp <min> <max> | hold <value> [<tolerance>] | bytes \"<hex>\" | string \"<text>\"] [--min <value>] [--max <value>] [--tolerance <value>] [--interval <duration>] [--for <duration>]".into()),
+    })
+}
+
+fn save_session(session: &mut Session, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    session.regions.refresh()?;
+    let modules = session.regions.modules().to_vec();
+    let file = session_file(session, &modules)?;
+    let (count, written, patched) = (file.locks.len(), file.writes.len(), file.patches.len());
+    file.save(path)?;
+    say!("saved {} results, {} locks, {} patches and {} journaled writes to {}", session.results.len(), count, patched, written, path);
+    if session.results.len() > SAVED_VALUE_LIMIT && session.scan_type.size().is_some() {
+        say!("note: there are more than {} results, so they were saved without their values", SAVED_VALUE_LIMIT);
+    }
+    Ok(())
+}
+
+// Everything a save keeps, with addresses made module-relative against the modules given
+fn session_file(session: &Session, modules: &[Module]) -> Result<SessionFile, Box<dyn std::error::Error>> {
+    let locks = session.locks.list().iter().map(|x| saved_lock(x, modules, session.lock_chains.get(&x.address))).collect::<Result<Vec<SavedLock>, String>>()?;
+    let writes = session.journal.entries.iter().map(|x| SavedWrite {
+        address: SavedAddress::from_address(x.address, modules),
+        old: x.old.clone(),
+        new: x.new.clone(),
+        time: x.time,
+        kind: x.kind.clone(),
+    }).collect::<Vec<SavedWrite>>();
+    let patches = session.patches.iter().map(|x| SavedPatch {
+        address: SavedAddress::from_address(x.address, modules),
+        original: x.original.clone(),
+        patched: x.patched.clone(),
+    }).collect::<Vec<SavedPatch>>();
+    // The values as they are now, for telling which results changed by the time they are loaded
+    let size = session.scan_type.size().filter(|_| session.results.len() <= SAVED_VALUE_LIMIT);
+    let values = match size {
+        Some(size) => read_many_bytes(&session.process, &session.results.iter().map(|x| (*x, size)).collect::<Vec<(usize, usize)>>()),
+        None => Vec::new(),
+    };
+    let mut values = values.into_iter();
+    let results = session.results.iter().map(|x| SavedResult {
+        address: SavedAddress::from_address(*x, modules),
+        value: values.next().and_then(|x| x.ok()).unwrap_or_default(),
+    }).collect::<Vec<SavedResult>>();
+    let endianness = Some(session.options.endianness).filter(|x| *x != Endianness::Native);
+    let bindings = session.bindings.clone();
+    let marks = session.marks.iter().map(|(address, mark)| SavedMark { address: SavedAddress::from_address(*address, modules), tag: mark.tag.clone(), starred: mark.starred }).collect();
+    Ok(SessionFile { scan_type: Some(session.scan_type), endianness, results, locks, patches, writes, bindings, marks })
+}
+
+fn saved_lock(lock: &LockEntry, modules: &[Module], chain: Option<&PointerChain>) -> Result<SavedLock, String> {
+    Ok(SavedLock {
+        address: SavedAddress::from_address(lock.address, modules),
+        value_type: lock.type_name.parse()?,
+        value_bytes: lock.value_bytes.to_vec(),
+        action: lock.action.clone(),
+        interval: lock.interval,
+        enabled: lock.enabled,
+        chain: chain.cloned(),
+    })
+}
+
+// The locks, frozen at their values where Cheat Engine can do the same, and the first EXPORT_LIMIT scan results as a Cheat Engine
+// table, with each address module-relative where it can be
+fn export_cheat_table(session: &mut Session, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    session.regions.refresh()?;
+    let modules = session.regions.modules().to_vec();
+    let endianness = session.options.endianness;
+    let locks = session.locks.list().into_iter().map(|lock| {
+        let value_type = lock.type_name.parse::<ValueType>()?;
+        // Locks keep their value in native byte order whatever order they write in
+        let value = value_type.value_from_bytes_in(&lock.value_bytes, Endianness::Native)?.format_value();
+        // Cheat Engine can only freeze an entry at a value, which holding comes close to
+        let (description, frozen) = match lock.action.split_whitespace().next() {
+            Some("set") => (format!("locked at {}", value), Some(value)),
+            Some("hold") => (lock.action.clone(), Some(value)),
+            _ => (format!("{} (not frozen, as Cheat Engine cannot)", lock.action), None),
+        };
+        Ok(CheatEntry {
+            description,
+            address: SavedAddress::from_address(lock.address, &modules),
+            value_type,
+            len: lock.value_bytes.len(),
+            frozen: frozen.filter(|_| lock.enabled),
+        })
+    }).collect::<Result<Vec<CheatEntry>, Box<dyn std::error::Error>>>()?;
+    let results = session.results.iter().take(EXPORT_LIMIT).enumerate().map(|(i, address)| CheatEntry {
+        description: format!("result #{}", i),
+        address: SavedAddress::from_address(*address, &modules),
+        value_type: session.scan_type,
+        len: session.scan_type.size().unwrap_or(0),
+        frozen: None,
+    }).collect::<Vec<CheatEntry>>();
+    let absolute = locks.iter().chain(&results).filter(|x| matches!(x.address, SavedAddress::Absolute(_))).count();
+    let (count, exported) = (locks.len(), results.len());
+    let groups = vec![CheatGroup { name: "Locks".to_string(), entries: locks }, CheatGroup { name: "Scan results".to_string(), entries: results }];
+    std::fs::write(path, CheatTable { groups, endianness }.to_xml())?;
+    say!("exported {} locks and {} of {} scan results to {}", count, exported, session.results.len(), path);
+    if absolute > 0 {
+        say!("{} of them are outside any module, so their addresses will not survive a restart of the process", absolute);
+    }
+    Ok(())
+}
+
+// Re-creates the saved results, locks and patches against the current process, resolving
+// module-relative addresses afresh, and says what could not be restored. Locks that only have an
+// absolute address may now point at something else entirely, so they come back disabled
+fn load_session(session: &mut Session, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let file = SessionFile::load(path)?;
+    session.saved = Some((file.clone(), path.to_string()));
+    restore_session(session, file, path)
+}
+
+// Where a saved entry is in the process as it is now: the end of its chain where it has one,
+// followed hop by hop, and otherwise its address, rebased through its module if it has one
+fn resolve_saved(session: &mut Session, address: &SavedAddress, chain: Option<&PointerChain>) -> Result<usize, Box<dyn std::error::Error>> {
+    match chain {
+        Some(chain) => resolve_pointer_chain(&session.process, &mut session.regions, chain),
+        None => session.regions.resolve(address),
+    }
+}
+
+// What the address holds now as the type, or why it could not be read
+fn read_for_display(session: &Session, address: usize, value_type: ValueType, len: usize) -> String {
+    match read_bytes_from_process(&session.process, value_type.size().unwrap_or(len), address) {
+        Ok(bytes) => format_bytes_as(session, &bytes, value_type),
+        Err(e) => format!("unreadable ({})", e),
+    }
+}
+
+// Numbers as the type, in the byte order locks use, and anything else as hex
+fn format_bytes_as(session: &Session, bytes: &[u8], value_type: ValueType) -> String {
+    match value_type.value_from_bytes_in(bytes, session.options.endianness) {
+        Ok(value) if value_type.is_numeric() => value.format_value(),
+        _ => format_hex(bytes),
+    }
+}
+
+// resolve all [--resume]
+// Finds every result and lock of the session file last loaded in the process as it is now, listing
+// each with what it holds. Locks made through a chain follow it again, moving with it if it now
+// leads elsewhere, and a lock that is missing is added back paused. Absolute addresses are only
+// where the value was in the process the file was saved from, so are reported as possibly stale.
+// --resume turns back on the locks that were active when saved
+fn resolve_all(session: &mut Session, resume: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let (file, from) = session.saved.clone().ok_or("resolve all finds a loaded session's entries again, so load one first")?;
+    session.regions.refresh()?;
+    let modules = session.regions.modules().to_vec();
+    if let Some(scan_type) = file.scan_type {
+        session.scan_type = scan_type;
+    }
+    let (mut through_modules, mut through_chains, mut stale, mut failed, mut paused) = (0, 0, 0, 0, 0);
+    let mut count = |address: &SavedAddress, chain: Option<&PointerChain>| match (chain, address) {
+        (Some(_), _) => through_chains += 1,
+        (None, SavedAddress::Module { .. }) => through_modules += 1,
+        (None, SavedAddress::Absolute(_)) => stale += 1,
+    };
+    let stale_note = |address: &SavedAddress| if matches!(address, SavedAddress::Absolute(_)) { "  (absolute, may be stale)" } else { "" };
+    if !file.results.is_empty() {
+        load_results(session, &file.results, &modules);
+        let resolved = file.results.iter().filter_map(|x| Some((&x.address, x.address.resolve(&modules).ok()?))).collect::<Vec<(&SavedAddress, usize)>>();
+        failed += file.results.len() - resolved.len();
+        for (saved, _) in &resolved {
+            count(saved, None);
+        }
+        for (saved, address) in resolved.iter().take(LIST_LIMIT) {
+            let value = read_for_display(session, *address, session.scan_type, 0);
+            say!("result {} = {}{}", format_address(session, *address), value, stale_note(saved));
+        }
+        if resolved.len() > LIST_LIMIT {
+            say!("... {} more results", resolved.len() - LIST_LIMIT);
+        }
+    }
+    for lock in &file.locks {
+        let label = lock.chain.as_ref().map(|x| x.to_string()).unwrap_or_else(|| lock.address.to_string());
+        let address = match resolve_saved(session, &lock.address, lock.chain.as_ref()) {
+            Ok(address) => address,
+            Err(e) => {
+                failed += 1;
+                say!("lock {} {}: {}", label, lock.value_type, e);
+                continue;
+            }
+        };
+        if let Some(chain) = &lock.chain {
+            let moved = session.lock_chains.iter().find(|x| x.1 == chain && *x.0 != address).map(|x| *x.0);
+            if let Some(old) = moved {
+                session.locks.unlock_value(old);
+                session.lock_chains.remove(&old);
+                say!("lock {} moved from 0x{:x}, where its chain led before", label, old);
+            }
+            session.lock_chains.insert(address, chain.clone());
+        }
+        if session.locks.get(address).is_none() {
+            let endianness = session.options.endianness;
+            if let Err(e) = session.locks.create_disabled(|locks| restore_lock(locks, lock, address, endianness)) {
+                failed += 1;
+                say!("lock {} {}: {}", label, lock.value_type, e);
+                continue;
+            }
+            log_lock(session, address, &[]);
+        }
+        count(&lock.address, lock.chain.as_ref());
+        let mut active = session.locks.get(address).is_some_and(|x| x.enabled);
+        if lock.enabled && !active && resume {
+            active = session.locks.resume(address);
+        }
+        else if lock.enabled && !active {
+            paused += 1;
+        }
+        let state = if active { "active" } else { "paused" };
+        let note = if lock.chain.is_some() { "" } else { stale_note(&lock.address) };
+        say!("lock {} {} {}: {} = {}, {}{}", label, lock.value_type, lock.action, format_address(session, address), read_for_display(session, address, lock.value_type, lock.value_bytes.len()), state, note);
+    }
+    let total = file.results.len() + file.locks.len();
+    say!("resolved {} of {} entries from {}: {} through their modules, {} through pointer chains, {} absolute and possibly stale, {} failed", total - failed, total, from, through_modules, through_chains, stale, failed);
+    if paused > 0 {
+        say!("{} locks were active when saved but are paused; `resolve all --resume` turns them back on", paused);
+    }
+    Ok(())
+}
+
+fn restore_session(session: &mut Session, file: SessionFile, from: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(scan_type) = file.scan_type {
+        session.scan_type = scan_type;
+    }
+    if let Some(endianness) = file.endianness {
+        session.options.endianness = endianness;
+    }
+    session.regions.refresh()?;
+    let modules = session.regions.modules().to_vec();
+    if !file.results.is_empty() {
+        load_results(session, &file.results, &modules);
+        // Marks on results that did not come back go with them
+        session.marks = file.marks.iter().filter_map(|x| Some((x.address.resolve(&modules).ok()?, Mark { tag: x.tag.clone(), starred: x.starred }))).filter(|x| session.results.contains(&x.0)).collect();
+    }
+    for binding in &file.bindings {
+        if let Err(e) = bind(session, binding.0, binding.1) {
+            say!("could not restore binding {}: {}", binding.0, e);
+        }
+    }
+    for saved in &file.patches {
+        let patched = saved.address.resolve(&modules).and_then(|address| {
+            if read_bytes_from_process(&session.process, saved.original.len(), address)? != saved.original {
+                return Err("the bytes there are no longer the ones it replaced".into());
+            }
+            check_writable(session, address, saved.patched.len(), false)?;
+            patch_bytes(session.process.clone(), address, &saved.patched)
+        });
+        match patched {
+            Ok(patch) => {
+                log_change(session, "patch", patch.address, &patch.original, &patch.patched, None);
+                session.patches.push(patch);
+            }
+            Err(e) => say!("could not reapply patch at {}: {}", saved.address, e),
+        }
+    }
+    if !file.patches.is_empty() {
+        say!("reapplied {} of {} patches", session.patches.len(), file.patches.len());
+    }
+    let mut restored = 0;
+    for lock in &file.locks {
+        // A lock made through a chain follows it to where the value is now, so is as
+        // trustworthy as one in a module
+        let result = resolve_saved(session, &lock.address, lock.chain.as_ref()).and_then(|address| {
+            if lock.enabled && (lock.chain.is_some() || matches!(lock.address, SavedAddress::Module { .. })) {
+                restore_lock(&mut session.locks, lock, address, session.options.endianness)?;
+            }
+            else {
+                session.locks.create_disabled(|locks| restore_lock(locks, lock, address, session.options.endianness))?;
+            }
+            Ok(address)
+        });
+        match result {
+            Ok(address) => {
+                restored += 1;
+                match &lock.chain {
+                    Some(chain) => session.lock_chains.insert(address, chain.clone()),
+                    None => session.lock_chains.remove(&address),
+                };
+                log_lock(session, address, &[]);
+                if let SavedAddress::Module { name, .. } = &lock.address && find_module(&modules, name).is_some_and(|x| x.deleted) {
+                    say!("warning: {} was deleted or replaced on disk after being loaded, so {} may not be where it was when saved", name, lock.address);
+                }
+                if let SavedAddress::Absolute(_) = lock.address && lock.enabled && lock.chain.is_none() {
+                    say!("warning: lock at 0x{:x} has no module to re-resolve against and was loaded paused; `locks resume 0x{:x}` once checked", address, address);
+                }
+            }
+            Err(e) => say!("could not restore lock at {}: {}", lock.address, e),
+        }
+    }
+    say!("restored {} of {} locks from {}", restored, file.locks.len(), from);
+    // The saved writes go before any made in this session, so `undo all` walks back both
+    let mut entries = Vec::with_capacity(file.writes.len());
+    for write in file.writes {
+        match write.address.resolve(&modules) {
+            // Numbered once prepended
+            Ok(address) => entries.push(JournalEntry { id: 0, address, old: write.old, new: write.new, time: write.time, kind: write.kind }),
+            Err(e) => say!("could not restore journaled write at {}: {}", write.address, e),
+        }
+    }
+    if !entries.is_empty() {
+        say!("restored {} journaled writes; `undo` reverts them", entries.len());
+    }
+    session.journal.prepend(entries);
+    Ok(())
+}
+
+// Replaces the results with the saved ones, reporting those whose module is gone and how many no
+// longer hold the value they had when saved
+fn load_results(session: &mut Session, saved: &[SavedResult], modules: &[Module]) {
+    let mut resolved: Vec<(usize, &[u8])> = Vec::with_capacity(saved.len());
+    let mut unresolved: Vec<String> = Vec::new();
+    for result in saved {
+        match result.address.resolve(modules) {
+            Ok(address) => resolved.push((address, &result.value)),
+            Err(e) => unresolved.push(format!("{}: {}", result.address, e)),
+        }
+    }
+    let compared = resolved.iter().filter(|x| !x.1.is_empty()).collect::<Vec<&(usize, &[u8])>>();
+    let now = read_many_bytes(&session.process, &compared.iter().map(|x| (x.0, x.1.len())).collect::<Vec<(usize, usize)>>());
+    let changed = now.into_iter().zip(&compared).filter(|x| x.0.as_deref().ok() != Some(x.1.1)).count();
+    let results = resolved.into_iter().map(|x| x.0).collect::<Vec<usize>>();
+    session.results = results;
+    session.unknown = None;
+    session.scan_endianness = session.options.endianness;
+    session.stats = ScanStats::default();
+    session.initial = initial_values(session).ok();
+    say!("restored {} of {} results", session.results.len(), saved.len());
+    for line in unresolved.iter().take(5) {
+        say!("could not restore result {}", line);
+    }
+    if unresolved.len() > 5 {
+        say!("... and {} more", unresolved.len() - 5);
+    }
+    if changed > 0 {
+        say!("{} results no longer hold the values they had when saved", changed);
+    }
+}
+
+fn restore_lock(locks: &mut LockManager<Process>, lock: &SavedLock, address: usize, endianness: Endianness) -> Result<(), Box<dyn std::error::Error>> {
+    if lock.value_type == ValueType::Bytes && lock.action.starts_with("range") {
+        locks.lock_range(lock.value_bytes.clone(), address, lock.interval, lock.action.ends_with("if changed"));
+    }
+    else if lock.value_type == ValueType::Bytes {
+        locks.lock_bytes(lock.value_bytes.clone(), address, lock.interval);
+    }
+    else {
+        with_scan_type!(lock.value_type, T, {
+            if lock.value_bytes.len() != T::SIZE {
+                return Err(format!("Expected {} bytes for a {} lock, got {}", T::SIZE, lock.value_type, lock.value_bytes.len()).into());
+            }
+            let action = match lock.action.as_str() {
+                "set" => LockAction::Set(T::from_bytes(&lock.value_bytes)),
+                action => parse_lock_action::<T>(&action.split_whitespace().collect::<Vec<&str>>())?,
+            };
+            locks.lock_with_action(action, address, lock.interval, endianness);
+        });
+    }
+    Ok(())
+}
+
+fn bind(session: &mut Session, key: &str, command: &str) -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "hotkeys")]
+    if !memory::hotkeys::is_key(key) {
+        return Err(format!("Unknown key '{}', expected a name like F7 or KP1", key).into());
+    }
+    session.bindings.insert(key.to_ascii_uppercase(), command.to_string());
+    start_hotkeys(session);
+    Ok(())
+}
+
+// Starts listening for hotkeys the first time something is bound. Failing to (usually for lack of
+// permission on /dev/input) leaves the bindings in place but inactive
+#[cfg(feature = "hotkeys")]
+fn start_hotkeys(session: &mut Session) {
+    if session.hotkeys_started {
+        return;
+    }
+    session.hotkeys_started = true;
+    let input = std::sync::Mutex::new(session.input.clone());
+    match memory::hotkeys::listen(move |key| { let _ = input.lock().unwrap().send(Input::Key(key.to_string())); }) {
+        Ok(keyboards) => say!("listening for hotkeys on {} keyboards", keyboards),
+        Err(e) => say!("warning: hotkeys are unavailable: {}", e),
+    }
+}
+
+#[cfg(not(feature = "hotkeys"))]
+fn start_hotkeys(session: &mut Session) {
+    if !session.hotkeys_started {
+        session.hotkeys_started = true;
+        say!("warning: built without the hotkeys feature, so bindings will not trigger");
+    }
+}
+
+// Ctrl-C stops every running script, watch and snapshot rather than the session, and only ends the
+// session when none are running. On Windows it always ends the session, and scripts are stopped
+// with script stop
+#[cfg(feature = "scripting")]
+static SCRIPTS_RUNNING: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+#[cfg(feature = "scripting")]
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static SNAPSHOTTING: AtomicBool = AtomicBool::new(false);
+static SNAPSHOT_INTERRUPTED: AtomicBool = AtomicBool::new(false);
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+static WATCHING: AtomicBool = AtomicBool::new(false);
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+static WATCH_INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn interrupt(_: libc::c_int) {
+    let mut caught = false;
+    if SNAPSHOTTING.load(Ordering::SeqCst) {
+        SNAPSHOT_INTERRUPTED.store(true, Ordering::SeqCst);
+        caught = true;
+    }
+    #[cfg(feature = "scripting")]
+    if SCRIPTS_RUNNING.load(Ordering::SeqCst) > 0 {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+        caught = true;
+    }
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    if WATCHING.load(Ordering::SeqCst) {
+        WATCH_INTERRUPTED.store(true, Ordering::SeqCst);
+        caught = true;
+    }
+    if !caught {
+        unsafe {
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
+            libc::raise(libc::SIGINT);
+        }
+    }
+}
+
+// The signal, once SIGTERM or SIGHUP has asked the session to end. A second one while it is saving
+// and cleaning up ends it there and then
+#[cfg(unix)]
+static TERMINATED: AtomicI32 = AtomicI32::new(0);
+
+#[cfg(unix)]
+extern "C" fn terminate(signal: libc::c_int) {
+    if TERMINATED.swap(signal, Ordering::SeqCst) != 0 {
+        unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+            libc::raise(signal);
+        }
+    }
+}
+
+// The handler only notes the signal, and this thread passes it on to the session between commands.
+// Whatever the session is doing, memory exits SHUTDOWN_TIMEOUT later
+#[cfg(unix)]
+fn catch_termination(input: Sender<Input>) {
+    unsafe {
+        libc::signal(libc::SIGTERM, terminate as *const () as libc::sighandler_t);
+        libc::signal(libc::SIGHUP, terminate as *const () as libc::sighandler_t);
+    }
+    std::thread::spawn(move || loop {
+        std::thread::sleep(Duration::from_millis(50));
+        let signal = TERMINATED.load(Ordering::SeqCst);
+        if signal != 0 {
+            let _ = input.send(Input::Terminated);
+            std::thread::sleep(SHUTDOWN_TIMEOUT);
+            let _ = writeln!(std::io::stderr(), "gave up cleaning up after {}, which took longer than {:?}", signal_name(signal), SHUTDOWN_TIMEOUT);
+            std::process::exit(128 + signal);
+        }
+    });
+}
+
+#[cfg(unix)]
+fn signal_name(signal: libc::c_int) -> &'static str {
+    match signal {
+        libc::SIGHUP => "SIGHUP",
+        _ => "SIGTERM",
+    }
+}
+
+#[cfg(unix)]
+fn catch_interrupts() {
+    static HANDLER: std::sync::Once = std::sync::Once::new();
+    HANDLER.call_once(|| unsafe {
+        libc::signal(libc::SIGINT, interrupt as *const () as libc::sighandler_t);
+    });
+}
+
+// Each script runs on its own thread, and everything but its reads goes through the same channel
+// as typed commands, so it never runs at the same time as one
+#[cfg(feature = "scripting")]
+fn start_script(session: &mut Session, name: String, source: String) -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(unix)]
+    catch_interrupts();
+    let id = session.next_script;
+    session.next_script += 1;
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = stop.clone();
+    let input = session.input.clone();
+    let process = session.process.clone();
+    let endianness = session.scan_endianness;
+    let label = name.clone();
+    SCRIPTS_RUNNING.fetch_add(1, Ordering::SeqCst);
+    let thread = std::thread::spawn(move || {
+        let host = memory::script::ScriptHost {
+            send: Box::new(move |request| input.send(Input::Script(request)).is_ok()),
+            stopped: Box::new(move || stopped.load(Ordering::SeqCst) || INTERRUPTED.load(Ordering::SeqCst)),
+            log: Box::new(|text| say!("[script] {}", text)),
+        };
+        let result = memory::script::run_script(&source, process, endianness, host);
+        match result {
+            Ok(()) => say!("script {} ({}) finished", id, label),
+            Err(memory::script::ScriptError::Stopped) => say!("script {} ({}) stopped", id, label),
+            Err(memory::script::ScriptError::Failed(e)) => say!("script {} ({}) failed: {}", id, label, e),
+        }
+        if SCRIPTS_RUNNING.fetch_sub(1, Ordering::SeqCst) == 1 {
+            INTERRUPTED.store(false, Ordering::SeqCst);
+        }
+    });
+    session.scripts.retain(|x| !x.thread.is_finished());
+    session.scripts.push(RunningScript { id, name, stop, thread });
+    say!("started script {}", id);
+    Ok(())
+}
+
+#[cfg(feature = "scripting")]
+fn answer_script(session: &mut Session, request: memory::script::ScriptRequest) {
+    match request {
+        // A script cannot end the session, so quit does nothing
+        memory::script::ScriptRequest::Command(line, reply) => {
+            let _ = reply.send(run_command(session, &line).map(|_| ()).map_err(|e| e.to_string()));
+        }
+        memory::script::ScriptRequest::Results(reply) => {
+            let _ = reply.send(session.results.clone());
+        }
+    }
+}
+
+#[cfg(feature = "scripting")]
+fn stop_scripts(session: &mut Session, which: &str) -> Result<(), Box<dyn std::error::Error>> {
+    session.scripts.retain(|x| !x.thread.is_finished());
+    let id = match which {
+        "all" => None,
+        _ => Some(which.trim_start_matches('#').parse::<usize>().map_err(|_| format!("Expected a script number or all, not '{}'", which))?),
+    };
+    let mut found = false;
+    for script in session.scripts.iter().filter(|x| id.is_none_or(|id| x.id == id)) {
+        script.stop.store(true, Ordering::SeqCst);
+        found = true;
+    }
+    match (found, id) {
+        (false, Some(id)) => Err(format!("No script {} is running", id).into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(feature = "scripting")]
+fn list_scripts(session: &mut Session) {
+    session.scripts.retain(|x| !x.thread.is_finished());
+    if session.scripts.is_empty() {
+        say!("no scripts are running");
+    }
+    for script in &session.scripts {
+        say!("#{} {}", script.id, script.name);
+    }
+}
+
+#[cfg(not(feature = "scripting"))]
+fn start_script(_: &mut Session, _: String, _: String) -> Result<(), Box<dyn std::error::Error>> {
+    Err("Scripting needs memory built with --features scripting".into())
+}
+
+#[cfg(not(feature = "scripting"))]
+fn stop_scripts(_: &mut Session, _: &str) -> Result<(), Box<dyn std::error::Error>> {
+    Err("Scripting needs memory built with --features scripting".into())
+}
+
+#[cfg(not(feature = "scripting"))]
+fn list_scripts(_: &mut Session) {
+    say!("no scripts are running");
+}
+
+// Hits are printed as they happen, from the watcher's thread, with their own view of the maps so
+// that the session is not needed. A signal handler cannot stop the watcher itself, so Ctrl-C is
+// passed on to the session as input
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn watcher(session: &mut Session) -> Result<&memory::Watcher, Box<dyn std::error::Error>> {
+    if session.watcher.is_none() {
+        session.process.arch().require_x86_64("Watchpoints and breakpoints")?;
+        let regions = std::sync::Mutex::new(RegionCache::from_process(Arc::new(session.process.clone()))?);
+        let callback = memory::WatchCallback(Arc::new(move |event: &memory::WatchEvent| print_watch_event(&mut regions.lock().unwrap(), event)));
+        let watcher = memory::Watcher::start(session.process.pid(), callback)?;
+        watcher.set_backtrace_depth(session.backtrace_depth);
+        watcher.set_history_limit(session.watch_history);
+        session.watcher = Some(watcher);
+        WATCHING.store(true, Ordering::SeqCst);
+        catch_interrupts();
+        static FORWARDER: std::sync::Once = std::sync::Once::new();
+        let input = session.input.clone();
+        FORWARDER.call_once(move || {
+            std::thread::spawn(move || loop {
+                std::thread::sleep(Duration::from_millis(50));
+                if WATCH_INTERRUPTED.swap(false, Ordering::SeqCst) && input.send(Input::Interrupted).is_err() {
+                    return;
+                }
+            });
+        });
+    }
+    session.watcher.as_ref().ok_or("Expected a watcher".into())
+}
+
+// Detaches once nothing is watched and no breakpoints are left
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn release_watcher(session: &mut Session) {
+    if session.watcher.as_ref().is_some_and(|x| x.watches().is_empty() && x.breakpoints().is_empty()) {
+        session.watcher = None;
+        WATCHING.store(false, Ordering::SeqCst);
+    }
+}
+
+// With reads, the watchpoint traps on reads as well as writes
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn watch_address(session: &mut Session, address: usize, size: usize, reads: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let kind = if reads { memory::WatchKind::Access } else { memory::WatchKind::Write };
+    let result = watcher(session)?.watch(address, size, kind);
+    release_watcher(session);
+    result?;
+    say!("watching {} bytes at {} for {}", size, format_address(session, address), if reads { "reads and writes" } else { "writes" });
+    Ok(())
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn set_breakpoint(session: &mut Session, address: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let result = watcher(session)?.break_at(address);
+    release_watcher(session);
+    result?;
+    say!("breakpoint at {}", format_address(session, address));
+    Ok(())
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn print_watch_event(regions: &mut RegionCache, event: &memory::WatchEvent) {
+    match event {
+        memory::WatchEvent::Watch(hit) => print_watch_hit(regions, hit),
+        memory::WatchEvent::Break(hit) => print_break_hit(regions, hit),
+    }
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+// Only the first hit from each instruction and access is printed, so a hot loop is one entry; the
+// rest are counted, for watches to show
+fn print_watch_hit(regions: &mut RegionCache, hit: &memory::WatchHit) {
+    if hit.count > 1 {
+        return;
+    }
+    let location = describe_symbol(regions, hit.rip);
+    // The code before rip ends with the instruction that made the access
+    let instruction = memory::instruction_before(&hit.code, hit.code_start, hit.rip);
+    if MACHINE.load(Ordering::Relaxed) {
+        let instruction = instruction.map(|x| x.text);
+        return MachineMessage::WatchHit { address: format!("0x{:x}", hit.address), thread: hit.thread.as_raw(), rip: format!("0x{:x}", hit.rip), location, value: format_hex(&hit.value), access: hit.access.to_string(), instruction, backtrace: hit.backtrace.iter().map(|x| x.to_string()).collect() }.emit();
+    }
+    let what = match hit.access {
+        memory::Access::Read => "read of",
+        memory::Access::Write => "write to",
+    };
+    // Shown as bytes either side of rip if it cannot be decoded. A closed stderr is ignored rather
+    // than panicking the watcher's thread
+    let code = match instruction {
+        Some(instruction) => format_instruction(regions, &instruction),
+        None => {
+            let split = (hit.rip - hit.code_start).min(hit.code.len());
+            format!("0x{:x}: {} | {}", hit.code_start, format_hex(&hit.code[..split]), format_hex(&hit.code[split..]))
+        }
+    };
+    let _ = writeln!(std::io::stderr(), "\n{} 0x{:x} by tid {} before {} (0x{:x}), now {}\n    {}", what, hit.address, hit.thread, location, hit.rip, format_hex(&hit.value), code);
+    print_backtrace(&hit.backtrace);
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn print_break_hit(regions: &mut RegionCache, hit: &memory::BreakHit) {
+    let location = describe_symbol(regions, hit.address);
+    if MACHINE.load(Ordering::Relaxed) {
+        let registers = hit.registers.iter().map(|(name, value)| (name.to_string(), format!("0x{:x}", value))).collect();
+        return MachineMessage::BreakHit { address: format!("0x{:x}", hit.address), thread: hit.thread.as_raw(), location, hits: hit.hits, registers, backtrace: hit.backtrace.iter().map(|x| x.to_string()).collect() }.emit();
+    }
+    let registers = hit.registers.iter().map(|(name, value)| format!("{}=0x{:x}", name, value)).collect::<Vec<String>>();
+    let _ = writeln!(std::io::stderr(), "\nbreak at {} (0x{:x}) by tid {}, hit {}\n    {}", location, hit.address, hit.thread, hit.hits, registers.join(" "));
+    print_backtrace(&hit.backtrace);
+}
+
+// Frame 0 is where the hit was
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn print_backtrace(frames: &[memory::Location]) {
+    for (n, frame) in frames.iter().enumerate() {
+        let _ = writeln!(std::io::stderr(), "    #{:<2} {}", n, frame);
+    }
+}
+
+// The main thread unless a tid is given, up to the depth hits are unwound to
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn show_backtrace(session: &mut Session, thread: Option<Pid>) -> Result<(), Box<dyn std::error::Error>> {
+    if session.process.backend() == MemBackend::Offline {
+        return Err("An offline capture has no threads to unwind".into());
+    }
+    let pid = session.process.pid();
+    for (n, frame) in memory::backtrace(pid, thread.unwrap_or(pid), session.backtrace_depth.max(1))?.iter().enumerate() {
+        say!("#{:<2} 0x{:x} {}", n, frame.address, frame);
+    }
+    Ok(())
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn set_backtrace_depth(session: &mut Session, depth: usize) {
+    session.backtrace_depth = depth;
+    if let Some(watcher) = &session.watcher {
+        watcher.set_backtrace_depth(depth);
+    }
+    match depth {
+        0 => say!("hits are shown without a backtrace"),
+        _ => say!("hits are shown with up to {} frames", depth),
+    }
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn list_watches(session: &mut Session) {
+    let watches = session.watcher.as_ref().map(|x| x.watches()).unwrap_or_default();
+    if watches.is_empty() {
+        say!("nothing is watched");
+    }
+    for watch in watches {
+        let total = |access| watch.hits.iter().filter(|x| x.0.1 == access).map(|x| x.1).sum::<u64>();
+        let counts = match watch.kind {
+            memory::WatchKind::Write => format!("{} writes", total(memory::Access::Write)),
+            memory::WatchKind::Access => format!("{} reads, {} writes", total(memory::Access::Read), total(memory::Access::Write)),
+        };
+        say!("{} ({} bytes): {}", format_address(session, watch.address), watch.size, counts);
+        let mut hits = watch.hits.into_iter().collect::<Vec<((usize, memory::Access), u64)>>();
+        hits.sort_by_key(|x| std::cmp::Reverse(x.1));
+        for ((rip, access), count) in hits {
+            say!("    {:>8} {:<5} before {} (0x{:x})", count, access, describe_symbol(&mut session.regions, rip), rip);
+        }
+    }
+}
+
+// Every watch with its coalesced hits, for looking at offline
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn export_watches(session: &mut Session, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let watches = session.watcher.as_ref().ok_or("Nothing is watched")?.watches();
+    let entries = watches.iter().map(|watch| {
+        let hits = watch.hits.iter().map(|((rip, access), count)| serde_json::json!({
+            "rip": rip,
+            "location": describe_symbol(&mut session.regions, *rip),
+            "access": access.to_string(),
+            "count": count,
+        })).collect::<Vec<serde_json::Value>>();
+        serde_json::json!({
+            "address": watch.address,
+            "location": session.regions.describe(watch.address).to_string(),
+            "size": watch.size,
+            "kind": match watch.kind {
+                memory::WatchKind::Write => "write",
+                memory::WatchKind::Access => "access",
+            },
+            "value": format_hex(&watch.value),
+            "hits": hits,
+        })
+    }).collect::<Vec<serde_json::Value>>();
+    std::fs::write(path, serde_json::to_string_pretty(&entries)?)?;
+    say!("wrote {} watches to {}", entries.len(), path);
+    Ok(())
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn set_watch_history(session: &mut Session, limit: usize) {
+    session.watch_history = limit;
+    if let Some(watcher) = &session.watcher {
+        watcher.set_history_limit(limit);
+    }
+    match limit {
+        0 => say!("watches keep no history"),
+        _ => say!("watches keep their last {} changes", limit),
+    }
+}
+
+// The watched bytes as the type if it is given or is the scan type and fits, otherwise in hex
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn format_watched(value_type: Option<ValueType>, endianness: Endianness, bytes: &[u8], format: DisplayFormat) -> String {
+    value_type.and_then(|x| x.value_from_bytes_in(bytes, endianness).ok()).map(|x| x.format_as(format)).unwrap_or_else(|| format_hex(bytes))
+}
+
+// The type a watch's history is shown as: the one given, or else the scan type if it is the
+// watch's size
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn history_type(session: &Session, watch: &memory::Watch, value_type: Option<&str>) -> Result<Option<ValueType>, Box<dyn std::error::Error>> {
+    match value_type {
+        Some(value_type) => Ok(Some(value_type.parse::<ValueType>()?)),
+        None => Ok(Some(session.scan_type).filter(|x| x.size() == Some(watch.size))),
+    }
+}
+
+// history <address> [<type>] [--fmt <format>] [--precision <digits>]: the latest changes the watch
+// saw, each timed from the first shown
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn show_history(session: &mut Session, arguments: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut arguments = arguments.to_vec();
+    let format = take_display_format(session, &mut arguments)?;
+    let (address, value_type) = match arguments[..] {
+        [address] => (address, None),
+        [address, value_type] => (address, Some(value_type)),
+        _ => return Err("Usage: history <address> [<type>] [--fmt <format>] [--precision <digits>]".into()),
+    };
+    let address = parse_address(session, address)?;
+    let watches = session.watcher.as_ref().ok_or("Nothing is watched")?.watches();
+    let watch = watches.iter().find(|x| x.address == address).ok_or(format!("0x{:x} is not watched", address))?;
+    let value_type = history_type(session, watch, value_type)?;
+    let earlier = watch.history.len().saturating_sub(LIST_LIMIT) as u64 + watch.dropped;
+    let dropped = if earlier > 0 { format!(", after {} earlier ones", earlier) } else { String::new() };
+    let shown = watch.history.iter().skip(watch.history.len().saturating_sub(LIST_LIMIT)).collect::<Vec<&memory::ValueChange>>();
+    let first = match shown.first() {
+        Some(first) => first.time,
+        None => {
+            say!("no changes kept for {}", format_address(session, address));
+            return Ok(());
+        }
+    };
+    say!("{} changes at {}{}, from {}", shown.len(), format_address(session, address), dropped, format_time(first));
+    let endianness = session.options.endianness;
+    for change in shown {
+        let since = change.time.duration_since(first).unwrap_or_default();
+        let (old, new) = (format_watched(value_type, endianness, &change.old[..watch.size], format), format_watched(value_type, endianness, &change.new[..watch.size], format));
+        say!("  +{:.3}s {} -> {} by tid {} before {}", since.as_secs_f64(), old, new, change.thread, describe_symbol(&mut session.regions, change.rip));
+    }
+    Ok(())
+}
+
+// history export <file> [<type>]: every change every watch kept, as CSV with the time in seconds
+// since the epoch, for plotting
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn export_history(session: &mut Session, path: &str, value_type: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let watches = session.watcher.as_ref().ok_or("Nothing is watched")?.watches();
+    let endianness = session.options.endianness;
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    writeln!(file, "address,time,thread,rip,old,new")?;
+    let mut rows = 0;
+    for watch in &watches {
+        let value_type = history_type(session, watch, value_type)?;
+        for change in &watch.history {
+            let time = change.time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64();
+            writeln!(file, "0x{:x},{:.6},{},0x{:x},{},{}", watch.address, time, change.thread, change.rip, format_watched(value_type, endianness, &change.old[..watch.size], DisplayFormat::default()), format_watched(value_type, endianness, &change.new[..watch.size], DisplayFormat::default()))?;
+            rows += 1;
+        }
+    }
+    file.flush()?;
+    say!("wrote {} changes from {} watches to {}", rows, watches.len(), path);
+    Ok(())
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn list_breakpoints(session: &mut Session) {
+    let breakpoints = session.watcher.as_ref().map(|x| x.breakpoints()).unwrap_or_default();
+    if breakpoints.is_empty() {
+        say!("no breakpoints");
+    }
+    for breakpoint in breakpoints {
+        say!("{}: {} hits", format_address(session, breakpoint.address), breakpoint.hits);
+    }
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn unwatch(session: &mut Session, which: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if session.watcher.is_none() {
+        return Err("Nothing is watched".into());
+    }
+    let addresses = match which {
+        "all" => {
+            list_watches(session);
+            session.watcher.as_ref().map(|x| x.watches()).unwrap_or_default().into_iter().map(|x| x.address).collect()
+        }
+        address => vec![parse_address(session, address)?],
+    };
+    let watcher = session.watcher.as_ref().ok_or("Nothing is watched")?;
+    let result = addresses.into_iter().try_for_each(|x| watcher.unwatch(x));
+    release_watcher(session);
+    result
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn unbreak(session: &mut Session, which: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if session.watcher.is_none() {
+        return Err("No breakpoints are set".into());
+    }
+    let addresses = match which {
+        "all" => {
+            list_breakpoints(session);
+            session.watcher.as_ref().map(|x| x.breakpoints()).unwrap_or_default().into_iter().map(|x| x.address).collect()
+        }
+        address => vec![parse_address(session, address)?],
+    };
+    let watcher = session.watcher.as_ref().ok_or("No breakpoints are set")?;
+    let result = addresses.into_iter().try_for_each(|x| watcher.unbreak(x));
+    release_watcher(session);
+    result
+}
+
+// Ctrl-C: everything is taken out, with what it caught, and the process let go
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn stop_watching(session: &mut Session) {
+    if session.watcher.is_none() {
+        return;
+    }
+    list_watches(session);
+    list_breakpoints(session);
+    session.watcher = None;
+    WATCHING.store(false, Ordering::SeqCst);
+    say!("stopped watching");
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn watch_address(_: &mut Session, _: usize, _: usize, _: bool) -> Result<(), Box<dyn std::error::Error>> {
+    Err("Watchpoints use the x86-64 debug registers through ptrace, so only work on x86-64 Linux".into())
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn export_watches(_: &mut Session, _: &str) -> Result<(), Box<dyn std::error::Error>> {
+    Err("Nothing is watched".into())
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn set_breakpoint(_: &mut Session, _: usize) -> Result<(), Box<dyn std::error::Error>> {
+    Err("Breakpoints are set and stepped over through ptrace, so only work on x86-64 Linux".into())
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn list_watches(_: &mut Session) {
+    say!("nothing is watched");
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn list_breakpoints(_: &mut Session) {
+    say!("no breakpoints");
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn unwatch(_: &mut Session, _: &str) -> Result<(), Box<dyn std::error::Error>> {
+    Err("Nothing is watched".into())
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn unbreak(_: &mut Session, _: &str) -> Result<(), Box<dyn std::error::Error>> {
+    Err("No breakpoints are set".into())
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn show_backtrace(_: &mut Session, _: Option<Pid>) -> Result<(), Box<dyn std::error::Error>> {
+    Err("bt unwinds the stack through ptrace, so only works on x86-64 Linux".into())
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn set_watch_history(_: &mut Session, _: usize) {
+    say!("there is no history to keep, since watchpoints only work on x86-64 Linux");
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn show_history(_: &mut Session, _: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    Err("Nothing is watched".into())
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn export_history(_: &mut Session, _: &str, _: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    Err("Nothing is watched".into())
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn set_backtrace_depth(_: &mut Session, _: usize) {
+    say!("there are no hits to unwind, since watchpoints and breakpoints only work on x86-64 Linux");
+}
+
+// The config file holds commands run at startup, one per line, e.g. `bind F7 locks toggle #0`
+fn default_config_path() -> Option<std::path::PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME").map(std::path::PathBuf::from).or_else(|| std::env::var_os("HOME").map(|x| std::path::PathBuf::from(x).join(".config")))?;
+    Some(base.join("memory").join("config"))
+}
+
+// Where the session is saved when a SIGTERM or SIGHUP ends it, to be offered on the next start
+fn recovery_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_STATE_HOME").map(PathBuf::from).or_else(|| std::env::var_os("HOME").map(|x| PathBuf::from(x).join(".local").join("state")))?;
+    Some(base.join("memory").join("recovered-session.json"))
+}
+
+// A session with nothing in it to lose is not worth offering back
+#[cfg(unix)]
+fn save_recovery(session: &mut Session) {
+    if session.results.is_empty() && session.locks.is_empty() && session.patches.is_empty() && session.journal.entries.is_empty() && session.marks.is_empty() {
+        return;
+    }
+    let Some(path) = recovery_path() else {
+        say!("could not save the session, as neither XDG_STATE_HOME nor HOME is set");
+        return;
+    };
+    let saved = path.parent().map(std::fs::create_dir_all).transpose().map_err(|e| e.into()).and_then(|_| save_session(session, &path.to_string_lossy()));
+    if let Err(e) = saved {
+        say!("could not save the session to {}: {}", path.display(), e);
+    }
+}
+
+// session load --recover: loads what the last SIGTERM or SIGHUP saved, which is then gone so it is
+// not offered again
+fn load_recovered(session: &mut Session) -> Result<(), Box<dyn std::error::Error>> {
+    let path = recovery_path().filter(|x| x.exists()).ok_or("No session was saved by a SIGTERM or SIGHUP to recover")?;
+    load_session(session, &path.to_string_lossy())?;
+    std::fs::remove_file(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(())
+}
+
+fn run_config(session: &mut Session, path: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+    let contents = std::fs::read_to_string(path)?;
+    for (number, line) in contents.lines().enumerate() {
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        if let Err(e) = run_command(session, line) {
+            say!("{}:{}: {}", path.display(), number + 1, e);
+        }
+    }
+    Ok(())
+}
+
+// Reads whatever has arrived rather than whole lines, as while browse has the terminal out of
+// canonical mode each read is a keystroke or two
+fn spawn_stdin_reader(input: Sender<Input>) {
+    std::thread::spawn(move || {
+        let mut stdin = std::io::stdin();
+        let (mut buffer, mut line) = ([0u8; 4096], Vec::new());
+        'reading: loop {
+            let read = match std::io::Read::read(&mut stdin, &mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(read) => read,
+            };
+            if BROWSE_KEYS.load(Ordering::SeqCst) {
+                for key in decode_keys(&buffer[..read]) {
+                    if input.send(Input::BrowseKey(key)).is_err() {
+                        break 'reading;
+                    }
+                }
+                continue;
+            }
+            for byte in &buffer[..read] {
+                line.push(*byte);
+                if *byte == b'\n' && input.send(Input::Line(String::from_utf8_lossy(&std::mem::take(&mut line)).into_owned())).is_err() {
+                    break 'reading;
+                }
+            }
+        }
+        if !line.is_empty() {
+            let _ = input.send(Input::Line(String::from_utf8_lossy(&line).into_owned()));
+        }
+        let _ = input.send(Input::Closed);
+    });
+}
+
+fn run_command(session: &mut Session, line: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut words = split_words(line);
+    session.command = line.trim().to_string();
+    // Anything but yes after kill leaves the target alone and runs as usual
+    if std::mem::take(&mut session.kill_pending) {
+        if words == ["yes"] {
+            return kill(session, true);
+        }
+        say!("not killed");
+    }
+    #[cfg(target_os = "linux")]
+    if let Some(pid) = session.reattach_pending.take() && words.is_empty() {
+        reattach(session, Some(pid))?;
+        return Ok(true);
+    }
+    // Any scan can be given --endian, to scan in that byte order rather than the session's. The
+    // results then keep it for rescans and listings. Scans and snapshots can also be given
+    // --throttle, to read no faster than that, and --priority, to run at that priority
+    let endianness = match words.first() == Some(&"scan") {
+        true => take_option(&mut words, "--endian")?.map(|x| x.parse::<Endianness>()).transpose()?,
+        false => None,
+    };
+    let scanning = matches!(words.first(), Some(&("scan" | "rescan" | "snapshot")));
+    let throttle = match scanning {
+        true => take_option(&mut words, "--throttle")?.map(parse_throttle).transpose()?.map(Some),
+        false => None,
+    };
+    let priority = match scanning {
+        true => take_option(&mut words, "--priority")?.map(|x| x.parse::<ScanPriority>()).transpose()?,
+        false => None,
+    };
+    let result = match endianness.is_some() || throttle.is_some() || priority.is_some() {
+        true => {
+            let before = session.options.clone();
+            session.options.endianness = endianness.unwrap_or(before.endianness);
+            session.options.throttle = throttle.unwrap_or(before.throttle);
+            session.options.priority = priority.unwrap_or(before.priority);
+            let result = run_words(session, line, &words);
+            (session.options.endianness, session.options.throttle, session.options.priority) = (before.endianness, before.throttle, before.priority);
+            result
+        }
+        false => run_words(session, line, &words),
+    };
+    if matches!(words.first(), Some(&("scan" | "rescan"))) && result.is_ok() {
+        remember_last_values(session);
+    }
+    if let Some(error) = priority_error() {
+        say!("warning: {}, so the scan ran at normal priority", error);
+    }
+    result
+}
+
+impl Target {
+    // Nothing found in it yet, with locks that report failures as the session's do
+    fn new(process: Process) -> Result<Target, Box<dyn std::error::Error>> {
+        let mut locks = LockManager::new(process.clone());
+        locks.set_failure_callback(Some(LockFailureCallback(Arc::new(print_lock_failure))));
+        Ok(Target {
+            regions: RegionCache::from_process(Arc::new(process.clone()))?,
+            process,
+            scan_type: ValueType::I32,
+            results: Vec::new(),
+            scan_endianness: Endianness::Native,
+            stats: ScanStats::default(),
+            unknown: None,
+            initial: None,
+            last: None,
+            unfiltered: Vec::new(),
+            marks: BTreeMap::new(),
+            flag_convention: FlagConvention::default(),
+            struct_pattern: None,
+            scan_mask: None,
+            lock_chains: BTreeMap::new(),
+            saved: None,
+            snapshot: None,
+            autosnap: None,
+            locks,
+            patches: Vec::new(),
+            journal: Journal::default(),
+            stopped: false,
+            #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+            watcher: None,
+            #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+            protections: Vec::new(),
+            #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+            allocations: Vec::new(),
+            #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+            injections: Vec::new(),
+        })
+    }
+}
+
+// Puts the target in use in place of the one in use, which the target is left holding
+fn swap_target(session: &mut Session, target: &mut Target) {
+    std::mem::swap(&mut session.process, &mut target.process);
+    std::mem::swap(&mut session.scan_type, &mut target.scan_type);
+    std::mem::swap(&mut session.results, &mut target.results);
+    std::mem::swap(&mut session.scan_endianness, &mut target.scan_endianness);
+    std::mem::swap(&mut session.stats, &mut target.stats);
+    std::mem::swap(&mut session.unknown, &mut target.unknown);
+    std::mem::swap(&mut session.initial, &mut target.initial);
+    std::mem::swap(&mut session.last, &mut target.last);
+    std::mem::swap(&mut session.unfiltered, &mut target.unfiltered);
+    std::mem::swap(&mut session.marks, &mut target.marks);
+    std::mem::swap(&mut session.flag_convention, &mut target.flag_convention);
+    std::mem::swap(&mut session.struct_pattern, &mut target.struct_pattern);
+    std::mem::swap(&mut session.scan_mask, &mut target.scan_mask);
+    std::mem::swap(&mut session.lock_chains, &mut target.lock_chains);
+    std::mem::swap(&mut session.saved, &mut target.saved);
+    std::mem::swap(&mut session.snapshot, &mut target.snapshot);
+    std::mem::swap(&mut session.autosnap, &mut target.autosnap);
+    std::mem::swap(&mut session.locks, &mut target.locks);
+    std::mem::swap(&mut session.patches, &mut target.patches);
+    std::mem::swap(&mut session.journal, &mut target.journal);
+    std::mem::swap(&mut session.regions, &mut target.regions);
+    std::mem::swap(&mut session.stopped, &mut target.stopped);
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    {
+        std::mem::swap(&mut session.watcher, &mut target.watcher);
+        std::mem::swap(&mut session.protections, &mut target.protections);
+        std::mem::swap(&mut session.allocations, &mut target.allocations);
+        std::mem::swap(&mut session.injections, &mut target.injections);
+        WATCHING.store(session.watcher.is_some(), Ordering::SeqCst);
+    }
+}
+
+// Runs f with the target with that pid in use for the while, None if it is not attached
+fn with_target<R>(session: &mut Session, pid: Pid, f: impl FnOnce(&mut Session) -> R) -> Option<R> {
+    let mut target = session.targets.remove(&pid)?;
+    swap_target(session, &mut target);
+    let result = f(session);
+    swap_target(session, &mut target);
+    session.targets.insert(pid, target);
+    Some(result)
+}
+
+// Makes the target with that pid the one in use, parking the one that was. What follows the
+// prompt stops, as it would go on reading the old target
+fn switch_target(session: &mut Session, pid: Pid) -> Result<(), Box<dyn std::error::Error>> {
+    let mut target = session.targets.remove(&pid).ok_or(format!("Process {} is not attached; attach {} adds it", pid, pid))?;
+    let old = session.process.pid();
+    stop_autorescan(session, &format!("as its results are in process {}", old));
+    if session.value_monitor.take().is_some() {
+        say!("stopped the monitor, as its values are in process {}", old);
+    }
+    if session.expressions.take().is_some() {
+        say!("stopped watching expressions, as their addresses are in process {}", old);
+    }
+    session.browsing = None;
+    swap_target(session, &mut target);
+    session.targets.insert(old, target);
+    session.kill_pending = false;
+    #[cfg(target_os = "linux")]
+    {
+        session.reattach_pending = None;
+        *session.monitor.lock().unwrap() = memory::TargetMonitor::new(pid).ok();
+    }
+    Ok(())
+}
+
+fn process_name(process: &Process) -> Option<String> {
+    process.executable_path().and_then(|x| Some(std::path::Path::new(&x).file_name()?.to_string_lossy().into_owned()))
+}
+
+fn describe_target(process: &Process) -> String {
+    format!("process {} ({})", process.pid(), process_name(process).unwrap_or_else(|| "?".to_string()))
+}
+
+// A pid, or the name of the one target whose executable has it
+fn find_target(session: &Session, which: &str) -> Result<Pid, String> {
+    if let Ok(pid) = which.parse::<i32>() {
+        return Ok(Pid::from_raw(pid));
+    }
+    let processes = std::iter::once(&session.process).chain(session.targets.values().map(|x| &x.process));
+    let pids = processes.filter(|x| process_name(x).as_deref() == Some(which)).map(|x| x.pid()).collect::<Vec<Pid>>();
+    match pids[..] {
+        [pid] => Ok(pid),
+        [] => Err(format!("No target is named '{}'; targets lists them", which)),
+        _ => Err(format!("{} targets are named '{}', so give the pid", pids.len(), which)),
+    }
+}
+
+// attach <pid>: adds a process to the session alongside those attached already, read the way the
+// one in use is, and uses it
+fn attach_target(session: &mut Session, pid: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if session.process.backend() == MemBackend::Offline {
+        return Err("An offline capture has no process to attach alongside; start the session on a pid to attach more".into());
+    }
+    let pid = Pid::from_raw(pid.parse::<i32>().map_err(|_| format!("Expected a pid to attach to, got '{}'", pid))?);
+    if pid == session.process.pid() || session.targets.contains_key(&pid) {
+        return Err(format!("Process {} is attached already; use {} switches to it", pid, pid).into());
+    }
+    let process = match session.process.backend() {
+        MemBackend::ProcMem if session.process.is_seized() => Process::seize(pid)?,
+        MemBackend::ProcessVmReadv => Process::attach(pid)?,
+        backend => Process::with_backend(pid, backend)?,
+    };
+    let old = session.process.pid();
+    session.targets.insert(pid, Target::new(process)?);
+    switch_target(session, pid)?;
+    say!("attached to {} using {}, alongside process {}; use <pid|name> switches between them", pid, describe_backend(&session.process), old);
+    Ok(())
+}
+
+fn use_target(session: &mut Session, which: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let pid = find_target(session, which)?;
+    if pid != session.process.pid() {
+        switch_target(session, pid)?;
+    }
+    say!("using {}", describe_target(&session.process));
+    Ok(())
+}
+
+fn list_targets(session: &mut Session) {
+    say!("{}, in use: {} results, {} locks", describe_target(&session.process), session.results.len(), session.locks.list().len());
+    for target in session.targets.values() {
+        say!("{}: {} results, {} locks", describe_target(&target.process), target.results.len(), target.locks.list().len());
+    }
+}
+
+// Puts back what the session changed in the target in use, as quitting does
+fn clean_up(session: &mut Session) {
+    remove_injections(session);
+    restore_patches(session);
+    restore_protections(session);
+    free_allocations(session);
+    continue_on_exit(session);
+}
+
+// detach <pid|name>: cleans up after the session in that target and drops it, its locks and
+// watches with it, leaving the others as they are. Detaching the one in use moves to another
+fn detach_target(session: &mut Session, which: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let pid = find_target(session, which)?;
+    let description = match session.targets.get(&pid) {
+        Some(target) => describe_target(&target.process),
+        None if pid == session.process.pid() && session.targets.is_empty() => return Err(format!("Process {} is the only target; quit ends the session", pid).into()),
+        None if pid == session.process.pid() => describe_target(&session.process),
+        None => return Err(format!("Process {} is not attached; targets lists those that are", pid).into()),
+    };
+    let in_use = pid == session.process.pid();
+    if in_use {
+        let next = *session.targets.keys().next().unwrap();
+        clean_up(session);
+        switch_target(session, next)?;
+    }
+    else {
+        with_target(session, pid, clean_up);
+    }
+    session.targets.remove(&pid);
+    say!("detached from {}, stopping its locks and watches", description);
+    if in_use {
+        say!("using {}", describe_target(&session.process));
+    }
+    Ok(())
+}
+
+// Shown in the prompt once there is more than one target
+fn target_tag(session: &Session) -> String {
+    match session.targets.is_empty() {
+        true => String::new(),
+        false => format!("[{} {}] ", session.process.pid(), process_name(&session.process).unwrap_or_else(|| "?".to_string())),
+    }
+}
+
+// Shown in the prompt once anything is not native, as values in the wrong order just look wrong:
+// the session's order, and the results' when they were found in another
+fn endianness_tag(session: &Session) -> String {
+    let session_tag = Some(session.options.endianness).filter(|x| *x != Endianness::Native).map(|x| format!("[{}] ", x));
+    let results_tag = Some(session.scan_endianness).filter(|x| *x != session.options.endianness && !session.results.is_empty()).map(|x| format!("[results {}] ", x));
+    format!("{}{}", session_tag.unwrap_or_default(), results_tag.unwrap_or_default())
+}
+
+// One line for each lock, and with verbose another for how its writes are going
+fn print_locks(session: &mut Session, format: DisplayFormat, verbose: bool, indent: &str) {
+    let locks = session.locks.list();
+    if locks.is_empty() && !indent.is_empty() {
+        say!("{}no locks", indent);
+    }
+    for lock in locks {
+        let action = match lock.action.as_str() {
+            "set" => format!("= {}", format_lock_value(&lock, format)),
+            action if action.starts_with("range") => format!("{} = {}", action, format_lock_value(&lock, format)),
+            action => action.to_string(),
+        };
+        let corrections = if lock.action.starts_with("hold") || lock.action.starts_with("clamp") || lock.action == "range if changed" { format!(", {} corrective writes", lock.writes) } else { String::new() };
+        say!("{}{} {} {} every {:?} ({}){}", indent, format_address(session, lock.address), lock.type_name, action, lock.interval, format_lock_status(&lock), corrections);
+        if verbose {
+            let last_error = lock.last_errno.map(|x| format!(", last error {}", x)).unwrap_or_default();
+            say!("{}    {:.1} writes/s, {} writes, {} failures ({} in a row){}", indent, lock.writes_per_second(), lock.writes, lock.failures, lock.consecutive_failures, last_error);
+        }
+    }
+}
+
+// The command once split into words, with the line it came from for those that take the rest of it
+// as it was typed
+fn run_words(session: &mut Session, line: &str, words: &[&str]) -> Result<bool, Box<dyn std::error::Error>> {
+    match words {
+        [] => {}
+        ["quit"] | ["exit"] => return Ok(false),
+        ["quit" | "exit", "--leave-stopped"] => {
+            session.leave_stopped = true;
+            return Ok(false);
+        }
+        ["stop"] => stop(session)?,
+        ["cont"] | ["continue"] => cont(session)?,
+        ["kill"] => return kill(session, false),
+        ["kill", "--yes"] => return kill(session, true),
+        ["scan", "unknown", arguments @ ..] => scan_unknown(session, arguments)?,
+        ["scan", "flag", arguments @ ..] => scan_flags(session, arguments)?,
+        ["scan", "struct", arguments @ ..] => scan_struct(session, arguments)?,
+        ["scan", "pair", arguments @ ..] => scan_pair(session, arguments)?,
+        ["scan", "string", arguments @ ..] => scan_string(session, arguments)?,
+        ["scan", "masked", arguments @ ..] => scan_masked(session, arguments)?,
+        ["scan", bit, state @ ("set" | "clear"), scope @ ..] if parse_bit(bit).is_some() && scope.len() <= 1 => {
+            let options = scan_scope(&session.options, scope)?;
+            warn_slow_scan(session)?;
+            let (results, stats) = find_bit(&session.process, parse_bit(bit).unwrap(), *state == "set", &options)?;
+            session.scan_type = ValueType::U8;
+            session.scan_endianness = session.options.endianness;
+            session.results = results;
+            session.unknown = None;
+            session.initial = initial_values(session).ok();
+            print_scan_summary(session, &stats);
+            session.stats = stats;
+        }
+        ["scan", scan_type, value, scope @ ..] if scope.len() <= 1 => {
+            let scan_type = scan_type.parse::<ValueType>()?;
+            let options = scan_scope(&session.options, scope)?;
+            warn_slow_scan(session)?;
+            with_scan_type!(scan_type, T, {
+                let (results, stats, endianness) = match parse_shown(value)? {
+                    Some((shown, endianness)) => {
+                        let endianness = endianness.unwrap_or(session.options.endianness);
+                        let (results, stats) = find_rounded::<T>(&session.process, shown, &ScanOptions { endianness, ..options })?;
+                        (results, stats, endianness)
+                    }
+                    None => {
+                        let (value, endianness) = parse_value::<T>(session, value)?;
+                        let (results, stats) = find_value(&session.process, value, &ScanOptions { endianness, ..options })?;
+                        (results, stats, endianness)
+                    }
+                };
+                session.scan_type = scan_type;
+                session.scan_endianness = endianness;
+                session.results = results;
+                session.unknown = None;
+                session.initial = initial_values(session).ok();
+                print_scan_summary(session, &stats);
+                session.stats = stats;
+            });
+        }
+        ["autorescan", "stop"] => match session.autorescan.is_some() {
+            true => stop_autorescan(session, "as asked"),
+            false => return Err("autorescan is not running".into()),
+        },
+        ["autorescan", arguments @ ..] => start_autorescan(session, arguments)?,
+        ["browse", arguments @ ..] => start_browse(session, arguments)?,
+        ["tag", address, tag] => mark(session, address, |x| x.tag = Some(tag.to_string()))?,
+        ["untag", address] => mark(session, address, |x| x.tag = None)?,
+        ["star", address] => mark(session, address, |x| x.starred = true)?,
+        ["unstar", address] => mark(session, address, |x| x.starred = false)?,
+        ["tag" | "untag" | "star" | "unstar", ..] => return Err("Usage: tag <address> \"<text>\", untag <address>, star <address> or unstar <address>".into()),
+        ["filter", "align", alignment] => {
+            let alignment = alignment.parse::<usize>()?;
+            if alignment == 0 {
+                return Err("Expected an alignment of at least 1".into());
+            }
+            filter_results(session, |x| x % alignment == 0)?;
+        }
+        ["filter", "region", filter @ ..] if !filter.is_empty() => {
+            let regions = filter_regions(session, &filter.join(" ").parse::<RegionFilter>()?)?;
+            filter_by_regions(session, &regions)?;
+        }
+        ["filter", "module", name] => {
+            let regions = filter_regions(session, &RegionFilter::Module(name.to_string()))?;
+            if regions.is_empty() {
+                return Err(format!("No module named '{}'", name).into());
+            }
+            filter_by_regions(session, &regions)?;
+        }
+        ["filter", "range", range] => {
+            let (start, end) = range.split_once("..").ok_or("Expected a range like 0x7f0000000000..0x7f8000000000")?;
+            let (start, end) = (parse_address(session, start)?, parse_address(session, end)?);
+            if start >= end {
+                return Err(format!("The range 0x{:x}..0x{:x} is empty", start, end).into());
+            }
+            filter_results(session, |x| (start..end).contains(&x))?;
+        }
+        ["filter", "undo"] => unfilter_results(session)?,
+        ["filter", ..] => return Err("Usage: filter align <alignment>|region <region filter>|module <name>|range <start>..<end>|undo".into()),
+        ["rescan", change @ ("changed" | "unchanged" | "increased" | "decreased")] if session.unknown.is_none() && session.stats.rounded => rescan_rounded_change(session, change)?,
+        ["rescan", "changed" | "unchanged" | "increased" | "decreased", ..] => rescan_unknown(session, &words[1..])?,
+        ["rescan", "struct", fields @ ..] => {
+            let mut pattern = session.struct_pattern.clone().ok_or("rescan struct checks the fields of the last scan struct or scan pair, so start with one")?;
+            // Fields given are checked from now on too
+            if !fields.is_empty() {
+                pattern.fields.extend(fields.join(" ").parse::<StructPattern>()?.fields);
+                session.struct_pattern = Some(pattern.clone());
+            }
+            session.unknown = None;
+            let options = ScanOptions { endianness: session.scan_endianness, ..session.options.clone() };
+            reduce_found_structs(&session.process, &mut session.results, &pattern, &options, &mut session.stats)?;
+            print_rescan_summary(session);
+        }
+        ["rescan", "masked", value, mask @ ..] if matches!(mask, [] | ["mask", _]) => {
+            let mask = match mask {
+                [_, mask] => parse_bits(mask)?,
+                _ => session.scan_mask.ok_or("rescan masked checks with the mask of the last scan masked, so start with one or give mask <mask>")?,
+            };
+            let width = masked_width(session.scan_type)?;
+            let value = parse_bits(value)?;
+            session.unknown = None;
+            let options = ScanOptions { endianness: session.scan_endianness, ..session.options.clone() };
+            reduce_found_masked(&session.process, &mut session.results, value, mask, width, &options, &mut session.stats)?;
+            print_rescan_summary(session);
+        }
+        ["rescan", "flag", state @ ("on" | "off")] => {
+            session.unknown = None;
+            reduce_found_flags(&session.process, &mut session.results, session.flag_convention, *state == "on", &session.options, &mut session.stats)?;
+            print_rescan_summary(session);
+        }
+        // The results take over from an unknown scan's candidates, as after a rescan for a bit
+        ["rescan", "initial"] => {
+            let initial = session.initial.as_ref().ok_or("rescan initial goes back to the values the results had when first found or listed, and there are no results yet")?;
+            with_scan_type!(session.scan_type, T, {
+                let options = ScanOptions { endianness: session.scan_endianness, ..session.options.clone() };
+                reduce_to_initial::<T>(&session.process, &mut session.results, initial, &options, &mut session.stats)?;
+            });
+            session.unknown = None;
+            print_rescan_summary(session);
+        }
+        ["rescan", "expr", _] if session.unknown.is_some() => rescan_unknown(session, &words[1..])?,
+        // The expression is parsed before anything is read, so a typo costs no rescan
+        ["rescan", "expr", expression] => {
+            with_scan_type!(session.scan_type, T, {
+                let expression = Expression::parse::<T>(expression)?;
+                let last = last_values(&session.last, "rescan expr")?;
+                let options = ScanOptions { endianness: session.scan_endianness, ..session.options.clone() };
+                reduce_by_expression::<T>(&session.process, &mut session.results, last, &expression, &options, &mut session.stats)?;
+            });
+            print_rescan_summary(session);
+        }
+        ["rescan", "expr", ..] => return Err("Usage: rescan expr \"<expression>\", e.g. rescan expr \"old * 2\"".into()),
+        ["rescan", _] if session.unknown.is_some() => rescan_unknown(session, &words[1..])?,
+        ["rescan", value] => {
+            with_scan_type!(session.scan_type, T, {
+                let (endianness, value) = strip_endianness(value);
+                if endianness.is_some_and(|x| x != session.scan_endianness) {
+                    return Err(format!("The results were found as {} values", session.scan_endianness).into());
+                }
+                let options = ScanOptions { endianness: session.scan_endianness, ..session.options.clone() };
+                match parse_shown(value)? {
+                    Some((shown, _)) => reduce_found_rounded::<T>(&session.process, &mut session.results, shown, &options, &mut session.stats)?,
+                    None => reduce_found_values(&session.process, &mut session.results, parse_scalar::<T>(value)?, &options, &mut session.stats)?,
+                }
+            });
+            print_rescan_summary(session);
+        }
+        ["rescan", bit, state @ ("set" | "clear")] if parse_bit(bit).is_some() => {
+            session.unknown = None;
+            reduce_found_bits(&session.process, &mut session.results, parse_bit(bit).unwrap(), *state == "set", &session.options, &mut session.stats)?;
+            print_rescan_summary(session);
+        }
+        ["list", ..] if session.results.is_empty() && session.unknown.as_ref().is_some_and(|x| x.candidates() > UNKNOWN_LIST_LIMIT) => {
+            let unknown = session.unknown.as_ref().unwrap();
+            let approximate = if unknown.generation == 0 { "~" } else { "" };
+            say!("candidates: {}{}, listed once rescans leave at most {}", approximate, format_count(unknown.candidates()), format_count(UNKNOWN_LIST_LIMIT));
+        }
+        ["list", arguments @ ..] => {
+            let mut arguments = arguments.to_vec();
+            let format = take_display_format(session, &mut arguments)?;
+            // Starred or tagged, with the indices they have among all the results
+            let marked = take_flag(&mut arguments, "--starred");
+            if arguments.len() > 1 {
+                return Err("Usage: list [<type>] [--starred] [--fmt <format>] [--precision <digits>]".into());
+            }
+            // Another type shows the results read as that, e.g. `list str` for a name field
+            let list_type = arguments.first().map(|x| x.parse::<ValueType>()).transpose()?.unwrap_or(session.scan_type);
+            if let ValueType::Str { encoding: Encoding::Utf16, .. } = list_type {
+                return Err("Only UTF-8 strings can be listed".into());
+            }
+            let listed = session.results.iter().copied().enumerate().filter(|x| !marked || session.marks.contains_key(&x.1)).collect::<Vec<(usize, usize)>>();
+            let (indices, shown): (Vec<usize>, Vec<usize>) = listed.iter().copied().take(LIST_LIMIT).unzip();
+            // Numbers are all read in one gather read, strings one at a time since their lengths are unknown
+            let values = match list_type {
+                ValueType::Str { .. } => shown.iter().map(|x| read_string_lossy(&session.process, &mut session.regions, *x, STRING_LIMIT).map(|x| format!("{:?}", x))).collect::<Vec<_>>(),
+                _ => with_scan_type!(list_type, T, {
+                    read_many_in::<T>(&session.process, &shown, session.scan_endianness).into_iter().map(|x| x.and_then(|x| list_type.value_from_bytes(&x.to_bytes())).map(|x| x.format_as(format))).collect::<Vec<_>>()
+                }),
+            };
+            for ((index, address), value) in indices.into_iter().zip(shown).zip(values) {
+                let permissions = session.regions.find(address).map(|x| format!(" [{}]", x.permissions())).unwrap_or_default();
+                match value {
+                    Ok(x) => say!("#{} {} {}{}{}", index, format_address(session, address), x, permissions, format_mark(session, address)),
+                    Err(e) => say!("#{} {} <{}>{}{}", index, format_address(session, address), e, permissions, format_mark(session, address)),
+                }
+            }
+            if listed.len() > LIST_LIMIT {
+                say!("... {} more", listed.len() - LIST_LIMIT);
+            }
+            if marked && listed.is_empty() {
+                say!("none of the results are starred or tagged");
+            }
+        }
+        ["write", "all", value] | ["write", "all", value, "--force"] => {
+            let force = words.len() > 3;
+            let (endianness, untagged) = strip_endianness(value);
+            let bytes = match untagged.split_once(':') {
+                Some((value_type, _)) if value_type.parse::<ValueType>().is_ok() => untagged.parse::<TypedValue>()?.to_bytes_in(endianness.unwrap_or(session.options.endianness)),
+                _ => with_scan_type!(session.scan_type, T, {
+                    let (value, endianness) = parse_value::<T>(session, value)?;
+                    value.to_bytes_in(endianness)
+                }),
+            };
+            if session.process.backend() == MemBackend::Offline {
+                return Err("Offline captures are read-only".into());
+            }
+            // Results outside writable regions are skipped up front unless forced
+            let mut addresses = Vec::with_capacity(session.results.len());
+            for address in session.results.clone() {
+                match check_writable(session, address, bytes.len(), force) {
+                    Ok(_) => addresses.push(address),
+                    Err(e) => say!("skipped {}: {}", format_address(session, address), e),
+                }
+            }
+            let writes = addresses.iter().map(|x| (*x, bytes.as_slice())).collect::<Vec<(usize, &[u8])>>();
+            let old = read_many_bytes(&session.process, &addresses.iter().map(|x| (*x, bytes.len())).collect::<Vec<(usize, usize)>>());
+            let mut written = 0;
+            for ((address, result), old) in addresses.iter().zip(write_many(&session.process, &writes)).zip(old) {
+                match (result, old) {
+                    (Ok(_), Ok(old)) => {
+                        log_change(session, "write", *address, &old, &bytes, None);
+                        session.journal.record(*address, old, bytes.clone(), "write");
+                        written += 1;
+                    }
+                    (Ok(_), Err(_)) => written += 1,
+                    (Err(e), _) => say!("could not write {}: {}", format_address(session, *address), e),
+                }
+            }
+            say!("wrote {} of {} results", written, session.results.len());
+        }
+        ["write", address, operation @ ("+=" | "-=" | "*="), operand, flags @ ..] => {
+            let mut flags = flags.to_vec();
+            let force = take_flag(&mut flags, "--force");
+            let wrapping = take_flag(&mut flags, "--wrapping");
+            if let Some(flag) = flags.first() {
+                return Err(format!("Unexpected '{}'", flag).into());
+            }
+            let address = parse_address(session, address)?;
+            write_arithmetic(session, address, operation.parse()?, operand, wrapping, force)?;
+        }
+        ["replace-string", address, text, flags @ ..] => replace_string(session, address, text, flags)?,
+        ["replace-string", ..] => return Err("Usage: replace-string <address> \"<text>\" [--utf16] [--pad null|space|none] [--force]".into()),
+        ["write", address, encoding @ ("string" | "utf16"), text, flags @ ..] => {
+            let encoding = if *encoding == "utf16" { Encoding::Utf16 } else { Encoding::Utf8 };
+            write_string_command(session, address, encoding, text, flags)?;
+        }
+        ["write", address, bit, value] | ["write", address, bit, value, "--force"] if parse_bit(bit).is_some() => {
+            let address = parse_address(session, address)?;
+            let bit = parse_bit(bit).unwrap() as usize;
+            let value = match *value {
+                "0" => 0,
+                "1" => 1,
+                _ => return Err("A bit is written as 0 or 1".into()),
+            };
+            check_writable(session, address + bit / 8, 1, words.len() > 4)?;
+            let (old, new) = write_bits(&session.process, address, bit, 1, value)?;
+            log_change(session, "write", address + bit / 8, &old, &new, None);
+            session.journal.record(address + bit / 8, old, new, "write");
+        }
+        ["write", address, value] | ["write", address, value, "--force"] => {
+            let address = parse_address(session, address)?;
+            let force = words.len() > 3;
+            // A tagged value like "u8:7", "be:u16:7" or "strz:name" carries its own type
+            let (endianness, untagged) = strip_endianness(value);
+            if let Some((value_type, _)) = untagged.split_once(':') && value_type.parse::<ValueType>().is_ok() {
+                let bytes = untagged.parse::<TypedValue>()?.to_bytes_in(endianness.unwrap_or(session.options.endianness));
+                check_writable(session, address, bytes.len(), force)?;
+                return journaled_write(session, address, &bytes, "write").map(|_| true);
+            }
+            with_scan_type!(session.scan_type, T, {
+                let (value, endianness) = parse_value::<T>(session, value)?;
+                check_writable(session, address, T::SIZE, force)?;
+                journaled_write(session, address, &value.to_bytes_in(endianness), "write")?;
+            });
+        }
+        ["poke", address, bytes] | ["poke", address, bytes, "--force"] => {
+            let address = parse_address(session, address)?;
+            let bytes = parse_hex_bytes(bytes)?;
+            check_writable(session, address, bytes.len(), words.len() > 3)?;
+            journaled_write(session, address, &bytes, "poke")?;
+        }
+        ["undo"] => match undo(session, None)? {
+            true => {}
+            false => say!("nothing to undo"),
+        },
+        ["undo", "all"] => {
+            let mut undone = 0;
+            while undo(session, None)? {
+                undone += 1;
+            }
+            say!("undid {} writes", undone);
+        }
+        ["undo", id] => {
+            let id = id.strip_prefix('#').and_then(|x| x.parse::<usize>().ok()).ok_or_else(|| format!("Expected undo, undo all or undo #<id>, not undo {}", id))?;
+            undo(session, Some(id))?;
+        }
+        ["redo"] => match redo(session)? {
+            true => {}
+            false => say!("nothing to redo"),
+        },
+        ["errors"] => list_read_failures(session),
+        ["journal"] => {
+            let entries = session.journal.entries.clone();
+            for entry in &entries {
+                let age = entry.time.elapsed().unwrap_or_default().as_secs();
+                say!("#{} {} {} {} -> {} ({}s ago)", entry.id, entry.kind, format_address(session, entry.address), format_hex(&entry.old), format_hex(&entry.new), age);
+            }
+            if let Some(entry) = session.journal.undone.last() {
+                say!("{} undone, `redo` writes #{} again", session.journal.undone.len(), entry.id);
+            }
+        }
+        ["journal", "file"] => match &session.journal_file {
+            Some(file) => say!("journaling every change to {}", file.path().display()),
+            None => say!("no journal file; `journal file <path>` starts one"),
+        },
+        ["journal", "file", "off"] => match session.journal_file.take() {
+            Some(file) => say!("stopped journaling to {}", file.path().display()),
+            None => say!("no journal file"),
+        },
+        ["journal", "file", path] => {
+            session.journal_file = Some(JournalFile::open(path)?);
+            say!("journaling every change to {}", path);
+        }
+        ["journal", "replay", arguments @ ..] => replay_journal(session, arguments)?,
+        ["nop", address, len] | ["nop", address, len, "--force"] => {
+            let address = parse_address(session, address)?;
+            let len = parse_size(len)?;
+            let patch = patch(session, address, &vec![NOP; len], words.len() > 3)?;
+            say!("patched {} bytes at {} (was {})", len, format_address(session, address), format_hex(&patch.original));
+            session.patches.push(patch);
+        }
+        ["patches"] => {
+            let patches = session.patches.iter().map(|x| (x.address, format!("{} bytes: {} -> {}", x.len(), format_hex(&x.original), format_hex(&x.patched)))).collect::<Vec<_>>();
+            for (address, patch) in patches {
+                say!("{} {}", format_address(session, address), patch);
+            }
+        }
+        ["restore", "all"] => restore_patches(session),
+        ["restore", address] => {
+            let address = parse_address(session, address)?;
+            restore_patch(session, address)?;
+        }
+        ["make_writable", address, len] | ["make_writable", address, len, "--restore"] => {
+            let address = parse_address(session, address)?;
+            let len = parse_size(len)?;
+            require_dangerous(session, "make_writable")?;
+            make_writable(session, address, len, words.len() > 3)?;
+        }
+        ["alloc", arguments @ ..] => {
+            require_dangerous(session, "alloc")?;
+            alloc(session, arguments)?;
+        }
+        ["allocations"] => list_allocations(session),
+        ["inject", code] | ["inject", code, "--at", _] => {
+            let code = parse_hex_bytes(code)?;
+            let at = words.get(3).map(|x| parse_address(session, x)).transpose()?;
+            require_dangerous(session, "inject")?;
+            inject(session, &code, at)?;
+        }
+        ["injections"] => list_injections(session),
+        ["uninject", "all"] => remove_injections(session),
+        ["uninject", address] => {
+            let address = parse_address(session, address)?;
+            uninject(session, address)?;
+        }
+        ["free", address] => {
+            let address = parse_address(session, address)?;
+            require_dangerous(session, "free")?;
+            free(session, address)?;
+        }
+        ["lock", "interval", address, interval] => {
+            let address = parse_address(session, address)?;
+            if !session.locks.set_interval(address, parse_duration(interval)?) {
+                return Err(format!("No lock at 0x{:x}", address).into());
+            }
+        }
+        ["lock", "renew", address] => {
+            let address = parse_address(session, address)?;
+            if !session.locks.renew(address) {
+                return Err(format!("No lock with a duration at 0x{:x}", address).into());
+            }
+        }
+        ["lock", arguments @ ..] => run_lock(session, arguments)?,
+        // Flags only, as anything else is one of the subcommands below
+        ["locks", arguments @ ..] if arguments.first().is_none_or(|x| x.starts_with("--")) => {
+            let mut arguments = arguments.to_vec();
+            let format = take_display_format(session, &mut arguments)?;
+            let verbose = take_flag(&mut arguments, "--verbose");
+            let all = take_flag(&mut arguments, "--all");
+            if let Some(argument) = arguments.first() {
+                return Err(format!("Unexpected '{}'", argument).into());
+            }
+            if !all {
+                print_locks(session, format, verbose, "");
+                return Ok(true);
+            }
+            say!("{}, in use:", describe_target(&session.process));
+            print_locks(session, format, verbose, "  ");
+            for pid in session.targets.keys().copied().collect::<Vec<Pid>>() {
+                with_target(session, pid, |session| {
+                    say!("{}:", describe_target(&session.process));
+                    print_locks(session, format, verbose, "  ");
+                });
+            }
+        }
+        ["attach", pid] => attach_target(session, pid)?,
+        ["attach", ..] => return Err("Usage: attach <pid>".into()),
+        ["use", which] => use_target(session, which)?,
+        ["use", ..] => return Err("Usage: use <pid|name>".into()),
+        ["targets"] => list_targets(session),
+        ["detach", which] => detach_target(session, which)?,
+        ["detach", ..] => return Err("Usage: detach <pid|name>".into()),
+        ["locks", "pause", "all"] => session.locks.pause_all(),
+        ["locks", "resume", "all"] => session.locks.resume_all(),
+        ["locks", "toggle", "all"] => {
+            if session.locks.list().iter().any(|x| x.enabled) {
+                session.locks.pause_all();
+            }
+            else {
+                session.locks.resume_all();
+            }
+        }
+        ["locks", "toggle", address] => {
+            let address = parse_address(session, address)?;
+            let lock = session.locks.get(address).ok_or(format!("No lock at 0x{:x}", address))?;
+            session.locks.set_enabled(address, !lock.enabled);
+        }
+        ["locks", operation @ ("pause" | "resume"), address] => {
+            let address = parse_address(session, address)?;
+            let found = if *operation == "pause" { session.locks.pause(address) } else { session.locks.resume(address) };
+            if !found {
+                return Err(format!("No lock at 0x{:x}", address).into());
+            }
+        }
+        ["unlock", "all"] => {
+            for lock in session.locks.list() {
+                log_change(session, "unlock", lock.address, &[], &[], None);
+            }
+            session.locks.remove_all();
+        }
+        ["unlock", address] => {
+            let address = parse_address(session, address)?;
+            if !session.locks.unlock_value(address) {
+                return Err(format!("No lock at 0x{:x}", address).into());
+            }
+            log_change(session, "unlock", address, &[], &[], None);
+        }
+        ["bind", key, _, ..] => {
+            let command = line.trim().strip_prefix("bind").unwrap_or_default().trim_start().strip_prefix(key).unwrap_or_default().trim();
+            bind(session, key, command)?;
+        }
+        ["unbind", key] => {
+            if session.bindings.remove(&key.to_ascii_uppercase()).is_none() {
+                return Err(format!("Nothing is bound to {}", key).into());
+            }
+        }
+        ["script", "run", path] => {
+            let source = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+            start_script(session, path.to_string(), source)?;
+        }
+        ["script", "eval", _, ..] => {
+            let code = line.trim().strip_prefix("script").unwrap_or_default().trim_start().strip_prefix("eval").unwrap_or_default().trim();
+            let code = code.strip_prefix('\'').and_then(|x| x.strip_suffix('\'')).unwrap_or(code);
+            start_script(session, "eval".to_string(), code.to_string())?;
+        }
+        ["script", "stop", which] => stop_scripts(session, which)?,
+        ["scripts"] => list_scripts(session),
+        ["watchwrite", address, size] => {
+            let address = parse_address(session, address)?;
+            watch_address(session, address, parse_size(size)?, false)?;
+        }
+        // The size defaults to the scan type's, since that is usually what was found there
+        ["watchaccess", address, size @ ..] if size.len() <= 1 => {
+            let address = parse_address(session, address)?;
+            let size = match size {
+                [size] => parse_size(size)?,
+                _ => session.scan_type.size().ok_or("Give a size, since the scan type has none")?,
+            };
+            watch_address(session, address, size, true)?;
+        }
+        ["watches"] => list_watches(session),
+        ["watches", "export", path] => export_watches(session, path)?,
+        ["unwatch", which] => unwatch(session, which)?,
+        ["history", "export", path, value_type @ ..] if value_type.len() <= 1 => export_history(session, path, value_type.first().copied())?,
+        ["history", arguments @ ..] => show_history(session, arguments)?,
+        ["set", "watch_history", "off"] => set_watch_history(session, 0),
+        ["set", "watch_history", limit] => set_watch_history(session, limit.parse()?),
+        ["break", address] => {
+            let address = parse_address(session, address)?;
+            set_breakpoint(session, address)?;
+        }
+        ["breakpoints"] => list_breakpoints(session),
+        ["unbreak", which] => unbreak(session, which)?,
+        ["bindings"] => {
+            for (key, command) in &session.bindings {
+                say!("{} => {}", key, command);
+            }
+        }
+        ["maps", filter @ ..] => {
+            session.regions.refresh()?;
+            let regions = match filter {
+                [] => session.regions.regions().iter().collect::<Vec<&MemoryRegion>>(),
+                filter => filter.join(" ").parse::<RegionFilter>()?.select(&session.process, session.regions.regions()),
+            };
+            for region in regions {
+                let deleted = if region.deleted { " (deleted)" } else { "" };
+                let allocated = allocated_by(session, region).map(|x| format!(" [allocated by {}]", x)).unwrap_or_default();
+                say!("0x{:x}-0x{:x} {} {:>10} offset 0x{:x} {}{}{}", region.start, region.end, region.permissions(), format_bytes(region.len()), region.offset, region.pathname, deleted, allocated);
+            }
+        }
+        ["threads"] => list_threads(session)?,
+        ["stacks"] => list_stacks(session)?,
+        ["status"] => show_status(session)?,
+        ["doctor"] if session.process.backend() == MemBackend::Offline => return Err("An offline capture has no process to check".into()),
+        ["doctor"] => doctor(session.process.pid())?,
+        ["reattach"] => reattach(session, None)?,
+        ["reattach", pid] => reattach(session, Some(Pid::from_raw(pid.parse().map_err(|_| format!("Not a pid: {}", pid))?)))?,
+        ["follow"] => set_follow(session, None)?,
+        ["follow", state @ ("on" | "off")] => set_follow(session, Some(*state == "on"))?,
+        ["bt"] => show_backtrace(session, None)?,
+        ["bt", "depth", depth] => set_backtrace_depth(session, depth.parse()?),
+        ["bt", tid] => show_backtrace(session, Some(Pid::from_raw(tid.parse().map_err(|_| format!("Not a thread id: {}", tid))?)))?,
+        ["disasm", address] => {
+            let address = parse_address(session, address)?;
+            print_disassembly(session, address, 16)?;
+        }
+        ["disasm", address, count] => {
+            let address = parse_address(session, address)?;
+            print_disassembly(session, address, count.parse()?)?;
+        }
+        ["guess", address] => {
+            let address = parse_address(session, address)?;
+            guess(session, address)?;
+        }
+        ["guess", ..] => return Err("Usage: guess <address>".into()),
+        ["pointers", arguments @ ..] => find_pointers(session, arguments)?,
+        ["pointermap", "build", file] => build_pointer_map(session, file)?,
+        ["pointermap", ..] => return Err("Usage: pointermap build <file>".into()),
+        ["pointerscan", arguments @ ..] => pointer_scan(session, arguments)?,
+        ["sym", address] => {
+            let address = parse_address(session, address)?;
+            say!("0x{:x} {}", address, describe_symbol(&mut session.regions, address));
+        }
+        ["addr", name] => {
+            session.regions.refresh()?;
+            let found = memory::find_symbol(session.regions.modules(), name);
+            if found.is_empty() {
+                return Err(format!("No loaded module has a symbol named {}", name).into());
+            }
+            for location in found {
+                say!("0x{:x} {}", location.address, location);
+            }
+        }
+        ["regs"] => show_registers(session, None)?,
+        ["regs", tid] => show_registers(session, Some(Pid::from_raw(tid.parse().map_err(|_| format!("Not a thread id: {}", tid))?)))?,
+        ["modules"] | ["modules", "--json"] => {
+            session.regions.refresh()?;
+            let executable = session.process.executable_path();
+            let modules = session.regions.modules();
+            if words.len() > 1 {
+                let entries = modules.iter().map(|x| module_json(x, executable.as_deref() == Some(x.path.as_str()))).collect::<Vec<serde_json::Value>>();
+                say!("{}", serde_json::Value::Array(entries));
+            }
+            else {
+                for module in modules {
+                    let mut flags = Vec::new();
+                    if executable.as_deref() == Some(module.path.as_str()) {
+                        flags.push("main");
+                    }
+                    if !module.executable {
+                        flags.push("data");
+                    }
+                    if module.writable {
+                        flags.push("writable");
+                    }
+                    if module.deleted {
+                        flags.push("deleted");
+                    }
+                    let flags = if flags.is_empty() { String::new() } else { format!(" ({})", flags.join(", ")) };
+                    say!("0x{:x} {:>10} {}{} {}", module.base, format_bytes(module.size), module.name, flags, module.path);
+                }
+                let deleted = modules.iter().filter(|x| x.deleted).count();
+                if deleted > 0 {
+                    say!("warning: {} modules were deleted or replaced on disk after loading; offsets into them describe the old file", deleted);
+                }
+            }
+        }
+        ["dump", "all", file] => {
+            let dumped = dump_all(&session.process, Path::new(file))?;
+            let holes = dumped.iter().filter(|x| !x.holes.is_empty()).count();
+            say!("dumped {} regions ({}) to {}, {} with unreadable holes", dumped.len(), format_bytes(dumped.iter().map(|x| x.region.len()).sum()), file, holes);
+        }
+        ["capture", "info", file] => print_capture_info(&Capture::open(Path::new(file))?),
+        ["dump", "region", address, file] => {
+            let address = parse_address(session, address)?;
+            let (start, end) = session.regions.region_for_address(address).map(|x| (x.start, x.end)).ok_or(format!("0x{:x} is not mapped", address))?;
+            print_dump(dump_to_file(&session.process, start, end, Path::new(file))?, start, end, file);
+        }
+        ["dump", address, len, file] => {
+            let start = parse_address(session, address)?;
+            let end = start.checked_add(parse_size(len)?).ok_or("Range overflows")?;
+            print_dump(dump_to_file(&session.process, start, end, Path::new(file))?, start, end, file);
+        }
+        ["save", path] | ["session", "save", path] => save_session(session, path)?,
+        ["export", "ct", path] => export_cheat_table(session, path)?,
+        ["session", "load", "--recover"] => load_recovered(session)?,
+        ["load", path] | ["session", "load", path] => load_session(session, path)?,
+        ["resolve", "all", flags @ ..] => {
+            let mut flags = flags.to_vec();
+            let resume = take_flag(&mut flags, "--resume");
+            if !flags.is_empty() {
+                return Err("Usage: resolve all [--resume]".into());
+            }
+            resolve_all(session, resume)?
+        }
+        ["snapshot", "diff", before, after, arguments @ ..] => diff_snapshots(session, before, after, arguments)?,
+        ["crosscompare", first, second, arguments @ ..] => cross_compare(session, first, second, arguments)?,
+        ["snapshot"] => {
+            warn_slow_scan(session)?;
+            let snapshot = Snapshot::capture(&session.process, &session.options)?;
+            say!("captured {} in {} chunks, stored as {}; heatmap compares with it", format_bytes(snapshot.len()), snapshot.chunks.len(), format_bytes(snapshot.stored_len()));
+            print_fallbacks(&snapshot.fallbacks);
+            if !snapshot.failed_regions.is_empty() {
+                say!("{} regions could not be read", snapshot.failed_regions.len());
+            }
+            session.snapshot = Some((snapshot, SystemTime::now()));
+        }
+        ["heatmap", arguments @ ..] => heatmap(session, arguments)?,
+        ["autosnap", "start", arguments @ ..] => start_autosnap(session, arguments)?,
+        ["autosnap", "stop"] => match session.autosnap.as_mut().filter(|x| x.running()) {
+            Some(autosnap) => {
+                autosnap.stop();
+                say!("stopped autosnap, keeping {} snapshots in {}", autosnap.snapshots().len(), autosnap.options().directory.display());
+            }
+            None => say!("autosnap is not running"),
+        },
+        ["autosnap"] | ["autosnap", "list"] => list_autosnaps(session)?,
+        ["monitor", "start", arguments @ ..] => start_value_monitor(session, arguments)?,
+        ["monitor", "stop"] => match session.value_monitor.take() {
+            Some(monitoring) => say!("stopped the monitor after {} alerts", monitoring.alerts),
+            None => say!("the monitor is not running"),
+        },
+        ["monitor"] => monitor_status(session)?,
+        ["watchexpr"] => list_expressions(session),
+        ["watchexpr", arguments @ ..] => watch_expression(session, arguments)?,
+        ["unwatchexpr", name] => unwatch_expression(session, name)?,
+        ["autosnap", "diff", from, to, arguments @ ..] => diff_autosnaps(session, from, to, arguments)?,
+        ["snapshot", file] => snapshot_to_file(session, file, false)?,
+        ["snapshot", file, "--stop"] => snapshot_to_file(session, file, true)?,
+        ["backend"] => say!("{}", describe_backend(&session.process)),
+        ["arch"] => say!("{}", session.process.arch()),
+        ["endian"] => say!("{}", session.options.endianness),
+        ["endian", endianness] => session.options.endianness = endianness.parse()?,
+        ["set", "compress_snapshots", value] => {
+            session.options.compress_snapshots = parse_toggle(value)?;
+        }
+        ["set", "lock_interval", interval] => {
+            session.lock_interval = parse_duration(interval)?;
+        }
+        ["set", "browse_layout", layout @ ..] if !layout.is_empty() => {
+            session.browse_layout = layout.join(" ").parse()?;
+        }
+        ["set", "range_lock_limit", size] => {
+            session.range_lock_limit = parse_size(size)?;
+        }
+        ["set", "drop_unmapped", value] => {
+            session.options.drop_unmapped = parse_toggle(value)?;
+        }
+        ["set", "relative_addresses", value] => {
+            session.relative_addresses = parse_toggle(value)?;
+        }
+        ["set", "format", radix] => session.display.radix = radix.parse()?,
+        ["set", "precision", "off"] => session.display.precision = None,
+        ["set", "precision", digits] => session.display.precision = Some(digits.parse()?),
+        ["set", name, value] if name.strip_prefix("include_").is_some_and(|x| category_toggle(&mut session.options, x).is_some()) => {
+            let value = parse_toggle(value)?;
+            *category_toggle(&mut session.options, &name["include_".len()..]).unwrap() = value;
+        }
+        ["set", "resident_only", value] => {
+            if session.process.backend() == MemBackend::Offline {
+                return Err("Residency needs a live process".into());
+            }
+            session.options.resident_only = parse_toggle(value)?;
+        }
+        ["set", "soft_dirty", value] => {
+            if session.process.backend() == MemBackend::Offline {
+                return Err("Soft-dirty tracking needs a live process".into());
+            }
+            session.options.soft_dirty = parse_toggle(value)?;
+        }
+        ["set", "stop_after", count] => {
+            session.options.stop_after = match *count {
+                "none" | "off" => None,
+                count => Some(count.parse::<usize>()?),
+            };
+        }
+        ["set", "filter", "none" | "off"] => session.options.filter = None,
+        ["set", "filter", filter @ ..] if !filter.is_empty() => {
+            session.options.filter = Some(filter.join(" ").parse::<RegionFilter>()?);
+        }
+        // set retry <attempts> [<delay>], e.g. `set retry 3 5ms`; `set retry 1` turns retrying off
+        ["set", "retry", attempts] | ["set", "retry", attempts, _] => {
+            let delay = words.get(3).map(|x| parse_duration(x)).transpose()?.unwrap_or(session.options.retry.delay);
+            session.options.retry = Retry::new(attempts.parse()?, delay);
+        }
+        ["set", "mem_budget", size] => {
+            session.options.memory_budget = match *size {
+                "none" | "off" => None,
+                size => Some(parse_size(size)?),
+            };
+        }
+        ["set", "max_region_size", size] => {
+            session.options.max_region_size = match *size {
+                "none" | "off" => None,
+                size => Some(parse_size(size)?),
+            };
+        }
+        ["help", "rescan"] => print_rescan_help(),
+        ["help", ..] => return Err("Usage: help rescan".into()),
+        _ => return Err(format!("Unknown command '{}'", line.trim()).into()),
+    }
+    drop_lost_marks(session);
+    Ok(true)
+}
+
+// Each step of --self-test, as a name and what it found or why it failed
+type SelfTestStep = Result<String, Box<dyn std::error::Error>>;
+
+// Runs the whole pipeline on this process, which needs no permission to attach to anything: a
+// value is planted in its own heap, found by scanning the maps' regions, narrowed after it
+// changes, written, and locked against this process writing over it. Fails if any step does
+fn self_test() -> Result<(), Box<dyn std::error::Error>> {
+    let process = Process::current()?;
+    say!("testing on this process ({}) using {}", process.pid(), describe_backend(&process));
+    // Different on every run, so a copy left behind by something else is unlikely to match
+    let seed = 0x5e1f_0000 | (SystemTime::now().duration_since(UNIX_EPOCH)?.subsec_nanos() as i32 & 0xfff0);
+    let planted = Box::new(AtomicI32::new(seed));
+    let address = planted.as_ptr() as usize;
+    let mut failed = 0;
+    let mut report = |name: &str, step: SelfTestStep| match step {
+        Ok(found) => say!("pass  {}: {}", name, found),
+        Err(e) => {
+            failed += 1;
+            say!("FAIL  {}: {}", name, e);
+        }
+    };
+    report("maps", (|| -> SelfTestStep {
+        let regions = process.memory_regions()?;
+        let region = regions.iter().find(|x| x.contains(address)).ok_or(format!("no region holds 0x{:x}", address))?;
+        if !region.readable || !region.writable {
+            return Err(format!("0x{:x} is in a {} region", address, region.permissions()).into());
+        }
+        Ok(format!("{} regions, 0x{:x} in 0x{:x}-0x{:x} {} {}", regions.len(), address, region.start, region.end, region.permissions(), region.pathname))
+    })());
+    let options = ScanOptions::default();
+    let (mut found, mut stats) = (Vec::new(), ScanStats::default());
+    report("scan", (|| -> SelfTestStep {
+        (found, stats) = find_value(&process, seed, &options)?;
+        if !found.contains(&address) {
+            return Err(format!("0x{:x} is not among the {} found", address, found.len()).into());
+        }
+        Ok(format!("{} found in {}, 0x{:x} among them", found.len(), format_bytes(stats.bytes_scanned), address))
+    })());
+    report("narrow", (|| -> SelfTestStep {
+        planted.store(seed + 1, Ordering::SeqCst);
+        let before = found.len();
+        reduce_found_values(&process, &mut found, seed + 1, &options, &mut stats)?;
+        if !found.contains(&address) {
+            return Err(format!("0x{:x} was dropped after changing to {}", address, seed + 1).into());
+        }
+        Ok(format!("{} of {} left after changing it", found.len(), before))
+    })());
+    report("write", (|| -> SelfTestStep {
+        write_to_process(&process, address, &(seed + 2))?;
+        match planted.load(Ordering::SeqCst) {
+            value if value == seed + 2 => Ok(format!("wrote {}", value)),
+            value => Err(format!("wrote {} but this process sees {}", seed + 2, value).into()),
+        }
+    })());
+    report("lock", (|| -> SelfTestStep {
+        let mut locks = LockManager::new(process.clone());
+        locks.lock_value(seed + 3, address, Duration::from_millis(1));
+        planted.store(seed + 4, Ordering::SeqCst);
+        let started = Instant::now();
+        while planted.load(Ordering::SeqCst) != seed + 3 {
+            if started.elapsed() > Duration::from_secs(1) {
+                return Err(format!("still {} a second after locking it to {}", planted.load(Ordering::SeqCst), seed + 3).into());
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let writes = locks.get(address).map(|x| x.writes).unwrap_or(0);
+        locks.unlock_value(address);
+        Ok(format!("held {} over this process's own write, {} writes", seed + 3, writes))
+    })());
+    match failed {
+        0 => {
+            say!("self-test passed");
+            Ok(())
+        }
+        failed => Err(format!("{} of 5 self-test steps failed", failed).into()),
+    }
+}
+
+// Runs the session for clients of the socket instead of stdin, until killed
+fn serve_session(address: &ServeAddress, allow_remote: bool, options: ScanOptions, process: Option<Process>) -> Result<(), Box<dyn std::error::Error>> {
+    let server = Server::bind(address, allow_remote, options)?;
+    if let Some(process) = process {
+        server.attach(process)?;
+    }
+    say!("serving on {}", server.local_address()?);
+    server.run()
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = std::env::args().collect::<Vec<String>>();
+    let target = args.get(1).ok_or("Usage: memory <pid>|--offline <capture>|--self-test|--doctor <pid> [--backend process_vm|procmem|ptrace|win32|mach] [--max-region-size <size>] [--resident-only] [--no-heap|--no-stack|--no-anonymous|--no-file-backed|--no-main-executable] [--lock-interval <duration>] [--seize] [--config <path>] [--serve <socket path>|<ip:port> [--serve-remote]] [--scanmem-compat] [--machine] [--dangerous] [--follow] [--journal <path>] [--throttle <MB/s>] [--priority normal|nice|batch|idle] [--mem-budget <size>] [--fix-perms]")?;
+    if target == "--self-test" {
+        if args.len() > 2 {
+            return Err("--self-test takes no other arguments".into());
+        }
+        return self_test();
+    }
+    if target == "--doctor" {
+        let pid = args.get(2).ok_or("Expected a pid after --doctor")?;
+        if args.len() > 3 {
+            return Err("--doctor takes only a pid".into());
+        }
+        return doctor(Pid::from_raw(pid.parse().map_err(|_| format!("Not a pid: {}", pid))?));
+    }
+    // With no process the server waits for a client to attach one, and a scanmem frontend sends "pid"
+    let standalone = target == "--serve" || target == "--scanmem-compat";
+    let offline = match target.as_str() {
+        "--offline" => Some(Path::new(args.get(2).ok_or("Expected a capture written by `dump all` after --offline")?)),
+        _ => None,
+    };
+    let mut options = ScanOptions::default();
+    let mut backend: Option<MemBackend> = None;
+    let mut seize = false;
+    let mut lock_interval = DEFAULT_LOCK_INTERVAL;
+    let mut config: Option<std::path::PathBuf> = None;
+    let mut serve: Option<ServeAddress> = None;
+    let mut serve_remote = false;
+    let mut scanmem = standalone && target == "--scanmem-compat";
+    let mut machine = false;
+    let mut dangerous = false;
+    let mut follow = false;
+    let mut journal: Option<PathBuf> = None;
+    let mut fix_perms = false;
+    let mut iter = args.iter().skip(if offline.is_some() { 3 } else if standalone { 1 } else { 2 });
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--backend" => backend = Some(iter.next().ok_or("Expected a backend after --backend")?.parse::<MemBackend>()?),
+            "--config" => config = Some(iter.next().ok_or("Expected a path after --config")?.into()),
+            "--lock-interval" => lock_interval = parse_duration(iter.next().ok_or("Expected a duration after --lock-interval")?)?,
+            "--max-region-size" => options.max_region_size = Some(parse_size(iter.next().ok_or("Expected a size after --max-region-size")?)?),
+            "--resident-only" => options.resident_only = true,
+            "--seize" => seize = true,
+            "--serve" => serve = Some(iter.next().ok_or("Expected a socket path or ip:port after --serve")?.parse::<ServeAddress>()?),
+            "--serve-remote" => serve_remote = true,
+            "--scanmem-compat" => scanmem = true,
+            "--machine" => machine = true,
+            "--dangerous" => dangerous = true,
+            "--follow" => follow = true,
+            "--fix-perms" => fix_perms = true,
+            "--journal" => journal = Some(iter.next().ok_or("Expected a path after --journal")?.into()),
+            "--priority" => options.priority = iter.next().ok_or("Expected normal, nice, batch or idle after --priority")?.parse::<ScanPriority>()?,
+            "--mem-budget" => options.memory_budget = Some(parse_size(iter.next().ok_or("Expected a size after --mem-budget")?)?),
+            "--throttle" => options.throttle = Some(parse_throttle(iter.next().ok_or("Expected a rate in MB/s after --throttle")?)?),
+            flag if flag.starts_with("--no-") && let Some(include) = category_toggle(&mut options, &flag[5..].replace('-', "_")) => *include = false,
+            _ => return Err(format!("Unknown argument '{}'", arg).into()),
+        }
+    }
+    if serve_remote && serve.is_none() {
+        return Err("--serve-remote only goes with --serve".into());
+    }
+    if scanmem && serve.is_some() {
+        return Err("--scanmem-compat and --serve both take over the session, so only one can be given".into());
+    }
+    if machine && (scanmem || serve.is_some() || standalone) {
+        return Err("--machine needs a pid or --offline, and replaces the prompt, so it does not go with --serve or --scanmem-compat".into());
+    }
+    if fix_perms && (standalone || offline.is_some()) {
+        return Err("--fix-perms is for attaching to a process, so it needs a pid".into());
+    }
+    if standalone {
+        if seize || backend.is_some() {
+            return Err("--seize and --backend need a pid; without one, the client's attach picks the backend".into());
+        }
+        if scanmem {
+            return Ok(ScanmemSession::new(None, options).run(std::io::stdin().lock(), &mut std::io::stdout(), &mut std::io::stderr())?);
+        }
+        return serve_session(&serve.ok_or("Expected an address after --serve")?, serve_remote, options, None);
+    }
+    // Progress is an event there, wherever standard error goes
+    if machine {
+        MACHINE.store(true, Ordering::Relaxed);
+        options.progress = Some(ProgressCallback(Arc::new(print_progress)));
+    }
+    else if std::io::stderr().is_terminal() {
+        options.progress = Some(ProgressCallback(Arc::new(print_progress)));
+    }
+    #[cfg(not(target_os = "linux"))]
+    if follow {
+        return Err("--follow watches /proc for the target, so only works on Linux".into());
+    }
+    if fix_perms {
+        fix_permissions(Pid::from_raw(target.parse::<i32>()?), &args)?;
+    }
+    let attached = match (offline, backend) {
+        (Some(_), Some(_)) => return Err("--backend does not apply to --offline".into()),
+        (Some(_), None) if seize => return Err("--seize needs a live process".into()),
+        (Some(_), None) if follow => return Err("--follow needs a live process".into()),
+        (None, Some(_)) if seize => return Err("--seize always reads /proc/<pid>/mem, so it does not go with --backend".into()),
+        (None, None) if seize => Process::seize(Pid::from_raw(target.parse::<i32>()?)),
+        (Some(_), None) if options.resident_only => return Err("--resident-only needs a live process".into()),
+        (Some(dir), None) => Process::offline(dir),
+        (None, Some(backend)) => Process::with_backend(Pid::from_raw(target.parse::<i32>()?), backend),
+        (None, None) => Process::attach(Pid::from_raw(target.parse::<i32>()?)),
+    };
+    let process = match attached {
+        Ok(process) => process,
+        // Says what stands in the way before giving up, rather than leaving it at EPERM
+        #[cfg(target_os = "linux")]
+        Err(e) if offline.is_none() => {
+            say!("could not attach to {}, checking why:", target);
+            print_preflight(&memory::preflight(Pid::from_raw(target.parse::<i32>()?)));
+            return Err(e);
+        }
+        Err(e) => return Err(e),
+    };
+    // The frontend reads everything on standard output, so nothing else goes there
+    if scanmem {
+        return Ok(ScanmemSession::new(Some(process), options).run(std::io::stdin().lock(), &mut std::io::stdout(), &mut std::io::stderr())?);
+    }
+    match offline {
+        _ if machine => MachineMessage::Attached { pid: process.pid().as_raw(), backend: process.backend().to_string(), version: memory::MACHINE_VERSION }.emit(),
+        Some(dir) => say!("opened capture of {} from {} (read-only)", process.pid(), dir.display()),
+        None => say!("attached to {} using {}", process.pid(), describe_backend(&process)),
+    }
+    // Memory is still read and written without ptrace, so this only stops what needs it
+    #[cfg(target_os = "linux")]
+    if offline.is_none() && !machine && let Err(traced) = memory::check_not_traced(process.pid()) {
+        say!("warning: {}; reading and writing its memory still works", traced);
+    }
+    if let Some(address) = serve {
+        return serve_session(&address, serve_remote, options, Some(process));
+    }
+    let (input, receiver): (Sender<Input>, Receiver<Input>) = std::sync::mpsc::channel();
+    let mut session = Session {
+        process: process.clone(),
+        scan_type: ValueType::I32,
+        results: Vec::new(),
+        initial: None,
+        last: None,
+        autorescan: None,
+        value_monitor: None,
+        expressions: None,
+        browsing: None,
+        browse_layout: DEFAULT_BROWSE_LAYOUT.parse()?,
+        unfiltered: Vec::new(),
+        marks: BTreeMap::new(),
+        flag_convention: FlagConvention::default(),
+        struct_pattern: None,
+        scan_mask: None,
+        lock_chains: BTreeMap::new(),
+        saved: None,
+        scan_endianness: Endianness::Native,
+        stats: ScanStats::default(),
+        unknown: None,
+        snapshot: None,
+        autosnap: None,
+        options,
+        locks: LockManager::new(process.clone()),
+        lock_interval,
+        range_lock_limit: DEFAULT_RANGE_LOCK_LIMIT,
+        patches: Vec::new(),
+        journal: Journal::default(),
+        journal_file: journal.map(JournalFile::open).transpose()?,
+        command: String::new(),
+        regions: RegionCache::from_process(Arc::new(process.clone()))?,
+        relative_addresses: true,
+        display: DisplayFormat::default(),
+        bindings: BTreeMap::new(),
+        input: input.clone(),
+        hotkeys_started: false,
+        #[cfg(feature = "scripting")]
+        scripts: Vec::new(),
+        #[cfg(feature = "scripting")]
+        next_script: 1,
+        #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+        watcher: None,
+        #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+        backtrace_depth: memory::DEFAULT_BACKTRACE_DEPTH,
+        #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+        watch_history: memory::DEFAULT_HISTORY_LIMIT,
+        dangerous,
+        #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+        protections: Vec::new(),
+        #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+        allocations: Vec::new(),
+        #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+        injections: Vec::new(),
+        stopped: false,
+        kill_pending: false,
+        leave_stopped: false,
+        #[cfg(target_os = "linux")]
+        monitor: Arc::new(std::sync::Mutex::new(None)),
+        #[cfg(target_os = "linux")]
+        follow,
+        #[cfg(target_os = "linux")]
+        reattach_pending: None,
+        targets: BTreeMap::new(),
+    };
+    session.locks.set_failure_callback(Some(LockFailureCallback(Arc::new(print_lock_failure))));
+    #[cfg(target_os = "linux")]
+    if offline.is_none() {
+        start_monitor(&session);
+    }
+    // An explicitly given config file has to exist, the default one does not
+    match config {
+        Some(path) => run_config(&mut session, &path)?,
+        None => if let Some(path) = default_config_path().filter(|x| x.exists()) {
+            run_config(&mut session, &path)?;
+        },
+    }
+    if let Some(path) = recovery_path().filter(|x| x.exists()) {
+        say!("recovered session from {}; `session load --recover` to restore", path.display());
+    }
+    #[cfg(unix)]
+    catch_termination(input.clone());
+    spawn_stdin_reader(input);
+    let mut prompt = true;
+    loop {
+        // browse draws its own screen while it has the terminal. A terminal that hung up is left to
+        // the SIGHUP to end the session
+        if prompt && !machine && !BROWSE_KEYS.load(Ordering::SeqCst) {
+            let mut stdout = std::io::stdout();
+            let _ = write!(stdout, "{}{}{}> ", target_tag(&session), if target_stopped(&session) { "[stopped] " } else { "" }, endianness_tag(&session)).and_then(|_| stdout.flush());
+        }
+        let line = match receiver.recv() {
+            // Enter alone stops autorescan rather than doing nothing
+            Ok(Input::Line(line)) if line.trim().is_empty() && session.autorescan.is_some() => {
+                stop_autorescan(&mut session, "as asked");
+                continue;
+            }
+            Ok(Input::Line(line)) if session.browsing.is_some() => {
+                browse_command(&mut session, line.trim());
+                if session.browsing.is_some() {
+                    draw_browser(&mut session, true);
+                }
+                continue;
+            }
+            Ok(Input::Line(line)) => line,
+            Ok(Input::Browse) => {
+                prompt = browse_refresh(&mut session);
+                continue;
+            }
+            Ok(Input::BrowseKey(key)) => {
+                prompt = browse_key(&mut session, key);
+                continue;
+            }
+            Ok(Input::AutoRescan) => {
+                autorescan_pass(&mut session);
+                continue;
+            }
+            Ok(Input::Monitor(change)) => {
+                prompt = monitor_alert(&mut session, change);
+                continue;
+            }
+            Ok(Input::Expression(change)) => {
+                prompt = expression_changed(&mut session, change);
+                continue;
+            }
+            Ok(Input::Key(key)) => match session.bindings.get(&key) {
+                Some(command) => {
+                    say!("[{}] {}", key, command);
+                    command.clone()
+                }
+                // Every unbound keystroke arrives here too, so these must not redraw the prompt
+                None => {
+                    prompt = false;
+                    continue;
+                }
+            },
+            #[cfg(feature = "scripting")]
+            Ok(Input::Script(request)) => {
+                answer_script(&mut session, request);
+                prompt = false;
+                continue;
+            }
+            #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+            Ok(Input::Interrupted) => {
+                stop_watching(&mut session);
+                continue;
+            }
+            #[cfg(target_os = "linux")]
+            Ok(Input::Target(change)) => {
+                target_changed(&mut session, change);
+                continue;
+            }
+            #[cfg(unix)]
+            Ok(Input::Terminated) => break,
+            Ok(Input::Closed) | Err(_) => break,
+        };
+        prompt = true;
+        match run_line(&mut session, &line) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => say!("error: {}", e),
+        }
+    }
+    session.browsing = None;
+    // A hangup closes standard input too, so either may be what ended the loop
+    #[cfg(unix)]
+    let terminated = TERMINATED.load(Ordering::SeqCst);
+    #[cfg(unix)]
+    if terminated != 0 {
+        say!("{}: saving the session and cleaning up as for quit", signal_name(terminated));
+        save_recovery(&mut session);
+    }
+    // Whatever ended the session, the journal file puts the clean-up down to exiting
+    session.command = "exit".to_string();
+    clean_up(&mut session);
+    for pid in session.targets.keys().copied().collect::<Vec<Pid>>() {
+        with_target(&mut session, pid, clean_up);
+    }
+    if let Some(file) = &session.journal_file && let Err(e) = file.sync() {
+        say!("could not sync the journal: {}", e);
+    }
+    // Locks stop and watches come out as the session goes, before the exit says it was signalled
+    #[cfg(unix)]
+    if terminated != 0 {
+        drop(session);
+        std::process::exit(128 + terminated);
+    }
     Ok(())
 }
@@ -0,0 +1,549 @@
+use std::{collections::{HashMap, HashSet}, sync::Arc};
+use serde::{Deserialize, Serialize};
+use crate::{filter::{heap_regions, stack_regions}, platform::{FileExt, Pid}, process::ProcessMemory, resident::page_size, session::SavedAddress};
+
+// One line of /proc/<pid>/maps
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MemoryRegion {
+    pub start: usize,
+    pub end: usize,
+    pub readable: bool,
+    pub writable: bool,
+    pub executable: bool,
+    // Shared mappings (s) write through to the underlying file or other processes, private (p) ones are copy-on-write
+    pub shared: bool,
+    // Offset into the mapped file, zero for anonymous mappings
+    pub offset: usize,
+    pub device: String,
+    pub inode: u64,
+    // A file path, a pseudo-path such as [heap] or [stack], or empty for anonymous mappings. Any
+    // " (deleted)" suffix the kernel adds is stripped and recorded in `deleted` instead
+    pub pathname: String,
+    // The file was deleted or replaced on disk after it was mapped, so what is on disk at the path
+    // no longer matches what is mapped
+    pub deleted: bool,
+    // The size of the pages backing it where hugetlbfs backs it, which are read, resident and
+    // written all or nothing. None for base pages. Transparent huge pages keep None, as the kernel
+    // still reads and reports them a base page at a time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kernel_page_size: Option<usize>,
+}
+
+impl MemoryRegion {
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn contains(&self, address: usize) -> bool {
+        address >= self.start && address < self.end
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.kernel_page_size.unwrap_or_else(page_size)
+    }
+
+    // In the same form as the maps file, e.g. "rw-p"
+    pub fn permissions(&self) -> String {
+        [(self.readable, 'r'), (self.writable, 'w'), (self.executable, 'x')].iter().map(|x| if x.0 { x.1 } else { '-' })
+            .chain(std::iter::once(if self.shared { 's' } else { 'p' })).collect()
+    }
+}
+
+impl std::str::FromStr for MemoryRegion {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        // address perms offset dev inode pathname, separated by single spaces except for the padding
+        // before the pathname. Only spaces separate them, since pathnames may hold other whitespace
+        let mut iter = line.splitn(6, ' ');
+        let range = iter.next().filter(|x| !x.is_empty()).ok_or("Expected no line in memory map to be empty")?.split_once('-').ok_or("Expected each memory region to have address ranges")?;
+        let flags = iter.next().ok_or("Expected each line in memory map to contain memory flags")?.as_bytes();
+        if flags.len() != 4 || ![b'r', b'w', b'x'].iter().zip(flags).all(|(x, flag)| flag == x || *flag == b'-') || !matches!(flags[3], b's' | b'p') {
+            return Err(format!("Expected four permission flags like rw-p in '{}'", line).into());
+        }
+        let offset = iter.next().ok_or("Expected each line in memory map to contain an offset")?;
+        let device = iter.next().ok_or("Expected each line in memory map to contain a device")?;
+        let inode = iter.next().ok_or("Expected each line in memory map to contain an inode")?;
+        let pathname = iter.next().unwrap_or_default().trim_start_matches(' ');
+        let (pathname, deleted) = match pathname.strip_suffix(" (deleted)") {
+            Some(pathname) => (pathname, true),
+            None => (pathname, false),
+        };
+        let (start, end) = (parse_hex(range.0)?, parse_hex(range.1)?);
+        if end <= start {
+            return Err(format!("Expected the region in '{}' to end above where it starts", line).into());
+        }
+        Ok(MemoryRegion {
+            start,
+            end,
+            readable: flags[0] == b'r',
+            writable: flags[1] == b'w',
+            executable: flags[2] == b'x',
+            shared: flags[3] == b's',
+            offset: parse_hex(offset)?,
+            device: device.to_string(),
+            inode: inode.parse()?,
+            pathname: pathname.to_string(),
+            deleted,
+            kernel_page_size: None,
+        })
+    }
+}
+
+// from_str_radix on its own also takes a leading + sign
+fn parse_hex(s: &str) -> Result<usize, Box<dyn std::error::Error>> {
+    if s.is_empty() || !s.bytes().all(|x| x.is_ascii_hexdigit()) {
+        return Err(format!("Expected a hex number, got '{}'", s).into());
+    }
+    Ok(usize::from_str_radix(s, 16)?)
+}
+
+// Every mapping in address order, as the kernel lists them, with the page size of those hugetlbfs
+// backs. Only files on a pseudo-filesystem's device can be on hugetlbfs, so the mounts are not read
+// for targets with none
+pub fn get_memory_regions(pid: Pid) -> Result<Vec<MemoryRegion>, Box<dyn std::error::Error>> {
+    let mut regions = std::fs::read_to_string(format!("/proc/{}/maps", pid))?.lines().map(|x| x.parse::<MemoryRegion>()).collect::<Result<Vec<MemoryRegion>, _>>()?;
+    if regions.iter().any(|x| x.pathname.starts_with('/') && x.device.starts_with("00:")) {
+        let mounts = std::fs::read_to_string(format!("/proc/{}/mounts", pid)).unwrap_or_default();
+        apply_page_sizes(&mut regions, &mounts, || std::fs::read_to_string(format!("/proc/{}/smaps", pid)).ok());
+    }
+    Ok(regions)
+}
+
+// Names the kernel gives hugetlbfs files that are on no mount: MAP_HUGETLB's anonymous memory,
+// SysV shared memory and memfds. SysV and memfds may be on tmpfs instead, which only smaps tells
+const UNMOUNTED_HUGETLB: [&str; 3] = ["/anon_hugepage", "/SYSV", "/memfd:"];
+
+// Sets the page size of the regions on a hugetlbfs mount to its pagesize option, given the
+// target's mounts as /proc/<pid>/mounts lists them. The size of the others hugetlbfs may back is
+// taken from smaps' KernelPageSize, which is read only if there are any, as reading it walks the
+// target's page tables
+pub fn apply_page_sizes(regions: &mut [MemoryRegion], mounts: &str, smaps: impl FnOnce() -> Option<String>) {
+    let mut hugetlbfs = mounts.lines().filter_map(|line| {
+        let fields = line.split(' ').collect::<Vec<&str>>();
+        if fields.get(2) != Some(&"hugetlbfs") {
+            return None;
+        }
+        let size = fields.get(3)?.split(',').find_map(|x| x.strip_prefix("pagesize="))?;
+        let (digits, unit) = size.split_at(size.find(|x: char| !x.is_ascii_digit()).unwrap_or(size.len()));
+        let shift = match unit {
+            "" => 0,
+            "K" | "k" => 10,
+            "M" => 20,
+            "G" => 30,
+            _ => return None,
+        };
+        // Spaces in mount points are written as \040
+        Some((fields[1].replace("\\040", " "), digits.parse::<usize>().ok()? << shift))
+    }).collect::<Vec<(String, usize)>>();
+    // The longest mount point a path is under is the one it is on
+    hugetlbfs.sort_by_key(|x| std::cmp::Reverse(x.0.len()));
+    let mut unknown = false;
+    for region in regions.iter_mut().filter(|x| x.device.starts_with("00:")) {
+        let on = hugetlbfs.iter().find(|(point, _)| region.pathname.strip_prefix(point.trim_end_matches('/')).is_some_and(|x| x.starts_with('/')));
+        match on {
+            Some((_, size)) => region.kernel_page_size = Some(*size),
+            None => unknown |= region.deleted && UNMOUNTED_HUGETLB.iter().any(|x| region.pathname.starts_with(x)),
+        }
+    }
+    if !unknown {
+        return;
+    }
+    let Some(smaps) = smaps() else {
+        return;
+    };
+    let mut sizes = HashMap::new();
+    let mut start = None;
+    for line in smaps.lines() {
+        if let Some(size) = line.strip_prefix("KernelPageSize:") {
+            if let (Some(start), Ok(size)) = (start, size.trim().trim_end_matches("kB").trim().parse::<usize>()) {
+                sizes.insert(start, size << 10);
+            }
+        }
+        else if let Some((range, _)) = line.split_once(' ') && let Some((first, _)) = range.split_once('-') && let Ok(first) = usize::from_str_radix(first, 16) {
+            start = Some(first);
+        }
+    }
+    for region in regions.iter_mut().filter(|x| x.kernel_page_size.is_none()) {
+        if let Some(size) = sizes.get(&region.start).filter(|x| **x > page_size()) {
+            region.kernel_page_size = Some(*size);
+        }
+    }
+}
+
+pub fn get_possible_memory_ranges(pid: Pid) -> Result<Vec<(usize, usize)>, Box<dyn std::error::Error>> {
+    Ok(get_memory_regions(pid)?.iter().filter(|x| x.readable).map(|x| (x.start, x.end)).collect())
+}
+
+// Enough of the layout to tell whether it changed: the range and inode of every mapping
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MapsFingerprint {
+    pub regions: Vec<(usize, usize, u64)>,
+}
+
+// How the layout differs from an earlier fingerprint
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MapsChange {
+    pub appeared: Vec<(usize, usize)>,
+    pub disappeared: Vec<(usize, usize)>,
+    // Old and new range of mappings that moved, or that were resized in place
+    pub moved: Vec<((usize, usize), (usize, usize))>,
+}
+
+impl MapsChange {
+    pub fn is_empty(&self) -> bool {
+        self.appeared.is_empty() && self.disappeared.is_empty() && self.moved.is_empty()
+    }
+}
+
+impl MapsFingerprint {
+    pub fn new(regions: &[MemoryRegion]) -> MapsFingerprint {
+        MapsFingerprint { regions: regions.iter().map(|x| (x.start, x.end, x.inode)).collect() }
+    }
+
+    // A mapping of the same file and size at another address counts as moved, as does an anonymous
+    // mapping that still starts at the same address but ends elsewhere
+    pub fn compare(&self, current: &MapsFingerprint) -> MapsChange {
+        let mut disappeared = self.regions.iter().filter(|x| !current.regions.contains(x)).copied().collect::<Vec<(usize, usize, u64)>>();
+        let mut appeared = current.regions.iter().filter(|x| !self.regions.contains(x)).copied().collect::<Vec<(usize, usize, u64)>>();
+        let mut moved = Vec::new();
+        disappeared.retain(|old| {
+            let same = |new: &(usize, usize, u64)| if old.2 == 0 { new.2 == 0 && new.0 == old.0 } else { new.2 == old.2 && new.1 - new.0 == old.1 - old.0 };
+            match appeared.iter().position(same) {
+                Some(index) => {
+                    let new = appeared.remove(index);
+                    moved.push(((old.0, old.1), (new.0, new.1)));
+                    false
+                }
+                None => true,
+            }
+        });
+        MapsChange {
+            appeared: appeared.iter().map(|x| (x.0, x.1)).collect(),
+            disappeared: disappeared.iter().map(|x| (x.0, x.1)).collect(),
+            moved,
+        }
+    }
+}
+
+// The regions of one process, kept sorted so that lookups are a binary search. The maps file is
+// only re-read when a lookup misses, since that is when the cached layout may be stale
+#[derive(Clone)]
+pub struct RegionCache {
+    source: Arc<dyn ProcessMemory>,
+    regions: Vec<MemoryRegion>,
+    modules: Vec<Module>,
+    // Indices into regions, as RegionFilter classifies them
+    heap: HashSet<usize>,
+    stacks: HashMap<usize, Option<Pid>>,
+}
+
+impl std::fmt::Debug for RegionCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RegionCache").field("pid", &self.source.pid()).field("regions", &self.regions.len()).finish()
+    }
+}
+
+impl RegionCache {
+    // Outside Linux a pid has to be opened first, with Process::attach
+    pub fn new(pid: Pid) -> Result<RegionCache, Box<dyn std::error::Error>> {
+        #[cfg(target_os = "linux")]
+        let source = pid;
+        #[cfg(not(target_os = "linux"))]
+        let source = crate::process::Process::attach(pid)?;
+        RegionCache::from_process(Arc::new(source))
+    }
+
+    // Takes the regions from the process's memory_regions rather than straight from /proc, for
+    // backends like offline captures that have their own
+    pub fn from_process(source: Arc<dyn ProcessMemory>) -> Result<RegionCache, Box<dyn std::error::Error>> {
+        let mut cache = RegionCache { source, regions: Vec::new(), modules: Vec::new(), heap: HashSet::new(), stacks: HashMap::new() };
+        cache.refresh()?;
+        Ok(cache)
+    }
+
+    pub fn refresh(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.regions = self.source.memory_regions()?;
+        self.modules = modules_from_regions(&self.regions);
+        self.heap = heap_regions(&self.regions);
+        self.stacks = stack_regions(self.source.pid(), &self.regions, &self.source.thread_stacks());
+        Ok(())
+    }
+
+    pub fn regions(&self) -> &[MemoryRegion] {
+        &self.regions
+    }
+
+    pub fn modules(&self) -> &[Module] {
+        &self.modules
+    }
+
+    // Refreshes the cache once if no module contains the address
+    pub fn module_for_address(&mut self, address: usize) -> Option<&Module> {
+        if module_for_address(&self.modules, address).is_none() && self.refresh().is_err() {
+            return None;
+        }
+        module_for_address(&self.modules, address)
+    }
+
+    // Refreshes the cache once if the module is not loaded, in case it was loaded since
+    pub fn find_module(&mut self, name: &str) -> Option<&Module> {
+        if find_module(&self.modules, name).is_none() && self.refresh().is_err() {
+            return None;
+        }
+        find_module(&self.modules, name)
+    }
+
+    // Resolves against the modules loaded now, refreshing the cache once if the module is not in
+    // it, so a module-relative address lands wherever the module was loaded this time
+    pub fn resolve(&mut self, address: &SavedAddress) -> Result<usize, Box<dyn std::error::Error>> {
+        if let SavedAddress::Module { name, .. } = address {
+            self.find_module(name);
+        }
+        address.resolve(&self.modules)
+    }
+
+    // Every region holding a thread's stack, in address order, with the thread where it is known
+    pub fn stacks(&self) -> Vec<(Option<Pid>, &MemoryRegion)> {
+        let mut stacks = self.stacks.iter().map(|(index, tid)| (*tid, &self.regions[*index])).collect::<Vec<(Option<Pid>, &MemoryRegion)>>();
+        stacks.sort_by_key(|x| x.1.start);
+        stacks
+    }
+
+    // Only looks at the cached regions
+    pub fn find(&self, address: usize) -> Option<&MemoryRegion> {
+        let index = self.regions.partition_point(|x| x.end <= address);
+        self.regions.get(index).filter(|x| x.contains(address))
+    }
+
+    // Refreshes the cache once on a miss, in case the address was mapped since
+    pub fn region_for_address(&mut self, address: usize) -> Option<&MemoryRegion> {
+        if self.find(address).is_none() && self.refresh().is_err() {
+            return None;
+        }
+        self.find(address)
+    }
+
+    // Fails unless every byte of the range is in a writable mapping. The maps are re-read once
+    // before failing, in case the target changed its protections since they were cached
+    pub fn check_writable(&mut self, address: usize, len: usize) -> Result<(), Box<dyn std::error::Error>> {
+        if check_writable(&self.regions, address, len).is_err() {
+            self.refresh()?;
+        }
+        check_writable(&self.regions, address, len)
+    }
+
+    // Refreshes the cache once if the address is unmapped, like region_for_address
+    pub fn describe(&mut self, address: usize) -> AddressDescription {
+        if self.find(address).is_none() && self.refresh().is_err() {
+            return AddressDescription::Unmapped;
+        }
+        let index = self.regions.partition_point(|x| x.end <= address);
+        let Some(region) = self.regions.get(index).filter(|x| x.contains(address)) else {
+            return AddressDescription::Unmapped;
+        };
+        if let Some(module) = module_for_address(&self.modules, address) {
+            return AddressDescription::Module { name: module.name.clone(), offset: address - module.base };
+        }
+        if self.heap.contains(&index) {
+            return AddressDescription::Heap { offset: address - region.start };
+        }
+        if let Some(tid) = self.stacks.get(&index) {
+            return AddressDescription::Stack { tid: *tid };
+        }
+        if region.pathname.is_empty() {
+            AddressDescription::Anonymous { offset: address - region.start }
+        }
+        else {
+            AddressDescription::Named { name: region.pathname.clone(), offset: address - region.start }
+        }
+    }
+}
+
+pub(crate) fn check_writable(regions: &[MemoryRegion], address: usize, len: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let end = address.checked_add(len.max(1)).ok_or("Address range overflows")?;
+    let mut position = address;
+    while position < end {
+        let index = regions.partition_point(|x| x.end <= position);
+        let region = regions.get(index).filter(|x| x.contains(position)).ok_or(format!("0x{:x} is not mapped", position))?;
+        if !region.writable {
+            let name = if region.pathname.is_empty() { "anonymous memory" } else { region.pathname.as_str() };
+            return Err(format!("0x{:x} is in a {} mapping of {}, which is not writable", position, region.permissions(), name).into());
+        }
+        position = region.end;
+    }
+    Ok(())
+}
+
+// What an address points into, for annotating printed addresses
+#[derive(Debug, Clone, PartialEq)]
+pub enum AddressDescription {
+    // Relative to the module's base
+    Module { name: String, offset: usize },
+    // Relative to the start of the heap region holding it
+    Heap { offset: usize },
+    // The thread is unknown for stacks found only by their guard page
+    Stack { tid: Option<Pid> },
+    // Relative to the start of the mapping
+    Anonymous { offset: usize },
+    // Special mappings like [vdso], and file mappings that are not modules
+    Named { name: String, offset: usize },
+    Unmapped,
+}
+
+impl std::fmt::Display for AddressDescription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AddressDescription::Module { name, offset } | AddressDescription::Named { name, offset } => write!(f, "{}+0x{:x}", name, offset),
+            AddressDescription::Heap { offset } => write!(f, "heap+0x{:x}", offset),
+            AddressDescription::Stack { tid: Some(tid) } => write!(f, "stack of tid {}", tid),
+            AddressDescription::Stack { tid: None } => write!(f, "thread stack"),
+            AddressDescription::Anonymous { offset } => write!(f, "anonymous+0x{:x}", offset),
+            AddressDescription::Unmapped => write!(f, "unmapped"),
+        }
+    }
+}
+
+// For a single lookup; anything describing many addresses should keep a RegionCache instead
+pub fn describe_address(pid: Pid, address: usize) -> AddressDescription {
+    match RegionCache::new(pid) {
+        Ok(mut cache) => cache.describe(address),
+        Err(_) => AddressDescription::Unmapped,
+    }
+}
+
+// A file mapped into the process, spanning from its lowest to its highest mapping
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    pub name: String,
+    pub path: String,
+    pub base: usize,
+    pub size: usize,
+    // Whether any of its mappings is executable, i.e. it is loaded code rather than a mapped data file
+    pub executable: bool,
+    pub writable: bool,
+    // Any of its mappings was deleted or replaced on disk, as when a library is upgraded under a
+    // running process. Module-relative addresses then refer to the old file rather than the one at
+    // the path, and the ELF headers are not read from it
+    pub deleted: bool,
+}
+
+impl Module {
+    pub fn contains(&self, address: usize) -> bool {
+        address >= self.base && address < self.base + self.size
+    }
+}
+
+// Every file-backed mapping grouped by path, sorted by base address. A mapping at file offset zero
+// of a path already seen starts another instance of it, as when a library is loaded twice. An
+// anonymous read-write mapping that starts exactly where a module's last mapping ends is counted
+// as part of that module, since that is where the loader puts its zero-initialised globals (.bss).
+// The kernel merges that mapping with any anonymous memory mapped right after it, so where the
+// file's ELF headers can be read the module only extends as far as they say its image does
+pub fn modules(pid: Pid) -> Result<Vec<Module>, Box<dyn std::error::Error>> {
+    Ok(modules_from_regions(&get_memory_regions(pid)?))
+}
+
+pub fn modules_from_regions(regions: &[MemoryRegion]) -> Vec<Module> {
+    let mut modules: Vec<Module> = Vec::new();
+    // Per module, the size its ELF headers give it
+    let mut image_sizes: Vec<Option<usize>> = Vec::new();
+    // The module the previous region belonged to, if any
+    let mut previous: Option<usize> = None;
+    for region in regions {
+        if region.pathname.is_empty() && region.readable && region.writable {
+            if let Some(index) = previous && modules[index].base + modules[index].size == region.start {
+                let module = &mut modules[index];
+                let end = image_sizes[index].map(|x| region.end.min(module.base + x)).unwrap_or(region.end);
+                module.size = module.size.max(end - module.base);
+            }
+            previous = None;
+            continue;
+        }
+        // Shared anonymous memory, memfds and System V segments show up with paths too, but are not
+        // files. On Windows the images are the regions with a drive-letter path
+        if !(region.pathname.starts_with('/') || region.pathname.get(1..3) == Some(":\\")) || ["/dev/zero", "/memfd:", "/SYSV"].iter().any(|x| region.pathname.starts_with(x)) {
+            previous = None;
+            continue;
+        }
+        let (path, deleted) = (region.pathname.as_str(), region.deleted);
+        // The latest instance of this file, unless this mapping starts a new one
+        let instance = modules.iter().rposition(|x| x.path == path).filter(|_| region.offset != 0);
+        let index = match instance {
+            Some(index) => {
+                let module = &mut modules[index];
+                let end = (module.base + module.size).max(region.end);
+                module.base = module.base.min(region.start);
+                module.size = end - module.base;
+                module.executable |= region.executable;
+                module.writable |= region.writable;
+                module.deleted |= deleted;
+                index
+            }
+            None => {
+                modules.push(Module {
+                    name: path.rsplit(['/', '\\']).next().unwrap_or(path).to_string(),
+                    path: path.to_string(),
+                    base: region.start,
+                    size: region.len(),
+                    executable: region.executable,
+                    writable: region.writable,
+                    deleted,
+                });
+                image_sizes.push(if deleted { None } else { elf_image_size(path) });
+                modules.len() - 1
+            }
+        };
+        previous = Some(index);
+    }
+    modules.sort_by_key(|x| x.base);
+    modules
+}
+
+// The span of an ELF file's loadable segments once mapped, rounded up to whole pages, or None if
+// it is not a 64-bit little-endian ELF file
+fn elf_image_size(path: &str) -> Option<usize> {
+    let file = std::fs::File::open(path).ok()?;
+    let mut header = [0u8; 64];
+    file.read_exact_at(&mut header, 0).ok()?;
+    if &header[..4] != b"\x7fELF" || header[4] != 2 || header[5] != 1 {
+        return None;
+    }
+    let field = |bytes: &[u8], offset: usize, len: usize| bytes[offset..offset + len].iter().rev().fold(0usize, |x, y| x << 8 | *y as usize);
+    let (table, entry_size, count) = (field(&header, 0x20, 8), field(&header, 0x36, 2), field(&header, 0x38, 2));
+    if entry_size < 0x30 || count == 0 {
+        return None;
+    }
+    let mut entries = vec![0u8; entry_size * count];
+    file.read_exact_at(&mut entries, table as u64).ok()?;
+    // PT_LOAD segments, as (virtual address, size in memory)
+    let segments = entries.chunks_exact(entry_size).filter(|x| field(x, 0, 4) == 1).map(|x| (field(x, 0x10, 8), field(x, 0x28, 8))).collect::<Vec<(usize, usize)>>();
+    let page_size = 4096;
+    let start = segments.iter().map(|x| x.0).min()? / page_size * page_size;
+    let end = segments.iter().map(|x| x.0.checked_add(x.1)).max()??;
+    Some((end - start).div_ceil(page_size) * page_size)
+}
+
+// The path of the process's main executable, without any " (deleted)" suffix
+pub fn executable_path(pid: Pid) -> Result<String, Box<dyn std::error::Error>> {
+    let path = std::fs::read_link(format!("/proc/{}/exe", pid))?.to_string_lossy().into_owned();
+    Ok(path.strip_suffix(" (deleted)").map(str::to_string).unwrap_or(path))
+}
+
+// Looks a module up by basename or full path. When several match, a file with executable code is
+// preferred over a data file of the same name, and then the lowest instance
+pub fn find_module<'a>(modules: &'a [Module], name: &str) -> Option<&'a Module> {
+    let mut candidates = modules.iter().filter(|x| x.name == name || x.path == name);
+    let first = candidates.next()?;
+    Some(if first.executable { first } else { candidates.find(|x| x.executable).unwrap_or(first) })
+}
+
+// The module containing the address, if any, from modules sorted by base as modules() returns them
+pub fn module_for_address(modules: &[Module], address: usize) -> Option<&Module> {
+    let index = modules.partition_point(|x| x.base <= address).checked_sub(1)?;
+    modules.get(index).filter(|x| x.contains(address))
+}
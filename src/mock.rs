@@ -0,0 +1,361 @@
+use std::{collections::{BTreeSet, HashMap}, sync::{Mutex, MutexGuard}};
+use crate::{maps::MemoryRegion, platform::{Errno, Pid}, process::ProcessMemory, value::Scalar};
+
+// A process made up in memory, for testing what runs on a ProcessMemory against layouts a live
+// process cannot be made to have on demand: a value in the last bytes of a region, a page that
+// cannot be read in the middle of one, reads that stop short, or a region that goes away while a
+// scan is reading it. Reads and writes behave like process_vm_readv/process_vm_writev: they run on
+// across adjacent regions, stop short at the first byte they cannot transfer, and fail only if
+// that is the first. Nothing is ever read from /proc
+pub const MOCK_PAGE_SIZE: usize = 4096;
+
+#[derive(Debug)]
+pub struct MockProcess {
+    pid: Pid,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    // By start address; they never overlap
+    regions: HashMap<usize, MockRegion>,
+    executable: Option<String>,
+    stacks: Vec<(Pid, usize)>,
+    // Most bytes a single read transfers, None for no limit
+    read_limit: Option<usize>,
+    // Each change and the number of reads after which it happens
+    remaps: Vec<(usize, Remap)>,
+    reads: usize,
+    maps_reads: usize,
+    // The pages written since clear_soft_dirty, by address, or None until it is first called
+    dirty: Option<BTreeSet<usize>>,
+    // Set to act as a kernel without soft-dirty tracking
+    no_soft_dirty: bool,
+}
+
+#[derive(Debug)]
+struct MockRegion {
+    region: MemoryRegion,
+    bytes: Vec<u8>,
+    // Indices of the pages that can be neither read nor written, as after mprotect(PROT_NONE)
+    unreadable: BTreeSet<usize>,
+    // Indices of the pages that fault although the maps list them as readable, as a huge page does
+    // when the pool has none left to back it
+    failing: BTreeSet<usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Remap {
+    Unmap(usize),
+    Move(usize, usize),
+}
+
+impl MockProcess {
+    pub fn new(pid: i32) -> MockProcess {
+        MockProcess { pid: Pid::from_raw(pid), state: Mutex::new(State::default()) }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    // Maps a zeroed region, which must be whole pages and not overlap any other. A region with a
+    // kernel_page_size stands for one of huge pages, and must be whole pages of that size
+    pub fn add_region(&self, region: MemoryRegion) -> Result<(), Box<dyn std::error::Error>> {
+        let page_size = mock_page_size(&region);
+        if region.is_empty() || !page_size.is_multiple_of(MOCK_PAGE_SIZE) || !region.start.is_multiple_of(page_size) || !region.end.is_multiple_of(page_size) {
+            return Err(format!("0x{:x}-0x{:x} is not a whole number of pages", region.start, region.end).into());
+        }
+        let mut state = self.state();
+        if let Some(other) = state.regions.values().find(|x| x.region.start < region.end && region.start < x.region.end) {
+            return Err(format!("0x{:x}-0x{:x} overlaps 0x{:x}-0x{:x}", region.start, region.end, other.region.start, other.region.end).into());
+        }
+        let bytes = vec![0; region.len()];
+        state.mark_dirty(region.start, region.len());
+        state.regions.insert(region.start, MockRegion { region, bytes, unreadable: BTreeSet::new(), failing: BTreeSet::new() });
+        Ok(())
+    }
+
+    // add_region with the region given as a line of a maps file, e.g.
+    // "10000-20000 rw-p 00000000 00:00 0 [heap]"
+    pub fn map(&self, line: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.add_region(line.parse::<MemoryRegion>()?)
+    }
+
+    pub fn unmap(&self, start: usize) -> Result<(), Box<dyn std::error::Error>> {
+        self.state().regions.remove(&start).map(|_| ()).ok_or_else(|| format!("No region starts at 0x{:x}", start).into())
+    }
+
+    // Moves a region and everything in it to start at `to`, as mremap does
+    pub fn move_region(&self, start: usize, to: usize) -> Result<(), Box<dyn std::error::Error>> {
+        apply(&mut self.state(), Remap::Move(start, to))
+    }
+
+    // Unmaps or moves the region once `reads` more reads have been done, so that it happens in the
+    // middle of whatever is reading. Scans read chunks in parallel, so which chunk a remap falls
+    // between is only certain for zero, or with a single chunk
+    pub fn unmap_after(&self, reads: usize, start: usize) {
+        let mut state = self.state();
+        let at = state.reads + reads;
+        state.remaps.push((at, Remap::Unmap(start)));
+    }
+
+    pub fn move_after(&self, reads: usize, start: usize, to: usize) {
+        let mut state = self.state();
+        let at = state.reads + reads;
+        state.remaps.push((at, Remap::Move(start, to)));
+    }
+
+    // Writes bytes whatever the permissions and unreadable pages, as setting up a test needs
+    pub fn plant(&self, address: usize, bytes: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut state = self.state();
+        let (region, offset) = state.locate(address, bytes.len())?;
+        region.bytes[offset..offset + bytes.len()].copy_from_slice(bytes);
+        state.mark_dirty(address, bytes.len());
+        Ok(())
+    }
+
+    pub fn plant_value<T: Scalar>(&self, address: usize, value: T) -> Result<(), Box<dyn std::error::Error>> {
+        self.plant(address, &value.to_bytes())
+    }
+
+    // What is in memory, whether or not the target could read it
+    pub fn bytes(&self, address: usize, len: usize) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut state = self.state();
+        let (region, offset) = state.locate(address, len)?;
+        Ok(region.bytes[offset..offset + len].to_vec())
+    }
+
+    // Takes away access to the pages from start to end, which must be page-aligned and inside one
+    // region, and whole huge pages in a region of them. The maps then list that region in pieces,
+    // with the unreadable ones as ---p
+    pub fn mark_unreadable(&self, start: usize, end: usize) -> Result<(), Box<dyn std::error::Error>> {
+        self.mark_pages(start, end, |x| &mut x.unreadable)
+    }
+
+    // Makes reads and writes of the pages from start to end fault, with the maps still listing
+    // them as they were, under the same rules as mark_unreadable
+    pub fn mark_failing(&self, start: usize, end: usize) -> Result<(), Box<dyn std::error::Error>> {
+        self.mark_pages(start, end, |x| &mut x.failing)
+    }
+
+    fn mark_pages(&self, start: usize, end: usize, pages: fn(&mut MockRegion) -> &mut BTreeSet<usize>) -> Result<(), Box<dyn std::error::Error>> {
+        let mut state = self.state();
+        let (region, offset) = state.locate(start, end.saturating_sub(start))?;
+        let page_size = mock_page_size(&region.region);
+        if start >= end || !start.is_multiple_of(page_size) || !end.is_multiple_of(page_size) {
+            return Err(format!("0x{:x}-0x{:x} is not a whole number of pages", start, end).into());
+        }
+        pages(region).extend(offset / MOCK_PAGE_SIZE..(offset + end - start) / MOCK_PAGE_SIZE);
+        Ok(())
+    }
+
+    // Every read after this transfers at most limit bytes, as a read of memory that is being
+    // unmapped under it can. None reads everything again
+    pub fn limit_reads(&self, limit: Option<usize>) {
+        self.state().read_limit = limit;
+    }
+
+    // How many reads have been asked for, including those that failed
+    pub fn reads(&self) -> usize {
+        self.state().reads
+    }
+
+    // How many times the maps have been listed
+    pub fn maps_reads(&self) -> usize {
+        self.state().maps_reads
+    }
+
+    pub fn set_executable(&self, path: Option<&str>) {
+        self.state().executable = path.map(|x| x.to_string());
+    }
+
+    pub fn set_thread_stacks(&self, stacks: Vec<(Pid, usize)>) {
+        self.state().stacks = stacks;
+    }
+
+    // Whether the pages written are tracked as soft-dirty ones are, which they are unless this
+    // turns it off as a kernel without CONFIG_MEM_SOFT_DIRTY has it
+    pub fn track_soft_dirty(&self, tracked: bool) {
+        self.state().no_soft_dirty = !tracked;
+    }
+}
+
+impl State {
+    // The region holding all len bytes from the address, and the address's offset into it
+    fn locate(&mut self, address: usize, len: usize) -> Result<(&mut MockRegion, usize), Box<dyn std::error::Error>> {
+        let region = self.regions.values_mut().find(|x| x.region.contains(address) && address + len <= x.region.end);
+        region.map(|x| {
+            let offset = address - x.region.start;
+            (x, offset)
+        }).ok_or_else(|| format!("0x{:x}-0x{:x} is not inside one region", address, address + len).into())
+    }
+
+    // Plants and writes count as the target's own, and so does all of a new mapping. A huge page
+    // is dirtied whole
+    fn mark_dirty(&mut self, address: usize, len: usize) {
+        let Some(dirty) = &mut self.dirty else {
+            return;
+        };
+        let page_size = self.regions.values().find(|x| x.region.contains(address)).map(|x| mock_page_size(&x.region)).unwrap_or(MOCK_PAGE_SIZE);
+        let (start, end) = (address / page_size * page_size, (address + len).next_multiple_of(page_size));
+        dirty.extend((start..end).step_by(MOCK_PAGE_SIZE));
+    }
+
+    fn apply_due(&mut self) {
+        let reads = self.reads;
+        let (due, pending) = self.remaps.drain(..).partition::<Vec<(usize, Remap)>, _>(|x| x.0 <= reads);
+        self.remaps = pending;
+        for (_, remap) in due {
+            let _ = apply(self, remap);
+        }
+    }
+
+    // Hands each run of bytes from the address on to `each` as (region, offset into it, offset into
+    // the transfer, len), for as long as every byte is in a region that `allowed` accepts and not
+    // in an unreadable page, returning how many bytes that was
+    fn transfer(&mut self, address: usize, len: usize, allowed: fn(&MemoryRegion) -> bool, mut each: impl FnMut(&mut MockRegion, usize, usize, usize)) -> usize {
+        let mut done = 0;
+        while done < len {
+            let position = address + done;
+            let Some(region) = self.regions.values_mut().find(|x| x.region.contains(position) && allowed(&x.region)) else {
+                break;
+            };
+            let offset = position - region.region.start;
+            // To the end of the region or the next page that cannot be read, whichever comes first
+            let page = offset / MOCK_PAGE_SIZE;
+            let end = region.unreadable.range(page..).chain(region.failing.range(page..)).min().map(|x| x * MOCK_PAGE_SIZE).unwrap_or(region.bytes.len());
+            if end <= offset {
+                break;
+            }
+            let count = (end - offset).min(len - done);
+            each(region, offset, done, count);
+            done += count;
+        }
+        done
+    }
+}
+
+fn mock_page_size(region: &MemoryRegion) -> usize {
+    region.kernel_page_size.unwrap_or(MOCK_PAGE_SIZE)
+}
+
+fn apply(state: &mut State, remap: Remap) -> Result<(), Box<dyn std::error::Error>> {
+    let (start, to) = match remap {
+        Remap::Unmap(start) => return state.regions.remove(&start).map(|_| ()).ok_or_else(|| format!("No region starts at 0x{:x}", start).into()),
+        Remap::Move(start, to) => (start, to),
+    };
+    if !to.is_multiple_of(MOCK_PAGE_SIZE) {
+        return Err(format!("0x{:x} is not page-aligned", to).into());
+    }
+    let mut moved = state.regions.remove(&start).ok_or_else(|| format!("No region starts at 0x{:x}", start))?;
+    let end = to + moved.region.len();
+    if let Some(other) = state.regions.values().find(|x| x.region.start < end && to < x.region.end) {
+        let error = format!("0x{:x}-0x{:x} would overlap 0x{:x}-0x{:x}", to, end, other.region.start, other.region.end);
+        state.regions.insert(start, moved);
+        return Err(error.into());
+    }
+    moved.region.start = to;
+    moved.region.end = end;
+    state.mark_dirty(to, end - to);
+    state.regions.insert(to, moved);
+    Ok(())
+}
+
+impl ProcessMemory for MockProcess {
+    fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    fn read_at(&self, address: usize, buffer: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut state = self.state();
+        state.apply_due();
+        state.reads += 1;
+        let len = state.read_limit.unwrap_or(usize::MAX).min(buffer.len());
+        let done = state.transfer(address, len, |x| x.readable, |region, offset, done, count| {
+            buffer[done..done + count].copy_from_slice(&region.bytes[offset..offset + count]);
+        });
+        if done == 0 && !buffer.is_empty() {
+            return Err(Errno::EFAULT.into());
+        }
+        Ok(done)
+    }
+
+    fn write_at(&self, address: usize, data: &[u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut state = self.state();
+        let done = state.transfer(address, data.len(), |x| x.writable, |region, offset, done, count| {
+            region.bytes[offset..offset + count].copy_from_slice(&data[done..done + count]);
+        });
+        if done == 0 && !data.is_empty() {
+            return Err(Errno::EFAULT.into());
+        }
+        state.mark_dirty(address, done);
+        Ok(done)
+    }
+
+    // Each region in address order, split into pieces around its unreadable pages
+    fn memory_regions(&self) -> Result<Vec<MemoryRegion>, Box<dyn std::error::Error>> {
+        let mut state = self.state();
+        state.maps_reads += 1;
+        let mut regions = Vec::new();
+        for mock in state.regions.values() {
+            let pages = mock.bytes.len() / MOCK_PAGE_SIZE;
+            let mut start = 0;
+            while start < pages {
+                let unreadable = mock.unreadable.contains(&start);
+                let end = (start..pages).find(|x| mock.unreadable.contains(x) != unreadable).unwrap_or(pages);
+                let mut region = MemoryRegion { start: mock.region.start + start * MOCK_PAGE_SIZE, end: mock.region.start + end * MOCK_PAGE_SIZE, ..mock.region.clone() };
+                if !region.pathname.is_empty() && !region.pathname.starts_with('[') {
+                    region.offset += start * MOCK_PAGE_SIZE;
+                }
+                if unreadable {
+                    (region.readable, region.writable, region.executable) = (false, false, false);
+                }
+                regions.push(region);
+                start = end;
+            }
+        }
+        regions.sort_by_key(|x| x.start);
+        Ok(regions)
+    }
+
+    fn executable_path(&self) -> Option<String> {
+        self.state().executable.clone()
+    }
+
+    fn thread_stacks(&self) -> Vec<(Pid, usize)> {
+        self.state().stacks.clone()
+    }
+
+    fn clear_soft_dirty(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut state = self.state();
+        if state.no_soft_dirty {
+            return Err("This kernel does not track soft-dirty pages".into());
+        }
+        state.dirty = Some(BTreeSet::new());
+        Ok(())
+    }
+
+    // Until the bits are first cleared every page counts as written, as every page of a new
+    // mapping does
+    fn dirty_ranges(&self, ranges: &[(usize, usize)]) -> Result<Vec<(usize, usize)>, Box<dyn std::error::Error>> {
+        let state = self.state();
+        if state.no_soft_dirty {
+            return Err("This kernel does not track soft-dirty pages".into());
+        }
+        let Some(dirty) = &state.dirty else {
+            return Ok(ranges.to_vec());
+        };
+        let mut found: Vec<(usize, usize)> = Vec::new();
+        for &(start, end) in ranges {
+            for page in dirty.range(start / MOCK_PAGE_SIZE * MOCK_PAGE_SIZE..end) {
+                let (start, end) = ((*page).max(start), (page + MOCK_PAGE_SIZE).min(end));
+                match found.last_mut() {
+                    Some(last) if last.1 == start => last.1 = end,
+                    _ => found.push((start, end)),
+                }
+            }
+        }
+        Ok(found)
+    }
+}
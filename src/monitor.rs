@@ -0,0 +1,271 @@
+use std::{sync::{Arc, Condvar, Mutex}, thread::JoinHandle, time::{Duration, Instant, SystemTime}};
+use crate::{expr::{Derived, DerivedValue}, process::{ProcessMemory, read_many_bytes}, value::{Endianness, ValueType}};
+
+// A cheap watch over a whole table of addresses: one background thread reads every entry in a
+// single batched read each interval and reports those that changed since the last. Nothing traps
+// the writes, so a change undone before the next read is never seen, and one that cannot be read
+// keeps the last value that could until it can be again
+
+// How often a monitor reads its entries unless told otherwise
+pub const DEFAULT_MONITOR_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonitorEntry {
+    // What the change is reported as, e.g. a result's tag
+    pub label: String,
+    pub address: usize,
+    pub size: usize,
+    // How the bytes read are shown
+    pub value_type: ValueType,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorChange {
+    pub entry: MonitorEntry,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+    // When the read that saw it was made
+    pub time: SystemTime,
+}
+
+// Called on the monitor's thread for every change, so it must not block for long
+#[derive(Clone)]
+pub struct MonitorCallback(pub Arc<dyn Fn(MonitorChange) + Send + Sync>);
+
+impl std::fmt::Debug for MonitorCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MonitorCallback")
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    reads: u64,
+    changes: u64,
+    shutdown: bool,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+#[derive(Debug)]
+pub struct ValueMonitor {
+    entries: Vec<MonitorEntry>,
+    interval: Duration,
+    shared: Arc<Shared>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ValueMonitor {
+    // The first read only records what each entry holds, so changes are from when it started
+    pub fn start(process: impl ProcessMemory + 'static, entries: Vec<MonitorEntry>, interval: Duration, callback: MonitorCallback) -> Result<ValueMonitor, Box<dyn std::error::Error>> {
+        if interval.is_zero() {
+            return Err("A monitor needs an interval above zero".into());
+        }
+        if entries.is_empty() {
+            return Err("There is nothing to monitor".into());
+        }
+        let shared = Arc::new(Shared::default());
+        let (threads_shared, threads_entries) = (shared.clone(), entries.clone());
+        let handle = std::thread::spawn(move || service(process, &threads_entries, interval, &callback, &threads_shared));
+        Ok(ValueMonitor { entries, interval, shared, handle: Some(handle) })
+    }
+
+    pub fn entries(&self) -> &[MonitorEntry] {
+        &self.entries
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    // Batched reads made so far
+    pub fn reads(&self) -> u64 {
+        self.shared.state.lock().unwrap().reads
+    }
+
+    // Changes reported so far
+    pub fn changes(&self) -> u64 {
+        self.shared.state.lock().unwrap().changes
+    }
+
+    pub fn stop(&mut self) {
+        self.shared.state.lock().unwrap().shutdown = true;
+        self.shared.changed.notify_all();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for ValueMonitor {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn service(process: impl ProcessMemory, entries: &[MonitorEntry], interval: Duration, callback: &MonitorCallback, shared: &Shared) {
+    let requests = entries.iter().map(|x| (x.address, x.size)).collect::<Vec<(usize, usize)>>();
+    let mut last = vec![None; entries.len()];
+    let mut due = Instant::now();
+    loop {
+        let mut state = shared.state.lock().unwrap();
+        while !state.shutdown && Instant::now() < due {
+            state = shared.changed.wait_timeout(state, due.saturating_duration_since(Instant::now())).unwrap().0;
+        }
+        if state.shutdown {
+            return;
+        }
+        drop(state);
+        let time = SystemTime::now();
+        let mut changes = 0;
+        for ((read, last), entry) in read_many_bytes(&process, &requests).into_iter().zip(&mut last).zip(entries) {
+            let Ok(new) = read else {
+                continue;
+            };
+            if let Some(old) = last.replace(new.clone()).filter(|x| *x != new) {
+                changes += 1;
+                (callback.0)(MonitorChange { entry: entry.clone(), old, new, time });
+            }
+        }
+        let mut state = shared.state.lock().unwrap();
+        state.reads += 1;
+        state.changes += changes;
+        // A read slower than the interval is followed straight away by the next, not by a backlog
+        due = (due + interval).max(Instant::now());
+    }
+}
+
+// A derived value watched by an ExpressionMonitor, with the addresses its reads resolved to
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchedExpression {
+    pub name: String,
+    pub derived: Derived,
+    pub addresses: Vec<usize>,
+}
+
+impl WatchedExpression {
+    // One (address, size) for each read
+    fn requests(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.addresses.iter().zip(&self.derived.reads).map(|(address, read)| (*address, read.value_type.size().unwrap_or(0)))
+    }
+
+    // Worked out from one read of its addresses now, None if any of them could not be read
+    pub fn read(&self, process: &impl ProcessMemory, endianness: Endianness) -> Option<DerivedValue> {
+        let bytes = read_many_bytes(process, &self.requests().collect::<Vec<(usize, usize)>>()).into_iter().map(|x| x.ok()).collect::<Vec<Option<Vec<u8>>>>();
+        self.derived.evaluate(&bytes, endianness)
+    }
+}
+
+// None for a value that could not be worked out, as when a read failed
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpressionChange {
+    pub name: String,
+    pub old: Option<DerivedValue>,
+    pub new: Option<DerivedValue>,
+    pub time: SystemTime,
+}
+
+#[derive(Clone)]
+pub struct ExpressionCallback(pub Arc<dyn Fn(ExpressionChange) + Send + Sync>);
+
+impl std::fmt::Debug for ExpressionCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ExpressionCallback")
+    }
+}
+
+// As a ValueMonitor, but over derived values: every address of every expression is read in one
+// batched read each interval, and an expression is reported when what it works out to changes.
+// One that cannot be worked out is reported as unavailable, and again once it can be
+#[derive(Debug)]
+pub struct ExpressionMonitor {
+    watched: Vec<WatchedExpression>,
+    interval: Duration,
+    values: Arc<Mutex<Vec<Option<DerivedValue>>>>,
+    shared: Arc<Shared>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ExpressionMonitor {
+    // Starts from the values given, one for each expression, which are reported as changed from
+    pub fn start(process: impl ProcessMemory + 'static, watched: Vec<WatchedExpression>, values: Vec<Option<DerivedValue>>, interval: Duration, endianness: Endianness, callback: ExpressionCallback) -> Result<ExpressionMonitor, Box<dyn std::error::Error>> {
+        if interval.is_zero() {
+            return Err("A monitor needs an interval above zero".into());
+        }
+        if watched.is_empty() || watched.len() != values.len() {
+            return Err("Expected a value for each expression watched".into());
+        }
+        let (shared, values) = (Arc::new(Shared::default()), Arc::new(Mutex::new(values)));
+        let (threads_shared, threads_values, threads_watched) = (shared.clone(), values.clone(), watched.clone());
+        let handle = std::thread::spawn(move || service_expressions(process, &threads_watched, interval, endianness, &callback, &threads_values, &threads_shared));
+        Ok(ExpressionMonitor { watched, interval, values, shared, handle: Some(handle) })
+    }
+
+    pub fn watched(&self) -> &[WatchedExpression] {
+        &self.watched
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    // What each expression worked out to at the last read
+    pub fn values(&self) -> Vec<Option<DerivedValue>> {
+        self.values.lock().unwrap().clone()
+    }
+
+    pub fn reads(&self) -> u64 {
+        self.shared.state.lock().unwrap().reads
+    }
+
+    pub fn stop(&mut self) {
+        self.shared.state.lock().unwrap().shutdown = true;
+        self.shared.changed.notify_all();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for ExpressionMonitor {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn service_expressions(process: impl ProcessMemory, watched: &[WatchedExpression], interval: Duration, endianness: Endianness, callback: &ExpressionCallback, values: &Mutex<Vec<Option<DerivedValue>>>, shared: &Shared) {
+    let requests = watched.iter().flat_map(|x| x.requests()).collect::<Vec<(usize, usize)>>();
+    let mut due = Instant::now() + interval;
+    loop {
+        let mut state = shared.state.lock().unwrap();
+        while !state.shutdown && Instant::now() < due {
+            state = shared.changed.wait_timeout(state, due.saturating_duration_since(Instant::now())).unwrap().0;
+        }
+        if state.shutdown {
+            return;
+        }
+        drop(state);
+        let time = SystemTime::now();
+        let mut bytes = read_many_bytes(&process, &requests).into_iter().map(|x| x.ok());
+        let mut changes = 0;
+        for (watched, last) in watched.iter().zip(values.lock().unwrap().iter_mut()) {
+            let new = watched.derived.evaluate(&bytes.by_ref().take(watched.addresses.len()).collect::<Vec<Option<Vec<u8>>>>(), endianness);
+            let same = match (*last, new) {
+                (Some(old), Some(new)) => old.same(new),
+                (old, new) => old.is_none() && new.is_none(),
+            };
+            if !same {
+                changes += 1;
+                (callback.0)(ExpressionChange { name: watched.name.clone(), old: std::mem::replace(last, new), new, time });
+            }
+        }
+        let mut state = shared.state.lock().unwrap();
+        state.reads += 1;
+        state.changes += changes;
+        due = (due + interval).max(Instant::now());
+    }
+}
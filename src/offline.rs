@@ -0,0 +1,146 @@
+use std::{fs::File, path::Path};
+use crate::{capture::Capture, dump::{DUMP_INDEX, DumpIndex}, elf::Arch, maps::MemoryRegion, platform::{Errno, FileExt, Pid}, process::{ProcessMemory, loaded_arch}};
+
+// A capture written by dump_all, or a dump directory from before captures, read back as if it
+// were the process it was captured from. Addresses are the original ones, the regions, executable
+// and thread stacks those recorded with it, and writes always fail. Mappings that were recorded
+// without being captured are listed among the regions as unreadable, which is what they are now
+#[derive(Debug)]
+pub struct OfflineCapture {
+    pid: Pid,
+    executable: Option<String>,
+    // As the capture recorded it, so that offline mode refuses and allows what the live target did
+    arch: Arch,
+    stacks: Vec<(Pid, usize)>,
+    regions: Vec<MemoryRegion>,
+    uncaptured: Vec<MemoryRegion>,
+    holes: Vec<Vec<(usize, usize)>>,
+    storage: Storage,
+}
+
+#[derive(Debug)]
+enum Storage {
+    // One file per region, in the same order as the regions
+    Files(Vec<File>),
+    Capture(Capture),
+}
+
+impl OfflineCapture {
+    pub fn open(path: &Path) -> Result<OfflineCapture, Box<dyn std::error::Error>> {
+        if path.is_dir() {
+            return OfflineCapture::open_dir(path);
+        }
+        let capture = Capture::open(path)?;
+        // Reads look regions up by address, so they have to be in order
+        if capture.regions.windows(2).any(|x| x[0].region.end > x[1].region.start) {
+            return Err(format!("The regions in {} are out of order or overlap", path.display()).into());
+        }
+        Ok(OfflineCapture {
+            pid: Pid::from_raw(capture.header.pid),
+            executable: capture.header.executable.clone(),
+            arch: capture.header.arch,
+            stacks: capture.header.stacks.iter().map(|x| (Pid::from_raw(x.0), x.1)).collect(),
+            regions: capture.regions.iter().map(|x| x.region.clone()).collect(),
+            uncaptured: capture.header.uncaptured.iter().map(|x| MemoryRegion { readable: false, ..x.clone() }).collect(),
+            holes: capture.regions.iter().map(|x| x.holes.clone()).collect(),
+            storage: Storage::Capture(capture),
+        })
+    }
+
+    fn open_dir(dir: &Path) -> Result<OfflineCapture, Box<dyn std::error::Error>> {
+        let index_path = dir.join(DUMP_INDEX);
+        let index: DumpIndex = serde_json::from_str(&std::fs::read_to_string(&index_path).map_err(|e| format!("Could not read {}: {}", index_path.display(), e))?)?;
+        let mut capture = OfflineCapture {
+            pid: Pid::from_raw(index.pid),
+            executable: index.executable,
+            arch: Arch::native(),
+            stacks: index.stacks.into_iter().map(|x| (Pid::from_raw(x.0), x.1)).collect(),
+            regions: Vec::new(),
+            uncaptured: Vec::new(),
+            holes: Vec::new(),
+            storage: Storage::Files(Vec::new()),
+        };
+        let mut files = Vec::new();
+        let mut regions = index.regions;
+        regions.sort_by_key(|x| x.region.start);
+        for dumped in regions {
+            let path = dir.join(&dumped.file);
+            let file = File::open(&path).map_err(|e| format!("Could not open {}: {}", path.display(), e))?;
+            if file.metadata()?.len() != dumped.region.len() as u64 {
+                return Err(format!("{} should hold 0x{:x} bytes for 0x{:x}-0x{:x}", path.display(), dumped.region.len(), dumped.region.start, dumped.region.end).into());
+            }
+            capture.regions.push(dumped.region);
+            files.push(file);
+            capture.holes.push(dumped.holes);
+        }
+        capture.storage = Storage::Files(files);
+        // Dump directories never recorded it, but have the executable's header if it was dumped
+        capture.arch = loaded_arch(&capture).unwrap_or_else(Arch::native);
+        Ok(capture)
+    }
+}
+
+impl ProcessMemory for OfflineCapture {
+    fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    // Like process_vm_readv, a read runs on across adjacent regions and stops short at the first
+    // byte that was not captured, failing only if not even the first byte was
+    fn read_at(&self, address: usize, buffer: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut done = 0;
+        while done < buffer.len() {
+            let position = address + done;
+            let index = self.regions.partition_point(|x| x.end <= position);
+            let Some(region) = self.regions.get(index).filter(|x| x.contains(position)) else {
+                break;
+            };
+            let holes = &self.holes[index];
+            if holes.iter().any(|x| position >= x.0 && position < x.1) {
+                break;
+            }
+            let end = holes.iter().map(|x| x.0).filter(|x| *x > position).min().unwrap_or(region.end);
+            let len = (end - position).min(buffer.len() - done);
+            match &self.storage {
+                Storage::Files(files) => files[index].read_exact_at(&mut buffer[done..done + len], (position - region.start) as u64)?,
+                Storage::Capture(capture) => capture.read_region(index, position - region.start, &mut buffer[done..done + len])?,
+            }
+            done += len;
+        }
+        if done == 0 && !buffer.is_empty() {
+            return Err(Errno::EFAULT.into());
+        }
+        Ok(done)
+    }
+
+    fn write_at(&self, _address: usize, _data: &[u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        Err("Offline captures are read-only".into())
+    }
+
+    fn memory_regions(&self) -> Result<Vec<MemoryRegion>, Box<dyn std::error::Error>> {
+        let mut regions = [self.regions.as_slice(), &self.uncaptured].concat();
+        regions.sort_by_key(|x| x.start);
+        Ok(regions)
+    }
+
+    fn executable_path(&self) -> Option<String> {
+        self.executable.clone()
+    }
+
+    fn thread_stacks(&self) -> Vec<(Pid, usize)> {
+        self.stacks.clone()
+    }
+
+    fn arch(&self) -> Arch {
+        self.arch
+    }
+
+    // The pid is the one the capture was taken from, whose pages are nothing to do with it now
+    fn clear_soft_dirty(&self) -> Result<(), Box<dyn std::error::Error>> {
+        Err("A capture has no soft-dirty pages to track".into())
+    }
+
+    fn dirty_ranges(&self, _ranges: &[(usize, usize)]) -> Result<Vec<(usize, usize)>, Box<dyn std::error::Error>> {
+        Err("A capture has no soft-dirty pages to track".into())
+    }
+}
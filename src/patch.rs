@@ -0,0 +1,90 @@
+use crate::process::{ProcessMemory, read_bytes_from_process, write_bytes_to_process};
+
+// The x86 one-byte no-op
+pub const NOP: u8 = 0x90;
+
+// Bytes written over code or data, with the original bytes kept so they can be put back. Nothing
+// is restored when the handle is dropped unless restore_on_drop is set
+pub struct Patch<P: ProcessMemory> {
+    process: P,
+    pub address: usize,
+    pub original: Vec<u8>,
+    pub patched: Vec<u8>,
+    pub restore_on_drop: bool,
+    restored: bool,
+}
+
+impl<P: ProcessMemory> std::fmt::Debug for Patch<P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Patch").field("address", &self.address).field("original", &self.original).field("patched", &self.patched).field("restored", &self.restored).finish()
+    }
+}
+
+impl<P: ProcessMemory> Patch<P> {
+    pub fn len(&self) -> usize {
+        self.patched.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patched.is_empty()
+    }
+
+    pub fn is_restored(&self) -> bool {
+        self.restored
+    }
+
+    pub fn contains(&self, address: usize) -> bool {
+        address >= self.address && address < self.address + self.len()
+    }
+
+    // Writes the original bytes back. Fails without writing if the patched bytes are no longer
+    // there, e.g. because the module was unloaded and something else now lives at the address
+    pub fn restore(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.restored {
+            return Ok(());
+        }
+        if read_bytes_from_process(&self.process, self.len(), self.address)? != self.patched {
+            return Err(format!("The bytes at 0x{:x} changed since they were patched", self.address).into());
+        }
+        write_all(&self.process, self.address, &self.original)?;
+        self.restored = true;
+        Ok(())
+    }
+}
+
+impl<P: ProcessMemory> Drop for Patch<P> {
+    fn drop(&mut self) {
+        if self.restore_on_drop {
+            let _ = self.restore();
+        }
+    }
+}
+
+// Saves the bytes at the address, then writes the new ones over them. A write that only partly
+// lands is undone before failing, so the target is never left half patched
+pub fn patch_bytes<P: ProcessMemory>(process: P, address: usize, bytes: &[u8]) -> Result<Patch<P>, Box<dyn std::error::Error>> {
+    let original = read_bytes_from_process(&process, bytes.len(), address)?;
+    if original.len() < bytes.len() {
+        return Err(format!("Only {} of {} bytes at 0x{:x} could be read", original.len(), bytes.len(), address).into());
+    }
+    let written = write_bytes_to_process(&process, address, bytes)?;
+    if written < bytes.len() {
+        let _ = write_bytes_to_process(&process, address, &original[..written]);
+        return Err(format!("Only {} of {} bytes at 0x{:x} could be written", written, bytes.len(), address).into());
+    }
+    Ok(Patch { process, address, original, patched: bytes.to_vec(), restore_on_drop: false, restored: false })
+}
+
+// Replaces len bytes of code with NOPs, e.g. to take out an instruction that decrements a value.
+// len has to cover whole instructions, or the target will run the tail of a cut one
+pub fn patch_nop<P: ProcessMemory>(process: P, address: usize, len: usize) -> Result<Patch<P>, Box<dyn std::error::Error>> {
+    patch_bytes(process, address, &vec![NOP; len])
+}
+
+fn write_all(process: impl ProcessMemory, address: usize, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    let written = write_bytes_to_process(process, address, data)?;
+    if written < data.len() {
+        return Err(format!("Only {} of {} bytes at 0x{:x} could be written", written, data.len(), address).into());
+    }
+    Ok(())
+}
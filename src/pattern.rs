@@ -0,0 +1,399 @@
+use std::str::FromStr;
+use crate::{process::{ProcessMemory, read_many_bytes}, scan::{ChunkHit, ScanOptions, ScanStats, read_error_name, reduce_failures, scan_chunks}, value::{Encoding, Endianness, Scalar, TypedValue, ValueType}};
+
+// What one field of a struct has to hold for the struct to match
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldConstraint {
+    // Exactly the value, compared as its type so that 0.0 also matches -0.0
+    Equals(TypedValue),
+    // Anything from the first value to the second, inclusive, compared as their type
+    Range(TypedValue, TypedValue),
+    // A pointer-sized value pointing into a readable mapping
+    Pointer,
+    // Any value of the type: a field known to be there, but not what it holds
+    Any(ValueType),
+}
+
+impl FieldConstraint {
+    pub fn size(&self) -> usize {
+        match self {
+            FieldConstraint::Equals(value) | FieldConstraint::Range(value, _) => value.to_bytes().len(),
+            FieldConstraint::Pointer => std::mem::size_of::<usize>(),
+            FieldConstraint::Any(value_type) => value_type.size().unwrap_or(0),
+        }
+    }
+
+    // Equal values go first, as they rule out the most, and wildcards are left out
+    fn cost(&self) -> usize {
+        match self {
+            FieldConstraint::Equals(_) => 0,
+            FieldConstraint::Range(..) => 1,
+            FieldConstraint::Pointer => 2,
+            FieldConstraint::Any(_) => 3,
+        }
+    }
+}
+
+// Fields at fixed offsets from a struct's start, from a layout known only in part. Parsed from a
+// comma-separated list like "+0x10 i32 1..1000, +0x18 f32 0.5..20, +0x0 ptr", where a field's
+// value is one to equal, a range, or * for anything, and ptr is a pointer into mapped memory
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StructPattern {
+    pub fields: Vec<(usize, FieldConstraint)>,
+}
+
+// Whether the field's bytes satisfy its constraint
+type FieldMatcher = Box<dyn Fn(&[u8]) -> bool + Send + Sync>;
+
+// Each field's offset and size, with what checks it
+type CompiledFields = Vec<(usize, usize, FieldMatcher)>;
+
+impl StructPattern {
+    // From the start to the end of the last field
+    pub fn size(&self) -> usize {
+        self.fields.iter().map(|(offset, field)| offset + field.size()).max().unwrap_or(0)
+    }
+
+    // The widest field's size, which is what a compiler aligns such a struct to, pointers and
+    // 64-bit values being at most 8
+    pub fn alignment(&self) -> usize {
+        self.fields.iter().map(|x| x.1.size()).max().unwrap_or(1).clamp(1, 8)
+    }
+
+    fn compile(&self, process: &impl ProcessMemory, endianness: Endianness) -> Result<CompiledFields, Box<dyn std::error::Error>> {
+        if self.fields.iter().all(|x| matches!(x.1, FieldConstraint::Any(_))) {
+            return Err("A struct pattern needs a field that is not a wildcard, or it matches everywhere".into());
+        }
+        let mut fields = self.fields.iter().collect::<Vec<&(usize, FieldConstraint)>>();
+        fields.sort_by_key(|x| x.1.cost());
+        let mut matchers = Vec::new();
+        for (offset, field) in fields {
+            let matcher: FieldMatcher = match field {
+                FieldConstraint::Equals(value) => equals_matcher(value, endianness)?,
+                FieldConstraint::Range(min, max) => {
+                    if min.value_type() != max.value_type() {
+                        return Err(format!("A range is between values of one type, not {} and {}", min.value_type(), max.value_type()).into());
+                    }
+                    crate::with_scan_type!(min.value_type(), T, {
+                        let (min, max) = (T::from_bytes(&min.to_bytes()), T::from_bytes(&max.to_bytes()));
+                        if min.partial_cmp(&max).is_none_or(|x| x.is_gt()) {
+                            return Err(format!("The range {}..{} is empty", min, max).into());
+                        }
+                        Box::new(move |x| {
+                            let value = if endianness.is_native() { T::from_bytes(x) } else { T::from_bytes_in(x, endianness) };
+                            min <= value && value <= max
+                        })
+                    })
+                }
+                FieldConstraint::Pointer => {
+                    let mut regions = process.memory_regions()?.into_iter().filter(|x| x.readable).map(|x| (x.start, x.end)).collect::<Vec<(usize, usize)>>();
+                    regions.sort();
+                    Box::new(move |x| {
+                        let pointer = if endianness.is_native() { usize::from_bytes(x) } else { usize::from_bytes_in(x, endianness) };
+                        let index = regions.partition_point(|x| x.1 <= pointer);
+                        regions.get(index).is_some_and(|x| x.0 <= pointer)
+                    })
+                }
+                FieldConstraint::Any(_) => continue,
+            };
+            matchers.push((*offset, field.size(), matcher));
+        }
+        Ok(matchers)
+    }
+}
+
+fn equals_matcher(value: &TypedValue, endianness: Endianness) -> Result<FieldMatcher, Box<dyn std::error::Error>> {
+    if let TypedValue::Bytes(_) | TypedValue::Str { .. } = value {
+        let bytes = value.to_bytes_in(endianness);
+        return Ok(Box::new(move |x| x == bytes));
+    }
+    crate::with_scan_type!(value.value_type(), T, {
+        let patterns = T::from_bytes(&value.to_bytes()).equal_patterns_in(endianness);
+        Ok(Box::new(move |x| patterns.iter().any(|pattern| pattern[..] == x[..])))
+    })
+}
+
+impl FromStr for StructPattern {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields = s.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()).map(parse_field).collect::<Result<Vec<(usize, FieldConstraint)>, Self::Err>>()?;
+        if fields.is_empty() {
+            return Err("Expected fields like +0x10 i32 1..1000, separated by commas".into());
+        }
+        Ok(StructPattern { fields })
+    }
+}
+
+// "+0x10 i32 1..1000", "+8 f32 1.5", "+0x20 u8 *" or "+0x0 ptr"
+fn parse_field(s: &str) -> Result<(usize, FieldConstraint), Box<dyn std::error::Error>> {
+    let mut words = s.splitn(3, char::is_whitespace).map(|x| x.trim());
+    let (Some(offset), Some(value_type)) = (words.next(), words.next()) else {
+        return Err(format!("Expected '<offset> <type> [<value>|<min>..<max>|*]' or '<offset> ptr', got '{}'", s).into());
+    };
+    let offset = offset.trim_start_matches('+');
+    let offset = match offset.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16)?,
+        None => offset.parse::<usize>()?,
+    };
+    let value = words.next().filter(|x| !x.is_empty());
+    if value_type == "ptr" || value_type == "pointer" {
+        return match value {
+            None => Ok((offset, FieldConstraint::Pointer)),
+            Some(value) => Err(format!("A pointer field only checks that it points into mapped memory, so takes no value like '{}'", value).into()),
+        };
+    }
+    let value_type = value_type.parse::<ValueType>()?;
+    let constraint = match value {
+        None | Some("*") if value_type.size().is_none() => return Err(format!("A {} field needs a value to give it a length", value_type).into()),
+        None | Some("*") => FieldConstraint::Any(value_type),
+        Some(value) => match value.split_once("..") {
+            Some((min, max)) if value_type.size().is_some() => FieldConstraint::Range(value_type.parse_value(min)?, value_type.parse_value(max)?),
+            _ => FieldConstraint::Equals(value_type.parse_value(value)?),
+        },
+    };
+    Ok((offset, constraint))
+}
+
+// Finds the addresses at which every field of the pattern holds what it should, stepping through
+// memory by the options' alignment, which should be what the struct is aligned to
+pub fn find_struct(process: impl ProcessMemory, pattern: &StructPattern, options: &ScanOptions) -> Result<(Vec<usize>, ScanStats), Box<dyn std::error::Error>> {
+    let matchers = pattern.compile(&process, options.endianness)?;
+    scan_chunks(process, options, pattern.size(), |data, offsets, found| {
+        for offset in offsets.iter() {
+            if matchers.iter().all(|(field, size, matches)| matches(&data[offset + field..offset + field + size])) {
+                found.push(offset);
+            }
+        }
+    })
+}
+
+// Keeps the results at which the pattern still matches, and those that can no longer be read as
+// other rescans do
+pub fn reduce_found_structs(process: impl ProcessMemory, found_values: &mut Vec<usize>, pattern: &StructPattern, options: &ScanOptions, stats: &mut ScanStats) -> Result<(), Box<dyn std::error::Error>> {
+    let matchers = pattern.compile(&process, options.endianness)?;
+    let size = pattern.size();
+    let structs = read_many_bytes(&process, &found_values.iter().map(|x| (*x, size)).collect::<Vec<(usize, usize)>>());
+    let mut unread = Vec::new();
+    let mut structs = structs.into_iter();
+    found_values.retain(|address| match structs.next() {
+        Some(Ok(bytes)) => matchers.iter().all(|(field, size, matches)| matches(&bytes[*field..field + size])),
+        Some(Err(e)) => {
+            unread.push((*address, read_error_name(e.as_ref())));
+            true
+        }
+        None => true,
+    });
+    stats.read_failures = reduce_failures(&process.memory_regions()?, unread, size);
+    stats.matches = found_values.len();
+    Ok(())
+}
+
+// How far apart scan pair looks for the two values unless told otherwise
+pub const DEFAULT_PAIR_DISTANCE: usize = 64;
+
+// How far the second value of a pair may be from the first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PairDistance {
+    // Either side of it, by at most this many bytes
+    Within(usize),
+    // Exactly this far after it, or before it if negative
+    Exactly(isize),
+}
+
+// Where one of a pair's values was seen; the first of them when !second
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct ValueHit {
+    address: usize,
+    second: bool,
+}
+
+impl ChunkHit for ValueHit {
+    fn shift(&mut self, base: usize) {
+        self.address += base;
+    }
+}
+
+// The first's address, the second's, and how far the second is from the first
+pub type Pair = (usize, usize, isize);
+
+// Finds where both values are held close enough together to be fields of one object. Both are
+// looked for in the same pass, and the places each was seen are then joined with a window the
+// distance wide. Values that overlap are not fields side by side, so are never paired
+pub fn find_pair(process: impl ProcessMemory, first: &TypedValue, second: &TypedValue, distance: PairDistance, options: &ScanOptions) -> Result<(Vec<Pair>, ScanStats), Box<dyn std::error::Error>> {
+    let sizes = (first.to_bytes().len(), second.to_bytes().len());
+    if sizes.0 == 0 || sizes.1 == 0 {
+        return Err("Both values of a pair need at least one byte".into());
+    }
+    let matchers = (equals_matcher(first, options.endianness)?, equals_matcher(second, options.endianness)?);
+    let (hits, mut stats) = scan_chunks(process, options, sizes.0.max(sizes.1), |data, offsets, found| {
+        for offset in offsets.iter() {
+            if (matchers.0)(&data[offset..offset + sizes.0]) {
+                found.push(ValueHit { address: offset, second: false });
+            }
+            if (matchers.1)(&data[offset..offset + sizes.1]) {
+                found.push(ValueHit { address: offset, second: true });
+            }
+        }
+    })?;
+    let (seconds, firsts): (Vec<ValueHit>, Vec<ValueHit>) = hits.into_iter().partition(|x| x.second);
+    let seconds = seconds.into_iter().map(|x| x.address).collect::<Vec<usize>>();
+    let apart = |a: usize, b: usize| b >= a + sizes.0 || a >= b + sizes.1;
+    let mut pairs = Vec::new();
+    let mut start = 0;
+    for a in firsts.into_iter().map(|x| x.address) {
+        match distance {
+            PairDistance::Exactly(delta) => {
+                if let Some(b) = a.checked_add_signed(delta) && apart(a, b) && seconds.binary_search(&b).is_ok() {
+                    pairs.push((a, b, delta));
+                }
+            }
+            PairDistance::Within(max) => {
+                while start < seconds.len() && seconds[start].saturating_add(max) < a {
+                    start += 1;
+                }
+                for b in seconds[start..].iter().copied().take_while(|x| *x <= a.saturating_add(max)).filter(|x| apart(a, *x)) {
+                    pairs.push((a, b, b as isize - a as isize));
+                }
+            }
+        }
+    }
+    stats.matches = pairs.len();
+    Ok((pairs, stats))
+}
+
+// The most characters a * in a string glob stands for unless told otherwise
+pub const DEFAULT_GLOB_RUN: usize = 32;
+
+// How scan string matches its needle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StringSearch {
+    pub encoding: Encoding,
+    // ASCII letters match in either case
+    pub ignore_case: bool,
+    // ? is any one character and * a run of up to max_run, with \ before either to match it as
+    // itself. Neither matches a NUL, so a match stays inside one string. Otherwise the needle is
+    // matched as it is written
+    pub glob: bool,
+    pub max_run: usize,
+    // Only a match with a NUL just before it, the start of a string rather than the tail of a longer
+    // one. A string at the very start of a region has nothing before it, so is not found
+    pub after_nul: bool,
+}
+
+impl Default for StringSearch {
+    fn default() -> Self {
+        StringSearch { encoding: Encoding::Utf8, ignore_case: false, glob: false, max_run: DEFAULT_GLOB_RUN, after_nul: false }
+    }
+}
+
+// One code unit of a compiled needle. ? and * count code units, so in UTF-8 a character outside
+// ASCII takes as many ? as it has bytes
+#[derive(Debug, Clone, PartialEq)]
+enum GlobToken {
+    // The unit as bytes in memory, or any of them where case is ignored
+    Unit(Vec<Vec<u8>>),
+    Any,
+    Run,
+}
+
+fn compile_needle(needle: &str, search: &StringSearch, endianness: Endianness) -> Result<Vec<GlobToken>, Box<dyn std::error::Error>> {
+    let unit = |c: char| -> Vec<Vec<u8>> {
+        let variants = match search.ignore_case && c.is_ascii_alphabetic() {
+            true => vec![c.to_ascii_lowercase(), c.to_ascii_uppercase()],
+            false => vec![c],
+        };
+        variants.into_iter().map(|c| TypedValue::Str { text: c.to_string(), encoding: search.encoding, null_terminate: false }.to_bytes_in(endianness)).collect()
+    };
+    let mut tokens = Vec::new();
+    let mut characters = needle.chars();
+    while let Some(c) = characters.next() {
+        let c = match (search.glob, c) {
+            (true, '?') => {
+                tokens.push(GlobToken::Any);
+                continue;
+            }
+            (true, '*') => {
+                tokens.push(GlobToken::Run);
+                continue;
+            }
+            (true, '\\') => characters.next().ok_or_else(|| format!("'{}' ends in a \\ with nothing after it to match as itself", needle))?,
+            (_, c) => c,
+        };
+        // A character may take several units, each matched on its own
+        let variants = unit(c);
+        let size = search.encoding.unit_size();
+        for index in 0..variants[0].len() / size {
+            tokens.push(GlobToken::Unit(variants.iter().map(|x| x[index * size..(index + 1) * size].to_vec()).collect()));
+        }
+    }
+    if !tokens.iter().any(|x| matches!(x, GlobToken::Unit(_))) {
+        return Err(match needle.is_empty() {
+            true => "An empty string would match everywhere".into(),
+            false => format!("'{}' is all wildcards, which would match almost everywhere", needle).into(),
+        });
+    }
+    Ok(tokens)
+}
+
+// Whether the tokens match from the start of the data. Every way through the tokens is followed at
+// once, a unit at a time, rather than by trying each length of each run in turn, which for a needle
+// like *a*a*a*b costs max_run to the power of its runs. reached holds each token the data so far can
+// have got to, with the fewest units its run has taken; fewer leave more for the rest, so only that
+// one need be kept. next is scratch, and the caller keeps both from one start to the next
+fn glob_matches(tokens: &[GlobToken], data: &[u8], unit: usize, max_run: usize, reached: &mut Vec<(usize, usize)>, next: &mut Vec<(usize, usize)>) -> bool {
+    // Most starts fail at the first unit, which is quicker to see without the states
+    if let Some(GlobToken::Unit(variants)) = tokens.first() && !data.get(..unit).is_some_and(|x| variants.iter().any(|v| v == x)) {
+        return false;
+    }
+    let nul = |x: &[u8]| x.iter().all(|x| *x == 0);
+    // A run can also take nothing, so whatever reaches one reaches the token after it too
+    let reach = |states: &mut Vec<(usize, usize)>, mut index: usize, mut run: usize| loop {
+        match states.iter_mut().find(|x| x.0 == index) {
+            Some(state) => state.1 = state.1.min(run),
+            None => states.push((index, run)),
+        }
+        if tokens.get(index) != Some(&GlobToken::Run) {
+            break;
+        }
+        (index, run) = (index + 1, 0);
+    };
+    reached.clear();
+    reach(reached, 0, 0);
+    for x in data.chunks_exact(unit) {
+        if reached.iter().any(|x| x.0 == tokens.len()) {
+            return true;
+        }
+        next.clear();
+        for &(index, run) in reached.iter().filter(|x| x.0 < tokens.len()) {
+            match &tokens[index] {
+                GlobToken::Unit(variants) if variants.iter().any(|v| v == x) => reach(next, index + 1, 0),
+                GlobToken::Any if !nul(x) => reach(next, index + 1, 0),
+                GlobToken::Run if !nul(x) && run < max_run => reach(next, index, run + 1),
+                _ => {}
+            }
+        }
+        std::mem::swap(reached, next);
+        if reached.is_empty() {
+            return false;
+        }
+    }
+    reached.iter().any(|x| x.0 == tokens.len())
+}
+
+// Finds where the needle starts, at the options' alignment. With after_nul each match also looks
+// at the unit before it, which has to be a terminator
+pub fn find_string(process: impl ProcessMemory, needle: &str, search: &StringSearch, options: &ScanOptions) -> Result<(Vec<usize>, ScanStats), Box<dyn std::error::Error>> {
+    let tokens = compile_needle(needle, search, options.endianness)?;
+    let unit = search.encoding.unit_size();
+    let lead = if search.after_nul { unit } else { 0 };
+    let longest = tokens.iter().map(|x| if *x == GlobToken::Run { search.max_run } else { 1 }).sum::<usize>() * unit;
+    scan_chunks(process, options, lead + longest, |data, offsets, found| {
+        let (mut reached, mut next) = (Vec::new(), Vec::new());
+        for offset in offsets.iter_led(lead) {
+            if data.len() >= offset + lead && data[offset..offset + lead].iter().all(|x| *x == 0) && glob_matches(&tokens, &data[offset + lead..], unit, search.max_run, &mut reached, &mut next) {
+                found.push(offset + lead);
+            }
+        }
+    })
+}
@@ -0,0 +1,8 @@
+// The few names whose type differs between the platforms the library builds on. On Linux they
+// are nix's and std's own, on Windows the equivalents in windows.rs
+#[cfg(unix)]
+pub use nix::{errno::Errno, unistd::Pid};
+#[cfg(unix)]
+pub use std::os::unix::fs::FileExt;
+#[cfg(windows)]
+pub use crate::windows::{Errno, FileExt, Pid};
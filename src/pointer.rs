@@ -0,0 +1,185 @@
+use std::str::FromStr;
+use crate::{address::AddressExpression, maps::{Module, RegionCache, module_for_address, modules_from_regions}, process::{ProcessMemory, read_many_in, read_scalar, write_scalar}, scan::{ScanOptions, ScanStats, scan_chunks}, session::SavedAddress, value::{Endianness, Scalar}};
+
+// How far past what a pointer points at find_pointers_to looks for the target by default
+pub const DEFAULT_POINTER_OFFSET: isize = 0x1000;
+
+// A base address followed by the offsets to add after following each pointer, written
+// "libgame.so+0x1a2b0->0x10->-0x8": read the pointer at libgame.so+0x1a2b0, add 0x10, read the
+// pointer there and subtract 8. A chain without offsets is just its base
+#[derive(Debug, Clone, PartialEq)]
+pub struct PointerChain {
+    pub base: SavedAddress,
+    pub offsets: Vec<isize>,
+}
+
+impl std::fmt::Display for PointerChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.base)?;
+        for offset in &self.offsets {
+            match *offset < 0 {
+                true => write!(f, "->-0x{:x}", offset.unsigned_abs())?,
+                false => write!(f, "->0x{:x}", offset)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PointerChain {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split("->");
+        let base = parts.next().unwrap_or_default().parse::<SavedAddress>()?;
+        let offsets = parts.map(parse_offset).collect::<Result<Vec<isize>, Box<dyn std::error::Error>>>()?;
+        Ok(PointerChain { base, offsets })
+    }
+}
+
+// A signed hex offset as a chain writes one, "0x10", "-0x8" or "+10", the 0x being optional
+pub fn parse_offset(s: &str) -> Result<isize, Box<dyn std::error::Error>> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let offset = isize::from_str_radix(digits.trim_start_matches("0x"), 16).map_err(|e| format!("Bad offset '{}': {}", s, e))?;
+    Ok(if negative { -offset } else { offset })
+}
+
+// An address in any of the forms a user types one: absolute ("0x7f31c2a0"), module-relative
+// ("libgame.so+0x2a10"), a pointer chain ("libgame.so+0x1a2b0->0x10") or arithmetic on them, as
+// AddressExpression parses them. Modules are looked up when this is called rather than when the
+// text was written, so a saved address still works after the target is restarted and loaded
+// somewhere else. There are no results or tags to name outside a session
+pub fn resolve_address(process: impl ProcessMemory, regions: &mut RegionCache, s: &str) -> Result<usize, Box<dyn std::error::Error>> {
+    s.parse::<AddressExpression>()?.evaluate(process, regions, &())
+}
+
+// Follows the chain to the address it ends at. Every address along the way, the last included,
+// must be in a mapped readable region, and no pointer read may be null; otherwise the error says
+// which hop went wrong, hop 0 being the base
+pub fn resolve_pointer_chain(process: impl ProcessMemory, regions: &mut RegionCache, chain: &PointerChain) -> Result<usize, Box<dyn std::error::Error>> {
+    let address = regions.resolve(&chain.base).map_err(|e| format!("hop 0 ({}): {}", chain.base, e))?;
+    follow_pointers(process, regions, address, &chain.offsets)
+}
+
+// The hops of a chain after its base, checked as resolve_pointer_chain checks them
+pub(crate) fn follow_pointers(process: impl ProcessMemory, regions: &mut RegionCache, mut address: usize, offsets: &[isize]) -> Result<usize, Box<dyn std::error::Error>> {
+    for (hop, offset) in offsets.iter().enumerate() {
+        address = read_pointer(&process, regions, address).map_err(|e| format!("hop {}: {}", hop, e))?.wrapping_add_signed(*offset);
+    }
+    if !regions.region_for_address(address).is_some_and(|x| x.readable) {
+        return Err(format!("hop {}: 0x{:x} is not in mapped readable memory", offsets.len(), address).into());
+    }
+    Ok(address)
+}
+
+// The pointer at the address, which has to be in mapped readable memory and not be null
+pub(crate) fn read_pointer(process: impl ProcessMemory, regions: &mut RegionCache, address: usize) -> Result<usize, Box<dyn std::error::Error>> {
+    if !regions.region_for_address(address).is_some_and(|x| x.readable) {
+        return Err(format!("0x{:x} is not in mapped readable memory", address).into());
+    }
+    let pointer = match process.pointer_width() {
+        4 => read_scalar::<u32>(&process, address, Endianness::Native)? as usize,
+        _ => read_scalar::<u64>(&process, address, Endianness::Native)? as usize,
+    };
+    if pointer == 0 {
+        return Err(format!("null pointer at 0x{:x}", address).into());
+    }
+    Ok(pointer)
+}
+
+pub fn read_chain<T: Scalar>(process: impl ProcessMemory, regions: &mut RegionCache, chain: &PointerChain, endianness: Endianness) -> Result<T, Box<dyn std::error::Error>> {
+    let address = resolve_pointer_chain(&process, regions, chain)?;
+    read_scalar(process, address, endianness)
+}
+
+pub fn write_chain<T: Scalar>(process: impl ProcessMemory, regions: &mut RegionCache, chain: &PointerChain, value: T, endianness: Endianness) -> Result<(), Box<dyn std::error::Error>> {
+    let address = resolve_pointer_chain(&process, regions, chain)?;
+    write_scalar(process, address, value, endianness)
+}
+
+// Which pointers find_pointers_to accepts. The offset is what is added to a pointer to reach the
+// target, as in a chain, so 0x10 is a pointer to an object whose field 0x10 in is the target, and
+// -0x8 one to a header just past it or to a later element of an array. Both ends are included
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PointerSearch {
+    pub min_offset: isize,
+    pub max_offset: isize,
+    // Only pointers stored at a multiple of the pointer width, as a compiler lays them out
+    pub aligned: bool,
+    // Only pointers stored inside a module, the executable's data and bss among them, whose
+    // addresses can be written module-relative and so still found after a restart
+    pub static_only: bool,
+}
+
+impl Default for PointerSearch {
+    fn default() -> PointerSearch {
+        PointerSearch { min_offset: 0, max_offset: DEFAULT_POINTER_OFFSET, aligned: false, static_only: false }
+    }
+}
+
+// A pointer near the target: where it is stored, what it points at and the offset from that to the
+// target, which is exact rather than rounded to the search's window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PointerHit {
+    pub location: usize,
+    pub pointer: usize,
+    pub offset: isize,
+}
+
+impl PointerHit {
+    // The one-hop chain that reads the pointer and adds the offset, module-relative where the
+    // pointer is stored in a module
+    pub fn chain(&self, modules: &[Module]) -> PointerChain {
+        PointerChain { base: SavedAddress::from_address(self.location, modules), offsets: vec![self.offset] }
+    }
+}
+
+// Every pointer-sized value in the regions the options select that points within the search's
+// window of the target, sorted by where it is stored. The options' alignment is overridden by the
+// pointer width for an aligned search
+pub fn find_pointers_to(process: impl ProcessMemory, target: usize, search: &PointerSearch, options: &ScanOptions) -> Result<(Vec<PointerHit>, ScanStats), Box<dyn std::error::Error>> {
+    if search.min_offset > search.max_offset {
+        return Err(format!("The lowest offset, {}, is above the highest, {}", search.min_offset, search.max_offset).into());
+    }
+    let modules = modules_from_regions(&process.memory_regions()?);
+    let width = process.pointer_width();
+    // Widened so that a window reaching past either end of the address space is cut off there
+    // rather than wrapping around. Null is never a pointer to anything
+    let low = (target as i128 - search.max_offset as i128).max(1);
+    let high = (target as i128 - search.min_offset as i128).min(if width == 4 { u32::MAX as i128 } else { u64::MAX as i128 });
+    let mut options = options.clone();
+    if search.aligned {
+        options.alignment = Some(width);
+    }
+    if search.static_only {
+        (options.include_heap, options.include_stack) = (false, false);
+    }
+    let endianness = options.endianness;
+    let value = move |bytes: &[u8]| match (width, endianness.is_native()) {
+        (4, true) => u32::from_bytes(bytes) as i128,
+        (4, false) => u32::from_bytes_in(bytes, endianness) as i128,
+        (_, true) => u64::from_bytes(bytes) as i128,
+        (_, false) => u64::from_bytes_in(bytes, endianness) as i128,
+    };
+    let (locations, stats) = scan_chunks(&process, &options, width, |data, offsets, found| {
+        for offset in offsets.iter() {
+            if (low..=high).contains(&value(&data[offset..offset + width])) {
+                found.push(offset);
+            }
+        }
+    })?;
+    // Read again for what each points at, dropping any that changed to point elsewhere since
+    let pointers = match width {
+        4 => read_many_in::<u32>(&process, &locations, endianness).into_iter().map(|x| x.map(|x| x as usize)).collect::<Vec<_>>(),
+        _ => read_many_in::<u64>(&process, &locations, endianness).into_iter().map(|x| x.map(|x| x as usize)).collect::<Vec<_>>(),
+    };
+    let hits = locations.into_iter().zip(pointers).filter_map(|(location, pointer)| {
+        let pointer = pointer.ok().filter(|x| (low..=high).contains(&(*x as i128)))?;
+        let hit = PointerHit { location, pointer, offset: (target as i128 - pointer as i128) as isize };
+        (!search.static_only || module_for_address(&modules, location).is_some()).then_some(hit)
+    }).collect::<Vec<PointerHit>>();
+    Ok((hits, stats))
+}
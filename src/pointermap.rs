@@ -0,0 +1,218 @@
+use std::{collections::HashSet, fs::File, io::{BufWriter, Read, Write}, path::Path};
+use crate::{capture::{CAPTURE_VERSION, CaptureHeader, header_fields, read_header_fields}, maps::{Module, module_for_address, modules_from_regions}, pointer::PointerChain, process::{ProcessMemory, read_many_in}, scan::{ScanOptions, ScanStats, scan_chunks}, session::SavedAddress, value::Scalar};
+
+// Every pointer in a target, built once so that a pointer chain scan looks pointers up instead of
+// scanning all of memory again for each level. Saved little-endian, laid out as a capture is:
+//     magic          8 bytes, "RMHPMAP\0"
+//     version        u16
+//     header length  u32, then the header fields of a capture, with every mapping recorded as one
+//                    that was not captured, so the modules the map was built in can be rebuilt
+//     count          u64, the number of pointers
+// followed by the pointers in blocks of POINTER_MAP_BLOCK, the last one shorter, each block written
+// as a u32 length and the lz4-compressed pairs of its pointers' location and target, both u64,
+// sorted by location
+
+pub const POINTER_MAP_MAGIC: [u8; 8] = *b"RMHPMAP\0";
+// Pointers per block, a MiB of them before compression
+pub const POINTER_MAP_BLOCK: usize = 1 << 16;
+
+// Chains are looked for this many pointers deep unless asked otherwise
+pub const DEFAULT_CHAIN_DEPTH: usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PointerMap {
+    // The layout the map was built in is the header's uncaptured mappings
+    pub header: CaptureHeader,
+    // Where each pointer is and what it points at, sorted by where it is
+    pub pointers: Vec<(usize, usize)>,
+}
+
+// How find_chains looks for chains: the offsets each hop may add, as for find_pointers_to, how
+// many pointers deep a chain may go, and how many chains to stop at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainSearch {
+    pub min_offset: isize,
+    pub max_offset: isize,
+    pub depth: usize,
+    pub limit: usize,
+}
+
+impl PointerMap {
+    // Every value stored at a multiple of the pointer width in the regions the options select that
+    // points into a readable mapping
+    pub fn build(process: impl ProcessMemory, options: &ScanOptions) -> Result<(PointerMap, ScanStats), Box<dyn std::error::Error>> {
+        let regions = process.memory_regions()?;
+        let mut header = CaptureHeader::of(&process)?;
+        let width = header.arch.pointer_width();
+        let mut readable = regions.iter().filter(|x| x.readable).map(|x| (x.start, x.end)).collect::<Vec<(usize, usize)>>();
+        readable.sort();
+        let endianness = options.endianness;
+        let value = move |bytes: &[u8]| match (width, endianness.is_native()) {
+            (4, true) => u32::from_bytes(bytes) as usize,
+            (4, false) => u32::from_bytes_in(bytes, endianness) as usize,
+            (_, true) => u64::from_bytes(bytes) as usize,
+            (_, false) => u64::from_bytes_in(bytes, endianness) as usize,
+        };
+        let mapped = |pointer: usize| {
+            let index = readable.partition_point(|x| x.1 <= pointer);
+            readable.get(index).is_some_and(|x| x.0 <= pointer)
+        };
+        let options = ScanOptions { alignment: Some(width), ..options.clone() };
+        let (locations, stats) = scan_chunks(&process, &options, width, |data, offsets, found| {
+            for offset in offsets.iter() {
+                if mapped(value(&data[offset..offset + width])) {
+                    found.push(offset);
+                }
+            }
+        })?;
+        let targets = match width {
+            4 => read_many_in::<u32>(&process, &locations, endianness).into_iter().map(|x| x.map(|x| x as usize)).collect::<Vec<_>>(),
+            _ => read_many_in::<u64>(&process, &locations, endianness).into_iter().map(|x| x.map(|x| x as usize)).collect::<Vec<_>>(),
+        };
+        let mut pointers = locations.into_iter().zip(targets).filter_map(|(location, target)| Some((location, target.ok().filter(|x| mapped(*x))?))).collect::<Vec<(usize, usize)>>();
+        pointers.sort_unstable();
+        header.uncaptured = regions;
+        Ok((PointerMap { header, pointers }, stats))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let mut file = BufWriter::new(File::create(path).map_err(|e| format!("Could not create {}: {}", path.display(), e))?);
+        let fields = header_fields(&self.header);
+        file.write_all(&POINTER_MAP_MAGIC)?;
+        file.write_all(&CAPTURE_VERSION.to_le_bytes())?;
+        file.write_all(&(fields.len() as u32).to_le_bytes())?;
+        file.write_all(&fields)?;
+        file.write_all(&(self.pointers.len() as u64).to_le_bytes())?;
+        for block in self.pointers.chunks(POINTER_MAP_BLOCK) {
+            let bytes = block.iter().flat_map(|x| [(x.0 as u64).to_le_bytes(), (x.1 as u64).to_le_bytes()]).flatten().collect::<Vec<u8>>();
+            let compressed = lz4_flex::compress(&bytes);
+            file.write_all(&(compressed.len() as u32).to_le_bytes())?;
+            file.write_all(&compressed)?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+
+    pub fn open(path: &Path) -> Result<PointerMap, Box<dyn std::error::Error>> {
+        let mut bytes = Vec::new();
+        File::open(path).map_err(|e| format!("Could not open {}: {}", path.display(), e))?.read_to_end(&mut bytes)?;
+        let corrupt = || format!("{} is truncated or corrupt", path.display());
+        if bytes.get(..8) != Some(&POINTER_MAP_MAGIC[..]) {
+            return Err(format!("{} is not a pointer map", path.display()).into());
+        }
+        let mut position = 8;
+        let mut take = |len: usize| -> Result<&[u8], String> {
+            let taken = bytes.get(position..position + len).ok_or_else(corrupt)?;
+            position += len;
+            Ok(taken)
+        };
+        let version = u16::from_le_bytes(take(2)?.try_into()?);
+        if version > CAPTURE_VERSION {
+            return Err(format!("{} is a version {} pointer map, but only versions up to {} can be read", path.display(), version, CAPTURE_VERSION).into());
+        }
+        let header_len = u32::from_le_bytes(take(4)?.try_into()?) as usize;
+        let header = read_header_fields(version, take(header_len)?)?;
+        let count = u64::from_le_bytes(take(8)?.try_into()?) as usize;
+        let mut pointers = Vec::with_capacity(count.min(bytes.len() / 16 + POINTER_MAP_BLOCK));
+        while pointers.len() < count {
+            let expected = (count - pointers.len()).min(POINTER_MAP_BLOCK);
+            let len = u32::from_le_bytes(take(4)?.try_into()?) as usize;
+            let block = lz4_flex::decompress(take(len)?, expected * 16).map_err(|_| corrupt())?;
+            if block.len() != expected * 16 {
+                return Err(corrupt().into());
+            }
+            pointers.extend(block.chunks_exact(16).map(|x| (u64::from_le_bytes(x[..8].try_into().unwrap()) as usize, u64::from_le_bytes(x[8..].try_into().unwrap()) as usize)));
+        }
+        if !pointers.is_sorted() {
+            return Err(corrupt().into());
+        }
+        Ok(PointerMap { header, pointers })
+    }
+
+    // The modules as they were laid out when the map was built
+    pub fn modules(&self) -> Vec<Module> {
+        modules_from_regions(&self.header.uncaptured)
+    }
+
+    // Moves every location, target and mapping inside one of the map's modules to where the module
+    // of the same path is loaded in `modules`, as for a map built in an earlier run of the target.
+    // Modules that are not loaded now, and everything outside modules, stay where they were
+    pub fn rebase(&mut self, modules: &[Module]) {
+        let old = self.modules();
+        let moves = old.iter().filter_map(|x| {
+            let now = modules.iter().find(|y| y.path == x.path)?;
+            Some((x.base, x.base + x.size, now.base as isize - x.base as isize))
+        }).collect::<Vec<(usize, usize, isize)>>();
+        let moved = |address: usize| match moves.iter().find(|x| x.0 <= address && address < x.1) {
+            Some(x) => address.wrapping_add_signed(x.2),
+            None => address,
+        };
+        for pointer in &mut self.pointers {
+            *pointer = (moved(pointer.0), moved(pointer.1));
+        }
+        self.pointers.sort_unstable();
+        for region in &mut self.header.uncaptured {
+            let start = moved(region.start);
+            (region.start, region.end) = (start, start + region.len());
+        }
+        self.header.uncaptured.sort_by_key(|x| x.start);
+    }
+
+    // What the pointer at the address points at, if the map has one there
+    pub fn pointer_at(&self, location: usize) -> Option<usize> {
+        self.pointers.binary_search_by_key(&location, |x| x.0).ok().map(|x| self.pointers[x].1)
+    }
+
+    // Where the chain ends going by the map's pointers, or None if it reads a location the map has
+    // no pointer at
+    pub fn follow(&self, chain: &PointerChain) -> Option<usize> {
+        let mut address = match &chain.base {
+            SavedAddress::Absolute(address) => *address,
+            SavedAddress::Module { name, offset } => self.modules().iter().find(|x| x.name == *name)?.base + offset,
+        };
+        for offset in &chain.offsets {
+            address = self.pointer_at(address)?.wrapping_add_signed(*offset);
+        }
+        Some(address)
+    }
+
+    // Chains from a pointer stored in a module to the target, working back from the target one
+    // pointer at a time: the pointers near it, then the pointers near where those are stored, and
+    // so on. The shortest chains come first. Each address is only worked back from once
+    pub fn find_chains(&self, target: usize, search: &ChainSearch) -> Vec<PointerChain> {
+        let modules = self.modules();
+        let mut by_target = self.pointers.iter().map(|x| (x.1, x.0)).collect::<Vec<(usize, usize)>>();
+        by_target.sort_unstable();
+        let mut chains = Vec::new();
+        let mut visited = HashSet::from([target]);
+        let mut level = vec![(target, Vec::new())];
+        for _ in 0..search.depth {
+            let mut next = Vec::new();
+            for (address, offsets) in &level {
+                let low = address.saturating_add_signed(-search.max_offset);
+                let high = address.saturating_add_signed(-search.min_offset);
+                let start = by_target.partition_point(|x| x.0 < low);
+                for (pointer, location) in by_target[start..].iter().take_while(|x| x.0 <= high) {
+                    let chain_offsets = [vec![*address as isize - *pointer as isize], offsets.clone()].concat();
+                    if module_for_address(&modules, *location).is_some() {
+                        chains.push(PointerChain { base: SavedAddress::from_address(*location, &modules), offsets: chain_offsets });
+                        if chains.len() >= search.limit {
+                            return chains;
+                        }
+                    }
+                    else if visited.insert(*location) {
+                        next.push((*location, chain_offsets));
+                    }
+                }
+            }
+            level = next;
+        }
+        chains
+    }
+}
+
+// Keeps the chains that, followed through the other map, end at the target there, as when the
+// maps were built before and after a restart of the target and the target moved with it
+pub fn intersect_chains(chains: Vec<PointerChain>, other: &PointerMap, target: usize) -> Vec<PointerChain> {
+    chains.into_iter().filter(|x| other.follow(x) == Some(target)).collect()
+}
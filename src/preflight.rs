@@ -0,0 +1,192 @@
+use std::{ffi::OsStr, fs::File, os::unix::fs::PermissionsExt, path::{Path, PathBuf}};
+use nix::{errno::Errno, sys::signal::kill, unistd::Pid};
+use crate::{maps::get_memory_regions, process::ProcessMemory, status::target_status};
+
+// Everything that decides whether a process can be attached to, checked in the order the kernel
+// gets to them, each with what to change when it fails, so that a refused attach says more than
+// EPERM
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreflightCheck {
+    pub name: &'static str,
+    pub passed: bool,
+    // What was found, e.g. "uid 1000, the same as this process"
+    pub detail: String,
+    // The command to run or the thing to change when it failed, if there is one
+    pub suggestion: Option<String>,
+}
+
+// The capability's bit in CapEff
+const CAP_SYS_PTRACE: u32 = 19;
+
+impl PreflightCheck {
+    fn pass(name: &'static str, detail: String) -> PreflightCheck {
+        PreflightCheck { name, passed: true, detail, suggestion: None }
+    }
+
+    fn fail(name: &'static str, detail: String, suggestion: Option<String>) -> PreflightCheck {
+        PreflightCheck { name, passed: false, detail, suggestion }
+    }
+}
+
+fn has_cap_sys_ptrace() -> bool {
+    let status = std::fs::read_to_string("/proc/self/status").unwrap_or_default();
+    let effective = status.lines().find_map(|x| x.strip_prefix("CapEff:")).and_then(|x| u64::from_str_radix(x.trim(), 16).ok());
+    effective.is_some_and(|x| x & (1 << CAP_SYS_PTRACE) != 0)
+}
+
+// Whether this process is the target's parent, or its parent's, and so on up to init
+fn is_ancestor_of(pid: Pid) -> bool {
+    let mut pid = pid.as_raw();
+    while pid > 1 {
+        let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).unwrap_or_default();
+        pid = status.lines().find_map(|x| x.strip_prefix("PPid:")).and_then(|x| x.trim().parse().ok()).unwrap_or(0);
+        if pid == Pid::this().as_raw() {
+            return true;
+        }
+    }
+    false
+}
+
+fn this_executable() -> String {
+    std::env::current_exe().map(|x| x.display().to_string()).unwrap_or_else(|_| "memory".to_string())
+}
+
+// The checks up to the first that leaves the rest meaningless, which is only the process not
+// existing at all
+pub fn preflight(pid: Pid) -> Vec<PreflightCheck> {
+    let mut checks = Vec::new();
+    // EPERM means it exists but cannot be signalled, which is all this asks
+    match kill(pid, None) {
+        Ok(()) | Err(Errno::EPERM) => (),
+        Err(e) => {
+            checks.push(PreflightCheck::fail("process", format!("no process {} ({})", pid, e), Some("check the pid with `pgrep -a <name>` or `ps -e`; it may have exited".to_string())));
+            return checks;
+        }
+    }
+    let status = target_status(pid);
+    checks.push(match &status {
+        Ok(status) => PreflightCheck::pass("process", format!("{} ({})", pid, status.name)),
+        Err(e) => PreflightCheck::fail("process", e.to_string(), Some(format!("/proc/{} could not be read; with /proc mounted hidepid, run as the target's user or root", pid))),
+    });
+    let capable = has_cap_sys_ptrace();
+    let executable = this_executable();
+    let setcap = format!("`sudo setcap cap_sys_ptrace=eip {}`", executable);
+    let scope = std::fs::read_to_string("/proc/sys/kernel/yama/ptrace_scope").ok().and_then(|x| x.trim().parse::<u8>().ok());
+    checks.push(match scope {
+        None => PreflightCheck::pass("ptrace_scope", "no Yama".to_string()),
+        Some(0) => PreflightCheck::pass("ptrace_scope", "0 (any process of the same user)".to_string()),
+        // A process can always read itself
+        Some(x) if pid == Pid::this() => PreflightCheck::pass("ptrace_scope", format!("{}, but this is the process itself", x)),
+        Some(1) if capable => PreflightCheck::pass("ptrace_scope", "1 (only descendants), but this process has CAP_SYS_PTRACE".to_string()),
+        Some(1) if is_ancestor_of(pid) => PreflightCheck::pass("ptrace_scope", "1 (only descendants), which the target is".to_string()),
+        Some(1) => PreflightCheck::fail("ptrace_scope", "1 (only descendants can be traced, and the target is not one)".to_string(), Some(format!("`sudo sysctl kernel.yama.ptrace_scope=0` until the next reboot, or {}, or start the target from this tool", setcap))),
+        Some(2) if capable => PreflightCheck::pass("ptrace_scope", "2 (only with CAP_SYS_PTRACE), which this process has".to_string()),
+        Some(2) => PreflightCheck::fail("ptrace_scope", "2 (only processes with CAP_SYS_PTRACE can trace)".to_string(), Some(format!("run with sudo, or {}, or `sudo sysctl kernel.yama.ptrace_scope=0`", setcap))),
+        Some(3) => PreflightCheck::fail("ptrace_scope", "3 (nothing can be traced, and it cannot be lowered until a reboot)".to_string(), Some("set kernel.yama.ptrace_scope to 0 or 1 in /etc/sysctl.d and reboot".to_string())),
+        Some(x) => PreflightCheck::pass("ptrace_scope", format!("{} (not one Yama knows)", x)),
+    });
+    let status = status.ok();
+    let own_uid = target_status(Pid::this()).map(|x| x.effective_uid).unwrap_or(0);
+    checks.push(match &status {
+        None => PreflightCheck::fail("permission", "the target's uid could not be read".to_string(), None),
+        Some(status) if status.uid == own_uid && status.effective_uid == own_uid => PreflightCheck::pass("permission", format!("uid {}, the same as this process", own_uid)),
+        Some(_) if capable => PreflightCheck::pass("permission", "another user's, but this process has CAP_SYS_PTRACE".to_string()),
+        Some(status) => PreflightCheck::fail("permission", format!("the target runs as uid {} (effective {}) and this process as uid {}", status.uid, status.effective_uid, own_uid), Some(format!("run as the same user, as with `sudo -u '#{}' {} {}`, or with sudo, or {}", status.effective_uid, executable, pid, setcap))),
+    });
+    checks.push(match status.as_ref().map(|x| x.dumpable) {
+        None => PreflightCheck::fail("dumpable", "the target's status could not be read".to_string(), None),
+        Some(Some(true)) => PreflightCheck::pass("dumpable", "yes".to_string()),
+        Some(None) => PreflightCheck::pass("dumpable", "unknown, as the target runs as root".to_string()),
+        Some(Some(false)) if capable => PreflightCheck::pass("dumpable", "no, but this process has CAP_SYS_PTRACE".to_string()),
+        Some(Some(false)) => PreflightCheck::fail("dumpable", "no, as after a setuid exec or prctl(PR_SET_DUMPABLE, 0), so only root can read its memory".to_string(), Some(format!("run with sudo, or {}", setcap))),
+    });
+    let earlier_failed = checks.iter().any(|x| !x.passed);
+    let refused = match earlier_failed {
+        true => "fix the checks that failed above".to_string(),
+        false => format!("something other than the checks above refuses this process the target's /proc files, as a sandbox or a security module can; run with sudo, or {}", setcap),
+    };
+    let first = get_memory_regions(pid).map(|x| x.into_iter().find(|x| x.readable));
+    checks.push(match first {
+        Err(e) => PreflightCheck::fail("process_vm_readv", format!("/proc/{}/maps could not be read ({})", pid, e), Some(refused)),
+        Ok(None) => PreflightCheck::fail("process_vm_readv", format!("/proc/{}/maps lists no readable region", pid), None),
+        Ok(Some(region)) => {
+            let mut page = vec![0u8; region.len().min(4096)];
+            match pid.read_at(region.start, &mut page) {
+                Ok(_) => PreflightCheck::pass("process_vm_readv", format!("read a page at 0x{:x}", region.start)),
+                Err(e) => {
+                    let suggestion = match e.downcast_ref::<Errno>() {
+                        _ if earlier_failed => "fix the checks that failed above".to_string(),
+                        Some(Errno::ENOSYS) => "this kernel has no process_vm_readv; `--backend procmem` reads /proc/<pid>/mem instead".to_string(),
+                        _ => "a seccomp profile or security module may be refusing it; try `--backend procmem` or `--seize`".to_string(),
+                    };
+                    PreflightCheck::fail("process_vm_readv", format!("reading a page at 0x{:x} failed ({})", region.start, e), Some(suggestion))
+                }
+            }
+        }
+    });
+    let path = format!("/proc/{}/mem", pid);
+    checks.push(match File::open(&path) {
+        Ok(_) => PreflightCheck::pass("/proc/<pid>/mem", format!("{} opens", path)),
+        Err(e) => PreflightCheck::fail("/proc/<pid>/mem", format!("{} does not open ({})", path, e), Some(match earlier_failed {
+            true => "fix the checks that failed above".to_string(),
+            false => "`--seize` opens it as the target's tracer, which some kernels require".to_string(),
+        })),
+    });
+    checks
+}
+
+// A way past failed checks that --fix-perms can take once it is told to, as the program to run and
+// its arguments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PermissionFix {
+    // The same command line again as root, through sudo or pkexec. pkexec starts in root's home,
+    // so it is run through sh to change back to the directory the tool was started in
+    Reexec { program: PathBuf, arguments: Vec<String> },
+    // setcap cap_sys_ptrace+ep on the executable, through sudo or pkexec. Only runs of it started
+    // after have the capability
+    Setcap { program: PathBuf, arguments: Vec<String> },
+}
+
+// The first executable file of that name in the directories of a PATH
+pub fn find_in_path(name: &str, path: Option<&OsStr>) -> Option<PathBuf> {
+    std::env::split_paths(path?).map(|x| x.join(name)).find(|x| x.metadata().is_ok_and(|x| x.is_file() && x.permissions().mode() & 0o111 != 0))
+}
+
+// What could get past the failed checks, for a process running as root or not, with sudo or
+// pkexec looked for on the PATH. Arguments are the command line after the executable, which is run
+// again without --fix-perms, so a fix that does not work cannot ask to be fixed again. An error
+// says why nothing here would help
+pub fn permission_fixes(checks: &[PreflightCheck], executable: &Path, arguments: &[String], directory: &Path, root: bool, path: Option<&OsStr>) -> Result<Vec<PermissionFix>, String> {
+    let failed = checks.iter().filter(|x| !x.passed).collect::<Vec<&PreflightCheck>>();
+    if failed.is_empty() {
+        return Ok(Vec::new());
+    }
+    if failed.iter().any(|x| x.name == "process") {
+        return Err("there is no process to attach to, which no permission changes".to_string());
+    }
+    if failed.iter().any(|x| x.name == "ptrace_scope" && x.detail.starts_with('3')) {
+        return Err("ptrace_scope 3 refuses even root until a reboot, so only lowering it in /etc/sysctl.d and rebooting helps".to_string());
+    }
+    if root {
+        return Err("this already runs as root, so neither sudo nor setcap would get further; the failed checks say what else refuses it".to_string());
+    }
+    let executable = executable.display().to_string();
+    let mut command = vec![executable.clone()];
+    command.extend(arguments.iter().filter(|x| *x != "--fix-perms").cloned());
+    let setcap = ["setcap".to_string(), "cap_sys_ptrace+ep".to_string(), executable];
+    if let Some(sudo) = find_in_path("sudo", path) {
+        return Ok(vec![
+            PermissionFix::Reexec { program: sudo.clone(), arguments: ["--".to_string()].into_iter().chain(command).collect() },
+            PermissionFix::Setcap { program: sudo, arguments: ["--".to_string()].into_iter().chain(setcap).collect() },
+        ]);
+    }
+    if let Some(pkexec) = find_in_path("pkexec", path) {
+        let shell = ["/bin/sh".to_string(), "-c".to_string(), "cd \"$0\" && exec \"$@\"".to_string(), directory.display().to_string()];
+        return Ok(vec![
+            PermissionFix::Reexec { program: pkexec.clone(), arguments: shell.into_iter().chain(command).collect() },
+            PermissionFix::Setcap { program: pkexec, arguments: setcap.to_vec() },
+        ]);
+    }
+    Err("neither sudo nor pkexec is on the PATH, so run it as root some other way".to_string())
+}
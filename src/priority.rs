@@ -0,0 +1,125 @@
+use std::sync::{Mutex, OnceLock};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+// Scans, snapshots and unknown scan narrows at a lower priority than normal run on a pool of their
+// own, whose threads lower themselves as they start, so the scheduler favours the target over the
+// scanner. Nothing else runs there: locks, watches and monitors keep their own threads and timing.
+// Where the platform or the process's limits do not allow the priority asked for, the threads try
+// nice 10 instead, and failing that run at normal priority, with the reason kept for
+// priority_error
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScanPriority {
+    #[default]
+    Normal,
+    // Nice 10, on Windows below normal
+    Nice,
+    // SCHED_BATCH, which the scheduler treats as CPU-bound and so preempts less for it
+    Batch,
+    // SCHED_IDLE, only run when nothing else wants the CPU. On Windows the idle thread priority
+    Idle,
+}
+
+impl std::fmt::Display for ScanPriority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScanPriority::Normal => write!(f, "normal"),
+            ScanPriority::Nice => write!(f, "nice"),
+            ScanPriority::Batch => write!(f, "batch"),
+            ScanPriority::Idle => write!(f, "idle"),
+        }
+    }
+}
+
+impl std::str::FromStr for ScanPriority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ScanPriority, String> {
+        match s {
+            "normal" => Ok(ScanPriority::Normal),
+            "nice" => Ok(ScanPriority::Nice),
+            "batch" => Ok(ScanPriority::Batch),
+            "idle" => Ok(ScanPriority::Idle),
+            _ => Err(format!("Unknown priority '{}', expected normal, nice, batch or idle", s)),
+        }
+    }
+}
+
+// One pool for each priority below normal, built the first time a scan asks for it
+static POOLS: [OnceLock<Option<ThreadPool>>; 3] = [OnceLock::new(), OnceLock::new(), OnceLock::new()];
+static ERROR: Mutex<Option<String>> = Mutex::new(None);
+
+// Why a scan pool thread could not run at the priority it was given, if one could not since the
+// last call
+pub fn priority_error() -> Option<String> {
+    ERROR.lock().unwrap().take()
+}
+
+// Runs the work, and any parallel iterators in it, on the pool for the priority
+pub(crate) fn at_priority<R: Send>(priority: ScanPriority, work: impl FnOnce() -> R + Send) -> R {
+    let index = match priority {
+        ScanPriority::Normal => return work(),
+        ScanPriority::Nice => 0,
+        ScanPriority::Batch => 1,
+        ScanPriority::Idle => 2,
+    };
+    let pool = POOLS[index].get_or_init(|| {
+        let pool = ThreadPoolBuilder::new().thread_name(move |x| format!("scan-{}-{}", priority, x)).start_handler(move |_| {
+            if let Err(e) = lower_current_thread(priority) {
+                *ERROR.lock().unwrap() = Some(e);
+            }
+        }).build();
+        pool.map_err(|e| *ERROR.lock().unwrap() = Some(format!("Could not start the scan threads: {}", e))).ok()
+    });
+    match pool {
+        Some(pool) => pool.install(work),
+        None => work(),
+    }
+}
+
+// The priority asked for, or the gentlest after it that the thread is allowed
+#[cfg(target_os = "linux")]
+fn lower_current_thread(priority: ScanPriority) -> Result<(), String> {
+    let param = libc::sched_param { sched_priority: 0 };
+    let policies = match priority {
+        ScanPriority::Idle => &[libc::SCHED_IDLE, libc::SCHED_BATCH][..],
+        ScanPriority::Batch => &[libc::SCHED_BATCH][..],
+        _ => &[][..],
+    };
+    // A pid of 0 is the calling thread, not the whole process
+    if policies.iter().any(|x| unsafe { libc::sched_setscheduler(0, *x, &param) } == 0) {
+        return Ok(());
+    }
+    match unsafe { libc::setpriority(libc::PRIO_PROCESS, libc::gettid() as libc::id_t, 10) } {
+        0 => Ok(()),
+        _ => Err(format!("Could not lower the scan threads to {}: {}", priority, std::io::Error::last_os_error())),
+    }
+}
+
+// Threads can only be niced one at a time as a background thread, which covers every priority
+#[cfg(target_os = "macos")]
+fn lower_current_thread(priority: ScanPriority) -> Result<(), String> {
+    match unsafe { libc::setpriority(libc::PRIO_DARWIN_THREAD, 0, libc::PRIO_DARWIN_BG) } {
+        0 => Ok(()),
+        _ => Err(format!("Could not lower the scan threads to {}: {}", priority, std::io::Error::last_os_error())),
+    }
+}
+
+#[cfg(windows)]
+fn lower_current_thread(priority: ScanPriority) -> Result<(), String> {
+    use windows_sys::Win32::System::Threading::{GetCurrentThread, SetThreadPriority, THREAD_PRIORITY_BELOW_NORMAL, THREAD_PRIORITY_IDLE, THREAD_PRIORITY_LOWEST};
+    let level = match priority {
+        ScanPriority::Idle => THREAD_PRIORITY_IDLE,
+        ScanPriority::Batch => THREAD_PRIORITY_LOWEST,
+        _ => THREAD_PRIORITY_BELOW_NORMAL,
+    };
+    match unsafe { SetThreadPriority(GetCurrentThread(), level) } {
+        0 => Err(format!("Could not lower the scan threads to {}: {}", priority, std::io::Error::last_os_error())),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn lower_current_thread(priority: ScanPriority) -> Result<(), String> {
+    Err(format!("Scan threads cannot be lowered to {} on this platform", priority))
+}
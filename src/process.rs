@@ -0,0 +1,860 @@
+use std::{ffi::CString, str::FromStr};
+#[cfg(target_os = "linux")]
+use std::{fs::{File, OpenOptions}, io::{IoSlice, IoSliceMut}, path::Path, sync::Arc};
+#[cfg(target_os = "linux")]
+use nix::sys::uio::{process_vm_readv, RemoteIoVec, process_vm_writev};
+use zerocopy::{FromBytes, Immutable, IntoBytes};
+use crate::{elf::{Arch, ELF_IDENT_LEN}, filter::thread_stacks, maps::{MemoryRegion, RegionCache, check_writable, executable_path, get_memory_regions, modules_from_regions}, platform::{Errno, Pid}, resident::{clear_soft_dirty, dirty_ranges}, retry::Retry, value::{Encoding, Endianness, Scalar, StringWriteOptions, TypedValue, encode_string}};
+#[cfg(target_os = "linux")]
+use crate::{offline::OfflineCapture, platform::FileExt, tracer::PtraceSession};
+#[cfg(windows)]
+pub use crate::windows::Process;
+#[cfg(target_os = "macos")]
+pub use crate::macos::Process;
+
+// Large writes are split into calls of this size, so that progress up to a failure is known
+pub const WRITE_CHUNK_SIZE: usize = 1 << 20;
+
+// The most iovecs process_vm_readv takes in one call
+#[cfg(target_os = "linux")]
+const IOV_MAX: usize = 1024;
+
+// NUL-terminated strings are read in pieces of this size, since most are far shorter than their cap
+const CSTRING_CHUNK_SIZE: usize = 256;
+// Most NUL bytes after a string's terminator counted as room a replacement can grow into
+const STRING_SPARE_LIMIT: usize = 256;
+
+// Anything memory can be read from and written to. Every read, write and scan path goes through
+// this, so the same code works whichever mechanism is used to reach the target
+pub trait ProcessMemory: Sync + Send {
+    fn pid(&self) -> Pid;
+
+    // Both return the number of bytes actually transferred, which may be short of the request
+    fn read_at(&self, address: usize, buffer: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>>;
+    fn write_at(&self, address: usize, data: &[u8]) -> Result<usize, Box<dyn std::error::Error>>;
+
+    fn memory_regions(&self) -> Result<Vec<MemoryRegion>, Box<dyn std::error::Error>> {
+        get_memory_regions(self.pid())
+    }
+
+    // The readable regions, which are all a scan can look at
+    fn memory_ranges(&self) -> Result<Vec<(usize, usize)>, Box<dyn std::error::Error>> {
+        Ok(self.memory_regions()?.iter().filter(|x| x.readable).map(|x| (x.start, x.end)).collect())
+    }
+
+    // None where it cannot be found out
+    fn executable_path(&self) -> Option<String> {
+        executable_path(self.pid()).ok()
+    }
+
+    // Each thread with an address inside its stack, where known
+    fn thread_stacks(&self) -> Vec<(Pid, usize)> {
+        thread_stacks(self.pid())
+    }
+
+    // Whether every word read costs a system call, which makes scanning everything take a long time
+    fn is_slow(&self) -> bool {
+        false
+    }
+
+    // What the target runs on, going by the ELF header its main executable was loaded with.
+    // Targets whose header cannot be read are assumed to match this process
+    fn arch(&self) -> Arch {
+        loaded_arch(self).unwrap_or_else(Arch::native)
+    }
+
+    // 4 for a 32-bit target and 8 for a 64-bit one
+    fn pointer_width(&self) -> usize {
+        self.arch().pointer_width()
+    }
+
+    // Reads each (address, len) request into the next len bytes of the buffer, which must be as
+    // long as all the requests together. Every request succeeds or fails on its own, and only
+    // succeeds if all of its bytes were read. Backends that can gather reads override this
+    fn read_many_into(&self, requests: &[(usize, usize)], buffer: &mut [u8]) -> Vec<Result<(), Box<dyn std::error::Error>>> {
+        read_each(self, requests, buffer)
+    }
+
+    // Writes each (address, data) pair, with the same per-entry results as read_many_into.
+    // Backends that can scatter writes override this
+    fn write_many_at(&self, writes: &[(usize, &[u8])]) -> Vec<Result<(), Box<dyn std::error::Error>>> {
+        write_each(self, writes)
+    }
+
+    // Starts over tracking which pages the target writes, see resident.rs. Errors where they
+    // cannot be tracked, which leaves whoever asked to read everything
+    fn clear_soft_dirty(&self) -> Result<(), Box<dyn std::error::Error>> {
+        clear_soft_dirty(self.pid())
+    }
+
+    // The parts of the ranges on pages written since clear_soft_dirty
+    fn dirty_ranges(&self, ranges: &[(usize, usize)]) -> Result<Vec<(usize, usize)>, Box<dyn std::error::Error>> {
+        dirty_ranges(self.pid(), ranges, &self.memory_regions()?)
+    }
+}
+
+impl<P: ProcessMemory + ?Sized> ProcessMemory for &P {
+    fn pid(&self) -> Pid {
+        (**self).pid()
+    }
+
+    fn read_at(&self, address: usize, buffer: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        (**self).read_at(address, buffer)
+    }
+
+    fn write_at(&self, address: usize, data: &[u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        (**self).write_at(address, data)
+    }
+
+    fn memory_regions(&self) -> Result<Vec<MemoryRegion>, Box<dyn std::error::Error>> {
+        (**self).memory_regions()
+    }
+
+    fn memory_ranges(&self) -> Result<Vec<(usize, usize)>, Box<dyn std::error::Error>> {
+        (**self).memory_ranges()
+    }
+
+    fn executable_path(&self) -> Option<String> {
+        (**self).executable_path()
+    }
+
+    fn thread_stacks(&self) -> Vec<(Pid, usize)> {
+        (**self).thread_stacks()
+    }
+
+    fn is_slow(&self) -> bool {
+        (**self).is_slow()
+    }
+
+    fn arch(&self) -> Arch {
+        (**self).arch()
+    }
+
+    fn read_many_into(&self, requests: &[(usize, usize)], buffer: &mut [u8]) -> Vec<Result<(), Box<dyn std::error::Error>>> {
+        (**self).read_many_into(requests, buffer)
+    }
+
+    fn write_many_at(&self, writes: &[(usize, &[u8])]) -> Vec<Result<(), Box<dyn std::error::Error>>> {
+        (**self).write_many_at(writes)
+    }
+
+    fn clear_soft_dirty(&self) -> Result<(), Box<dyn std::error::Error>> {
+        (**self).clear_soft_dirty()
+    }
+
+    fn dirty_ranges(&self, ranges: &[(usize, usize)]) -> Result<Vec<(usize, usize)>, Box<dyn std::error::Error>> {
+        (**self).dirty_ranges(ranges)
+    }
+}
+
+// A bare pid always uses process_vm_readv/process_vm_writev
+#[cfg(target_os = "linux")]
+impl ProcessMemory for Pid {
+    fn pid(&self) -> Pid {
+        *self
+    }
+
+    fn read_at(&self, address: usize, buffer: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        let len = buffer.len();
+        let local_binding = IoSliceMut::new(buffer);
+        let remote_binding = RemoteIoVec{ base: address, len };
+        Ok(process_vm_readv(*self, &mut [local_binding], &[remote_binding])?)
+    }
+
+    fn write_at(&self, address: usize, data: &[u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        let local_binding = IoSlice::new(data);
+        let remote_binding = RemoteIoVec{ base: address, len: data.len() };
+        Ok(process_vm_writev(*self, &[local_binding], &[remote_binding])?)
+    }
+
+    // Up to IOV_MAX requests per process_vm_readv. A fault stops the transfer partway through a
+    // batch, so the request it stopped in is read again on its own to find out whether it failed,
+    // and the batch carries on from the one after it
+    fn read_many_into(&self, requests: &[(usize, usize)], buffer: &mut [u8]) -> Vec<Result<(), Box<dyn std::error::Error>>> {
+        let mut pieces: Vec<&mut [u8]> = Vec::with_capacity(requests.len());
+        let mut rest = buffer;
+        for request in requests {
+            let (piece, remainder) = std::mem::take(&mut rest).split_at_mut(request.1);
+            pieces.push(piece);
+            rest = remainder;
+        }
+        let mut results = Vec::with_capacity(requests.len());
+        let mut next = 0;
+        while next < requests.len() {
+            let batch_end = (next + IOV_MAX).min(requests.len());
+            let read = {
+                let remote = requests[next..batch_end].iter().map(|x| RemoteIoVec { base: x.0, len: x.1 }).collect::<Vec<RemoteIoVec>>();
+                let mut local = pieces[next..batch_end].iter_mut().map(|x| IoSliceMut::new(x)).collect::<Vec<IoSliceMut>>();
+                process_vm_readv(*self, &mut local, &remote).unwrap_or(0)
+            };
+            let mut covered = 0;
+            while next < batch_end && covered + requests[next].1 <= read {
+                covered += requests[next].1;
+                results.push(Ok(()));
+                next += 1;
+            }
+            if next < batch_end {
+                results.push(read_exact(self, requests[next].0, pieces[next]));
+                next += 1;
+            }
+        }
+        results
+    }
+
+    // Up to IOV_MAX writes per process_vm_writev, recovering from a fault the same way as
+    // read_many_into
+    fn write_many_at(&self, writes: &[(usize, &[u8])]) -> Vec<Result<(), Box<dyn std::error::Error>>> {
+        let mut results = Vec::with_capacity(writes.len());
+        let mut next = 0;
+        while next < writes.len() {
+            let batch_end = (next + IOV_MAX).min(writes.len());
+            let remote = writes[next..batch_end].iter().map(|x| RemoteIoVec { base: x.0, len: x.1.len() }).collect::<Vec<RemoteIoVec>>();
+            let local = writes[next..batch_end].iter().map(|x| IoSlice::new(x.1)).collect::<Vec<IoSlice>>();
+            let written = process_vm_writev(*self, &local, &remote).unwrap_or(0);
+            let mut covered = 0;
+            while next < batch_end && covered + writes[next].1.len() <= written {
+                covered += writes[next].1.len();
+                results.push(Ok(()));
+                next += 1;
+            }
+            if next < batch_end {
+                results.push(write_exact(self, writes[next].0, writes[next].1));
+                next += 1;
+            }
+        }
+        results
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MemBackend {
+    ProcessVmReadv,
+    ProcMem,
+    // A capture written by dump all, see Process::offline
+    Offline,
+    // ReadProcessMemory and WriteProcessMemory, the only backend on Windows
+    Win32,
+    // mach_vm_read_overwrite and mach_vm_write on the task port, the only backend on macOS
+    Mach,
+    // PTRACE_PEEKDATA and PTRACE_POKEDATA a word at a time as the target's tracer, for when
+    // neither of the others is let in. Far slower than either
+    Ptrace,
+}
+
+impl FromStr for MemBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "process_vm" | "process_vm_readv" => Ok(MemBackend::ProcessVmReadv),
+            "procmem" | "proc_mem" => Ok(MemBackend::ProcMem),
+            "ptrace" | "peekdata" => Ok(MemBackend::Ptrace),
+            "win32" => Ok(MemBackend::Win32),
+            "mach" => Ok(MemBackend::Mach),
+            _ => Err(format!("Unknown backend '{}', expected process_vm, procmem, ptrace, win32 or mach", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for MemBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MemBackend::ProcessVmReadv => write!(f, "process_vm"),
+            MemBackend::ProcMem => write!(f, "procmem"),
+            MemBackend::Offline => write!(f, "offline"),
+            MemBackend::Win32 => write!(f, "win32"),
+            MemBackend::Mach => write!(f, "mach"),
+            MemBackend::Ptrace => write!(f, "ptrace"),
+        }
+    }
+}
+
+impl MemBackend {
+    // The error for asking for a backend that the platform being run on does not have
+    pub(crate) fn unavailable(self) -> Box<dyn std::error::Error> {
+        let platform = match self {
+            MemBackend::ProcessVmReadv | MemBackend::ProcMem | MemBackend::Ptrace => "Linux",
+            MemBackend::Win32 => "Windows",
+            MemBackend::Mach => "macOS",
+            MemBackend::Offline => return "An offline capture is opened with Process::offline".into(),
+        };
+        format!("The {} backend is only available on {}", self, platform).into()
+    }
+}
+
+// The kept-open /proc/<pid>/mem file, shared between clones of a Process
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+struct ProcMemFile {
+    file: File,
+    // Held when the file could only be opened as the target's tracer
+    tracer: Option<PtraceSession>,
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone)]
+pub struct Process {
+    pid: Pid,
+    backend: MemBackend,
+    mem: Option<Arc<ProcMemFile>>,
+    // The session peeking and poking for the ptrace backend
+    peek: Option<PtraceSession>,
+    offline: Option<Arc<OfflineCapture>>,
+    // Read from /proc/<pid>/exe when attaching, or from the capture's header
+    arch: Arch,
+}
+
+#[cfg(target_os = "linux")]
+impl Process {
+    // Uses process_vm_readv when it works for this target, falling back to /proc/<pid>/mem. If
+    // both were refused permission, as opposed to the process being gone, it tries seizing the
+    // target with ptrace and then /proc/<pid>/mem as its tracer. Unless the process is gone, the
+    // last resort is peeking and poking it with ptrace, which some seccomp profiles and old
+    // kernels leave as the only way in
+    pub fn attach(pid: Pid) -> Result<Process, Box<dyn std::error::Error>> {
+        let process = Process::new(pid, MemBackend::ProcessVmReadv, None, None, None);
+        let vm_error = match probe(&process) {
+            Ok(()) => return Ok(process),
+            Err(e) => e,
+        };
+        let mem_error = match Process::with_backend(pid, MemBackend::ProcMem) {
+            Ok(process) => return Ok(process),
+            Err(e) => e,
+        };
+        if matches!(vm_error.downcast_ref::<Errno>(), Some(Errno::ESRCH)) {
+            return Err(format!("Could not access process {}: process_vm_readv failed ({}) and /proc/{}/mem failed ({})", pid, vm_error, pid, mem_error).into());
+        }
+        let mut failures = format!("process_vm_readv failed ({}), /proc/{}/mem failed ({})", vm_error, pid, mem_error);
+        if is_permission_error(vm_error.as_ref()) {
+            match Process::seize(pid) {
+                Ok(process) => return Ok(process),
+                Err(e) => failures += &format!(", reading it as its tracer failed ({})", e),
+            }
+        }
+        Process::with_backend(pid, MemBackend::Ptrace).map_err(|peek_error| {
+            format!("Could not access process {}: {} and so did PTRACE_PEEKDATA ({})", pid, failures, peek_error).into()
+        })
+    }
+
+    // The calling process itself, through process_vm_readv, which needs no ptrace permission when
+    // the target is oneself. For smoke tests where attaching to anything else is refused
+    pub fn current() -> Result<Process, Box<dyn std::error::Error>> {
+        let process = Process::with_backend(Pid::this(), MemBackend::ProcessVmReadv)?;
+        probe(&process)?;
+        Ok(process)
+    }
+
+    // Reads and writes /proc/<pid>/mem as the target's tracer, having seized it with ptrace. The
+    // target keeps running, and is detached from once the last clone of the Process is dropped
+    pub fn seize(pid: Pid) -> Result<Process, Box<dyn std::error::Error>> {
+        let tracer = PtraceSession::seize(pid)?;
+        let path = format!("/proc/{}/mem", pid);
+        let file = OpenOptions::new().read(true).write(true).open(&path).or_else(|_| File::open(&path))?;
+        let process = Process::new(pid, MemBackend::ProcMem, Some(Arc::new(ProcMemFile { file, tracer: Some(tracer) })), None, None);
+        probe(&process)?;
+        Ok(process)
+    }
+
+    pub fn with_backend(pid: Pid, backend: MemBackend) -> Result<Process, Box<dyn std::error::Error>> {
+        match backend {
+            MemBackend::ProcessVmReadv => Ok(Process::new(pid, backend, None, None, None)),
+            MemBackend::ProcMem => {
+                let path = format!("/proc/{}/mem", pid);
+                let file = OpenOptions::new().read(true).write(true).open(&path).or_else(|_| File::open(&path))?;
+                let process = Process::new(pid, backend, Some(Arc::new(ProcMemFile { file, tracer: None })), None, None);
+                // Kernels before 2.6.39 only allow reading it from the tracer of a stopped target,
+                // which the session is there, having had to attach
+                match probe(&process) {
+                    Ok(()) => Ok(process),
+                    Err(_) => Process::seize(pid),
+                }
+            }
+            // Seized rather than attached, so the target only stops while words are moved
+            MemBackend::Ptrace => {
+                let process = Process::new(pid, backend, None, Some(PtraceSession::seize(pid)?), None);
+                probe(&process)?;
+                Ok(process)
+            }
+            _ => Err(backend.unavailable()),
+        }
+    }
+
+    // Reads a capture written by dump all, or an older dump directory, instead of a live process
+    pub fn offline(path: &Path) -> Result<Process, Box<dyn std::error::Error>> {
+        let capture = OfflineCapture::open(path)?;
+        Ok(Process::new(capture.pid(), MemBackend::Offline, None, None, Some(Arc::new(capture))))
+    }
+
+    // With the target's architecture found out once, here. The executable is read through
+    // /proc/<pid>/exe, which works even once the file is deleted or out of this mount namespace,
+    // and failing that the header loaded in its memory
+    fn new(pid: Pid, backend: MemBackend, mem: Option<Arc<ProcMemFile>>, peek: Option<PtraceSession>, offline: Option<Arc<OfflineCapture>>) -> Process {
+        let mut process = Process { pid, backend, mem, peek, offline, arch: Arch::native() };
+        process.arch = match &process.offline {
+            Some(offline) => offline.arch(),
+            None => Arch::read(&format!("/proc/{}/exe", pid)).ok().or_else(|| loaded_arch(&process)).unwrap_or_else(Arch::native),
+        };
+        process
+    }
+
+    pub fn backend(&self) -> MemBackend {
+        self.backend
+    }
+
+    // Whether the target is being read as its tracer after seizing it, through /proc/<pid>/mem or
+    // by peeking
+    pub fn is_seized(&self) -> bool {
+        self.mem.as_ref().is_some_and(|x| x.tracer.is_some()) || self.peek.is_some()
+    }
+
+    // The ptrace session on the target, which is the one the Process holds if it was seized, so
+    // that everything tracing it shares a single tracer
+    pub fn ptrace_session(&self) -> Result<PtraceSession, Box<dyn std::error::Error>> {
+        if self.offline.is_some() {
+            return Err("An offline capture has no process to trace".into());
+        }
+        match self.mem.as_ref().and_then(|x| x.tracer.clone()).or_else(|| self.peek.clone()) {
+            Some(session) => Ok(session),
+            None => PtraceSession::seize(self.pid),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn is_permission_error(error: &(dyn std::error::Error + 'static)) -> bool {
+    matches!(error.downcast_ref::<Errno>(), Some(Errno::EPERM | Errno::EACCES))
+}
+
+// Reads a few bytes from the first readable region to find out whether the backend works at all
+#[cfg(target_os = "linux")]
+fn probe(process: &Process) -> Result<(), Box<dyn std::error::Error>> {
+    let ranges = process.memory_ranges()?;
+    let first = ranges.first().ok_or("Expected the process to have at least one readable region")?;
+    let mut buffer = [0u8; 8];
+    process.read_at(first.0, &mut buffer)?;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+impl ProcessMemory for Process {
+    fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    fn read_at(&self, address: usize, buffer: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        match (&self.mem, &self.peek, &self.offline) {
+            (_, _, Some(offline)) => offline.read_at(address, buffer),
+            (_, Some(tracer), None) => Ok(tracer.read(address, buffer)?),
+            (Some(mem), None, None) => Ok(mem.file.read_at(buffer, address as u64).map_err(|e| Errno::from_raw(e.raw_os_error().unwrap_or(0)))?),
+            (None, None, None) => self.pid.read_at(address, buffer),
+        }
+    }
+
+    fn write_at(&self, address: usize, data: &[u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        match (&self.mem, &self.peek, &self.offline) {
+            (_, _, Some(offline)) => offline.write_at(address, data),
+            (_, Some(tracer), None) => Ok(tracer.write(address, data)?),
+            (Some(mem), None, None) => Ok(mem.file.write_at(data, address as u64).map_err(|e| Errno::from_raw(e.raw_os_error().unwrap_or(0)))?),
+            (None, None, None) => self.pid.write_at(address, data),
+        }
+    }
+
+    fn memory_regions(&self) -> Result<Vec<MemoryRegion>, Box<dyn std::error::Error>> {
+        match &self.offline {
+            Some(offline) => offline.memory_regions(),
+            None => get_memory_regions(self.pid),
+        }
+    }
+
+    fn executable_path(&self) -> Option<String> {
+        match &self.offline {
+            Some(offline) => offline.executable_path(),
+            None => executable_path(self.pid).ok(),
+        }
+    }
+
+    fn thread_stacks(&self) -> Vec<(Pid, usize)> {
+        match &self.offline {
+            Some(offline) => offline.thread_stacks(),
+            None => thread_stacks(self.pid),
+        }
+    }
+
+    fn is_slow(&self) -> bool {
+        self.backend == MemBackend::Ptrace
+    }
+
+    fn arch(&self) -> Arch {
+        self.arch
+    }
+
+    fn read_many_into(&self, requests: &[(usize, usize)], buffer: &mut [u8]) -> Vec<Result<(), Box<dyn std::error::Error>>> {
+        match (&self.mem, &self.peek, &self.offline) {
+            (None, None, None) => self.pid.read_many_into(requests, buffer),
+            _ => read_each(self, requests, buffer),
+        }
+    }
+
+    fn write_many_at(&self, writes: &[(usize, &[u8])]) -> Vec<Result<(), Box<dyn std::error::Error>>> {
+        match (&self.mem, &self.peek, &self.offline) {
+            (None, None, None) => self.pid.write_many_at(writes),
+            _ => write_each(self, writes),
+        }
+    }
+
+    fn clear_soft_dirty(&self) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.offline {
+            Some(offline) => offline.clear_soft_dirty(),
+            None => clear_soft_dirty(self.pid),
+        }
+    }
+
+    fn dirty_ranges(&self, ranges: &[(usize, usize)]) -> Result<Vec<(usize, usize)>, Box<dyn std::error::Error>> {
+        match &self.offline {
+            Some(offline) => offline.dirty_ranges(ranges),
+            None => dirty_ranges(self.pid, ranges, &get_memory_regions(self.pid)?),
+        }
+    }
+}
+
+// From the ELF header at the start of the main executable's first mapping, if it has one
+pub(crate) fn loaded_arch(process: impl ProcessMemory) -> Option<Arch> {
+    let executable = process.executable_path()?;
+    let modules = modules_from_regions(&process.memory_regions().ok()?);
+    let main = modules.iter().find(|x| x.path == executable)?;
+    let mut header = [0u8; ELF_IDENT_LEN];
+    read_exact(&process, main.base, &mut header).ok()?;
+    Arch::from_elf_header(&header).ok()
+}
+
+// Fails on a short read as well as on an error
+fn read_exact(process: impl ProcessMemory, address: usize, buffer: &mut [u8]) -> Result<(), Box<dyn std::error::Error>> {
+    let read = process.read_at(address, buffer)?;
+    if read < buffer.len() {
+        return Err(format!("Short read at 0x{:x}: got {} of {} bytes", address, read, buffer.len()).into());
+    }
+    Ok(())
+}
+
+// One read per request, for backends that cannot gather them
+fn read_each(process: impl ProcessMemory, requests: &[(usize, usize)], buffer: &mut [u8]) -> Vec<Result<(), Box<dyn std::error::Error>>> {
+    let mut offset = 0;
+    requests.iter().map(|&(address, len)| {
+        offset += len;
+        read_exact(&process, address, &mut buffer[offset - len..offset])
+    }).collect()
+}
+
+fn write_exact(process: impl ProcessMemory, address: usize, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    let written = process.write_at(address, data)?;
+    if written < data.len() {
+        return Err(format!("Short write at 0x{:x}: wrote {} of {} bytes", address, written, data.len()).into());
+    }
+    Ok(())
+}
+
+fn write_each(process: impl ProcessMemory, writes: &[(usize, &[u8])]) -> Vec<Result<(), Box<dyn std::error::Error>>> {
+    writes.iter().map(|&(address, data)| write_exact(&process, address, data)).collect()
+}
+
+// Writes every (address, data) pair with as few system calls as the backend allows, returning a
+// result for each in the same order. One failing write does not stop the others, and a write that
+// failed because it lands in a mapped but read-only region fails with EACCES, so the cause is clear
+pub fn write_many(process: impl ProcessMemory, writes: &[(usize, &[u8])]) -> Vec<Result<(), Box<dyn std::error::Error>>> {
+    let mut results = process.write_many_at(writes);
+    explain_failed_writes(&process, writes, &mut results, |_| true);
+    results
+}
+
+// Makes each failed write the filter picks that lands in a mapped but read-only region fail with
+// EACCES. The maps are only read if one of them failed
+pub(crate) fn explain_failed_writes(process: impl ProcessMemory, writes: &[(usize, &[u8])], results: &mut [Result<(), Box<dyn std::error::Error>>], filter: impl Fn(usize) -> bool) {
+    let mut regions: Option<Vec<MemoryRegion>> = None;
+    for (index, (result, &(address, data))) in results.iter_mut().zip(writes).enumerate() {
+        if result.is_err() && filter(index) {
+            let regions = regions.get_or_insert_with(|| process.memory_regions().unwrap_or_default());
+            let end = address + data.len();
+            if regions.iter().any(|x| !x.writable && x.start < end && address < x.end) {
+                *result = Err(Box::new(Errno::EACCES));
+            }
+        }
+    }
+}
+
+// Reads all the (address, len) requests, with as few system calls as the backend allows. The
+// results are in the same order as the requests, and a request that cannot be read fails alone
+pub fn read_many_bytes(process: impl ProcessMemory, requests: &[(usize, usize)]) -> Vec<Result<Vec<u8>, Box<dyn std::error::Error>>> {
+    let mut buffer = vec![0u8; requests.iter().map(|x| x.1).sum()];
+    let mut offset = 0;
+    process.read_many_into(requests, &mut buffer).into_iter().zip(requests).map(|(result, request)| {
+        offset += request.1;
+        result.map(|_| buffer[offset - request.1..offset].to_vec())
+    }).collect()
+}
+
+// A value from each address, like read_many_bytes
+pub fn read_many<T: Scalar>(process: impl ProcessMemory, addresses: &[usize]) -> Vec<Result<T, Box<dyn std::error::Error>>> {
+    read_many_in(process, addresses, Endianness::Native)
+}
+
+pub fn read_many_in<T: Scalar>(process: impl ProcessMemory, addresses: &[usize], endianness: Endianness) -> Vec<Result<T, Box<dyn std::error::Error>>> {
+    let requests = addresses.iter().map(|x| (*x, T::SIZE)).collect::<Vec<(usize, usize)>>();
+    let mut buffer = vec![0u8; addresses.len() * T::SIZE];
+    let results = process.read_many_into(&requests, &mut buffer);
+    results.into_iter().zip(buffer.chunks_exact(T::SIZE)).map(|(result, bytes)| result.map(|_| T::from_bytes_in(bytes, endianness))).collect()
+}
+
+// Reads into a caller-supplied buffer, returning how many bytes were actually transferred
+pub fn read_bytes_into(process: impl ProcessMemory, address: usize, buffer: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>> {
+    process.read_at(address, buffer)
+}
+
+// Reads into an existing value, returning how many of its bytes were actually transferred. A
+// short read leaves the remaining bytes of the value untouched. Any bytes are a valid T, as
+// FromBytes promises, so whatever the target holds there can be read into it
+pub fn read_into<T: FromBytes + IntoBytes>(process: impl ProcessMemory, address: usize, output: &mut T) -> Result<usize, Box<dyn std::error::Error>> {
+    process.read_at(address, output.as_mut_bytes())
+}
+
+// For scalars and for structs modelling the target's own, e.g. a #[repr(C)] Player deriving
+// FromBytes, which is what makes every bit pattern the target holds a valid T
+pub fn read_from_process<T: FromBytes>(process: impl ProcessMemory, address: usize) -> Result<T, Box<dyn std::error::Error>> {
+    let mut bytes = vec![0u8; std::mem::size_of::<T>()];
+    read_exact(process, address, &mut bytes)?;
+    T::read_from_bytes(&bytes).map_err(|_| "Struct size mismatch".into())
+}
+
+// read_from_process under a retry policy, also returning how many retries were needed
+pub fn read_from_process_with_retry<T: FromBytes>(process: impl ProcessMemory, address: usize, retry: &Retry) -> (Result<T, Box<dyn std::error::Error>>, u32) {
+    retry.run(|| read_from_process(&process, address))
+}
+
+// The same as read_from_process, by the name the struct helpers go by
+pub fn read_struct<T: FromBytes>(process: impl ProcessMemory, address: usize) -> Result<T, Box<dyn std::error::Error>> {
+    read_from_process(process, address)
+}
+
+// Like read_from_process, with the value stored in the given byte order
+pub fn read_scalar<T: Scalar>(process: impl ProcessMemory, address: usize, endianness: Endianness) -> Result<T, Box<dyn std::error::Error>> {
+    let mut bytes = vec![0u8; T::SIZE];
+    let read = read_bytes_into(process, address, &mut bytes)?;
+    if read < T::SIZE {
+        return Err(format!("Short read at 0x{:x}: got {} of {} bytes", address, read, T::SIZE).into());
+    }
+    Ok(T::from_bytes_in(&bytes, endianness))
+}
+
+// The returned vector is truncated to the bytes actually read
+pub fn read_bytes_from_process(process: impl ProcessMemory, bytes: usize, address: usize) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut output: Vec<u8> = vec![0; bytes];
+    let read = read_bytes_into(process, address, &mut output)?;
+    output.truncate(read);
+    Ok(output)
+}
+
+// Reads up to max_len bytes, stopping before the first NUL. Reads go chunk by chunk and never past
+// the end of the region holding the address, so a short string at the end of a mapping does not
+// fail just because a fixed-size read would have run off it. Hitting the cap, the end of the region
+// or an unreadable byte gives the string read so far without a terminator having been seen
+pub fn read_cstring(process: impl ProcessMemory, regions: &mut RegionCache, address: usize, max_len: usize) -> Result<CString, Box<dyn std::error::Error>> {
+    let end = regions.region_for_address(address).map(|x| x.end).ok_or(format!("0x{:x} is not mapped", address))?;
+    let limit = max_len.min(end - address);
+    let mut bytes = Vec::new();
+    let mut buffer = [0u8; CSTRING_CHUNK_SIZE];
+    while bytes.len() < limit {
+        let len = CSTRING_CHUNK_SIZE.min(limit - bytes.len());
+        let read = match process.read_at(address + bytes.len(), &mut buffer[..len]) {
+            Ok(read) => read,
+            Err(e) if bytes.is_empty() => return Err(e),
+            Err(_) => break,
+        };
+        if let Some(nul) = buffer[..read].iter().position(|x| *x == 0) {
+            bytes.extend_from_slice(&buffer[..nul]);
+            break;
+        }
+        bytes.extend_from_slice(&buffer[..read]);
+        if read < len {
+            break;
+        }
+    }
+    Ok(CString::new(bytes)?)
+}
+
+// Like read_cstring, with invalid UTF-8 replaced by U+FFFD
+pub fn read_string_lossy(process: impl ProcessMemory, regions: &mut RegionCache, address: usize, max_len: usize) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(read_cstring(process, regions, address, max_len)?.to_string_lossy().into_owned())
+}
+
+// A NUL-terminated string as it is in memory, for replacing it in place: its text, the bytes that
+// takes, and the NULs after its terminator, which are likely the rest of a buffer zeroed past what
+// was written to it, as a fixed-size name field or a fresh allocation is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StringSlot {
+    pub text: String,
+    // Bytes of text before the terminator
+    pub len: usize,
+    // Whether a terminator was found before max_len, the end of the region or an unreadable byte
+    pub terminated: bool,
+    // NUL bytes after the terminator, in whole code units and at most STRING_SPARE_LIMIT
+    pub spare: usize,
+    pub encoding: Encoding,
+}
+
+impl StringSlot {
+    // Bytes a replacement can likely take, terminator included, without running into other data.
+    // Without a terminator nothing past the text is known to be free
+    pub fn room(&self) -> usize {
+        match self.terminated {
+            true => self.len + self.encoding.unit_size() + self.spare,
+            false => self.len,
+        }
+    }
+}
+
+// Reads the string at the address, UTF-8 ones by read_cstring and UTF-16 ones up to the first NUL
+// code unit, then the NULs after it
+pub fn read_string_slot(process: impl ProcessMemory, regions: &mut RegionCache, address: usize, encoding: Encoding, endianness: Endianness, max_len: usize) -> Result<StringSlot, Box<dyn std::error::Error>> {
+    let end = regions.region_for_address(address).map(|x| x.end).ok_or(format!("0x{:x} is not mapped", address))?;
+    let unit = encoding.unit_size();
+    let (text, len) = match encoding {
+        Encoding::Utf8 => {
+            let text = read_cstring(&process, regions, address, max_len)?.into_bytes();
+            (String::from_utf8_lossy(&text).into_owned(), text.len())
+        }
+        Encoding::Utf16 => {
+            let bytes = read_bytes_from_process(&process, max_len.min(end - address), address)?;
+            let units = bytes.chunks_exact(2).map(|x| u16::from_bytes_in(x, endianness)).take_while(|x| *x != 0).collect::<Vec<u16>>();
+            (String::from_utf16_lossy(&units), units.len() * 2)
+        }
+    };
+    // The terminator and the NULs after it, read as far as the region and the limit allow
+    let after = read_bytes_from_process(&process, (STRING_SPARE_LIMIT + unit).min(end - address - len), address + len).unwrap_or_default();
+    let zeros = after.chunks_exact(unit).take_while(|x| x.iter().all(|x| *x == 0)).count() * unit;
+    let terminated = zeros > 0 && len < max_len;
+    Ok(StringSlot { text, len, terminated, spare: zeros.saturating_sub(unit), encoding })
+}
+
+// Refuses to write into a mapping without the w flag, going by the maps as they are now. IntoBytes
+// and Immutable mean the value's bytes are all initialised and cannot change while being written
+pub fn write_to_process<T: IntoBytes + Immutable>(process: impl ProcessMemory, address: usize, to_write: &T) -> Result<(), Box<dyn std::error::Error>> {
+    check_writable(&process.memory_regions()?, address, std::mem::size_of::<T>())?;
+    write_to_process_forced(process, address, to_write)
+}
+
+// Writes whatever the mapping's protections. Depending on the backend and kernel a write into a
+// mapping without the w flag either fails with an unhelpful error or goes through, which for a
+// private file mapping of code is occasionally what is wanted. Fails if the write stops short, e.g.
+// at a page it could not get into after all
+pub fn write_to_process_forced<T: IntoBytes + Immutable>(process: impl ProcessMemory, address: usize, to_write: &T) -> Result<(), Box<dyn std::error::Error>> {
+    write_exact(process, address, to_write.as_bytes())
+}
+
+// The counterpart to read_struct, failing on a short write so no struct is left half written
+pub fn write_struct<T: IntoBytes + Immutable>(process: impl ProcessMemory, address: usize, value: &T) -> Result<(), Box<dyn std::error::Error>> {
+    write_exact(process, address, value.as_bytes())
+}
+
+// The absolute address of a field of a struct at base, for locking or watching just that field,
+// e.g. field_address!(player, Player, pos) or field_address!(player, Player, pos.1)
+#[macro_export]
+macro_rules! field_address {
+    ($base:expr, $struct:ty, $($field:tt).+) => {
+        ($base as usize) + ::std::mem::offset_of!($struct, $($field).+)
+    };
+}
+
+// Writes the data in calls of at most WRITE_CHUNK_SIZE, each starting where the last one stopped,
+// and returns how many bytes landed. Writing stops at the first byte that cannot be written, e.g. at
+// an unmapped page, and only fails if not even the first byte could be
+pub fn write_bytes_to_process(process: impl ProcessMemory, address: usize, data: &[u8]) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut written = 0;
+    while written < data.len() {
+        let end = (written + WRITE_CHUNK_SIZE).min(data.len());
+        match process.write_at(address + written, &data[written..end]) {
+            Ok(0) => break,
+            Ok(count) => written += count,
+            Err(e) if written == 0 => return Err(e),
+            Err(_) => break,
+        }
+    }
+    Ok(written)
+}
+
+// Like write_to_process, storing the value in the given byte order and failing on a short write
+pub fn write_scalar<T: Scalar>(process: impl ProcessMemory, address: usize, value: T, endianness: Endianness) -> Result<(), Box<dyn std::error::Error>> {
+    check_writable(&process.memory_regions()?, address, T::SIZE)?;
+    write_exact(process, address, &value.to_bytes_in(endianness))
+}
+
+// Like write_to_process, going by the cached maps, which are re-read once before refusing, and
+// writing anyway if forced
+pub fn write_to_process_checked<T: IntoBytes + Immutable>(process: impl ProcessMemory, regions: &mut RegionCache, address: usize, to_write: &T, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if !force {
+        regions.check_writable(address, std::mem::size_of::<T>())?;
+    }
+    write_to_process_forced(process, address, to_write)
+}
+
+// Fails if any of the value's bytes could not be written, so a string or byte buffer left half
+// written does not go unnoticed
+pub fn write_typed(process: impl ProcessMemory, address: usize, value: &TypedValue, endianness: Endianness) -> Result<(), Box<dyn std::error::Error>> {
+    let bytes = value.to_bytes_in(endianness);
+    let written = write_bytes_to_process(process, address, &bytes)?;
+    if written < bytes.len() {
+        return Err(format!("Short write at 0x{:x}: wrote {} of {} bytes", address, written, bytes.len()).into());
+    }
+    Ok(())
+}
+
+// Writes a string into a buffer, e.g. a 16-byte name field, padding what the text leaves of it so
+// the end of a longer old name does not show through. Fails without writing if the text does not
+// fit in max_len, and if only part of it could be written
+pub fn write_string(process: impl ProcessMemory, address: usize, text: &str, options: &StringWriteOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let bytes = encode_string(text, options)?;
+    let written = write_bytes_to_process(process, address, &bytes)?;
+    if written < bytes.len() {
+        return Err(format!("Short write at 0x{:x}: wrote {} of {} bytes", address, written, bytes.len()).into());
+    }
+    Ok(())
+}
+
+// Bits are numbered LSB-first everywhere: bit 0 is the lowest bit of the byte at the address, bit
+// 7 its highest and bit 8 the lowest bit of the next byte, so a field's bits run upwards from
+// bit_offset. This is how compilers lay out bitfields on little-endian targets. Returns the offset
+// of the first byte holding the field, the field's shift within it and how many bytes it covers
+fn bit_span(bit_offset: usize, bit_len: u32) -> Result<(usize, u32, usize), Box<dyn std::error::Error>> {
+    if !(1..=64).contains(&bit_len) {
+        return Err(format!("Bit fields are 1 to 64 bits long, not {}", bit_len).into());
+    }
+    let shift = (bit_offset % 8) as u32;
+    Ok((bit_offset / 8, shift, (shift + bit_len).div_ceil(8) as usize))
+}
+
+fn bit_mask(bit_len: u32) -> u128 {
+    (1u128 << bit_len) - 1
+}
+
+// Reads the bit_len bits starting bit_offset bits into the memory at the address
+pub fn read_bits(process: impl ProcessMemory, address: usize, bit_offset: usize, bit_len: u32) -> Result<u64, Box<dyn std::error::Error>> {
+    let (first, shift, len) = bit_span(bit_offset, bit_len)?;
+    let mut bytes = [0u8; 16];
+    read_exact(process, address + first, &mut bytes[..len])?;
+    Ok(((u128::from_le_bytes(bytes) >> shift) & bit_mask(bit_len)) as u64)
+}
+
+// Sets the bit_len bits starting bit_offset bits into the memory at the address, leaving the other
+// bits of the bytes they share untouched. The bytes are read, changed and written back in a single
+// write, which keeps the window for the target changing a neighbouring bit in between as small as
+// it can be. Returns the bytes before and after, starting from the first byte holding the field
+pub fn write_bits(process: impl ProcessMemory, address: usize, bit_offset: usize, bit_len: u32, value: u64) -> Result<(Vec<u8>, Vec<u8>), Box<dyn std::error::Error>> {
+    let (first, shift, len) = bit_span(bit_offset, bit_len)?;
+    if value as u128 > bit_mask(bit_len) {
+        return Err(format!("{} does not fit in {} bits", value, bit_len).into());
+    }
+    let mut bytes = [0u8; 16];
+    read_exact(&process, address + first, &mut bytes[..len])?;
+    let old = u128::from_le_bytes(bytes);
+    let new = (old & !(bit_mask(bit_len) << shift)) | ((value as u128) << shift);
+    write_exact(&process, address + first, &new.to_le_bytes()[..len])?;
+    Ok((bytes[..len].to_vec(), new.to_le_bytes()[..len].to_vec()))
+}
@@ -0,0 +1,281 @@
+use std::{sync::Mutex, time::Duration};
+use nix::{errno::Errno, unistd::Pid};
+use pyo3::{IntoPyObjectExt, exceptions::{PyOSError, PyRuntimeError, PyValueError}, prelude::*};
+use crate::{lock::{DEFAULT_LOCK_INTERVAL, LockAction, LockEntry, LockManager}, maps::MemoryRegion, process::{MemBackend, Process, ProcessMemory, read_bytes_from_process, read_many_in, read_scalar, write_bytes_to_process, write_scalar}, scan::{ScanOptions, ScanStats, find_value, reduce_found_values}, value::{Endianness, Scalar, ValueType}, with_scan_type};
+
+// The rust_memory_hack Python module, built with maturin (see pyproject.toml):
+//     import rust_memory_hack as rmh
+//     p = rmh.Process(pid)
+//     addrs = p.find_i32(100)
+//     p.write_i32(addrs[0], 999)
+//     p.lock_i32(addrs[0], 999)
+// Scans let go of the GIL while they run, so other Python threads carry on. An error carrying an
+// errno is raised as the OSError subclass Python picks for it, e.g. PermissionError or
+// ProcessLookupError, a bad type or value as ValueError, and any other error as RuntimeError
+
+fn to_py_err(error: impl Into<Box<dyn std::error::Error>>) -> PyErr {
+    let error = error.into();
+    match error.downcast_ref::<Errno>() {
+        Some(errno) => PyOSError::new_err((*errno as i32, errno.desc())),
+        None => PyRuntimeError::new_err(error.to_string()),
+    }
+}
+
+#[pyclass(name = "Process", module = "rust_memory_hack", frozen)]
+struct PyProcess {
+    process: Process,
+    locks: Mutex<LockManager<Process>>,
+}
+
+// One read, write, find and lock method per numeric type, e.g. read_i32, write_i32, find_i32 and
+// lock_i32, alongside the untyped ones
+macro_rules! process_methods {
+    ($(($t:ty, $read:ident, $write:ident, $find:ident, $lock:ident)),*) => {
+        #[pymethods]
+        impl PyProcess {
+            // Attaches like Process::attach, or with the given backend, or as the target's tracer
+            #[new]
+            #[pyo3(signature = (pid, backend=None, seize=false))]
+            fn new(pid: i32, backend: Option<&str>, seize: bool) -> PyResult<PyProcess> {
+                let pid = Pid::from_raw(pid);
+                if unsafe { libc::kill(pid.as_raw(), 0) } == -1 && Errno::last() == Errno::ESRCH {
+                    return Err(PyOSError::new_err((Errno::ESRCH as i32, format!("No process {}", pid))));
+                }
+                let process = match (backend, seize) {
+                    (Some(_), true) => return Err(to_py_err("seize always reads /proc/<pid>/mem, so it does not go with a backend")),
+                    (Some(backend), false) => backend.parse::<MemBackend>().map_err(Into::into).and_then(|x| Process::with_backend(pid, x)),
+                    (None, true) => Process::seize(pid),
+                    (None, false) => Process::attach(pid),
+                };
+                // Every backend failed on a process that exists
+                let process = process.map_err(|e| PyOSError::new_err((Errno::EACCES as i32, e.to_string())))?;
+                Ok(PyProcess { locks: Mutex::new(LockManager::new(process.clone())), process })
+            }
+
+            #[getter]
+            fn pid(&self) -> i32 {
+                self.process.pid().as_raw()
+            }
+
+            #[getter]
+            fn backend(&self) -> String {
+                self.process.backend().to_string()
+            }
+
+            // Reads up to len bytes, stopping short at the end of readable memory
+            fn read(&self, address: usize, len: usize) -> PyResult<Vec<u8>> {
+                read_bytes_from_process(&self.process, len, address).map_err(to_py_err)
+            }
+
+            // Returns how many bytes were written
+            fn write(&self, address: usize, data: &[u8]) -> PyResult<usize> {
+                write_bytes_to_process(&self.process, address, data).map_err(to_py_err)
+            }
+
+            fn regions(&self) -> PyResult<Vec<PyRegion>> {
+                Ok(self.process.memory_regions().map_err(to_py_err)?.iter().map(PyRegion::from).collect())
+            }
+
+            // Starts a scan for value as the named type, e.g. "i32", to be narrowed with rescan
+            fn scan(&self, py: Python<'_>, value_type: &str, value: &Bound<'_, PyAny>) -> PyResult<PyScan> {
+                let value_type = value_type.parse::<ValueType>().map_err(PyValueError::new_err)?;
+                let scan = PyScan { process: self.process.clone(), value_type, results: Mutex::new((Vec::new(), ScanStats::default())) };
+                scan.run(py, value, false)?;
+                Ok(scan)
+            }
+
+            fn locks(&self) -> Vec<PyLock> {
+                self.locks.lock().unwrap().list().iter().map(PyLock::from).collect()
+            }
+
+            // Returns whether there was a lock to remove
+            fn unlock(&self, address: usize) -> bool {
+                self.locks.lock().unwrap().unlock_value(address)
+            }
+
+            $(
+                fn $read(&self, address: usize) -> PyResult<$t> {
+                    read_scalar::<$t>(&self.process, address, Endianness::Native).map_err(to_py_err)
+                }
+
+                fn $write(&self, address: usize, value: $t) -> PyResult<()> {
+                    write_scalar(&self.process, address, value, Endianness::Native).map_err(to_py_err)
+                }
+
+                fn $find(&self, py: Python<'_>, value: $t) -> PyResult<Vec<usize>> {
+                    let process = &self.process;
+                    py.detach(|| find_value(process, value, &ScanOptions::default()).map(|x| x.0).map_err(to_py_err))
+                }
+
+                // Writes value to address every interval_ms until unlocked, replacing any lock there
+                #[pyo3(signature = (address, value, interval_ms=None))]
+                fn $lock(&self, address: usize, value: $t, interval_ms: Option<u64>) {
+                    let interval = interval_ms.map(Duration::from_millis).unwrap_or(DEFAULT_LOCK_INTERVAL);
+                    self.locks.lock().unwrap().lock_with_action(LockAction::Set(value), address, interval, Endianness::Native);
+                }
+            )*
+        }
+    };
+}
+
+process_methods!(
+    (i8, read_i8, write_i8, find_i8, lock_i8),
+    (i16, read_i16, write_i16, find_i16, lock_i16),
+    (i32, read_i32, write_i32, find_i32, lock_i32),
+    (i64, read_i64, write_i64, find_i64, lock_i64),
+    (u8, read_u8, write_u8, find_u8, lock_u8),
+    (u16, read_u16, write_u16, find_u16, lock_u16),
+    (u32, read_u32, write_u32, find_u32, lock_u32),
+    (u64, read_u64, write_u64, find_u64, lock_u64),
+    (f32, read_f32, write_f32, find_f32, lock_f32),
+    (f64, read_f64, write_f64, find_f64, lock_f64)
+);
+
+// The addresses a scan has narrowed down to, from Process.scan
+#[pyclass(name = "Scan", module = "rust_memory_hack", frozen)]
+struct PyScan {
+    process: Process,
+    value_type: ValueType,
+    results: Mutex<(Vec<usize>, ScanStats)>,
+}
+
+impl PyScan {
+    fn run(&self, py: Python<'_>, value: &Bound<'_, PyAny>, rescan: bool) -> PyResult<()> {
+        // Taken as its str() and parsed as the scan's type, so 100, 1.5 and "100" all work
+        let value = value.str()?.to_string();
+        check_value(self.value_type, &value).map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let (process, value_type) = (&self.process, self.value_type);
+        let results = &mut *self.results.lock().unwrap();
+        py.detach(|| scan(process, value_type, &value, rescan, results).map_err(to_py_err))
+    }
+}
+
+fn check_value(value_type: ValueType, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+    with_scan_type!(value_type, T, {
+        value.parse::<T>().map_err(|e| format!("Invalid {} '{}': {}", value_type, value, e))?;
+    });
+    Ok(())
+}
+
+fn scan(process: &Process, value_type: ValueType, value: &str, rescan: bool, (found, stats): &mut (Vec<usize>, ScanStats)) -> Result<(), Box<dyn std::error::Error>> {
+    let options = ScanOptions::default();
+    with_scan_type!(value_type, T, {
+        let value = value.parse::<T>()?;
+        if rescan {
+            reduce_found_values(process, found, value, &options, stats)?;
+        }
+        else {
+            (*found, *stats) = find_value(process, value, &options)?;
+        }
+    });
+    Ok(())
+}
+
+fn values<T: Scalar + for<'py> IntoPyObject<'py>>(py: Python<'_>, process: &Process, addresses: &[usize]) -> PyResult<Vec<Py<PyAny>>> {
+    read_many_in::<T>(process, addresses, Endianness::Native).into_iter().map(|x| x.ok().into_py_any(py)).collect()
+}
+
+#[pymethods]
+impl PyScan {
+    // Keeps the addresses that now hold value
+    fn rescan(&self, py: Python<'_>, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.run(py, value, true)
+    }
+
+    #[getter]
+    fn addresses(&self) -> Vec<usize> {
+        self.results.lock().unwrap().0.clone()
+    }
+
+    #[getter]
+    fn value_type(&self) -> String {
+        self.value_type.to_string()
+    }
+
+    #[getter]
+    fn bytes_scanned(&self) -> usize {
+        self.results.lock().unwrap().1.bytes_scanned
+    }
+
+    // What each address holds now, in the same order, with None where it could not be read
+    fn values(&self, py: Python<'_>) -> PyResult<Vec<Py<PyAny>>> {
+        let addresses = self.addresses();
+        let values: Result<PyResult<Vec<Py<PyAny>>>, Box<dyn std::error::Error>> = (|| Ok(with_scan_type!(self.value_type, T, {
+            values::<T>(py, &self.process, &addresses)
+        })))();
+        values.map_err(to_py_err)?
+    }
+
+    fn __len__(&self) -> usize {
+        self.results.lock().unwrap().0.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Scan of {} for {}: {} addresses>", self.process.pid(), self.value_type, self.__len__())
+    }
+}
+
+#[pyclass(name = "Region", module = "rust_memory_hack", frozen, get_all)]
+struct PyRegion {
+    start: usize,
+    end: usize,
+    permissions: String,
+    offset: usize,
+    pathname: String,
+}
+
+impl From<&MemoryRegion> for PyRegion {
+    fn from(region: &MemoryRegion) -> PyRegion {
+        PyRegion { start: region.start, end: region.end, permissions: region.permissions(), offset: region.offset, pathname: region.pathname.clone() }
+    }
+}
+
+#[pymethods]
+impl PyRegion {
+    fn __repr__(&self) -> String {
+        format!("<Region 0x{:x}-0x{:x} {} {}>", self.start, self.end, self.permissions, self.pathname)
+    }
+}
+
+// A snapshot of one lock, from Process.locks
+#[pyclass(name = "Lock", module = "rust_memory_hack", frozen, get_all)]
+struct PyLock {
+    address: usize,
+    value_type: String,
+    action: String,
+    interval_ms: u64,
+    status: String,
+    writes: u64,
+    failures: u64,
+}
+
+impl From<&LockEntry> for PyLock {
+    fn from(lock: &LockEntry) -> PyLock {
+        PyLock {
+            address: lock.address,
+            value_type: lock.type_name.to_string(),
+            action: lock.action.clone(),
+            interval_ms: lock.interval.as_millis() as u64,
+            status: lock.status().to_string(),
+            writes: lock.writes,
+            failures: lock.failures,
+        }
+    }
+}
+
+#[pymethods]
+impl PyLock {
+    fn __repr__(&self) -> String {
+        format!("<Lock 0x{:x} {} {} ({})>", self.address, self.value_type, self.action, self.status)
+    }
+}
+
+// Public only so that tests can build the module in process with wrap_pymodule!
+#[pymodule]
+pub fn rust_memory_hack(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyProcess>()?;
+    module.add_class::<PyScan>()?;
+    module.add_class::<PyRegion>()?;
+    module.add_class::<PyLock>()?;
+    Ok(())
+}
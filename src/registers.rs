@@ -0,0 +1,92 @@
+use nix::unistd::Pid;
+use crate::tracer::PtraceSession;
+
+// The threads of a process from /proc/<pid>/task, and the general-purpose registers of one of
+// them, read through the target's PtraceSession. Which registers there are and which of them
+// are the instruction and stack pointers is the only part that depends on the architecture
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadInfo {
+    pub tid: Pid,
+    // The state letter from its stat, e.g. R running, S sleeping, T stopped, t traced
+    pub state: char,
+    pub name: String,
+}
+
+// Sorted by tid, so the main thread comes first. A thread that ends while being listed is left out
+pub fn list_threads(pid: Pid) -> Result<Vec<ThreadInfo>, Box<dyn std::error::Error>> {
+    let tasks = std::fs::read_dir(format!("/proc/{}/task", pid)).map_err(|e| format!("Could not list the threads of process {}: {}", pid, e))?;
+    let mut threads = tasks.filter_map(|x| {
+        let tid = Pid::from_raw(x.ok()?.file_name().to_str()?.parse().ok()?);
+        thread_info(pid, tid)
+    }).collect::<Vec<ThreadInfo>>();
+    threads.sort_by_key(|x| x.tid);
+    Ok(threads)
+}
+
+// The name is in parentheses and may itself hold spaces and parentheses, so the state is found
+// after the last closing one
+fn thread_info(pid: Pid, tid: Pid) -> Option<ThreadInfo> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/task/{}/stat", pid, tid)).ok()?;
+    let (name, rest) = stat.split_once('(')?.1.rsplit_once(')')?;
+    Some(ThreadInfo { tid, state: rest.trim_start().chars().next()?, name: name.to_string() })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Registers {
+    pub thread: Pid,
+    // In the order a debugger shows them
+    pub values: Vec<(&'static str, u64)>,
+    // Where the thread is and its stack pointer, whatever they are called on the architecture
+    pub pc: u64,
+    pub sp: u64,
+}
+
+impl Registers {
+    pub fn get(&self, name: &str) -> Option<u64> {
+        self.values.iter().find(|x| x.0 == name).map(|x| x.1)
+    }
+}
+
+// The thread is interrupted for as long as it takes to read them, then carries on as it was,
+// so this works on running threads as well as stopped ones
+pub fn read_registers(pid: Pid, thread: Pid) -> Result<Registers, Box<dyn std::error::Error>> {
+    let session = PtraceSession::seize(pid)?;
+    let regs = session.run(move |traced| {
+        if !traced.threads.contains(&thread) {
+            return Err(format!("{} is not a thread of process {}", thread, pid));
+        }
+        let resume = traced.stop(thread).ok_or(format!("Thread {} exited", thread))?;
+        let regs = get_regset(thread);
+        resume.resume(thread);
+        regs.map_err(|e| format!("Could not read the registers of thread {}: {}", thread, e))
+    })??;
+    Ok(named(thread, &regs).ok_or("Reading registers is only supported on x86-64")?)
+}
+
+// PTRACE_GETREGSET with NT_PRSTATUS gives user_regs_struct on every architecture, unlike
+// PTRACE_GETREGS, which some do not have
+fn get_regset(thread: Pid) -> Result<libc::user_regs_struct, nix::errno::Errno> {
+    let mut regs: libc::user_regs_struct = unsafe { std::mem::zeroed() };
+    let mut iovec = libc::iovec { iov_base: &mut regs as *mut libc::user_regs_struct as *mut libc::c_void, iov_len: size_of::<libc::user_regs_struct>() };
+    let result = unsafe { libc::ptrace(libc::PTRACE_GETREGSET, thread.as_raw(), libc::NT_PRSTATUS, &mut iovec as *mut libc::iovec) };
+    nix::errno::Errno::result(result).map(|_| regs)
+}
+
+#[cfg(target_arch = "x86_64")]
+fn named(thread: Pid, regs: &libc::user_regs_struct) -> Option<Registers> {
+    let values = vec![
+        ("rax", regs.rax), ("rbx", regs.rbx), ("rcx", regs.rcx), ("rdx", regs.rdx),
+        ("rsi", regs.rsi), ("rdi", regs.rdi), ("rbp", regs.rbp), ("rsp", regs.rsp),
+        ("r8", regs.r8), ("r9", regs.r9), ("r10", regs.r10), ("r11", regs.r11),
+        ("r12", regs.r12), ("r13", regs.r13), ("r14", regs.r14), ("r15", regs.r15),
+        ("rip", regs.rip), ("eflags", regs.eflags), ("orig_rax", regs.orig_rax),
+        ("fs_base", regs.fs_base), ("gs_base", regs.gs_base),
+    ];
+    Some(Registers { thread, values, pc: regs.rip, sp: regs.rsp })
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn named(_: Pid, _: &libc::user_regs_struct) -> Option<Registers> {
+    None
+}
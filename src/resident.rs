@@ -0,0 +1,192 @@
+use std::{fs::File, io::{BufRead, BufReader}};
+#[cfg(target_os = "linux")]
+use std::sync::OnceLock;
+use crate::{maps::MemoryRegion, platform::{FileExt, Pid}};
+
+// Pagemap entries read per call, 32 KB of entries covering 16 MB of 4 KB pages
+const PAGEMAP_BATCH: usize = 4096;
+// Set in a pagemap entry when the page is in RAM
+const PAGE_PRESENT: u64 = 1 << 63;
+// Set for pages of a file or of shared anonymous memory
+const PAGE_FILE: u64 = 1 << 61;
+// Set for pages mapped only by this process
+const PAGE_EXCLUSIVE: u64 = 1 << 56;
+// Set for pages written since the soft-dirty bits were last cleared through clear_refs
+const PAGE_SOFT_DIRTY: u64 = 1 << 55;
+
+// Where the residency information came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Residency {
+    // Page by page from /proc/<pid>/pagemap
+    Pagemap,
+    // Region by region from the Rss lines of /proc/<pid>/smaps, used when pagemap cannot be read.
+    // Only regions with nothing resident at all are dropped
+    Smaps,
+    // Neither could be read, so nothing was dropped
+    Unavailable,
+}
+
+impl std::fmt::Display for Residency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Residency::Pagemap => write!(f, "pagemap"),
+            Residency::Smaps => write!(f, "smaps"),
+            Residency::Unavailable => write!(f, "unavailable"),
+        }
+    }
+}
+
+// The parts of the ranges that are in RAM. Pages that were never touched read back as zeroes and
+// pages that were swapped out are not resident either, so this can miss values in swapped pages
+pub fn resident_ranges(pid: Pid, ranges: &[(usize, usize)], regions: &[MemoryRegion]) -> (Vec<(usize, usize)>, Residency) {
+    if let Ok(resident) = pagemap_ranges(pid, ranges, regions) {
+        return (resident, Residency::Pagemap);
+    }
+    match smaps_ranges(pid, ranges) {
+        Ok(resident) => (resident, Residency::Smaps),
+        Err(_) => (ranges.to_vec(), Residency::Unavailable),
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn page_size() -> usize {
+    match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        size if size > 0 => size as usize,
+        _ => 4096,
+    }
+}
+
+// There is no pagemap to read there anyway
+#[cfg(windows)]
+pub(crate) fn page_size() -> usize {
+    4096
+}
+
+// Some kernels refuse to open pagemap without CAP_SYS_ADMIN, which ends up as an error here
+fn pagemap_ranges(pid: Pid, ranges: &[(usize, usize)], regions: &[MemoryRegion]) -> Result<Vec<(usize, usize)>, Box<dyn std::error::Error>> {
+    // Reading an untouched anonymous page maps the shared zero page, which is present but neither
+    // file-backed nor exclusive. Requiring one of them keeps an earlier full scan from making
+    // everything look resident, at the cost of also skipping anonymous pages still shared with a
+    // parent after fork
+    pages_where(pid, ranges, regions, |x| x & PAGE_PRESENT != 0 && x & (PAGE_FILE | PAGE_EXCLUSIVE) != 0)
+}
+
+// The parts of the ranges whose pagemap entries the test accepts, with neighbouring pages joined.
+// Pagemap has an entry per base page whatever backs it, and gives every base page of a huge page
+// the same one, so in regions of huge pages only each one's first entry is read
+fn pages_where(pid: Pid, ranges: &[(usize, usize)], regions: &[MemoryRegion], test: impl Fn(u64) -> bool) -> Result<Vec<(usize, usize)>, Box<dyn std::error::Error>> {
+    let pagemap = File::open(format!("/proc/{}/pagemap", pid))?;
+    let page_size = page_size();
+    let mut found: Vec<(usize, usize)> = Vec::new();
+    let mut add = |start: usize, end: usize| match found.last_mut() {
+        Some(last) if last.1 == start => last.1 = end,
+        _ => found.push((start, end)),
+    };
+    let mut entries = vec![0u8; PAGEMAP_BATCH * 8];
+    for &(start, end) in ranges {
+        let index = regions.partition_point(|x| x.end <= start);
+        if let Some(huge) = regions.get(index).filter(|x| x.contains(start)).and_then(|x| x.kernel_page_size).filter(|x| *x > page_size) {
+            for address in (start / huge * huge..end).step_by(huge) {
+                if pagemap.read_at(&mut entries[..8], (address / page_size * 8) as u64)? < 8 {
+                    return Err(format!("Short read from pagemap at page 0x{:x}", address / page_size).into());
+                }
+                if test(u64::from_ne_bytes(entries[..8].try_into()?)) {
+                    add(address.max(start), (address + huge).min(end));
+                }
+            }
+            continue;
+        }
+        let mut page = start / page_size;
+        let last_page = end.div_ceil(page_size);
+        while page < last_page {
+            let count = PAGEMAP_BATCH.min(last_page - page);
+            let read = pagemap.read_at(&mut entries[..count * 8], (page * 8) as u64)?;
+            if read < count * 8 {
+                return Err(format!("Short read from pagemap at page 0x{:x}", page).into());
+            }
+            for (index, entry) in entries[..count * 8].chunks_exact(8).enumerate() {
+                if test(u64::from_ne_bytes(entry.try_into()?)) {
+                    add(((page + index) * page_size).max(start), ((page + index + 1) * page_size).min(end));
+                }
+            }
+            page += count;
+        }
+    }
+    Ok(found)
+}
+
+// Clears the soft-dirty bit of every page of the target, so that dirty_ranges afterwards gives the
+// pages it wrote since. The bits are the target's, not this process's: anything else relying on
+// them, such as CRIU's incremental dumps, sees them cleared too
+#[cfg(target_os = "linux")]
+pub fn clear_soft_dirty(pid: Pid) -> Result<(), Box<dyn std::error::Error>> {
+    if !soft_dirty_supported() {
+        return Err("This kernel does not track soft-dirty pages".into());
+    }
+    Ok(std::fs::write(format!("/proc/{}/clear_refs", pid), "4")?)
+}
+
+// The parts of the ranges on pages written since clear_soft_dirty. Pages written since are those
+// a new mapping brought too, and pages that were given back and have not been touched again are
+// not, although they now read as zeroes
+#[cfg(target_os = "linux")]
+pub fn dirty_ranges(pid: Pid, ranges: &[(usize, usize)], regions: &[MemoryRegion]) -> Result<Vec<(usize, usize)>, Box<dyn std::error::Error>> {
+    if !soft_dirty_supported() {
+        return Err("This kernel does not track soft-dirty pages".into());
+    }
+    pages_where(pid, ranges, regions, |x| x & PAGE_SOFT_DIRTY != 0)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn clear_soft_dirty(_pid: Pid) -> Result<(), Box<dyn std::error::Error>> {
+    Err("Soft-dirty pages are only tracked on Linux".into())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn dirty_ranges(_pid: Pid, _ranges: &[(usize, usize)], _regions: &[MemoryRegion]) -> Result<Vec<(usize, usize)>, Box<dyn std::error::Error>> {
+    Err("Soft-dirty pages are only tracked on Linux".into())
+}
+
+#[cfg(target_os = "linux")]
+fn soft_dirty_supported() -> bool {
+    static SUPPORTED: OnceLock<bool> = OnceLock::new();
+    *SUPPORTED.get_or_init(|| probe_soft_dirty().unwrap_or(false))
+}
+
+// Kernels built without CONFIG_MEM_SOFT_DIRTY take the write to clear_refs all the same but never
+// set the bit, which would make every page look untouched. So this clears this process's own bits
+// and checks that writing one of its pages sets that page's again
+#[cfg(target_os = "linux")]
+fn probe_soft_dirty() -> Result<bool, Box<dyn std::error::Error>> {
+    let page_size = page_size();
+    let mut buffer = vec![1u8; page_size * 2];
+    let index = (buffer.as_ptr() as usize).next_multiple_of(page_size) - buffer.as_ptr() as usize;
+    std::fs::write("/proc/self/clear_refs", "4")?;
+    unsafe { std::ptr::write_volatile(buffer.as_mut_ptr().add(index), 2) };
+    let mut entry = [0u8; 8];
+    let page = (buffer.as_ptr() as usize + index) / page_size;
+    if File::open("/proc/self/pagemap")?.read_at(&mut entry, (page * 8) as u64)? < 8 {
+        return Err("Short read from pagemap".into());
+    }
+    Ok(u64::from_ne_bytes(entry) & PAGE_SOFT_DIRTY != 0)
+}
+
+// Keeps every range that overlaps a mapping with some resident memory
+fn smaps_ranges(pid: Pid, ranges: &[(usize, usize)]) -> Result<Vec<(usize, usize)>, Box<dyn std::error::Error>> {
+    let smaps = BufReader::new(File::open(format!("/proc/{}/smaps", pid))?);
+    let mut mapping: Option<(usize, usize)> = None;
+    let mut occupied: Vec<(usize, usize)> = Vec::new();
+    for line in smaps.lines() {
+        let line = line?;
+        if let Some(rss) = line.strip_prefix("Rss:") {
+            if let Some(mapping) = mapping && rss.trim().trim_end_matches("kB").trim() != "0" {
+                occupied.push(mapping);
+            }
+        }
+        else if let Some((start, rest)) = line.split_once('-') && let Some(end) = rest.split_whitespace().next()
+            && let (Ok(start), Ok(end)) = (usize::from_str_radix(start, 16), usize::from_str_radix(end, 16)) {
+            mapping = Some((start, end));
+        }
+    }
+    Ok(ranges.iter().filter(|x| occupied.iter().any(|y| y.0 < x.1 && x.0 < y.1)).copied().collect())
+}
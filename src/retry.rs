@@ -0,0 +1,56 @@
+use std::time::Duration;
+use crate::platform::Errno;
+
+// How many times to try a read whose failure may be transient, as when the target is in the
+// middle of an munmap or mremap or is being stopped. Only EFAULT and EIO are retried; anything
+// else, like ESRCH for a process that is gone or EPERM, fails straight away. The default of a
+// single attempt never retries
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Retry {
+    pub attempts: u32,
+    pub delay: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Retry {
+        Retry { attempts: 1, delay: Duration::ZERO }
+    }
+}
+
+impl Retry {
+    pub fn new(attempts: u32, delay: Duration) -> Retry {
+        Retry { attempts: attempts.max(1), delay }
+    }
+
+    // Runs the operation until it succeeds, fails for a reason worth not retrying or runs out of
+    // attempts. Also returns how many retries it took, so callers can report flaky reads
+    pub fn run<R>(&self, mut operation: impl FnMut() -> Result<R, Box<dyn std::error::Error>>) -> (Result<R, Box<dyn std::error::Error>>, u32) {
+        let first = operation();
+        self.after(first, operation)
+    }
+
+    // Like run, for when the first attempt was already made some other way, e.g. as part of a
+    // batched read
+    pub fn after<R>(&self, first: Result<R, Box<dyn std::error::Error>>, mut operation: impl FnMut() -> Result<R, Box<dyn std::error::Error>>) -> (Result<R, Box<dyn std::error::Error>>, u32) {
+        let mut result = first;
+        let mut retries = 0;
+        while let Err(e) = &result && retries + 1 < self.attempts && is_transient(e.as_ref()) {
+            retries += 1;
+            std::thread::sleep(self.delay);
+            result = operation();
+        }
+        (result, retries)
+    }
+}
+
+// The process_vm backend fails with an Errno and the /proc/<pid>/mem one with an io::Error
+pub(crate) fn errno_of(error: &(dyn std::error::Error + 'static)) -> Option<Errno> {
+    match error.downcast_ref::<Errno>() {
+        Some(errno) => Some(*errno),
+        None => error.downcast_ref::<std::io::Error>().and_then(|x| x.raw_os_error()).map(Errno::from_raw),
+    }
+}
+
+pub fn is_transient(error: &(dyn std::error::Error + 'static)) -> bool {
+    matches!(errno_of(error), Some(Errno::EFAULT | Errno::EIO))
+}
@@ -0,0 +1,950 @@
+use std::{collections::{BTreeMap, HashSet}, sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, Mutex, RwLock}, time::{Duration, Instant}};
+use rayon::prelude::*;
+use zerocopy::{FromBytes, IntoBytes};
+use crate::{budget::{BudgetFallback, budget_chunk_size}, expr::Expression, filter::{RegionCategory, RegionFilter, classify_regions}, maps::{MapsChange, MapsFingerprint, MemoryRegion}, priority::{ScanPriority, at_priority}, process::{ProcessMemory, read_bytes_into, read_into, read_many_in, read_scalar}, retry::{Retry, errno_of}, resident::{Residency, page_size, resident_ranges}, value::{Endianness, Scalar}};
+
+// How often the progress callback is invoked during a scan
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+// How long a throttled scan may read at full speed after a pause, before the throttle applies
+const THROTTLE_BURST: Duration = Duration::from_millis(100);
+
+// Addresses per gather read when reducing, one process_vm_readv call's worth
+const REDUCE_BATCH: usize = 1024;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ScanProgress {
+    pub bytes_scanned: usize,
+    pub bytes_total: usize,
+    pub elapsed: Duration,
+    // Bytes per second since the previous report, so a scan stuck on one region shows up as a drop
+    pub rate: f64,
+    // The cap on the rate the scan was given, if any
+    pub throttle: Option<u64>,
+}
+
+#[derive(Clone)]
+pub struct ProgressCallback(pub Arc<dyn Fn(&ScanProgress) + Send + Sync>);
+
+impl std::fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ProgressCallback")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    // Regions larger than this are skipped entirely; None scans everything
+    pub max_region_size: Option<usize>,
+    // Store snapshot chunks lz4-compressed, trading CPU on capture and compare for memory
+    pub compress_snapshots: bool,
+    // Workers stop picking up new chunks once this many hits have been found, leaving a partial result
+    pub stop_after: Option<usize>,
+    pub progress: Option<ProgressCallback>,
+    // Only consider addresses that are a multiple of this; None checks every byte offset
+    pub alignment: Option<usize>,
+    // Only scan or capture the regions this selects; None takes every readable region
+    pub filter: Option<RegionFilter>,
+    // Only scan pages that are in RAM, which skips the untouched parts of huge mappings but misses
+    // values in swapped-out pages
+    pub resident_only: bool,
+    // When the layout changed since the scan, drop results that are no longer in a readable region
+    // before reducing instead of only reporting the change
+    pub drop_unmapped: bool,
+    // Which categories of region to scan, see RegionCategory. All on by default
+    pub include_heap: bool,
+    pub include_stack: bool,
+    pub include_anonymous: bool,
+    pub include_file_backed: bool,
+    pub include_main_executable: bool,
+    // The byte order values are stored in, for equality scans and reduces
+    pub endianness: Endianness,
+    // How reduces retry reads that fail transiently. A read that still fails keeps its result
+    pub retry: Retry,
+    // Most bytes a second a scan or snapshot reads, all its workers together, so that it leaves the
+    // target some memory bandwidth. None or 0 reads as fast as it can
+    pub throttle: Option<u64>,
+    // The scheduling priority the scan's workers run at
+    pub priority: ScanPriority,
+    // Roughly the most memory, in bytes, a scan, snapshot or narrow may take before it changes
+    // strategy to stay under, see budget.rs. None lets it take what it needs
+    pub memory_budget: Option<usize>,
+    // Have an unknown scan clear the target's soft-dirty bits, so that its narrows only read the
+    // pages written since the last. Where the bits cannot be used it reads everything as without
+    pub soft_dirty: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> ScanOptions {
+        ScanOptions {
+            max_region_size: None,
+            compress_snapshots: false,
+            stop_after: None,
+            progress: None,
+            alignment: None,
+            filter: None,
+            resident_only: false,
+            drop_unmapped: false,
+            include_heap: true,
+            include_stack: true,
+            include_anonymous: true,
+            include_file_backed: true,
+            include_main_executable: true,
+            endianness: Endianness::Native,
+            retry: Retry::default(),
+            throttle: None,
+            priority: ScanPriority::Normal,
+            memory_budget: None,
+            soft_dirty: false,
+        }
+    }
+}
+
+impl ScanOptions {
+    pub fn includes(&self, category: RegionCategory) -> bool {
+        match category {
+            RegionCategory::MainExecutable => self.include_main_executable,
+            RegionCategory::Heap => self.include_heap,
+            RegionCategory::Stack => self.include_stack,
+            RegionCategory::FileBacked => self.include_file_backed,
+            RegionCategory::Anonymous => self.include_anonymous,
+        }
+    }
+}
+
+// Memory a scan or reduce could not read, one per region and error. A reduce's are the results it
+// could not read, grouped by the region they are in, or by themselves if it is now unmapped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadFailure {
+    pub region: (usize, usize),
+    // The errno's name, e.g. EFAULT, or the error itself when it has none
+    pub error: String,
+    // Bytes of the region that went unread, or for a reduce the size of the values
+    pub bytes_lost: usize,
+}
+
+// The name a read's error is counted under in a ReadFailure
+pub fn read_error_name(error: &(dyn std::error::Error + 'static)) -> String {
+    match errno_of(error) {
+        Some(errno) => format!("{:?}", errno),
+        None => error.to_string(),
+    }
+}
+
+// Adds the bytes to the region's failure with the same error, or starts one
+fn add_failure(failures: &mut Vec<ReadFailure>, region: (usize, usize), error: String, bytes: usize) {
+    match failures.iter_mut().find(|x| x.region == region && x.error == error) {
+        Some(failure) => failure.bytes_lost += bytes,
+        None => failures.push(ReadFailure { region, error, bytes_lost: bytes }),
+    }
+}
+
+// Groups the results a reduce could not read, as (address, error), by region
+pub(crate) fn reduce_failures(regions: &[MemoryRegion], unread: Vec<(usize, String)>, size: usize) -> Vec<ReadFailure> {
+    let mut failures = Vec::new();
+    for (address, error) in unread {
+        let index = regions.partition_point(|x| x.start <= address);
+        let region = match index > 0 && regions[index - 1].contains(address) {
+            true => (regions[index - 1].start, regions[index - 1].end),
+            false => (address, address + size),
+        };
+        add_failure(&mut failures, region, error, size);
+    }
+    failures.sort_by(|a, b| a.region.cmp(&b.region).then(a.error.cmp(&b.error)));
+    failures
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ScanStats {
+    pub skipped_regions: Vec<(usize, usize)>,
+    pub largest_region: usize,
+    pub bytes_scanned: usize,
+    // Bytes that would have been scanned had nothing stopped the scan early
+    pub bytes_total: usize,
+    // Set when the scan stopped before covering everything, so the results may be incomplete
+    pub partial: bool,
+    // Regions where at least one chunk could not be read
+    pub failed_regions: Vec<(usize, usize)>,
+    pub matches: usize,
+    pub elapsed: Duration,
+    // How residency was determined, when resident_only was set
+    pub residency: Option<Residency>,
+    // Bytes left out for not being resident
+    pub bytes_not_resident: usize,
+    // The layout when the results were found, or last reduced
+    pub maps: Option<MapsFingerprint>,
+    // Set by a reduce when the layout differs from `maps`
+    pub maps_change: Option<MapsChange>,
+    // Results a reduce dropped for no longer being in a readable region
+    pub dropped_unmapped: usize,
+    // Bytes selected for scanning from each category of region
+    pub bytes_by_category: BTreeMap<RegionCategory, usize>,
+    // Results a reduce only managed to read after retrying
+    pub retried: usize,
+    // What the scan did to keep within the memory budget, in the order it did it
+    pub fallbacks: Vec<BudgetFallback>,
+    // What the scan, or the reduce since, could not read
+    pub read_failures: Vec<ReadFailure>,
+    // Set when the results are floats found by the integer they round to, as by find_rounded
+    pub rounded: bool,
+}
+
+impl ScanStats {
+    pub fn bytes_skipped(&self) -> usize {
+        self.skipped_regions.iter().map(|x| x.1 - x.0).sum()
+    }
+
+    pub fn bytes_lost(&self) -> usize {
+        self.read_failures.iter().map(|x| x.bytes_lost).sum()
+    }
+
+    // Bytes per second over the whole scan
+    pub fn throughput(&self) -> f64 {
+        if self.elapsed.is_zero() {
+            0.0
+        }
+        else {
+            self.bytes_scanned as f64 / self.elapsed.as_secs_f64()
+        }
+    }
+
+    pub fn coverage(&self) -> f64 {
+        if self.bytes_total == 0 {
+            1.0
+        }
+        else {
+            self.bytes_scanned as f64 / self.bytes_total as f64
+        }
+    }
+}
+
+// A range to scan and the category of the region it came from
+pub type CategorizedRange = ((usize, usize), RegionCategory);
+
+// The readable ranges the options' filter and category toggles select, each with its category
+pub fn categorized_ranges(process: &impl ProcessMemory, options: &ScanOptions) -> Result<Vec<CategorizedRange>, Box<dyn std::error::Error>> {
+    let regions = process.memory_regions()?;
+    let categories = classify_regions(process, &regions);
+    let selected: Option<HashSet<usize>> = options.filter.as_ref().map(|x| x.select(process, &regions).iter().map(|x| x.start).collect());
+    Ok(regions.iter().zip(categories).filter(|(region, category)| {
+        region.readable && options.includes(*category) && selected.as_ref().is_none_or(|x| x.contains(&region.start))
+    }).map(|(region, category)| ((region.start, region.end), category)).collect())
+}
+
+pub fn filtered_ranges(process: &impl ProcessMemory, options: &ScanOptions) -> Result<Vec<(usize, usize)>, Box<dyn std::error::Error>> {
+    Ok(categorized_ranges(process, options)?.into_iter().map(|x| x.0).collect())
+}
+
+fn select_ranges(process: &impl ProcessMemory, options: &ScanOptions, stats: &mut ScanStats) -> Result<Vec<(usize, usize)>, Box<dyn std::error::Error>> {
+    let categorized = categorized_ranges(process, options)?;
+    let mut ranges = categorized.iter().map(|x| x.0).collect::<Vec<(usize, usize)>>();
+    stats.largest_region = ranges.iter().map(|x| x.1 - x.0).max().unwrap_or(0);
+    if let Some(max_region_size) = options.max_region_size {
+        ranges.retain(|x| {
+            if x.1 - x.0 > max_region_size {
+                stats.skipped_regions.push(*x);
+                false
+            }
+            else {
+                true
+            }
+        });
+    }
+    if options.resident_only {
+        let before = ranges.iter().map(|x| x.1 - x.0).sum::<usize>();
+        let (resident, residency) = resident_ranges(process.pid(), &ranges, &process.memory_regions()?);
+        ranges = resident;
+        stats.residency = Some(residency);
+        stats.bytes_not_resident = before - ranges.iter().map(|x| x.1 - x.0).sum::<usize>();
+    }
+    // Resident ranges are pieces of the selected ones, so each still lies inside exactly one of them
+    for range in &ranges {
+        let index = categorized.partition_point(|x| x.0.0 <= range.0) - 1;
+        *stats.bytes_by_category.entry(categorized[index].1).or_default() += range.1 - range.0;
+    }
+    Ok(ranges)
+}
+
+// What a scan with these options would read, when the process is behind a backend as slow as
+// ptrace and no region filter narrows it down, so the caller can warn before it starts. None
+// otherwise
+pub fn slow_scan_bytes(process: &impl ProcessMemory, options: &ScanOptions) -> Result<Option<usize>, Box<dyn std::error::Error>> {
+    if !process.is_slow() || options.filter.is_some() {
+        return Ok(None);
+    }
+    let ranges = select_ranges(process, options, &mut ScanStats::default())?;
+    Ok(Some(ranges.iter().map(|x| x.1 - x.0).sum()))
+}
+
+// Largest unit of work handed to a rayon worker, so that one huge region is still spread across every core
+pub const SCAN_CHUNK_SIZE: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorkChunk {
+    pub region: (usize, usize),
+    pub address: usize,
+    pub len: usize,
+}
+
+pub fn split_into_chunks(ranges: &[(usize, usize)], chunk_size: usize) -> Vec<WorkChunk> {
+    ranges.iter().flat_map(|region| {
+        (region.0..region.1).step_by(chunk_size).map(move |address| WorkChunk { region: *region, address, len: chunk_size.min(region.1 - address) })
+    }).collect()
+}
+
+// As split_into_chunks, but cutting the ranges that are in regions of huge pages at page
+// boundaries, so that a page that cannot be read fails whole chunks of its own rather than taking
+// the readable ends of its neighbours' with it. Chunks there are a multiple of the page size, or a
+// power of two that divides it if chunk_size is smaller, so the budget still holds
+pub fn split_regions_into_chunks(ranges: &[(usize, usize)], regions: &[MemoryRegion], chunk_size: usize) -> Vec<WorkChunk> {
+    ranges.iter().flat_map(|range| {
+        let index = regions.partition_point(|x| x.end <= range.0);
+        let Some(page_size) = regions.get(index).filter(|x| x.contains(range.0)).and_then(|x| x.kernel_page_size) else {
+            return split_into_chunks(&[*range], chunk_size);
+        };
+        let size = match chunk_size >= page_size {
+            true => chunk_size / page_size * page_size,
+            false => 1 << chunk_size.max(1).ilog2(),
+        };
+        let mut chunks = Vec::new();
+        let mut address = range.0;
+        while address < range.1 {
+            let end = (address / size + 1).saturating_mul(size).min(range.1);
+            chunks.push(WorkChunk { region: *range, address, len: end - address });
+            address = end;
+        }
+        chunks
+    }).collect()
+}
+
+// Keeps the bytes every worker of a scan reads together under the options' throttle. Each read
+// takes its length out of a bucket refilled at the throttled rate, and a worker that leaves it
+// overdrawn sleeps until its share of the debt is paid back. The bucket holds THROTTLE_BURST's
+// worth, so a pause in reading is not made up for with a burst after it
+pub(crate) struct Throttle {
+    rate: Option<f64>,
+    bucket: Mutex<(Instant, f64)>,
+}
+
+impl Throttle {
+    pub(crate) fn new(throttle: Option<u64>) -> Throttle {
+        Throttle { rate: throttle.filter(|x| *x > 0).map(|x| x as f64), bucket: Mutex::new((Instant::now(), 0.0)) }
+    }
+
+    // Waits, if need be, for the bytes to be read within the rate
+    pub(crate) fn take(&self, bytes: usize) {
+        let Some(rate) = self.rate else { return };
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let now = Instant::now();
+            let refilled = (bucket.1 + (now - bucket.0).as_secs_f64() * rate).min(rate * THROTTLE_BURST.as_secs_f64());
+            *bucket = (now, refilled - bytes as f64);
+            -bucket.1 / rate
+        };
+        if wait > 0.0 {
+            std::thread::sleep(Duration::from_secs_f64(wait));
+        }
+    }
+}
+
+// Reports progress at most once per PROGRESS_INTERVAL, whichever worker happens to get there first
+pub(crate) struct ProgressReporter<'a> {
+    callback: Option<&'a ProgressCallback>,
+    throttle: Option<u64>,
+    start: Instant,
+    bytes_total: usize,
+    last: Mutex<(Instant, usize)>,
+}
+
+impl<'a> ProgressReporter<'a> {
+    pub(crate) fn new(options: &'a ScanOptions, bytes_total: usize) -> ProgressReporter<'a> {
+        let start = Instant::now();
+        ProgressReporter { callback: options.progress.as_ref(), throttle: options.throttle.filter(|x| *x > 0), start, bytes_total, last: Mutex::new((start, 0)) }
+    }
+
+    pub(crate) fn update(&self, bytes_scanned: usize) {
+        let Some(callback) = self.callback else { return };
+        let Ok(mut last) = self.last.try_lock() else { return };
+        let now = Instant::now();
+        let since = now - last.0;
+        if since < PROGRESS_INTERVAL {
+            return;
+        }
+        let rate = bytes_scanned.saturating_sub(last.1) as f64 / since.as_secs_f64();
+        *last = (now, bytes_scanned);
+        drop(last);
+        (callback.0)(&ScanProgress { bytes_scanned, bytes_total: self.bytes_total, elapsed: now - self.start, rate, throttle: self.throttle });
+    }
+}
+
+// Offsets within a chunk's buffer at which a match may start
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ChunkOffsets {
+    first: usize,
+    end: usize,
+    step: usize,
+    // The chunk's own bytes, before what is read past it
+    len: usize,
+}
+
+impl ChunkOffsets {
+    pub(crate) fn iter(&self) -> std::iter::StepBy<std::ops::Range<usize>> {
+        (self.first..self.end).step_by(self.step)
+    }
+
+    // Every offset in the chunk, for matches of varying length that check for themselves that they
+    // fit, so one shorter than the longest is still seen at the end of a region. The lead bytes
+    // before each are what the match looks at ahead of where it starts, which is what is aligned
+    pub(crate) fn iter_led(&self, lead: usize) -> std::iter::StepBy<std::ops::Range<usize>> {
+        ((self.first + self.step - lead % self.step) % self.step..self.len).step_by(self.step)
+    }
+}
+
+// What a matcher reports, found at offsets into the chunk's buffer and moved to addresses once the
+// chunk is done. Usually just the offset, but a hit can carry more, like which value it matched
+pub(crate) trait ChunkHit: Ord + Send + Sync {
+    fn shift(&mut self, base: usize);
+}
+
+impl ChunkHit for usize {
+    fn shift(&mut self, base: usize) {
+        *self += base;
+    }
+}
+
+// Drives a scan over every chunk, handing each worker's buffer to the matcher which appends the
+// offsets of any hits. `size` is the width of the values being matched, which decides how far a
+// chunk reads past its end
+pub(crate) fn scan_chunks<H: ChunkHit, F: Fn(&[u8], ChunkOffsets, &mut Vec<H>) + Sync>(process: impl ProcessMemory, options: &ScanOptions, size: usize, matcher: F) -> Result<(Vec<H>, ScanStats), Box<dyn std::error::Error>> {
+    let start = Instant::now();
+    let regions = process.memory_regions()?;
+    let mut stats = ScanStats { maps: Some(MapsFingerprint::new(&regions)), ..ScanStats::default() };
+    let ranges = select_ranges(&process, options, &mut stats)?;
+    let alignment = options.alignment.unwrap_or(1).max(1);
+    let found: Arc<RwLock<Vec<H>>> = Arc::new(RwLock::new(Vec::new()));
+    let found_count = AtomicUsize::new(0);
+    let bytes_scanned = AtomicUsize::new(0);
+    let (chunk_size, buffers, smaller) = at_priority(options.priority, || budget_chunk_size(options.memory_budget, SCAN_CHUNK_SIZE, size));
+    stats.fallbacks.extend(smaller);
+    // Whatever the buffers leave of the budget is for the results
+    let result_limit = options.memory_budget.map(|x| x.saturating_sub(buffers) / std::mem::size_of::<usize>());
+    let truncated = AtomicBool::new(false);
+    let chunks = split_regions_into_chunks(&ranges, &regions, chunk_size);
+    stats.bytes_total = chunks.iter().map(|x| x.len).sum();
+    let failed: Mutex<Vec<ReadFailure>> = Mutex::new(Vec::new());
+    let progress = ProgressReporter::new(options, stats.bytes_total);
+    let throttle = Throttle::new(options.throttle);
+    at_priority(options.priority, || chunks.par_iter().for_each_init(Vec::new, |data: &mut Vec<u8>, chunk| {
+        let count = found_count.load(Ordering::Relaxed);
+        if let Some(stop_after) = options.stop_after && count >= stop_after {
+            return;
+        }
+        if let Some(limit) = result_limit && count >= limit {
+            truncated.store(true, Ordering::Relaxed);
+            return;
+        }
+        // Read a little past the end of the chunk (without leaving the region) so that values
+        // straddling the seam are still seen, but only report hits that start inside the chunk
+        // so that the next chunk does not report them again. A read that comes back short is
+        // retried from the next page boundary, with what it skipped counted as lost
+        let chunk_end = chunk.address + chunk.len;
+        let mut address = chunk.address;
+        while address < chunk_end {
+            let data_len = (chunk_end + size - 1).min(chunk.region.1) - address;
+            data.resize(data_len, 0);
+            throttle.take(data_len);
+            let (read_len, error) = match read_bytes_into(&process, address, data) {
+                Ok(read_len) => (read_len, "short read".to_string()),
+                // Nothing of the chunk could be read, so it is not tried page by page
+                Err(e) if address == chunk.address => return add_failure(&mut failed.lock().unwrap(), chunk.region, read_error_name(e.as_ref()), chunk.len),
+                Err(e) => (0, read_error_name(e.as_ref())),
+            };
+            let scanned = read_len.min(chunk_end - address);
+            progress.update(bytes_scanned.fetch_add(scanned, Ordering::Relaxed) + scanned);
+            let offsets = ChunkOffsets {
+                first: (alignment - address % alignment) % alignment,
+                end: scanned.min((read_len + 1).saturating_sub(size)),
+                step: alignment,
+                len: scanned,
+            };
+            let mut local = Vec::new();
+            matcher(&data[..read_len], offsets, &mut local);
+            if !local.is_empty() {
+                local.iter_mut().for_each(|x| x.shift(address));
+                let before = found_count.fetch_add(local.len(), Ordering::Relaxed);
+                if let Some(limit) = result_limit && before + local.len() > limit {
+                    local.truncate(limit.saturating_sub(before));
+                    truncated.store(true, Ordering::Relaxed);
+                }
+                found.write().unwrap().append(&mut local);
+            }
+            if address + read_len >= chunk_end {
+                break;
+            }
+            let page_size = regions.iter().find(|x| x.contains(chunk.region.0)).map(|x| x.page_size()).unwrap_or_else(page_size);
+            let next = ((address + read_len) / page_size + 1).saturating_mul(page_size).min(chunk_end);
+            add_failure(&mut failed.lock().unwrap(), chunk.region, error, next - address - read_len);
+            address = next;
+        }
+    }));
+    let mut found = Arc::into_inner(found).unwrap().into_inner().unwrap();
+    // Chunks only report hits that start in them, but maps can list ranges that overlap, so the
+    // same address may still be found twice; everything keyed by address needs it once
+    found.par_sort();
+    found.dedup();
+    stats.read_failures = failed.into_inner().unwrap();
+    stats.read_failures.sort_by(|a, b| a.region.cmp(&b.region).then(a.error.cmp(&b.error)));
+    stats.failed_regions = stats.read_failures.iter().map(|x| x.region).collect();
+    stats.failed_regions.dedup();
+    stats.matches = found.len();
+    stats.elapsed = start.elapsed();
+    stats.bytes_scanned = bytes_scanned.into_inner();
+    stats.partial = options.stop_after.is_some_and(|x| found.len() >= x) && stats.bytes_scanned < stats.bytes_total;
+    if truncated.into_inner() {
+        stats.partial = true;
+        stats.fallbacks.push(BudgetFallback::TruncatedResults(found.len()));
+    }
+    Ok((found, stats))
+}
+
+// Compares fixed-size windows against the patterns; N being a constant lets the compiler turn each
+// comparison into a single integer compare
+fn match_patterns<const N: usize>(data: &[u8], offsets: ChunkOffsets, patterns: &[[u8; N]], found: &mut Vec<usize>) {
+    if offsets.first >= offsets.end {
+        return;
+    }
+    let windows = data[offsets.first..offsets.end + N - 1].windows(N).map(|x| <[u8; N]>::try_from(x).unwrap());
+    // The single pattern, unaligned case is by far the most common, so keep its loop free of
+    // anything that would stop it vectorising
+    match (patterns, offsets.step) {
+        ([pattern], 1) => found.extend(windows.enumerate().filter(|(_, x)| x == pattern).map(|(index, _)| offsets.first + index)),
+        ([pattern], step) => found.extend(windows.step_by(step).enumerate().filter(|(_, x)| x == pattern).map(|(index, _)| offsets.first + index * step)),
+        (patterns, step) => found.extend(windows.step_by(step).enumerate().filter(|(_, x)| patterns.contains(x)).map(|(index, _)| offsets.first + index * step)),
+    }
+}
+
+fn match_pattern_bytes<const N: usize>(data: &[u8], offsets: ChunkOffsets, patterns: &[Vec<u8>], found: &mut Vec<usize>) {
+    let patterns = patterns.iter().map(|x| x.as_slice().try_into().unwrap()).collect::<Vec<[u8; N]>>();
+    match_patterns::<N>(data, offsets, &patterns, found);
+}
+
+// Matches any type by reading it out of the buffer at every offset, for types that have no fixed
+// byte representation to compare against. FromBytes makes any bytes there a valid T
+fn match_unaligned<T: FromBytes, F: Fn(&T) -> bool>(data: &[u8], offsets: ChunkOffsets, matches: &F, found: &mut Vec<usize>) {
+    let size = std::mem::size_of::<T>();
+    for offset in offsets.iter() {
+        if T::read_from_bytes(&data[offset..offset + size]).is_ok_and(|x| matches(&x)) {
+            found.push(offset);
+        }
+    }
+}
+
+pub fn find_value<T: Scalar>(process: impl ProcessMemory, value: T, options: &ScanOptions) -> Result<(Vec<usize>, ScanStats), Box<dyn std::error::Error>> {
+    let patterns = value.equal_patterns_in(options.endianness);
+    scan_chunks(process, options, T::SIZE, |data, offsets, found| {
+        match T::SIZE {
+            1 => match_pattern_bytes::<1>(data, offsets, &patterns, found),
+            2 => match_pattern_bytes::<2>(data, offsets, &patterns, found),
+            4 => match_pattern_bytes::<4>(data, offsets, &patterns, found),
+            8 => match_pattern_bytes::<8>(data, offsets, &patterns, found),
+            _ => {
+                for offset in offsets.iter() {
+                    if patterns.iter().any(|x| data[offset..offset + T::SIZE] == x[..]) {
+                        found.push(offset);
+                    }
+                }
+            }
+        }
+    })
+}
+
+// Equality scan for arbitrary types, e.g. structs, comparing with PartialEq at every offset
+pub fn find_value_generic<T: FromBytes + PartialEq + Send + Sync>(process: impl ProcessMemory, value: T, options: &ScanOptions) -> Result<(Vec<usize>, ScanStats), Box<dyn std::error::Error>> {
+    scan_chunks(process, options, std::mem::size_of::<T>(), |data, offsets, found| match_unaligned(data, offsets, &|x: &T| *x == value, found))
+}
+
+pub fn find_value_by_predicate<T: FromBytes>(process: impl ProcessMemory, predicate: fn(&T) -> bool, options: &ScanOptions) -> Result<(Vec<usize>, ScanStats), Box<dyn std::error::Error>> {
+    scan_chunks(process, options, std::mem::size_of::<T>(), |data, offsets, found| match_unaligned(data, offsets, &predicate, found))
+}
+
+// Compares the layout against the one the results were found in, recording any change in the
+// stats, and drops results outside every readable region if the options ask for it. Returns the
+// layout, for the reduce's read failures to be grouped by
+fn check_maps(process: &impl ProcessMemory, found_values: &mut Vec<usize>, options: &ScanOptions, stats: &mut ScanStats) -> Result<Vec<MemoryRegion>, Box<dyn std::error::Error>> {
+    let regions = process.memory_regions()?;
+    let current = MapsFingerprint::new(&regions);
+    stats.maps_change = stats.maps.as_ref().map(|x| x.compare(&current)).filter(|x| !x.is_empty());
+    stats.dropped_unmapped = 0;
+    if stats.maps_change.is_some() && options.drop_unmapped {
+        let before = found_values.len();
+        found_values.retain(|address| {
+            let index = regions.partition_point(|x| x.start <= *address);
+            index > 0 && regions[index - 1].contains(*address) && regions[index - 1].readable
+        });
+        stats.dropped_unmapped = before - found_values.len();
+    }
+    stats.maps = Some(current);
+    Ok(regions)
+}
+
+// Drops the results at the given indices in one pass. Removing them one at a time is quadratic,
+// which a reduce of the millions of results a bit scan finds would never get through
+fn remove_indices(found_values: &mut Vec<usize>, to_remove: &mut [usize]) {
+    to_remove.par_sort();
+    let mut next = to_remove.iter().peekable();
+    let mut index = 0;
+    found_values.retain(|_| {
+        let keep = next.next_if_eq(&&index).is_none();
+        index += 1;
+        keep
+    });
+}
+
+// `stats` are those of the scan or reduce that produced the results, and are updated to describe
+// this reduce
+pub fn reduce_found_values<T: Scalar>(process: impl ProcessMemory, found_values: &mut Vec<usize>, value: T, options: &ScanOptions, stats: &mut ScanStats) -> Result<(), Box<dyn std::error::Error>> {
+    reduce_scalars(process, found_values, &|x: T| x == value, options, stats)
+}
+
+// Keeps the results whose value the predicate holds of, read in the options' byte order
+fn reduce_scalars<T: Scalar, F: Fn(T) -> bool + Sync>(process: impl ProcessMemory, found_values: &mut Vec<usize>, predicate: &F, options: &ScanOptions, stats: &mut ScanStats) -> Result<(), Box<dyn std::error::Error>> {
+    let regions = check_maps(&process, found_values, options, stats)?;
+    let to_remove: Arc<RwLock<Vec<usize>>> = Arc::new(RwLock::new(Vec::with_capacity(found_values.len())));
+    let retried = AtomicUsize::new(0);
+    let unread: Mutex<Vec<(usize, String)>> = Mutex::new(Vec::new());
+    found_values.par_chunks(REDUCE_BATCH).enumerate().for_each(|(chunk, addresses)| {
+        for (index, read) in read_many_in::<T>(&process, addresses, options.endianness).into_iter().enumerate() {
+            let (read, retries) = options.retry.after(read, || read_scalar::<T>(&process, addresses[index], options.endianness));
+            if retries > 0 && read.is_ok() {
+                retried.fetch_add(1, Ordering::Relaxed);
+            }
+            match read {
+                Ok(x) if !predicate(x) => to_remove.write().unwrap().push(chunk * REDUCE_BATCH + index),
+                Ok(_) => {}
+                Err(e) => unread.lock().unwrap().push((addresses[index], read_error_name(e.as_ref()))),
+            }
+        }
+    });
+    stats.retried = retried.into_inner();
+    stats.read_failures = reduce_failures(&regions, unread.into_inner().unwrap(), T::SIZE);
+    remove_indices(found_values, &mut to_remove.write().unwrap());
+    stats.matches = found_values.len();
+    Ok(())
+}
+
+// What each result was when it was first found, which the rescans narrowing the results since
+// leave alone, to go back to once a value is restored, as by loading a save. Each value's bytes
+// are kept in a u64, which any numeric type fits in, sorted by address
+#[derive(Debug, Clone, Default)]
+pub struct InitialValues {
+    values: Vec<(usize, u64)>,
+    size: usize,
+}
+
+// Floats a few units in the last place apart still count as the same value
+pub(crate) const INITIAL_FLOAT_ULPS: f64 = 4.0;
+
+fn to_bits<T: Scalar>(value: T) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes[..T::SIZE].copy_from_slice(&value.to_bytes());
+    u64::from_ne_bytes(bytes)
+}
+
+impl InitialValues {
+    // Reads the values as they are now; those that cannot be read have none
+    pub fn record<T: Scalar>(process: impl ProcessMemory, found_values: &[usize], endianness: Endianness) -> InitialValues {
+        let read = read_many_in::<T>(&process, found_values, endianness);
+        let mut values = found_values.iter().zip(read).filter_map(|(address, x)| Some((*address, to_bits(x.ok()?)))).collect::<Vec<(usize, u64)>>();
+        values.par_sort_unstable_by_key(|x| x.0);
+        InitialValues { values, size: T::SIZE }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    // None if it was not recorded, or was recorded as a type of another size
+    pub fn get<T: Scalar>(&self, address: usize) -> Option<T> {
+        if T::SIZE != self.size {
+            return None;
+        }
+        let index = self.values.binary_search_by_key(&address, |x| x.0).ok()?;
+        Some(T::from_bytes(&self.values[index].1.to_ne_bytes()[..T::SIZE]))
+    }
+}
+
+// Keeps the results that are what they were when first found, floats within a few units in the
+// last place of it. Results with no initial value are dropped, and those that cannot be read kept,
+// as by reduce_found_values
+pub fn reduce_to_initial<T: Scalar>(process: impl ProcessMemory, found_values: &mut Vec<usize>, initial: &InitialValues, options: &ScanOptions, stats: &mut ScanStats) -> Result<(), Box<dyn std::error::Error>> {
+    if initial.size != T::SIZE {
+        return Err(format!("The initial values are {}-byte values, not {}-byte ones", initial.size, T::SIZE).into());
+    }
+    let tolerance = T::epsilon().to_f64() * INITIAL_FLOAT_ULPS;
+    // NaN is the same as nothing, not even a NaN with the same bits, as it equals nothing in a scan
+    // either. An infinity is only the same as itself, as any tolerance relative to it is infinite too
+    let same = |initial: T, x: T| !initial.to_f64().is_nan() && !x.to_f64().is_nan() && (to_bits(x) == to_bits(initial) || initial.to_f64().is_finite() && Scalar::abs_diff(x, initial).to_f64() <= tolerance * initial.to_f64().abs());
+    reduce_by_previous(process, found_values, initial, &same, options, stats)
+}
+
+// Keeps the results whose value is what the expression makes of the one they had when last read,
+// which the values given hold, dropping and keeping the rest as reduce_to_initial does
+pub fn reduce_by_expression<T: Scalar>(process: impl ProcessMemory, found_values: &mut Vec<usize>, last: &InitialValues, expression: &Expression, options: &ScanOptions, stats: &mut ScanStats) -> Result<(), Box<dyn std::error::Error>> {
+    if last.size != T::SIZE {
+        return Err(format!("The results were last read as {}-byte values, not {}-byte ones", last.size, T::SIZE).into());
+    }
+    reduce_by_previous(process, found_values, last, &|old: T, new: T| expression.matches(old, new), options, stats)
+}
+
+// Whether the float is shown as the integer once rounded to the nearest one, halves rounding away
+// from zero: 99.5 up to but not including 100.5 is shown as 100. This is not a tolerance around it,
+// as 100.5 is 0.5 from 100 and still shown as 101
+pub fn rounds_to<T: Scalar>(x: T, value: i64) -> bool {
+    x.to_f64().round() == value as f64
+}
+
+// Rounded scans are only for floats
+pub fn check_rounded<T: Scalar>() -> Result<(), Box<dyn std::error::Error>> {
+    match T::default().to_i128() {
+        None => Ok(()),
+        Some(_) => Err(format!("{} values are integers already; rounded scans are for the floats a UI shows rounded, so scan f32 or f64", T::NAME).into()),
+    }
+}
+
+// Finds the floats shown as the integer once rounded, as by rounds_to
+pub fn find_rounded<T: Scalar>(process: impl ProcessMemory, value: i64, options: &ScanOptions) -> Result<(Vec<usize>, ScanStats), Box<dyn std::error::Error>> {
+    check_rounded::<T>()?;
+    let endianness = options.endianness;
+    let (found, stats) = scan_chunks(process, options, T::SIZE, |data, offsets, found| {
+        for offset in offsets.iter() {
+            let bytes = &data[offset..offset + T::SIZE];
+            let x = if endianness.is_native() { T::from_bytes(bytes) } else { T::from_bytes_in(bytes, endianness) };
+            if rounds_to(x, value) {
+                found.push(offset);
+            }
+        }
+    })?;
+    Ok((found, ScanStats { rounded: true, ..stats }))
+}
+
+// Keeps the results still shown as the integer once rounded, which rescans for a change then compare
+// rounded too
+pub fn reduce_found_rounded<T: Scalar>(process: impl ProcessMemory, found_values: &mut Vec<usize>, value: i64, options: &ScanOptions, stats: &mut ScanStats) -> Result<(), Box<dyn std::error::Error>> {
+    check_rounded::<T>()?;
+    reduce_scalars(process, found_values, &|x: T| rounds_to(x, value), options, stats)?;
+    stats.rounded = true;
+    Ok(())
+}
+
+// Keeps the results for which keep holds of the value they had when last read and the one they
+// have now, both rounded to the nearest integer, so "increased" means the integer shown went up
+pub fn reduce_rounded_change<T: Scalar>(process: impl ProcessMemory, found_values: &mut Vec<usize>, last: &InitialValues, keep: fn(f64, f64) -> bool, options: &ScanOptions, stats: &mut ScanStats) -> Result<(), Box<dyn std::error::Error>> {
+    check_rounded::<T>()?;
+    if last.size != T::SIZE {
+        return Err(format!("The results were last read as {}-byte values, not {}-byte ones", last.size, T::SIZE).into());
+    }
+    reduce_by_previous(process, found_values, last, &|old: T, new: T| keep(old.to_f64().round(), new.to_f64().round()), options, stats)
+}
+
+// Keeps the results for which keep holds of the value recorded for them and the one they have now
+fn reduce_by_previous<T: Scalar, F: Fn(T, T) -> bool + Sync>(process: impl ProcessMemory, found_values: &mut Vec<usize>, previous: &InitialValues, keep: &F, options: &ScanOptions, stats: &mut ScanStats) -> Result<(), Box<dyn std::error::Error>> {
+    let regions = check_maps(&process, found_values, options, stats)?;
+    let to_remove: Arc<RwLock<Vec<usize>>> = Arc::new(RwLock::new(Vec::with_capacity(found_values.len())));
+    let retried = AtomicUsize::new(0);
+    let unread: Mutex<Vec<(usize, String)>> = Mutex::new(Vec::new());
+    found_values.par_chunks(REDUCE_BATCH).enumerate().for_each(|(chunk, addresses)| {
+        for (index, read) in read_many_in::<T>(&process, addresses, options.endianness).into_iter().enumerate() {
+            let (read, retries) = options.retry.after(read, || read_scalar::<T>(&process, addresses[index], options.endianness));
+            if retries > 0 && read.is_ok() {
+                retried.fetch_add(1, Ordering::Relaxed);
+            }
+            let kept = match (previous.get::<T>(addresses[index]), read) {
+                (None, _) => false,
+                (Some(_), Err(e)) => {
+                    unread.lock().unwrap().push((addresses[index], read_error_name(e.as_ref())));
+                    true
+                }
+                (Some(previous), Ok(x)) => keep(previous, x),
+            };
+            if !kept {
+                to_remove.write().unwrap().push(chunk * REDUCE_BATCH + index);
+            }
+        }
+    });
+    stats.retried = retried.into_inner();
+    stats.read_failures = reduce_failures(&regions, unread.into_inner().unwrap(), T::SIZE);
+    remove_indices(found_values, &mut to_remove.write().unwrap());
+    stats.matches = found_values.len();
+    Ok(())
+}
+
+pub fn reduce_found_values_by_predicate<T: FromBytes + IntoBytes>(process: impl ProcessMemory, found_values: &mut Vec<usize>, predicate: fn(&T) -> bool, options: &ScanOptions, stats: &mut ScanStats) -> Result<(), Box<dyn std::error::Error>> {
+    reduce_by(process, found_values, &predicate, options, stats)
+}
+
+fn reduce_by<T: FromBytes + IntoBytes, F: Fn(&T) -> bool + Sync>(process: impl ProcessMemory, found_values: &mut Vec<usize>, predicate: &F, options: &ScanOptions, stats: &mut ScanStats) -> Result<(), Box<dyn std::error::Error>> {
+    let regions = check_maps(&process, found_values, options, stats)?;
+    let to_remove: Arc<RwLock<Vec<usize>>> = Arc::new(RwLock::new(Vec::with_capacity(found_values.len())));
+    let retried = AtomicUsize::new(0);
+    let unread: Mutex<Vec<(usize, String)>> = Mutex::new(Vec::new());
+    found_values.par_iter().enumerate().for_each(|(index, address)| {
+        let mut x = T::new_zeroed();
+        let (read, retries) = options.retry.run(|| read_into(&process, *address, &mut x));
+        if retries > 0 && read.is_ok() {
+            retried.fetch_add(1, Ordering::Relaxed);
+        }
+        match read {
+            Ok(read) if read < std::mem::size_of::<T>() => unread.lock().unwrap().push((*address, "short read".to_string())),
+            Ok(_) if !predicate(&x) => to_remove.write().unwrap().push(index),
+            Ok(_) => {}
+            Err(e) => unread.lock().unwrap().push((*address, read_error_name(e.as_ref()))),
+        }
+    });
+    stats.retried = retried.into_inner();
+    stats.read_failures = reduce_failures(&regions, unread.into_inner().unwrap(), std::mem::size_of::<T>());
+    remove_indices(found_values, &mut to_remove.write().unwrap());
+    stats.matches = found_values.len();
+    Ok(())
+}
+
+// Finds the bytes whose given bit is set, or clear. Bits are numbered as in read_bits, 0 being the
+// lowest, so a flag at bit 10 of a field is bit 2 of the field's second byte
+pub fn find_bit(process: impl ProcessMemory, bit: u32, set: bool, options: &ScanOptions) -> Result<(Vec<usize>, ScanStats), Box<dyn std::error::Error>> {
+    check_bit(bit)?;
+    scan_chunks(process, options, 1, |data, offsets, found| match_unaligned(data, offsets, &|x: &u8| (x >> bit & 1 == 1) == set, found))
+}
+
+// Keeps the results whose given bit is still set, or clear
+pub fn reduce_found_bits(process: impl ProcessMemory, found_values: &mut Vec<usize>, bit: u32, set: bool, options: &ScanOptions, stats: &mut ScanStats) -> Result<(), Box<dyn std::error::Error>> {
+    check_bit(bit)?;
+    reduce_by(process, found_values, &|x: &u8| (x >> bit & 1 == 1) == set, options, stats)
+}
+
+// How a byte holds a boolean. 0 is always off; on is 1, 255 as some compilers and VB-style code
+// store true, or anything else
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FlagConvention {
+    #[default]
+    One,
+    AllOnes,
+    Nonzero,
+}
+
+impl FlagConvention {
+    // Whether the byte holds a flag at all, and if so whether it is on
+    fn state(self, byte: u8) -> Option<bool> {
+        match (self, byte) {
+            (_, 0) => Some(false),
+            (FlagConvention::One, 1) | (FlagConvention::AllOnes, 255) | (FlagConvention::Nonzero, _) => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for FlagConvention {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FlagConvention::One => write!(f, "1"),
+            FlagConvention::AllOnes => write!(f, "255"),
+            FlagConvention::Nonzero => write!(f, "nonzero"),
+        }
+    }
+}
+
+impl std::str::FromStr for FlagConvention {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1" | "0/1" => Ok(FlagConvention::One),
+            "255" | "0/255" | "0xff" => Ok(FlagConvention::AllOnes),
+            "nonzero" | "0/nonzero" => Ok(FlagConvention::Nonzero),
+            _ => Err(format!("Unknown flag convention '{}', expected 1, 255 or nonzero for what on is stored as", s)),
+        }
+    }
+}
+
+// Finds the bytes holding a flag in the given state, or in either when state is None. The byte
+// values are compared as patterns, as find_value does, so only they are ever returned; a nonzero
+// flag needs a state, as either state of it is every byte there is
+pub fn find_flags(process: impl ProcessMemory, convention: FlagConvention, state: Option<bool>, options: &ScanOptions) -> Result<(Vec<usize>, ScanStats), Box<dyn std::error::Error>> {
+    let patterns = match (convention, state) {
+        (_, Some(false)) => vec![[0]],
+        (FlagConvention::One, Some(true)) => vec![[1]],
+        (FlagConvention::AllOnes, Some(true)) => vec![[255]],
+        (FlagConvention::One, None) => vec![[0], [1]],
+        (FlagConvention::AllOnes, None) => vec![[0], [255]],
+        (FlagConvention::Nonzero, Some(true)) => return scan_chunks(process, options, 1, |data, offsets, found| match_unaligned(data, offsets, &|x: &u8| *x != 0, found)),
+        (FlagConvention::Nonzero, None) => return Err("A flag stored as 0 or anything else could be any byte, so scan for it on or off".into()),
+    };
+    scan_chunks(process, options, 1, |data, offsets, found| match_patterns::<1>(data, offsets, &patterns, found))
+}
+
+// Keeps the results holding a flag that is now in the given state
+pub fn reduce_found_flags(process: impl ProcessMemory, found_values: &mut Vec<usize>, convention: FlagConvention, on: bool, options: &ScanOptions, stats: &mut ScanStats) -> Result<(), Box<dyn std::error::Error>> {
+    reduce_by(process, found_values, &|x: &u8| convention.state(*x) == Some(on), options, stats)
+}
+
+// The value and mask as bytes in memory order, so a candidate's bytes can be masked and compared
+// as they are. Refuses what could never match or would match everything
+fn masked_bytes(value: u64, mask: u64, width: usize, endianness: Endianness) -> Result<(Vec<u8>, Vec<u8>), Box<dyn std::error::Error>> {
+    if ![1, 2, 4, 8].contains(&width) {
+        return Err(format!("A masked scan is over 1, 2, 4 or 8 bytes, not {}", width).into());
+    }
+    let bits = u64::MAX >> (64 - width * 8);
+    if mask & bits == 0 {
+        return Err(format!("The mask 0x{:x} leaves no bits of a {}-byte value to compare", mask, width).into());
+    }
+    if value & !(mask & bits) != 0 {
+        return Err(format!("0x{:x} has bits outside the mask 0x{:x}, so nothing masked could equal it", value, mask & bits).into());
+    }
+    let in_memory = |x: u64| {
+        let mut bytes = match width {
+            1 => (x as u8).to_bytes(),
+            2 => (x as u16).to_bytes(),
+            4 => (x as u32).to_bytes(),
+            _ => x.to_bytes(),
+        };
+        endianness.convert(&mut bytes);
+        bytes
+    };
+    Ok((in_memory(value), in_memory(mask & bits)))
+}
+
+// Finds the width-byte values that equal the value in the bits the mask has set, whatever the
+// others hold, as in (x & 0x00ffff00) == 0x00123400. Unless the options say otherwise, only
+// multiples of the width are looked at, as a field that wide would be aligned to them
+pub fn find_masked(process: impl ProcessMemory, value: u64, mask: u64, width: usize, options: &ScanOptions) -> Result<(Vec<usize>, ScanStats), Box<dyn std::error::Error>> {
+    let (value, mask) = masked_bytes(value, mask, width, options.endianness)?;
+    let options = ScanOptions { alignment: Some(options.alignment.unwrap_or(width)), ..options.clone() };
+    scan_chunks(process, &options, width, |data, offsets, found| {
+        for offset in offsets.iter() {
+            if data[offset..offset + width].iter().zip(&mask).zip(&value).all(|((x, mask), value)| x & mask == *value) {
+                found.push(offset);
+            }
+        }
+    })
+}
+
+// Keeps the results whose masked bits still equal the value
+pub fn reduce_found_masked(process: impl ProcessMemory, found_values: &mut Vec<usize>, value: u64, mask: u64, width: usize, options: &ScanOptions, stats: &mut ScanStats) -> Result<(), Box<dyn std::error::Error>> {
+    let (value, mask) = masked_bytes(value, mask, width, options.endianness)?;
+    let matches = |x: &[u8]| x.iter().zip(&mask).zip(&value).all(|((x, mask), value)| x & mask == *value);
+    match width {
+        1 => reduce_by(process, found_values, &|x: &[u8; 1]| matches(x), options, stats),
+        2 => reduce_by(process, found_values, &|x: &[u8; 2]| matches(x), options, stats),
+        4 => reduce_by(process, found_values, &|x: &[u8; 4]| matches(x), options, stats),
+        _ => reduce_by(process, found_values, &|x: &[u8; 8]| matches(x), options, stats),
+    }
+}
+
+fn check_bit(bit: u32) -> Result<(), Box<dyn std::error::Error>> {
+    match bit < 8 {
+        true => Ok(()),
+        false => Err(format!("Bit scans look at a single byte, so the bit is 0 to 7, not {}", bit).into()),
+    }
+}
@@ -0,0 +1,327 @@
+use std::io::{BufRead, Write};
+use crate::{filter::{RegionCategory, classify_regions}, maps::{MemoryRegion, module_for_address, modules_from_regions}, platform::Pid, process::{Process, ProcessMemory, read_many_bytes, write_bytes_to_process}, scan::{ScanOptions, find_value}, value::{Endianness, TypedValue, ValueType}, with_scan_type};
+
+// Enough of scanmem's command language for frontends written against it, such as GameConqueror,
+// to drive this crate instead: one command per line on the input, with results on the output and
+// scanmem's "info:" and "error:" messages on the error stream. Supported are
+//     option scan_data_type int8|int16|int32|int64|int|float32|float64|float|number
+//     option endianness 0|1|2, for the host's, little or big
+//     <value>, or = <value>      exact value scan, narrowing the matches if there are any
+//     = != > < + -               compared with each match's value at the last scan
+//     > <value>, < <value>, != <value>, + <value>, - <value>, e.g. "+ 5" for increased by 5
+//     list, set [ids=]<value>, delete <ids>, reset, pid [pid], version, exit
+// where ids are match numbers as list shows them, separated by commas, or a range like 2..5.
+// scanmem's int and number types match every width at once; here they are int32, and float is
+// float32. Other options, such as region_scan_level, are accepted and ignored
+
+const VERSION: &str = concat!("memory ", env!("CARGO_PKG_VERSION"), ", scanmem-compatible mode");
+
+// A match's value, wide enough to compare any of the numeric types without losing anything
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Number {
+    Int(i128),
+    Float(f64),
+}
+
+impl Number {
+    fn from_typed(value: &TypedValue) -> Option<Number> {
+        Some(match *value {
+            TypedValue::I8(x) => Number::Int(x as i128),
+            TypedValue::I16(x) => Number::Int(x as i128),
+            TypedValue::I32(x) => Number::Int(x as i128),
+            TypedValue::I64(x) => Number::Int(x as i128),
+            TypedValue::U8(x) => Number::Int(x as i128),
+            TypedValue::U16(x) => Number::Int(x as i128),
+            TypedValue::U32(x) => Number::Int(x as i128),
+            TypedValue::U64(x) => Number::Int(x as i128),
+            TypedValue::F32(x) => Number::Float(x as f64),
+            TypedValue::F64(x) => Number::Float(x),
+            _ => return None,
+        })
+    }
+
+    fn minus(self, other: Number) -> Number {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => Number::Int(a - b),
+            (Number::Float(a), Number::Float(b)) => Number::Float(a - b),
+            _ => Number::Float(f64::NAN),
+        }
+    }
+
+    fn zero(self) -> Number {
+        match self {
+            Number::Int(_) => Number::Int(0),
+            Number::Float(_) => Number::Float(0.0),
+        }
+    }
+}
+
+// How a rescan compares a match's value now with its value at the last scan, or with an operand
+#[derive(Debug, Clone, Copy)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Greater,
+    Less,
+    Increased,
+    Decreased,
+}
+
+impl Comparison {
+    fn keeps(self, now: Number, last: Number, operand: Option<Number>) -> bool {
+        match (self, operand) {
+            (Comparison::Equal, Some(x)) => now == x,
+            (Comparison::Equal, None) => now == last,
+            (Comparison::NotEqual, Some(x)) => now != x,
+            (Comparison::NotEqual, None) => now != last,
+            (Comparison::Greater, Some(x)) => now > x,
+            (Comparison::Greater, None) => now > last,
+            (Comparison::Less, Some(x)) => now < x,
+            (Comparison::Less, None) => now < last,
+            (Comparison::Increased, Some(x)) => now.minus(last) == x,
+            (Comparison::Increased, None) => now.minus(last) > now.zero(),
+            (Comparison::Decreased, Some(x)) => last.minus(now) == x,
+            (Comparison::Decreased, None) => last.minus(now) > now.zero(),
+        }
+    }
+}
+
+pub struct ScanmemSession {
+    process: Option<Process>,
+    options: ScanOptions,
+    data_type: ValueType,
+    // Each match's address and the bytes it held at the last scan
+    matches: Vec<(usize, Vec<u8>)>,
+}
+
+impl ScanmemSession {
+    // Without a process, the frontend has to pick one with "pid" first
+    pub fn new(process: Option<Process>, options: ScanOptions) -> ScanmemSession {
+        ScanmemSession { process, options, data_type: ValueType::I32, matches: Vec::new() }
+    }
+
+    // Runs commands until the input ends or says exit. Output is flushed after every command,
+    // since a frontend waits on it before sending the next
+    pub fn run(&mut self, input: impl BufRead, output: &mut impl Write, errors: &mut impl Write) -> std::io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            match self.command(line.trim(), output, errors) {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(e) => writeln!(errors, "error: {}", e)?,
+            }
+            output.flush()?;
+            errors.flush()?;
+        }
+        Ok(())
+    }
+
+    // Returns false once told to exit
+    pub fn command(&mut self, line: &str, output: &mut impl Write, errors: &mut impl Write) -> Result<bool, Box<dyn std::error::Error>> {
+        let words = line.split_whitespace().collect::<Vec<&str>>();
+        match words.as_slice() {
+            [] => {}
+            ["exit" | "quit"] => return Ok(false),
+            ["version"] => writeln!(output, "{}", VERSION)?,
+            ["option", name, value] => self.set_option(name, value)?,
+            ["option", ..] => return Err("usage: option <name> <value>".into()),
+            ["pid"] => match &self.process {
+                Some(process) => writeln!(errors, "info: target pid is {}.", process.pid())?,
+                None => writeln!(errors, "info: no target selected.")?,
+            },
+            ["pid", pid] => {
+                self.process = Some(Process::attach(Pid::from_raw(pid.parse::<i32>().map_err(|_| format!("'{}' is not a pid", pid))?))?);
+                self.matches.clear();
+            }
+            ["reset"] => self.matches.clear(),
+            ["list"] => self.list(output)?,
+            ["delete", ids] => {
+                let mut ids = self.parse_ids(ids)?;
+                ids.sort_unstable();
+                ids.dedup();
+                for id in ids.into_iter().rev() {
+                    self.matches.remove(id);
+                }
+            }
+            ["set", assignment] => self.set(assignment, errors)?,
+            [op @ ("=" | "!=" | ">" | "<" | "+" | "-"), operand @ ..] if operand.len() <= 1 => {
+                let comparison = match *op {
+                    "=" => Comparison::Equal,
+                    "!=" => Comparison::NotEqual,
+                    ">" => Comparison::Greater,
+                    "<" => Comparison::Less,
+                    "+" => Comparison::Increased,
+                    _ => Comparison::Decreased,
+                };
+                match (comparison, operand.first()) {
+                    (Comparison::Equal, Some(value)) if self.matches.is_empty() => self.first_scan(value)?,
+                    _ => self.rescan(comparison, operand.first().copied())?,
+                }
+                writeln!(errors, "info: we currently have {} matches.", self.matches.len())?;
+            }
+            [value] if value.parse::<f64>().is_ok() => {
+                match self.matches.is_empty() {
+                    true => self.first_scan(value)?,
+                    false => self.rescan(Comparison::Equal, Some(value))?,
+                }
+                writeln!(errors, "info: we currently have {} matches.", self.matches.len())?;
+            }
+            _ => return Err(format!("unknown command '{}'", line).into()),
+        }
+        Ok(true)
+    }
+
+    fn process(&self) -> Result<&Process, Box<dyn std::error::Error>> {
+        self.process.as_ref().ok_or("no target selected, set one with \"pid <pid>\"".into())
+    }
+
+    fn set_option(&mut self, name: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        match name {
+            "scan_data_type" => {
+                self.data_type = match value {
+                    "int8" => ValueType::I8,
+                    "int16" => ValueType::I16,
+                    "int32" | "int" | "number" => ValueType::I32,
+                    "int64" => ValueType::I64,
+                    "float32" | "float" => ValueType::F32,
+                    "float64" => ValueType::F64,
+                    "bytearray" | "string" => return Err(format!("scan_data_type {} is not supported, only numeric types are", value).into()),
+                    _ => return Err(format!("bad value for scan_data_type: {}", value).into()),
+                };
+                self.matches.clear();
+            }
+            "endianness" => {
+                self.options.endianness = match value {
+                    "0" => Endianness::Native,
+                    "1" => Endianness::Little,
+                    "2" => Endianness::Big,
+                    _ => return Err(format!("bad value for endianness: {}", value).into()),
+                };
+            }
+            "region_scan_level" | "dump_with_ascii" | "detect_reverse_change" => {}
+            _ => return Err(format!("unknown option {}", name).into()),
+        }
+        Ok(())
+    }
+
+    fn parse(&self, value: &str) -> Result<TypedValue, Box<dyn std::error::Error>> {
+        self.data_type.parse_value(value).map_err(|_| format!("'{}' is not a valid {} value", value, self.data_type).into())
+    }
+
+    fn number(&self, bytes: &[u8]) -> Option<Number> {
+        Number::from_typed(&self.data_type.value_from_bytes_in(bytes, self.options.endianness).ok()?)
+    }
+
+    fn first_scan(&mut self, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let bytes = self.parse(value)?.to_bytes_in(self.options.endianness);
+        let process = self.process()?;
+        let found = with_scan_type!(self.data_type, T, {
+            find_value(process, value.parse::<T>()?, &self.options)?.0
+        });
+        self.matches = found.into_iter().map(|x| (x, bytes.clone())).collect();
+        Ok(())
+    }
+
+    // Reads every match again, keeping those that compare as asked and updating their values.
+    // Matches that can no longer be read are dropped
+    fn rescan(&mut self, comparison: Comparison, operand: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+        if self.matches.is_empty() {
+            return Err("there are no matches to compare, scan for a value first".into());
+        }
+        let operand = operand.map(|x| self.parse(x)).transpose()?.as_ref().and_then(Number::from_typed);
+        let size = self.data_type.size().unwrap_or(0);
+        let requests = self.matches.iter().map(|x| (x.0, size)).collect::<Vec<(usize, usize)>>();
+        let reads = read_many_bytes(self.process()?, &requests);
+        let mut kept = Vec::with_capacity(self.matches.len());
+        for ((address, last), read) in self.matches.iter().zip(reads) {
+            let Ok(now) = read else {
+                continue;
+            };
+            if let (Some(a), Some(b)) = (self.number(&now), self.number(last)) && comparison.keeps(a, b, operand) {
+                kept.push((*address, now));
+            }
+        }
+        self.matches = kept;
+        Ok(())
+    }
+
+    // One line per match in scanmem's layout: its number, address, region number and offset,
+    // region type, value at the last scan and type
+    fn list(&self, output: &mut impl Write) -> Result<(), Box<dyn std::error::Error>> {
+        if self.matches.is_empty() {
+            return Ok(());
+        }
+        let process = self.process()?;
+        let regions = process.memory_regions()?;
+        let categories = classify_regions(process, &regions);
+        let modules = modules_from_regions(&regions);
+        let flag = match self.data_type {
+            ValueType::I8 | ValueType::U8 => "I8",
+            ValueType::I16 | ValueType::U16 => "I16",
+            ValueType::I32 | ValueType::U32 => "I32",
+            ValueType::I64 | ValueType::U64 => "I64",
+            ValueType::F32 => "F32",
+            _ => "F64",
+        };
+        for (id, (address, bytes)) in self.matches.iter().enumerate() {
+            let index = regions.partition_point(|x| x.end <= *address);
+            let (region_type, offset) = match regions.get(index).filter(|x| x.contains(*address)) {
+                Some(region) => describe(region, categories[index], module_for_address(&modules, *address).map(|x| x.base), *address),
+                None => ("misc", *address),
+            };
+            let value = self.data_type.value_from_bytes_in(bytes, self.options.endianness)?.format_value();
+            writeln!(output, "[{:2}] {:12x}, {:2} + {:12x}, {:>5}, {}, [{} ]", id, address, index, offset, region_type, value, flag)?;
+        }
+        Ok(())
+    }
+
+    // "set 5" writes every match, "set 0=5" or "set 0,3=5" only those. Like scanmem, the value at
+    // the last scan stays what the next comparison is made against
+    fn set(&mut self, assignment: &str, errors: &mut impl Write) -> Result<(), Box<dyn std::error::Error>> {
+        if self.matches.is_empty() {
+            return Err("there are no matches to set".into());
+        }
+        let (ids, value) = match assignment.split_once('=') {
+            Some((ids, value)) => (self.parse_ids(ids)?, value),
+            None => ((0..self.matches.len()).collect(), assignment),
+        };
+        let bytes = self.parse(value)?.to_bytes_in(self.options.endianness);
+        let process = self.process()?;
+        for id in ids {
+            let address = self.matches[id].0;
+            match write_bytes_to_process(process, address, &bytes) {
+                Ok(written) if written == bytes.len() => {}
+                Ok(_) => writeln!(errors, "error: only part of match {} at {:x} could be written.", id, address)?,
+                Err(e) => writeln!(errors, "error: failed to set match {} at {:x}: {}.", id, address, e)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_ids(&self, s: &str) -> Result<Vec<usize>, Box<dyn std::error::Error>> {
+        let mut ids = Vec::new();
+        for part in s.split(',') {
+            let (first, last) = match part.split_once("..") {
+                Some((first, last)) => (first.parse::<usize>()?, last.parse::<usize>()?),
+                None => (part.parse::<usize>()?, part.parse::<usize>()?),
+            };
+            if first > last || last >= self.matches.len() {
+                return Err(format!("no match {}, there are {}", if first > last { first } else { last }, self.matches.len()).into());
+            }
+            ids.extend(first..=last);
+        }
+        Ok(ids)
+    }
+}
+
+// scanmem's name for the kind of region, and the address's offset into it, which for the
+// executable and libraries ("code") counts from the module's base rather than the region's start
+fn describe(region: &MemoryRegion, category: RegionCategory, module_base: Option<usize>, address: usize) -> (&'static str, usize) {
+    match category {
+        RegionCategory::MainExecutable => ("exe", address - module_base.unwrap_or(region.start)),
+        RegionCategory::FileBacked if module_base.is_some() => ("code", address - module_base.unwrap_or(region.start)),
+        RegionCategory::Heap => ("heap", address - region.start),
+        RegionCategory::Stack => ("stack", address - region.start),
+        _ => ("misc", address - region.start),
+    }
+}
@@ -0,0 +1,172 @@
+use std::{rc::Rc, sync::mpsc::Sender, time::{Duration, Instant}};
+use rhai::{Blob, Dynamic, Engine, EvalAltResult, INT, Scope};
+use crate::{maps::RegionCache, pointer::resolve_address, process::{Process, ProcessMemory, read_bytes_from_process, read_scalar}, value::Endianness};
+
+// Rhai scripts for when a list of commands is not enough, e.g.
+//     loop {
+//         if proc.read_i32(proc.resolve("game+0x1a2b0->0x10")) < 1000 {
+//             proc.write_u8(proc.resolve("game+0x5000"), 1);
+//         }
+//         sleep(500);
+//     }
+// A script gets
+//     proc.pid, proc.read_<type>(address) and proc.write_<type>(address, value) for the numeric
+//     types, proc.read_bytes(address, len), proc.write_bytes(address, blob) and
+//     proc.resolve(text) for anything the prompt takes as an address
+//     scan(type, value) and rescan(value), which return the number of results, and results()
+//     lock(address, type, value), unlock(address), command(line) for any other command,
+//     sleep(ms) and log(text)
+// Reads go straight to the process. Everything else is a command run by the session, the same as
+// one typed at the prompt, so writes are journaled and checked like any other
+
+// How often a sleeping script checks whether it has been stopped
+const SLEEP_SLICE: Duration = Duration::from_millis(10);
+
+// What a running script asks of the session
+pub enum ScriptRequest {
+    // A command line, answered with its error if it failed
+    Command(String, Sender<Result<(), String>>),
+    // The current scan results
+    Results(Sender<Vec<usize>>),
+}
+
+// Why a script ended early
+#[derive(Debug, PartialEq)]
+pub enum ScriptError {
+    // The host asked it to
+    Stopped,
+    Failed(String),
+}
+
+// The session end of a script: sends its requests, e.g. down the channel the prompt's commands
+// arrive on, says whether the script has been asked to stop and shows what it logs
+pub struct ScriptHost {
+    pub send: Box<dyn Fn(ScriptRequest) -> bool>,
+    pub stopped: Box<dyn Fn() -> bool>,
+    pub log: Box<dyn Fn(&str)>,
+}
+
+impl ScriptHost {
+    fn command(&self, line: String) -> Result<(), Box<EvalAltResult>> {
+        let (reply, answer) = std::sync::mpsc::channel();
+        if !(self.send)(ScriptRequest::Command(line.clone(), reply)) {
+            return Err("The session has ended".into());
+        }
+        match answer.recv() {
+            Ok(result) => result.map_err(|e| format!("{}: {}", line, e).into()),
+            Err(_) => Err("The session has ended".into()),
+        }
+    }
+
+    fn results(&self) -> Result<Vec<usize>, Box<EvalAltResult>> {
+        let (reply, answer) = std::sync::mpsc::channel();
+        if !(self.send)(ScriptRequest::Results(reply)) {
+            return Err("The session has ended".into());
+        }
+        answer.recv().map_err(|_| "The session has ended".into())
+    }
+}
+
+// The proc object
+#[derive(Clone)]
+struct ScriptProcess {
+    process: Process,
+    endianness: Endianness,
+    host: Rc<ScriptHost>,
+}
+
+fn error(e: Box<dyn std::error::Error>) -> Box<EvalAltResult> {
+    e.to_string().into()
+}
+
+fn address(address: INT) -> Result<usize, Box<EvalAltResult>> {
+    usize::try_from(address).map_err(|_| format!("{} is not an address", address).into())
+}
+
+macro_rules! register_scalars {
+    ($engine:expr, $(($t:ty, $name:literal, $script:ty)),*) => {
+        $(
+            $engine.register_fn(concat!("read_", $name), |p: &mut ScriptProcess, at: INT| -> Result<$script, Box<EvalAltResult>> {
+                Ok(read_scalar::<$t>(&p.process, address(at)?, p.endianness).map_err(error)? as $script)
+            });
+            $engine.register_fn(concat!("write_", $name), |p: &mut ScriptProcess, at: INT, value: $script| -> Result<(), Box<EvalAltResult>> {
+                p.host.command(format!("write 0x{:x} {}:{}", address(at)?, $name, value))
+            });
+        )*
+    };
+}
+
+// Runs the script to the end, or until the host says it has been stopped. Rhai's own print is
+// logged like log is
+pub fn run_script(source: &str, process: Process, endianness: Endianness, host: ScriptHost) -> Result<(), ScriptError> {
+    let host = Rc::new(host);
+    let mut engine = Engine::new();
+    let stopped = host.clone();
+    engine.on_progress(move |_| (stopped.stopped)().then(|| Dynamic::from("stopped")));
+    engine.register_type_with_name::<ScriptProcess>("Process");
+    engine.register_get("pid", |p: &mut ScriptProcess| p.process.pid().as_raw() as INT);
+    register_scalars!(engine,
+        (i8, "i8", INT), (i16, "i16", INT), (i32, "i32", INT), (i64, "i64", INT),
+        (u8, "u8", INT), (u16, "u16", INT), (u32, "u32", INT), (u64, "u64", INT),
+        (f32, "f32", f64), (f64, "f64", f64)
+    );
+    engine.register_fn("read_bytes", |p: &mut ScriptProcess, at: INT, len: INT| -> Result<Blob, Box<EvalAltResult>> {
+        read_bytes_from_process(&p.process, len.max(0) as usize, address(at)?).map_err(error)
+    });
+    engine.register_fn("write_bytes", |p: &mut ScriptProcess, at: INT, bytes: Blob| -> Result<(), Box<EvalAltResult>> {
+        let hex = bytes.iter().map(|x| format!("{:02x}", x)).collect::<String>();
+        p.host.command(format!("poke 0x{:x} {}", address(at)?, hex))
+    });
+    engine.register_fn("resolve", |p: &mut ScriptProcess, text: &str| -> Result<INT, Box<EvalAltResult>> {
+        let mut regions = RegionCache::from_process(std::sync::Arc::new(p.process.clone())).map_err(error)?;
+        Ok(resolve_address(&p.process, &mut regions, text).map_err(error)? as INT)
+    });
+    let scan_host = host.clone();
+    engine.register_fn("scan", move |scan_type: &str, value: Dynamic| -> Result<INT, Box<EvalAltResult>> {
+        scan_host.command(format!("scan {} {}", scan_type, value))?;
+        Ok(scan_host.results()?.len() as INT)
+    });
+    let rescan_host = host.clone();
+    engine.register_fn("rescan", move |value: Dynamic| -> Result<INT, Box<EvalAltResult>> {
+        rescan_host.command(format!("rescan {}", value))?;
+        Ok(rescan_host.results()?.len() as INT)
+    });
+    let results_host = host.clone();
+    engine.register_fn("results", move || -> Result<rhai::Array, Box<EvalAltResult>> {
+        Ok(results_host.results()?.into_iter().map(|x| Dynamic::from(x as INT)).collect())
+    });
+    let lock_host = host.clone();
+    engine.register_fn("lock", move |at: INT, value_type: &str, value: Dynamic| -> Result<(), Box<EvalAltResult>> {
+        lock_host.command(format!("lock 0x{:x} {} {}", address(at)?, value_type, value))
+    });
+    let unlock_host = host.clone();
+    engine.register_fn("unlock", move |at: INT| -> Result<(), Box<EvalAltResult>> {
+        unlock_host.command(format!("unlock 0x{:x}", address(at)?))
+    });
+    let command_host = host.clone();
+    engine.register_fn("command", move |line: &str| -> Result<(), Box<EvalAltResult>> {
+        command_host.command(line.to_string())
+    });
+    let sleep_host = host.clone();
+    engine.register_fn("sleep", move |ms: INT| -> Result<(), Box<EvalAltResult>> {
+        let until = Instant::now() + Duration::from_millis(ms.max(0) as u64);
+        while Instant::now() < until {
+            if (sleep_host.stopped)() {
+                return Err(EvalAltResult::ErrorTerminated(Dynamic::from("stopped"), rhai::Position::NONE).into());
+            }
+            std::thread::sleep(SLEEP_SLICE.min(until.saturating_duration_since(Instant::now())));
+        }
+        Ok(())
+    });
+    let log_host = host.clone();
+    engine.register_fn("log", move |text: Dynamic| (log_host.log)(&text.to_string()));
+    let print_host = host.clone();
+    engine.on_print(move |text| (print_host.log)(text));
+    let mut scope = Scope::new();
+    scope.push_constant("proc", ScriptProcess { process, endianness, host });
+    match engine.run_with_scope(&mut scope, source) {
+        Ok(()) => Ok(()),
+        Err(e) if matches!(*e, EvalAltResult::ErrorTerminated(..)) => Err(ScriptError::Stopped),
+        Err(e) => Err(ScriptError::Failed(e.to_string())),
+    }
+}
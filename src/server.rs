@@ -0,0 +1,569 @@
+use std::{collections::{BTreeMap, HashSet}, io::{BufRead, BufReader, Read, Write}, net::{SocketAddr, TcpListener}, path::PathBuf, str::FromStr, sync::{Arc, Mutex, MutexGuard, Weak}, time::Duration};
+#[cfg(unix)]
+use std::os::unix::{fs::PermissionsExt, net::{UnixListener, UnixStream}};
+use serde_json::{Value, json};
+use crate::{lock::{LockAction, LockManager}, maps::RegionCache, platform::Pid, pointer::resolve_address, process::{MemBackend, Process, ProcessMemory, read_bytes_from_process, read_many_bytes, read_many_in, read_string_lossy, write_bytes_to_process}, scan::{ProgressCallback, ScanOptions, ScanStats, find_value, reduce_found_values}, value::{ValueType, parse_hex_bytes}, with_scan_type};
+
+// The session exposed over a socket, one JSON object per line each way, for frontends that drive
+// the scanner from another process. A request is {"id": 1, "method": "read", "params": {...}}
+// and is answered by {"id": 1, "result": ...} or {"id": 1, "error": "..."}; the id is anything
+// the client likes and is echoed back. Clients that subscribe also get notifications, which have a
+// method and params but no id:
+//     attach {pid, backend?}                 -> {pid, backend}
+//     scan {type, value}, rescan {value}     -> {matches, bytes_scanned, elapsed_ms}, once finished
+//     list_results {offset?, limit?, type?}  -> {total, offset, results: [{address, location, value}]}
+//     read {address, type, len?}             -> {address, location, value}
+//     write {address, type, value, force?}   -> {written}
+//     lock {address, type, value, interval_ms?, force?}, unlock {address}, locks
+//     maps                                   -> [{start, end, permissions, offset, pathname}]
+//     watch {address, type, len?}            -> {watch}, then "watch" notifications on change
+//     unwatch {watch}
+//     subscribe {topics}, unsubscribe {topics}, with topics among "progress" and "watch"
+// Addresses are numbers or strings in any form resolve_address takes, e.g. "libgame.so+0x2a10".
+// Every client shares the one session. Scans run on their own thread and answer when done, so
+// a client can go on making requests, and watching progress, while one runs
+
+// Results in a list_results page unless the client asks for another number, and the most it can
+const DEFAULT_PAGE: usize = 100;
+const MAX_PAGE: usize = 10_000;
+// How often watched addresses are read
+pub const WATCH_INTERVAL: Duration = Duration::from_millis(100);
+// Longest string a read of a string type returns unless given a len
+const STRING_LIMIT: usize = 256;
+const TOPICS: [&str; 2] = ["progress", "watch"];
+
+// Where to listen, written as a path for a Unix socket or ip:port for TCP
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServeAddress {
+    Unix(PathBuf),
+    Tcp(SocketAddr),
+}
+
+impl FromStr for ServeAddress {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<SocketAddr>() {
+            Ok(address) => Ok(ServeAddress::Tcp(address)),
+            Err(_) if s.contains('/') => Ok(ServeAddress::Unix(PathBuf::from(s))),
+            Err(_) => Err(format!("Expected a socket path or ip:port to serve on, got '{}'", s).into()),
+        }
+    }
+}
+
+impl std::fmt::Display for ServeAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServeAddress::Unix(path) => write!(f, "{}", path.display()),
+            ServeAddress::Tcp(address) => write!(f, "{}", address),
+        }
+    }
+}
+
+enum Listener {
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+    Tcp(TcpListener),
+}
+
+pub struct Server {
+    listener: Listener,
+    shared: Arc<Shared>,
+}
+
+impl Server {
+    // Anyone who can connect can read and write the target's memory, so a TCP address other than
+    // loopback is refused unless allow_remote is set, and a Unix socket is only accessible to its
+    // owner. A leftover socket file nothing is listening on any more is replaced
+    pub fn bind(address: &ServeAddress, allow_remote: bool, options: ScanOptions) -> Result<Server, Box<dyn std::error::Error>> {
+        let listener = match address {
+            ServeAddress::Tcp(address) => {
+                if !address.ip().is_loopback() && !allow_remote {
+                    return Err(format!("Refusing to serve on {}, which is not loopback: anyone who can reach it could read and write the target's memory", address).into());
+                }
+                Listener::Tcp(TcpListener::bind(address)?)
+            }
+            #[cfg(unix)]
+            ServeAddress::Unix(path) => {
+                if path.exists() {
+                    if UnixStream::connect(path).is_ok() {
+                        return Err(format!("Something is already serving on {}", path.display()).into());
+                    }
+                    std::fs::remove_file(path)?;
+                }
+                let listener = UnixListener::bind(path)?;
+                std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+                Listener::Unix(listener, path.clone())
+            }
+            #[cfg(windows)]
+            ServeAddress::Unix(_) => return Err("Unix sockets are only served on Linux; give an ip:port to serve on instead".into()),
+        };
+        let shared = Arc::new(Shared { state: Mutex::new(State::new(options)), clients: Mutex::new(Vec::new()) });
+        let watched = Arc::downgrade(&shared);
+        std::thread::spawn(move || watch(watched));
+        Ok(Server { listener, shared })
+    }
+
+    // The address actually bound, e.g. the port picked for 127.0.0.1:0
+    pub fn local_address(&self) -> Result<ServeAddress, Box<dyn std::error::Error>> {
+        match &self.listener {
+            #[cfg(unix)]
+            Listener::Unix(_, path) => Ok(ServeAddress::Unix(path.clone())),
+            Listener::Tcp(listener) => Ok(ServeAddress::Tcp(listener.local_addr()?)),
+        }
+    }
+
+    // Attaches the session to a process before any client has connected
+    pub fn attach(&self, process: Process) -> Result<(), Box<dyn std::error::Error>> {
+        self.shared.state().attach(process)
+    }
+
+    // Accepts clients until accepting fails, each on its own thread
+    pub fn run(&self) -> Result<(), Box<dyn std::error::Error>> {
+        loop {
+            let (reader, writer): (Box<dyn Read + Send>, Box<dyn Write + Send>) = match &self.listener {
+                #[cfg(unix)]
+                Listener::Unix(listener, _) => {
+                    let stream = listener.accept()?.0;
+                    (Box::new(stream.try_clone()?), Box::new(stream))
+                }
+                Listener::Tcp(listener) => {
+                    let stream = listener.accept()?.0;
+                    (Box::new(stream.try_clone()?), Box::new(stream))
+                }
+            };
+            let shared = self.shared.clone();
+            std::thread::spawn(move || serve_client(shared, reader, writer));
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Server {
+    fn drop(&mut self) {
+        if let Listener::Unix(_, path) = &self.listener {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+struct Client {
+    writer: Mutex<Box<dyn Write + Send>>,
+    topics: Mutex<HashSet<String>>,
+}
+
+impl Client {
+    // A client that has gone away is noticed by its reader, so a failed send is ignored
+    fn send(&self, message: &Value) {
+        let mut writer = self.writer.lock().unwrap();
+        let _ = writeln!(writer, "{}", message).and_then(|_| writer.flush());
+    }
+
+    fn reply(&self, id: Value, result: Result<Value, Box<dyn std::error::Error>>) {
+        match result {
+            Ok(result) => self.send(&json!({ "id": id, "result": result })),
+            Err(e) => self.send(&json!({ "id": id, "error": e.to_string() })),
+        }
+    }
+}
+
+struct Shared {
+    state: Mutex<State>,
+    clients: Mutex<Vec<Arc<Client>>>,
+}
+
+impl Shared {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    fn notify(&self, topic: &str, params: Value) {
+        let message = json!({ "method": topic, "params": params });
+        for client in self.clients.lock().unwrap().iter() {
+            if client.topics.lock().unwrap().contains(topic) {
+                client.send(&message);
+            }
+        }
+    }
+}
+
+struct Target {
+    process: Process,
+    regions: RegionCache,
+    locks: LockManager<Process>,
+}
+
+struct Watch {
+    address: usize,
+    value_type: ValueType,
+    len: usize,
+    last: Option<Vec<u8>>,
+}
+
+struct State {
+    target: Option<Target>,
+    options: ScanOptions,
+    scan_type: ValueType,
+    results: Vec<usize>,
+    stats: ScanStats,
+    scanning: bool,
+    watches: BTreeMap<u64, Watch>,
+    next_watch: u64,
+}
+
+impl State {
+    fn new(options: ScanOptions) -> State {
+        State { target: None, options, scan_type: ValueType::I32, results: Vec::new(), stats: ScanStats::default(), scanning: false, watches: BTreeMap::new(), next_watch: 0 }
+    }
+
+    // Starts over against the process, dropping the old one's results, locks and watches
+    fn attach(&mut self, process: Process) -> Result<(), Box<dyn std::error::Error>> {
+        if self.scanning {
+            return Err("A scan is running".into());
+        }
+        let regions = RegionCache::from_process(Arc::new(process.clone()))?;
+        self.target = Some(Target { locks: LockManager::new(process.clone()), process, regions });
+        self.results.clear();
+        self.stats = ScanStats::default();
+        self.watches.clear();
+        Ok(())
+    }
+
+    fn target(&mut self) -> Result<&mut Target, Box<dyn std::error::Error>> {
+        self.target.as_mut().ok_or("No process is attached; call attach first".into())
+    }
+
+    fn call(&mut self, method: &str, params: &Value) -> Result<Value, Box<dyn std::error::Error>> {
+        let endianness = self.options.endianness;
+        match method {
+            "attach" => {
+                let pid = Pid::from_raw(params.get("pid").and_then(Value::as_i64).ok_or("Expected a pid")? as i32);
+                let process = match params.get("backend").and_then(Value::as_str) {
+                    Some(backend) => Process::with_backend(pid, backend.parse::<MemBackend>()?)?,
+                    None => Process::attach(pid)?,
+                };
+                self.attach(process)?;
+                let process = &self.target()?.process;
+                Ok(json!({ "pid": process.pid().as_raw(), "backend": process.backend().to_string() }))
+            }
+            "list_results" => {
+                if self.scanning {
+                    return Err("A scan is running; its results can be listed once it finishes".into());
+                }
+                let offset = usize_param(params, "offset")?.unwrap_or(0);
+                let limit = usize_param(params, "limit")?.unwrap_or(DEFAULT_PAGE).min(MAX_PAGE);
+                let value_type = match params.get("type") {
+                    Some(_) => type_param(params)?,
+                    None => self.scan_type,
+                };
+                let page = self.results.iter().skip(offset).take(limit).copied().collect::<Vec<usize>>();
+                let total = self.results.len();
+                let target = self.target()?;
+                let values = with_scan_type!(value_type, T, {
+                    read_many_in::<T>(&target.process, &page, endianness).into_iter().map(|x| x.map(|x| x.to_string())).collect::<Vec<_>>()
+                });
+                let results = page.iter().zip(values).map(|(address, value)| {
+                    let mut item = describe(target, *address);
+                    match value {
+                        Ok(value) => item["value"] = json!(value),
+                        Err(e) => item["error"] = json!(e.to_string()),
+                    }
+                    item
+                }).collect::<Vec<Value>>();
+                Ok(json!({ "total": total, "offset": offset, "results": results }))
+            }
+            "read" => {
+                let target = self.target()?;
+                let address = address_param(target, params)?;
+                let value_type = type_param(params)?;
+                let value = match value_type {
+                    ValueType::Str { .. } => read_string_lossy(&target.process, &mut target.regions, address, usize_param(params, "len")?.unwrap_or(STRING_LIMIT))?,
+                    _ => {
+                        let len = value_len(value_type, params)?;
+                        let bytes = read_bytes_from_process(&target.process, len, address)?;
+                        if bytes.len() < len {
+                            return Err(format!("Short read at 0x{:x}: got {} of {} bytes", address, bytes.len(), len).into());
+                        }
+                        value_type.value_from_bytes_in(&bytes, endianness)?.format_value()
+                    }
+                };
+                let mut item = describe(target, address);
+                item["value"] = json!(value);
+                Ok(item)
+            }
+            "write" => {
+                let target = self.target()?;
+                let address = address_param(target, params)?;
+                let bytes = value_bytes(params, endianness)?;
+                if !bool_param(params, "force") {
+                    target.regions.check_writable(address, bytes.len())?;
+                }
+                let written = write_bytes_to_process(&target.process, address, &bytes)?;
+                if written < bytes.len() {
+                    return Err(format!("Only {} of {} bytes could be written, stopping at 0x{:x}", written, bytes.len(), address + written).into());
+                }
+                Ok(json!({ "written": written }))
+            }
+            "lock" => {
+                let target = self.target()?;
+                let address = address_param(target, params)?;
+                let interval = usize_param(params, "interval_ms")?.map(|x| Duration::from_millis(x as u64)).unwrap_or(crate::lock::DEFAULT_LOCK_INTERVAL);
+                let value_type = type_param(params)?;
+                let bytes = value_bytes(params, endianness)?;
+                if !bool_param(params, "force") {
+                    target.regions.check_writable(address, bytes.len())?;
+                }
+                match value_type {
+                    ValueType::Bytes | ValueType::Str { .. } => target.locks.lock_bytes(bytes, address, interval),
+                    _ => with_scan_type!(value_type, T, {
+                        let value = string_param(params, "value")?.parse::<T>()?;
+                        target.locks.lock_with_action(LockAction::Set(value), address, interval, endianness)
+                    }),
+                }
+                Ok(describe(target, address))
+            }
+            "unlock" => {
+                let target = self.target()?;
+                let address = address_param(target, params)?;
+                Ok(json!({ "unlocked": target.locks.unlock_value(address) }))
+            }
+            "locks" => {
+                let target = self.target()?;
+                let locks = target.locks.list();
+                Ok(Value::Array(locks.iter().map(|lock| {
+                    let mut item = describe(target, lock.address);
+                    item["type"] = json!(lock.type_name);
+                    item["action"] = json!(lock.action);
+                    item["interval_ms"] = json!(lock.interval.as_millis() as u64);
+                    item["status"] = json!(lock.status().to_string());
+                    item["writes"] = json!(lock.writes);
+                    item["failures"] = json!(lock.failures);
+                    item
+                }).collect()))
+            }
+            "maps" => {
+                let regions = self.target()?.process.memory_regions()?;
+                Ok(Value::Array(regions.iter().map(|x| json!({
+                    "start": format!("0x{:x}", x.start),
+                    "end": format!("0x{:x}", x.end),
+                    "permissions": x.permissions(),
+                    "offset": x.offset,
+                    "pathname": x.pathname,
+                })).collect()))
+            }
+            "watch" => {
+                let target = self.target()?;
+                let address = address_param(target, params)?;
+                let value_type = type_param(params)?;
+                let len = value_len(value_type, params)?;
+                let id = self.next_watch;
+                self.next_watch += 1;
+                self.watches.insert(id, Watch { address, value_type, len, last: None });
+                Ok(json!({ "watch": id }))
+            }
+            "unwatch" => {
+                let id = params.get("watch").and_then(Value::as_u64).ok_or("Expected the watch id to remove")?;
+                Ok(json!({ "removed": self.watches.remove(&id).is_some() }))
+            }
+            _ => Err(format!("Unknown method '{}'", method).into()),
+        }
+    }
+}
+
+fn serve_client(shared: Arc<Shared>, reader: Box<dyn Read + Send>, writer: Box<dyn Write + Send>) {
+    let client = Arc::new(Client { writer: Mutex::new(writer), topics: Mutex::new(HashSet::new()) });
+    shared.clients.lock().unwrap().push(client.clone());
+    for line in BufReader::new(reader).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request = match serde_json::from_str::<Value>(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                client.reply(Value::Null, Err(format!("Invalid request: {}", e).into()));
+                continue;
+            }
+        };
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let method = request.get("method").and_then(Value::as_str).unwrap_or_default();
+        let params = request.get("params").cloned().unwrap_or_else(|| json!({}));
+        match method {
+            "scan" | "rescan" => {
+                if let Err(e) = start_scan(&shared, &client, id.clone(), method == "rescan", &params) {
+                    client.reply(id, Err(e));
+                }
+            }
+            "subscribe" | "unsubscribe" => client.reply(id, subscribe(&client, method == "subscribe", &params)),
+            _ => client.reply(id, shared.state().call(method, &params)),
+        }
+    }
+    shared.clients.lock().unwrap().retain(|x| !Arc::ptr_eq(x, &client));
+}
+
+fn subscribe(client: &Client, subscribe: bool, params: &Value) -> Result<Value, Box<dyn std::error::Error>> {
+    let topics = params.get("topics").and_then(Value::as_array).ok_or("Expected a list of topics")?;
+    let mut subscribed = client.topics.lock().unwrap();
+    for topic in topics {
+        let topic = topic.as_str().filter(|x| TOPICS.contains(x)).ok_or(format!("Unknown topic {}, expected one of {}", topic, TOPICS.join(", ")))?;
+        if subscribe {
+            subscribed.insert(topic.to_string());
+        }
+        else {
+            subscribed.remove(topic);
+        }
+    }
+    Ok(json!({ "topics": subscribed.iter().collect::<Vec<&String>>() }))
+}
+
+// Checks the request, then scans on another thread and answers once done. Progress goes to the
+// clients subscribed to it, as does a "scan_done" notification, so every frontend sharing the
+// session learns that the results changed
+fn start_scan(shared: &Arc<Shared>, client: &Arc<Client>, id: Value, rescan: bool, params: &Value) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = shared.state();
+    if state.scanning {
+        return Err("A scan is already running".into());
+    }
+    let process = state.target()?.process.clone();
+    let scan_type = if rescan { state.scan_type } else { type_param(params)? };
+    let value = string_param(params, "value")?;
+    with_scan_type!(scan_type, T, {
+        value.parse::<T>().map_err(|e| format!("Invalid {} '{}': {}", scan_type, value, e))?;
+    });
+    let mut options = state.options.clone();
+    let progress = Arc::downgrade(shared);
+    options.progress = Some(ProgressCallback(Arc::new(move |x| if let Some(shared) = progress.upgrade() {
+        shared.notify("progress", json!({ "bytes_scanned": x.bytes_scanned, "bytes_total": x.bytes_total, "elapsed_ms": x.elapsed.as_millis() as u64 }));
+    })));
+    let mut results = if rescan { std::mem::take(&mut state.results) } else { Vec::new() };
+    let mut stats = state.stats.clone();
+    state.scanning = true;
+    drop(state);
+    let (shared, client) = (shared.clone(), client.clone());
+    std::thread::spawn(move || {
+        let scanned = run_scan(&process, rescan, scan_type, &value, &options, &mut results, &mut stats);
+        let mut state = shared.state();
+        state.scanning = false;
+        let result = scanned.map(|_| {
+            state.scan_type = scan_type;
+            state.stats = stats.clone();
+            json!({ "matches": results.len(), "bytes_scanned": stats.bytes_scanned, "elapsed_ms": stats.elapsed.as_millis() as u64 })
+        });
+        // A failed rescan leaves the results as they were
+        state.results = results;
+        drop(state);
+        if let Ok(result) = &result {
+            shared.notify("progress", json!({ "scan_done": result }));
+        }
+        client.reply(id, result);
+    });
+    Ok(())
+}
+
+fn run_scan(process: &Process, rescan: bool, scan_type: ValueType, value: &str, options: &ScanOptions, results: &mut Vec<usize>, stats: &mut ScanStats) -> Result<(), Box<dyn std::error::Error>> {
+    with_scan_type!(scan_type, T, {
+        let value = value.parse::<T>()?;
+        if rescan {
+            reduce_found_values(process, results, value, options, stats)?;
+        }
+        else {
+            (*results, *stats) = find_value(process, value, options)?;
+        }
+    });
+    Ok(())
+}
+
+// Reads every watched address each interval, telling the clients subscribed to "watch" about any
+// that changed, including the first read of a new watch. Stops once the server is gone
+fn watch(shared: Weak<Shared>) {
+    loop {
+        std::thread::sleep(WATCH_INTERVAL);
+        let Some(shared) = shared.upgrade() else {
+            return;
+        };
+        let (process, watched) = {
+            let state = shared.state();
+            let Some(target) = &state.target else {
+                continue;
+            };
+            (target.process.clone(), state.watches.iter().map(|(id, x)| (*id, x.address, x.len)).collect::<Vec<_>>())
+        };
+        if watched.is_empty() {
+            continue;
+        }
+        let reads = read_many_bytes(&process, &watched.iter().map(|x| (x.1, x.2)).collect::<Vec<(usize, usize)>>());
+        let mut changes = Vec::new();
+        let mut state = shared.state();
+        let endianness = state.options.endianness;
+        for ((id, address, _), read) in watched.into_iter().zip(reads) {
+            let Some(watch) = state.watches.get_mut(&id) else {
+                continue;
+            };
+            let read = read.ok();
+            if read != watch.last {
+                let value = read.as_ref().and_then(|x| watch.value_type.value_from_bytes_in(x, endianness).ok()).map(|x| x.format_value());
+                changes.push(json!({ "watch": id, "address": format!("0x{:x}", address), "value": value }));
+                watch.last = read;
+            }
+        }
+        drop(state);
+        for change in changes {
+            shared.notify("watch", change);
+        }
+    }
+}
+
+fn describe(target: &mut Target, address: usize) -> Value {
+    json!({ "address": format!("0x{:x}", address), "location": target.regions.describe(address).to_string() })
+}
+
+fn address_param(target: &mut Target, params: &Value) -> Result<usize, Box<dyn std::error::Error>> {
+    match params.get("address") {
+        Some(Value::String(address)) => resolve_address(&target.process, &mut target.regions, address),
+        Some(Value::Number(address)) => Ok(address.as_u64().ok_or("Expected a non-negative address")? as usize),
+        _ => Err("Expected an address".into()),
+    }
+}
+
+fn type_param(params: &Value) -> Result<ValueType, Box<dyn std::error::Error>> {
+    Ok(params.get("type").and_then(Value::as_str).ok_or("Expected a type such as \"i32\"")?.parse::<ValueType>()?)
+}
+
+// A value given as a JSON string or number
+fn string_param(params: &Value, name: &str) -> Result<String, Box<dyn std::error::Error>> {
+    match params.get(name) {
+        Some(Value::String(value)) => Ok(value.clone()),
+        Some(value @ Value::Number(_)) => Ok(value.to_string()),
+        _ => Err(format!("Expected a {}", name).into()),
+    }
+}
+
+fn usize_param(params: &Value, name: &str) -> Result<Option<usize>, Box<dyn std::error::Error>> {
+    match params.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => Ok(Some(value.as_u64().ok_or(format!("Expected {} to be a non-negative integer", name))? as usize)),
+    }
+}
+
+fn bool_param(params: &Value, name: &str) -> bool {
+    params.get(name).and_then(Value::as_bool).unwrap_or(false)
+}
+
+// Bytes given as hex, e.g. "90 90"; anything else is parsed as the type
+fn value_bytes(params: &Value, endianness: crate::value::Endianness) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let value = string_param(params, "value")?;
+    match type_param(params)? {
+        ValueType::Bytes => parse_hex_bytes(&value),
+        value_type => Ok(value_type.parse_value(&value)?.to_bytes_in(endianness)),
+    }
+}
+
+// How many bytes a read or watch of the type covers; byte buffers need a len
+fn value_len(value_type: ValueType, params: &Value) -> Result<usize, Box<dyn std::error::Error>> {
+    match value_type.size() {
+        Some(size) => Ok(size),
+        None => usize_param(params, "len")?.ok_or(format!("Expected a len for {}", value_type).into()),
+    }
+}
@@ -0,0 +1,260 @@
+use std::{collections::BTreeMap, str::FromStr, time::{Duration, SystemTime, UNIX_EPOCH}};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+use crate::{maps::{Module, find_module, module_for_address}, pointer::PointerChain, value::{Endianness, ValueType}};
+
+// The first line of the tab-separated session files that came before version 2
+const SESSION_HEADER: &str = "memory-session 1";
+pub const SESSION_VERSION: u32 = 2;
+
+// Session files store scan results past this many without their values, to keep them small
+pub const SAVED_VALUE_LIMIT: usize = 100_000;
+
+// An address as typed, printed or stored in a session file, e.g. "0x7f31c2a0" or "libgame.so+0x2a10".
+// Module-relative addresses can be found again in a new instance of the process despite ASLR,
+// absolute ones only if nothing moved
+#[derive(Debug, Clone, PartialEq)]
+pub enum SavedAddress {
+    Absolute(usize),
+    Module { name: String, offset: usize },
+}
+
+impl SavedAddress {
+    // Module-relative if the address falls inside one of the modules
+    pub fn from_address(address: usize, modules: &[Module]) -> SavedAddress {
+        match module_for_address(modules, address) {
+            Some(module) => SavedAddress::Module { name: module.name.clone(), offset: address - module.base },
+            None => SavedAddress::Absolute(address),
+        }
+    }
+
+    pub fn resolve(&self, modules: &[Module]) -> Result<usize, Box<dyn std::error::Error>> {
+        match self {
+            SavedAddress::Absolute(address) => Ok(*address),
+            SavedAddress::Module { name, offset } => {
+                let module = find_module(modules, name).ok_or_else(|| not_loaded(modules, name))?;
+                if *offset >= module.size {
+                    return Err(format!("Offset 0x{:x} is outside {}, which is only 0x{:x} bytes", offset, name, module.size).into());
+                }
+                Ok(module.base + offset)
+            }
+        }
+    }
+}
+
+// Names the module, and any loaded ones it could have been meant as, e.g. "game" for "game.x86_64"
+fn not_loaded(modules: &[Module], name: &str) -> Box<dyn std::error::Error> {
+    let mut similar = modules.iter().map(|x| x.name.as_str()).filter(|x| x.starts_with(name) || name.starts_with(*x)).collect::<Vec<&str>>();
+    similar.dedup();
+    match similar.is_empty() {
+        true => format!("Module {} is not loaded", name).into(),
+        false => format!("Module {} is not loaded (loaded: {})", name, similar.join(", ")).into(),
+    }
+}
+
+impl std::fmt::Display for SavedAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SavedAddress::Absolute(address) => write!(f, "0x{:x}", address),
+            SavedAddress::Module { name, offset } => write!(f, "{}+0x{:x}", name, offset),
+        }
+    }
+}
+
+impl FromStr for SavedAddress {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.rsplit_once('+') {
+            Some((name, offset)) => Ok(SavedAddress::Module { name: name.to_string(), offset: usize::from_str_radix(offset.trim_start_matches("0x"), 16)? }),
+            None => Ok(SavedAddress::Absolute(usize::from_str_radix(s.trim_start_matches("0x"), 16)?)),
+        }
+    }
+}
+
+// Stored as the text they are typed as, e.g. "i32", "be" or "libgame.so+0x2a10"
+macro_rules! serde_as_string {
+    ($($t:ty),*) => {
+        $(
+            impl Serialize for $t {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.collect_str(self)
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $t {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    String::deserialize(deserializer)?.parse().map_err(D::Error::custom)
+                }
+            }
+        )*
+    };
+}
+
+serde_as_string!(SavedAddress, ValueType, Endianness, PointerChain);
+
+// Enough about a lock to re-create it against another instance of the process
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedLock {
+    pub address: SavedAddress,
+    // Bytes for a byte buffer, otherwise the numeric type the lock acts on
+    pub value_type: ValueType,
+    #[serde(with = "hex")]
+    pub value_bytes: Vec<u8>,
+    pub action: String,
+    pub interval: Duration,
+    pub enabled: bool,
+    // The chain the lock was made through, followed again to find the value in another instance
+    // of the process, where the address is only where the chain led this time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain: Option<PointerChain>,
+}
+
+// A journal entry, kept so the write can still be undone from another session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedWrite {
+    pub address: SavedAddress,
+    #[serde(with = "hex")]
+    pub old: Vec<u8>,
+    #[serde(with = "hex")]
+    pub new: Vec<u8>,
+    pub time: SystemTime,
+    pub kind: String,
+}
+
+// A scan result and the bytes it held when saved, which are left out past SAVED_VALUE_LIMIT
+// results
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedResult {
+    pub address: SavedAddress,
+    #[serde(default, skip_serializing_if = "Vec::is_empty", with = "hex")]
+    pub value: Vec<u8>,
+}
+
+// A result that was tagged or starred. Marks are kept by address, so one only loads along with a
+// result at the same address
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedMark {
+    pub address: SavedAddress,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub starred: bool,
+}
+
+// A patch that was active, to be applied again where the original bytes are still found
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedPatch {
+    pub address: SavedAddress,
+    #[serde(with = "hex")]
+    pub original: Vec<u8>,
+    #[serde(with = "hex")]
+    pub patched: Vec<u8>,
+}
+
+// Fields a file leaves out, such as those added since it was written, are left empty
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionFile {
+    pub scan_type: Option<ValueType>,
+    // The byte order locks read and write in, when not native
+    pub endianness: Option<Endianness>,
+    pub results: Vec<SavedResult>,
+    pub locks: Vec<SavedLock>,
+    pub patches: Vec<SavedPatch>,
+    // Oldest first, like the journal
+    pub writes: Vec<SavedWrite>,
+    // Key name to the command it runs
+    pub bindings: BTreeMap<String, String>,
+    pub marks: Vec<SavedMark>,
+}
+
+// Borrowed when saving, owned when loading
+#[derive(Serialize, Deserialize)]
+struct Versioned<T> {
+    version: u32,
+    #[serde(flatten)]
+    session: T,
+}
+
+impl SessionFile {
+    // JSON with a version number, raised whenever a field changes meaning
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let contents = serde_json::to_string_pretty(&Versioned { version: SESSION_VERSION, session: self })?;
+        Ok(std::fs::write(path, contents + "\n")?)
+    }
+
+    // Also reads the tab-separated files written before sessions were JSON
+    pub fn load(path: &str) -> Result<SessionFile, Box<dyn std::error::Error>> {
+        let contents = std::fs::read_to_string(path)?;
+        if contents.lines().next() == Some(SESSION_HEADER) {
+            return SessionFile::load_records(path, &contents);
+        }
+        let value = serde_json::from_str::<serde_json::Value>(&contents).map_err(|_| format!("{} is not a session file", path))?;
+        let version = value.get("version").and_then(|x| x.as_u64()).ok_or(format!("{} is not a session file", path))?;
+        if version > SESSION_VERSION as u64 {
+            return Err(format!("{} was saved by a newer version of memory (session format {}, this one reads up to {}); update memory to load it", path, version, SESSION_VERSION).into());
+        }
+        Ok(serde_json::from_value::<Versioned<SessionFile>>(value).map_err(|e| format!("{}: {}", path, e))?.session)
+    }
+
+    fn load_records(path: &str, contents: &str) -> Result<SessionFile, Box<dyn std::error::Error>> {
+        let mut session = SessionFile::default();
+        for (number, line) in contents.lines().enumerate().skip(1) {
+            let fields = line.split('\t').collect::<Vec<&str>>();
+            let parsed: Result<(), Box<dyn std::error::Error>> = match fields.as_slice() {
+                [] | [""] => Ok(()),
+                ["scan_type", scan_type] => {
+                    scan_type.parse().map(|x| session.scan_type = Some(x)).map_err(|e: String| e.into())
+                }
+                ["endianness", endianness] => {
+                    endianness.parse().map(|x| session.endianness = Some(x)).map_err(|e: String| e.into())
+                }
+                ["lock", address, value_type, bytes, interval, enabled, action] => parse_bytes(bytes).and_then(|value_bytes| {
+                    session.locks.push(SavedLock {
+                        address: address.parse()?,
+                        value_type: value_type.parse()?,
+                        value_bytes,
+                        action: action.to_string(),
+                        interval: Duration::from_nanos(interval.parse()?),
+                        enabled: *enabled == "enabled",
+                        chain: None,
+                    });
+                    Ok(())
+                }),
+                ["write", address, old, new, time, kind] => parse_bytes(old).and_then(|old| {
+                    session.writes.push(SavedWrite {
+                        address: address.parse()?,
+                        old,
+                        new: parse_bytes(new)?,
+                        time: UNIX_EPOCH + Duration::from_nanos(time.parse()?),
+                        kind: kind.to_string(),
+                    });
+                    Ok(())
+                }),
+                _ => Err("Unrecognised record".into()),
+            };
+            parsed.map_err(|e| format!("{} line {}: {}", path, number + 1, e))?;
+        }
+        Ok(session)
+    }
+}
+
+// Byte strings are stored as hex
+pub(crate) mod hex {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&bytes.iter().map(|x| format!("{:02x}", x)).collect::<String>())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        super::parse_bytes(&String::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+fn parse_bytes(s: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if !s.len().is_multiple_of(2) {
+        return Err("Expected an even number of hex digits".into());
+    }
+    Ok((0..s.len()).step_by(2).map(|x| u8::from_str_radix(&s[x..x + 2], 16)).collect::<Result<Vec<u8>, _>>()?)
+}
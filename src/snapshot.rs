@@ -0,0 +1,577 @@
+use std::{borrow::Cow, collections::HashMap, path::Path, sync::{Arc, RwLock, atomic::{AtomicUsize, Ordering}}, time::{Duration, Instant}};
+use rayon::prelude::*;
+use zerocopy::FromBytes;
+use crate::{budget::{BudgetFallback, SpillFile, Spiller}, capture::{Capture, CaptureHeader, CapturedRegion, Compression, uncaptured}, dump::dump_range, maps::MemoryRegion, priority::{ScanPriority, at_priority}, process::{ProcessMemory, read_bytes_from_process, read_bytes_into}, scan::{ProgressReporter, ScanOptions, Throttle, filtered_ranges, split_regions_into_chunks}, value::{Endianness, Scalar}};
+
+// Regions are stored as independent chunks of this size so a single chunk can be decompressed
+// without touching the rest of its region. Being a multiple of 8 means aligned values of any
+// primitive type never straddle two chunks
+pub const SNAPSHOT_CHUNK_SIZE: usize = 1 << 20;
+
+// A heatmap compares memory with a snapshot a page at a time
+pub const HEATMAP_PAGE_SIZE: usize = 4096;
+
+// An unknown scan's candidates are listed as addresses once there are no more than this many
+pub const UNKNOWN_LIST_LIMIT: usize = 1_000_000;
+
+#[derive(Debug, Clone)]
+pub enum ChunkData {
+    Raw(Vec<u8>),
+    Lz4 { compressed: Vec<u8>, len: usize },
+    // Uncompressed at offset in a spill file, for a snapshot that went over its memory budget
+    Spilled { file: Arc<SpillFile>, offset: u64, len: usize },
+}
+
+#[derive(Debug, Clone)]
+pub struct SnapshotChunk {
+    pub address: usize,
+    pub data: ChunkData,
+}
+
+impl SnapshotChunk {
+    fn new(address: usize, bytes: Vec<u8>, compress: bool) -> SnapshotChunk {
+        let data = if compress {
+            ChunkData::Lz4 { compressed: lz4_flex::compress(&bytes), len: bytes.len() }
+        }
+        else {
+            ChunkData::Raw(bytes)
+        };
+        SnapshotChunk { address, data }
+    }
+
+    // As new, but in the spiller's file once its budget has no room left for the chunk
+    fn budgeted(address: usize, bytes: Vec<u8>, compress: bool, spiller: &Spiller) -> Result<SnapshotChunk, String> {
+        let chunk = SnapshotChunk::new(address, bytes, compress);
+        if spiller.fits(chunk.stored_len()) {
+            return Ok(chunk);
+        }
+        let bytes = chunk.bytes().map_err(|e| e.to_string())?;
+        let (file, offset) = spiller.spill(&bytes).map_err(|e| e.to_string())?;
+        Ok(SnapshotChunk { address, data: ChunkData::Spilled { file, offset, len: bytes.len() } })
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.data {
+            ChunkData::Raw(x) => x.len(),
+            ChunkData::Lz4 { len, .. } => *len,
+            ChunkData::Spilled { len, .. } => *len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Bytes the chunk takes in memory, which for a spilled one is none
+    pub fn stored_len(&self) -> usize {
+        match &self.data {
+            ChunkData::Raw(x) => x.len(),
+            ChunkData::Lz4 { compressed, .. } => compressed.len(),
+            ChunkData::Spilled { .. } => 0,
+        }
+    }
+
+    pub fn bytes(&self) -> Result<Cow<'_, [u8]>, Box<dyn std::error::Error>> {
+        match &self.data {
+            ChunkData::Raw(x) => Ok(Cow::Borrowed(x)),
+            ChunkData::Lz4 { compressed, len } => Ok(Cow::Owned(lz4_flex::decompress(compressed, *len)?)),
+            ChunkData::Spilled { file, offset, len } => Ok(Cow::Owned(file.read(*offset, *len)?)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub chunks: Vec<SnapshotChunk>,
+    pub failed_regions: Vec<(usize, usize)>,
+    // What capturing the snapshot, or the narrow that last stored it, did to keep within the
+    // memory budget
+    pub fallbacks: Vec<BudgetFallback>,
+}
+
+impl Snapshot {
+    pub fn capture(process: impl ProcessMemory, options: &ScanOptions) -> Result<Snapshot, Box<dyn std::error::Error>> {
+        let mut ranges = filtered_ranges(&process, options)?;
+        if let Some(max_region_size) = options.max_region_size {
+            ranges.retain(|x| x.1 - x.0 <= max_region_size);
+        }
+        let regions = process.memory_regions()?;
+        let failed: Arc<RwLock<Vec<(usize, usize)>>> = Arc::new(RwLock::new(Vec::new()));
+        let throttle = Throttle::new(options.throttle);
+        let (chunks, spiller) = at_priority(options.priority, || {
+            let spiller = Spiller::new(options.memory_budget, rayon::current_num_threads() * SNAPSHOT_CHUNK_SIZE);
+            let chunks = split_regions_into_chunks(&ranges, &regions, SNAPSHOT_CHUNK_SIZE).par_iter().filter_map(|chunk| {
+                throttle.take(chunk.len);
+                match read_bytes_from_process(&process, chunk.len, chunk.address) {
+                    Ok(bytes) => Some(SnapshotChunk::budgeted(chunk.address, bytes, options.compress_snapshots, &spiller)),
+                    Err(_) => {
+                        let mut failed = failed.write().unwrap();
+                        if !failed.contains(&chunk.region) {
+                            failed.push(chunk.region);
+                        }
+                        None
+                    }
+                }
+            }).collect::<Result<Vec<SnapshotChunk>, String>>();
+            (chunks, spiller)
+        });
+        let mut chunks = chunks?;
+        chunks.sort_by_key(|x| x.address);
+        Ok(Snapshot { chunks, failed_regions: Arc::into_inner(failed).unwrap().into_inner().unwrap(), fallbacks: spiller.fallback().into_iter().collect() })
+    }
+
+    // Writes the snapshot as a capture, one region per run of adjacent chunks, described by the
+    // process's region there. Compressed snapshots are compressed on disk too
+    pub fn save(&self, process: impl ProcessMemory, path: &Path) -> Result<Vec<CapturedRegion>, Box<dyn std::error::Error>> {
+        let mapped = process.memory_regions()?;
+        let mut regions: Vec<MemoryRegion> = Vec::new();
+        for chunk in &self.chunks {
+            match regions.last_mut() {
+                // A run is only extended by whole chunks, so its blocks line up with the chunks
+                Some(last) if last.end == chunk.address && (last.end - last.start) % SNAPSHOT_CHUNK_SIZE == 0 => last.end += chunk.len(),
+                _ => {
+                    let region = mapped.iter().find(|x| x.contains(chunk.address)).cloned().unwrap_or(MemoryRegion { readable: true, ..MemoryRegion::default() });
+                    regions.push(MemoryRegion { start: chunk.address, end: chunk.address + chunk.len(), ..region });
+                }
+            }
+        }
+        let compression = match self.chunks.first().map(|x| &x.data) {
+            Some(ChunkData::Lz4 { .. }) => Compression::Lz4,
+            _ => Compression::None,
+        };
+        let header = CaptureHeader { uncaptured: uncaptured(&mapped, &regions), ..CaptureHeader::of(&process)? };
+        Capture::write(path, &header, compression, regions, |address, block| {
+            let index = self.chunks.binary_search_by_key(&address, |x| x.address).map_err(|_| format!("No chunk at 0x{:x}", address))?;
+            block.copy_from_slice(&self.chunks[index].bytes()?[..block.len()]);
+            Ok(Vec::new())
+        })
+    }
+
+    // Reads a capture back as a snapshot, for comparing with the process it was taken from. Blocks
+    // with holes in them are left out, and their regions listed as failed
+    pub fn load(path: &Path, compress: bool) -> Result<Snapshot, Box<dyn std::error::Error>> {
+        let capture = Capture::open(path)?;
+        let mut snapshot = Snapshot::default();
+        for (index, entry) in capture.regions.iter().enumerate() {
+            let region = &entry.region;
+            for address in (region.start..region.end).step_by(SNAPSHOT_CHUNK_SIZE) {
+                let end = (address + SNAPSHOT_CHUNK_SIZE).min(region.end);
+                if entry.holes.iter().any(|x| x.0 < end && x.1 > address) {
+                    if !snapshot.failed_regions.contains(&(region.start, region.end)) {
+                        snapshot.failed_regions.push((region.start, region.end));
+                    }
+                    continue;
+                }
+                let mut bytes = vec![0u8; end - address];
+                capture.read_region(index, address - region.start, &mut bytes)?;
+                snapshot.chunks.push(SnapshotChunk::new(address, bytes, compress));
+            }
+        }
+        snapshot.chunks.sort_by_key(|x| x.address);
+        Ok(snapshot)
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.iter().map(|x| x.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    pub fn stored_len(&self) -> usize {
+        self.chunks.iter().map(|x| x.stored_len()).sum()
+    }
+
+    // Re-reads every stored chunk from the process and returns the addresses where the old and
+    // new values, interpreted as T, satisfy the predicate
+    pub fn compare<T: FromBytes + Send + Sync>(&self, process: impl ProcessMemory, predicate: fn(&T, &T) -> bool) -> Result<Vec<usize>, Box<dyn std::error::Error>> {
+        let found: Arc<RwLock<Vec<usize>>> = Arc::new(RwLock::new(Vec::new()));
+        self.chunks.par_iter().for_each_init(Vec::new, |new: &mut Vec<u8>, chunk| {
+            new.resize(chunk.len(), 0);
+            let old = chunk.bytes();
+            let read = read_bytes_into(&process, chunk.address, new);
+            if let (Ok(old), Ok(read)) = (old, read) {
+                let mut local: Vec<usize> = Vec::new();
+                let size = std::mem::size_of::<T>();
+                for offset in 0..(read + 1).saturating_sub(size) {
+                    // FromBytes makes any bytes a valid T
+                    if let (Ok(old), Ok(new)) = (T::read_from_bytes(&old[offset..offset + size]), T::read_from_bytes(&new[offset..offset + size])) && predicate(&old, &new) {
+                        local.push(chunk.address + offset);
+                    }
+                }
+                found.write().unwrap().append(&mut local);
+            }
+        });
+        let mut found = Arc::into_inner(found).unwrap().into_inner().unwrap();
+        found.par_sort();
+        found.dedup();
+        Ok(found)
+    }
+}
+
+// One region of a heatmap, with whether each of its pages the snapshot had has changed since
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionHeat {
+    pub region: MemoryRegion,
+    // By address
+    pub pages: Vec<(usize, bool)>,
+}
+
+impl RegionHeat {
+    pub fn changed(&self) -> usize {
+        self.pages.iter().filter(|x| x.1).count()
+    }
+
+    pub fn fraction(&self) -> f64 {
+        self.changed() as f64 / self.pages.len().max(1) as f64
+    }
+
+    // The pages spread over width cells, each '#' where at least half its pages changed, '+' where
+    // some did and '.' where none did
+    pub fn bar(&self, width: usize) -> String {
+        let width = width.min(self.pages.len());
+        (0..width).map(|cell| {
+            let pages = &self.pages[cell * self.pages.len() / width..(cell + 1) * self.pages.len() / width];
+            match pages.iter().filter(|x| x.1).count() {
+                0 => '.',
+                changed if changed * 2 >= pages.len() => '#',
+                _ => '+',
+            }
+        }).collect()
+    }
+}
+
+// Where memory changed since a snapshot, region by region
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Heatmap {
+    // Most of their pages changed first
+    pub regions: Vec<RegionHeat>,
+    // Pages that could not be read again or are no longer mapped
+    pub unread: usize,
+    // Pages left uncompared once the time ran out
+    pub skipped: usize,
+}
+
+impl Heatmap {
+    pub fn pages(&self) -> usize {
+        self.regions.iter().map(|x| x.pages.len()).sum()
+    }
+
+    pub fn changed(&self) -> usize {
+        self.regions.iter().map(|x| x.changed()).sum()
+    }
+}
+
+enum ChunkHeat {
+    Compared(Vec<bool>),
+    Unread(usize),
+    Skipped(usize),
+}
+
+impl Snapshot {
+    // Compares each page the snapshot holds with the process's memory now, the chunks in parallel.
+    // Once the time allowed is up, chunks not yet started are skipped rather than compared
+    pub fn heatmap(&self, process: impl ProcessMemory, allowed: Option<Duration>) -> Result<Heatmap, Box<dyn std::error::Error>> {
+        let regions = process.memory_regions()?;
+        let started = Instant::now();
+        let pages = |len: usize| len.div_ceil(HEATMAP_PAGE_SIZE);
+        let compared = self.chunks.par_iter().map_init(Vec::new, |new: &mut Vec<u8>, chunk| {
+            if allowed.is_some_and(|x| started.elapsed() > x) {
+                return ChunkHeat::Skipped(pages(chunk.len()));
+            }
+            new.resize(chunk.len(), 0);
+            match (chunk.bytes(), read_bytes_into(&process, chunk.address, new)) {
+                (Ok(old), Ok(read)) => ChunkHeat::Compared(old[..read].chunks(HEATMAP_PAGE_SIZE).zip(new[..read].chunks(HEATMAP_PAGE_SIZE)).map(|(old, new)| old != new).collect()),
+                _ => ChunkHeat::Unread(pages(chunk.len())),
+            }
+        }).collect::<Vec<ChunkHeat>>();
+        let mut heatmap = Heatmap::default();
+        let mut heats: Vec<RegionHeat> = Vec::new();
+        for (chunk, heat) in self.chunks.iter().zip(compared) {
+            let changed = match heat {
+                ChunkHeat::Compared(changed) => changed,
+                ChunkHeat::Unread(pages) => {
+                    heatmap.unread += pages;
+                    continue;
+                }
+                ChunkHeat::Skipped(pages) => {
+                    heatmap.skipped += pages;
+                    continue;
+                }
+            };
+            // A short read leaves the rest of the chunk unread
+            heatmap.unread += pages(chunk.len()) - changed.len();
+            for (index, changed) in changed.into_iter().enumerate() {
+                let address = chunk.address + index * HEATMAP_PAGE_SIZE;
+                match heats.last_mut().filter(|x| x.region.contains(address)) {
+                    Some(heat) => heat.pages.push((address, changed)),
+                    None => match regions.iter().find(|x| x.contains(address)) {
+                        Some(region) => heats.push(RegionHeat { region: region.clone(), pages: vec![(address, changed)] }),
+                        None => heatmap.unread += 1,
+                    },
+                }
+            }
+        }
+        heats.sort_by(|a, b| b.fraction().total_cmp(&a.fraction()).then(b.changed().cmp(&a.changed())).then(a.region.start.cmp(&b.region.start)));
+        heatmap.regions = heats;
+        Ok(heatmap)
+    }
+}
+
+// The aligned offsets in a chunk at which a whole value starts
+fn value_offsets(address: usize, len: usize, size: usize, alignment: usize) -> std::iter::StepBy<std::ops::Range<usize>> {
+    let first = (alignment - address % alignment) % alignment;
+    (first..(len + 1).saturating_sub(size)).step_by(alignment)
+}
+
+// A scan for a value that is not known, only how it changes: at first every aligned value of the
+// type is a candidate, remembered by a snapshot of the memory, and each narrow keeps those whose
+// old and new values satisfy the comparison and stores the new ones for the next. Candidates are
+// kept as a bit per aligned offset of each chunk, and chunks left with none are dropped, so
+// however many there are the snapshot is the most this takes. Values that straddle two chunks,
+// which only an alignment that does not divide SNAPSHOT_CHUNK_SIZE allows, are never candidates
+#[derive(Debug, Clone)]
+pub struct UnknownScan {
+    snapshot: Snapshot,
+    // One per chunk, None while every aligned offset in it is still a candidate
+    candidates: Vec<Option<Vec<u64>>>,
+    size: usize,
+    alignment: usize,
+    pub endianness: Endianness,
+    // How many narrows have been done, 0 being the snapshot alone
+    pub generation: usize,
+    // The options' throttle, priority and memory budget, which each narrow keeps to as the
+    // snapshot did
+    pub throttle: Option<u64>,
+    pub priority: ScanPriority,
+    pub memory_budget: Option<usize>,
+    // Whether the target's soft-dirty bits were cleared as the stored bytes were read, so that a
+    // narrow need only read the pages written since. Once they cannot be used it goes back to
+    // reading everything for good
+    pub soft_dirty: bool,
+    // The bytes the last narrow took from what was stored rather than reading them again, their
+    // pages not having been written since
+    pub unread: usize,
+}
+
+impl UnknownScan {
+    // Values are size bytes long, at multiples of the options' alignment or else of their size.
+    // With the options' soft_dirty the bits are cleared before anything is read, so that a write
+    // made while the snapshot is taken is read again by the first narrow
+    pub fn start(process: impl ProcessMemory, size: usize, options: &ScanOptions) -> Result<UnknownScan, Box<dyn std::error::Error>> {
+        let soft_dirty = options.soft_dirty && process.clear_soft_dirty().is_ok();
+        let snapshot = Snapshot::capture(&process, options)?;
+        let candidates = vec![None; snapshot.chunks.len()];
+        Ok(UnknownScan { snapshot, candidates, size, alignment: options.alignment.unwrap_or(size).max(1), endianness: options.endianness, generation: 0, throttle: options.throttle, priority: options.priority, memory_budget: options.memory_budget, soft_dirty, unread: 0 })
+    }
+
+    pub fn snapshot(&self) -> &Snapshot {
+        &self.snapshot
+    }
+
+    pub fn candidates(&self) -> usize {
+        self.snapshot.chunks.iter().zip(&self.candidates).map(|(chunk, bits)| match bits {
+            None => value_offsets(chunk.address, chunk.len(), self.size, self.alignment).len(),
+            Some(bits) => bits.iter().map(|x| x.count_ones() as usize).sum(),
+        }).sum()
+    }
+
+    // The snapshot and the candidate bits together
+    pub fn stored_len(&self) -> usize {
+        self.snapshot.stored_len() + self.candidates.iter().flatten().map(|x| x.len() * 8).sum::<usize>()
+    }
+
+    pub fn addresses(&self) -> Vec<usize> {
+        let mut addresses = Vec::new();
+        for (chunk, bits) in self.snapshot.chunks.iter().zip(&self.candidates) {
+            let offsets = value_offsets(chunk.address, chunk.len(), self.size, self.alignment).enumerate();
+            addresses.extend(offsets.filter(|(i, _)| bits.as_ref().is_none_or(|x| x[i / 64] & 1 << (i % 64) != 0)).map(|x| chunk.address + x.1));
+        }
+        addresses
+    }
+
+    // Keeps the candidates whose value as it was and as it is now satisfy the predicate, returning
+    // how many chunks could no longer be read, whose candidates are dropped with them
+    pub fn narrow<T: Scalar>(&mut self, process: impl ProcessMemory, predicate: impl Fn(T, T) -> bool + Sync) -> Result<usize, Box<dyn std::error::Error>> {
+        if T::SIZE != self.size {
+            return Err(format!("The candidates are {}-byte values, not {}-byte ones", self.size, T::SIZE).into());
+        }
+        let (size, alignment, endianness) = (self.size, self.alignment, self.endianness);
+        let value = |bytes: &[u8]| if endianness.is_native() { T::from_bytes(bytes) } else { T::from_bytes_in(bytes, endianness) };
+        let written = self.written_since(&process);
+        let unread = AtomicUsize::new(0);
+        let throttle = Throttle::new(self.throttle);
+        // The old and new bytes of each chunk being compared are the buffers here
+        let spiller = at_priority(self.priority, || Spiller::new(self.memory_budget, rayon::current_num_threads() * SNAPSHOT_CHUNK_SIZE * 2));
+        let narrowed = at_priority(self.priority, || self.snapshot.chunks.par_iter().zip(self.candidates.par_iter()).map(|(chunk, bits)| {
+            let old = chunk.bytes().ok()?;
+            let new = match written.as_ref().and_then(|x| x.get(&chunk.address)) {
+                // Only the pages written since are read, over a copy of what was stored
+                Some(pieces) => {
+                    let mut new = old.to_vec();
+                    for &(start, end) in pieces {
+                        throttle.take(end - start);
+                        if read_bytes_into(&process, start, &mut new[start - chunk.address..end - chunk.address]).ok()? < end - start {
+                            return None;
+                        }
+                    }
+                    unread.fetch_add(chunk.len() - pieces.iter().map(|x| x.1 - x.0).sum::<usize>(), Ordering::Relaxed);
+                    new
+                }
+                None => {
+                    throttle.take(chunk.len());
+                    let mut new = vec![0u8; chunk.len()];
+                    if read_bytes_into(&process, chunk.address, &mut new).ok()? < new.len() {
+                        return None;
+                    }
+                    new
+                }
+            };
+            let offsets = value_offsets(chunk.address, chunk.len(), size, alignment);
+            let mut kept = vec![0u64; offsets.len().div_ceil(64)];
+            for (i, offset) in offsets.enumerate() {
+                if bits.as_ref().is_none_or(|x| x[i / 64] & 1 << (i % 64) != 0) && predicate(value(&old[offset..offset + size]), value(&new[offset..offset + size])) {
+                    kept[i / 64] |= 1 << (i % 64);
+                }
+            }
+            // A chunk left with no candidates is dropped, so its new bytes are not worth storing
+            if kept.iter().all(|x| *x == 0) {
+                return Some(Ok(None));
+            }
+            let compress = matches!(chunk.data, ChunkData::Lz4 { .. });
+            Some(SnapshotChunk::budgeted(chunk.address, new, compress, &spiller).map(|x| Some((x, kept))))
+        }).collect::<Vec<Option<Result<Option<(SnapshotChunk, Vec<u64>)>, String>>>>());
+        let unreadable = narrowed.iter().filter(|x| x.is_none()).count();
+        let narrowed = narrowed.into_iter().flatten().collect::<Result<Vec<Option<(SnapshotChunk, Vec<u64>)>>, String>>()?;
+        (self.snapshot.chunks, self.candidates) = narrowed.into_iter().flatten().map(|(chunk, kept)| (chunk, Some(kept))).unzip();
+        self.snapshot.fallbacks = spiller.fallback().into_iter().collect();
+        self.unread = unread.into_inner();
+        self.generation += 1;
+        Ok(unreadable)
+    }
+
+    // The pages of each chunk written since the bits were last cleared, by the chunk's address,
+    // clearing them again for the next narrow. Chunks that are no longer wholly inside a readable
+    // region are left out, so that reading them fails as it would without. A page written after
+    // pagemap is read but before the bits are cleared would be missed, so that is done at once;
+    // None, and no more tracking, once either fails
+    fn written_since(&mut self, process: impl ProcessMemory) -> Option<HashMap<usize, Vec<(usize, usize)>>> {
+        if !self.soft_dirty {
+            return None;
+        }
+        let chunks = self.snapshot.chunks.iter().map(|x| (x.address, x.address + x.len())).collect::<Vec<(usize, usize)>>();
+        let written = process.memory_ranges().and_then(|mapped| Ok((mapped, process.dirty_ranges(&chunks)?)));
+        let Ok((mapped, dirty)) = written.and_then(|x| process.clear_soft_dirty().map(|_| x)) else {
+            self.soft_dirty = false;
+            return None;
+        };
+        let mut written = HashMap::new();
+        for (start, end) in chunks {
+            let index = mapped.partition_point(|x| x.1 <= start);
+            if mapped.get(index).is_none_or(|x| x.0 > start || x.1 < end) {
+                continue;
+            }
+            let first = dirty.partition_point(|x| x.1 <= start);
+            let pieces = dirty[first..].iter().take_while(|x| x.0 < end).map(|x| (x.0.max(start), x.1.min(end))).collect();
+            written.insert(start, pieces);
+        }
+        Some(written)
+    }
+
+    // Keeps the candidates at addresses keep accepts without reading anything, as a generation of
+    // its own, and returns the candidates as they were for restore to put back
+    pub fn retain(&mut self, keep: impl Fn(usize) -> bool) -> Candidates {
+        let before = Candidates { candidates: self.candidates.clone(), generation: self.generation };
+        for (chunk, bits) in self.snapshot.chunks.iter().zip(self.candidates.iter_mut()) {
+            let offsets = value_offsets(chunk.address, chunk.len(), self.size, self.alignment);
+            let mut kept = vec![0u64; offsets.len().div_ceil(64)];
+            for (i, offset) in offsets.enumerate() {
+                if bits.as_ref().is_none_or(|x| x[i / 64] & 1 << (i % 64) != 0) && keep(chunk.address + offset) {
+                    kept[i / 64] |= 1 << (i % 64);
+                }
+            }
+            *bits = Some(kept);
+        }
+        // Chunks left with none are kept rather than dropped, so restore has them to go back to
+        self.generation += 1;
+        before
+    }
+
+    // Undoes the retain that returned the candidates, as long as nothing narrowed them since
+    pub fn restore(&mut self, before: Candidates) -> Result<(), Box<dyn std::error::Error>> {
+        if before.generation + 1 != self.generation || before.candidates.len() != self.candidates.len() {
+            return Err(format!("The candidates were narrowed since, at generation {} rather than {}", self.generation, before.generation + 1).into());
+        }
+        (self.candidates, self.generation) = (before.candidates, before.generation);
+        Ok(())
+    }
+}
+
+// Which of an unknown scan's candidates there were before a retain
+#[derive(Debug, Clone)]
+pub struct Candidates {
+    candidates: Vec<Option<Vec<u64>>>,
+    generation: usize,
+}
+
+// Whether new is old changed by percent of it, within tolerance, a fraction of old: health bars
+// show a percentage of what memory holds. The change is measured against old's magnitude, so a
+// decrease is always towards lower values, and an old value of 0 has no percentage to change by
+fn changed_by_percent<T: Scalar>(old: T, new: T, percent: f64, tolerance: f64) -> bool {
+    let (old, new) = (old.to_f64(), new.to_f64());
+    old != 0.0 && ((new - old) / old.abs() - percent / 100.0).abs() <= tolerance
+}
+
+fn check_percent(percent: f64, tolerance: f64) -> Result<(), Box<dyn std::error::Error>> {
+    if !(percent >= 0.0 && tolerance >= 0.0) {
+        return Err(format!("Expected a percentage and a tolerance of at least 0, got {} and {}", percent, tolerance).into());
+    }
+    Ok(())
+}
+
+// Keeps the candidates that dropped by about percent of what they were, so 25.0 with a tolerance
+// of 0.02 keeps a 100 that is now anywhere from 73 to 77. Returns what narrow does
+pub fn reduce_decreased_by_percent<T: Scalar>(process: impl ProcessMemory, scan: &mut UnknownScan, percent: f64, tolerance: f64) -> Result<usize, Box<dyn std::error::Error>> {
+    check_percent(percent, tolerance)?;
+    scan.narrow::<T>(process, |old, new| changed_by_percent(old, new, -percent, tolerance))
+}
+
+pub fn reduce_increased_by_percent<T: Scalar>(process: impl ProcessMemory, scan: &mut UnknownScan, percent: f64, tolerance: f64) -> Result<usize, Box<dyn std::error::Error>> {
+    check_percent(percent, tolerance)?;
+    scan.narrow::<T>(process, |old, new| changed_by_percent(old, new, percent, tolerance))
+}
+
+// Streams the regions the options select straight into an lz4-compressed capture at path, a block
+// at a time, for a snapshot of a target too big to hold in memory. Progress is reported as for a
+// scan. Once cancelled returns true nothing more is read and the file is removed
+pub fn snapshot_to_file(process: impl ProcessMemory, path: &Path, options: &ScanOptions, cancelled: impl Fn() -> bool) -> Result<Vec<CapturedRegion>, Box<dyn std::error::Error>> {
+    let mapped = process.memory_regions()?;
+    let mut ranges = filtered_ranges(&process, options)?;
+    if let Some(max_region_size) = options.max_region_size {
+        ranges.retain(|x| x.1 - x.0 <= max_region_size);
+    }
+    let regions = ranges.iter().map(|x| {
+        let region = mapped.iter().find(|y| y.start == x.0).cloned().unwrap_or(MemoryRegion { readable: true, ..MemoryRegion::default() });
+        MemoryRegion { start: x.0, end: x.1, ..region }
+    }).collect::<Vec<MemoryRegion>>();
+    let header = CaptureHeader { uncaptured: uncaptured(&mapped, &regions), ..CaptureHeader::of(&process)? };
+    let progress = ProgressReporter::new(options, regions.iter().map(|x| x.len()).sum());
+    let throttle = Throttle::new(options.throttle);
+    let mut done = 0;
+    let written = Capture::write(path, &header, Compression::Lz4, regions, |address, mut block| {
+        if cancelled() {
+            return Err("The snapshot was cancelled".into());
+        }
+        let end = address + block.len();
+        throttle.take(block.len());
+        let holes = dump_range(&process, address, end, &mut block)?;
+        done += end - address;
+        progress.update(done);
+        Ok(holes)
+    });
+    if written.is_err() {
+        let _ = std::fs::remove_file(path);
+    }
+    written
+}
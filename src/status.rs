@@ -0,0 +1,68 @@
+use std::os::unix::fs::MetadataExt;
+use nix::unistd::Pid;
+use crate::tracer::{Tracer, tracer_of};
+
+// What stands between this tool and the target, from /proc/<pid>/status and the kernel's ptrace
+// policy, in one place for the status command to show before anything is tried
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetStatus {
+    pub pid: Pid,
+    pub name: String,
+    // As the kernel puts it, e.g. "S (sleeping)" or "t (tracing stop)"
+    pub state: String,
+    pub tracer: Option<Tracer>,
+    pub uid: u32,
+    pub effective_uid: u32,
+    // None when it cannot be told, for a target running as root
+    pub dumpable: Option<bool>,
+    // 0 for none, 1 for strict, 2 for filters, with how many of them
+    pub seccomp: u8,
+    pub seccomp_filters: usize,
+    pub no_new_privs: bool,
+    // Yama's kernel.yama.ptrace_scope, if the kernel has Yama
+    pub ptrace_scope: Option<u8>,
+}
+
+impl TargetStatus {
+    pub fn stopped(&self) -> bool {
+        self.state.starts_with('T')
+    }
+
+    pub fn seccomp_mode(&self) -> &'static str {
+        match self.seccomp {
+            0 => "disabled",
+            1 => "strict",
+            _ => "filter",
+        }
+    }
+}
+
+// The kernel hands a process that is not dumpable, as after a setuid exec or prctl, its /proc files
+// to root, which is what stops anyone else reading its memory, so a target that is not root
+// whose mem is owned by root is not dumpable
+pub fn target_status(pid: Pid) -> Result<TargetStatus, Box<dyn std::error::Error>> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).map_err(|e| format!("Could not read the status of process {}: {}", pid, e))?;
+    let field = |name: &str| status.lines().find_map(|x| x.strip_prefix(name)?.strip_prefix(':')).map(str::trim);
+    let number = |name: &str| field(name).and_then(|x| x.parse::<usize>().ok());
+    let uids = field("Uid").unwrap_or_default().split_whitespace().filter_map(|x| x.parse::<u32>().ok()).collect::<Vec<u32>>();
+    let (uid, effective_uid) = (uids.first().copied().unwrap_or(0), uids.get(1).copied().unwrap_or(0));
+    let dumpable = match effective_uid {
+        0 => None,
+        _ => std::fs::metadata(format!("/proc/{}/mem", pid)).ok().map(|x| x.uid() != 0),
+    };
+    let ptrace_scope = std::fs::read_to_string("/proc/sys/kernel/yama/ptrace_scope").ok().and_then(|x| x.trim().parse().ok());
+    Ok(TargetStatus {
+        pid,
+        name: field("Name").unwrap_or_default().to_string(),
+        state: field("State").unwrap_or_default().to_string(),
+        tracer: tracer_of(pid),
+        uid,
+        effective_uid,
+        dumpable,
+        seccomp: number("Seccomp").unwrap_or(0) as u8,
+        seccomp_filters: number("Seccomp_filters").unwrap_or(0),
+        no_new_privs: number("NoNewPrivs") == Some(1),
+        ptrace_scope,
+    })
+}
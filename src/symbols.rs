@@ -0,0 +1,114 @@
+use std::{collections::BTreeMap, sync::{Arc, Mutex}, time::SystemTime};
+use crate::{elf::{ElfFile, Symbol, demangle}, maps::{Module, module_for_address}};
+
+// Naming addresses after the functions and data they are in, and finding them by name, from the
+// symbols of the ELF files the maps name. A module's symbols are in the file's own addresses, so
+// the load bias, its base less where the file's image starts, is added to them. The files are
+// read from the paths in the maps, so a module deleted or replaced on disk, a stripped one with
+// no .dynsym either, or one that is not ELF at all is given as module+offset only
+
+// Each file's table, parsed the first time any address in it is looked up. It is only used while
+// the file on disk has the size and modification time it did when it was read
+static TABLES: Mutex<BTreeMap<String, Cached>> = Mutex::new(BTreeMap::new());
+
+struct Cached {
+    len: u64,
+    modified: Option<SystemTime>,
+    table: Option<Arc<SymbolTable>>,
+}
+
+#[derive(Debug)]
+pub struct SymbolTable {
+    // Demangled, sorted by address
+    symbols: Vec<Symbol>,
+    image_start: usize,
+}
+
+impl SymbolTable {
+    fn read(path: &str) -> Option<SymbolTable> {
+        let elf = ElfFile::read(path).ok()?;
+        let symbols = elf.symbols().iter().map(|x| Symbol { name: demangle(&x.name), ..x.clone() }).collect::<Vec<Symbol>>();
+        (!symbols.is_empty()).then_some(SymbolTable { symbols, image_start: elf.image_start })
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    // The symbol an offset into the module is in, with how far into it. One without a size is
+    // taken to reach as far as the next
+    pub fn symbol_at(&self, offset: usize) -> Option<(&Symbol, usize)> {
+        let address = offset + self.image_start;
+        let symbol = &self.symbols[self.symbols.partition_point(|x| x.address <= address).checked_sub(1)?];
+        if symbol.size != 0 && address >= symbol.address + symbol.size {
+            return None;
+        }
+        Some((symbol, address - symbol.address))
+    }
+
+    // The offsets into the module of the symbols with the name
+    pub fn offsets_of(&self, name: &str) -> Vec<usize> {
+        self.symbols.iter().filter(|x| x.name == name && x.address >= self.image_start).map(|x| x.address - self.image_start).collect()
+    }
+}
+
+// Cached per file, None for one with no symbols to be had
+pub fn symbol_table(module: &Module) -> Option<Arc<SymbolTable>> {
+    if module.deleted {
+        return None;
+    }
+    let metadata = std::fs::metadata(&module.path).ok()?;
+    let (len, modified) = (metadata.len(), metadata.modified().ok());
+    let mut tables = TABLES.lock().unwrap();
+    if let Some(cached) = tables.get(&module.path) && (cached.len, cached.modified) == (len, modified) {
+        return cached.table.clone();
+    }
+    let table = SymbolTable::read(&module.path).map(Arc::new);
+    tables.insert(module.path.clone(), Cached { len, modified, table: table.clone() });
+    table
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub address: usize,
+    // The module and how far into it, then the symbol and how far into that where the module
+    // has symbols
+    pub module: Option<(String, usize)>,
+    pub symbol: Option<(String, usize)>,
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.module, &self.symbol) {
+            (Some((module, _)), Some((symbol, into))) => write!(f, "{}!{}+0x{:x}", module, symbol, into),
+            (Some((module, offset)), None) => write!(f, "{}+0x{:x}", module, offset),
+            _ => write!(f, "0x{:x}", self.address),
+        }
+    }
+}
+
+pub fn locate(modules: &[Module], address: usize) -> Location {
+    let Some(module) = module_for_address(modules, address) else {
+        return Location { address, module: None, symbol: None };
+    };
+    let offset = address - module.base;
+    let symbol = symbol_table(module).and_then(|table| table.symbol_at(offset).map(|(symbol, into)| (symbol.name.clone(), into)));
+    Location { address, module: Some((module.name.clone(), offset)), symbol }
+}
+
+// Every loaded symbol with the name, demangled without its hash, e.g. bench_target::tick. A
+// name given as module!symbol is only looked for in that module
+pub fn find_symbol(modules: &[Module], name: &str) -> Vec<Location> {
+    let (module_name, name) = match name.split_once('!') {
+        Some((module, name)) => (Some(module), name),
+        None => (None, name),
+    };
+    let mut found = Vec::new();
+    for module in modules.iter().filter(|x| module_name.is_none_or(|name| x.name == name)) {
+        let Some(table) = symbol_table(module) else { continue };
+        for offset in table.offsets_of(name) {
+            found.push(Location { address: module.base + offset, module: Some((module.name.clone(), offset)), symbol: Some((name.to_string(), 0)) });
+        }
+    }
+    found
+}
@@ -0,0 +1,546 @@
+use std::{collections::{BTreeMap, HashSet}, panic::AssertUnwindSafe, sync::{Arc, Mutex, Weak, mpsc::{Receiver, RecvTimeoutError, Sender, channel}}, thread::JoinHandle, time::Duration};
+use nix::{errno::Errno, sys::{ptrace::{self, AddressType}, signal::Signal, wait::{WaitPidFlag, WaitStatus, waitpid}}, unistd::Pid};
+
+// How often the session's thread checks on the target. A signal sent to a tracee holds it stopped
+// until its tracer passes the signal on, so this is the longest a signal is held up
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(2);
+
+// What PTRACE_PEEKDATA and PTRACE_POKEDATA move at a time. It is the tracer's word, so a 32-bit
+// target traced from a 64-bit build still moves 8 bytes a request
+const WORD: usize = size_of::<libc::c_long>();
+
+// The sessions open in this process, by target. A thread has only one tracer, so a second
+// session would be refused by the kernel; seize hands out the one already open instead
+static SESSIONS: Mutex<BTreeMap<i32, Weak<Shared>>> = Mutex::new(BTreeMap::new());
+
+// A PTRACE_PEEKDATA or PTRACE_POKEDATA transfer. The reply says how many bytes were read or written
+#[derive(Debug)]
+enum Transfer {
+    Read(usize, usize, Sender<Result<Vec<u8>, Errno>>),
+    Write(usize, Vec<u8>, Sender<Result<usize, Errno>>),
+}
+
+type Job = Box<dyn FnOnce(&mut Traced) + Send>;
+
+// What the session's thread is asked to do, since only the thread that seized may make ptrace
+// requests of the target
+enum Request {
+    Transfer(Transfer),
+    Run(Job),
+}
+
+// How a tracee stopped on purpose is let go again, the same as serve would have
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Resume {
+    Continue(Option<Signal>),
+    // A group-stop from the signal, which PTRACE_LISTEN leaves stopped, as it would be untraced,
+    // until a SIGCONT
+    Listen(Signal),
+    // Attached the old way, which keeps it stopped for as long as the session lasts
+    Hold,
+}
+
+impl Resume {
+    pub(crate) fn resume(self, pid: Pid) {
+        match self {
+            Resume::Continue(signal) => {
+                let _ = ptrace::cont(pid, signal);
+            }
+            Resume::Listen(_) => unsafe {
+                libc::ptrace(libc::PTRACE_LISTEN, pid.as_raw(), 0, 0);
+            },
+            Resume::Hold => {}
+        }
+    }
+
+    // What to hand on with a detach, so that no signal is lost
+    fn signal(self) -> Option<Signal> {
+        match self {
+            Resume::Continue(signal) => signal,
+            _ => None,
+        }
+    }
+}
+
+// What a handler made of a SIGTRAP
+pub(crate) enum Trap {
+    Handled(Resume),
+    // The thread went while being dealt with
+    Gone,
+    // The target's own, delivered to it as any other signal
+    NotMine,
+}
+
+// Something that puts traps into the target, such as debug registers or int3s, and so has to see
+// the SIGTRAPs they raise before the target does. Called on the session's thread, where the
+// ptrace requests it makes are allowed
+pub(crate) trait Handler: Send {
+    fn trapped(&mut self, thread: Pid) -> Trap;
+    // A thread the target started, stopped before it has run anything
+    fn started(&mut self, thread: Pid);
+    // Taken off the session, with the threads it could stop stopped. Whatever the handler put into
+    // the target comes out here, since a trap left behind kills the target once nothing handles it
+    fn removed(&mut self, stopped: &[(Pid, Resume)]);
+}
+
+// The ptrace session on a process: every thread seized with PTRACE_SEIZE, which unlike
+// PTRACE_ATTACH does not stop the target, and PTRACE_O_TRACECLONE to bring in the threads it
+// starts later. Being its tracer can be what it takes to be let into its /proc/<pid>/mem, and is
+// what watchpoints, breakpoints and injected syscalls need. A tracee stops for every signal sent
+// to it until the tracer lets it carry on, so a thread of the session's own passes each signal on
+// for as long as it lasts, and makes every ptrace request, since they have to come from the thread
+// that seized. There is one session per target: seize shares the open one, as do clones. Dropping
+// the last detaches, even when that thread panicked, handing on any signal caught in between
+#[derive(Debug, Clone)]
+pub struct PtraceSession {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    pid: Pid,
+    requests: Option<Sender<Request>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PtraceSession {
+    // On a kernel without PTRACE_SEIZE, from before 3.4, only the main thread is attached to,
+    // which stops the target until the session ends
+    pub fn seize(pid: Pid) -> Result<PtraceSession, Box<dyn std::error::Error>> {
+        let mut sessions = SESSIONS.lock().unwrap();
+        // One whose thread has ended, with the target gone or after a panic, is no use
+        if let Some(shared) = sessions.get(&pid.as_raw()).and_then(Weak::upgrade)
+            && shared.thread.as_ref().is_some_and(|x| !x.is_finished()) {
+            return Ok(PtraceSession { shared });
+        }
+        check_not_traced(pid)?;
+        let (seized, result) = channel();
+        let (requests, received) = channel();
+        let thread = std::thread::spawn(move || match trace(pid) {
+            Ok(traced) => {
+                let _ = seized.send(Ok(()));
+                traced.serve(received);
+            }
+            Err(e) => {
+                let _ = seized.send(Err(e));
+            }
+        });
+        match result.recv()? {
+            Ok(()) => {
+                let shared = Arc::new(Shared { pid, requests: Some(requests), thread: Some(thread) });
+                sessions.insert(pid.as_raw(), Arc::downgrade(&shared));
+                Ok(PtraceSession { shared })
+            }
+            // Attached to since it was checked
+            Err(Errno::EPERM) if let Err(traced) = check_not_traced(pid) => Err(traced.into()),
+            Err(e) => Err(format!("Could not seize process {}: {}", pid, e).into()),
+        }
+    }
+
+    pub fn pid(&self) -> Pid {
+        self.shared.pid
+    }
+
+    // Every thread of the target being traced, including those it started since the seize
+    pub fn threads(&self) -> Vec<Pid> {
+        let mut threads = self.run(|traced| traced.threads.iter().copied().collect::<Vec<Pid>>()).unwrap_or_default();
+        threads.sort();
+        threads
+    }
+
+    // Reads with PTRACE_PEEKDATA a word at a time, stopping a thread of the target for as long as
+    // it takes. A read that faults partway through returns what it got before the fault
+    pub fn read(&self, address: usize, buffer: &mut [u8]) -> Result<usize, Errno> {
+        let (reply, result) = channel();
+        self.send(Request::Transfer(Transfer::Read(address, buffer.len(), reply)))?;
+        let data = result.recv().map_err(|_| Errno::ESRCH)??;
+        buffer[..data.len()].copy_from_slice(&data);
+        Ok(data.len())
+    }
+
+    // Writes with PTRACE_POKEDATA, which like /proc/<pid>/mem ignores the page's protection
+    pub fn write(&self, address: usize, data: &[u8]) -> Result<usize, Errno> {
+        let (reply, result) = channel();
+        self.send(Request::Transfer(Transfer::Write(address, data.to_vec(), reply)))?;
+        result.recv().map_err(|_| Errno::ESRCH)?
+    }
+
+    // Runs job on the session's thread and waits for what it returns. A job that panics is an
+    // error, and leaves the session as it was
+    pub(crate) fn run<R: Send + 'static>(&self, job: impl FnOnce(&mut Traced) -> R + Send + 'static) -> Result<R, Box<dyn std::error::Error>> {
+        let (reply, result) = channel();
+        let gone = || format!("process {} is gone", self.pid());
+        self.send(Request::Run(Box::new(move |traced| {
+            let _ = reply.send(std::panic::catch_unwind(AssertUnwindSafe(|| job(traced))));
+        }))).map_err(|_| gone())?;
+        match result.recv() {
+            Ok(Ok(returned)) => Ok(returned),
+            Ok(Err(_)) => Err(format!("what the ptrace session was running on process {} panicked", self.pid()).into()),
+            Err(_) => Err(gone().into()),
+        }
+    }
+
+    // The thread only stops listening once the target is gone
+    fn send(&self, request: Request) -> Result<(), Errno> {
+        self.shared.requests.as_ref().ok_or(Errno::ESRCH)?.send(request).map_err(|_| Errno::ESRCH)
+    }
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        drop(self.requests.take());
+        if let Some(thread) = self.thread.take()
+            && thread.thread().id() != std::thread::current().id() {
+            let _ = thread.join();
+        }
+        let mut sessions = SESSIONS.lock().unwrap();
+        if sessions.get(&self.pid.as_raw()).is_some_and(|x| x.strong_count() == 0) {
+            sessions.remove(&self.pid.as_raw());
+        }
+    }
+}
+
+// What the session's thread holds: the threads it traces and the handler of their traps. Dropping
+// it lets them all go, so that a panic on that thread does not leave the target traced
+pub(crate) struct Traced {
+    pub(crate) pid: Pid,
+    pub(crate) threads: HashSet<Pid>,
+    pub(crate) handler: Option<Box<dyn Handler>>,
+    // The thread whose stop the handler is dealing with, which is already stopped should the
+    // handler panic and the threads be let go
+    handling: Option<Pid>,
+    // Attached with PTRACE_ATTACH, and so stopped throughout
+    attached: bool,
+}
+
+// Seizes every thread in /proc/<pid>/task, listing it again until a pass finds none new, since a
+// thread not yet seized can start others that PTRACE_O_TRACECLONE would miss. Unknown requests
+// fail with EIO, which is how a kernel without PTRACE_SEIZE refuses it
+fn trace(pid: Pid) -> Result<Traced, Errno> {
+    let mut traced = Traced { pid, threads: HashSet::new(), handler: None, handling: None, attached: false };
+    loop {
+        let tasks = std::fs::read_dir(format!("/proc/{}/task", pid)).map_err(|_| Errno::ESRCH)?;
+        let new = tasks.filter_map(|x| x.ok()?.file_name().to_str()?.parse::<i32>().ok()).map(Pid::from_raw).filter(|x| !traced.threads.contains(x)).collect::<Vec<Pid>>();
+        if new.is_empty() {
+            return Ok(traced);
+        }
+        for thread in new {
+            match ptrace::seize(thread, ptrace::Options::PTRACE_O_TRACECLONE) {
+                Ok(()) => {
+                    traced.threads.insert(thread);
+                }
+                Err(Errno::EIO) if traced.threads.is_empty() => {
+                    ptrace::attach(pid)?;
+                    waitpid(pid, Some(WaitPidFlag::__WALL))?;
+                    traced.attached = true;
+                    traced.threads.insert(pid);
+                    return Ok(traced);
+                }
+                // Exited since it was listed
+                Err(Errno::ESRCH) if thread != pid => {}
+                // Dropping traced lets go of those seized so far
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Traced {
+    // Lets the target carry on after each stop, and does what it is asked, until the session is
+    // dropped or the target is gone. __WNOTHREAD takes the stops of the traced threads and leaves
+    // the children of the process's other threads alone
+    fn serve(mut self, requests: Receiver<Request>) {
+        while !self.threads.is_empty() {
+            match waitpid(None, Some(WaitPidFlag::WNOHANG | WaitPidFlag::__WALL | WaitPidFlag::__WNOTHREAD)) {
+                Ok(WaitStatus::StillAlive) => match requests.recv_timeout(POLL_INTERVAL) {
+                    Ok(request) => {
+                        let mut transfers = Vec::new();
+                        for request in std::iter::once(request).chain(requests.try_iter()) {
+                            match request {
+                                Request::Transfer(transfer) => transfers.push(transfer),
+                                Request::Run(job) => {
+                                    self.transfer(std::mem::take(&mut transfers));
+                                    job(&mut self);
+                                    // The handler panicked under the job, which only caught it
+                                    // for the job's own sake, so the threads are let go
+                                    if self.handling.is_some() {
+                                        return;
+                                    }
+                                }
+                            }
+                        }
+                        self.transfer(transfers);
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => return,
+                },
+                Ok(status) => {
+                    if let Some(thread) = status.pid()
+                        && let Some(resume) = self.stopped(thread, status) {
+                        resume.resume(thread);
+                    }
+                }
+                // Nothing left to wait for
+                Err(_) => return,
+            }
+        }
+    }
+
+    // Says how a thread carries on after a stop, or None if it is gone. Signals are passed on as
+    // if nothing were tracing it, other than the SIGTRAPs the handler says are its own
+    pub(crate) fn stopped(&mut self, thread: Pid, status: WaitStatus) -> Option<Resume> {
+        match status {
+            WaitStatus::Stopped(_, Signal::SIGTRAP) if let Some(handler) = &mut self.handler => {
+                self.handling = Some(thread);
+                let trap = handler.trapped(thread);
+                self.handling = None;
+                match trap {
+                    Trap::Handled(resume) => Some(resume),
+                    Trap::NotMine => Some(Resume::Continue(Some(Signal::SIGTRAP))),
+                    Trap::Gone => {
+                        self.threads.remove(&thread);
+                        None
+                    }
+                }
+            }
+            WaitStatus::Stopped(_, signal) => Some(Resume::Continue(Some(signal))),
+            // A thread the target started, which begins stopped
+            WaitStatus::PtraceEvent(_, _, libc::PTRACE_EVENT_STOP) if !self.threads.contains(&thread) => {
+                self.threads.insert(thread);
+                if let Some(handler) = &mut self.handler {
+                    self.handling = Some(thread);
+                    handler.started(thread);
+                    self.handling = None;
+                }
+                Some(Resume::Continue(None))
+            }
+            WaitStatus::PtraceEvent(_, signal, libc::PTRACE_EVENT_STOP) if is_stop_signal(signal) => Some(Resume::Listen(signal)),
+            WaitStatus::PtraceEvent(..) => Some(Resume::Continue(None)),
+            // Exited, killed, or gone some other way
+            _ => {
+                self.threads.remove(&thread);
+                None
+            }
+        }
+    }
+
+    // Interrupts the thread and waits for it to stop, giving how to let it go again, or None if it
+    // is gone. Any stop will do, so one that was already pending is used and the interrupt's own
+    // is let go by serve. A trap can come in just as the thread is interrupted, leaving its SIGTRAP
+    // queued behind the interrupt's stop, so it is let through and dealt with first: left queued,
+    // it would reach the target after whatever raised it is gone and kill the thread
+    pub(crate) fn stop(&mut self, thread: Pid) -> Option<Resume> {
+        if self.attached {
+            return self.threads.contains(&thread).then_some(Resume::Hold);
+        }
+        if self.handling == Some(thread) {
+            return Some(Resume::Continue(None));
+        }
+        let mut status = ptrace::interrupt(thread).and_then(|_| waitpid(thread, Some(WaitPidFlag::__WALL)));
+        loop {
+            let resume = match status {
+                Ok(status) => self.stopped(thread, status),
+                Err(_) => {
+                    self.threads.remove(&thread);
+                    None
+                }
+            };
+            match resume {
+                Some(Resume::Continue(None)) if trap_pending(thread) => status = ptrace::cont(thread, None).and_then(|_| waitpid(thread, Some(WaitPidFlag::__WALL))),
+                resume => return resume,
+            }
+        }
+    }
+
+    pub(crate) fn stop_all(&mut self) -> Vec<(Pid, Resume)> {
+        self.threads.clone().into_iter().filter_map(|thread| Some((thread, self.stop(thread)?))).collect()
+    }
+
+    // Whatever the handler put into the target comes out before it goes
+    pub(crate) fn remove_handler(&mut self) {
+        let stopped = self.stop_all();
+        if let Some(mut handler) = self.handler.take() {
+            handler.removed(&stopped);
+        }
+        for (thread, resume) in stopped {
+            resume.resume(thread);
+        }
+    }
+
+    // Peeking and poking need a stopped thread, and memory is shared by all of them, so the main
+    // thread is interrupted and let go again once every transfer queued by then is done, which
+    // keeps a scan's many reads to a few stops
+    fn transfer(&mut self, transfers: Vec<Transfer>) {
+        if transfers.is_empty() {
+            return;
+        }
+        let thread = Some(self.pid).filter(|x| self.threads.contains(x)).or_else(|| self.threads.iter().next().copied());
+        let Some((thread, resume)) = thread.and_then(|thread| Some((thread, self.stop(thread)?))) else {
+            for transfer in transfers {
+                fail(transfer, Errno::ESRCH);
+            }
+            return;
+        };
+        // Answered once the target is running again
+        let replies = transfers.into_iter().map(|transfer| -> Box<dyn FnOnce()> {
+            match transfer {
+                Transfer::Read(address, len, reply) => {
+                    let result = peek(thread, address, len);
+                    Box::new(move || {
+                        let _ = reply.send(result);
+                    })
+                }
+                Transfer::Write(address, data, reply) => {
+                    let result = poke(thread, address, &data);
+                    Box::new(move || {
+                        let _ = reply.send(result);
+                    })
+                }
+            }
+        }).collect::<Vec<Box<dyn FnOnce()>>>();
+        resume.resume(thread);
+        for reply in replies {
+            reply();
+        }
+    }
+}
+
+// PTRACE_DETACH only works on a stopped tracee, so every thread is stopped first
+impl Drop for Traced {
+    fn drop(&mut self) {
+        let stopped = self.stop_all();
+        if let Some(mut handler) = self.handler.take() {
+            handler.removed(&stopped);
+        }
+        for (thread, resume) in stopped {
+            let _ = ptrace::detach(thread, resume.signal());
+        }
+    }
+}
+
+fn fail(transfer: Transfer, errno: Errno) {
+    match transfer {
+        Transfer::Read(_, _, reply) => {
+            let _ = reply.send(Err(errno));
+        }
+        Transfer::Write(_, _, reply) => {
+            let _ = reply.send(Err(errno));
+        }
+    }
+}
+
+// Whether the kernel holds a SIGTRAP for the thread that it has not stopped for yet, from
+// SigPnd in its status, which is the thread's own pending set
+fn trap_pending(thread: Pid) -> bool {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", thread)).unwrap_or_default();
+    let pending = status.lines().find_map(|x| u64::from_str_radix(x.strip_prefix("SigPnd:")?.trim(), 16).ok()).unwrap_or(0);
+    pending & 1 << (Signal::SIGTRAP as u64 - 1) != 0
+}
+
+// Words are read at aligned addresses, so none straddles into a page that is not mapped, and
+// only the requested bytes of the words at either end are kept
+pub(crate) fn peek(pid: Pid, address: usize, len: usize) -> Result<Vec<u8>, Errno> {
+    let mut data = Vec::with_capacity(len);
+    while data.len() < len {
+        let at = address + data.len();
+        let skip = at % WORD;
+        let word = match ptrace::read(pid, (at - skip) as AddressType) {
+            Ok(word) => word.to_ne_bytes(),
+            Err(e) if data.is_empty() => return Err(e),
+            Err(_) => break,
+        };
+        let take = (WORD - skip).min(len - data.len());
+        data.extend_from_slice(&word[skip..skip + take]);
+    }
+    Ok(data)
+}
+
+// A partial word at either end is read first, so that the bytes around the range are written
+// back as they were
+pub(crate) fn poke(pid: Pid, address: usize, data: &[u8]) -> Result<usize, Errno> {
+    let mut written = 0;
+    while written < data.len() {
+        let at = address + written;
+        let skip = at % WORD;
+        let take = (WORD - skip).min(data.len() - written);
+        let result = match take == WORD {
+            true => Ok([0; WORD]),
+            false => ptrace::read(pid, (at - skip) as AddressType).map(|x| x.to_ne_bytes()),
+        }.and_then(|mut word| {
+            word[skip..skip + take].copy_from_slice(&data[written..written + take]);
+            ptrace::write(pid, (at - skip) as AddressType, libc::c_long::from_ne_bytes(word))
+        });
+        match result {
+            Ok(()) => written += take,
+            Err(e) if written == 0 => return Err(e),
+            Err(_) => break,
+        }
+    }
+    Ok(written)
+}
+
+pub(crate) fn is_stop_signal(signal: Signal) -> bool {
+    matches!(signal, Signal::SIGSTOP | Signal::SIGTSTP | Signal::SIGTTIN | Signal::SIGTTOU)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tracer {
+    pub pid: Pid,
+    // Unless it has gone, or is not ours to look at
+    pub name: Option<String>,
+}
+
+impl std::fmt::Display for Tracer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{} ({})", self.pid, name),
+            None => write!(f, "{}", self.pid),
+        }
+    }
+}
+
+impl Tracer {
+    // This process, with a session of its own open on the target
+    pub fn is_us(&self) -> bool {
+        self.pid.as_raw() as u32 == std::process::id()
+    }
+}
+
+fn status_field(pid: i32, name: &str) -> Option<String> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    status.lines().find_map(|x| x.strip_prefix(name)?.strip_prefix(':')).map(|x| x.trim().to_string())
+}
+
+// Whatever is tracing the process, from the TracerPid line of its status. That is the thread
+// doing the tracing, as with this tool's own sessions, so it is given as the process it is in
+pub fn tracer_of(pid: Pid) -> Option<Tracer> {
+    let thread = status_field(pid.as_raw(), "TracerPid")?.parse::<i32>().ok().filter(|x| *x != 0)?;
+    let tracer = status_field(thread, "Tgid").and_then(|x| x.parse::<i32>().ok()).unwrap_or(thread);
+    let name = std::fs::read_to_string(format!("/proc/{}/comm", tracer)).ok().map(|x| x.trim().to_string());
+    Some(Tracer { pid: Pid::from_raw(tracer), name })
+}
+
+// A process has one tracer, so with a debugger on it, or a target that traces itself to keep
+// debuggers off, ptrace fails with the same EPERM a permissions problem gives. Reading and
+// writing its memory without ptrace still works
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlreadyTraced {
+    pub pid: Pid,
+    pub tracer: Tracer,
+}
+
+impl std::fmt::Display for AlreadyTraced {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Process {} is already being traced by {}, which has to detach before anything needing ptrace can be used", self.pid, self.tracer)
+    }
+}
+
+impl std::error::Error for AlreadyTraced {}
+
+// Fails if anything other than this process is tracing the target
+pub fn check_not_traced(pid: Pid) -> Result<(), AlreadyTraced> {
+    match tracer_of(pid) {
+        Some(tracer) if !tracer.is_us() => Err(AlreadyTraced { pid, tracer }),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,513 @@
+use std::{collections::HashMap, sync::Arc};
+use nix::{sys::ptrace, unistd::Pid};
+use crate::{elf::ElfFile, maps::{MemoryRegion, Module, get_memory_regions, module_for_address, modules_from_regions}, symbols::{Location, locate}, tracer::{PtraceSession, peek}};
+
+// Walking a stopped thread's stack to the return address of each caller: by the call frame
+// information in a module's .eh_frame where it covers the code, which is how code built without
+// frame pointers is unwound, and otherwise by following the chain of saved rbp values that code
+// built with them keeps. Both can be wrong about code doing something unusual with its stack, so
+// a backtrace ends, rather than failing, at the first frame that does not look right: a stack read
+// that fails, a rule the unwinder does not follow, a return address outside executable memory or a
+// stack pointer that does not move up. The files are read from the paths in the maps, so a module
+// deleted or replaced on disk is only followed by its frame pointers
+
+// Frames in a backtrace, counting the one the thread is in
+pub const DEFAULT_BACKTRACE_DEPTH: usize = 16;
+
+// The DWARF numbers of the registers the unwinder follows
+const RBP: usize = 6;
+const RSP: usize = 7;
+const RETURN_ADDRESS: usize = 16;
+
+// What unwinding a frame needs of the registers, and gives back for its caller
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FrameRegisters {
+    pub(crate) pc: usize,
+    pub(crate) sp: usize,
+    pub(crate) bp: usize,
+}
+
+impl From<libc::user_regs_struct> for FrameRegisters {
+    fn from(regs: libc::user_regs_struct) -> FrameRegisters {
+        FrameRegisters { pc: regs.rip as usize, sp: regs.rsp as usize, bp: regs.rbp as usize }
+    }
+}
+
+// Where a register's value in the caller is, once the CFA (the stack pointer before the call) is
+// known. Registers the code does not save are the same in the caller
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rule {
+    Same,
+    Undefined,
+    // Saved at CFA + offset
+    Offset(i64),
+    // Is CFA + offset
+    ValOffset(i64),
+    // Held in another register or given by a DWARF expression, which are not followed
+    Unsupported,
+}
+
+// The row of the call frame information for one address
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rules {
+    // A register and an offset from it, or None for a DWARF expression
+    cfa: Option<(usize, i64)>,
+    registers: [Rule; RETURN_ADDRESS + 1],
+}
+
+impl Rules {
+    fn unwind(&self, registers: FrameRegisters, read: &mut impl FnMut(usize) -> Option<usize>) -> Option<FrameRegisters> {
+        let (register, offset) = self.cfa?;
+        let base = match register {
+            RSP => registers.sp,
+            RBP => registers.bp,
+            _ => return None,
+        };
+        let cfa = base.checked_add_signed(offset as isize)?;
+        let mut restore = |rule: Rule, current: Option<usize>| match rule {
+            Rule::Same => current,
+            Rule::Offset(offset) => read(cfa.wrapping_add_signed(offset as isize)),
+            Rule::ValOffset(offset) => Some(cfa.wrapping_add_signed(offset as isize)),
+            Rule::Undefined | Rule::Unsupported => None,
+        };
+        // The return address is always given where it is known
+        let pc = restore(self.registers[RETURN_ADDRESS], None)?;
+        let bp = restore(self.registers[RBP], Some(registers.bp))?;
+        Some(FrameRegisters { pc, sp: cfa, bp })
+    }
+}
+
+// The caller's frame by the frame pointer: rbp points at the caller's saved rbp, with the return
+// address just above it
+fn frame_pointer(registers: FrameRegisters, read: &mut impl FnMut(usize) -> Option<usize>) -> Option<FrameRegisters> {
+    if registers.bp < registers.sp || !registers.bp.is_multiple_of(8) {
+        return None;
+    }
+    Some(FrameRegisters { pc: read(registers.bp + 8)?, sp: registers.bp + 16, bp: read(registers.bp)? })
+}
+
+// Reads the call frame information, with addresses as the file has them: the data starts at base
+struct Reader<'a> {
+    data: &'a [u8],
+    base: usize,
+    at: usize,
+}
+
+impl Reader<'_> {
+    fn bytes(&mut self, len: usize) -> Option<&[u8]> {
+        let bytes = self.data.get(self.at..self.at.checked_add(len)?)?;
+        self.at += len;
+        Some(bytes)
+    }
+
+    fn unsigned(&mut self, len: usize) -> Option<u64> {
+        Some(self.bytes(len)?.iter().rev().fold(0u64, |x, y| x << 8 | *y as u64))
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn uleb(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn sleb(&mut self) -> Option<i64> {
+        let mut value = 0i64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7f) as i64) << shift;
+            if byte & 0x80 == 0 {
+                return Some(if shift < 57 && byte & 0x40 != 0 { value | -1 << (shift + 7) } else { value });
+            }
+        }
+        None
+    }
+
+    // A DW_EH_PE-encoded pointer. Only absolute and pc-relative ones turn up in .eh_frame; an
+    // indirect one is given as the address it would be read from, since it is only ever skipped
+    fn pointer(&mut self, encoding: u8) -> Option<usize> {
+        let address = self.base + self.at;
+        let value = match encoding & 0x0f {
+            0x00 | 0x04 | 0x0c => self.unsigned(8)? as i64,
+            0x01 => self.uleb()? as i64,
+            0x02 => self.unsigned(2)? as i64,
+            0x03 => self.unsigned(4)? as i64,
+            0x09 => self.sleb()?,
+            0x0a => self.unsigned(2)? as i16 as i64,
+            0x0b => self.unsigned(4)? as i32 as i64,
+            _ => return None,
+        };
+        match encoding & 0x70 {
+            0x00 => Some(value as usize),
+            0x10 => Some(address.wrapping_add_signed(value as isize)),
+            _ => None,
+        }
+    }
+}
+
+// What a CIE says about the FDEs that refer to it
+struct Cie {
+    code_align: u64,
+    data_align: i64,
+    return_register: usize,
+    fde_encoding: u8,
+    // Whether FDEs have augmentation data to skip
+    augmented: bool,
+    // Its initial instructions, as offsets into .eh_frame
+    instructions: (usize, usize),
+}
+
+// The entry at offset starts with its length; none are more than 4 GiB, so the 64-bit form is
+// left alone. Gives where its contents start and end
+fn entry(data: &[u8], offset: usize) -> Option<(usize, usize)> {
+    let length = Reader { data, base: 0, at: offset }.unsigned(4)? as usize;
+    if length == 0 || length == 0xffff_ffff {
+        return None;
+    }
+    Some((offset + 4, (offset + 4).checked_add(length).filter(|&x| x <= data.len())?))
+}
+
+fn parse_cie(data: &[u8], base: usize, offset: usize) -> Option<Cie> {
+    let (start, end) = entry(data, offset)?;
+    let mut reader = Reader { data: &data[..end], base, at: start };
+    if reader.unsigned(4)? != 0 {
+        return None;
+    }
+    let version = reader.u8()?;
+    let augmentation = reader.bytes(data[reader.at..end].iter().position(|&x| x == 0)? + 1)?.to_vec();
+    if augmentation.windows(2).any(|x| x == b"eh") {
+        return None;
+    }
+    if version == 4 {
+        reader.bytes(2)?;
+    }
+    let (code_align, data_align) = (reader.uleb()?, reader.sleb()?);
+    let return_register = if version == 1 { reader.u8()? as usize } else { reader.uleb()? as usize };
+    let mut fde_encoding = 0;
+    let augmented = augmentation.first() == Some(&b'z');
+    if augmented {
+        let len = reader.uleb()? as usize;
+        let after = reader.at.checked_add(len)?;
+        for &c in &augmentation[1..augmentation.len() - 1] {
+            match c {
+                b'R' => fde_encoding = reader.u8()?,
+                b'L' => {
+                    reader.u8()?;
+                }
+                b'P' => {
+                    let encoding = reader.u8()?;
+                    reader.pointer(encoding);
+                }
+                _ => {}
+            }
+        }
+        reader.at = after;
+    }
+    Some(Cie { code_align, data_align, return_register, fde_encoding, augmented, instructions: (reader.at, end) })
+}
+
+// Runs call frame instructions on the row, up to the first one for code past target
+fn execute(reader: &mut Reader, end: usize, cie: &Cie, mut location: usize, target: usize, initial: &Rules, rules: &mut Rules) -> Option<()> {
+    let mut remembered = Vec::new();
+    let set = |rules: &mut Rules, register: u64, rule: Rule| {
+        if let Some(x) = rules.registers.get_mut(register as usize) {
+            *x = rule;
+        }
+    };
+    let factored = |x: u64| x as i64 * cie.data_align;
+    while reader.at < end {
+        let op = reader.u8()?;
+        let advance = match (op >> 6, op & 0x3f) {
+            (1, delta) => delta as u64,
+            (2, register) => {
+                let offset = factored(reader.uleb()?);
+                set(rules, register as u64, Rule::Offset(offset));
+                0
+            }
+            (3, register) => {
+                let register = register as usize;
+                if let Some(rule) = initial.registers.get(register) {
+                    rules.registers[register] = *rule;
+                }
+                0
+            }
+            (_, 0x00) => 0,
+            (_, 0x01) => {
+                location = reader.pointer(cie.fde_encoding)?;
+                if location > target {
+                    return Some(());
+                }
+                0
+            }
+            (_, 0x02) => reader.unsigned(1)?,
+            (_, 0x03) => reader.unsigned(2)?,
+            (_, 0x04) => reader.unsigned(4)?,
+            (_, 0x05) => {
+                let (register, offset) = (reader.uleb()?, factored(reader.uleb()?));
+                set(rules, register, Rule::Offset(offset));
+                0
+            }
+            (_, 0x06) => {
+                let register = reader.uleb()? as usize;
+                if let Some(rule) = initial.registers.get(register) {
+                    rules.registers[register] = *rule;
+                }
+                0
+            }
+            (_, 0x07) => {
+                set(rules, reader.uleb()?, Rule::Undefined);
+                0
+            }
+            (_, 0x08) => {
+                set(rules, reader.uleb()?, Rule::Same);
+                0
+            }
+            (_, 0x09) => {
+                let register = reader.uleb()?;
+                reader.uleb()?;
+                set(rules, register, Rule::Unsupported);
+                0
+            }
+            (_, 0x0a) => {
+                remembered.push(rules.clone());
+                0
+            }
+            (_, 0x0b) => {
+                *rules = remembered.pop()?;
+                0
+            }
+            (_, 0x0c) => {
+                rules.cfa = Some((reader.uleb()? as usize, reader.uleb()? as i64));
+                0
+            }
+            (_, 0x0d) => {
+                let register = reader.uleb()? as usize;
+                rules.cfa = Some((register, rules.cfa.map_or(0, |x| x.1)));
+                0
+            }
+            (_, 0x0e) => {
+                let offset = reader.uleb()? as i64;
+                rules.cfa = rules.cfa.map(|x| (x.0, offset));
+                0
+            }
+            (_, 0x0f) => {
+                let len = reader.uleb()? as usize;
+                reader.bytes(len)?;
+                rules.cfa = None;
+                0
+            }
+            (_, 0x10) | (_, 0x16) => {
+                let register = reader.uleb()?;
+                let len = reader.uleb()? as usize;
+                reader.bytes(len)?;
+                set(rules, register, Rule::Unsupported);
+                0
+            }
+            (_, 0x11) => {
+                let (register, offset) = (reader.uleb()?, reader.sleb()? * cie.data_align);
+                set(rules, register, Rule::Offset(offset));
+                0
+            }
+            (_, 0x12) => {
+                rules.cfa = Some((reader.uleb()? as usize, reader.sleb()? * cie.data_align));
+                0
+            }
+            (_, 0x13) => {
+                let offset = reader.sleb()? * cie.data_align;
+                rules.cfa = rules.cfa.map(|x| (x.0, offset));
+                0
+            }
+            (_, 0x14) => {
+                let (register, offset) = (reader.uleb()?, factored(reader.uleb()?));
+                set(rules, register, Rule::ValOffset(offset));
+                0
+            }
+            (_, 0x15) => {
+                let (register, offset) = (reader.uleb()?, reader.sleb()? * cie.data_align);
+                set(rules, register, Rule::ValOffset(offset));
+                0
+            }
+            // DW_CFA_GNU_args_size
+            (_, 0x2e) => {
+                reader.uleb()?;
+                0
+            }
+            // DW_CFA_GNU_negative_offset_extended
+            (_, 0x2f) => {
+                let (register, offset) = (reader.uleb()?, -factored(reader.uleb()?));
+                set(rules, register, Rule::Offset(offset));
+                0
+            }
+            _ => return None,
+        };
+        location = location.checked_add((advance * cie.code_align) as usize)?;
+        if location > target {
+            return Some(());
+        }
+    }
+    Some(())
+}
+
+// A module's file, read once, with every FDE in its .eh_frame as (start, end, offset), by start
+struct Loaded {
+    elf: ElfFile,
+    fdes: Vec<(usize, usize, usize)>,
+}
+
+impl Loaded {
+    fn read(path: &str) -> Option<Loaded> {
+        let elf = ElfFile::read(path).ok()?;
+        let mut fdes = Vec::new();
+        if let Some((base, data)) = elf.section(".eh_frame") {
+            let mut offset = 0;
+            while let Some((start, end)) = entry(data, offset) {
+                let mut reader = Reader { data: &data[..end], base, at: start };
+                let id = reader.unsigned(4).unwrap_or(0) as usize;
+                if id != 0 && let Some(cie) = start.checked_sub(id).and_then(|x| parse_cie(data, base, x)) {
+                    let range = reader.pointer(cie.fde_encoding).zip(reader.pointer(cie.fde_encoding & 0x0f));
+                    if let Some((begin, len)) = range {
+                        fdes.push((begin, begin.wrapping_add(len), offset));
+                    }
+                }
+                offset = end;
+            }
+        }
+        fdes.sort();
+        Some(Loaded { elf, fdes })
+    }
+
+    // The row that applies at an address in the file
+    fn rules(&self, address: usize) -> Option<Rules> {
+        let (base, data) = self.elf.section(".eh_frame")?;
+        let &(begin, end, offset) = self.fdes.get(self.fdes.partition_point(|x| x.0 <= address).checked_sub(1)?)?;
+        if address >= end {
+            return None;
+        }
+        let (start, fde_end) = entry(data, offset)?;
+        let mut reader = Reader { data: &data[..fde_end], base, at: start };
+        let cie = parse_cie(data, base, start.checked_sub(reader.unsigned(4)? as usize)?).filter(|x| x.return_register == RETURN_ADDRESS)?;
+        reader.pointer(cie.fde_encoding)?;
+        reader.pointer(cie.fde_encoding & 0x0f)?;
+        if cie.augmented {
+            let len = reader.uleb()? as usize;
+            reader.bytes(len)?;
+        }
+        let mut rules = Rules { cfa: None, registers: [Rule::Same; RETURN_ADDRESS + 1] };
+        let mut initial_reader = Reader { data, base, at: cie.instructions.0 };
+        let empty = rules.clone();
+        execute(&mut initial_reader, cie.instructions.1, &cie, 0, usize::MAX, &empty, &mut rules)?;
+        let initial = rules.clone();
+        execute(&mut reader, fde_end, &cie, begin, address, &initial, &mut rules)?;
+        Some(rules)
+    }
+}
+
+// Unwinds and names frames in one process, reading each module's file only once
+pub struct Unwinder {
+    pid: Pid,
+    regions: Vec<MemoryRegion>,
+    modules: Vec<Module>,
+    files: HashMap<String, Option<Arc<Loaded>>>,
+}
+
+impl std::fmt::Debug for Unwinder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Unwinder").field("pid", &self.pid).field("files", &self.files.len()).finish()
+    }
+}
+
+impl Unwinder {
+    pub fn new(pid: Pid) -> Unwinder {
+        let mut unwinder = Unwinder { pid, regions: Vec::new(), modules: Vec::new(), files: HashMap::new() };
+        unwinder.refresh();
+        unwinder
+    }
+
+    // Reads the maps again, for code loaded since
+    pub fn refresh(&mut self) {
+        if let Ok(regions) = get_memory_regions(self.pid) {
+            self.modules = modules_from_regions(&regions);
+            self.regions = regions;
+        }
+    }
+
+    // The maps are read again once before an address is taken to be outside executable memory
+    fn executable(&mut self, address: usize) -> bool {
+        let known = |regions: &[MemoryRegion]| regions.iter().any(|x| x.executable && x.contains(address));
+        if !known(&self.regions) {
+            self.refresh();
+        }
+        known(&self.regions)
+    }
+
+    // Where the thread is, then the return address of each caller, up to depth in all. read gives
+    // the word at an address of the thread's stack
+    pub(crate) fn walk(&mut self, registers: FrameRegisters, depth: usize, read: &mut impl FnMut(usize) -> Option<usize>) -> Vec<usize> {
+        let mut frames = Vec::new();
+        let mut registers = registers;
+        while frames.len() < depth {
+            frames.push(registers.pc);
+            // A return address is just past its call, which can be the last instruction of the
+            // function, so a caller's rules are looked up just before it
+            let lookup = if frames.len() == 1 { registers.pc } else { registers.pc.saturating_sub(1) };
+            let next = match self.rules(lookup) {
+                Some(rules) => rules.unwind(registers, read),
+                None => frame_pointer(registers, read),
+            };
+            match next {
+                Some(next) if next.pc != 0 && next.sp > registers.sp && self.executable(next.pc) => registers = next,
+                _ => break,
+            }
+        }
+        frames
+    }
+
+    fn load(&mut self, module: &Module) -> Option<Arc<Loaded>> {
+        if module.deleted {
+            return None;
+        }
+        self.files.entry(module.path.clone()).or_insert_with(|| Loaded::read(&module.path).map(Arc::new)).clone()
+    }
+
+    fn rules(&mut self, address: usize) -> Option<Rules> {
+        let module = module_for_address(&self.modules, address)?.clone();
+        let loaded = self.load(&module)?;
+        loaded.rules(address - module.base + loaded.elf.image_start)
+    }
+
+    // The module, and the function where the module has symbols, an address is in
+    pub fn describe(&self, address: usize) -> Location {
+        locate(&self.modules, address)
+    }
+}
+
+// A word of the target's memory through a thread the session has stopped
+pub(crate) fn read_word(thread: Pid, address: usize) -> Option<usize> {
+    Some(usize::from_ne_bytes(peek(thread, address, size_of::<usize>()).ok()?.try_into().ok()?))
+}
+
+// The thread is only stopped while its stack is walked, and the frames are named once it is
+// running again
+pub fn backtrace(pid: Pid, thread: Pid, depth: usize) -> Result<Vec<Location>, Box<dyn std::error::Error>> {
+    let mut unwinder = Unwinder::new(pid);
+    let (unwinder, frames) = PtraceSession::seize(pid)?.run(move |traced| {
+        if !traced.threads.contains(&thread) {
+            return Err(format!("{} is not a thread of process {}", thread, pid));
+        }
+        let resume = traced.stop(thread).ok_or(format!("Thread {} exited", thread))?;
+        let frames = ptrace::getregs(thread).map(|regs| unwinder.walk(regs.into(), depth, &mut |address| read_word(thread, address)));
+        resume.resume(thread);
+        let frames = frames.map_err(|e| format!("Could not read the registers of thread {}: {}", thread, e))?;
+        Ok((unwinder, frames))
+    })??;
+    Ok(frames.into_iter().map(|x| unwinder.describe(x)).collect())
+}
@@ -0,0 +1,779 @@
+use std::str::FromStr;
+use zerocopy::{FromBytes, Immutable, IntoBytes};
+
+// Fixed-size primitive values that can be converted to and from their native-endian bytes, which
+// lets scans compare byte patterns instead of casting pointers into the read buffer. Every one can
+// also be read and written whole by the generic functions, which need the zerocopy traits
+pub trait Scalar: FromBytes + IntoBytes + Immutable + Copy + PartialEq + PartialOrd + Default + Send + Sync + std::fmt::Display + std::fmt::Debug + 'static {
+    const SIZE: usize;
+    // As the type is typed, e.g. "u32"
+    const NAME: &'static str;
+    const MIN: Self;
+    const MAX: Self;
+
+    fn to_bytes(self) -> Vec<u8>;
+    // Expects exactly SIZE bytes
+    fn from_bytes(bytes: &[u8]) -> Self;
+
+    // Every byte pattern that compares equal to this value under PartialEq. For integers this is
+    // just the value's own bytes, but for floats 0.0 also equals -0.0 and NaN equals nothing
+    fn equal_patterns(self) -> Vec<Vec<u8>> {
+        vec![self.to_bytes()]
+    }
+
+    fn to_bytes_in(self, endianness: Endianness) -> Vec<u8> {
+        let mut bytes = self.to_bytes();
+        endianness.convert(&mut bytes);
+        bytes
+    }
+
+    fn from_bytes_in(bytes: &[u8], endianness: Endianness) -> Self {
+        let mut bytes = bytes.to_vec();
+        endianness.convert(&mut bytes);
+        Self::from_bytes(&bytes)
+    }
+
+    fn equal_patterns_in(self, endianness: Endianness) -> Vec<Vec<u8>> {
+        let mut patterns = self.equal_patterns();
+        patterns.iter_mut().for_each(|x| endianness.convert(x));
+        patterns
+    }
+
+    // Integers saturate at the type's bounds, or wrap around them; floats use ordinary arithmetic
+    // for both, going to infinity past their largest value
+    fn saturating_add(self, other: Self) -> Self;
+    fn saturating_sub(self, other: Self) -> Self;
+    fn saturating_mul(self, other: Self) -> Self;
+    fn wrapping_add(self, other: Self) -> Self;
+    fn wrapping_sub(self, other: Self) -> Self;
+    fn wrapping_mul(self, other: Self) -> Self;
+
+    // The smallest difference worth treating as a change: zero for integers, machine epsilon for floats
+    fn epsilon() -> Self {
+        Self::default()
+    }
+
+    fn abs_diff(self, other: Self) -> Self {
+        if self > other { self.saturating_sub(other) } else { other.saturating_sub(self) }
+    }
+
+    // For working out ratios between values; the largest 64-bit integers lose their lowest bits
+    fn to_f64(self) -> f64;
+
+    // For working things out exactly; None for floats, which have to_f64
+    fn to_i128(self) -> Option<i128> {
+        None
+    }
+
+    // A NaN equals nothing, itself included, so no comparison of one should keep it
+    fn is_nan(self) -> bool {
+        false
+    }
+}
+
+macro_rules! impl_scalar_int {
+    ($($t:ty),*) => {
+        $(
+            impl Scalar for $t {
+                const SIZE: usize = std::mem::size_of::<$t>();
+                const NAME: &'static str = stringify!($t);
+                const MIN: Self = <$t>::MIN;
+                const MAX: Self = <$t>::MAX;
+
+                fn to_bytes(self) -> Vec<u8> {
+                    self.to_ne_bytes().to_vec()
+                }
+
+                fn from_bytes(bytes: &[u8]) -> Self {
+                    <$t>::from_ne_bytes(bytes.try_into().expect("Expected a slice of exactly the value's size"))
+                }
+
+                fn saturating_add(self, other: Self) -> Self {
+                    <$t>::saturating_add(self, other)
+                }
+
+                fn saturating_sub(self, other: Self) -> Self {
+                    <$t>::saturating_sub(self, other)
+                }
+
+                fn saturating_mul(self, other: Self) -> Self {
+                    <$t>::saturating_mul(self, other)
+                }
+
+                fn wrapping_add(self, other: Self) -> Self {
+                    <$t>::wrapping_add(self, other)
+                }
+
+                fn wrapping_sub(self, other: Self) -> Self {
+                    <$t>::wrapping_sub(self, other)
+                }
+
+                fn wrapping_mul(self, other: Self) -> Self {
+                    <$t>::wrapping_mul(self, other)
+                }
+
+                fn to_f64(self) -> f64 {
+                    self as f64
+                }
+
+                fn to_i128(self) -> Option<i128> {
+                    Some(self as i128)
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_scalar_float {
+    ($($t:ty),*) => {
+        $(
+            impl Scalar for $t {
+                const SIZE: usize = std::mem::size_of::<$t>();
+                const NAME: &'static str = stringify!($t);
+                const MIN: Self = <$t>::MIN;
+                const MAX: Self = <$t>::MAX;
+
+                fn to_bytes(self) -> Vec<u8> {
+                    self.to_ne_bytes().to_vec()
+                }
+
+                fn from_bytes(bytes: &[u8]) -> Self {
+                    <$t>::from_ne_bytes(bytes.try_into().expect("Expected a slice of exactly the value's size"))
+                }
+
+                fn saturating_add(self, other: Self) -> Self {
+                    self + other
+                }
+
+                fn saturating_sub(self, other: Self) -> Self {
+                    self - other
+                }
+
+                fn saturating_mul(self, other: Self) -> Self {
+                    self * other
+                }
+
+                fn wrapping_add(self, other: Self) -> Self {
+                    self + other
+                }
+
+                fn wrapping_sub(self, other: Self) -> Self {
+                    self - other
+                }
+
+                fn wrapping_mul(self, other: Self) -> Self {
+                    self * other
+                }
+
+                fn to_f64(self) -> f64 {
+                    self as f64
+                }
+
+                fn epsilon() -> Self {
+                    <$t>::EPSILON
+                }
+
+                fn abs_diff(self, other: Self) -> Self {
+                    (self - other).abs()
+                }
+
+                fn is_nan(self) -> bool {
+                    <$t>::is_nan(self)
+                }
+
+                fn equal_patterns(self) -> Vec<Vec<u8>> {
+                    if self.is_nan() {
+                        Vec::new()
+                    }
+                    else if self == 0.0 {
+                        vec![(0.0 as $t).to_bytes(), (-0.0 as $t).to_bytes()]
+                    }
+                    else {
+                        vec![self.to_bytes()]
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_scalar_int!(i8, i16, i32, i64, u8, u16, u32, u64, isize, usize);
+impl_scalar_float!(f32, f64);
+
+// A value that is not one of its type's, with why, e.g. a negative number for an unsigned type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseValueError(pub String);
+
+impl std::fmt::Display for ParseValueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ParseValueError {}
+
+// Parses a value of the type, saying why a number is out of the type's range rather than only that
+// it is invalid: a negative value for an unsigned type is an easy mistake to make, and one that
+// would otherwise say "invalid digit"
+pub fn parse_scalar<T: Scalar + FromStr>(s: &str) -> Result<T, ParseValueError> where T::Err: std::fmt::Display {
+    let error = match s.parse::<T>() {
+        Ok(value) => return Ok(value),
+        Err(e) => e,
+    };
+    let article = if T::NAME.starts_with('u') { "a" } else { "an" };
+    let Ok(number) = s.parse::<i128>() else {
+        return Err(ParseValueError(format!("'{}' is not {} {}: {}", s, article, T::NAME, error)));
+    };
+    if number < 0 && T::MIN == T::default() {
+        return Err(ParseValueError(format!("{} is unsigned, so it cannot be {}; use i{} for values that can go below 0", T::NAME, s, T::SIZE * 8)));
+    }
+    Err(ParseValueError(format!("{} does not fit in {} {}, which holds {} to {}", s, article, T::NAME, T::MIN, T::MAX)))
+}
+
+// Runs the body with $t bound to the concrete Rust type of a numeric value type. Bytes and strings
+// cannot be scanned for or locked with an action, so they return an error instead
+#[macro_export]
+macro_rules! with_scan_type {
+    ($scan_type:expr, $t:ident, $body:block) => {
+        match $scan_type {
+            $crate::ValueType::I8 => { type $t = i8; $body }
+            $crate::ValueType::I16 => { type $t = i16; $body }
+            $crate::ValueType::I32 => { type $t = i32; $body }
+            $crate::ValueType::I64 => { type $t = i64; $body }
+            $crate::ValueType::U8 => { type $t = u8; $body }
+            $crate::ValueType::U16 => { type $t = u16; $body }
+            $crate::ValueType::U32 => { type $t = u32; $body }
+            $crate::ValueType::U64 => { type $t = u64; $body }
+            $crate::ValueType::F32 => { type $t = f32; $body }
+            $crate::ValueType::F64 => { type $t = f64; $body }
+            other => return Err(format!("Expected a numeric type, got {}", other).into()),
+        }
+    };
+}
+
+// A change made to a value in place, as by write <address> += <value>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arithmetic {
+    Add,
+    Sub,
+    Mul,
+}
+
+impl Arithmetic {
+    // In the value's own type, saturating at its bounds unless told to wrap around them. Floats
+    // are the same either way
+    pub fn apply<T: Scalar>(&self, current: T, operand: T, wrapping: bool) -> T {
+        match (self, wrapping) {
+            (Arithmetic::Add, false) => current.saturating_add(operand),
+            (Arithmetic::Sub, false) => current.saturating_sub(operand),
+            (Arithmetic::Mul, false) => current.saturating_mul(operand),
+            (Arithmetic::Add, true) => current.wrapping_add(operand),
+            (Arithmetic::Sub, true) => current.wrapping_sub(operand),
+            (Arithmetic::Mul, true) => current.wrapping_mul(operand),
+        }
+    }
+}
+
+impl std::fmt::Display for Arithmetic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Arithmetic::Add => write!(f, "+="),
+            Arithmetic::Sub => write!(f, "-="),
+            Arithmetic::Mul => write!(f, "*="),
+        }
+    }
+}
+
+impl FromStr for Arithmetic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "+=" => Ok(Arithmetic::Add),
+            "-=" => Ok(Arithmetic::Sub),
+            "*=" => Ok(Arithmetic::Mul),
+            _ => Err(format!("Unknown operation '{}', expected +=, -= or *=", s)),
+        }
+    }
+}
+
+// The byte order of numeric values in the target's memory. Native unless the target is, say, a
+// big-endian machine running under an emulator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Endianness {
+    #[default]
+    Native,
+    Little,
+    Big,
+}
+
+impl Endianness {
+    pub fn is_native(&self) -> bool {
+        match self {
+            Endianness::Native => true,
+            Endianness::Little => cfg!(target_endian = "little"),
+            Endianness::Big => cfg!(target_endian = "big"),
+        }
+    }
+
+    // Turns a native-endian value's bytes into this byte order, or back, which is the same swap
+    pub fn convert(&self, bytes: &mut [u8]) {
+        if !self.is_native() {
+            bytes.reverse();
+        }
+    }
+}
+
+impl std::fmt::Display for Endianness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Endianness::Native => write!(f, "native"),
+            Endianness::Little => write!(f, "le"),
+            Endianness::Big => write!(f, "be"),
+        }
+    }
+}
+
+impl FromStr for Endianness {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "native" => Ok(Endianness::Native),
+            "le" | "little" => Ok(Endianness::Little),
+            "be" | "big" => Ok(Endianness::Big),
+            _ => Err(format!("Unknown byte order '{}', expected le, be or native", s)),
+        }
+    }
+}
+
+// How numbers are shown, independently of the type they were read as. Hex and binary show the
+// value's bits, two's complement for negative integers and IEEE 754 for floats, padded to the
+// type's size
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Radix {
+    #[default]
+    Decimal,
+    // An integer as if it had the other signedness, e.g. an i8 of -1 as 255 or a u8 of 255 as -1
+    Signed,
+    Unsigned,
+    Hex,
+    // In groups of four digits, e.g. 0b0000_0010_1010
+    Binary,
+    // Decimal, then hex in brackets
+    Both,
+}
+
+impl std::fmt::Display for Radix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Radix::Decimal => write!(f, "dec"),
+            Radix::Signed => write!(f, "signed"),
+            Radix::Unsigned => write!(f, "unsigned"),
+            Radix::Hex => write!(f, "hex"),
+            Radix::Binary => write!(f, "bin"),
+            Radix::Both => write!(f, "both"),
+        }
+    }
+}
+
+impl FromStr for Radix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dec" | "decimal" => Ok(Radix::Decimal),
+            "signed" => Ok(Radix::Signed),
+            "unsigned" => Ok(Radix::Unsigned),
+            "hex" => Ok(Radix::Hex),
+            "bin" | "binary" => Ok(Radix::Binary),
+            "both" => Ok(Radix::Both),
+            _ => Err(format!("Unknown format '{}', expected dec, signed, unsigned, hex, bin or both", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DisplayFormat {
+    pub radix: Radix,
+    // Digits after the point of a float shown in decimal, or as few as tell it apart if None
+    pub precision: Option<usize>,
+}
+
+// Splits a "be:" or "le:" prefix off a value, e.g. "be:i32:999" or "be:999"
+pub fn strip_endianness(s: &str) -> (Option<Endianness>, &str) {
+    match s.split_once(':') {
+        Some((prefix @ ("be" | "le"), rest)) => (prefix.parse().ok(), rest),
+        _ => (None, s),
+    }
+}
+
+// How a string value is turned into bytes. UTF-16 code units follow the value's byte order, like the
+// scalars
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16,
+}
+
+impl Encoding {
+    // Bytes in a code unit, and so in a terminator
+    pub fn unit_size(&self) -> usize {
+        match self {
+            Encoding::Utf8 => 1,
+            Encoding::Utf16 => 2,
+        }
+    }
+}
+
+// The type tag shared by typed writes, scans and saved locks. Written as the part of "i32:999"
+// before the colon; strings are "str" or "utf16", with a "z" suffix when a terminating NUL is added
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueType {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+    Bytes,
+    Str { encoding: Encoding, null_terminate: bool },
+}
+
+// A value together with its type, e.g. parsed from "i32:999", "f32:1.5", "bytes:90 90" or "strz:hello"
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypedValue {
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    F32(f32),
+    F64(f64),
+    Bytes(Vec<u8>),
+    Str { text: String, encoding: Encoding, null_terminate: bool },
+}
+
+impl ValueType {
+    pub const NUMERIC: [ValueType; 10] = [ValueType::I8, ValueType::I16, ValueType::I32, ValueType::I64, ValueType::U8, ValueType::U16, ValueType::U32, ValueType::U64, ValueType::F32, ValueType::F64];
+
+    pub fn is_numeric(&self) -> bool {
+        !matches!(self, ValueType::Bytes | ValueType::Str { .. })
+    }
+
+    // The size of every value of the type, or None where it depends on the value
+    pub fn size(&self) -> Option<usize> {
+        match self {
+            ValueType::I8 | ValueType::U8 => Some(1),
+            ValueType::I16 | ValueType::U16 => Some(2),
+            ValueType::I32 | ValueType::U32 | ValueType::F32 => Some(4),
+            ValueType::I64 | ValueType::U64 | ValueType::F64 => Some(8),
+            ValueType::Bytes | ValueType::Str { .. } => None,
+        }
+    }
+
+    // Parses the part of the textual form after the colon
+    pub fn parse_value(&self, s: &str) -> Result<TypedValue, Box<dyn std::error::Error>> {
+        Ok(match *self {
+            ValueType::I8 => TypedValue::I8(parse_scalar(s)?),
+            ValueType::I16 => TypedValue::I16(parse_scalar(s)?),
+            ValueType::I32 => TypedValue::I32(parse_scalar(s)?),
+            ValueType::I64 => TypedValue::I64(parse_scalar(s)?),
+            ValueType::U8 => TypedValue::U8(parse_scalar(s)?),
+            ValueType::U16 => TypedValue::U16(parse_scalar(s)?),
+            ValueType::U32 => TypedValue::U32(parse_scalar(s)?),
+            ValueType::U64 => TypedValue::U64(parse_scalar(s)?),
+            ValueType::F32 => TypedValue::F32(parse_scalar(s)?),
+            ValueType::F64 => TypedValue::F64(parse_scalar(s)?),
+            ValueType::Bytes => TypedValue::Bytes(parse_hex_bytes(s)?),
+            ValueType::Str { encoding, null_terminate } => TypedValue::Str { text: s.to_string(), encoding, null_terminate },
+        })
+    }
+
+    // The inverse of TypedValue::to_bytes. Numeric types expect exactly their size, and a NUL
+    // terminated string its terminator
+    pub fn value_from_bytes(&self, bytes: &[u8]) -> Result<TypedValue, Box<dyn std::error::Error>> {
+        self.value_from_bytes_in(bytes, Endianness::Native)
+    }
+
+    // The inverse of TypedValue::to_bytes_in
+    pub fn value_from_bytes_in(&self, bytes: &[u8], endianness: Endianness) -> Result<TypedValue, Box<dyn std::error::Error>> {
+        if let Some(size) = self.size() && bytes.len() != size {
+            return Err(format!("Expected {} bytes for a {} value, got {}", size, self, bytes.len()).into());
+        }
+        Ok(match *self {
+            ValueType::I8 => TypedValue::I8(i8::from_bytes_in(bytes, endianness)),
+            ValueType::I16 => TypedValue::I16(i16::from_bytes_in(bytes, endianness)),
+            ValueType::I32 => TypedValue::I32(i32::from_bytes_in(bytes, endianness)),
+            ValueType::I64 => TypedValue::I64(i64::from_bytes_in(bytes, endianness)),
+            ValueType::U8 => TypedValue::U8(u8::from_bytes_in(bytes, endianness)),
+            ValueType::U16 => TypedValue::U16(u16::from_bytes_in(bytes, endianness)),
+            ValueType::U32 => TypedValue::U32(u32::from_bytes_in(bytes, endianness)),
+            ValueType::U64 => TypedValue::U64(u64::from_bytes_in(bytes, endianness)),
+            ValueType::F32 => TypedValue::F32(f32::from_bytes_in(bytes, endianness)),
+            ValueType::F64 => TypedValue::F64(f64::from_bytes_in(bytes, endianness)),
+            ValueType::Bytes => TypedValue::Bytes(bytes.to_vec()),
+            ValueType::Str { encoding, null_terminate } => {
+                let unit = if encoding == Encoding::Utf16 { 2 } else { 1 };
+                let bytes = if null_terminate { bytes.strip_suffix(&[0u8; 2][..unit]).ok_or(format!("Expected a {} string to end in a NUL", self))? } else { bytes };
+                let text = match encoding {
+                    Encoding::Utf8 => String::from_utf8(bytes.to_vec())?,
+                    Encoding::Utf16 => {
+                        if !bytes.len().is_multiple_of(2) {
+                            return Err("Expected an even number of bytes for a UTF-16 string".into());
+                        }
+                        String::from_utf16(&bytes.chunks_exact(2).map(|x| u16::from_bytes_in(x, endianness)).collect::<Vec<u16>>())?
+                    }
+                };
+                TypedValue::Str { text, encoding, null_terminate }
+            }
+        })
+    }
+}
+
+impl std::fmt::Display for ValueType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValueType::I8 => write!(f, "i8"),
+            ValueType::I16 => write!(f, "i16"),
+            ValueType::I32 => write!(f, "i32"),
+            ValueType::I64 => write!(f, "i64"),
+            ValueType::U8 => write!(f, "u8"),
+            ValueType::U16 => write!(f, "u16"),
+            ValueType::U32 => write!(f, "u32"),
+            ValueType::U64 => write!(f, "u64"),
+            ValueType::F32 => write!(f, "f32"),
+            ValueType::F64 => write!(f, "f64"),
+            ValueType::Bytes => write!(f, "bytes"),
+            ValueType::Str { encoding, null_terminate } => {
+                write!(f, "{}", if *encoding == Encoding::Utf8 { "str" } else { "utf16" })?;
+                if *null_terminate { write!(f, "z") } else { Ok(()) }
+            }
+        }
+    }
+}
+
+impl FromStr for ValueType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "i8" => Ok(ValueType::I8),
+            "i16" => Ok(ValueType::I16),
+            "i32" => Ok(ValueType::I32),
+            "i64" => Ok(ValueType::I64),
+            "u8" => Ok(ValueType::U8),
+            "u16" => Ok(ValueType::U16),
+            "u32" => Ok(ValueType::U32),
+            "u64" => Ok(ValueType::U64),
+            "f32" => Ok(ValueType::F32),
+            "f64" => Ok(ValueType::F64),
+            "bytes" => Ok(ValueType::Bytes),
+            "str" => Ok(ValueType::Str { encoding: Encoding::Utf8, null_terminate: false }),
+            "strz" => Ok(ValueType::Str { encoding: Encoding::Utf8, null_terminate: true }),
+            "utf16" => Ok(ValueType::Str { encoding: Encoding::Utf16, null_terminate: false }),
+            "utf16z" => Ok(ValueType::Str { encoding: Encoding::Utf16, null_terminate: true }),
+            _ => Err(format!("Unknown type '{}', expected one of i8, i16, i32, i64, u8, u16, u32, u64, f32, f64, bytes, str, strz, utf16, utf16z", s)),
+        }
+    }
+}
+
+impl TypedValue {
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            TypedValue::I8(_) => ValueType::I8,
+            TypedValue::I16(_) => ValueType::I16,
+            TypedValue::I32(_) => ValueType::I32,
+            TypedValue::I64(_) => ValueType::I64,
+            TypedValue::U8(_) => ValueType::U8,
+            TypedValue::U16(_) => ValueType::U16,
+            TypedValue::U32(_) => ValueType::U32,
+            TypedValue::U64(_) => ValueType::U64,
+            TypedValue::F32(_) => ValueType::F32,
+            TypedValue::F64(_) => ValueType::F64,
+            TypedValue::Bytes(_) => ValueType::Bytes,
+            TypedValue::Str { encoding, null_terminate, .. } => ValueType::Str { encoding: *encoding, null_terminate: *null_terminate },
+        }
+    }
+
+    // The part of the textual form after the colon
+    pub fn format_value(&self) -> String {
+        match self {
+            TypedValue::I8(x) => x.to_string(),
+            TypedValue::I16(x) => x.to_string(),
+            TypedValue::I32(x) => x.to_string(),
+            TypedValue::I64(x) => x.to_string(),
+            TypedValue::U8(x) => x.to_string(),
+            TypedValue::U16(x) => x.to_string(),
+            TypedValue::U32(x) => x.to_string(),
+            TypedValue::U64(x) => x.to_string(),
+            TypedValue::F32(x) => x.to_string(),
+            TypedValue::F64(x) => x.to_string(),
+            TypedValue::Bytes(bytes) => bytes.iter().map(|x| format!("{:02x}", x)).collect::<Vec<String>>().join(" "),
+            TypedValue::Str { text, .. } => text.clone(),
+        }
+    }
+
+    // An integer's or a float's bits, zero-extended, and how many bytes they take
+    fn bits(&self) -> Option<(u64, usize)> {
+        Some(match self {
+            TypedValue::I8(x) => (*x as u8 as u64, 1),
+            TypedValue::I16(x) => (*x as u16 as u64, 2),
+            TypedValue::I32(x) => (*x as u32 as u64, 4),
+            TypedValue::I64(x) => (*x as u64, 8),
+            TypedValue::U8(x) => (*x as u64, 1),
+            TypedValue::U16(x) => (*x as u64, 2),
+            TypedValue::U32(x) => (*x as u64, 4),
+            TypedValue::U64(x) => (*x, 8),
+            TypedValue::F32(x) => (x.to_bits() as u64, 4),
+            TypedValue::F64(x) => (x.to_bits(), 8),
+            TypedValue::Bytes(_) | TypedValue::Str { .. } => return None,
+        })
+    }
+
+    // The value as the format shows numbers; bytes and strings are shown as format_value does
+    pub fn format_as(&self, format: DisplayFormat) -> String {
+        let Some((bits, size)) = self.bits() else {
+            return self.format_value();
+        };
+        let decimal = match (self, format.precision) {
+            (TypedValue::F32(x), Some(precision)) => format!("{:.*}", precision, x),
+            (TypedValue::F64(x), Some(precision)) => format!("{:.*}", precision, x),
+            _ => self.format_value(),
+        };
+        let float = matches!(self, TypedValue::F32(_) | TypedValue::F64(_));
+        let hex = format!("0x{:0width$x}", bits, width = size * 2);
+        match format.radix {
+            Radix::Decimal => decimal,
+            // Floats have no other signedness to be shown in
+            Radix::Signed | Radix::Unsigned if float => decimal,
+            Radix::Signed => (((bits << (64 - size * 8)) as i64) >> (64 - size * 8)).to_string(),
+            Radix::Unsigned => bits.to_string(),
+            Radix::Hex => hex,
+            Radix::Binary => {
+                let digits = format!("{:0width$b}", bits, width = size * 8).into_bytes();
+                format!("0b{}", digits.chunks(4).map(|x| String::from_utf8_lossy(x).into_owned()).collect::<Vec<String>>().join("_"))
+            }
+            Radix::Both => format!("{} ({})", decimal, hex),
+        }
+    }
+
+    // Exactly what a write of the value puts in memory
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_bytes_in(Endianness::Native)
+    }
+
+    // Numbers and UTF-16 code units in the given byte order; UTF-8 and raw bytes are unaffected
+    pub fn to_bytes_in(&self, endianness: Endianness) -> Vec<u8> {
+        match self {
+            TypedValue::I8(x) => x.to_bytes_in(endianness),
+            TypedValue::I16(x) => x.to_bytes_in(endianness),
+            TypedValue::I32(x) => x.to_bytes_in(endianness),
+            TypedValue::I64(x) => x.to_bytes_in(endianness),
+            TypedValue::U8(x) => x.to_bytes_in(endianness),
+            TypedValue::U16(x) => x.to_bytes_in(endianness),
+            TypedValue::U32(x) => x.to_bytes_in(endianness),
+            TypedValue::U64(x) => x.to_bytes_in(endianness),
+            TypedValue::F32(x) => x.to_bytes_in(endianness),
+            TypedValue::F64(x) => x.to_bytes_in(endianness),
+            TypedValue::Bytes(bytes) => bytes.clone(),
+            TypedValue::Str { text, encoding, null_terminate } => {
+                let mut bytes = match encoding {
+                    Encoding::Utf8 => text.as_bytes().to_vec(),
+                    Encoding::Utf16 => text.encode_utf16().flat_map(|x| x.to_bytes_in(endianness)).collect(),
+                };
+                if *null_terminate {
+                    bytes.extend(std::iter::repeat_n(0, if *encoding == Encoding::Utf16 { 2 } else { 1 }));
+                }
+                bytes
+            }
+        }
+    }
+}
+
+// The inverse of FromStr, e.g. "i32:999" or "bytes:90 90"
+impl std::fmt::Display for TypedValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.value_type(), self.format_value())
+    }
+}
+
+impl FromStr for TypedValue {
+    type Err = Box<dyn std::error::Error>;
+
+    // Everything after the first colon is the value, so strings may contain colons themselves
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (value_type, value) = s.split_once(':').ok_or(format!("Expected <type>:<value>, e.g. i32:999, got '{}'", s))?;
+        value_type.parse::<ValueType>()?.parse_value(value)
+    }
+}
+
+// Accepts hex bytes with or without spaces between them, e.g. "de ad be ef", "dead beef" or
+// "deadbeef". A group with an odd number of digits splits a byte, as in "dea dbeef", and is taken
+// for a typo rather than joined up with the next
+pub fn parse_hex_bytes(s: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let groups = s.split_whitespace().collect::<Vec<&str>>();
+    if groups.is_empty() || groups.iter().any(|x| !x.len().is_multiple_of(2) || !x.bytes().all(|x| x.is_ascii_hexdigit())) {
+        return Err(format!("Expected hex bytes, each as two digits, got '{}'", s).into());
+    }
+    let digits = groups.concat();
+    Ok((0..digits.len()).step_by(2).map(|x| u8::from_str_radix(&digits[x..x + 2], 16)).collect::<Result<Vec<u8>, _>>()?)
+}
+
+// What fills the rest of a fixed-size string buffer after the text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Pad {
+    #[default]
+    None,
+    Null,
+    Space,
+}
+
+// For writing a string into a buffer of known size. max_len is in bytes and counts the terminator;
+// a text that does not fit is an error rather than being cut short, since cutting a UTF-8 string
+// could split a character
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StringWriteOptions {
+    pub encoding: Encoding,
+    // Byte order of UTF-16 code units
+    pub endianness: Endianness,
+    pub max_len: Option<usize>,
+    pub pad: Pad,
+    pub add_terminator: bool,
+}
+
+// The bytes write_string writes: the text, then the terminator if asked for, then padding up to
+// max_len. Padding is in whole code units, so a UTF-16 space is 20 00 in little-endian order
+pub fn encode_string(text: &str, options: &StringWriteOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut bytes = TypedValue::Str { text: text.to_string(), encoding: options.encoding, null_terminate: options.add_terminator }.to_bytes_in(options.endianness);
+    let Some(max_len) = options.max_len else {
+        return Ok(bytes);
+    };
+    if bytes.len() > max_len {
+        return Err(format!("{:?} takes {} bytes, more than the {} allowed", text, bytes.len(), max_len).into());
+    }
+    let unit: u16 = match options.pad {
+        Pad::None => return Ok(bytes),
+        Pad::Null => 0,
+        Pad::Space => b' ' as u16,
+    };
+    let unit = match options.encoding {
+        Encoding::Utf8 => vec![unit as u8],
+        Encoding::Utf16 => unit.to_bytes_in(options.endianness),
+    };
+    if !(max_len - bytes.len()).is_multiple_of(unit.len()) {
+        return Err(format!("{} bytes cannot be padded with whole UTF-16 code units", max_len).into());
+    }
+    while bytes.len() < max_len {
+        bytes.extend_from_slice(&unit);
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,514 @@
+use std::{collections::{BTreeMap, VecDeque}, sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}}, time::SystemTime};
+use nix::{errno::Errno, sys::{ptrace::{self, AddressType}, signal::Signal, wait::{WaitPidFlag, WaitStatus, waitpid}}, unistd::Pid};
+use crate::{maps::get_memory_regions, symbols::Location, tracer::{Handler, PtraceSession, Resume, Trap, Traced, peek, poke}, unwind::{DEFAULT_BACKTRACE_DEPTH, FrameRegisters, Unwinder, read_word}};
+
+// x86-64 has four debug address registers, DR0 to DR3, so no more can be armed at once
+pub const MAX_WATCHPOINTS: usize = 4;
+
+// Bytes of code shown before and after the instruction pointer of a hit
+pub const CODE_WINDOW: usize = 16;
+
+// Changes each watch keeps in its history unless told otherwise, the oldest going first
+pub const DEFAULT_HISTORY_LIMIT: usize = 1000;
+
+// The one-byte int3 a breakpoint puts over the first byte of its instruction
+const INT3: u8 = 0xcc;
+
+// What a watchpoint traps on, as the R/W field of DR7 has it. There is no reads-only setting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Write,
+    // Reads and writes both
+    Access,
+}
+
+// What a hit did. The hardware only says that a watchpoint fired, so an access watch tells the two
+// apart by whether the value changed since the last hit, and a write of the value already there
+// counts as a read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+impl std::fmt::Display for Access {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Access::Read => write!(f, "read"),
+            Access::Write => write!(f, "write"),
+        }
+    }
+}
+
+// One armed watchpoint, with how often each instruction was caught at it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watch {
+    pub address: usize,
+    pub size: usize,
+    pub kind: WatchKind,
+    // Keyed by the instruction pointer after the access and what it did, so a hot loop is one
+    // entry however often it runs
+    pub hits: BTreeMap<(usize, Access), u64>,
+    // What the watched bytes held at the last hit, or when armed
+    pub value: Vec<u8>,
+    // The latest hits that changed the value, oldest first, and how many went before them
+    pub history: VecDeque<ValueChange>,
+    pub dropped: u64,
+}
+
+// A hit that changed the watched value. Only the first size bytes of old and new are the value:
+// they are kept as they were read, for the hit to cost no more than a copy, and made sense of
+// when shown
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ValueChange {
+    pub time: SystemTime,
+    pub thread: Pid,
+    pub rip: usize,
+    pub old: [u8; 8],
+    pub new: [u8; 8],
+}
+
+// An access caught by a watchpoint. The trap comes once the instruction has finished, so rip is
+// the instruction after it
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchHit {
+    pub address: usize,
+    pub thread: Pid,
+    pub rip: usize,
+    pub access: Access,
+    // How many hits there have been from this rip with this access, counting this one
+    pub count: u64,
+    // What the watched bytes hold afterwards
+    pub value: Vec<u8>,
+    // Code around rip, starting at code_start, for telling which instruction it was
+    pub code_start: usize,
+    pub code: Vec<u8>,
+    // From rip, then each caller, as far as the stack could be unwound
+    pub backtrace: Vec<Location>,
+}
+
+// One software breakpoint, with the byte its int3 covers
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breakpoint {
+    pub address: usize,
+    pub original: u8,
+    pub hits: u64,
+}
+
+// A thread reaching a breakpoint, before the instruction there runs. registers holds the ones
+// worth looking at on the way into a function: the System V arguments, rax, the stack and frame
+// pointers, and the return address at the top of the stack
+#[derive(Debug, Clone, PartialEq)]
+pub struct BreakHit {
+    pub address: usize,
+    pub thread: Pid,
+    // Counting this one
+    pub hits: u64,
+    pub registers: Vec<(&'static str, u64)>,
+    // From the breakpoint, then each caller
+    pub backtrace: Vec<Location>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatchEvent {
+    Watch(WatchHit),
+    Break(BreakHit),
+}
+
+// Called on the watcher's thread for every hit, so it must not block for long
+#[derive(Clone)]
+pub struct WatchCallback(pub Arc<dyn Fn(&WatchEvent) + Send + Sync>);
+
+impl std::fmt::Debug for WatchCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "WatchCallback")
+    }
+}
+
+type Slots = Arc<Mutex<[Option<Watch>; MAX_WATCHPOINTS]>>;
+type Breakpoints = Arc<Mutex<BTreeMap<usize, Breakpoint>>>;
+
+// Hardware watchpoints and software breakpoints on every thread of a process, through its
+// PtraceSession, so the target keeps running. The debug registers are per thread and only
+// writable while it is stopped, so every change interrupts them all. Dropping the watcher disarms
+// everything, and the session detaches once nothing else holds it
+#[derive(Debug)]
+pub struct Watcher {
+    pid: Pid,
+    slots: Slots,
+    breakpoints: Breakpoints,
+    backtrace_depth: Arc<AtomicUsize>,
+    history_limit: Arc<AtomicUsize>,
+    session: PtraceSession,
+}
+
+impl Watcher {
+    pub fn start(pid: Pid, callback: WatchCallback) -> Result<Watcher, Box<dyn std::error::Error>> {
+        let slots: Slots = Arc::default();
+        let breakpoints: Breakpoints = Arc::default();
+        let backtrace_depth = Arc::new(AtomicUsize::new(DEFAULT_BACKTRACE_DEPTH));
+        let history_limit = Arc::new(AtomicUsize::new(DEFAULT_HISTORY_LIMIT));
+        let session = PtraceSession::seize(pid)?;
+        let server = Server { slots: slots.clone(), breakpoints: breakpoints.clone(), backtrace_depth: backtrace_depth.clone(), history_limit: history_limit.clone(), unwinder: Unwinder::new(pid), callback };
+        let installed = session.run(move |traced| match traced.handler {
+            Some(_) => false,
+            None => {
+                traced.handler = Some(Box::new(server));
+                true
+            }
+        })?;
+        if !installed {
+            return Err(format!("Process {} is already being watched", pid).into());
+        }
+        Ok(Watcher { pid, slots, breakpoints, backtrace_depth, history_limit, session })
+    }
+
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    // How many frames each hit's backtrace goes to, counting the one hit; 0 leaves them empty
+    pub fn set_backtrace_depth(&self, depth: usize) {
+        self.backtrace_depth.store(depth, Ordering::Relaxed);
+    }
+
+    pub fn backtrace_depth(&self) -> usize {
+        self.backtrace_depth.load(Ordering::Relaxed)
+    }
+
+    // How many changes each watch's history keeps; 0 records none. Histories already longer are
+    // cut down to the latest
+    pub fn set_history_limit(&self, limit: usize) {
+        self.history_limit.store(limit, Ordering::Relaxed);
+        for watch in self.slots.lock().unwrap().iter_mut().flatten() {
+            while watch.history.len() > limit {
+                watch.history.pop_front();
+                watch.dropped += 1;
+            }
+        }
+    }
+
+    pub fn history_limit(&self) -> usize {
+        self.history_limit.load(Ordering::Relaxed)
+    }
+
+    // Arms a watchpoint on size bytes, which have to be 1, 2, 4 or 8 and aligned to their size
+    pub fn watch(&self, address: usize, size: usize, kind: WatchKind) -> Result<(), Box<dyn std::error::Error>> {
+        if !matches!(size, 1 | 2 | 4 | 8) {
+            return Err(format!("A watchpoint covers 1, 2, 4 or 8 bytes, not {}", size).into());
+        }
+        if !address.is_multiple_of(size) {
+            return Err(format!("A {}-byte watchpoint has to be at a multiple of {}, which 0x{:x} is not", size, size, address).into());
+        }
+        {
+            let mut slots = self.slots.lock().unwrap();
+            if slots.iter().flatten().any(|x| x.address < address + size && address < x.address + x.size) {
+                return Err(format!("0x{:x} is already watched", address).into());
+            }
+            let free = slots.iter_mut().find(|x| x.is_none()).ok_or(format!("At most {} watchpoints can be armed at once, one per debug register; unwatch one first", MAX_WATCHPOINTS))?;
+            *free = Some(Watch { address, size, kind, hits: BTreeMap::new(), value: Vec::new(), history: VecDeque::new(), dropped: 0 });
+        }
+        let result = self.program();
+        if result.is_err() {
+            self.slots.lock().unwrap().iter_mut().filter(|x| x.as_ref().is_some_and(|x| x.address == address)).for_each(|x| *x = None);
+            let _ = self.program();
+        }
+        result
+    }
+
+    pub fn unwatch(&self, address: usize) -> Result<(), Box<dyn std::error::Error>> {
+        {
+            let mut slots = self.slots.lock().unwrap();
+            let slot = slots.iter_mut().find(|x| x.as_ref().is_some_and(|x| x.address == address)).ok_or(format!("0x{:x} is not watched", address))?;
+            *slot = None;
+        }
+        self.program()
+    }
+
+    // The armed watchpoints in debug register order
+    pub fn watches(&self) -> Vec<Watch> {
+        self.slots.lock().unwrap().iter().flatten().cloned().collect()
+    }
+
+    // Puts an int3 over the instruction at address, which has to be in executable memory
+    pub fn break_at(&self, address: usize) -> Result<(), Box<dyn std::error::Error>> {
+        if self.breakpoints.lock().unwrap().contains_key(&address) {
+            return Err(format!("0x{:x} already has a breakpoint", address).into());
+        }
+        if !get_memory_regions(self.pid)?.iter().any(|x| x.contains(address) && x.executable) {
+            return Err(format!("0x{:x} is not in executable memory, and a breakpoint only traps when code there runs", address).into());
+        }
+        let breakpoints = self.breakpoints.clone();
+        Ok(self.request(move |traced| set_breakpoint(traced, &breakpoints, address)).map_err(|e| format!("Could not set a breakpoint at 0x{:x}: {}", address, e))?)
+    }
+
+    pub fn unbreak(&self, address: usize) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.breakpoints.lock().unwrap().contains_key(&address) {
+            return Err(format!("0x{:x} has no breakpoint", address).into());
+        }
+        let breakpoints = self.breakpoints.clone();
+        Ok(self.request(move |traced| remove_breakpoint(traced, &breakpoints, address)).map_err(|e| format!("Could not remove the breakpoint at 0x{:x}: {}", address, e))?)
+    }
+
+    // The breakpoints in address order
+    pub fn breakpoints(&self) -> Vec<Breakpoint> {
+        self.breakpoints.lock().unwrap().values().cloned().collect()
+    }
+
+    // Has the session's thread load the slots into every thread's debug registers
+    fn program(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let slots = self.slots.clone();
+        Ok(self.request(move |traced| program_all(traced, &slots)).map_err(|e| format!("Could not set the debug registers: {}", e))?)
+    }
+
+    // Every thread is let go again by the time it returns
+    fn request(&self, job: impl FnOnce(&mut Traced) -> Result<(), Errno> + Send + 'static) -> Result<(), String> {
+        self.session.run(job).map_err(|e| e.to_string())?.map_err(|e| e.to_string())
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        let _ = self.session.run(|traced| traced.remove_handler());
+    }
+}
+
+// Byte offset of debug register n in struct user, as PTRACE_POKEUSER addresses it
+fn debug_register(n: usize) -> AddressType {
+    (std::mem::offset_of!(libc::user, u_debugreg) + n * size_of::<u64>()) as AddressType
+}
+
+// DR7 arming each slot: its local enable bit, 01 (break on writes) or 11 (on reads and writes) in
+// its R/W field, and its length in the LEN field, where 8 bytes is 10 and 4 bytes 11
+fn control(slots: &[Option<Watch>]) -> u64 {
+    slots.iter().enumerate().filter_map(|(n, x)| Some((n, x.as_ref()?))).map(|(n, watch)| {
+        let len = match watch.size {
+            1 => 0b00,
+            2 => 0b01,
+            8 => 0b10,
+            _ => 0b11,
+        };
+        let rw = match watch.kind {
+            WatchKind::Write => 0b01,
+            WatchKind::Access => 0b11,
+        };
+        1 << (2 * n) | rw << (16 + 4 * n) | len << (18 + 4 * n)
+    }).fold(0, |control, x| control | x)
+}
+
+// The session's handler for the watcher's traps, running on the session's thread. Whatever it
+// armed comes out when it is removed, even by a panicking callback, since a thread left with its
+// debug registers set or an int3 in its code after its tracer is gone is killed by the next
+// hit's SIGTRAP
+struct Server {
+    slots: Slots,
+    breakpoints: Breakpoints,
+    backtrace_depth: Arc<AtomicUsize>,
+    history_limit: Arc<AtomicUsize>,
+    unwinder: Unwinder,
+    callback: WatchCallback,
+}
+
+impl Handler for Server {
+    fn trapped(&mut self, thread: Pid) -> Trap {
+        if self.report_hits(thread) {
+            return Trap::Handled(Resume::Continue(None));
+        }
+        match self.hit_breakpoint(thread) {
+            Some(address) => self.step_over(thread, address).map_or(Trap::Gone, Trap::Handled),
+            None => Trap::NotMine,
+        }
+    }
+
+    // It begins without any debug registers set
+    fn started(&mut self, thread: Pid) {
+        let slots = self.slots.lock().unwrap().clone();
+        let _ = program(thread, &slots);
+    }
+
+    // Puts back every byte under a breakpoint, as well as clearing the debug registers
+    fn removed(&mut self, stopped: &[(Pid, Resume)]) {
+        let breakpoints = std::mem::take(&mut *self.breakpoints.lock().unwrap());
+        if let Some(&(thread, _)) = stopped.first() {
+            for breakpoint in breakpoints.values() {
+                let _ = poke(thread, breakpoint.address, &[breakpoint.original]);
+            }
+        }
+        for &(thread, _) in stopped {
+            let _ = ptrace::write_user(thread, debug_register(7), 0);
+            let _ = ptrace::write_user(thread, debug_register(6), 0);
+        }
+    }
+}
+
+impl Server {
+    // DR6 has a bit set for each watchpoint that fired, and has to be cleared by hand. A SIGTRAP
+    // without any is an int3's, either a breakpoint's or the target's own
+    fn report_hits(&mut self, thread: Pid) -> bool {
+        let status = match ptrace::read_user(thread, debug_register(6)) {
+            Ok(status) => status as u64,
+            Err(_) => return false,
+        };
+        if status & 0xf == 0 {
+            return false;
+        }
+        let _ = ptrace::write_user(thread, debug_register(6), 0);
+        let regs = ptrace::getregs(thread).ok();
+        let rip = regs.map(|x| x.rip as usize).unwrap_or(0);
+        let backtrace = regs.map(|x| self.backtrace(thread, x.into())).unwrap_or_default();
+        let (code_start, code) = match peek(thread, rip.saturating_sub(CODE_WINDOW), 2 * CODE_WINDOW) {
+            Ok(code) => (rip.saturating_sub(CODE_WINDOW), code),
+            Err(_) => (rip, peek(thread, rip, CODE_WINDOW).unwrap_or_default()),
+        };
+        let mut hits = Vec::new();
+        {
+            let mut slots = self.slots.lock().unwrap();
+            for (n, slot) in slots.iter_mut().enumerate() {
+                if let Some(watch) = slot.as_mut().filter(|_| status & (1 << n) != 0) {
+                    let value = peek(thread, watch.address, watch.size).unwrap_or_default();
+                    let access = match watch.kind {
+                        WatchKind::Access if value == watch.value => Access::Read,
+                        _ => Access::Write,
+                    };
+                    let count = watch.hits.entry((rip, access)).or_default();
+                    *count += 1;
+                    let limit = self.history_limit.load(Ordering::Relaxed);
+                    if limit > 0 && value != watch.value && value.len() == watch.size && watch.value.len() == watch.size {
+                        let (mut old, mut new) = ([0u8; 8], [0u8; 8]);
+                        old[..watch.value.len()].copy_from_slice(&watch.value);
+                        new[..value.len()].copy_from_slice(&value);
+                        if watch.history.len() >= limit {
+                            watch.history.pop_front();
+                            watch.dropped += 1;
+                        }
+                        watch.history.push_back(ValueChange { time: SystemTime::now(), thread, rip, old, new });
+                    }
+                    hits.push(WatchEvent::Watch(WatchHit { address: watch.address, thread, rip, access, count: *count, value: value.clone(), code_start, code: code.clone(), backtrace: backtrace.clone() }));
+                    watch.value = value;
+                }
+            }
+        }
+        // Outside the lock, so the callback can look at the watches
+        for hit in &hits {
+            (self.callback.0)(hit);
+        }
+        true
+    }
+
+    // An int3 traps with rip just past it, so a breakpoint's is told from the target's own by
+    // whether the byte before rip is one of ours. Reports it, giving the breakpoint's address. rip
+    // is rewound onto the breakpoint before the callback, so that a thread let go by a panicking
+    // callback, once the original byte is back, carries on from the right place
+    fn hit_breakpoint(&mut self, thread: Pid) -> Option<usize> {
+        let regs = ptrace::getregs(thread).ok()?;
+        let address = (regs.rip as usize).checked_sub(1)?;
+        let hits = {
+            let mut breakpoints = self.breakpoints.lock().unwrap();
+            let breakpoint = breakpoints.get_mut(&address)?;
+            breakpoint.hits += 1;
+            breakpoint.hits
+        };
+        ptrace::setregs(thread, libc::user_regs_struct { rip: address as u64, ..regs }).ok()?;
+        let return_address = peek(thread, regs.rsp as usize, size_of::<u64>()).ok().and_then(|x| Some(u64::from_ne_bytes(x.try_into().ok()?))).unwrap_or(0);
+        let registers = vec![("rdi", regs.rdi), ("rsi", regs.rsi), ("rdx", regs.rdx), ("rcx", regs.rcx), ("r8", regs.r8), ("r9", regs.r9), ("rax", regs.rax), ("rsp", regs.rsp), ("rbp", regs.rbp), ("return", return_address)];
+        let backtrace = self.backtrace(thread, FrameRegisters { pc: address, ..regs.into() });
+        (self.callback.0)(&WatchEvent::Break(BreakHit { address, thread, hits, registers, backtrace }));
+        Some(address)
+    }
+
+    // Runs the breakpoint's instruction with the original byte back in place and puts the int3
+    // back after. Other threads are left running meanwhile, so one passing the
+    // same address during the step is not caught. A signal arriving during the step is handed
+    // on after it, and a watchpoint hit by the stepped instruction is reported
+    fn step_over(&mut self, thread: Pid, address: usize) -> Option<Resume> {
+        let original = self.breakpoints.lock().unwrap().get(&address)?.original;
+        let result = poke(thread, address, &[original]);
+        let mut held = None;
+        while result.is_ok() {
+            if ptrace::step(thread, None).is_err() {
+                return None;
+            }
+            match waitpid(thread, Some(WaitPidFlag::__WALL)) {
+                Ok(WaitStatus::Stopped(_, Signal::SIGTRAP)) => break,
+                Ok(WaitStatus::Stopped(_, signal)) => held = Some(signal),
+                // E.g. the stepped instruction starting a thread
+                Ok(WaitStatus::PtraceEvent(..)) => {}
+                _ => return None,
+            }
+        }
+        self.report_hits(thread);
+        if self.breakpoints.lock().unwrap().contains_key(&address) {
+            let _ = poke(thread, address, &[INT3]);
+        }
+        Some(Resume::Continue(held))
+    }
+
+    // Walked while the thread is stopped, through it
+    fn backtrace(&mut self, thread: Pid, registers: FrameRegisters) -> Vec<Location> {
+        let frames = self.unwinder.walk(registers, self.backtrace_depth.load(Ordering::Relaxed), &mut |address| read_word(thread, address));
+        frames.into_iter().map(|x| self.unwinder.describe(x)).collect()
+    }
+}
+
+// New watches also get the value they start from, for telling reads from writes
+fn program_all(traced: &mut Traced, slots: &Slots) -> Result<(), Errno> {
+    let stopped = traced.stop_all();
+    let slots = {
+        let mut slots = slots.lock().unwrap();
+        if let Some(&(thread, _)) = stopped.first() {
+            for watch in slots.iter_mut().flatten().filter(|x| x.value.is_empty()) {
+                watch.value = peek(thread, watch.address, watch.size).unwrap_or_default();
+            }
+        }
+        slots.clone()
+    };
+    let mut result = Ok(());
+    for (thread, resume) in stopped {
+        if let Err(e) = program(thread, &slots) {
+            result = Err(e);
+        }
+        resume.resume(thread);
+    }
+    result
+}
+
+// Code is shared by every thread, so the int3 goes in through whichever is stopped first
+fn set_breakpoint(traced: &mut Traced, breakpoints: &Breakpoints, address: usize) -> Result<(), Errno> {
+    let stopped = traced.stop_all();
+    let result = match stopped.first() {
+        Some(&(thread, _)) => peek(thread, address, 1).and_then(|original| {
+            poke(thread, address, &[INT3])?;
+            breakpoints.lock().unwrap().insert(address, Breakpoint { address, original: original[0], hits: 0 });
+            Ok(())
+        }),
+        None => Err(Errno::ESRCH),
+    };
+    for (thread, resume) in stopped {
+        resume.resume(thread);
+    }
+    result
+}
+
+fn remove_breakpoint(traced: &mut Traced, breakpoints: &Breakpoints, address: usize) -> Result<(), Errno> {
+    let stopped = traced.stop_all();
+    let removed = breakpoints.lock().unwrap().remove(&address);
+    let result = match (stopped.first(), removed) {
+        (Some(&(thread, _)), Some(breakpoint)) => poke(thread, address, &[breakpoint.original]).map(|_| ()),
+        _ => Err(Errno::ESRCH),
+    };
+    for (thread, resume) in stopped {
+        resume.resume(thread);
+    }
+    result
+}
+
+// DR7 is cleared first, since the kernel checks each address against the enable bits already set
+fn program(thread: Pid, slots: &[Option<Watch>]) -> Result<(), Errno> {
+    ptrace::write_user(thread, debug_register(7), 0)?;
+    for (n, watch) in slots.iter().enumerate() {
+        if let Some(watch) = watch {
+            ptrace::write_user(thread, debug_register(n), watch.address as libc::c_long)?;
+        }
+    }
+    ptrace::write_user(thread, debug_register(7), control(slots) as libc::c_long)
+}
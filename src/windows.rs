@@ -0,0 +1,384 @@
+use std::{ffi::c_void, fs::File, path::Path, sync::Arc};
+use windows_sys::Win32::{Foundation::{CloseHandle, ERROR_ACCESS_DENIED, ERROR_GEN_FAILURE, ERROR_INVALID_ADDRESS, ERROR_INVALID_PARAMETER, ERROR_NOACCESS, ERROR_PARTIAL_COPY, GetLastError, HANDLE, HMODULE}, System::{Diagnostics::Debug::{ReadProcessMemory, WriteProcessMemory}, Memory::{MEM_COMMIT, MEM_IMAGE, MEM_MAPPED, MEMORY_BASIC_INFORMATION, PAGE_EXECUTE, PAGE_EXECUTE_READ, PAGE_EXECUTE_READWRITE, PAGE_EXECUTE_WRITECOPY, PAGE_GUARD, PAGE_NOACCESS, PAGE_READONLY, PAGE_READWRITE, PAGE_WRITECOPY, VirtualQueryEx}, ProcessStatus::{EnumProcessModulesEx, GetMappedFileNameW, GetModuleFileNameExW, GetModuleInformation, LIST_MODULES_ALL, MODULEINFO}, Threading::{GetCurrentProcessId, OpenProcess, PROCESS_ACCESS_RIGHTS, PROCESS_NAME_WIN32, PROCESS_QUERY_INFORMATION, PROCESS_VM_OPERATION, PROCESS_VM_READ, PROCESS_VM_WRITE, QueryFullProcessImageNameW}}};
+use crate::{maps::MemoryRegion, offline::OfflineCapture, process::{MemBackend, ProcessMemory}};
+
+// The Windows backend: ReadProcessMemory and WriteProcessMemory through a handle from OpenProcess,
+// with the regions from VirtualQueryEx and the modules from EnumProcessModulesEx. Reading needs
+// PROCESS_VM_READ and PROCESS_QUERY_INFORMATION on the target, writing PROCESS_VM_WRITE and
+// PROCESS_VM_OPERATION too; a process running as another user, or elevated when this one is not,
+// refuses them unless this one has SeDebugPrivilege, i.e. runs as administrator
+
+// Longest path GetModuleFileNameExW and friends are asked for, in UTF-16 units
+const PATH_LIMIT: usize = 32 * 1024;
+
+const READABLE: u32 = PAGE_READONLY | PAGE_READWRITE | PAGE_WRITECOPY | PAGE_EXECUTE_READ | PAGE_EXECUTE_READWRITE | PAGE_EXECUTE_WRITECOPY;
+const WRITABLE: u32 = PAGE_READWRITE | PAGE_WRITECOPY | PAGE_EXECUTE_READWRITE | PAGE_EXECUTE_WRITECOPY;
+const EXECUTABLE: u32 = PAGE_EXECUTE | PAGE_EXECUTE_READ | PAGE_EXECUTE_READWRITE | PAGE_EXECUTE_WRITECOPY;
+
+// A process id, with the same methods as nix's
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pid(i32);
+
+impl Pid {
+    pub const fn from_raw(pid: i32) -> Pid {
+        Pid(pid)
+    }
+
+    pub const fn as_raw(self) -> i32 {
+        self.0
+    }
+
+    pub fn this() -> Pid {
+        Pid(unsafe { GetCurrentProcessId() } as i32)
+    }
+}
+
+impl std::fmt::Display for Pid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// The errnos the rest of the library tells failures apart by, with Win32 errors mapped onto them
+// so that retries and lock failures work the same as on Linux
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum Errno {
+    UnknownErrno = 0,
+    EPERM = 1,
+    ESRCH = 3,
+    EIO = 5,
+    EACCES = 13,
+    EFAULT = 14,
+}
+
+impl Errno {
+    pub fn from_raw(errno: i32) -> Errno {
+        match errno {
+            1 => Errno::EPERM,
+            3 => Errno::ESRCH,
+            5 => Errno::EIO,
+            13 => Errno::EACCES,
+            14 => Errno::EFAULT,
+            _ => Errno::UnknownErrno,
+        }
+    }
+
+    // From the calling thread's last Win32 error. OpenProcess fails with ERROR_INVALID_PARAMETER
+    // for a pid nothing has
+    pub fn last() -> Errno {
+        match unsafe { GetLastError() } {
+            ERROR_ACCESS_DENIED => Errno::EACCES,
+            ERROR_PARTIAL_COPY | ERROR_NOACCESS | ERROR_INVALID_ADDRESS => Errno::EFAULT,
+            ERROR_INVALID_PARAMETER => Errno::ESRCH,
+            ERROR_GEN_FAILURE => Errno::EIO,
+            _ => Errno::UnknownErrno,
+        }
+    }
+
+    pub fn desc(self) -> &'static str {
+        match self {
+            Errno::UnknownErrno => "Unknown error",
+            Errno::EPERM => "Operation not permitted",
+            Errno::ESRCH => "No such process",
+            Errno::EIO => "Input/output error",
+            Errno::EACCES => "Permission denied",
+            Errno::EFAULT => "Bad address",
+        }
+    }
+}
+
+impl std::fmt::Display for Errno {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}: {}", self, self.desc())
+    }
+}
+
+impl std::error::Error for Errno {}
+
+// Positioned reads and writes as std::os::unix::fs::FileExt has them. The file position moves,
+// which nothing reading captures or spilled chunks relies on
+pub trait FileExt {
+    fn read_at(&self, buffer: &mut [u8], offset: u64) -> std::io::Result<usize>;
+
+    fn write_at(&self, buffer: &[u8], offset: u64) -> std::io::Result<usize>;
+
+    fn read_exact_at(&self, mut buffer: &mut [u8], mut offset: u64) -> std::io::Result<()> {
+        while !buffer.is_empty() {
+            match self.read_at(buffer, offset)? {
+                0 => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+                read => {
+                    buffer = &mut buffer[read..];
+                    offset += read as u64;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_all_at(&self, mut buffer: &[u8], mut offset: u64) -> std::io::Result<()> {
+        while !buffer.is_empty() {
+            match self.write_at(buffer, offset)? {
+                0 => return Err(std::io::ErrorKind::WriteZero.into()),
+                written => {
+                    buffer = &buffer[written..];
+                    offset += written as u64;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FileExt for File {
+    fn read_at(&self, buffer: &mut [u8], offset: u64) -> std::io::Result<usize> {
+        std::os::windows::fs::FileExt::seek_read(self, buffer, offset)
+    }
+
+    fn write_at(&self, buffer: &[u8], offset: u64) -> std::io::Result<usize> {
+        std::os::windows::fs::FileExt::seek_write(self, buffer, offset)
+    }
+}
+
+#[derive(Debug)]
+struct Handle {
+    handle: HANDLE,
+    // Opened with the rights writing needs
+    writable: bool,
+}
+
+// A process handle can be used from any thread
+unsafe impl Send for Handle {}
+unsafe impl Sync for Handle {}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        unsafe { CloseHandle(self.handle) };
+    }
+}
+
+fn open(pid: Pid, access: PROCESS_ACCESS_RIGHTS) -> Result<HANDLE, Errno> {
+    match unsafe { OpenProcess(access, 0, pid.as_raw() as u32) } {
+        handle if handle.is_null() => Err(Errno::last()),
+        handle => Ok(handle),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Process {
+    pid: Pid,
+    handle: Option<Arc<Handle>>,
+    offline: Option<Arc<OfflineCapture>>,
+}
+
+impl Process {
+    // Asks for the rights to read and write, and settles for reading if writing is refused, in
+    // which case every write fails
+    pub fn attach(pid: Pid) -> Result<Process, Box<dyn std::error::Error>> {
+        let read = PROCESS_VM_READ | PROCESS_QUERY_INFORMATION;
+        let (handle, writable) = match open(pid, read | PROCESS_VM_WRITE | PROCESS_VM_OPERATION) {
+            Ok(handle) => (handle, true),
+            Err(Errno::EACCES) => (open(pid, read).map_err(|e| refused(pid, e))?, false),
+            Err(e) => return Err(refused(pid, e)),
+        };
+        Ok(Process { pid, handle: Some(Arc::new(Handle { handle, writable })), offline: None })
+    }
+
+    // The calling process itself, which a process may always open
+    pub fn current() -> Result<Process, Box<dyn std::error::Error>> {
+        Process::attach(Pid::this())
+    }
+
+    pub fn with_backend(pid: Pid, backend: MemBackend) -> Result<Process, Box<dyn std::error::Error>> {
+        match backend {
+            MemBackend::Win32 => Process::attach(pid),
+            _ => Err(backend.unavailable()),
+        }
+    }
+
+    pub fn seize(_pid: Pid) -> Result<Process, Box<dyn std::error::Error>> {
+        Err("Seizing a process needs Linux's ptrace".into())
+    }
+
+    // Reads a capture written by dump all, possibly on another machine, instead of a live process
+    pub fn offline(path: &Path) -> Result<Process, Box<dyn std::error::Error>> {
+        let capture = OfflineCapture::open(path)?;
+        Ok(Process { pid: capture.pid(), handle: None, offline: Some(Arc::new(capture)) })
+    }
+
+    pub fn backend(&self) -> MemBackend {
+        match self.offline {
+            Some(_) => MemBackend::Offline,
+            None => MemBackend::Win32,
+        }
+    }
+
+    pub fn is_seized(&self) -> bool {
+        false
+    }
+
+    // Whether writes were refused when the process was opened
+    pub fn is_read_only(&self) -> bool {
+        self.handle.as_ref().is_none_or(|x| !x.writable)
+    }
+
+    fn handle(&self) -> Result<&Handle, Box<dyn std::error::Error>> {
+        self.handle.as_deref().ok_or("Expected a process handle".into())
+    }
+}
+
+fn refused(pid: Pid, errno: Errno) -> Box<dyn std::error::Error> {
+    match errno {
+        Errno::EACCES => format!("Could not open process {}: {}. Reading needs PROCESS_VM_READ and PROCESS_QUERY_INFORMATION access, which another user's or an elevated process only grants to an administrator", pid, errno).into(),
+        e => format!("Could not open process {}: {}", pid, e).into(),
+    }
+}
+
+impl ProcessMemory for Process {
+    fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    // A read that fails partway through returns what it got before the fault
+    fn read_at(&self, address: usize, buffer: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        if let Some(offline) = &self.offline {
+            return offline.read_at(address, buffer);
+        }
+        let mut read = 0;
+        if unsafe { ReadProcessMemory(self.handle()?.handle, address as *const c_void, buffer.as_mut_ptr() as *mut c_void, buffer.len(), &mut read) } == 0 && read == 0 {
+            return Err(Errno::last().into());
+        }
+        Ok(read)
+    }
+
+    fn write_at(&self, address: usize, data: &[u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        if let Some(offline) = &self.offline {
+            return offline.write_at(address, data);
+        }
+        let handle = self.handle()?;
+        if !handle.writable {
+            return Err(format!("Process {} was opened read-only, since it refused PROCESS_VM_WRITE and PROCESS_VM_OPERATION access", self.pid).into());
+        }
+        let mut written = 0;
+        if unsafe { WriteProcessMemory(handle.handle, address as *const c_void, data.as_ptr() as *const c_void, data.len(), &mut written) } == 0 && written == 0 {
+            return Err(Errno::last().into());
+        }
+        Ok(written)
+    }
+
+    fn memory_regions(&self) -> Result<Vec<MemoryRegion>, Box<dyn std::error::Error>> {
+        match &self.offline {
+            Some(offline) => offline.memory_regions(),
+            None => memory_regions(self.handle()?.handle),
+        }
+    }
+
+    fn executable_path(&self) -> Option<String> {
+        if let Some(offline) = &self.offline {
+            return offline.executable_path();
+        }
+        let mut path = vec![0u16; PATH_LIMIT];
+        let mut len = path.len() as u32;
+        match unsafe { QueryFullProcessImageNameW(self.handle().ok()?.handle, PROCESS_NAME_WIN32, path.as_mut_ptr(), &mut len) } {
+            0 => None,
+            _ => Some(String::from_utf16_lossy(&path[..len as usize])),
+        }
+    }
+
+    // Not found out on Windows yet
+    fn thread_stacks(&self) -> Vec<(Pid, usize)> {
+        match &self.offline {
+            Some(offline) => offline.thread_stacks(),
+            None => Vec::new(),
+        }
+    }
+}
+
+// Every committed region, in address order. Regions of a loaded image get its path and their
+// offset into it, as a file mapping would on Linux, so modules are found the same way. Other
+// mapped files get the device path GetMappedFileNameW gives, e.g. \Device\HarddiskVolume3\x.dat
+fn memory_regions(handle: HANDLE) -> Result<Vec<MemoryRegion>, Box<dyn std::error::Error>> {
+    // A process that is still starting can fail to list its modules, which only costs the paths
+    let modules = modules(handle).unwrap_or_default();
+    let mut regions = Vec::new();
+    let mut address = 0usize;
+    loop {
+        let mut info = MEMORY_BASIC_INFORMATION::default();
+        // Fails past the highest user-mode address
+        if unsafe { VirtualQueryEx(handle, address as *const c_void, &mut info, size_of::<MEMORY_BASIC_INFORMATION>()) } == 0 {
+            if address == 0 {
+                return Err(Errno::last().into());
+            }
+            break;
+        }
+        let start = info.BaseAddress as usize;
+        let end = start.saturating_add(info.RegionSize);
+        if info.State == MEM_COMMIT {
+            let accessible = info.Protect & (PAGE_GUARD | PAGE_NOACCESS) == 0;
+            let (pathname, offset) = match info.Type {
+                MEM_IMAGE => match modules.iter().find(|x| start >= x.0 && start < x.1) {
+                    Some(module) => (module.2.clone(), start - module.0),
+                    None => (String::new(), 0),
+                },
+                MEM_MAPPED => (mapped_file(handle, start).unwrap_or_default(), start - info.AllocationBase as usize),
+                _ => (String::new(), 0),
+            };
+            regions.push(MemoryRegion {
+                start,
+                end,
+                readable: accessible && info.Protect & READABLE != 0,
+                writable: accessible && info.Protect & WRITABLE != 0,
+                executable: info.Protect & EXECUTABLE != 0,
+                // Mapped views other than copy-on-write ones write through to the file
+                shared: info.Type == MEM_MAPPED && info.Protect & (PAGE_WRITECOPY | PAGE_EXECUTE_WRITECOPY) == 0,
+                offset,
+                pathname,
+                ..MemoryRegion::default()
+            });
+        }
+        if end <= address {
+            break;
+        }
+        address = end;
+    }
+    Ok(regions)
+}
+
+// A loaded module as (start, end, path)
+type LoadedModule = (usize, usize, String);
+
+fn modules(handle: HANDLE) -> Result<Vec<LoadedModule>, Box<dyn std::error::Error>> {
+    let mut handles: Vec<HMODULE> = vec![std::ptr::null_mut(); 256];
+    loop {
+        let mut needed = 0u32;
+        if unsafe { EnumProcessModulesEx(handle, handles.as_mut_ptr(), size_of_val(handles.as_slice()) as u32, &mut needed, LIST_MODULES_ALL) } == 0 {
+            return Err(Errno::last().into());
+        }
+        let count = needed as usize / size_of::<HMODULE>();
+        if count <= handles.len() {
+            handles.truncate(count);
+            break;
+        }
+        handles.resize(count, std::ptr::null_mut());
+    }
+    let mut modules = Vec::with_capacity(handles.len());
+    let mut path = vec![0u16; PATH_LIMIT];
+    for module in handles {
+        let mut info = MODULEINFO::default();
+        if unsafe { GetModuleInformation(handle, module, &mut info, size_of::<MODULEINFO>() as u32) } == 0 {
+            continue;
+        }
+        let len = unsafe { GetModuleFileNameExW(handle, module, path.as_mut_ptr(), path.len() as u32) } as usize;
+        let base = info.lpBaseOfDll as usize;
+        modules.push((base, base + info.SizeOfImage as usize, String::from_utf16_lossy(&path[..len])));
+    }
+    Ok(modules)
+}
+
+fn mapped_file(handle: HANDLE, address: usize) -> Option<String> {
+    let mut path = vec![0u16; PATH_LIMIT];
+    match unsafe { GetMappedFileNameW(handle, address as *const c_void, path.as_mut_ptr(), path.len() as u32) } as usize {
+        0 => None,
+        len => Some(String::from_utf16_lossy(&path[..len])),
+    }
+}
@@ -0,0 +1,236 @@
+// Just enough x86-64 decoding to tell how long an instruction is and whether it can be moved,
+// for hooking code: not what it does. Anything it does not know is an error rather than a guess,
+// since a wrong length cuts an instruction in two
+
+// One decoded instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub len: usize,
+    // Where the disp32 of a [rip + disp32] operand starts, which has to be adjusted when the
+    // instruction moves
+    pub rip_relative: Option<usize>,
+    // jmp, call, jcc, loop and xbegin with a target relative to where they are
+    pub relative_branch: bool,
+    // ret, iret, jmp, ud2 and int3, after which the code does not carry on to the next instruction
+    pub ends_flow: bool,
+}
+
+// The immediate following an opcode and its ModRM
+#[derive(Clone, Copy)]
+enum Immediate {
+    None,
+    Byte,
+    Word,
+    Dword,
+    // word with a 66 prefix, dword otherwise, and still a dword with REX.W
+    Z,
+    // mov r, imm: qword with REX.W, word with a 66 prefix, dword otherwise
+    V,
+    // enter's word and byte
+    Enter,
+    // mov al/ax, [moffs]: an address, a qword unless there is a 67 prefix
+    Offset,
+}
+
+pub fn decode_instruction(code: &[u8]) -> Result<Instruction, Box<dyn std::error::Error>> {
+    let byte = |at: usize| code.get(at).copied().ok_or("The code ends in the middle of an instruction");
+    let mut at = 0;
+    let (mut operand16, mut address32, mut rex_w) = (false, false, false);
+    // Legacy prefixes, then an optional REX that only counts right before the opcode
+    loop {
+        match byte(at)? {
+            0x66 => operand16 = true,
+            0x67 => address32 = true,
+            0xf0 | 0xf2 | 0xf3 | 0x26 | 0x2e | 0x36 | 0x3e | 0x64 | 0x65 => {}
+            0x40..=0x4f if !matches!(byte(at + 1)?, 0x40..=0x4f | 0x66 | 0x67 | 0xf0 | 0xf2 | 0xf3 | 0x26 | 0x2e | 0x36 | 0x3e | 0x64 | 0x65) => {
+                rex_w = byte(at)? & 8 != 0;
+                at += 1;
+                break;
+            }
+            0x40..=0x4f => {}
+            _ => break,
+        }
+        at += 1;
+    }
+    let opcode = byte(at)?;
+    at += 1;
+    let mut relative_branch = false;
+    let mut ends_flow = false;
+    let (modrm, immediate) = match opcode {
+        0x0f => return decode_escaped(code, at),
+        // VEX and EVEX, which in 64-bit mode are never les, lds or bound
+        0xc4 | 0xc5 | 0x62 => return decode_vex(code, at - 1),
+        0x8f if byte(at)? & 0x38 != 0 => return Err("XOP instructions are not supported".into()),
+        0x06 | 0x07 | 0x0e | 0x16 | 0x17 | 0x1e | 0x1f | 0x27 | 0x2f | 0x37 | 0x3f | 0x60 | 0x61 | 0x82 | 0x9a | 0xce | 0xd4 | 0xd5 | 0xd6 | 0xea => {
+            return Err(format!("0x{:02x} is not an instruction in 64-bit mode", opcode).into());
+        }
+        0x00..=0x3f => match opcode & 7 {
+            0..=3 => (true, Immediate::None),
+            4 => (false, Immediate::Byte),
+            _ => (false, Immediate::Z),
+        },
+        0x50..=0x5f | 0x6c..=0x6f | 0x90..=0x99 | 0x9b..=0x9f | 0xa4..=0xa7 | 0xaa..=0xaf | 0xc9 | 0xd7 | 0xec..=0xef | 0xf1 | 0xf4 | 0xf5 | 0xf8..=0xfd => (false, Immediate::None),
+        0xc3 | 0xcb | 0xcc | 0xcf => {
+            ends_flow = true;
+            (false, Immediate::None)
+        }
+        0x63 | 0x84..=0x8f | 0xd0..=0xd3 | 0xd8..=0xdf | 0xfe => (true, Immediate::None),
+        0xff => {
+            // jmp r/m and jmp far
+            ends_flow = matches!(byte(at)? >> 3 & 7, 4 | 5);
+            (true, Immediate::None)
+        }
+        0x68 | 0xa9 => (false, Immediate::Z),
+        0x69 | 0x81 | 0xc7 => {
+            relative_branch = opcode == 0xc7 && byte(at)? == 0xf8;
+            (true, Immediate::Z)
+        }
+        0x6a | 0xa8 | 0xb0..=0xb7 | 0xcd | 0xe4..=0xe7 => (false, Immediate::Byte),
+        0x6b | 0x80 | 0x83 | 0xc0 | 0xc1 | 0xc6 => (true, Immediate::Byte),
+        0x70..=0x7f | 0xe0..=0xe3 => {
+            relative_branch = true;
+            (false, Immediate::Byte)
+        }
+        0xeb => {
+            (relative_branch, ends_flow) = (true, true);
+            (false, Immediate::Byte)
+        }
+        // call and jmp rel32 are rel32 whatever the operand size
+        0xe8 | 0xe9 => {
+            (relative_branch, ends_flow) = (true, opcode == 0xe9);
+            (false, Immediate::Dword)
+        }
+        0xa0..=0xa3 => (false, Immediate::Offset),
+        0xb8..=0xbf => (false, Immediate::V),
+        0xc2 | 0xca => {
+            ends_flow = true;
+            (false, Immediate::Word)
+        }
+        0xc8 => (false, Immediate::Enter),
+        // test r/m, imm is the only form of these with an immediate
+        0xf6 | 0xf7 => {
+            let test = byte(at)? >> 3 & 7 <= 1;
+            (true, match (test, opcode) { (false, _) => Immediate::None, (true, 0xf6) => Immediate::Byte, _ => Immediate::Z })
+        }
+        _ => return Err(format!("0x{:02x} is not an instruction in 64-bit mode", opcode).into()),
+    };
+    let mut instruction = Instruction { len: at, rip_relative: None, relative_branch, ends_flow };
+    if modrm {
+        operand(code, &mut instruction)?;
+    }
+    instruction.len += match immediate {
+        Immediate::None => 0,
+        Immediate::Byte => 1,
+        Immediate::Word => 2,
+        Immediate::Dword => 4,
+        Immediate::Z if operand16 && !rex_w => 2,
+        Immediate::Z => 4,
+        Immediate::V if rex_w => 8,
+        Immediate::V if operand16 => 2,
+        Immediate::V => 4,
+        Immediate::Enter => 3,
+        Immediate::Offset if address32 => 4,
+        Immediate::Offset => 8,
+    };
+    finish(code, instruction)
+}
+
+// The two and three byte opcodes after 0f, with at just past it
+fn decode_escaped(code: &[u8], mut at: usize) -> Result<Instruction, Box<dyn std::error::Error>> {
+    let byte = |at: usize| code.get(at).copied().ok_or("The code ends in the middle of an instruction");
+    let opcode = byte(at)?;
+    at += 1;
+    let mut instruction = Instruction { len: at, rip_relative: None, relative_branch: false, ends_flow: false };
+    let (modrm, immediate) = match opcode {
+        0x38 => {
+            instruction.len += 1;
+            (true, 0)
+        }
+        0x3a => {
+            instruction.len += 1;
+            (true, 1)
+        }
+        0x04 | 0x0a | 0x0c | 0x0e | 0x0f | 0x24..=0x27 | 0x36 | 0x39 | 0x3b..=0x3f | 0x7a | 0x7b | 0xa6 | 0xa7 | 0xff => {
+            return Err(format!("0x0f 0x{:02x} is not supported", opcode).into());
+        }
+        0x0b => {
+            instruction.ends_flow = true;
+            (false, 0)
+        }
+        0x05..=0x09 | 0x30..=0x35 | 0x37 | 0x77 | 0xa0..=0xa2 | 0xa8..=0xaa | 0xc8..=0xcf => (false, 0),
+        0x80..=0x8f => {
+            instruction.relative_branch = true;
+            (false, 4)
+        }
+        0x70..=0x73 | 0xa4 | 0xac | 0xba | 0xc2 | 0xc4..=0xc6 => (true, 1),
+        _ => (true, 0),
+    };
+    if modrm {
+        operand(code, &mut instruction)?;
+    }
+    instruction.len += immediate;
+    finish(code, instruction)
+}
+
+// VEX (c5 with one byte, c4 with two) and EVEX (62 with three), with at on that first byte. The
+// opcode map comes from the prefix, and only map 3 and a few opcodes of map 1 take an immediate
+fn decode_vex(code: &[u8], at: usize) -> Result<Instruction, Box<dyn std::error::Error>> {
+    let byte = |at: usize| code.get(at).copied().ok_or("The code ends in the middle of an instruction");
+    let (map, opcode_at) = match byte(at)? {
+        0xc5 => (1, at + 2),
+        0xc4 => (byte(at + 1)? & 0x1f, at + 3),
+        _ => (byte(at + 1)? & 7, at + 4),
+    };
+    let opcode = byte(opcode_at)?;
+    let mut instruction = Instruction { len: opcode_at + 1, rip_relative: None, relative_branch: false, ends_flow: false };
+    let immediate = match map {
+        // vzeroupper and vzeroall have no ModRM
+        1 if opcode == 0x77 => return finish(code, instruction),
+        1 if matches!(opcode, 0x70..=0x73 | 0xc2 | 0xc4..=0xc6) => 1,
+        1 | 2 | 5 | 6 => 0,
+        3 => 1,
+        _ => return Err(format!("VEX opcode map {} is not supported", map).into()),
+    };
+    operand(code, &mut instruction)?;
+    instruction.len += immediate;
+    finish(code, instruction)
+}
+
+// ModRM, then SIB and displacement, from instruction.len onwards. 64-bit and 32-bit addressing
+// encode these the same way
+fn operand(code: &[u8], instruction: &mut Instruction) -> Result<(), Box<dyn std::error::Error>> {
+    let modrm = *code.get(instruction.len).ok_or("The code ends in the middle of an instruction")?;
+    instruction.len += 1;
+    let (mode, rm) = (modrm >> 6, modrm & 7);
+    if mode == 3 {
+        return Ok(());
+    }
+    let mut displacement = match mode {
+        1 => 1,
+        2 => 4,
+        _ => 0,
+    };
+    if rm == 4 {
+        let sib = *code.get(instruction.len).ok_or("The code ends in the middle of an instruction")?;
+        instruction.len += 1;
+        if mode == 0 && sib & 7 == 5 {
+            displacement = 4;
+        }
+    }
+    else if mode == 0 && rm == 5 {
+        instruction.rip_relative = Some(instruction.len);
+        displacement = 4;
+    }
+    instruction.len += displacement;
+    Ok(())
+}
+
+fn finish(code: &[u8], instruction: Instruction) -> Result<Instruction, Box<dyn std::error::Error>> {
+    if instruction.len > 15 {
+        return Err("Longer than the 15 bytes an instruction can be".into());
+    }
+    if instruction.len > code.len() {
+        return Err("The code ends in the middle of an instruction".into());
+    }
+    Ok(instruction)
+}
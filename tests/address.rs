@@ -0,0 +1,148 @@
+mod common;
+use std::sync::Arc;
+use memory::{AddressExpression, AddressNames, MockProcess, RegionCache};
+
+const GAME: usize = 0x400000;
+const HEAP: usize = 0x100000;
+
+// A global in the game pointing at a player on the heap, whose +0x10 points at its stats
+fn mock() -> Arc<MockProcess> {
+    let mock = MockProcess::new(100);
+    mock.map("400000-401000 r--p 00000000 08:01 1234 /usr/bin/game").unwrap();
+    mock.map("401000-402000 rw-p 00001000 08:01 1234 /usr/bin/game").unwrap();
+    mock.map("100000-102000 rw-p 00000000 00:00 0 [heap]").unwrap();
+    mock.plant_value(GAME + 0x1a20, HEAP + 0x100).unwrap();
+    mock.plant_value(HEAP + 0x110, HEAP + 0x800).unwrap();
+    Arc::new(mock)
+}
+
+// Two results and one tag, as a session would have
+struct Names;
+
+impl AddressNames for Names {
+    fn result(&self, index: usize) -> Result<usize, String> {
+        [HEAP + 0x100, HEAP + 0x180].get(index).copied().ok_or(format!("No result with index {}", index))
+    }
+
+    fn tag(&self, name: &str) -> Option<usize> {
+        (name == "player_base").then_some(GAME + 0x1a20)
+    }
+}
+
+fn evaluate(text: &str) -> Result<usize, String> {
+    let mock = mock();
+    let mut regions = RegionCache::from_process(mock.clone()).unwrap();
+    text.parse::<AddressExpression>().map_err(|e| e.to_string())?.evaluate(&*mock, &mut regions, &Names).map_err(|e| e.to_string())
+}
+
+#[test]
+fn expressions_parse_as_written() {
+    let cases = [
+        ("0x7f31c2a0", "0x7f31c2a0"),
+        ("7f31c2a0", "0x7f31c2a0"),
+        ("0n100", "0x64"),
+        ("game+0x2a10", "game + 0x2a10"),
+        ("ld-linux-x86-64.so.2+10", "ld-linux-x86-64.so.2 + 0x10"),
+        ("libstdc++.so.6+0x8", "libstdc++.so.6 + 0x8"),
+        ("/usr/lib/libc.so.6 + 0x10", "/usr/lib/libc.so.6 + 0x10"),
+        ("#3 - 8", "#3 - 0x8"),
+        ("#3-8", "#3 - 0x8"),
+        ("@hp + 4 * 2", "@hp + 0x4 * 0x2"),
+        ("(@hp + 4) * 2", "(@hp + 0x4) * 0x2"),
+        ("x - (y - z)", "x - (y - z)"),
+        ("[player_base + 0x10] + 0x4", "[player_base + 0x10] + 0x4"),
+        ("[[player_base]+0x10]", "[[player_base] + 0x10]"),
+        ("game+0x1a20->0x10->-0x8", "game + 0x1a20->0x10->-0x8"),
+        ("-(8)", "-0x8"),
+    ];
+    for (text, shown) in cases {
+        let expression = text.parse::<AddressExpression>().unwrap_or_else(|e| panic!("{}: {}", text, e));
+        assert_eq!(expression.to_string(), shown, "{}", text);
+        assert_eq!(shown.parse::<AddressExpression>().unwrap(), expression, "{}", text);
+    }
+}
+
+// Each error names the token it stopped at and where it is
+#[test]
+fn parse_errors_point_at_the_token() {
+    let cases = [
+        ("#3 + + 8", "Expected a number, a name, #<index>, @<tag>, '(' or '[' at '+' (column 6) in '#3 + + 8'"),
+        ("[player_base + 0x10", "Expected ']' at the end of '[player_base + 0x10'"),
+        ("0x10 0x20", "Expected an operator at '0x20' (column 6) in '0x10 0x20'"),
+        ("#x", "Expected a result's index after # at 'x' (column 2) in '#x'"),
+        ("@ + 4", "Expected a tag after @ at '+' (column 3) in '@ + 4'"),
+        ("0xg0", "Expected a hex number, or a decimal one after 0n, that fits in 64 bits at '0xg0' (column 1) in '0xg0'"),
+        ("", "Expected a number, a name, #<index>, @<tag>, '(' or '[' at the end of ''"),
+        ("ffffffffffffffffff", "ffffffffffffffffff is too large for an address in 'ffffffffffffffffff'"),
+    ];
+    for (text, error) in cases {
+        assert_eq!(text.parse::<AddressExpression>().unwrap_err().to_string(), error, "{}", text);
+    }
+    assert!("[".repeat(100).parse::<AddressExpression>().unwrap_err().to_string().contains("at most 64 levels of brackets"));
+}
+
+#[test]
+fn expressions_evaluate_against_the_process() {
+    let cases = [
+        ("0x1000", GAME - 0x3ff000),
+        ("game", GAME),
+        ("game+0x1a20", GAME + 0x1a20),
+        ("/usr/bin/game + 0x10 * 2", GAME + 0x20),
+        ("#1 - 8", HEAP + 0x178),
+        ("player_base", GAME + 0x1a20),
+        ("@player_base + 0n16", GAME + 0x1a30),
+        ("[player_base]", HEAP + 0x100),
+        ("[[player_base] + 0x10] + 0x4", HEAP + 0x804),
+        ("[#0 + 0x10] - [#0 + 0x10]", 0),
+        ("game+0x1a20->0x10->-0x8", HEAP + 0x7f8),
+        ("player_base->0x10->0x4", HEAP + 0x804),
+    ];
+    for (text, address) in cases {
+        assert_eq!(evaluate(text), Ok(address), "{}", text);
+    }
+}
+
+#[test]
+fn evaluation_errors_say_what_went_wrong() {
+    let cases = [
+        ("#2", "No result with index 2"),
+        ("@hp", "No result is tagged hp; tag <address> hp tags one"),
+        ("nothere.so + 8", "Module nothere.so is not loaded"),
+        ("game-0x10", "Module game-0x10 is not loaded (loaded: game); to subtract from game, put spaces around the '-'"),
+        ("player_base-8", "Module player_base-8 is not loaded; to subtract from player_base, put spaces around the '-'"),
+        ("game+0x2000", "Offset 0x2000 is outside game, which is only 0x2000 bytes"),
+        ("#0 - 0x200000", "#0 - 0x200000 comes to -0xfff00, which is not an address"),
+        ("[#1]", "[#1]: null pointer at 0x100180"),
+        ("[[player_base] + 0x10] + [0x8000]", "[0x8000]: 0x8000 is not in mapped readable memory"),
+        ("#1->0", "hop 0: null pointer at 0x100180"),
+        ("@hp->0", "hop 0 (@hp): No result is tagged hp; tag <address> hp tags one"),
+        ("ffffffffffffffff * 2", "0xffffffffffffffff * 0x2 comes to 0x1fffffffffffffffe, past the last address"),
+    ];
+    for (text, error) in cases {
+        assert_eq!(evaluate(text).unwrap_err(), error, "{}", text);
+    }
+}
+
+// The victim's pointer to its player leads to hp, with gold 8 bytes on
+#[cfg(target_os = "linux")]
+#[test]
+fn the_cli_takes_expressions_wherever_it_takes_an_address() {
+    use std::{io::{BufRead, BufReader, Write}, process::{Command, Stdio}};
+    let path = common::victim_path();
+    let mut victim = Command::new(path).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut lines = BufReader::new(victim.stdout.take().unwrap()).lines();
+    let pid = lines.next().unwrap().unwrap().strip_prefix("pid ").unwrap().to_string();
+    let player = lines.next().unwrap().unwrap();
+    let player = usize::from_str_radix(player.strip_prefix("player 0x").unwrap().split_once(' ').unwrap().0, 16).unwrap();
+    let pointer = lines.next().unwrap().unwrap().strip_prefix("pointer ").unwrap().to_string();
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(&pid).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let commands = format!("guess \"[{0}] + 0n8\"\nwrite [{0}]+8 i64:7\nguess {0}->8\nguess \"#0 + (\"", pointer);
+    writeln!(session.stdin.take().unwrap(), "{}", commands).unwrap();
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    let _ = victim.kill();
+    let _ = victim.wait();
+    // Gold as the bracketed form found it, then as the chain form finds it after the write through it
+    let gold = stdout.lines().filter_map(|x| x.split_once(format!("0x{:x} (", player + 8).as_str())).map(|x| &x.1[x.1.find("): ").unwrap() + 3..][..23]).collect::<Vec<&str>>();
+    assert_eq!(gold, ["e8 03 00 00 00 00 00 00", "07 00 00 00 00 00 00 00"], "{}", stdout);
+    assert!(stdout.contains("error: Expected a number, a name, #<index>, @<tag>, '(' or '[' at the end of '#0 + ('"), "{}", stdout);
+}
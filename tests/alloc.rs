@@ -0,0 +1,81 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+mod common;
+use std::{io::Write, process::{Command, Stdio}, time::Duration};
+use memory::{Endianness, MemBackend, MemoryRegion, Process, ProcessMemory, read_bytes_from_process, read_scalar, remote_alloc, remote_free, write_bytes_to_process};
+use common::Target;
+
+impl Target {
+    // process_vm_writev, so writes go by the page protection
+    fn process(&self) -> Process {
+        Process::with_backend(self.pid(), MemBackend::ProcessVmReadv).unwrap()
+    }
+
+    fn region_at(&self, address: usize) -> Option<MemoryRegion> {
+        self.process().memory_regions().unwrap().into_iter().find(|x| x.contains(address))
+    }
+
+    fn still_running(&mut self) {
+        let process = self.process();
+        let before = read_scalar::<i32>(&process, self.player, Endianness::Native).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(read_scalar::<i32>(&process, self.player, Endianness::Native).unwrap() > before);
+        assert!(self.child.try_wait().unwrap().is_none());
+    }
+}
+
+#[test]
+fn allocates_writes_and_frees() {
+    let mut target = Target::spawn();
+    let address = remote_alloc(target.pid(), 10000, libc::PROT_READ | libc::PROT_WRITE).unwrap();
+    assert_eq!(address % 4096, 0);
+    let region = target.region_at(address).unwrap();
+    assert_eq!((region.permissions().as_str(), region.pathname.as_str()), ("rw-p", ""));
+    assert!(region.contains(address + 12287));
+    let pattern = (0..12288).map(|x| (x * 7) as u8).collect::<Vec<u8>>();
+    assert_eq!(read_bytes_from_process(target.process(), pattern.len(), address).unwrap(), vec![0; pattern.len()]);
+    write_bytes_to_process(target.process(), address, &pattern).unwrap();
+    assert_eq!(read_bytes_from_process(target.process(), pattern.len(), address).unwrap(), pattern);
+    target.still_running();
+    remote_free(target.pid(), address, 10000).unwrap();
+    assert!(target.region_at(address).is_none_or(|x| !x.contains(address + 4096)));
+    target.still_running();
+}
+
+#[test]
+fn maps_with_the_protection_asked_for() {
+    let target = Target::spawn();
+    let address = remote_alloc(target.pid(), 1, libc::PROT_READ | libc::PROT_EXEC).unwrap();
+    assert_eq!(target.region_at(address).unwrap().permissions(), "r-xp");
+    assert!(write_bytes_to_process(target.process(), address, &[1]).is_err());
+    remote_free(target.pid(), address, 1).unwrap();
+}
+
+// munmap would succeed over either of these and hide the mistake
+#[test]
+fn refuses_what_is_not_allocated() {
+    let target = Target::spawn();
+    assert!(remote_alloc(target.pid(), 0, libc::PROT_READ).is_err());
+    let address = remote_alloc(target.pid(), 4096, libc::PROT_READ).unwrap();
+    assert!(remote_free(target.pid(), address + 8, 8).unwrap_err().to_string().contains("not the start of a page"));
+    remote_free(target.pid(), address, 4096).unwrap();
+    assert!(remote_free(target.pid(), address, 4096).unwrap_err().to_string().contains("not mapped"));
+}
+
+// maps marks it, and --free-on-exit unmaps it when the session ends
+#[test]
+fn the_session_tracks_what_it_allocated() {
+    let target = Target::spawn();
+    let refused = Command::new(env!("CARGO_BIN_EXE_memory")).arg(target.pid().to_string()).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    writeln!(refused.stdin.as_ref().unwrap(), "alloc 4096").unwrap();
+    assert!(String::from_utf8(refused.wait_with_output().unwrap().stdout).unwrap().contains("start with --dangerous"));
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).args([&target.pid().to_string(), "--dangerous"]).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut stdin = session.stdin.take().unwrap();
+    writeln!(stdin, "alloc 4096 rwx --free-on-exit\nmaps").unwrap();
+    drop(stdin);
+    let output = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    let address = output.lines().find_map(|x| usize::from_str_radix(x.split(" at 0x").nth(1)?.split(',').next()?, 16).ok()).unwrap();
+    assert!(output.contains(&format!("allocated 4096 bytes rwx at 0x{:x}, freed on exit", address)), "{}", output);
+    assert!(output.lines().any(|x| x.contains("rwxp") && x.ends_with("[allocated by alloc]")), "{}", output);
+    assert!(output.contains(&format!("freed 4096 bytes at 0x{:x}", address)));
+    assert!(target.region_at(address).is_none_or(|x| x.permissions() != "rwxp"));
+}
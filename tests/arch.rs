@@ -0,0 +1,82 @@
+use std::{path::PathBuf, time::UNIX_EPOCH};
+use memory::{Arch, Capture, CaptureHeader, Compression, ELF_IDENT_LEN, MemoryRegion, MockProcess, Process, ProcessMemory};
+
+// The identification bytes and e_machine of an ELF header, the rest left zero
+fn header(class: u8, data: u8, machine: u16) -> [u8; ELF_IDENT_LEN] {
+    let mut header = [0u8; ELF_IDENT_LEN];
+    header[..4].copy_from_slice(b"\x7fELF");
+    (header[4], header[5]) = (class, data);
+    let machine = if data == 2 { machine.to_be_bytes() } else { machine.to_le_bytes() };
+    header[0x12..].copy_from_slice(&machine);
+    header
+}
+
+#[test]
+fn the_header_names_the_architecture() {
+    assert_eq!(Arch::from_elf_header(&header(2, 1, 62)).unwrap(), Arch::X86_64);
+    assert_eq!(Arch::from_elf_header(&header(1, 1, 3)).unwrap(), Arch::X86);
+    assert_eq!(Arch::from_elf_header(&header(2, 1, 183)).unwrap(), Arch::Aarch64);
+    assert_eq!(Arch::from_elf_header(&header(1, 2, 40)).unwrap(), Arch::Arm);
+    assert_eq!(Arch::from_elf_header(&header(2, 1, 243)).unwrap(), Arch::Riscv64);
+    assert_eq!(Arch::X86.pointer_width(), 4);
+    assert_eq!(Arch::Aarch64.to_string(), "aarch64");
+}
+
+// x32 runs x86-64 code with 4-byte pointers, which is not what x86-64 means anywhere else
+#[test]
+fn other_machines_and_classes_keep_their_width() {
+    let x32 = Arch::from_elf_header(&header(1, 1, 62)).unwrap();
+    assert_eq!(x32, Arch::Other { machine: 62, pointer_width: 4 });
+    assert_eq!((x32.pointer_width(), x32.to_string()), (4, "ELF machine 62 (32-bit)".to_string()));
+    assert_eq!(Arch::from_elf_header(&header(2, 1, 8)).unwrap().pointer_width(), 8);
+    assert_eq!(Arch::from_elf_header(b"MZ\x90\x00").unwrap_err().to_string(), "Not an ELF file");
+    assert!(Arch::from_elf_header(&header(3, 1, 62)).is_err() && Arch::from_elf_header(&header(2, 1, 62)[..8]).is_err());
+}
+
+#[test]
+fn a_width_alone_picks_the_counterpart() {
+    assert_eq!(Arch::X86_64.with_pointer_width(4), Arch::X86);
+    assert_eq!(Arch::Arm.with_pointer_width(8), Arch::Aarch64);
+    assert_eq!(Arch::X86.with_pointer_width(4), Arch::X86);
+    assert_eq!(Arch::Riscv64.with_pointer_width(4), Arch::Other { machine: 243, pointer_width: 4 });
+}
+
+#[test]
+fn only_x86_64_has_the_arch_specific_features() {
+    assert!(Arch::X86_64.require_x86_64("disasm").is_ok());
+    assert_eq!(Arch::Aarch64.require_x86_64("disasm").unwrap_err().to_string(), "disasm only supports x86-64 targets, and this one is aarch64");
+}
+
+// /proc/<pid>/exe of this test is read when attaching
+#[cfg(target_os = "linux")]
+#[test]
+fn this_process_is_the_native_architecture() {
+    assert_eq!(Arch::read("/proc/self/exe").unwrap(), Arch::native());
+    let process = Process::current().unwrap();
+    assert_eq!((process.arch(), process.pointer_width()), (Arch::native(), std::mem::size_of::<usize>()));
+}
+
+// With no executable's header to read, as in a mock, the target is taken to be like this process;
+// with one, its header decides
+#[test]
+fn a_loaded_header_is_read_from_memory() {
+    let mock = MockProcess::new(100);
+    assert_eq!(mock.arch(), Arch::native());
+    mock.map("400000-401000 r--p 00000000 08:01 42 /usr/bin/game32").unwrap();
+    mock.plant(0x400000, &header(1, 1, 3)).unwrap();
+    mock.set_executable(Some("/usr/bin/game32"));
+    assert_eq!((mock.arch(), mock.pointer_width()), (Arch::X86, 4));
+}
+
+// So offline mode refuses what it would have refused of the live target
+#[test]
+fn captures_record_the_architecture() {
+    let path = std::env::temp_dir().join(format!("rmh-arch-{}", std::process::id()));
+    let header = CaptureHeader { version: 1, pid: 4242, time: UNIX_EPOCH, arch: Arch::Aarch64, executable: None, stacks: Vec::new(), uncaptured: Vec::new() };
+    let region = MemoryRegion { start: 0x1000, end: 0x2000, readable: true, ..MemoryRegion::default() };
+    Capture::write(&path, &header, Compression::None, vec![region], |_, _| Ok(Vec::new())).unwrap();
+    assert_eq!(Capture::open(&path).unwrap().header.arch, Arch::Aarch64);
+    #[cfg(target_os = "linux")]
+    assert_eq!(Process::offline(&path).unwrap().arch(), Arch::Aarch64);
+    std::fs::remove_file(PathBuf::from(&path)).unwrap();
+}
@@ -0,0 +1,69 @@
+#![cfg(target_os = "linux")]
+mod common;
+use std::{io::{BufRead, BufReader, Write}, process::{Command, Stdio}};
+use memory::{Arithmetic, Endianness, Pid, read_scalar};
+use common::victim_path;
+
+// Must match the layout documented in examples/victim.rs
+const HP: usize = 0x0;
+const GOLD: usize = 0x8;
+
+// Runs the commands against a victim that never ticks, with {hp} and {gold} in them replaced by
+// those fields' addresses, and returns what the session printed and the victim's hp and gold afterwards
+fn on_victim(commands: &str) -> (String, i32, i64) {
+    let mut victim = Command::new(victim_path()).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut lines = BufReader::new(victim.stdout.take().unwrap()).lines();
+    let pid = lines.next().unwrap().unwrap().strip_prefix("pid ").unwrap().to_string();
+    let player = lines.next().unwrap().unwrap();
+    let player = usize::from_str_radix(player.strip_prefix("player 0x").unwrap().split_once(' ').unwrap().0, 16).unwrap();
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(&pid).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    writeln!(session.stdin.take().unwrap(), "{}", commands.replace("{hp}", &format!("0x{:x}", player + HP)).replace("{gold}", &format!("0x{:x}", player + GOLD))).unwrap();
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    let pid = Pid::from_raw(pid.parse().unwrap());
+    let values = (read_scalar::<i32>(pid, player + HP, Endianness::Native).unwrap(), read_scalar::<i64>(pid, player + GOLD, Endianness::Native).unwrap());
+    let _ = victim.kill();
+    let _ = victim.wait();
+    (stdout, values.0, values.1)
+}
+
+#[test]
+fn integers_saturate_unless_told_to_wrap() {
+    assert_eq!(Arithmetic::Add.apply(250u8, 10, false), 255);
+    assert_eq!(Arithmetic::Add.apply(250u8, 10, true), 4);
+    assert_eq!(Arithmetic::Sub.apply(3u32, 5, false), 0);
+    assert_eq!(Arithmetic::Mul.apply(i16::MIN, 2, true), 0);
+    assert_eq!(Arithmetic::Mul.apply(-100i32, i32::MAX, false), i32::MIN);
+}
+
+// An f32 is added up as an f32, and goes to infinity rather than saturating
+#[test]
+fn floats_keep_their_width() {
+    assert_eq!(Arithmetic::Add.apply(0.1f32, 0.2, false), 0.1f32 + 0.2f32);
+    assert_eq!(Arithmetic::Mul.apply(f32::MAX, 2.0, false), f32::INFINITY);
+    assert_eq!(Arithmetic::Sub.apply(1.5f64, 0.25, true), 1.25);
+    assert!("/=".parse::<Arithmetic>().unwrap_err().contains("expected +=, -= or *="));
+}
+
+// gold is an i64 starting at 1000; a scan for it gives the session that type
+#[test]
+fn adds_to_the_value_in_place_and_says_what_it_was() {
+    let (stdout, _, gold) = on_victim("scan i64 1000\nwrite {gold} += 1000\nwrite {gold} -= 1");
+    assert_eq!(gold, 1999, "{}", stdout);
+    assert!(stdout.contains(" i64 += 1000: 1000 -> 2000") && stdout.contains(" i64 -= 1: 2000 -> 1999"), "{}", stdout);
+}
+
+#[test]
+fn one_undo_puts_back_what_was_read() {
+    let (stdout, hp, _) = on_victim("scan i32 100\nwrite {hp} *= 3\nundo");
+    assert_eq!(hp, 100, "{}", stdout);
+}
+
+// hp is an i32 starting at 100
+#[test]
+fn overflow_is_said_and_wraps_only_when_asked() {
+    let (stdout, hp, _) = on_victim("scan i32 100\nwrite {hp} *= 2147483647\nwrite {hp} += 1 --wrapping\nwrite {hp} += 1 --sideways");
+    assert_eq!(hp, i32::MIN);
+    assert!(stdout.contains(" i32 *= 2147483647: 100 -> 2147483647 (saturated, --wrapping wraps around instead)"), "{}", stdout);
+    assert!(stdout.contains(" i32 += 1: 2147483647 -> -2147483648\n"), "{}", stdout);
+    assert!(stdout.contains("error: Unexpected '--sideways'"), "{}", stdout);
+}
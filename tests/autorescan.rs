@@ -0,0 +1,108 @@
+#![cfg(target_os = "linux")]
+mod common;
+use std::{io::{BufRead, BufReader, Lines, Write}, process::{Child, ChildStdin, ChildStdout, Command, Stdio}};
+use common::victim_path;
+
+// A session kept open while autorescan runs, as closing its input would end it
+struct Session {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Lines<BufReader<ChildStdout>>,
+}
+
+impl Session {
+    fn spawn(args: &[&str]) -> Session {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_memory")).args(args).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let lines = BufReader::new(child.stdout.take().unwrap()).lines();
+        Session { child, stdin, lines }
+    }
+
+    fn send(&mut self, line: &str) {
+        writeln!(self.stdin, "{}", line).unwrap();
+    }
+
+    // Every line up to and including the first containing the text
+    fn until(&mut self, text: &str) -> Vec<String> {
+        let mut read = Vec::new();
+        for line in self.lines.by_ref() {
+            let line = line.unwrap();
+            read.push(line.trim_start_matches("> ").to_string());
+            if read.last().unwrap().contains(text) {
+                return read;
+            }
+        }
+        panic!("no line containing {:?} in {:?}", text, read);
+    }
+
+    fn finish(mut self) {
+        self.send("exit");
+        assert!(self.child.wait().unwrap().success());
+    }
+}
+
+// A snapshot never changes, so the second pass leaves what the first did
+#[test]
+fn stops_once_the_count_stops_changing() {
+    let mut session = Session::spawn(&["--offline", "tests/fixtures/scan_before.cap"]);
+    session.send("scan i32 31337\nautorescan 31337 --every 50ms");
+    let read = session.until("autorescan stopped");
+    let passes = read.iter().filter(|x| x.starts_with("[autorescan ")).cloned().collect::<Vec<String>>();
+    assert_eq!(passes, ["[autorescan 1] rescan 31337", "[autorescan 2] rescan 31337"], "{:?}", read);
+    assert_eq!(read.last().unwrap(), "autorescan stopped at pass 2, with 5 left twice in a row");
+    session.finish();
+}
+
+#[test]
+fn an_empty_line_stops_it() {
+    let mut session = Session::spawn(&["--offline", "tests/fixtures/scan_before.cap"]);
+    session.send("scan i32 31337\nautorescan 31337 --every 50ms --below 0");
+    session.until("[autorescan 3]");
+    session.send("");
+    assert!(session.until("autorescan stopped").last().unwrap().ends_with(", as asked"));
+    session.send("autorescan stop");
+    session.until("error: autorescan is not running");
+    session.finish();
+}
+
+#[test]
+fn needs_something_to_rescan() {
+    let mut session = Session::spawn(&["--offline", "tests/fixtures/scan_before.cap"]);
+    session.send("autorescan increased");
+    session.until("error: There is nothing to rescan");
+    session.send("scan i32 31337\nautorescan --every 1s");
+    session.until("error: Usage: autorescan <rescan arguments>");
+    session.send("autorescan 31337 --every 0s");
+    session.until("error: Expected an interval longer than 0");
+    session.finish();
+}
+
+// A rescan that fails once fails every time, so the first failure ends it
+#[test]
+fn a_failing_rescan_stops_it() {
+    let mut session = Session::spawn(&["--offline", "tests/fixtures/scan_before.cap"]);
+    session.send("scan i32 31337\nautorescan decreased --every 50ms");
+    let read = session.until("autorescan stopped");
+    assert!(read.iter().any(|x| x.starts_with("error: rescan decreased compares with the values scan unknown stored")), "{:?}", read);
+    assert_eq!(read.last().unwrap(), "autorescan stopped at pass 1, as the rescan failed");
+    session.finish();
+}
+
+// The hp drops on every tick, and sooner or later is all that keeps dropping
+#[test]
+fn narrows_an_unknown_scan_down_to_the_hp() {
+    let mut victim = Command::new(victim_path()).arg("100").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut lines = BufReader::new(victim.stdout.take().unwrap()).lines();
+    let pid = lines.next().unwrap().unwrap().strip_prefix("pid ").unwrap().to_string();
+    let player = lines.next().unwrap().unwrap();
+    let player = usize::from_str_radix(player.strip_prefix("player 0x").unwrap().split_once(' ').unwrap().0, 16).unwrap();
+    let mut session = Session::spawn(&[&pid]);
+    session.send("scan unknown i32\nautorescan decreased --every 250ms");
+    session.until("autorescan stopped");
+    session.send("list");
+    let listed = session.until(&format!("0x{:x} ", player));
+    assert!(!listed.iter().any(|x| x.starts_with("error:")), "{:?}", listed);
+    session.finish();
+    let _ = victim.kill();
+    let _ = victim.wait();
+}
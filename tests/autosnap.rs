@@ -0,0 +1,119 @@
+#![cfg(target_os = "linux")]
+mod common;
+use std::{io::Write, path::PathBuf, process::{Command, Stdio}, time::{Duration, Instant}};
+use memory::{AutoSnapshotOptions, AutoSnapshotter, Endianness, Process, RegionFilter, ScanOptions, is_stopped, read_scalar};
+use common::Target;
+
+impl Target {
+    fn process(&self) -> Process {
+        Process::attach(self.pid()).unwrap()
+    }
+
+    fn hp(&self) -> i32 {
+        read_scalar::<i32>(&self.process(), self.player, Endianness::Native).unwrap()
+    }
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("rmh-autosnap-test-{}-{}", std::process::id(), name))
+}
+
+fn options(name: &str, interval: Duration, keep: usize) -> AutoSnapshotOptions {
+    let scan = ScanOptions { filter: Some("heap".parse::<RegionFilter>().unwrap()), ..ScanOptions::default() };
+    AutoSnapshotOptions { interval, keep, directory: temp_dir(name), budget: None, stop: false, scan }
+}
+
+fn wait_until(autosnap: &AutoSnapshotter, taken: usize) {
+    let started = Instant::now();
+    while autosnap.taken() < taken {
+        assert!(started.elapsed() < Duration::from_secs(10), "only {} taken: {:?}", autosnap.taken(), autosnap.error());
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+fn files(name: &str) -> usize {
+    std::fs::read_dir(temp_dir(name)).unwrap().count()
+}
+
+#[test]
+fn keeps_the_last_few() {
+    let target = Target::spawn();
+    let mut autosnap = AutoSnapshotter::start(target.process(), options("ring", Duration::from_millis(20), 3)).unwrap();
+    wait_until(&autosnap, 6);
+    autosnap.stop();
+    assert!(!autosnap.running());
+    let snapshots = autosnap.snapshots();
+    assert_eq!((snapshots.len(), files("ring")), (3, 3));
+    assert!(snapshots.windows(2).all(|x| x[0].time <= x[1].time));
+    // The player's hp went up between the oldest and the latest
+    let hp = |x: usize| read_scalar::<i32>(&Process::offline(&snapshots[x].path).unwrap(), target.player, Endianness::Native).unwrap();
+    assert!(hp(0) < hp(2), "{} {}", hp(0), hp(2));
+    std::fs::remove_dir_all(temp_dir("ring")).unwrap();
+}
+
+// The heap the snapshots are of can grow between them, so the budget is from the largest of a few
+// and the ring is only expected to have been cut to what fits it
+#[test]
+fn stays_within_the_budget() {
+    let target = Target::spawn();
+    let mut first = AutoSnapshotter::start(target.process(), options("sizes", Duration::from_millis(10), 3)).unwrap();
+    wait_until(&first, 3);
+    first.stop();
+    let size = first.snapshots().iter().map(|x| x.size).max().unwrap();
+    let budget = size * 5 / 2;
+    let mut autosnap = AutoSnapshotter::start(target.process(), AutoSnapshotOptions { budget: Some(budget), ..options("budget", Duration::from_millis(10), 10) }).unwrap();
+    wait_until(&autosnap, 6);
+    autosnap.stop();
+    let snapshots = autosnap.snapshots();
+    assert!(!snapshots.is_empty() && snapshots.len() < 6, "{:?}", snapshots);
+    assert!(snapshots.iter().map(|x| x.size).sum::<u64>() <= budget, "{} {:?}", budget, snapshots);
+    assert_eq!(files("budget"), snapshots.len());
+    // One that is more than the whole budget is not kept at all
+    let mut tiny = AutoSnapshotter::start(target.process(), AutoSnapshotOptions { budget: Some(1), ..options("tiny", Duration::from_millis(10), 10) }).unwrap();
+    let started = Instant::now();
+    while !tiny.error().is_some_and(|x| x.contains("more than the whole budget")) {
+        assert!(started.elapsed() < Duration::from_secs(10), "{:?}", tiny.error());
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    tiny.stop();
+    assert_eq!((tiny.snapshots().len(), files("tiny")), (0, 0));
+    for name in ["sizes", "budget", "tiny"] {
+        std::fs::remove_dir_all(temp_dir(name)).unwrap();
+    }
+}
+
+// A snapshot of everything takes longer than a millisecond, so cycles come round while one is
+// being taken
+#[test]
+fn skips_cycles_instead_of_queueing_them() {
+    let target = Target::spawn();
+    let mut autosnap = AutoSnapshotter::start(target.process(), AutoSnapshotOptions { scan: ScanOptions::default(), ..options("skip", Duration::from_millis(1), 2) }).unwrap();
+    wait_until(&autosnap, 3);
+    autosnap.stop();
+    assert!(autosnap.skipped() > 0);
+    std::fs::remove_dir_all(temp_dir("skip")).unwrap();
+}
+
+#[test]
+fn the_session_diffs_snapshots_taken_with_the_target_stopped() {
+    let target = Target::spawn();
+    let directory = temp_dir("session");
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(target.pid().to_string()).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    let mut stdin = session.stdin.take().unwrap();
+    writeln!(stdin, "set filter heap\nautosnap start 50ms --keep 2 --stop --dir {}", directory.display()).unwrap();
+    std::thread::sleep(Duration::from_millis(400));
+    writeln!(stdin, "autosnap stop\nautosnap list\nautosnap diff -2 -1 i32 increased --load\nlist\nautosnap diff -3 -1").unwrap();
+    drop(stdin);
+    let output = session.wait_with_output().unwrap();
+    let output = format!("{}{}", String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap());
+    assert!(output.contains("keeping the last 2, stopping the target for each"), "{}", output);
+    assert!(output.contains(" -2 ") && output.contains(" -1 ") && output.contains("2 of the last 2 kept"), "{}", output);
+    assert!(output.contains(&format!("0x{:x} (heap+", target.player)), "{}", output);
+    assert!(output.contains("There are only 2 snapshots"), "{}", output);
+    // Continued after each snapshot, and left running
+    assert!(!is_stopped(target.pid()));
+    let before = target.hp();
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(target.hp() > before);
+    std::fs::remove_dir_all(directory).unwrap();
+}
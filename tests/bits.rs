@@ -0,0 +1,116 @@
+use std::sync::{Mutex, atomic::{AtomicUsize, Ordering}};
+use memory::{MemoryRegion, ProcessMemory, ScanOptions, find_bit, read_bits, reduce_found_bits, write_bits};
+use nix::unistd::Pid;
+
+const BASE: usize = 0x10000;
+
+// A single read-write region held in a buffer, counting the writes made to it
+struct Buffer {
+    bytes: Mutex<Vec<u8>>,
+    writes: AtomicUsize,
+}
+
+impl Buffer {
+    fn new(bytes: &[u8]) -> Buffer {
+        Buffer { bytes: Mutex::new(bytes.to_vec()), writes: AtomicUsize::new(0) }
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        self.bytes.lock().unwrap().clone()
+    }
+}
+
+impl ProcessMemory for Buffer {
+    fn pid(&self) -> Pid {
+        Pid::from_raw(0)
+    }
+
+    fn read_at(&self, address: usize, buffer: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        let bytes = self.bytes.lock().unwrap();
+        let offset = address.checked_sub(BASE).filter(|x| *x < bytes.len()).ok_or("unmapped")?;
+        let len = buffer.len().min(bytes.len() - offset);
+        buffer[..len].copy_from_slice(&bytes[offset..offset + len]);
+        Ok(len)
+    }
+
+    fn write_at(&self, address: usize, data: &[u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        self.writes.fetch_add(1, Ordering::SeqCst);
+        let mut bytes = self.bytes.lock().unwrap();
+        let offset = address.checked_sub(BASE).filter(|x| *x < bytes.len()).ok_or("unmapped")?;
+        let len = data.len().min(bytes.len() - offset);
+        bytes[offset..offset + len].copy_from_slice(&data[..len]);
+        Ok(len)
+    }
+
+    fn memory_regions(&self) -> Result<Vec<MemoryRegion>, Box<dyn std::error::Error>> {
+        let len = self.bytes.lock().unwrap().len();
+        Ok(vec![format!("{:x}-{:x} rw-p 00000000 00:00 0", BASE, BASE + len).parse()?])
+    }
+
+    fn executable_path(&self) -> Option<String> {
+        None
+    }
+
+    fn thread_stacks(&self) -> Vec<(Pid, usize)> {
+        Vec::new()
+    }
+}
+
+#[test]
+fn numbers_bits_from_the_lowest_of_the_first_byte() {
+    let buffer = Buffer::new(&[0b0010_0001, 0b1000_0000, 0xff]);
+    assert_eq!(read_bits(&buffer, BASE, 0, 1).unwrap(), 1);
+    assert_eq!(read_bits(&buffer, BASE, 5, 1).unwrap(), 1);
+    assert_eq!(read_bits(&buffer, BASE, 1, 4).unwrap(), 0);
+    assert_eq!(read_bits(&buffer, BASE, 15, 1).unwrap(), 1);
+    // A field straddling two bytes takes its low bits from the first
+    assert_eq!(read_bits(&buffer, BASE, 5, 11).unwrap(), 0b100_0000_0001);
+    assert_eq!(read_bits(&buffer, BASE + 1, 7, 9).unwrap(), 0x1ff);
+}
+
+#[test]
+fn reads_and_writes_whole_words_at_any_offset() {
+    let buffer = Buffer::new(&[0; 10]);
+    write_bits(&buffer, BASE, 3, 64, u64::MAX - 1).unwrap();
+    assert_eq!(read_bits(&buffer, BASE, 3, 64).unwrap(), u64::MAX - 1);
+    assert_eq!(read_bits(&buffer, BASE, 0, 3).unwrap(), 0);
+    assert_eq!(buffer.bytes()[9], 0);
+}
+
+#[test]
+fn writes_only_the_field_in_a_single_write() {
+    let buffer = Buffer::new(&[0b1010_1010, 0b1111_0000]);
+    let (old, new) = write_bits(&buffer, BASE, 6, 4, 0b0110).unwrap();
+    assert_eq!(buffer.writes.load(Ordering::SeqCst), 1);
+    assert_eq!(old, [0b1010_1010, 0b1111_0000]);
+    assert_eq!(new, [0b1010_1010, 0b1111_0001]);
+    assert_eq!(buffer.bytes(), new);
+
+    write_bits(&buffer, BASE, 9, 1, 1).unwrap();
+    assert_eq!(buffer.bytes(), [0b1010_1010, 0b1111_0011]);
+}
+
+#[test]
+fn rejects_bad_fields_without_writing() {
+    let buffer = Buffer::new(&[0; 2]);
+    assert!(write_bits(&buffer, BASE, 0, 3, 8).is_err());
+    assert!(read_bits(&buffer, BASE, 0, 0).is_err());
+    assert!(read_bits(&buffer, BASE, 0, 65).is_err());
+    // The field would run off the end of the mapping
+    assert!(write_bits(&buffer, BASE, 12, 8, 1).is_err());
+    assert_eq!(buffer.writes.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn scans_for_set_and_clear_bits() {
+    let buffer = Buffer::new(&[0b0000_0100, 0, 0b1111_1011, 0b0000_0100]);
+    let options = ScanOptions::default();
+    let (mut found, mut stats) = find_bit(&buffer, 2, true, &options).unwrap();
+    assert_eq!(found, [BASE, BASE + 3]);
+    assert_eq!(find_bit(&buffer, 2, false, &options).unwrap().0, [BASE + 1, BASE + 2]);
+    assert!(find_bit(&buffer, 8, true, &options).is_err());
+
+    write_bits(&buffer, BASE, 2, 1, 0).unwrap();
+    reduce_found_bits(&buffer, &mut found, 2, true, &options, &mut stats).unwrap();
+    assert_eq!(found, [BASE + 3]);
+}
@@ -0,0 +1,96 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+mod common;
+use std::{sync::{Arc, Mutex, mpsc::{Receiver, channel}}, time::Duration};
+use memory::{Endianness, MemBackend, Process, WatchCallback, WatchEvent, WatchKind, Watcher, read_bytes_from_process, read_scalar};
+use common::Target;
+
+impl Target {
+    fn process(&self) -> Process {
+        Process::with_backend(self.pid(), MemBackend::ProcessVmReadv).unwrap()
+    }
+
+    fn hp(&self) -> i32 {
+        read_scalar::<i32>(&self.process(), self.player, Endianness::Native).unwrap()
+    }
+}
+
+// A watcher passing every event it reports back over a channel
+fn start(target: &Target) -> (Watcher, Receiver<WatchEvent>) {
+    let (send, events) = channel();
+    let send = Mutex::new(send);
+    let watcher = Watcher::start(target.pid(), WatchCallback(Arc::new(move |event| { let _ = send.lock().unwrap().send(event.clone()); }))).unwrap();
+    (watcher, events)
+}
+
+// tick's one argument is the pointer to hp, so rdi holds it on the way in
+#[test]
+fn reports_each_call_and_steps_over_it() {
+    let target = Target::spawn();
+    let (watcher, events) = start(&target);
+    watcher.break_at(target.tick.unwrap()).unwrap();
+    let before = target.hp();
+    for expected in 1..=3 {
+        let WatchEvent::Break(hit) = events.recv_timeout(Duration::from_secs(5)).unwrap() else { panic!("expected a breakpoint hit") };
+        assert_eq!((hit.address, hit.hits), (target.tick.unwrap(), expected));
+        assert_ne!(hit.thread, target.pid());
+        assert_eq!(hit.registers.iter().find(|x| x.0 == "rdi").unwrap().1 as usize, target.player);
+    }
+    assert!(target.hp() >= before + 2);
+    assert!(watcher.breakpoints()[0].hits >= 3);
+}
+
+// Left behind, the int3 would kill the target the next time tick is called
+#[test]
+fn puts_the_code_back_when_dropped() {
+    let mut target = Target::spawn();
+    let code = read_bytes_from_process(target.process(), 16, target.tick.unwrap()).unwrap();
+    let (watcher, events) = start(&target);
+    watcher.break_at(target.tick.unwrap()).unwrap();
+    // Read before a hit comes in, since the original byte is back while it is stepped over
+    let armed = read_bytes_from_process(target.process(), 16, target.tick.unwrap()).unwrap();
+    assert_eq!((armed[0], &armed[1..]), (0xcc, &code[1..]));
+    events.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(watcher.breakpoints()[0].original, code[0]);
+    drop(watcher);
+    assert_eq!(read_bytes_from_process(target.process(), 16, target.tick.unwrap()).unwrap(), code);
+    let before = target.hp();
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(target.hp() > before);
+    assert!(target.child.try_wait().unwrap().is_none());
+}
+
+#[test]
+fn refuses_what_is_not_code() {
+    let target = Target::spawn();
+    let (watcher, _events) = start(&target);
+    assert!(watcher.break_at(target.player).unwrap_err().to_string().contains("not in executable memory"));
+    watcher.break_at(target.tick.unwrap()).unwrap();
+    assert!(watcher.break_at(target.tick.unwrap()).unwrap_err().to_string().contains("already has a breakpoint"));
+    assert!(watcher.unbreak(target.tick.unwrap() + 1).unwrap_err().to_string().contains("has no breakpoint"));
+    watcher.unbreak(target.tick.unwrap()).unwrap();
+    assert!(watcher.breakpoints().is_empty());
+    assert_ne!(read_bytes_from_process(target.process(), 1, target.tick.unwrap()).unwrap()[0], 0xcc);
+}
+
+// Both go through the same tracer, since a thread can only have one
+#[test]
+fn shares_the_tracer_with_watchpoints() {
+    let target = Target::spawn();
+    let (watcher, events) = start(&target);
+    watcher.break_at(target.tick.unwrap()).unwrap();
+    watcher.watch(target.player, 4, WatchKind::Write).unwrap();
+    let (mut breaks, mut writes) = (0, 0);
+    while breaks < 3 || writes < 3 {
+        match events.recv_timeout(Duration::from_secs(5)).unwrap() {
+            WatchEvent::Break(hit) => {
+                assert_eq!(hit.address, target.tick.unwrap());
+                breaks += 1;
+            }
+            WatchEvent::Watch(hit) => {
+                assert_eq!(hit.address, target.player);
+                writes += 1;
+            }
+        }
+    }
+    assert!(watcher.watches()[0].hits.keys().all(|&(rip, _)| rip > target.tick.unwrap()));
+}
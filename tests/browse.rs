@@ -0,0 +1,98 @@
+mod common;
+use memory::{BrowseColumn, BrowseLayout, BrowseMove, Browser, DisplayFormat, Endianness, Key, MockProcess, ValueType, decode_keys};
+
+const HEAP: usize = 0x10000;
+
+fn mock() -> MockProcess {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", HEAP, HEAP + 0x1000)).unwrap();
+    mock.plant_value(HEAP + 0x100, 45i32).unwrap();
+    mock.plant_value(HEAP + 0x104, 1.5f32).unwrap();
+    mock
+}
+
+// Values take the row's bytes in turn, so the stride is what they add up to
+#[test]
+fn layouts_parse_into_consecutive_cells() {
+    let layout = "offset 2xi32, f32 ascii".parse::<BrowseLayout>().unwrap();
+    assert_eq!(layout.columns[1], BrowseColumn::Values { value_type: ValueType::I32, count: 2 });
+    assert_eq!(layout.stride(), 12);
+    assert_eq!(layout.cells(), [(0, ValueType::I32), (4, ValueType::I32), (8, ValueType::F32)]);
+    assert_eq!(layout.to_string(), "offset 2xi32 1xf32 ascii");
+    assert!("offset ascii".parse::<BrowseLayout>().unwrap_err().contains("shows no values"));
+    assert!("4xstr".parse::<BrowseLayout>().unwrap_err().contains("numeric type"));
+    assert!("hex".parse::<BrowseLayout>().unwrap_err().starts_with("Unknown column 'hex'"));
+}
+
+#[test]
+fn the_cursor_moves_and_the_window_scrolls_past_its_edges() {
+    let mut browser = Browser::new(HEAP + 0x100, "i32 f32".parse().unwrap(), 8);
+    assert_eq!((browser.top, browser.cursor_cell()), (-2, Some((HEAP + 0x100, ValueType::I32))));
+    browser.move_cursor(BrowseMove::Right);
+    browser.move_cursor(BrowseMove::Right);
+    assert_eq!(browser.cursor_cell(), Some((HEAP + 0x104, ValueType::F32)));
+    for _ in 0..7 {
+        browser.move_cursor(BrowseMove::Down);
+    }
+    assert_eq!((browser.top, browser.cursor.0, browser.row_address(0)), (0, 7, Some(HEAP + 0x100)));
+    browser.move_cursor(BrowseMove::PageUp);
+    assert_eq!((browser.top, browser.cursor_cell()), (-8, Some((HEAP + 0x100 - 4, ValueType::F32))));
+    // Nothing comes before address 0
+    let mut browser = Browser::new(0x8, "u32".parse().unwrap(), 8);
+    assert_eq!(browser.top, -2);
+    browser.move_cursor(BrowseMove::PageUp);
+    assert_eq!(browser.row_address(0), Some(0));
+}
+
+// A value is changed when its bytes differ from the last read of the same address
+#[test]
+fn refreshes_mark_what_changed_and_what_could_not_be_read() {
+    let mock = mock();
+    let mut browser = Browser::new(HEAP + 0x100, "i32 f32".parse().unwrap(), 4);
+    let rows = browser.refresh(&mock, Endianness::Native, DisplayFormat::default());
+    assert_eq!(rows[1].offset, 0);
+    assert_eq!((rows[1].cells[0].text.as_deref(), rows[1].cells[1].text.as_deref()), (Some("45"), Some("1.5")));
+    assert!(rows.iter().all(|x| x.cells.iter().all(|x| !x.changed)));
+    mock.plant_value(HEAP + 0x104, 2.5f32).unwrap();
+    let rows = browser.refresh(&mock, Endianness::Native, DisplayFormat::default());
+    assert_eq!(rows[1].cells.iter().map(|x| x.changed).collect::<Vec<bool>>(), [false, true]);
+    assert!(!browser.refresh(&mock, Endianness::Native, DisplayFormat::default())[1].cells[1].changed);
+    let mut browser = Browser::new(HEAP, "i32".parse().unwrap(), 4);
+    let rows = browser.refresh(&mock, Endianness::Native, DisplayFormat::default());
+    assert_eq!(rows[0].offset, -4);
+    assert!(rows[0].bytes.is_none() && rows[0].cells[0].text.is_none() && rows[1].bytes.is_some());
+}
+
+#[test]
+fn terminal_keys_are_decoded() {
+    assert_eq!(decode_keys(b"\x1b[A\x1b[B\x1b[C\x1b[D"), [BrowseMove::Up, BrowseMove::Down, BrowseMove::Right, BrowseMove::Left].map(Key::Move));
+    assert_eq!(decode_keys(b"\x1b[5~\x1b[6~"), [Key::Move(BrowseMove::PageUp), Key::Move(BrowseMove::PageDown)]);
+    assert_eq!(decode_keys(b"w5\x7f6\r"), [Key::Char('w'), Key::Char('5'), Key::Backspace, Key::Char('6'), Key::Enter]);
+    // F1, which browse has no use for, goes whole
+    assert_eq!(decode_keys(b"\x1b[11~q\x1b"), [Key::Char('q'), Key::Escape]);
+}
+
+// Without a terminal, browse takes the keys as lines and prints the window after each
+#[cfg(target_os = "linux")]
+#[test]
+fn the_cli_browses_from_lines() {
+    use std::{io::{BufRead, BufReader, Write}, process::{Command, Stdio}};
+    let path = common::victim_path();
+    let mut victim = Command::new(path).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut lines = BufReader::new(victim.stdout.take().unwrap()).lines();
+    let pid = lines.next().unwrap().unwrap().strip_prefix("pid ").unwrap().to_string();
+    let player = lines.next().unwrap().unwrap();
+    let player = usize::from_str_radix(player.strip_prefix("player 0x").unwrap().split_once(' ').unwrap().0, 16).unwrap();
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(&pid).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    // hp is the i32 at the player, gold the i64 8 bytes on
+    let commands = format!("browse 0x{:x} --rows 1 --layout \"offset 2xi32 i64\"\nw 55\nright\nright\nt gold\ndown\nlock\nfly\nq\nlist\nlocks", player);
+    writeln!(session.stdin.take().unwrap(), "{}", commands).unwrap();
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    let _ = victim.kill();
+    let _ = victim.wait();
+    assert!(stdout.contains(&format!("0x{:x} +0x0000[100]  0   1000  \n", player)), "{}", stdout);
+    assert!(stdout.contains(&format!("wrote i32 55 at 0x{:x}", player)) && stdout.contains(&format!("0x{:x} +0x0000[55]*", player)), "{}", stdout);
+    assert!(stdout.contains(&format!("tagged 0x{:x} as gold, which @gold now names", player + 8)) && stdout.contains(" 1000 [rw-p] \"gold\""), "{}", stdout);
+    assert!(stdout.contains(&format!("cursor at 0x{:x} (+0x0018)", player + 0x18)) && stdout.contains(&format!("locked 0x{:x} as the i64 it holds", player + 0x18)), "{}", stdout);
+    assert!(stdout.contains("error: While browsing: up, down") && stdout.contains("stopped browsing"), "{}", stdout);
+}
@@ -0,0 +1,165 @@
+use std::{path::{Path, PathBuf}, time::{Duration, UNIX_EPOCH}};
+use memory::{Arch, CAPTURE_BLOCK_SIZE, CAPTURE_MAGIC, Capture, CaptureHeader, Compression, Endianness, MemoryRegion, OfflineCapture, Process, ProcessMemory, ScanOptions, Snapshot, dump_all, read_scalar};
+use nix::unistd::Pid;
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("rmh-capture-{}-{}", std::process::id(), name))
+}
+
+fn header() -> CaptureHeader {
+    CaptureHeader { version: 1, pid: 4242, time: UNIX_EPOCH + Duration::from_secs(1_700_000_000), arch: Arch::X86, executable: Some("/usr/bin/game".to_string()), stacks: vec![(4242, 0x7ffd_0000), (4243, 0x7f00_2000)], uncaptured: vec![MemoryRegion { start: 0x3000, end: 0x4000, pathname: "[guard]".to_string(), ..MemoryRegion::default() }] }
+}
+
+fn region(start: usize, end: usize, pathname: &str) -> MemoryRegion {
+    MemoryRegion { start, end, readable: true, writable: true, pathname: pathname.to_string(), device: "00:00".to_string(), ..MemoryRegion::default() }
+}
+
+// Every byte a function of its address, so reads can be checked anywhere
+fn pattern(address: usize) -> u8 {
+    (address % 251) as u8
+}
+
+#[test]
+fn reads_back_what_was_written() {
+    for compression in [Compression::None, Compression::Lz4] {
+        let path = temp_path(&format!("round-trip-{:?}", compression));
+        let big = 0x10_0000 + CAPTURE_BLOCK_SIZE + CAPTURE_BLOCK_SIZE / 2;
+        let regions = vec![region(0x1000, 0x3000, "[heap]"), region(0x10_0000, big, "/usr/lib/libgame.so")];
+        let written = Capture::write(&path, &header(), compression, regions.clone(), |address, block| {
+            // The second page of the heap region could not be read
+            let hole = (0x2000, 0x3000);
+            for (i, byte) in block.iter_mut().enumerate().filter(|x| address + x.0 < hole.0 || address + x.0 >= hole.1) {
+                *byte = pattern(address + i);
+            }
+            Ok(if address == 0x1000 { vec![hole] } else { Vec::new() })
+        }).unwrap();
+        let capture = Capture::open(&path).unwrap();
+        assert_eq!(capture.header, header());
+        assert_eq!(capture.regions, written);
+        assert_eq!(capture.regions.iter().map(|x| x.region.clone()).collect::<Vec<MemoryRegion>>(), regions);
+        assert_eq!(capture.regions[0].holes, vec![(0x2000, 0x3000)]);
+        assert_eq!(capture.len(), 0x2000 + big - 0x10_0000);
+        let mut buffer = vec![0u8; CAPTURE_BLOCK_SIZE];
+        capture.read_region(1, 0x100, &mut buffer).unwrap();
+        assert!(buffer.iter().enumerate().all(|(i, x)| *x == pattern(0x10_0100 + i)));
+        capture.read_region(0, 0xff8, &mut buffer[..16]).unwrap();
+        assert_eq!(buffer[..16], [pattern(0x1ff8), pattern(0x1ff9), pattern(0x1ffa), pattern(0x1ffb), pattern(0x1ffc), pattern(0x1ffd), pattern(0x1ffe), pattern(0x1fff), 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert!(capture.read_region(0, 0x1ff8, &mut buffer[..16]).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+// A version 1 file written by a newer writer: an extra header field and an extra field at the
+// end of the region's table entry, neither of which this reader knows
+#[test]
+fn skips_fields_it_does_not_know() {
+    let mut file = CAPTURE_MAGIC.to_vec();
+    file.extend(1u16.to_le_bytes());
+    let table_offset = file.len();
+    file.extend(0u64.to_le_bytes());
+    let mut fields = Vec::new();
+    for (tag, value) in [(99u16, b"from the future".to_vec()), (1, 77i32.to_le_bytes().to_vec())] {
+        fields.extend(tag.to_le_bytes());
+        fields.extend((value.len() as u32).to_le_bytes());
+        fields.extend(value);
+    }
+    file.extend((fields.len() as u32).to_le_bytes());
+    file.extend(fields);
+    let payload_offset = file.len() as u64;
+    file.extend(4u32.to_le_bytes());
+    file.extend([1, 2, 3, 4]);
+    let table = file.len() as u64;
+    file[table_offset..table_offset + 8].copy_from_slice(&table.to_le_bytes());
+    let mut entry = Vec::new();
+    for x in [0x5000u64, 0x5004] {
+        entry.extend(x.to_le_bytes());
+    }
+    entry.push(1);
+    entry.extend(0u64.to_le_bytes());
+    entry.extend(0u64.to_le_bytes());
+    entry.extend([0, 0]);
+    entry.extend(payload_offset.to_le_bytes());
+    entry.extend(8u64.to_le_bytes());
+    entry.extend([0, 0, 0, 0]);
+    entry.extend(0u32.to_le_bytes());
+    entry.extend(b"appended later");
+    file.extend(1u32.to_le_bytes());
+    file.extend((entry.len() as u32).to_le_bytes());
+    file.extend(entry);
+    let path = temp_path("future");
+    std::fs::write(&path, file).unwrap();
+    let capture = Capture::open(&path).unwrap();
+    assert_eq!(capture.header.pid, 77);
+    assert_eq!(capture.header.executable, None);
+    assert_eq!(capture.header.arch, Arch::native());
+    assert_eq!(capture.regions.len(), 1);
+    assert!(capture.regions[0].region.readable && !capture.regions[0].region.writable);
+    let mut buffer = [0u8; 4];
+    capture.read_region(0, 0, &mut buffer).unwrap();
+    assert_eq!(buffer, [1, 2, 3, 4]);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn refuses_other_files_and_newer_versions() {
+    let path = temp_path("newer");
+    Capture::write(&path, &header(), Compression::None, Vec::new(), |_, _| Ok(Vec::new())).unwrap();
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes[8..10].copy_from_slice(&2u16.to_le_bytes());
+    std::fs::write(&path, &bytes).unwrap();
+    assert!(Capture::open(&path).unwrap_err().to_string().contains("version 2"));
+    std::fs::write(&path, b"{\"pid\": 1}").unwrap();
+    assert!(Capture::open(&path).unwrap_err().to_string().contains("is not a capture"));
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn dumps_a_process_for_offline_mode() {
+    let planted = Box::into_raw(Box::new(0x5eed_1234u32));
+    let path = temp_path("dump");
+    let process = Process::attach(Pid::this()).unwrap();
+    let dumped = dump_all(&process, &path).unwrap();
+    assert!(dumped.iter().any(|x| x.region.contains(planted as usize)));
+    let offline = Process::offline(&path).unwrap();
+    assert_eq!(offline.pid(), Pid::this());
+    assert_eq!(offline.arch(), process.arch());
+    assert_eq!(read_scalar::<u32>(&offline, planted as usize, Endianness::Native).unwrap(), 0x5eed_1234);
+    assert!(offline.write_at(planted as usize, &[0]).is_err());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn saves_and_loads_snapshots() {
+    let planted = Box::into_raw(Box::new(0x0bad_f00du32));
+    let path = temp_path("snapshot");
+    let process = Process::attach(Pid::this()).unwrap();
+    let options = ScanOptions { compress_snapshots: true, ..ScanOptions::default() };
+    let snapshot = Snapshot::capture(&process, &options).unwrap();
+    snapshot.save(&process, &path).unwrap();
+    assert!(Capture::open(&path).unwrap().regions.iter().all(|x| x.compression == Compression::Lz4));
+    let loaded = Snapshot::load(&path, false).unwrap();
+    assert_eq!(loaded.len(), snapshot.len());
+    assert!(loaded.failed_regions.is_empty());
+    let chunk = loaded.chunks.iter().find(|x| x.address <= planted as usize && planted as usize + 4 <= x.address + x.len()).unwrap();
+    let offset = planted as usize - chunk.address;
+    assert_eq!(chunk.bytes().unwrap()[offset..offset + 4], 0x0bad_f00du32.to_ne_bytes());
+    std::fs::remove_file(path).unwrap();
+}
+
+// The fuzzer's seeds, among them files it found opening or reading used to panic on: a time past
+// what SystemTime holds, and an lz4 block that decompresses to less than its region's share
+#[test]
+fn corrupt_captures_are_refused_rather_than_panicking() {
+    let seeds = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/seeds/capture");
+    for name in ["time-out-of-range", "short-lz4-block"] {
+        let error = match OfflineCapture::open(&seeds.join(name)) {
+            Err(e) => e.to_string(),
+            Ok(capture) => capture.memory_regions().unwrap().iter().find_map(|x| capture.read_at(x.start, &mut [0u8; 64]).err()).unwrap().to_string(),
+        };
+        assert!(error.contains("out of range") || error.contains("holds"), "{}: {}", name, error);
+    }
+    for name in ["dump", "lz4-with-hole"] {
+        let capture = OfflineCapture::open(&seeds.join(name)).unwrap();
+        assert_eq!(read_scalar::<i32>(&capture, 0x10010, Endianness::Native).unwrap(), 31337);
+    }
+}
@@ -0,0 +1,125 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+mod common;
+use std::{io::Write, process::{Command, Stdio}, time::Duration};
+use memory::{Endianness, Instruction, MemBackend, MemoryRegion, Process, ProcessMemory, decode_instruction, inject_code, read_bytes_from_process, read_scalar};
+use common::Target;
+
+impl Target {
+    fn process(&self) -> Process {
+        Process::with_backend(self.pid(), MemBackend::ProcessVmReadv).unwrap()
+    }
+
+    fn region_at(&self, address: usize) -> Option<MemoryRegion> {
+        self.process().memory_regions().unwrap().into_iter().find(|x| x.contains(address))
+    }
+
+    // How far hp counts up over 100 milliseconds, about 20 ticks
+    fn counted(&mut self) -> i32 {
+        let process = self.process();
+        let before = read_scalar::<i32>(&process, self.player, Endianness::Native).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(self.child.try_wait().unwrap().is_none());
+        read_scalar::<i32>(&process, self.player, Endianness::Native).unwrap() - before
+    }
+
+    // The first instruction in tick that matches, with its address
+    fn find_in_tick(&self, matches: impl Fn(&Instruction) -> bool) -> (usize, Instruction) {
+        let code = read_bytes_from_process(self.process(), 256, self.tick.unwrap()).unwrap();
+        let mut at = 0;
+        loop {
+            let instruction = decode_instruction(&code[at..]).unwrap();
+            if matches(&instruction) {
+                return (self.tick.unwrap() + at, instruction);
+            }
+            at += instruction.len;
+        }
+    }
+}
+
+// add dword [rdi], 100, run on the way into tick while rdi still holds its pointer to hp
+const ADD_100: [u8; 3] = [0x83, 0x07, 0x64];
+
+#[test]
+fn hooks_a_function_and_undoes_it() {
+    let mut target = Target::spawn();
+    let code = read_bytes_from_process(target.process(), 16, target.tick.unwrap()).unwrap();
+    let mut injection = inject_code(target.pid(), &ADD_100, Some(target.tick.unwrap())).unwrap();
+    let hook = injection.hook.as_ref().unwrap();
+    assert_eq!((hook.address, &hook.original[..], hook.patched[0]), (target.tick.unwrap(), &code[..hook.len()], 0xe9));
+    assert!(hook.patched[5..].iter().all(|&x| x == 0x90));
+    assert_eq!(target.region_at(injection.cave).unwrap().permissions(), "r-xp");
+    assert!(injection.cave.abs_diff(target.tick.unwrap()) < 1 << 31);
+    assert!(target.counted() > 100);
+    injection.remove().unwrap();
+    assert_eq!(read_bytes_from_process(target.process(), 16, target.tick.unwrap()).unwrap(), code);
+    assert!(target.region_at(injection.cave).is_none());
+    assert!(target.counted() < 100);
+}
+
+// In a debug build tick has a lea of a panic location to hand to a precondition check
+#[test]
+fn moves_rip_relative_instructions() {
+    let mut target = Target::spawn();
+    let (site, lea) = target.find_in_tick(|x| x.rip_relative.is_some());
+    let original = read_bytes_from_process(target.process(), lea.len, site).unwrap();
+    let displacement = |code: &[u8]| i32::from_le_bytes(code[lea.rip_relative.unwrap()..][..4].try_into().unwrap()) as isize;
+    let referred = (site + lea.len).wrapping_add_signed(displacement(&original));
+    let mut injection = inject_code(target.pid(), &[], Some(site)).unwrap();
+    let moved = &injection.cave_code;
+    assert_eq!(decode_instruction(moved).unwrap(), lea);
+    assert_eq!((injection.cave + lea.len).wrapping_add_signed(displacement(moved)), referred);
+    // Then back to right after what was moved
+    let hooked = injection.hook.as_ref().unwrap().len();
+    let back = &moved[moved.len() - 5..];
+    assert_eq!(back[0], 0xe9);
+    assert_eq!((injection.cave + moved.len()).wrapping_add_signed(i32::from_le_bytes(back[1..].try_into().unwrap()) as isize), site + hooked);
+    assert!(target.counted() > 0);
+    injection.remove().unwrap();
+    assert!(target.counted() > 0);
+}
+
+// Each failure leaves nothing behind, neither a hook nor an allocation
+#[test]
+fn refuses_what_cannot_be_moved() {
+    let mut target = Target::spawn();
+    let regions = target.process().memory_regions().unwrap().len();
+    let (call, _) = target.find_in_tick(|x| x.relative_branch);
+    let code = read_bytes_from_process(target.process(), 16, call).unwrap();
+    assert!(inject_code(target.pid(), &ADD_100, Some(call)).unwrap_err().to_string().contains("jumps relative"));
+    assert!(inject_code(target.pid(), &ADD_100, Some(target.player)).unwrap_err().to_string().contains("not in executable memory"));
+    assert!(inject_code(target.pid(), &[], None).is_err());
+    assert_eq!(read_bytes_from_process(target.process(), 16, call).unwrap(), code);
+    assert_eq!(target.process().memory_regions().unwrap().len(), regions);
+    assert!(target.counted() > 0);
+}
+
+#[test]
+fn puts_code_anywhere_without_a_hook() {
+    let target = Target::spawn();
+    let mut injection = inject_code(target.pid(), &[0x31, 0xc0, 0xc3], None).unwrap();
+    assert!(injection.hook.is_none());
+    assert_eq!(read_bytes_from_process(target.process(), 3, injection.cave).unwrap(), [0x31, 0xc0, 0xc3]);
+    assert_eq!(target.region_at(injection.cave).unwrap().permissions(), "r-xp");
+    injection.remove().unwrap();
+    assert!(target.region_at(injection.cave).is_none());
+}
+
+// Whatever the session injected comes out when it ends
+#[test]
+fn the_session_undoes_its_injections() {
+    let mut target = Target::spawn();
+    let code = read_bytes_from_process(target.process(), 16, target.tick.unwrap()).unwrap();
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).args([&target.pid().to_string(), "--dangerous"]).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut stdin = session.stdin.take().unwrap();
+    writeln!(stdin, "inject \"83 07 64\" --at 0x{:x}\ninject \"90\" --at 0x{:x}\ninjections\nmaps", target.tick.unwrap(), target.tick.unwrap() + 1).unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(target.counted() > 100);
+    drop(stdin);
+    let output = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    assert!(output.contains("hooked bench_target+"), "{}", output);
+    assert!(output.contains("already patched or hooked"), "{}", output);
+    assert!(output.lines().any(|x| x.contains("r-xp") && x.ends_with("[allocated by inject]")), "{}", output);
+    assert!(output.contains("removed the "), "{}", output);
+    assert_eq!(read_bytes_from_process(target.process(), 16, target.tick.unwrap()).unwrap(), code);
+    assert!(target.counted() < 100);
+}
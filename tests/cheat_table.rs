@@ -0,0 +1,74 @@
+use memory::{CheatEntry, CheatGroup, CheatTable, Encoding, Endianness, SavedAddress, ValueType};
+
+fn entry(address: SavedAddress, value_type: ValueType, len: usize, frozen: Option<&str>) -> CheatEntry {
+    CheatEntry { description: "health".to_string(), address, value_type, len, frozen: frozen.map(str::to_string) }
+}
+
+fn table(entries: Vec<CheatEntry>, endianness: Endianness) -> String {
+    CheatTable { groups: vec![CheatGroup { name: "Locks".to_string(), entries }], endianness }.to_xml()
+}
+
+#[test]
+fn nests_frozen_module_relative_entries_under_their_group() {
+    let game = SavedAddress::Module { name: "game".to_string(), offset: 0x2a10 };
+    let xml = table(vec![entry(game, ValueType::I32, 4, Some("100"))], Endianness::Native);
+    assert_eq!(xml, concat!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n",
+        "<CheatTable CheatEngineTableVersion=\"45\">\n",
+        "  <CheatEntries>\n",
+        "    <CheatEntry>\n",
+        "      <ID>0</ID>\n",
+        "      <Description>\"Locks\"</Description>\n",
+        "      <GroupHeader>1</GroupHeader>\n",
+        "      <CheatEntries>\n",
+        "        <CheatEntry>\n",
+        "          <ID>1</ID>\n",
+        "          <Description>\"health\"</Description>\n",
+        "          <LastState Value=\"100\" Activated=\"1\"/>\n",
+        "          <ShowAsSigned>1</ShowAsSigned>\n",
+        "          <VariableType>4 Bytes</VariableType>\n",
+        "          <Address>\"game\"+2A10</Address>\n",
+        "        </CheatEntry>\n",
+        "      </CheatEntries>\n",
+        "    </CheatEntry>\n",
+        "  </CheatEntries>\n",
+        "  <UserdefinedSymbols/>\n",
+        "</CheatTable>\n",
+    ));
+}
+
+#[test]
+fn notes_that_absolute_addresses_will_not_last() {
+    let xml = table(vec![entry(SavedAddress::Absolute(0x7f00_1234), ValueType::F64, 8, None)], Endianness::Native);
+    assert!(xml.contains("<!-- Not inside any module, so this address will not survive a restart of the process -->"));
+    assert!(xml.contains("<VariableType>Double</VariableType>"));
+    assert!(xml.contains("<Address>7F001234</Address>"));
+    assert!(!xml.contains("LastState") && !xml.contains("ShowAsSigned"));
+}
+
+#[test]
+fn maps_buffers_strings_and_byte_order() {
+    let address = SavedAddress::Absolute(0x1000);
+    let xml = table(vec![
+        entry(address.clone(), ValueType::Bytes, 3, Some("90 90 90")),
+        entry(address.clone(), ValueType::Str { encoding: Encoding::Utf16, null_terminate: true }, 10, None),
+        entry(address.clone(), ValueType::U32, 4, None),
+        entry(address, ValueType::U64, 8, None),
+    ], Endianness::Big);
+    assert!(xml.contains("<VariableType>Array of byte</VariableType>\n          <ByteLength>3</ByteLength>"));
+    assert!(xml.contains("<Length>5</Length>\n          <Unicode>1</Unicode>\n          <ZeroTerminate>1</ZeroTerminate>"));
+    assert!(xml.contains("<VariableType>Custom</VariableType>\n          <CustomType>4 Byte Big Endian</CustomType>"));
+    assert!(xml.contains("<!-- Big-endian in the target, but Cheat Engine has no big-endian type of this size -->\n          <VariableType>8 Bytes</VariableType>"));
+}
+
+#[test]
+fn escapes_text_and_leaves_out_empty_groups() {
+    let mut quoted = entry(SavedAddress::Module { name: "a&b".to_string(), offset: 0 }, ValueType::U8, 1, Some("<1>"));
+    quoted.description = "\"hp\" & <mp>".to_string();
+    let groups = vec![CheatGroup { name: "Empty".to_string(), entries: Vec::new() }, CheatGroup { name: "Locks".to_string(), entries: vec![quoted] }];
+    let xml = CheatTable { groups, endianness: Endianness::Native }.to_xml();
+    assert!(!xml.contains("Empty"));
+    assert!(xml.contains("<Description>\"&quot;hp&quot; &amp; &lt;mp&gt;\"</Description>"));
+    assert!(xml.contains("<LastState Value=\"&lt;1&gt;\" Activated=\"1\"/>"));
+    assert!(xml.contains("<Address>\"a&amp;b\"+0</Address>"));
+}
@@ -0,0 +1,100 @@
+mod common;
+use std::sync::Arc;
+use memory::{Endianness, MemBackend, MockProcess, Pid, Process, RegionCache, read_scalar, write_scalar, write_to_process, write_to_process_checked, write_to_process_forced};
+
+const DATA: usize = 0x10000;
+const CODE: usize = 0x20000;
+
+// A data page and, after it, a code page of the game's own file
+fn mock() -> Arc<MockProcess> {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", DATA, DATA + 0x1000)).unwrap();
+    mock.map(&format!("{:x}-{:x} r-xp 00001000 08:01 42 /opt/game/game", CODE, CODE + 0x1000)).unwrap();
+    Arc::new(mock)
+}
+
+#[test]
+fn writes_only_into_writable_mappings() {
+    let mock = mock();
+    write_to_process(&*mock, DATA + 8, &7i32).unwrap();
+    assert_eq!(read_scalar::<i32>(&*mock, DATA + 8, Endianness::Native).unwrap(), 7);
+    let error = write_to_process(&*mock, CODE + 8, &7i32).unwrap_err().to_string();
+    assert_eq!(error, format!("0x{:x} is in a r-xp mapping of /opt/game/game, which is not writable", CODE + 8));
+    // Every byte is checked, so a value running off the end of the data page is refused too
+    assert_eq!(write_to_process(&*mock, DATA + 0xffe, &7i32).unwrap_err().to_string(), format!("0x{:x} is not mapped", DATA + 0x1000));
+    assert_eq!(write_to_process(&*mock, 0x1000, &7i32).unwrap_err().to_string(), "0x1000 is not mapped");
+}
+
+// Scalars are checked like everything else, and a write that only partly lands is an error
+#[test]
+fn scalar_writes_are_checked_and_must_land_whole() {
+    let mock = mock();
+    write_scalar(&*mock, DATA + 8, 7u32, Endianness::Big).unwrap();
+    assert_eq!(mock.bytes(DATA + 8, 4).unwrap(), [0, 0, 0, 7]);
+    assert!(write_scalar(&*mock, CODE + 8, 7u32, Endianness::Native).unwrap_err().to_string().contains("which is not writable"));
+    assert_eq!(mock.bytes(CODE + 8, 4).unwrap(), [0; 4]);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0", DATA + 0x1000, DATA + 0x2000)).unwrap();
+    mock.mark_failing(DATA + 0x1000, DATA + 0x2000).unwrap();
+    let error = write_scalar(&*mock, DATA + 0xffe, 7u32, Endianness::Native).unwrap_err().to_string();
+    assert_eq!(error, format!("Short write at 0x{:x}: wrote 2 of 4 bytes", DATA + 0xffe));
+}
+
+// The cache is re-read once before refusing, so a page the target has made writable since is written
+#[test]
+fn checked_writes_go_by_the_cache() {
+    let mock = mock();
+    let mut regions = RegionCache::from_process(mock.clone()).unwrap();
+    assert!(write_to_process_checked(&*mock, &mut regions, CODE, &1u8, false).unwrap_err().to_string().contains("not writable"));
+    mock.unmap(CODE).unwrap();
+    mock.map(&format!("{:x}-{:x} rw-p 00001000 08:01 42 /opt/game/game", CODE, CODE + 0x1000)).unwrap();
+    write_to_process_checked(&*mock, &mut regions, CODE, &1u8, false).unwrap();
+    assert_eq!(read_scalar::<u8>(&*mock, CODE, Endianness::Native).unwrap(), 1);
+}
+
+// Forcing skips the check but not the byte count, so a forced write that runs off the end of the
+// data page fails
+#[test]
+fn forced_writes_must_land_whole() {
+    let mock = mock();
+    let error = write_to_process_forced(&*mock, DATA + 0xffe, &7u32).unwrap_err().to_string();
+    assert_eq!(error, format!("Short write at 0x{:x}: wrote 2 of 4 bytes", DATA + 0xffe));
+}
+
+// /proc/<pid>/mem writes read-only mappings as a debugger would, but only once forced
+#[cfg(target_os = "linux")]
+#[test]
+fn forced_writes_go_through_read_only_mappings() {
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize };
+    let address = unsafe { libc::mmap(std::ptr::null_mut(), page, libc::PROT_READ, libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, -1, 0) };
+    assert_ne!(address, libc::MAP_FAILED);
+    let address = address as usize;
+    let process = Process::with_backend(Pid::this(), MemBackend::ProcMem).unwrap();
+    assert!(write_to_process(&process, address, &0x5eedu32).unwrap_err().to_string().contains("which is not writable"));
+    let mut regions = RegionCache::new(Pid::this()).unwrap();
+    assert!(write_to_process_checked(&process, &mut regions, address, &0x5eedu32, false).is_err());
+    assert_eq!(unsafe { std::ptr::read_volatile(address as *const u32) }, 0);
+    write_to_process_forced(&process, address, &0x5eedu32).unwrap();
+    assert_eq!(unsafe { std::ptr::read_volatile(address as *const u32) }, 0x5eed);
+    write_to_process_checked(&process, &mut regions, address + 4, &0x1234u32, true).unwrap();
+    assert_eq!(unsafe { std::ptr::read_volatile((address + 4) as *const u32) }, 0x1234);
+}
+
+// The victim's ELF header is the first page of its own file, mapped r--p
+#[cfg(target_os = "linux")]
+#[test]
+fn the_cli_writes_read_only_mappings_only_with_force() {
+    use std::{io::{BufRead, BufReader, Write}, process::{Command, Stdio}};
+    let mut victim = Command::new(common::victim_path()).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut lines = BufReader::new(victim.stdout.take().unwrap()).lines();
+    let pid = lines.next().unwrap().unwrap().strip_prefix("pid ").unwrap().to_string();
+    let maps = std::fs::read_to_string(format!("/proc/{}/maps", pid)).unwrap();
+    let header = maps.lines().find(|x| x.contains(" r--p 00000000 ") && x.ends_with("examples/victim")).unwrap();
+    let header = usize::from_str_radix(header.split_once('-').unwrap().0, 16).unwrap();
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).args([&pid, "--backend", "procmem"]).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    writeln!(session.stdin.take().unwrap(), "scan i32 100\nwrite 0x{:x} 5\nwrite 0x{:x} 5 --force\nguess 0x{:x}", header, header, header).unwrap();
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    let _ = victim.kill();
+    let _ = victim.wait();
+    assert!(stdout.contains(&format!("error: 0x{:x} is in a r--p mapping of ", header)) && stdout.contains("which is not writable (add --force to try anyway)"), "{}", stdout);
+    assert!(stdout.contains(&format!("victim+0x0 (0x{:x}): 05 00 00 00 ", header)), "{}", stdout);
+}
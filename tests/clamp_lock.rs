@@ -0,0 +1,83 @@
+#![cfg(target_os = "linux")]
+mod common;
+use std::{io::{BufRead, BufReader, Write}, process::{Command, Stdio}, time::{Duration, Instant}};
+use memory::{Endianness, LockAction, LockManager, MockProcess, Pid, Scalar, read_scalar};
+use common::victim_path;
+
+// Polls until the read gives the value, for writes made by a lock's servicing thread
+fn wait_for<T: PartialEq>(read: impl Fn() -> T, expected: T) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(5) {
+        if read() == expected {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    false
+}
+
+#[test]
+fn only_values_outside_the_band_are_written() {
+    let floor = LockAction::Clamp { min: Some(50), max: None, tolerance: 0 };
+    assert_eq!((floor.needs_write(49), floor.apply(49)), (true, 50));
+    assert!(!floor.needs_write(50) && !floor.needs_write(i32::MAX));
+    let band = LockAction::Clamp { min: Some(50), max: Some(100), tolerance: 0 };
+    assert_eq!((band.needs_write(101), band.apply(101)), (true, 100));
+    assert!(!band.needs_write(75));
+}
+
+// A float clamp lets the value stray by the tolerance, then puts it back on the bound
+#[test]
+fn floats_may_stray_by_the_tolerance() {
+    let clamp = LockAction::Clamp { min: Some(1.0f32), max: Some(2.0), tolerance: 0.1 };
+    assert!(!clamp.needs_write(0.95) && !clamp.needs_write(2.05));
+    assert_eq!((clamp.needs_write(0.8), clamp.apply(0.8)), (true, 1.0));
+    assert_eq!((clamp.needs_write(2.5), clamp.apply(2.5)), (true, 2.0));
+}
+
+#[test]
+fn clamps_are_listed_by_their_bounds() {
+    assert_eq!(LockAction::Clamp { min: Some(50), max: None, tolerance: 0 }.to_string(), "clamp min 50");
+    assert_eq!(LockAction::Clamp { min: None, max: Some(1.5f32), tolerance: 0.25 }.to_string(), "clamp max 1.5 0.25");
+    assert_eq!(LockAction::Clamp { min: Some(-5i8), max: Some(5), tolerance: 0 }.to_string(), "clamp -5 5");
+}
+
+// Every write counted is a correction, as a value inside the band is only read
+#[test]
+fn the_servicing_thread_corrects_only_what_strays() {
+    let mock: &'static MockProcess = Box::leak(Box::new(MockProcess::new(100)));
+    mock.map("10000-11000 rw-p 00000000 00:00 0").unwrap();
+    mock.plant(0x10000, &70i32.to_bytes()).unwrap();
+    let mut locks = LockManager::new(mock);
+    locks.lock_with_action(LockAction::Clamp { min: Some(50), max: None, tolerance: 0 }, 0x10000, Duration::from_millis(1), Endianness::Native);
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(locks.get(0x10000).unwrap().writes, 0);
+    mock.plant(0x10000, &10i32.to_bytes()).unwrap();
+    assert!(wait_for(|| i32::from_bytes(&mock.bytes(0x10000, 4).unwrap()), 50));
+    assert_eq!(locks.get(0x10000).unwrap().writes, 1);
+    locks.remove_all();
+}
+
+// --min and --max are given as options, and the lock is listed as the clamp it makes
+#[test]
+fn the_cli_makes_clamp_locks() {
+    let mut victim = Command::new(victim_path()).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut lines = BufReader::new(victim.stdout.take().unwrap()).lines();
+    let pid = lines.next().unwrap().unwrap().strip_prefix("pid ").unwrap().to_string();
+    let player = lines.next().unwrap().unwrap();
+    let player = usize::from_str_radix(player.strip_prefix("player 0x").unwrap().split_once(' ').unwrap().0, 16).unwrap();
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(&pid).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut stdin = session.stdin.take().unwrap();
+    // hp starts at 100
+    writeln!(stdin, "lock 0x{:x} i32 --min 500 --max 1000\nlock 0x{:x} i32 --tolerance 1\nlock 0x{:x} i32 5 --min 1", player, player + 0x8, player + 0x8).unwrap();
+    let pid = Pid::from_raw(pid.parse().unwrap());
+    assert!(wait_for(|| read_scalar::<i32>(pid, player, Endianness::Native).unwrap(), 500));
+    writeln!(stdin, "locks\nquit").unwrap();
+    drop(stdin);
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    assert!(stdout.contains(&format!("0x{:x} (", player)) && stdout.contains(" i32 clamp 500 1000 every "), "{}", stdout);
+    assert!(stdout.contains(", 1 corrective writes"), "{}", stdout);
+    assert!(stdout.contains("error: --tolerance is how far past") && stdout.contains("error: --min and --max make a clamp lock"), "{}", stdout);
+    let _ = victim.kill();
+    let _ = victim.wait();
+}
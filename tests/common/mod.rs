@@ -0,0 +1,61 @@
+// Fixtures shared by the integration tests: bench_target as a running process to attach to, the
+// victim example for the tests that drive the CLI, and a mock with a heap to plant values in. Each
+// test file uses only some of it
+#![allow(dead_code)]
+use std::{io::{BufRead, BufReader}, path::PathBuf, process::{Child, Command, Stdio}};
+use memory::MockProcess;
+use nix::unistd::Pid;
+
+// bench_target with 1 MB of planted values, killed on drop. With "tick" a second thread counts
+// player.hp up through its tick function
+pub struct Target {
+    pub child: Child,
+    pub player: usize,
+    // Only in the tick mode
+    pub tick: Option<usize>,
+    // The block of planted values
+    pub block: usize,
+}
+
+impl Target {
+    pub fn spawn() -> Target {
+        Target::spawn_with("tick")
+    }
+
+    pub fn spawn_with(mode: &str) -> Target {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_bench_target")).args(["1", mode]).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+        // Every line is read before the pipe is closed, or the last one fails to be written
+        let lines = BufReader::new(child.stdout.take().unwrap()).lines().take(if mode == "tick" { 4 } else { 3 }).map(|x| x.unwrap()).collect::<Vec<String>>();
+        let address = |name: &str| lines.iter().find_map(|x| usize::from_str_radix(x.strip_prefix(name)?.strip_prefix(" 0x")?.split(' ').next()?, 16).ok());
+        Target { player: address("player").unwrap(), tick: address("tick"), block: address("allocated").unwrap(), child }
+    }
+
+    pub fn pid(&self) -> Pid {
+        Pid::from_raw(self.child.id() as i32)
+    }
+}
+
+impl Drop for Target {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// Next to the binaries, where cargo test puts examples; a test run on its own may not have built it
+pub fn victim_path() -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_BIN_EXE_memory")).parent().unwrap().join("examples/victim");
+    if !path.exists() {
+        assert!(Command::new(env!("CARGO")).args(["build", "-q", "--example", "victim"]).status().unwrap().success());
+    }
+    path
+}
+
+pub const HEAP: usize = 0x10000;
+
+// A mock process with nothing mapped but a heap of the given size at HEAP
+pub fn mock_with_heap(bytes: usize) -> MockProcess {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", HEAP, HEAP + bytes)).unwrap();
+    mock
+}
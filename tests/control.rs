@@ -0,0 +1,84 @@
+#![cfg(target_os = "linux")]
+mod common;
+use std::{io::Write, os::unix::process::ExitStatusExt, process::{Child, ChildStdin, Command, Stdio}, time::Duration};
+use memory::{Endianness, MemBackend, Process, continue_process, is_stopped, read_scalar, stop_process};
+use common::Target;
+
+impl Target {
+    // How far hp counts up over 100 milliseconds
+    fn counted(&self) -> i32 {
+        let process = Process::with_backend(self.pid(), MemBackend::ProcessVmReadv).unwrap();
+        let before = read_scalar::<i32>(&process, self.player, Endianness::Native).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        read_scalar::<i32>(&process, self.player, Endianness::Native).unwrap() - before
+    }
+
+    fn session(&self) -> (Child, ChildStdin) {
+        let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(self.pid().to_string()).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+        let stdin = session.stdin.take().unwrap();
+        (session, stdin)
+    }
+}
+
+fn output(session: Child) -> String {
+    String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap()
+}
+
+#[test]
+fn stops_and_continues_the_target() {
+    let target = Target::spawn();
+    stop_process(target.pid()).unwrap();
+    assert!(is_stopped(target.pid()));
+    assert_eq!(target.counted(), 0);
+    continue_process(target.pid()).unwrap();
+    std::thread::sleep(Duration::from_millis(20));
+    assert!(!is_stopped(target.pid()));
+    assert!(target.counted() > 0);
+}
+
+// The prompt says so for as long as the target is stopped
+#[test]
+fn stop_and_cont_from_the_session() {
+    let target = Target::spawn();
+    let (session, mut stdin) = target.session();
+    writeln!(stdin, "stop").unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(target.counted(), 0);
+    writeln!(stdin, "cont").unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(target.counted() > 0);
+    drop(stdin);
+    let output = output(session);
+    assert!(output.contains(&format!("stopped process {}\n[stopped] > continued process {}\n> ", target.pid(), target.pid())), "{}", output);
+}
+
+#[test]
+fn kill_asks_first() {
+    let mut target = Target::spawn();
+    let (session, mut stdin) = target.session();
+    writeln!(stdin, "kill\nmaps --json\nkill").unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(target.child.try_wait().unwrap().is_none());
+    writeln!(stdin, "yes").unwrap();
+    let output = output(session);
+    assert!(output.contains(&format!("kill process {} (bench_target) with SIGKILL? type yes to confirm", target.pid())), "{}", output);
+    assert!(output.contains("not killed"), "{}", output);
+    assert!(output.contains(&format!("killed process {}", target.pid())), "{}", output);
+    assert_eq!(target.child.wait().unwrap().signal(), Some(libc::SIGKILL));
+}
+
+// Quitting continues a stopped target, whether the session stopped it or not
+#[test]
+fn quit_continues_unless_told_not_to() {
+    let target = Target::spawn();
+    stop_process(target.pid()).unwrap();
+    let (session, mut stdin) = target.session();
+    writeln!(stdin, "quit").unwrap();
+    assert!(output(session).contains(&format!("continued process {}, which was stopped", target.pid())));
+    assert!(target.counted() > 0);
+    let (session, mut stdin) = target.session();
+    writeln!(stdin, "stop\nquit --leave-stopped").unwrap();
+    assert!(!output(session).contains("continued"));
+    assert!(is_stopped(target.pid()));
+    assert_eq!(target.counted(), 0);
+}
@@ -0,0 +1,71 @@
+#![cfg(target_os = "linux")]
+mod common;
+use std::{io::Write, process::{Command, Stdio}, time::Duration};
+use memory::{Endianness, MemoryRegion, Process, diff_processes_by, get_memory_regions, pair_regions};
+use common::Target;
+
+impl Target {
+    fn process(&self) -> Process {
+        Process::attach(self.pid()).unwrap()
+    }
+
+    // Runs the commands in a session on the target, returning what it printed
+    fn session(&self, commands: &str) -> String {
+        let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(self.pid().to_string()).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+        session.stdin.take().unwrap().write_all(commands.as_bytes()).unwrap();
+        let output = session.wait_with_output().unwrap();
+        format!("{}{}", String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap())
+    }
+}
+
+// Two targets, the first started long enough before the second to be some ticks ahead
+fn two_targets() -> (Target, Target) {
+    let first = Target::spawn();
+    std::thread::sleep(Duration::from_millis(100));
+    (first, Target::spawn())
+}
+
+fn region(start: usize, end: usize, offset: usize, pathname: &str) -> MemoryRegion {
+    MemoryRegion { start, end, readable: true, writable: true, offset, pathname: pathname.to_string(), ..MemoryRegion::default() }
+}
+
+#[test]
+fn pairs_regions_by_what_they_hold() {
+    let first = [region(0x1000, 0x2000, 0, "/usr/bin/game"), region(0x2000, 0x4000, 0x1000, "/usr/bin/game"), region(0x4000, 0x5000, 0, ""), region(0x10000, 0x30000, 0, "[heap]"), region(0x50000, 0x51000, 0, ""), region(0x60000, 0x61000, 0, "")];
+    let second = [region(0x8000, 0x9000, 0, "/usr/bin/game"), region(0x9000, 0xb000, 0x1000, "/usr/bin/game"), region(0xb000, 0xc000, 0, ""), region(0x20000, 0x28000, 0, "[heap]"), region(0x70000, 0x71000, 0, "")];
+    let (pairs, only_first, only_second) = pair_regions(&first, &second);
+    let found = pairs.iter().map(|x| (x.label(0x10), x.first.start, x.second.start)).collect::<Vec<(String, usize, usize)>>();
+    // The .bss after the executable is part of it, and other anonymous memory is numbered in order
+    assert_eq!(found, vec![
+        ("game+0x10".to_string(), 0x1000, 0x8000),
+        ("game+0x1010".to_string(), 0x2000, 0x9000),
+        ("game+0x3010".to_string(), 0x4000, 0xb000),
+        ("[heap]+0x10".to_string(), 0x10000, 0x20000),
+        ("[anonymous rw-p after game]+0x10".to_string(), 0x50000, 0x70000),
+    ]);
+    assert_eq!(pairs[3].common_len(), 0x8000);
+    assert_eq!((only_first.iter().map(|x| x.start).collect::<Vec<usize>>(), only_second.len()), (vec![0x60000], 0));
+}
+
+#[test]
+fn finds_the_value_that_differs_in_each_process() {
+    let (ahead, behind) = two_targets();
+    let diff = diff_processes_by::<i32>(ahead.process(), behind.process(), None, 4, Endianness::Native, |first, second| second < first).unwrap();
+    let heap = diff.changed.iter().find(|x| x.pair.name == "[heap]").unwrap();
+    let offset = ahead.player - heap.pair.first.start;
+    assert!(heap.ranges.contains(&(offset, offset + 4)), "{:x?}", heap.ranges);
+    assert_eq!(heap.pair.second.start + offset, behind.player);
+    assert!(diff.addresses(false).any(|x| x == ahead.player));
+    assert!(diff.addresses(true).any(|x| x == behind.player));
+    assert_eq!(diff.pairs + diff.only_first.len(), get_memory_regions(ahead.pid()).unwrap().iter().filter(|x| x.readable).count());
+}
+
+#[test]
+fn the_session_loads_the_results_in_its_own_process() {
+    let (ahead, behind) = two_targets();
+    let output = behind.session(&format!("set filter heap\ncrosscompare {} {} i32 decreased --load\nlist\ncrosscompare {} 1 --load\n", ahead.pid(), behind.pid(), ahead.pid()));
+    assert!(output.contains(&format!(" results as i32 in process {}", behind.pid())), "{}", output);
+    assert!(output.contains("[heap]+0x"), "{}", output);
+    assert!(output.contains(&format!("0x{:x} (heap+", behind.player)), "{}", output);
+    assert!(output.contains(&format!("is neither {} nor 1", ahead.pid())), "{}", output);
+}
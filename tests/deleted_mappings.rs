@@ -0,0 +1,52 @@
+mod common;
+use memory::{MemoryRegion, SavedAddress, find_module, modules_from_regions};
+
+fn regions(lines: &[&str]) -> Vec<MemoryRegion> {
+    lines.iter().map(|x| x.parse::<MemoryRegion>().unwrap()).collect()
+}
+
+#[test]
+fn the_suffix_is_recorded_rather_than_kept_in_the_path() {
+    let region = "7f0000000000-7f0000001000 r-xp 00000000 08:01 42 /opt/game/libgame.so (deleted)".parse::<MemoryRegion>().unwrap();
+    assert_eq!((region.pathname.as_str(), region.deleted), ("/opt/game/libgame.so", true));
+    // Only the kernel's suffix, at the very end, is taken off
+    let region = "7f0000000000-7f0000001000 r--p 00000000 08:01 43 /opt/game/(deleted) saves".parse::<MemoryRegion>().unwrap();
+    assert_eq!((region.pathname.as_str(), region.deleted), ("/opt/game/(deleted) saves", false));
+}
+
+// A module with any deleted mapping is marked, and is still found by its name and path
+#[test]
+fn deleted_modules_are_matched_without_the_suffix() {
+    let modules = modules_from_regions(&regions(&[
+        "7f0000000000-7f0000001000 r--p 00000000 08:01 42 /opt/game/libgame.so",
+        "7f0000001000-7f0000003000 r-xp 00001000 08:01 42 /opt/game/libgame.so (deleted)",
+        "7f0000010000-7f0000011000 r-xp 00000000 08:01 50 /usr/lib/libc.so.6",
+    ]));
+    let game = find_module(&modules, "libgame.so").unwrap();
+    assert_eq!((game.path.as_str(), game.base, game.size, game.deleted), ("/opt/game/libgame.so", 0x7f00_0000_0000, 0x3000, true));
+    assert!(find_module(&modules, "/opt/game/libgame.so").is_some() && !find_module(&modules, "libc.so.6").unwrap().deleted);
+    let saved = SavedAddress::Module { name: "libgame.so".to_string(), offset: 0x1010 };
+    assert_eq!(saved.resolve(&modules).unwrap(), 0x7f00_0000_1010);
+}
+
+// A copy of the victim that is deleted once started shows up as such in the listings
+#[cfg(target_os = "linux")]
+#[test]
+fn the_cli_warns_about_deleted_modules() {
+    use std::{io::{BufRead, BufReader, Write}, process::{Command, Stdio}};
+    let copy = std::env::temp_dir().join(format!("rmh-deleted-victim-{}", std::process::id()));
+    std::fs::copy(common::victim_path(), &copy).unwrap();
+    let mut victim = Command::new(&copy).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut lines = BufReader::new(victim.stdout.take().unwrap()).lines();
+    let pid = lines.next().unwrap().unwrap().strip_prefix("pid ").unwrap().to_string();
+    std::fs::remove_file(&copy).unwrap();
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(&pid).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    writeln!(session.stdin.take().unwrap(), "modules\nmaps").unwrap();
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    let _ = victim.kill();
+    let _ = victim.wait();
+    let name = copy.file_name().unwrap().to_str().unwrap();
+    assert!(stdout.contains(&format!(" {} (main, writable, deleted) {}", name, copy.display())), "{}", stdout);
+    assert!(stdout.contains("warning: 1 modules were deleted or replaced on disk after loading"), "{}", stdout);
+    assert!(stdout.contains(&format!(" {} (deleted)", copy.display())), "{}", stdout);
+}
@@ -0,0 +1,96 @@
+use std::{io::Write, path::PathBuf, process::{Command, Stdio}, time::SystemTime};
+use memory::{Arch, CAPTURE_BLOCK_SIZE, Capture, CaptureHeader, Compression, Endianness, MemoryRegion, diff_captures, diff_captures_by};
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("rmh-diff-{}-{}", std::process::id(), name))
+}
+
+fn region(start: usize, end: usize, pathname: &str) -> MemoryRegion {
+    MemoryRegion { start, end, readable: true, writable: true, pathname: pathname.to_string(), ..MemoryRegion::default() }
+}
+
+fn header() -> CaptureHeader {
+    CaptureHeader { version: 1, pid: 4242, time: SystemTime::now(), arch: Arch::X86_64, executable: Some("/usr/bin/game".to_string()), stacks: Vec::new(), uncaptured: Vec::new() }
+}
+
+// A capture of the regions with every byte zero but those given, and the holes left unread
+fn capture(name: &str, regions: &[MemoryRegion], bytes: &[(usize, &[u8])], holes: &[(usize, usize)]) -> PathBuf {
+    let path = temp_path(name);
+    Capture::write(&path, &header(), Compression::Lz4, regions.to_vec(), |address, block| {
+        let end = address + block.len();
+        for (at, value) in bytes {
+            for (i, byte) in value.iter().enumerate().filter(|x| (address..end).contains(&(at + x.0))) {
+                block[at + i - address] = *byte;
+            }
+        }
+        Ok(holes.iter().copied().filter(|x| x.0 >= address && x.0 < end).collect())
+    }).unwrap();
+    path
+}
+
+#[test]
+fn lists_changed_ranges_by_region() {
+    let big = 0x100_0000 + 2 * CAPTURE_BLOCK_SIZE;
+    let seam = 0x100_0000 + CAPTURE_BLOCK_SIZE;
+    let before = capture("bytes-before", &[region(0x1000, 0x3000, "[heap]"), region(0x10000, 0x11000, ""), region(0x100_0000, big, "")], &[], &[]);
+    let after = capture("bytes-after", &[region(0x1000, 0x3000, "[heap]"), region(0x100_0000, big, ""), region(0x20000, 0x21000, "")], &[(0x1100, &[1, 2, 3, 4]), (0x2ff0, &[9]), (seam - 2, &[7; 4])], &[]);
+    let diff = diff_captures(&before, &after).unwrap();
+    assert_eq!(diff.changed.len(), 2);
+    assert_eq!((diff.changed[0].region.pathname.as_str(), diff.changed[0].ranges.clone()), ("[heap]", vec![(0x1100, 0x1104), (0x2ff0, 0x2ff1)]));
+    // A run across the seam between two blocks is one range
+    assert_eq!(diff.changed[1].ranges, vec![(seam - 2, seam + 2)]);
+    assert_eq!((diff.only_before[0].start, diff.only_after[0].start), (0x10000, 0x20000));
+    assert_eq!(diff.bytes_compared, 0x2000 + 2 * CAPTURE_BLOCK_SIZE);
+    for path in [before, after] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn finds_values_that_changed_by_a_delta() {
+    let regions = [region(0x1000, 0x3000, "[heap]")];
+    let before = capture("typed-before", &regions, &[(0x1200, &100i32.to_ne_bytes()), (0x1204, &100i32.to_ne_bytes()), (0x1301, &100i32.to_ne_bytes())], &[]);
+    let after = capture("typed-after", &regions, &[(0x1200, &50i32.to_ne_bytes()), (0x1204, &90i32.to_ne_bytes()), (0x1301, &50i32.to_ne_bytes())], &[]);
+    let by = |alignment| diff_captures_by::<i32>(&before, &after, alignment, Endianness::Native, |old, new| new - old == -50).unwrap().addresses().collect::<Vec<usize>>();
+    assert_eq!(by(4), vec![0x1200]);
+    // Unaligned, the value moved to an odd address is found as well
+    assert_eq!(by(1), vec![0x1200, 0x1301]);
+    // The aligned value at 0x1300 holds most of the one at 0x1301, so it went down as well
+    let decreased = diff_captures_by::<i32>(&before, &after, 4, Endianness::Native, |old, new| new < old).unwrap();
+    assert_eq!(decreased.addresses().collect::<Vec<usize>>(), vec![0x1200, 0x1204, 0x1300]);
+    for path in [before, after] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn skips_what_either_capture_could_not_read() {
+    let regions = [region(0x1000, 0x3000, "")];
+    let before = capture("holes-before", &regions, &[(0x1000, &[1]), (0x2000, &[1])], &[(0x1000, 0x1800)]);
+    let after = capture("holes-after", &regions, &[(0x2000, &[2])], &[]);
+    let diff = diff_captures(&before, &after).unwrap();
+    assert_eq!(diff.changed[0].ranges, vec![(0x2000, 0x2001)]);
+    assert_eq!(diff.bytes_compared, 0x1800);
+    for path in [before, after] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn the_session_loads_what_it_found() {
+    let regions = [region(0x1000, 0x3000, "[heap]")];
+    let before = capture("session-before", &regions, &[(0x1200, &100i32.to_ne_bytes())], &[]);
+    let after = capture("session-after", &regions, &[(0x1200, &50i32.to_ne_bytes()), (0x2000, &[5])], &[]);
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg("--offline").arg(&after).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    writeln!(session.stdin.take().unwrap(), "snapshot diff {} {} i32 by -50 --load\nlist\nsnapshot diff {} {}", before.display(), after.display(), before.display(), after.display()).unwrap();
+    let output = session.wait_with_output().unwrap();
+    let output = format!("{}{}", String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap());
+    assert!(output.contains("1 values in 1 regions"), "{}", output);
+    assert!(output.contains("(heap+0x200) 100 -> 50"), "{}", output);
+    assert!(output.contains("loaded 1 results as i32"), "{}", output);
+    assert!(output.contains("#0 0x1200 (heap+0x200) 50"), "{}", output);
+    assert!(output.contains("2 changed ranges in 1 regions"), "{}", output);
+    for path in [before, after] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
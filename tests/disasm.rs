@@ -0,0 +1,100 @@
+use memory::{disassemble, instruction_before};
+
+fn text(code: &[u8]) -> String {
+    disassemble(code, 0x1000).unwrap().text
+}
+
+#[test]
+fn names_common_instructions() {
+    let cases: [(&[u8], &str); 20] = [
+        (&[0x55], "push rbp"),
+        (&[0x48, 0x89, 0xe5], "mov rbp, rsp"),
+        (&[0x48, 0x83, 0xec, 0x48], "sub rsp, 0x48"),
+        (&[0x48, 0x83, 0xe0, 0xf0], "and rax, 0xfffffffffffffff0"),
+        (&[0x89, 0x7c, 0x24, 0x08], "mov dword ptr [rsp+0x8], edi"),
+        (&[0x8b, 0x45, 0xf8], "mov eax, dword ptr [rbp-0x8]"),
+        (&[0x48, 0x8d, 0x04, 0x8b], "lea rax, [rbx+rcx*4]"),
+        (&[0x42, 0x0f, 0xb6, 0x04, 0x21], "movzx eax, byte ptr [rcx+r12]"),
+        (&[0x40, 0x88, 0xf0], "mov al, sil"),
+        (&[0x88, 0xe0], "mov al, ah"),
+        (&[0x64, 0x48, 0x8b, 0x04, 0x25, 0x28, 0x00, 0x00, 0x00], "mov rax, qword ptr fs:[0x28]"),
+        (&[0x66, 0x41, 0xc7, 0x00, 0x34, 0x12], "mov word ptr [r8], 0x1234"),
+        (&[0x48, 0xb8, 0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11], "movabs rax, 0x1122334455667788"),
+        (&[0xf0, 0x48, 0x0f, 0xb1, 0x0a], "lock cmpxchg qword ptr [rdx], rcx"),
+        (&[0xf3, 0x48, 0xab], "rep stosq"),
+        (&[0xf3, 0x0f, 0x1e, 0xfa], "endbr64"),
+        (&[0x0f, 0x1f, 0x44, 0x00, 0x00], "nop dword ptr [rax+rax]"),
+        (&[0xf2, 0x0f, 0x59, 0xc1], "mulsd xmm0, xmm1"),
+        (&[0xf3, 0x0f, 0x10, 0x45, 0xfc], "movss xmm0, dword ptr [rbp-0x4]"),
+        (&[0x66, 0x0f, 0xef, 0xc0], "pxor xmm0, xmm0"),
+    ];
+    for (code, expected) in cases {
+        assert_eq!(text(code), expected, "{:02x?}", code);
+    }
+    assert_eq!(disassemble(&[0xc3, 0x90], 0x1000).unwrap().bytes, [0xc3]);
+}
+
+// Both are taken from the address after the instruction
+#[test]
+fn resolves_relative_targets() {
+    let call = disassemble(&[0xe8, 0xfb, 0xff, 0xff, 0xff], 0x1000).unwrap();
+    assert_eq!((call.text.as_str(), call.target), ("call 0x1000", Some(0x1000)));
+    let jump = disassemble(&[0x75, 0x10], 0x2000).unwrap();
+    assert_eq!((jump.text.as_str(), jump.target), ("jne 0x2012", Some(0x2012)));
+    let load = disassemble(&[0x48, 0x8b, 0x05, 0x00, 0x01, 0x00, 0x00], 0x3000).unwrap();
+    assert_eq!((load.text.as_str(), load.target), ("mov rax, qword ptr [rip+0x100]", Some(0x3107)));
+    assert_eq!(disassemble(&[0x48, 0x01, 0xd8], 0x1000).unwrap().target, None);
+}
+
+// Unnamed instructions keep their length, so what follows is still decoded; invalid and cut
+// short ones are errors
+#[test]
+fn degrades_on_what_it_cannot_name() {
+    let fpu = disassemble(&[0xd9, 0xc9, 0x90], 0x1000).unwrap();
+    assert_eq!((fpu.text.as_str(), fpu.bytes.len()), ("(unknown)", 2));
+    assert!(disassemble(&[0x06], 0x1000).is_err());
+    assert!(disassemble(&[0x48, 0x8b], 0x1000).is_err());
+    assert!(disassemble(&[], 0x1000).is_err());
+}
+
+#[test]
+fn finds_the_instruction_before_an_address() {
+    // push rbp; mov rbp, rsp; mov dword ptr [rbp-0x8], eax; ret
+    let code = [0x55, 0x48, 0x89, 0xe5, 0x89, 0x45, 0xf8, 0xc3];
+    let found = instruction_before(&code, 0x1000, 0x1007).unwrap();
+    assert_eq!((found.address, found.text.as_str()), (0x1004, "mov dword ptr [rbp-0x8], eax"));
+    assert_eq!(instruction_before(&code, 0x1000, 0x1001).unwrap().text, "push rbp");
+    assert!(instruction_before(&code, 0x1000, 0x1000).is_none());
+    assert!(instruction_before(&code, 0x1000, 0x1009).is_none());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn the_session_disassembles_the_target() {
+    use std::{io::{BufRead, BufReader, Write}, process::{Command, Stdio}};
+    let mut child = Command::new(env!("CARGO_BIN_EXE_bench_target")).args(["1", "tick"]).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    // Every line is read before the pipe is closed, or the last one fails to be written
+    let lines = BufReader::new(child.stdout.take().unwrap()).lines().take(4).map(|x| x.unwrap()).collect::<Vec<String>>();
+    let address = |name: &str| lines.iter().find_map(|x| usize::from_str_radix(x.strip_prefix(name)?.strip_prefix(" 0x")?, 16).ok()).unwrap();
+    let (tick, player) = (address("tick"), address("player"));
+    let modules = memory::modules(nix::unistd::Pid::from_raw(child.id() as i32)).unwrap();
+    // The end of one of the module's mappings with nothing readable right after it
+    let main = modules.iter().find(|x| x.name == "bench_target").unwrap();
+    let regions = memory::get_memory_regions(nix::unistd::Pid::from_raw(child.id() as i32)).unwrap();
+    let end = regions.iter().filter(|x| x.readable).map(|x| x.end).find(|&end| main.contains(end - 1) && !regions.iter().any(|x| x.readable && x.start == end)).unwrap();
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(child.id().to_string()).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    let mut stdin = session.stdin.take().unwrap();
+    writeln!(stdin, "disasm 0x{:x} 5\ndisasm 0x{:x} 20\nwatchwrite 0x{:x} 4", tick, end - 4, player).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    drop(stdin);
+    let output = session.wait_with_output().unwrap();
+    let _ = child.kill();
+    let _ = child.wait();
+    let (stdout, stderr) = (String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap());
+    let listing = stdout.lines().skip_while(|x| !x.contains(&format!("0x{:x}  ", tick))).take(5).collect::<Vec<&str>>();
+    assert_eq!(listing.len(), 5, "{}", stdout);
+    assert!(!listing.iter().any(|x| x.contains("(bad")), "{}", stdout);
+    assert!(stdout.contains(&format!("0x{:x}  cannot be read\n", end)), "{}", stdout);
+    // The write to hp, with its operands, in place of the bytes around rip
+    assert!(stderr.lines().any(|x| x.starts_with("    0x") && x.contains("mov dword ptr [")), "{}", stderr);
+}
@@ -0,0 +1,78 @@
+use std::{io::Write, process::{Command, Stdio}};
+use memory::{DisplayFormat, Encoding, Radix, TypedValue};
+
+fn shown(value: TypedValue, radix: Radix) -> String {
+    value.format_as(DisplayFormat { radix, precision: None })
+}
+
+#[test]
+fn hex_and_binary_are_padded_to_the_type() {
+    assert_eq!(shown(TypedValue::U8(10), Radix::Hex), "0x0a");
+    assert_eq!(shown(TypedValue::I32(31337), Radix::Hex), "0x00007a69");
+    assert_eq!(shown(TypedValue::U64(1), Radix::Hex), "0x0000000000000001");
+    assert_eq!(shown(TypedValue::U8(10), Radix::Binary), "0b0000_1010");
+    assert_eq!(shown(TypedValue::U16(0x7a69), Radix::Binary), "0b0111_1010_0110_1001");
+    assert_eq!(shown(TypedValue::I32(31337), Radix::Both), "31337 (0x00007a69)");
+}
+
+// Negative numbers are shown as their two's complement, and either signedness can be viewed as
+// the other
+#[test]
+fn signedness_is_a_view_of_the_same_bits() {
+    assert_eq!(shown(TypedValue::I8(-1), Radix::Hex), "0xff");
+    assert_eq!(shown(TypedValue::I16(-2), Radix::Binary), "0b1111_1111_1111_1110");
+    assert_eq!(shown(TypedValue::I8(-1), Radix::Unsigned), "255");
+    assert_eq!(shown(TypedValue::U8(255), Radix::Signed), "-1");
+    assert_eq!(shown(TypedValue::U32(4294967196), Radix::Signed), "-100");
+    assert_eq!(shown(TypedValue::I64(-100), Radix::Signed), "-100");
+    assert_eq!(shown(TypedValue::U64(u64::MAX), Radix::Unsigned), u64::MAX.to_string());
+}
+
+#[test]
+fn floats_take_a_precision_and_show_their_bits_in_hex() {
+    let format = |radix, precision| DisplayFormat { radix, precision };
+    assert_eq!(TypedValue::F32(1.5).format_as(format(Radix::Decimal, None)), "1.5");
+    assert_eq!(TypedValue::F32(1.5).format_as(format(Radix::Decimal, Some(3))), "1.500");
+    assert_eq!(TypedValue::F64(2.0 / 3.0).format_as(format(Radix::Decimal, Some(2))), "0.67");
+    assert_eq!(TypedValue::F32(1.5).format_as(format(Radix::Hex, Some(3))), "0x3fc00000");
+    assert_eq!(TypedValue::F64(-2.0).format_as(format(Radix::Both, Some(1))), "-2.0 (0xc000000000000000)");
+    // Floats have no other signedness, and integers no decimal places
+    assert_eq!(TypedValue::F32(-1.5).format_as(format(Radix::Unsigned, None)), "-1.5");
+    assert_eq!(TypedValue::I32(7).format_as(format(Radix::Decimal, Some(3))), "7");
+}
+
+#[test]
+fn bytes_and_strings_are_shown_as_they_are() {
+    for radix in [Radix::Hex, Radix::Binary, Radix::Both] {
+        assert_eq!(shown(TypedValue::Bytes(vec![0xde, 0xad]), radix), "de ad");
+        assert_eq!(shown(TypedValue::Str { text: "gold".to_string(), encoding: Encoding::Utf8, null_terminate: false }, radix), "gold");
+    }
+}
+
+#[test]
+fn formats_parse_by_name() {
+    for radix in [Radix::Decimal, Radix::Signed, Radix::Unsigned, Radix::Hex, Radix::Binary, Radix::Both] {
+        assert_eq!(radix.to_string().parse::<Radix>().unwrap(), radix);
+    }
+    assert_eq!("binary".parse::<Radix>().unwrap(), Radix::Binary);
+    assert!("octal".parse::<Radix>().unwrap_err().contains("expected dec, signed, unsigned, hex, bin or both"));
+}
+
+// --fmt applies to the one listing, set format to every one after it
+#[test]
+fn the_cli_lists_in_the_format_asked_for() {
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).args(["--offline", "tests/fixtures/scan_before.cap"]).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut stdin = session.stdin.take().unwrap();
+    writeln!(stdin, "scan i32 31337\nlist --fmt hex\nlist u16 --fmt bin\nset format both\nlist\nlist --fmt dec\nlist --fmt octal\nlist i32 u16").unwrap();
+    drop(stdin);
+    let output = session.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let listed = stdout.lines().map(|x| x.trim_start_matches("> ")).filter(|x| x.starts_with("#0 ")).collect::<Vec<&str>>();
+    assert_eq!(listed, [
+        "#0 game+0x4010 (0x404010) 0x00007a69 [rw-p]",
+        "#0 game+0x4010 (0x404010) 0b0111_1010_0110_1001 [rw-p]",
+        "#0 game+0x4010 (0x404010) 31337 (0x00007a69) [rw-p]",
+        "#0 game+0x4010 (0x404010) 31337 [rw-p]",
+    ], "{}", stdout);
+    assert!(stdout.contains("error: Unknown format 'octal'") && stdout.contains("error: Usage: list [<type>] [--starred] [--fmt <format>]"), "{}", stdout);
+}
@@ -0,0 +1,58 @@
+mod common;
+use memory::{MOCK_PAGE_SIZE, MockProcess, dump_to_file};
+
+const HEAP: usize = 0x10_0000;
+
+fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("rmh-dump-{}-{}", std::process::id(), name))
+}
+
+// One byte per address, with the unreadable middle page written as zeroes and returned as a hole
+#[test]
+fn dumps_what_it_can_and_records_the_holes() {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", HEAP, HEAP + 3 * MOCK_PAGE_SIZE)).unwrap();
+    mock.plant(HEAP + 0x10, &[1, 2, 3]).unwrap();
+    mock.plant(HEAP + MOCK_PAGE_SIZE + 8, &[4]).unwrap();
+    mock.plant(HEAP + 3 * MOCK_PAGE_SIZE - 1, &[5]).unwrap();
+    mock.mark_unreadable(HEAP + MOCK_PAGE_SIZE, HEAP + 2 * MOCK_PAGE_SIZE).unwrap();
+    let path = temp_path("holes");
+    let holes = dump_to_file(&mock, HEAP + 0x10, HEAP + 3 * MOCK_PAGE_SIZE, &path).unwrap();
+    let dumped = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(holes, [(HEAP + MOCK_PAGE_SIZE, HEAP + 2 * MOCK_PAGE_SIZE)]);
+    assert_eq!(dumped.len(), 3 * MOCK_PAGE_SIZE - 0x10);
+    assert_eq!((&dumped[..3], dumped[3 * MOCK_PAGE_SIZE - 0x11]), (&[1, 2, 3][..], 5));
+    assert!(dumped[MOCK_PAGE_SIZE - 0x10..2 * MOCK_PAGE_SIZE - 0x10].iter().all(|x| *x == 0));
+    // Nothing readable at all is still a file of the range's size
+    let holes = dump_to_file(&mock, HEAP + MOCK_PAGE_SIZE, HEAP + MOCK_PAGE_SIZE + 0x100, &path).unwrap();
+    assert_eq!((holes, std::fs::metadata(&path).unwrap().len()), (vec![(HEAP + MOCK_PAGE_SIZE, HEAP + MOCK_PAGE_SIZE + 0x100)], 0x100));
+    std::fs::remove_file(&path).unwrap();
+}
+
+// The victim's player is on the heap, so dumping its region has the hp at its offset into it
+#[cfg(target_os = "linux")]
+#[test]
+fn the_cli_dumps_a_whole_region() {
+    use std::{io::{BufRead, BufReader, Write}, process::{Command, Stdio}};
+    let mut victim = Command::new(common::victim_path()).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut lines = BufReader::new(victim.stdout.take().unwrap()).lines();
+    let pid = lines.next().unwrap().unwrap().strip_prefix("pid ").unwrap().to_string();
+    let player = lines.next().unwrap().unwrap();
+    let player = usize::from_str_radix(player.strip_prefix("player 0x").unwrap().split_once(' ').unwrap().0, 16).unwrap();
+    let path = temp_path("region");
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(&pid).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    writeln!(session.stdin.take().unwrap(), "dump region 0x{:x} {}\ndump 0x{:x} 4 {}", player, path.display(), player, path.display()).unwrap();
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    let _ = victim.kill();
+    let _ = victim.wait();
+    let dumped = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let line = stdout.lines().find(|x| x.contains("dumped 0x")).unwrap_or_else(|| panic!("{}", stdout));
+    let (start, end) = line.split_once("dumped 0x").unwrap().1.split_once(' ').unwrap().0.split_once("-0x").unwrap();
+    let (start, end) = (usize::from_str_radix(start, 16).unwrap(), usize::from_str_radix(end, 16).unwrap());
+    assert!(start <= player && player < end, "{}", stdout);
+    // The second dump overwrote the file with the four bytes of hp
+    assert_eq!(dumped, 100i32.to_ne_bytes());
+    assert!(stdout.contains(&format!("dumped 0x{:x}-0x{:x} (4 B) to {}", player, player + 4, path.display())), "{}", stdout);
+}
@@ -0,0 +1,174 @@
+use std::{io::Write, process::{Command, Stdio}, sync::Mutex};
+use memory::{Endianness, MemoryRegion, ProcessMemory, ScanOptions, TypedValue, ValueType, find_value, read_scalar, reduce_found_values, strip_endianness, write_scalar, write_typed};
+use nix::unistd::Pid;
+
+const BASE: usize = 0x10000;
+
+// A single read-write region held in a buffer, standing in for a target's memory
+struct Buffer(Mutex<Vec<u8>>);
+
+impl Buffer {
+    fn new(bytes: &[u8]) -> Buffer {
+        Buffer(Mutex::new(bytes.to_vec()))
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl ProcessMemory for Buffer {
+    fn pid(&self) -> Pid {
+        Pid::from_raw(0)
+    }
+
+    fn read_at(&self, address: usize, buffer: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        let bytes = self.0.lock().unwrap();
+        let offset = address.checked_sub(BASE).filter(|x| *x < bytes.len()).ok_or("unmapped")?;
+        let len = buffer.len().min(bytes.len() - offset);
+        buffer[..len].copy_from_slice(&bytes[offset..offset + len]);
+        Ok(len)
+    }
+
+    fn write_at(&self, address: usize, data: &[u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut bytes = self.0.lock().unwrap();
+        let offset = address.checked_sub(BASE).filter(|x| *x < bytes.len()).ok_or("unmapped")?;
+        let len = data.len().min(bytes.len() - offset);
+        bytes[offset..offset + len].copy_from_slice(&data[..len]);
+        Ok(len)
+    }
+
+    fn memory_regions(&self) -> Result<Vec<MemoryRegion>, Box<dyn std::error::Error>> {
+        let len = self.0.lock().unwrap().len();
+        Ok(vec![format!("{:x}-{:x} rw-p 00000000 00:00 0", BASE, BASE + len).parse()?])
+    }
+
+    fn executable_path(&self) -> Option<String> {
+        None
+    }
+
+    fn thread_stacks(&self) -> Vec<(Pid, usize)> {
+        Vec::new()
+    }
+}
+
+// 0x01020304 at offset 0 big-endian, at offset 8 little-endian, and 1.5f32 big-endian at offset 16
+fn mixed_buffer() -> Buffer {
+    let mut bytes = vec![0u8; 32];
+    bytes[0..4].copy_from_slice(&[0x01, 0x02, 0x03, 0x04]);
+    bytes[8..12].copy_from_slice(&[0x04, 0x03, 0x02, 0x01]);
+    bytes[16..20].copy_from_slice(&[0x3f, 0xc0, 0x00, 0x00]);
+    Buffer::new(&bytes)
+}
+
+#[test]
+fn reads_in_either_byte_order() {
+    let buffer = mixed_buffer();
+    assert_eq!(read_scalar::<u32>(&buffer, BASE, Endianness::Big).unwrap(), 0x01020304);
+    assert_eq!(read_scalar::<u32>(&buffer, BASE + 8, Endianness::Little).unwrap(), 0x01020304);
+    assert_eq!(read_scalar::<u32>(&buffer, BASE + 8, Endianness::Big).unwrap(), 0x04030201);
+    assert_eq!(read_scalar::<u16>(&buffer, BASE, Endianness::Big).unwrap(), 0x0102);
+    assert_eq!(read_scalar::<f32>(&buffer, BASE + 16, Endianness::Big).unwrap(), 1.5);
+    let native = if cfg!(target_endian = "little") { BASE + 8 } else { BASE };
+    assert_eq!(read_scalar::<u32>(&buffer, native, Endianness::Native).unwrap(), 0x01020304);
+}
+
+#[test]
+fn writes_in_either_byte_order() {
+    let buffer = Buffer::new(&[0; 16]);
+    write_scalar(&buffer, BASE, 0x0102u16, Endianness::Big).unwrap();
+    write_scalar(&buffer, BASE + 2, 0x0102u16, Endianness::Little).unwrap();
+    write_scalar(&buffer, BASE + 4, -2.0f64, Endianness::Big).unwrap();
+    assert_eq!(buffer.bytes()[..12], [0x01, 0x02, 0x02, 0x01, 0xc0, 0, 0, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn typed_writes_swap_numbers_and_utf16_but_not_bytes_or_utf8() {
+    let buffer = Buffer::new(&[0; 16]);
+    for (text, expected) in [
+        ("i32:1", vec![0, 0, 0, 1]),
+        ("u64:258", vec![0, 0, 0, 0, 0, 0, 1, 2]),
+        ("bytes:01 02", vec![1, 2]),
+        ("str:ab", b"ab".to_vec()),
+        ("utf16z:ab", vec![0, b'a', 0, b'b', 0, 0]),
+    ] {
+        write_typed(&buffer, BASE, &text.parse::<TypedValue>().unwrap(), Endianness::Big).unwrap();
+        assert_eq!(buffer.bytes()[..expected.len()], expected[..], "{}", text);
+        let value_type = text.split(':').next().unwrap().parse::<ValueType>().unwrap();
+        assert_eq!(value_type.value_from_bytes_in(&expected, Endianness::Big).unwrap().to_string(), text);
+    }
+}
+
+#[test]
+fn scans_and_reduces_in_the_chosen_byte_order() {
+    let buffer = mixed_buffer();
+    let big = ScanOptions { endianness: Endianness::Big, ..ScanOptions::default() };
+    let little = ScanOptions { endianness: Endianness::Little, ..ScanOptions::default() };
+    assert_eq!(find_value(&buffer, 0x01020304u32, &big).unwrap().0, [BASE]);
+    assert_eq!(find_value(&buffer, 0x01020304u32, &little).unwrap().0, [BASE + 8]);
+    assert_eq!(find_value(&buffer, 1.5f32, &big).unwrap().0, [BASE + 16]);
+
+    let (mut found, mut stats) = find_value(&buffer, 0x01020304u32, &big).unwrap();
+    reduce_found_values(&buffer, &mut found, 0x01020304u32, &big, &mut stats).unwrap();
+    assert_eq!(found, [BASE]);
+    write_scalar(&buffer, BASE, 7u32, Endianness::Big).unwrap();
+    reduce_found_values(&buffer, &mut found, 7u32, &big, &mut stats).unwrap();
+    assert_eq!(found, [BASE]);
+    reduce_found_values(&buffer, &mut found, 7u32, &little, &mut stats).unwrap();
+    assert!(found.is_empty());
+}
+
+#[test]
+fn negative_zero_matches_zero_in_both_orders() {
+    let buffer = Buffer::new(&[0x80, 0, 0, 0, 0, 0, 0, 0x80]);
+    let big = ScanOptions { endianness: Endianness::Big, alignment: Some(4), ..ScanOptions::default() };
+    let little = ScanOptions { endianness: Endianness::Little, alignment: Some(4), ..ScanOptions::default() };
+    assert_eq!(find_value(&buffer, 0.0f32, &big).unwrap().0, [BASE]);
+    assert_eq!(find_value(&buffer, 0.0f32, &little).unwrap().0, [BASE + 4]);
+}
+
+#[test]
+fn parses_byte_order_prefixes() {
+    assert_eq!(strip_endianness("be:i32:999"), (Some(Endianness::Big), "i32:999"));
+    assert_eq!(strip_endianness("le:999"), (Some(Endianness::Little), "999"));
+    assert_eq!(strip_endianness("i32:999"), (None, "i32:999"));
+    assert_eq!(strip_endianness("999"), (None, "999"));
+    assert_eq!(strip_endianness("str:be:x"), (None, "str:be:x"));
+    for endianness in [Endianness::Native, Endianness::Little, Endianness::Big] {
+        assert_eq!(endianness.to_string().parse::<Endianness>(), Ok(endianness));
+    }
+    assert!("middle".parse::<Endianness>().is_err());
+}
+
+#[test]
+fn defaults_to_native() {
+    assert_eq!(ScanOptions::default().endianness, Endianness::Native);
+    assert!(Endianness::default().is_native());
+    assert_ne!(Endianness::Little.is_native(), Endianness::Big.is_native());
+}
+
+// The capture holds 31337 as little-endian bytes 69 7a 00 00, which big-endian is 1769603072
+fn offline(commands: &str) -> String {
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).args(["--offline", "tests/fixtures/scan_before.cap"]).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    writeln!(session.stdin.take().unwrap(), "{}", commands).unwrap();
+    String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap()
+}
+
+// --endian is for that scan alone, and its results are rescanned and listed in the same order
+#[test]
+fn a_scan_can_be_in_its_own_byte_order() {
+    let stdout = offline("scan i32 1769603072 --endian be\nrescan 1769603072\nlist\nscan struct +0 i32 1769603072, +4 i32 0 --endian be\nrescan struct");
+    assert_eq!(stdout.matches(", 5 matches").count() + stdout.matches("> 5 matches").count(), 2, "{}", stdout);
+    assert!(stdout.contains("#0 game+0x4010 (0x404010) 1769603072 [rw-p]"), "{}", stdout);
+    assert_eq!(stdout.matches(" 1 matches").count(), 2, "{}", stdout);
+    // And the session goes on in its own order
+    assert!(offline("scan i32 31337 --endian be\nscan i32 31337").contains(", 5 matches"));
+}
+
+#[test]
+fn the_prompt_shows_an_order_that_is_not_native() {
+    let stdout = offline("endian be\nendian native\nscan i32 1769603072 --endian be\nscan i32 5 --endian sideways");
+    assert!(stdout.contains("\n[be] > ") || stdout.contains("> [be] > "), "{}", stdout);
+    assert!(stdout.contains("[results be] > "), "{}", stdout);
+    assert!(stdout.contains("error: Unknown byte order 'sideways'"), "{}", stdout);
+}
@@ -0,0 +1,79 @@
+#![cfg(feature = "ffi")]
+use std::time::Duration;
+use memory::ffi::{RMH_ERR_BAD_ADDRESS, RMH_ERR_INVALID, RMH_ERR_NO_PROCESS, RMH_ERR_NOT_LOCKED, RMH_OK, RmhHandle, rmh_attach, rmh_detach, rmh_lock, rmh_read, rmh_scan_i32, rmh_unlock, rmh_write};
+
+fn attach_self() -> *mut RmhHandle {
+    let mut error = 1;
+    let handle = unsafe { rmh_attach(std::process::id() as i32, &mut error) };
+    assert_eq!(error, RMH_OK);
+    assert!(!handle.is_null());
+    handle
+}
+
+// Written by the library behind the compiler's back, so only touched through volatile reads and writes
+fn planted(value: i32) -> *mut i32 {
+    Box::into_raw(Box::new(value))
+}
+
+#[test]
+fn reports_why_attaching_failed() {
+    let mut error = 0;
+    assert!(unsafe { rmh_attach(i32::MAX, &mut error) }.is_null());
+    assert_eq!(error, RMH_ERR_NO_PROCESS);
+    assert!(unsafe { rmh_attach(0, std::ptr::null_mut()) }.is_null());
+    unsafe { rmh_detach(std::ptr::null_mut()) };
+}
+
+#[test]
+fn reads_and_writes_through_a_handle() {
+    let handle = attach_self();
+    let value = planted(5);
+    let mut bytes = [0u8; 4];
+    unsafe {
+        assert_eq!(rmh_read(handle, value as usize, bytes.as_mut_ptr(), 4), 4);
+        assert_eq!(i32::from_ne_bytes(bytes), 5);
+        assert_eq!(rmh_write(handle, value as usize, 9i32.to_ne_bytes().as_ptr(), 4), 4);
+        assert_eq!(std::ptr::read_volatile(value), 9);
+        assert_eq!(rmh_read(handle, 16, bytes.as_mut_ptr(), 4), RMH_ERR_BAD_ADDRESS as isize);
+        assert_eq!(rmh_read(handle, value as usize, std::ptr::null_mut(), 4), RMH_ERR_INVALID as isize);
+        assert_eq!(rmh_read(std::ptr::null(), value as usize, bytes.as_mut_ptr(), 4), RMH_ERR_INVALID as isize);
+        rmh_detach(handle);
+    }
+}
+
+#[test]
+fn scans_into_a_caller_buffer() {
+    let handle = attach_self();
+    let value = planted(0x2468_1357);
+    let mut found = vec![0usize; 4096];
+    unsafe {
+        let total = rmh_scan_i32(handle, 0x2468_1357, found.as_mut_ptr(), found.len());
+        assert!(total >= 1);
+        assert!(found[..(total as usize).min(found.len())].contains(&(value as usize)));
+        assert!(rmh_scan_i32(handle, 0x2468_1357, std::ptr::null_mut(), 0) >= 1);
+        rmh_detach(handle);
+    }
+}
+
+#[test]
+fn locks_from_several_threads_at_once() {
+    let handle = attach_self() as usize;
+    let values = (0..4).map(|_| planted(0) as usize).collect::<Vec<usize>>();
+    std::thread::scope(|scope| {
+        for (i, value) in values.iter().enumerate() {
+            scope.spawn(move || unsafe {
+                assert_eq!(rmh_lock(handle as *const RmhHandle, *value, (i as i32).to_ne_bytes().as_ptr(), 4, 5), RMH_OK);
+            });
+        }
+    });
+    std::thread::sleep(Duration::from_millis(50));
+    for (i, value) in values.iter().enumerate() {
+        assert_eq!(unsafe { std::ptr::read_volatile(*value as *const i32) }, i as i32);
+    }
+    unsafe {
+        assert_eq!(rmh_unlock(handle as *const RmhHandle, values[0]), RMH_OK);
+        assert_eq!(rmh_unlock(handle as *const RmhHandle, values[0]), RMH_ERR_NOT_LOCKED);
+        assert_eq!(rmh_lock(handle as *const RmhHandle, values[0], std::ptr::null(), 0, 5), RMH_ERR_INVALID);
+        rmh_detach(handle as *mut RmhHandle);
+    }
+}
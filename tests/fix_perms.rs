@@ -0,0 +1,83 @@
+#![cfg(target_os = "linux")]
+use std::{ffi::OsString, os::unix::fs::PermissionsExt, path::{Path, PathBuf}, process::{Command, Stdio}};
+use memory::{PermissionFix, PreflightCheck, find_in_path, permission_fixes};
+
+// A directory holding an executable file for each name, to stand in for a PATH
+fn path_with(name: &str, programs: &[&str]) -> (PathBuf, OsString) {
+    let directory = std::env::temp_dir().join(format!("rmh-fix-perms-{}-{}", std::process::id(), name));
+    std::fs::create_dir_all(&directory).unwrap();
+    for program in programs {
+        let file = directory.join(program);
+        std::fs::write(&file, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    let path = std::env::join_paths([directory.clone()]).unwrap();
+    (directory, path)
+}
+
+fn check(name: &'static str, passed: bool, detail: &str) -> PreflightCheck {
+    PreflightCheck { name, passed, detail: detail.to_string(), suggestion: None }
+}
+
+fn scope_failed() -> Vec<PreflightCheck> {
+    vec![check("process", true, "123 (game)"), check("ptrace_scope", false, "1 (only descendants can be traced, and the target is not one)")]
+}
+
+fn arguments() -> Vec<String> {
+    ["123", "--fix-perms", "--journal", "moves.log"].map(String::from).to_vec()
+}
+
+// The command line comes back whole but for --fix-perms, so a failed fix does not ask again
+#[test]
+fn sudo_runs_the_same_command_again() {
+    let (directory, path) = path_with("sudo", &["sudo", "pkexec"]);
+    let fixes = permission_fixes(&scope_failed(), Path::new("/opt/memory"), &arguments(), Path::new("/home/me"), false, Some(&path)).unwrap();
+    let sudo = directory.join("sudo");
+    assert_eq!(fixes, [
+        PermissionFix::Reexec { program: sudo.clone(), arguments: ["--", "/opt/memory", "123", "--journal", "moves.log"].map(String::from).to_vec() },
+        PermissionFix::Setcap { program: sudo, arguments: ["--", "setcap", "cap_sys_ptrace+ep", "/opt/memory"].map(String::from).to_vec() },
+    ]);
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+// pkexec starts in root's home, which would leave moves.log somewhere else
+#[test]
+fn pkexec_goes_back_to_the_directory() {
+    let (directory, path) = path_with("pkexec", &["pkexec"]);
+    let fixes = permission_fixes(&scope_failed(), Path::new("/opt/memory"), &arguments(), Path::new("/home/me"), false, Some(&path)).unwrap();
+    let PermissionFix::Reexec { program, arguments } = &fixes[0] else { panic!("{:?}", fixes) };
+    assert_eq!(program, &directory.join("pkexec"));
+    assert_eq!(arguments[..4], ["/bin/sh", "-c", "cd \"$0\" && exec \"$@\"", "/home/me"]);
+    assert_eq!(arguments[4..], ["/opt/memory", "123", "--journal", "moves.log"]);
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn nothing_is_offered_that_would_not_help() {
+    let (directory, path) = path_with("none", &["sudo"]);
+    let fixes = |checks: &[PreflightCheck], root: bool, path: &OsString| permission_fixes(checks, Path::new("/opt/memory"), &arguments(), Path::new("/"), root, Some(path));
+    assert_eq!(fixes(&[check("process", true, "123")], false, &path), Ok(Vec::new()));
+    assert!(fixes(&scope_failed(), true, &path).unwrap_err().starts_with("this already runs as root"));
+    assert!(fixes(&[check("process", false, "no process 123")], false, &path).unwrap_err().starts_with("there is no process"));
+    assert!(fixes(&[check("ptrace_scope", false, "3 (nothing can be traced)")], false, &path).unwrap_err().starts_with("ptrace_scope 3"));
+    assert!(fixes(&scope_failed(), false, &OsString::new()).unwrap_err().starts_with("neither sudo nor pkexec"));
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+// Only files that can be run count
+#[test]
+fn the_path_is_searched_for_executables() {
+    let (directory, path) = path_with("search", &["sudo"]);
+    std::fs::write(directory.join("pkexec"), "").unwrap();
+    assert_eq!(find_in_path("sudo", Some(&path)), Some(directory.join("sudo")));
+    assert_eq!(find_in_path("pkexec", Some(&path)), None);
+    assert_eq!(find_in_path("sudo", None), None);
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn the_cli_only_fixes_attaching() {
+    let output = Command::new(env!("CARGO_BIN_EXE_memory")).args(["--offline", "tests/fixtures/scan_before.cap", "--fix-perms"]).stdin(Stdio::null()).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--fix-perms is for attaching to a process, so it needs a pid"), "{:?}", output);
+}
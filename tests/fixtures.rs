@@ -0,0 +1,282 @@
+use std::{path::{Path, PathBuf}, time::{Duration, UNIX_EPOCH}};
+use memory::{Capture, CaptureHeader, Compression, MemoryRegion, MockProcess, OfflineCapture, ProcessMemory, ScanOptions, Snapshot, TypedValue, UnknownScan, find_value, find_value_by_predicate, find_value_generic, read_bytes_into};
+use serde::{Deserialize, Serialize};
+
+// Scans run over the captures in tests/fixtures, which are images of a made-up process with values
+// planted at known places: at the start and end of regions, across the seam between two scan
+// chunks, across the boundary between two adjacent regions, and at unaligned offsets. Each test
+// asserts the hits exactly, so a change to what a scan finds there shows up as a failure rather
+// than going unnoticed. The fixtures and their manifest are written by plant_fixtures below; after
+// changing it, run `REGENERATE_FIXTURES=1 cargo test --test fixtures` and commit the result
+const EXECUTABLE: usize = 0x40_0000;
+const DATA: usize = 0x40_4000;
+const GUARD: usize = 0x40_6000;
+// Two mebibytes and a bit, so a scan splits it into chunks with seams at 0x110_0000 and 0x120_0000
+const HEAP: usize = 0x100_0000;
+const ANON: usize = 0x121_0000;
+const STACK: usize = 0x7ffd_0000;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct Manifest {
+    fixtures: Vec<Fixture>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct Fixture {
+    file: String,
+    maps: Vec<String>,
+    planted: Vec<Planted>,
+}
+
+// A value in the form TypedValue parses, as "i32:31337"
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct Planted {
+    name: String,
+    address: String,
+    value: String,
+}
+
+impl Manifest {
+    fn load() -> Manifest {
+        serde_json::from_str(&std::fs::read_to_string(fixtures().join("manifest.json")).unwrap()).unwrap()
+    }
+
+    fn fixture(&self, file: &str) -> &Fixture {
+        self.fixtures.iter().find(|x| x.file == file).unwrap()
+    }
+}
+
+impl Fixture {
+    fn open(&self) -> OfflineCapture {
+        OfflineCapture::open(&fixtures().join(&self.file)).unwrap()
+    }
+
+    // Where each of the named values was planted, in the order given
+    fn at(&self, names: &[&str]) -> Vec<usize> {
+        names.iter().map(|name| {
+            let planted = self.planted.iter().find(|x| x.name == *name).unwrap_or_else(|| panic!("{} is not in the manifest", name));
+            usize::from_str_radix(planted.address.trim_start_matches("0x"), 16).unwrap()
+        }).collect()
+    }
+}
+
+fn fixtures() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+// The layout both fixtures share. Everything but the executable's code and the stack is zeroes
+// until something is planted in it
+const MAPS: [&str; 6] = [
+    "400000-404000 r-xp 00000000 08:01 1234 /usr/bin/game",
+    "404000-406000 rw-p 00004000 08:01 1234 /usr/bin/game",
+    "406000-407000 ---p 00000000 00:00 0",
+    "1000000-1210000 rw-p 00000000 00:00 0 [heap]",
+    "1210000-1220000 rw-p 00000000 00:00 0",
+    "7ffd0000-7ffd8000 rw-p 00000000 00:00 0 [stack]",
+];
+
+// The values in the first fixture. The second has the same but for `after`
+fn planted() -> Vec<(&'static str, usize, TypedValue)> {
+    let i32 = TypedValue::I32;
+    vec![
+        // Exact: aligned, unaligned, the last offset in a region, across a chunk seam and across the
+        // boundary between adjacent regions
+        ("score", DATA + 0x10, i32(31337)),
+        ("score_unaligned", DATA + 0x101, i32(31337)),
+        ("data_last", GUARD - 4, i32(31337)),
+        ("seam", HEAP + 0x10_0000 - 2, i32(31337)),
+        ("heap_boundary", ANON - 2, i32(31337)),
+        ("anon_last", ANON + 0x10000 - 4, i32(31337)),
+        ("gold", HEAP + 0x2000, TypedValue::I64(0x0123_4567_89ab_cdef)),
+        ("gold_half_aligned", HEAP + 0x2014, TypedValue::I64(0x0123_4567_89ab_cdef)),
+        // A range of 1000 to 2000, and a value either side of it
+        ("below_range", HEAP + 0x3000, i32(999)),
+        ("range_low", HEAP + 0x3004, i32(1000)),
+        ("range_middle", HEAP + 0x3009, i32(1500)),
+        ("range_high", HEAP + 0x3010, i32(2000)),
+        ("above_range", HEAP + 0x3014, i32(2001)),
+        // Within a quarter of 99.5, which each of these is exactly representable as
+        ("float_low", HEAP + 0x4000, TypedValue::F32(99.25)),
+        ("float", HEAP + 0x4004, TypedValue::F32(99.5)),
+        ("float_high", DATA + 0x1003, TypedValue::F32(99.75)),
+        ("float_outside", HEAP + 0x400c, TypedValue::F32(100.0)),
+        ("float_negative", HEAP + 0x4010, TypedValue::F32(-99.5)),
+        ("float_nan", HEAP + 0x4014, TypedValue::F32(f32::NAN)),
+        // Strings, UTF-16 included, and byte patterns with a near miss
+        ("name", DATA + 0x200, TypedValue::Bytes(b"Player One".to_vec())),
+        ("name_copy", HEAP + 0x5001, TypedValue::Bytes(b"Player One".to_vec())),
+        ("name_truncated", HEAP + 0x5100, TypedValue::Bytes(b"Player On".to_vec())),
+        ("wide_name", HEAP + 0x5200, TypedValue::Bytes("Hero".encode_utf16().flat_map(|x| x.to_le_bytes()).collect())),
+        ("pattern", HEAP + 0x6003, TypedValue::Bytes(vec![0xde, 0xad, 0xbe, 0xef, 0x00, 0xff])),
+        ("pattern_miss", HEAP + 0x6103, TypedValue::Bytes(vec![0xde, 0xad, 0xbe, 0xee, 0x00, 0xff])),
+        ("pattern_across_seam", HEAP + 0x20_0000 - 3, TypedValue::Bytes(vec![0xde, 0xad, 0xbe, 0xef, 0x00, 0xff])),
+        // Changed between one fixture and the next, see after
+        ("timer", HEAP + 0x7000, TypedValue::U32(10)),
+        ("ammo", HEAP + 0x7008, TypedValue::U32(30)),
+        ("lives", HEAP + 0x7010, TypedValue::U32(3)),
+    ]
+}
+
+// What the second fixture has in place of the first's values
+fn after(name: &str) -> Option<TypedValue> {
+    match name {
+        "timer" => Some(TypedValue::U32(11)),
+        "ammo" => Some(TypedValue::U32(29)),
+        _ => None,
+    }
+}
+
+// Bytes that look like code or a used stack, the same every time they are generated
+fn fill_noise(bytes: &mut [u8], mut seed: u64) {
+    for byte in bytes {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        *byte = (seed >> 56) as u8;
+    }
+}
+
+fn plant_fixtures(dir: &Path) -> Manifest {
+    let mut fixtures = Vec::new();
+    for (file, changed) in [("scan_before.cap", false), ("scan_after.cap", true)] {
+        let mock = MockProcess::new(4242);
+        for line in MAPS {
+            mock.map(line).unwrap();
+        }
+        mock.set_executable(Some("/usr/bin/game"));
+        mock.set_thread_stacks(vec![(mock.pid(), STACK + 0x7000)]);
+        for (start, len, seed) in [(EXECUTABLE, DATA - EXECUTABLE, 1), (STACK, 0x8000, 2)] {
+            let mut noise = vec![0; len];
+            fill_noise(&mut noise, seed);
+            mock.plant(start, &noise).unwrap();
+        }
+        let mut planted = Vec::new();
+        for (name, address, value) in self::planted() {
+            let value = after(name).filter(|_| changed).unwrap_or(value);
+            // A byte at a time, as some run on into the next region
+            for (offset, byte) in value.to_bytes().into_iter().enumerate() {
+                mock.plant(address + offset, &[byte]).unwrap();
+            }
+            planted.push(Planted { name: name.to_string(), address: format!("0x{:x}", address), value: value.to_string() });
+        }
+        // Only the guard page is left out, as dump_all would
+        let mapped = mock.memory_regions().unwrap();
+        let (regions, uncaptured) = mapped.into_iter().partition(|x| x.readable);
+        let header = CaptureHeader { time: UNIX_EPOCH + Duration::from_secs(1_700_000_000), uncaptured, ..CaptureHeader::of(&mock).unwrap() };
+        Capture::write(&dir.join(file), &header, Compression::Lz4, regions, |address, block| {
+            read_bytes_into(&mock, address, block).unwrap();
+            Ok(Vec::new())
+        }).unwrap();
+        fixtures.push(Fixture { file: file.to_string(), maps: MAPS.iter().map(|x| x.to_string()).collect(), planted });
+    }
+    let manifest = Manifest { fixtures };
+    std::fs::write(dir.join("manifest.json"), serde_json::to_string_pretty(&manifest).unwrap() + "\n").unwrap();
+    manifest
+}
+
+#[test]
+fn the_fixtures_are_what_the_generator_writes() {
+    if std::env::var_os("REGENERATE_FIXTURES").is_some() {
+        plant_fixtures(&fixtures());
+    }
+    let dir = std::env::temp_dir().join(format!("memory-fixtures-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let manifest = plant_fixtures(&dir);
+    assert_eq!(manifest, Manifest::load());
+    for fixture in &manifest.fixtures {
+        let written = std::fs::read(dir.join(&fixture.file)).unwrap();
+        assert!(written == std::fs::read(fixtures().join(&fixture.file)).unwrap(), "{} is out of date; run with REGENERATE_FIXTURES=1", fixture.file);
+        // Each planted value reads back from the capture as it was planted
+        let capture = fixture.open();
+        for planted in &fixture.planted {
+            let value = planted.value.parse::<TypedValue>().unwrap();
+            let mut bytes = vec![0; value.to_bytes().len()];
+            read_bytes_into(&capture, fixture.at(&[&planted.name])[0], &mut bytes).unwrap();
+            assert_eq!(bytes, value.to_bytes(), "{}", planted.name);
+        }
+        let maps = fixture.maps.iter().map(|x| x.parse::<MemoryRegion>().unwrap()).collect::<Vec<MemoryRegion>>();
+        assert_eq!(capture.memory_regions().unwrap(), maps);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn exact_scans_check_every_offset_unless_aligned() {
+    let manifest = Manifest::load();
+    let fixture = manifest.fixture("scan_before.cap");
+    let capture = fixture.open();
+    // Every offset a whole value fits at inside one region, the seam between chunks included; a
+    // value across two adjacent regions is in neither of them
+    let (found, stats) = find_value(&capture, 31337i32, &ScanOptions::default()).unwrap();
+    assert_eq!(found, fixture.at(&["score", "score_unaligned", "data_last", "seam", "anon_last"]));
+    assert_eq!(stats.failed_regions, []);
+    let aligned = ScanOptions { alignment: Some(4), ..ScanOptions::default() };
+    let (found, _) = find_value(&capture, 31337i32, &aligned).unwrap();
+    assert_eq!(found, fixture.at(&["score", "data_last", "anon_last"]));
+    let (found, _) = find_value(&capture, 0x0123_4567_89ab_cdefi64, &ScanOptions::default()).unwrap();
+    assert_eq!(found, fixture.at(&["gold", "gold_half_aligned"]));
+    let (found, _) = find_value(&capture, 0x0123_4567_89ab_cdefi64, &ScanOptions { alignment: Some(8), ..ScanOptions::default() }).unwrap();
+    assert_eq!(found, fixture.at(&["gold"]));
+}
+
+#[test]
+fn range_scans_include_both_bounds() {
+    let manifest = Manifest::load();
+    let fixture = manifest.fixture("scan_before.cap");
+    let capture = fixture.open();
+    let aligned = ScanOptions { alignment: Some(4), ..ScanOptions::default() };
+    let (found, _) = find_value_by_predicate::<i32>(&capture, |x| (1000..=2000).contains(x), &aligned).unwrap();
+    assert_eq!(found, fixture.at(&["range_low", "range_high"]));
+    let (found, _) = find_value_by_predicate::<i32>(&capture, |x| (1000..=2000).contains(x), &ScanOptions::default()).unwrap();
+    assert_eq!(found, fixture.at(&["range_low", "range_middle", "range_high"]));
+}
+
+#[test]
+fn float_scans_within_a_tolerance() {
+    let manifest = Manifest::load();
+    let fixture = manifest.fixture("scan_before.cap");
+    let capture = fixture.open();
+    // Leaving out the stack, whose noise holds floats of every kind, NaNs included
+    let options = ScanOptions { include_stack: false, ..ScanOptions::default() };
+    // NaN is never within any tolerance
+    let (found, _) = find_value_by_predicate::<f32>(&capture, |x| (x - 99.5).abs() <= 0.25, &options).unwrap();
+    assert_eq!(found, fixture.at(&["float_high", "float_low", "float"]));
+    let (found, _) = find_value::<f32>(&capture, 99.5, &options).unwrap();
+    assert_eq!(found, fixture.at(&["float"]));
+    // and the executable's code, which does too
+    let (found, _) = find_value_by_predicate::<f32>(&capture, |x| x.is_nan(), &ScanOptions { include_main_executable: false, ..options }).unwrap();
+    assert_eq!(found, fixture.at(&["float_nan"]));
+}
+
+#[test]
+fn string_and_byte_pattern_scans() {
+    let manifest = Manifest::load();
+    let fixture = manifest.fixture("scan_before.cap");
+    let capture = fixture.open();
+    // A shorter string is found wherever the longer one is too
+    let (found, _) = find_value_generic(&capture, *b"Player One", &ScanOptions::default()).unwrap();
+    assert_eq!(found, fixture.at(&["name", "name_copy"]));
+    let (found, _) = find_value_generic(&capture, *b"Player On", &ScanOptions::default()).unwrap();
+    assert_eq!(found, fixture.at(&["name", "name_copy", "name_truncated"]));
+    let wide = "Hero".encode_utf16().collect::<Vec<u16>>();
+    let (found, _) = find_value_generic(&capture, [wide[0], wide[1], wide[2], wide[3]], &ScanOptions::default()).unwrap();
+    assert_eq!(found, fixture.at(&["wide_name"]));
+    let (found, _) = find_value_generic(&capture, [0xdeu8, 0xad, 0xbe, 0xef, 0x00, 0xff], &ScanOptions::default()).unwrap();
+    assert_eq!(found, fixture.at(&["pattern", "pattern_across_seam"]));
+}
+
+#[test]
+fn comparing_the_fixtures_finds_what_changed_between_them() {
+    let manifest = Manifest::load();
+    let (before, after) = (manifest.fixture("scan_before.cap"), manifest.fixture("scan_after.cap"));
+    // A comparison looks at every offset, so a changed byte is also the top byte of the values
+    // that end in it
+    let snapshot = Snapshot::capture(before.open(), &ScanOptions::default()).unwrap();
+    let (timer, ammo) = (before.at(&["timer"])[0], before.at(&["ammo"])[0]);
+    let increased = snapshot.compare::<u32>(after.open(), |old, new| new > old).unwrap();
+    assert_eq!(increased, [timer - 3, timer - 2, timer - 1, timer]);
+    let decreased = snapshot.compare::<u32>(after.open(), |old, new| new < old).unwrap();
+    assert_eq!(decreased, [ammo - 3, ammo - 2, ammo - 1, ammo]);
+    // An unknown-value scan only ever looks at multiples of the size it was started with
+    let mut unknown = UnknownScan::start(before.open(), 4, &ScanOptions::default()).unwrap();
+    unknown.narrow::<u32>(after.open(), |old, new| new != old).unwrap();
+    assert_eq!(unknown.addresses(), [timer, ammo]);
+}
@@ -0,0 +1,64 @@
+use std::{io::Write, process::{Command, Stdio}};
+use memory::{FlagConvention, MockProcess, ScanOptions, ScanStats, find_flags, reduce_found_flags};
+
+// A page of 7s, which no convention takes for a flag, with bytes planted at the start of it
+fn mock(bytes: &[u8]) -> MockProcess {
+    let mock = MockProcess::new(100);
+    mock.map("10000-11000 rw-p 00000000 00:00 0").unwrap();
+    mock.plant(0x10000, &[7; 0x1000]).unwrap();
+    mock.plant(0x10000, bytes).unwrap();
+    mock
+}
+
+#[test]
+fn only_bytes_holding_a_flag_are_found() {
+    let mock = mock(&[0, 1, 255, 2, 1, 0]);
+    let found = |convention, state| find_flags(&mock, convention, state, &ScanOptions::default()).unwrap().0;
+    assert_eq!(found(FlagConvention::One, None), [0x10000, 0x10001, 0x10004, 0x10005]);
+    assert_eq!(found(FlagConvention::One, Some(true)), [0x10001, 0x10004]);
+    assert_eq!(found(FlagConvention::AllOnes, None), [0x10000, 0x10002, 0x10005]);
+    assert_eq!(found(FlagConvention::AllOnes, Some(false)), [0x10000, 0x10005]);
+    // Every 7 is on too, as anything but 0 is
+    assert_eq!(found(FlagConvention::Nonzero, Some(true)).len(), 0x1000 - 2);
+}
+
+// Either state of a nonzero flag is every byte there is
+#[test]
+fn a_nonzero_flag_needs_a_state() {
+    let mock = mock(&[]);
+    let error = find_flags(&mock, FlagConvention::Nonzero, None, &ScanOptions::default()).unwrap_err();
+    assert!(error.to_string().contains("scan for it on or off"), "{}", error);
+}
+
+// Flipping the flag in the target and rescanning for the state it is now in leaves only it
+#[test]
+fn flips_narrow_down_to_the_flag() {
+    let mock = mock(&[0, 1, 1, 0]);
+    let (mut found, mut stats) = find_flags(&mock, FlagConvention::One, None, &ScanOptions::default()).unwrap();
+    mock.plant(0x10000, &[1, 1, 0, 0]).unwrap();
+    reduce_found_flags(&mock, &mut found, FlagConvention::One, true, &ScanOptions::default(), &mut stats).unwrap();
+    assert_eq!(found, [0x10000, 0x10001]);
+    mock.plant(0x10000, &[0, 2, 0, 0]).unwrap();
+    reduce_found_flags(&mock, &mut found, FlagConvention::One, false, &ScanOptions::default(), &mut ScanStats::default()).unwrap();
+    assert_eq!(found, [0x10000]);
+}
+
+#[test]
+fn conventions_parse_by_what_on_is() {
+    for convention in [FlagConvention::One, FlagConvention::AllOnes, FlagConvention::Nonzero] {
+        assert_eq!(convention.to_string().parse::<FlagConvention>().unwrap(), convention);
+    }
+    assert_eq!("0/255".parse::<FlagConvention>().unwrap(), FlagConvention::AllOnes);
+    assert!("2".parse::<FlagConvention>().unwrap_err().contains("expected 1, 255 or nonzero"));
+}
+
+// rescan flag goes on with the convention the scan was for
+#[test]
+fn the_cli_scans_and_rescans_flags() {
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).args(["--offline", "tests/fixtures/scan_before.cap"]).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    writeln!(session.stdin.take().unwrap(), "scan flag on --as 255\nrescan flag on\nrescan flag off\nscan flag --as nonzero\nscan flag sideways").unwrap();
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    let matches = stdout.lines().filter_map(|x| x.trim_start_matches("> ").split(", ").last()?.strip_suffix(" matches")).collect::<Vec<&str>>();
+    assert_eq!(matches, ["193", "193", "0"], "{}", stdout);
+    assert!(stdout.contains("error: A flag stored as 0 or anything else") && stdout.contains("error: Unknown scan option 'sideways'"), "{}", stdout);
+}
@@ -0,0 +1,80 @@
+use memory::{Endianness, InitialValues, LockAction, MockProcess, ScanOptions, ScanStats, UnknownScan, find_value, reduce_found_values, reduce_increased_by_percent, reduce_to_initial};
+
+const HEAP: usize = 0x10000;
+
+// 1.5, NaN, infinity, negative infinity, -0 and 0 as f32s, one every 16 bytes of a page of 0xee
+const PLANTED: [f32; 6] = [1.5, f32::NAN, f32::INFINITY, f32::NEG_INFINITY, -0.0, 0.0];
+
+fn mock() -> MockProcess {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", HEAP, HEAP + 0x1000)).unwrap();
+    mock.plant(HEAP, &[0xee; 0x1000]).unwrap();
+    for (index, value) in PLANTED.iter().enumerate() {
+        mock.plant_value(HEAP + index * 16, *value).unwrap();
+    }
+    mock
+}
+
+fn at(index: usize) -> usize {
+    HEAP + index * 16
+}
+
+#[test]
+fn nan_matches_nothing_and_zeroes_match_each_other() {
+    let mock = mock();
+    let options = ScanOptions::default();
+    assert!(find_value(&mock, f32::NAN, &options).unwrap().0.is_empty());
+    assert_eq!(find_value(&mock, 0.0f32, &options).unwrap().0, [at(4), at(5)]);
+    assert_eq!(find_value(&mock, -0.0f32, &options).unwrap().0, [at(4), at(5)]);
+}
+
+#[test]
+fn infinities_match_only_their_own_sign() {
+    let mock = mock();
+    assert_eq!(find_value(&mock, f32::INFINITY, &ScanOptions::default()).unwrap().0, [at(2)]);
+    assert_eq!(find_value(&mock, f32::NEG_INFINITY, &ScanOptions::default()).unwrap().0, [at(3)]);
+    // A value gone to NaN is dropped on a rescan, even one for NaN
+    let mut found = vec![at(0), at(1)];
+    reduce_found_values(&mock, &mut found, f32::NAN, &ScanOptions::default(), &mut ScanStats::default()).unwrap();
+    assert!(found.is_empty());
+}
+
+// An infinity is only the same as itself, not as every value an infinite tolerance would allow,
+// and a NaN is the same as nothing
+#[test]
+fn rescan_initial_keeps_infinities_only_while_they_last() {
+    let mock = mock();
+    let mut found = (0..PLANTED.len()).map(at).collect::<Vec<usize>>();
+    let initial = InitialValues::record::<f32>(&mock, &found, Endianness::Native);
+    mock.plant_value(at(4), 0.0f32).unwrap();
+    reduce_to_initial::<f32>(&mock, &mut found, &initial, &ScanOptions::default(), &mut ScanStats::default()).unwrap();
+    // The NaN is dropped although its bits are unchanged
+    assert_eq!(found, [at(0), at(2), at(3), at(4), at(5)]);
+    mock.plant_value(at(2), f32::MAX).unwrap();
+    reduce_to_initial::<f32>(&mock, &mut found, &initial, &ScanOptions::default(), &mut ScanStats::default()).unwrap();
+    assert_eq!(found, [at(0), at(3), at(4), at(5)]);
+}
+
+// NaN and infinity are no percentage of anything, so neither a value that was one nor one that
+// became one is kept
+#[test]
+fn percent_changes_skip_nan_and_infinity() {
+    let mock = mock();
+    let mut unknown = UnknownScan::start(&mock, 4, &ScanOptions { alignment: Some(16), ..ScanOptions::default() }).unwrap();
+    mock.plant_value(at(0), 3.0f32).unwrap();
+    mock.plant_value(at(1), 2.0f32).unwrap();
+    mock.plant_value(at(2), 2.0f32).unwrap();
+    mock.plant_value(at(5), f32::NAN).unwrap();
+    reduce_increased_by_percent::<f32>(&mock, &mut unknown, 100.0, 0.01).unwrap();
+    assert_eq!(unknown.addresses(), [at(0)]);
+}
+
+#[test]
+fn clamp_locks_put_a_nan_back_in_the_band() {
+    let band = LockAction::Clamp { min: Some(1.0f32), max: Some(2.0), tolerance: 0.0 };
+    assert_eq!((band.needs_write(f32::NAN), band.apply(f32::NAN)), (true, 1.0));
+    let ceiling = LockAction::Clamp { min: None, max: Some(2.0f32), tolerance: 0.0 };
+    assert_eq!((ceiling.needs_write(f32::NAN), ceiling.apply(f32::NAN)), (true, 2.0));
+    assert_eq!((ceiling.needs_write(f32::NEG_INFINITY), band.apply(f32::NEG_INFINITY)), (false, 1.0));
+    assert_eq!((band.needs_write(f32::INFINITY), band.apply(f32::INFINITY)), (true, 2.0));
+}
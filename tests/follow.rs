@@ -0,0 +1,168 @@
+#![cfg(target_os = "linux")]
+use std::{io::{BufRead, BufReader, Write}, process::{Child, ChildStdin, Command, Stdio}, sync::mpsc::Receiver, time::{Duration, Instant}};
+use memory::{TargetChange, TargetMonitor, modules};
+use nix::unistd::Pid;
+
+// bench_target, idling until it is killed
+struct Target(Child);
+
+impl Target {
+    fn spawn() -> Target {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_bench_target")).args(["1", "tick"]).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+        // Every line is read before the pipe is closed, or the last one fails to be written
+        let _ = BufReader::new(child.stdout.take().unwrap()).lines().take(4).map(|x| x.unwrap()).collect::<Vec<String>>();
+        Target(child)
+    }
+
+    fn pid(&self) -> Pid {
+        Pid::from_raw(self.0.id() as i32)
+    }
+}
+
+impl Drop for Target {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+// A shell that runs the script once it is sent a line, so there is time to start watching it first.
+// Spawning can return as soon as the exec begins, so this waits for it to be blocked reading
+fn shell(script: &str) -> (Target, ChildStdin) {
+    let mut child = Command::new("sh").args(["-c", script]).stdin(Stdio::piped()).spawn().unwrap();
+    let stdin = child.stdin.take().unwrap();
+    let started = Instant::now();
+    while !std::fs::read_to_string(format!("/proc/{}/stat", child.id())).unwrap().rsplit_once(") ").unwrap().1.starts_with('S') {
+        assert!(started.elapsed() < Duration::from_secs(10), "the shell never started reading");
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    (Target(child), stdin)
+}
+
+// Polls until a change matches, as other tests start processes of the same name alongside
+fn poll_until(monitor: &mut TargetMonitor, mut wanted: impl FnMut(&TargetChange) -> bool) -> TargetChange {
+    let started = Instant::now();
+    loop {
+        if let Some(change) = monitor.poll().into_iter().find(&mut wanted) {
+            return change;
+        }
+        assert!(started.elapsed() < Duration::from_secs(10), "nothing changed");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn reports_an_exit_once() {
+    let mut target = Target::spawn();
+    let mut monitor = TargetMonitor::new(target.pid()).unwrap();
+    assert_eq!((monitor.target().pid, monitor.target().name.as_str(), monitor.changed()), (target.pid(), "bench_target", false));
+    assert!(!monitor.poll().contains(&TargetChange::Exited));
+    // A zombie has exited as far as following it goes
+    target.0.kill().unwrap();
+    poll_until(&mut monitor, |x| *x == TargetChange::Exited);
+    assert!(monitor.changed());
+    assert!(!monitor.modules().is_empty());
+    let _ = target.0.wait();
+    assert!(!monitor.poll().contains(&TargetChange::Exited));
+}
+
+#[test]
+fn reports_processes_of_the_same_name() {
+    let target = Target::spawn();
+    let mut monitor = TargetMonitor::new(target.pid()).unwrap();
+    let other = Target::spawn();
+    let change = poll_until(&mut monitor, |x| matches!(x, TargetChange::Appeared { process, .. } if process.pid == other.pid()));
+    assert!(matches!(change, TargetChange::Appeared { descendant: false, .. }));
+    assert!(monitor.candidates().iter().any(|x| x.pid == other.pid()));
+    assert!(!monitor.poll().iter().any(|x| matches!(x, TargetChange::Appeared { process, .. } if process.pid == other.pid())));
+    // The target itself never replaces itself
+    assert!(!monitor.candidates().iter().any(|x| x.pid == target.pid()));
+}
+
+#[test]
+fn tells_its_own_children_apart() {
+    let (parent, mut stdin) = shell("read x; sh -c 'read y'; true");
+    let mut monitor = TargetMonitor::new(parent.pid()).unwrap();
+    writeln!(stdin, "go").unwrap();
+    let change = poll_until(&mut monitor, |x| matches!(x, TargetChange::Appeared { process, .. } if process.parent == parent.pid()));
+    assert!(matches!(change, TargetChange::Appeared { descendant: true, .. }));
+    assert!(!monitor.changed());
+    assert_eq!(monitor.candidates()[0].parent, parent.pid());
+}
+
+#[test]
+fn reports_an_exec() {
+    let (target, mut stdin) = shell("read x; exec sleep 60");
+    let mut monitor = TargetMonitor::new(target.pid()).unwrap();
+    let before = modules(target.pid()).unwrap();
+    writeln!(stdin, "go").unwrap();
+    let TargetChange::Execed(now) = poll_until(&mut monitor, |x| matches!(x, TargetChange::Execed(_))) else { unreachable!() };
+    assert_eq!((now.pid, now.name.as_str()), (target.pid(), "sleep"));
+    assert_eq!(monitor.successor().map(|x| x.pid), Some(target.pid()));
+    // Left as they were before the exec, for what was found there to be resolved against
+    assert_eq!(monitor.modules().iter().map(|x| &x.path).collect::<Vec<&String>>(), before.iter().map(|x| &x.path).collect::<Vec<&String>>());
+}
+
+// Every line of the session's output as it comes, the prompt taken off
+fn lines(session: &mut Child) -> Receiver<String> {
+    let (send, receive) = std::sync::mpsc::channel();
+    let stdout = BufReader::new(session.stdout.take().unwrap());
+    std::thread::spawn(move || {
+        for line in stdout.lines().map_while(Result::ok) {
+            let _ = send.send(line.trim_start_matches("> ").to_string());
+        }
+    });
+    receive
+}
+
+fn wait_for(lines: &Receiver<String>, seen: &mut Vec<String>, wanted: &str) {
+    let started = Instant::now();
+    while !seen.iter().any(|x| x.contains(wanted)) {
+        let left = Duration::from_secs(10).saturating_sub(started.elapsed());
+        match lines.recv_timeout(left) {
+            Ok(line) => seen.push(line),
+            Err(_) => panic!("never saw {:?} in {:#?}", wanted, seen),
+        }
+    }
+}
+
+#[test]
+fn the_session_reattaches_to_the_restarted_target() {
+    let mut first = Target::spawn();
+    let base = |pid: Pid| modules(pid).unwrap().into_iter().find(|x| x.name == "bench_target").unwrap().base;
+    // Somewhere writable in the executable, for a lock that survives a restart
+    let offset = memory::get_memory_regions(first.pid()).unwrap().into_iter().find(|x| x.writable && x.pathname.ends_with("/bench_target")).unwrap().start - base(first.pid());
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(first.pid().to_string()).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::null()).spawn().unwrap();
+    let (mut stdin, lines) = (session.stdin.take().unwrap(), lines(&mut session));
+    let mut seen = Vec::new();
+    writeln!(stdin, "lock bench_target+0x{:x} i32\nlocks", offset).unwrap();
+    wait_for(&lines, &mut seen, &format!("bench_target+0x{:x} (", offset));
+    let _ = first.0.kill();
+    let _ = first.0.wait();
+    wait_for(&lines, &mut seen, &format!("process {} (bench_target) exited", first.pid()));
+    let second = Target::spawn();
+    wait_for(&lines, &mut seen, &format!("press Enter to reattach to process {}", second.pid()));
+    // Named outright, in case another test's target was offered after it
+    writeln!(stdin, "reattach {}\nlocks", second.pid()).unwrap();
+    wait_for(&lines, &mut seen, &format!("reattached from process {} to {}", first.pid(), second.pid()));
+    wait_for(&lines, &mut seen, "restored 1 of 1 locks");
+    wait_for(&lines, &mut seen, &format!("bench_target+0x{:x} (0x{:x})", offset, base(second.pid()) + offset));
+    drop(stdin);
+    let _ = session.wait();
+}
+
+#[test]
+fn follow_reattaches_after_an_exec_without_asking() {
+    let (target, mut stdin) = shell("read x; exec sleep 60");
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(target.pid().to_string()).arg("--follow").stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::null()).spawn().unwrap();
+    let (mut commands, lines) = (session.stdin.take().unwrap(), lines(&mut session));
+    let mut seen = Vec::new();
+    writeln!(commands, "follow").unwrap();
+    wait_for(&lines, &mut seen, "follow is on");
+    writeln!(stdin, "go").unwrap();
+    wait_for(&lines, &mut seen, "exec'd /");
+    wait_for(&lines, &mut seen, &format!("reattached from process {} to {}", target.pid(), target.pid()));
+    assert!(!seen.iter().any(|x| x.contains("press Enter")), "{:#?}", seen);
+    drop(commands);
+    let _ = session.wait();
+}
@@ -0,0 +1,67 @@
+mod common;
+use std::{io::Write, process::{Command, Stdio}};
+use memory::{Endianness, Guess, ProcessMemory, Reading, Scalar, TypedValue, guess_types};
+use common::mock_with_heap;
+
+// Reads the bytes planted at the start of the mock's page
+fn guesses(bytes: &[u8]) -> Vec<Guess> {
+    let mock = mock_with_heap(0x2000);
+    mock.plant(0x10000, bytes).unwrap();
+    let mut read = vec![0; 32];
+    mock.read_at(0x10000, &mut read).unwrap();
+    guess_types(&read, &mock.memory_regions().unwrap(), Endianness::Native)
+}
+
+fn find(guesses: &[Guess], reading: Reading) -> &Guess {
+    guesses.iter().find(|x| x.reading == reading).unwrap()
+}
+
+#[test]
+fn a_small_integer_ranks_first() {
+    let guesses = guesses(&31337i32.to_bytes());
+    assert_eq!(guesses[0].reading, Reading::Value(TypedValue::I32(31337)));
+    // Its low byte alone is 105, but the next byte says it goes on
+    assert_eq!(find(&guesses, Reading::Value(TypedValue::U8(105))).note, Some("the bytes after it are not zero"));
+    assert!(guesses.windows(2).all(|x| x[0].score >= x[1].score));
+    // As an f32, the same bits are a denormal
+    let denormal = guesses.iter().find(|x| matches!(x.reading, Reading::Value(TypedValue::F32(_)))).unwrap();
+    assert_eq!((denormal.score, denormal.note), (0, Some("denormal, unlikely")));
+}
+
+#[test]
+fn floats_of_an_everyday_size_beat_what_their_bits_are_as_integers() {
+    let guesses = guesses(&1.5f32.to_bytes());
+    assert_eq!(guesses[0].reading, Reading::Value(TypedValue::F32(1.5)));
+    let nan = self::guesses(&f32::NAN.to_bytes());
+    let nan = nan.iter().find(|x| matches!(x.reading, Reading::Value(TypedValue::F32(x)) if x.is_nan())).unwrap();
+    assert_eq!((nan.score, nan.note), (0, Some("NaN, unlikely")));
+}
+
+#[test]
+fn pointers_are_checked_against_the_mappings() {
+    let guesses = guesses(&[0x10800usize.to_bytes(), 0x90000usize.to_bytes()].concat());
+    assert_eq!(guesses[0].reading, Reading::Pointer { target: 0x10800, mapped: true });
+    let mock = mock_with_heap(0x2000);
+    let unmapped = guess_types(&0x90000usize.to_bytes(), &mock.memory_regions().unwrap(), Endianness::Native);
+    assert_eq!(find(&unmapped, Reading::Pointer { target: 0x90000, mapped: false }).score, 0);
+}
+
+#[test]
+fn text_ending_in_a_nul_ranks_first() {
+    let guesses = guesses(b"Player\0");
+    assert_eq!(guesses[0].reading, Reading::Text("Player".to_string()));
+    assert_eq!(find(&guesses, Reading::Text("Player".to_string())).note, None);
+}
+
+// The offline capture's game+0x4010 holds 31337 as an i32
+#[test]
+fn the_cli_lists_every_reading() {
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).args(["--offline", "tests/fixtures/scan_before.cap"]).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    writeln!(session.stdin.take().unwrap(), "guess game+0x4010\nguess 0x10\nguess").unwrap();
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    let lines = stdout.lines().map(|x| x.trim_start_matches("> ")).collect::<Vec<&str>>();
+    let start = lines.iter().position(|x| x.starts_with("game+0x4010 (0x404010): 69 7a 00 00")).unwrap();
+    assert_eq!(lines[start + 1].split_whitespace().collect::<Vec<&str>>(), ["7", "i32", "31337"], "{}", stdout);
+    assert!(stdout.contains("f32  4.3912e-41  (denormal, unlikely)") && stdout.contains("ptr  0x7a69 (unmapped)  (points to nothing mapped)"), "{}", stdout);
+    assert!(stdout.contains("error: Could not read 0x10") && stdout.contains("error: Usage: guess <address>"), "{}", stdout);
+}
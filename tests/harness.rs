@@ -0,0 +1,163 @@
+#![cfg(target_os = "linux")]
+use std::{ffi::CString, io::{BufRead, BufReader}, process::{Child, Command, Stdio}, time::{Duration, Instant}};
+use memory::{DEFAULT_LOCK_INTERVAL, Endianness, LockManager, Process, RegionCache, ScanOptions, find_value, get_memory_regions, modules, read_cstring, read_scalar, reduce_found_values, resolve_address, write_to_process};
+use nix::{sys::signal::{Signal, kill}, unistd::Pid};
+
+// Must match the constants in tests/target/test_target.rs
+const COUNTER_START: i32 = 0x1ee7_0000;
+const FLOATS: usize = 16;
+const STRING_LEN: usize = 3000;
+const CHAIN_VALUE: i32 = 0x0c4a_1234;
+
+// test_target, whose counter only changes when it is sent SIGUSR1
+struct Target {
+    child: Child,
+    counter: usize,
+    floats: usize,
+    string: usize,
+    // The chain's base and its offsets
+    chain: (usize, Vec<usize>),
+    value: usize,
+}
+
+impl Target {
+    fn spawn() -> Target {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_test_target")).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+        // Every line is read before the pipe is closed, or the last one fails to be written
+        let lines = BufReader::new(child.stdout.take().unwrap()).lines().take(6).map(|x| x.unwrap()).collect::<Vec<String>>();
+        let fields = |name: &str| lines.iter().find_map(|x| x.strip_prefix(name)?.strip_prefix(' ')).unwrap().split(' ').map(|x| usize::from_str_radix(x.trim_start_matches("0x"), if x.starts_with("0x") { 16 } else { 10 }).unwrap()).collect::<Vec<usize>>();
+        let chain = fields("chain");
+        Target { counter: fields("counter")[0], floats: fields("floats")[0], string: fields("string")[0], chain: (chain[0], chain[1..].to_vec()), value: fields("value")[0], child }
+    }
+
+    fn pid(&self) -> Pid {
+        Pid::from_raw(self.child.id() as i32)
+    }
+
+    // None when this environment does not let one process read another, as in containers without
+    // CAP_SYS_PTRACE or with a strict /proc/sys/kernel/yama/ptrace_scope; the test then passes
+    // without having checked anything, and says so
+    fn process(&self) -> Option<Process> {
+        let attached = Process::attach(self.pid()).and_then(|x| read_scalar::<i32>(&x, self.counter, Endianness::Native).map(|_| x));
+        match attached {
+            Ok(process) => Some(process),
+            Err(e) if ["EPERM", "EACCES"].iter().any(|x| e.to_string().contains(x)) => {
+                eprintln!("skipping: process {} cannot be read here ({})", self.pid(), e);
+                None
+            }
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    fn increment(&self) {
+        kill(self.pid(), Signal::SIGUSR1).unwrap();
+    }
+
+    fn counter(&self, process: &Process) -> i32 {
+        read_scalar::<i32>(process, self.counter, Endianness::Native).unwrap()
+    }
+
+    // The handler runs some time after the signal is sent, so this polls for it
+    fn wait_for_counter(&self, process: &Process, expected: i32) {
+        let started = Instant::now();
+        while self.counter(process) != expected {
+            assert!(started.elapsed() < Duration::from_secs(10), "counter is {} rather than {}", self.counter(process), expected);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+impl Drop for Target {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn finds_the_counter_and_reduces_after_it_changes() {
+    let target = Target::spawn();
+    let Some(process) = target.process() else { return };
+    let (mut found, mut stats) = find_value(&process, COUNTER_START, &ScanOptions::default()).unwrap();
+    assert!(found.contains(&target.counter), "{:x?}", found);
+    target.increment();
+    target.wait_for_counter(&process, COUNTER_START + 1);
+    reduce_found_values(&process, &mut found, COUNTER_START + 1, &ScanOptions::default(), &mut stats).unwrap();
+    assert_eq!(found, vec![target.counter]);
+}
+
+#[test]
+fn reads_the_floats_and_the_string() {
+    let target = Target::spawn();
+    let Some(process) = target.process() else { return };
+    let floats = (0..FLOATS).map(|x| read_scalar::<f32>(&process, target.floats + x * 4, Endianness::Native).unwrap()).collect::<Vec<f32>>();
+    assert_eq!(floats, (0..FLOATS).map(|x| x as f32 + 0.5).collect::<Vec<f32>>());
+    let string = read_cstring(&process, &mut RegionCache::new(target.pid()).unwrap(), target.string, STRING_LEN * 2).unwrap();
+    let expected = (0..STRING_LEN).map(|x| b'a' + (x * 7 % 26) as u8).collect::<Vec<u8>>();
+    assert_eq!(string, CString::new(expected).unwrap());
+}
+
+#[test]
+fn the_target_counts_on_from_a_written_value() {
+    let target = Target::spawn();
+    let Some(process) = target.process() else { return };
+    write_to_process(&process, target.counter, &42i32).unwrap();
+    assert_eq!(target.counter(&process), 42);
+    target.increment();
+    target.wait_for_counter(&process, 43);
+    write_to_process(&process, target.floats + 4, &-2.25f32).unwrap();
+    assert_eq!(read_scalar::<f32>(&process, target.floats + 4, Endianness::Native).unwrap(), -2.25);
+}
+
+#[test]
+fn a_lock_holds_while_the_target_keeps_changing_the_value() {
+    let target = Target::spawn();
+    let Some(process) = target.process() else { return };
+    let mut locks = LockManager::new(process.clone());
+    locks.lock_value(7i32, target.counter, Duration::from_millis(1));
+    for _ in 0..50 {
+        target.increment();
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    // The target's last increment is written over within a few intervals
+    target.wait_for_counter(&process, 7);
+    assert!(locks.get(target.counter).unwrap().writes > 1);
+    assert!(locks.unlock_value(target.counter));
+    std::thread::sleep(DEFAULT_LOCK_INTERVAL);
+    target.increment();
+    target.wait_for_counter(&process, 8);
+}
+
+#[test]
+fn follows_the_pointer_chain_from_the_executable() {
+    let target = Target::spawn();
+    let Some(process) = target.process() else { return };
+    let executable = modules(target.pid()).unwrap().into_iter().find(|x| x.name == "test_target").unwrap();
+    assert!(executable.contains(target.chain.0));
+    let offsets = target.chain.1.iter().map(|x| format!("->0x{:x}", x)).collect::<Vec<String>>();
+    let chain = format!("test_target+0x{:x}{}", target.chain.0 - executable.base, offsets.concat());
+    let mut regions = RegionCache::new(target.pid()).unwrap();
+    assert_eq!(resolve_address(&process, &mut regions, &chain).unwrap(), target.value);
+    assert_eq!(read_scalar::<i32>(&process, target.value, Endianness::Native).unwrap(), CHAIN_VALUE);
+    // One hop further treats the value as a pointer, which it is not
+    let error = resolve_address(&process, &mut regions, &format!("{}->0x0", chain)).unwrap_err().to_string();
+    // Read as a pointer, so only the low 32 bits are the value's
+    let read = error.split_once("hop 3: 0x").and_then(|x| x.1.split_once(" is not")).unwrap_or_else(|| panic!("{}", error)).0;
+    assert_eq!(u64::from_str_radix(read, 16).unwrap() as u32, CHAIN_VALUE as u32, "{}", error);
+}
+
+#[test]
+fn the_maps_place_each_structure() {
+    let target = Target::spawn();
+    let regions = get_memory_regions(target.pid()).unwrap();
+    let region = |address: usize| regions.iter().find(|x| x.contains(address)).unwrap();
+    // The counter and the chain's base are statics, the rest was allocated
+    for address in [target.counter, target.chain.0] {
+        assert!(region(address).writable && region(address).pathname.ends_with("/test_target"), "{:?}", region(address));
+    }
+    for address in [target.floats, target.string, target.value] {
+        assert!(region(address).readable && region(address).writable && !region(address).pathname.ends_with("/test_target"), "{:?}", region(address));
+    }
+    assert!(regions.windows(2).all(|x| x[0].end <= x[1].start));
+    assert!(regions.iter().any(|x| x.pathname == "[stack]"));
+}
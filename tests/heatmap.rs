@@ -0,0 +1,70 @@
+#![cfg(target_os = "linux")]
+mod common;
+use std::{io::Write, process::{Command, Stdio}, time::Duration};
+use memory::{HEATMAP_PAGE_SIZE, MemoryRegion, Process, RegionFilter, RegionHeat, ScanOptions, Snapshot};
+use common::Target;
+
+impl Target {
+    fn process(&self) -> Process {
+        Process::attach(self.pid()).unwrap()
+    }
+
+    // Runs the commands in a session on the target, giving it a moment to change after the first,
+    // and returns what it printed
+    fn session(&self, first: &str, then: &str) -> String {
+        let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(self.pid().to_string()).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+        let mut stdin = session.stdin.take().unwrap();
+        stdin.write_all(first.as_bytes()).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        stdin.write_all(then.as_bytes()).unwrap();
+        drop(stdin);
+        let output = session.wait_with_output().unwrap();
+        format!("{}{}", String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap())
+    }
+}
+
+#[test]
+fn finds_the_page_that_changed() {
+    let target = Target::spawn();
+    let options = ScanOptions { filter: Some("heap".parse::<RegionFilter>().unwrap()), ..ScanOptions::default() };
+    let snapshot = Snapshot::capture(target.process(), &options).unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    let heatmap = snapshot.heatmap(target.process(), None).unwrap();
+    assert_eq!((heatmap.pages(), heatmap.unread, heatmap.skipped), (snapshot.len() / HEATMAP_PAGE_SIZE, 0, 0));
+    // The player's page is the hottest region's, and the block the target planted never changes
+    let hottest = &heatmap.regions[0];
+    assert!(hottest.region.contains(target.player), "{:?}", hottest.region);
+    let page = target.player / HEATMAP_PAGE_SIZE * HEATMAP_PAGE_SIZE;
+    assert!(hottest.pages.contains(&(page, true)), "{:x?}", hottest.pages);
+    assert!(heatmap.regions.windows(2).all(|x| x[0].fraction() >= x[1].fraction()));
+    // Nothing is compared once the time is up
+    let late = snapshot.heatmap(target.process(), Some(Duration::ZERO)).unwrap();
+    assert_eq!((late.pages(), late.skipped), (0, heatmap.pages()));
+}
+
+#[test]
+fn bars_spread_the_pages_over_the_cells() {
+    let region = MemoryRegion { start: 0x10000, end: 0x18000, readable: true, writable: true, ..MemoryRegion::default() };
+    let pages = [true, true, false, true, false, false, false, false].iter().enumerate().map(|(i, x)| (0x10000 + i * HEATMAP_PAGE_SIZE, *x)).collect();
+    let heat = RegionHeat { region, pages };
+    assert_eq!((heat.changed(), heat.fraction()), (3, 0.375));
+    assert_eq!(heat.bar(4), "##..");
+    assert_eq!(heat.bar(2), "#.");
+    assert_eq!(heat.bar(64), "##.#....");
+}
+
+#[test]
+fn the_session_suggests_a_filter_for_the_hot_regions() {
+    let target = Target::spawn();
+    let output = target.session("heatmap\nset filter heap\nsnapshot\n", "heatmap --bars --top 1\n");
+    assert!(output.contains("No snapshot to compare with"), "{}", output);
+    let line = output.lines().find(|x| x.contains(" [heap] [")).unwrap_or_else(|| panic!("{}", output));
+    let range = line.split_whitespace().find(|x| x.starts_with("0x")).unwrap();
+    assert!(output.contains(&format!("`set filter {}` scans only the hottest", range)), "{}", output);
+    // The range goes back in as a filter
+    let filter = range.parse::<RegionFilter>().unwrap();
+    let (start, end) = range.split_once('-').unwrap();
+    assert_eq!(filter, RegionFilter::Range(usize::from_str_radix(&start[2..], 16).unwrap(), usize::from_str_radix(&end[2..], 16).unwrap()));
+    assert_eq!(filter.to_string(), range);
+    assert!("0x2000-0x1000".parse::<RegionFilter>().is_err());
+}
@@ -0,0 +1,86 @@
+use memory::{MemoryRegion, MockProcess, SCAN_CHUNK_SIZE, ScanOptions, UnknownScan, apply_page_sizes, dump_all, find_value, scan::split_regions_into_chunks};
+
+const HUGE: usize = 2 << 20;
+const BASE: usize = 0x4000_0000;
+
+// Three 2 MB pages on hugetlbfs, as MAP_HUGETLB maps them
+fn huge_region() -> MemoryRegion {
+    let region = format!("{:x}-{:x} rw-p 00000000 00:0f 1001 /anon_hugepage (deleted)", BASE, BASE + 3 * HUGE).parse::<MemoryRegion>().unwrap();
+    MemoryRegion { kernel_page_size: Some(HUGE), ..region }
+}
+
+#[test]
+fn page_sizes_come_from_the_mount_or_smaps() {
+    let maps = [
+        "7f0000000000-7f0000400000 rw-s 00000000 00:2e 11 /mnt/huge 1g/buffer",
+        "7f0000400000-7f0000800000 rw-s 00000000 00:0f 12 /anon_hugepage (deleted)",
+        "7f0000800000-7f0000801000 rw-s 00000000 00:19 13 /dev/shm/ring",
+        "7f0000a00000-7f0000c00000 rw-p 00000000 08:01 14 /usr/lib/libc.so.6",
+    ];
+    let mounts = "tmpfs /dev/shm tmpfs rw,nosuid,nodev 0 0\nnone /mnt/huge\\0401g hugetlbfs rw,relatime,pagesize=1024M 0 0\n";
+    let smaps = "7f0000400000-7f0000800000 rw-s 00000000 00:0f 12 /anon_hugepage (deleted)\nSize:               4096 kB\nKernelPageSize:     2048 kB\nMMUPageSize:        2048 kB\n\
+        7f0000800000-7f0000801000 rw-s 00000000 00:19 13 /dev/shm/ring\nKernelPageSize:        4 kB\n";
+    let mut regions = maps.iter().map(|x| x.parse::<MemoryRegion>().unwrap()).collect::<Vec<MemoryRegion>>();
+    apply_page_sizes(&mut regions, mounts, || Some(smaps.to_string()));
+    assert_eq!(regions.iter().map(|x| x.kernel_page_size).collect::<Vec<Option<usize>>>(), [Some(1 << 30), Some(HUGE), None, None]);
+    // With nothing that only smaps can tell about, it is left unread
+    let mut regions = vec![maps[0].parse::<MemoryRegion>().unwrap(), maps[2].parse::<MemoryRegion>().unwrap()];
+    apply_page_sizes(&mut regions, mounts, || panic!("smaps was read"));
+    assert_eq!((regions[0].kernel_page_size, regions[1].kernel_page_size), (Some(1 << 30), None));
+}
+
+// However big the chunks are asked to be, none crosses a huge page's boundary
+#[test]
+fn chunks_are_cut_at_huge_page_boundaries() {
+    let regions = [huge_region()];
+    for chunk_size in [SCAN_CHUNK_SIZE, 3 << 20, 12 << 10, 192 << 10] {
+        let chunks = split_regions_into_chunks(&[(BASE, BASE + 3 * HUGE)], &regions, chunk_size);
+        assert!(chunks.iter().all(|x| x.len <= chunk_size.max(HUGE) && (x.address / HUGE == (x.address + x.len - 1) / HUGE || x.len % HUGE == 0)), "{:x?}", chunks);
+        assert!(chunks.iter().all(|x| x.address % x.len.min(HUGE) == 0), "{:x?}", chunks);
+        assert_eq!(chunks.iter().map(|x| x.len).sum::<usize>(), 3 * HUGE);
+    }
+    assert_eq!(split_regions_into_chunks(&[(BASE, BASE + 3 * HUGE)], &regions, 3 << 20).len(), 3);
+    // Base pages are split as they always were
+    let anonymous = "10000-30000 rw-p 00000000 00:00 0".parse::<MemoryRegion>().unwrap();
+    assert_eq!(split_regions_into_chunks(&[(0x10000, 0x30000)], &[anonymous], 0x3000).len(), 11);
+}
+
+// The middle huge page faults, as one the pool ran out of does, and a value sits at each edge of it
+fn mock() -> MockProcess {
+    let mock = MockProcess::new(100);
+    mock.add_region(huge_region()).unwrap();
+    mock.plant_value(BASE + HUGE - 4, 0x1234_5678i32).unwrap();
+    mock.plant_value(BASE + 2 * HUGE, 0x1234_5678i32).unwrap();
+    mock.mark_failing(BASE + HUGE, BASE + 2 * HUGE).unwrap();
+    mock
+}
+
+#[test]
+fn a_failing_huge_page_loses_only_itself() {
+    let mock = mock();
+    assert!(mock.mark_failing(BASE, BASE + 0x1000).unwrap_err().to_string().contains("not a whole number of pages"));
+    // A budget that makes chunks of 192 KB, which would straddle the page's end
+    let threads = rayon::current_num_threads();
+    let options = ScanOptions { memory_budget: Some(4 * threads * ((192 << 10) + 4 + 100)), alignment: Some(4), ..ScanOptions::default() };
+    let (found, stats) = find_value(&mock, 0x1234_5678i32, &options).unwrap();
+    assert_eq!(found, [BASE + HUGE - 4, BASE + 2 * HUGE]);
+    assert_eq!(stats.read_failures.iter().map(|x| x.bytes_lost).sum::<usize>(), HUGE);
+    // A dump retries it as one page rather than 512, and the hole is exactly it
+    let path = std::env::temp_dir().join(format!("memory-hugepages-{}.cap", std::process::id()));
+    let reads = mock.reads();
+    let captured = dump_all(&mock, &path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(captured[0].holes, [(BASE + HUGE, BASE + 2 * HUGE)]);
+    assert!(mock.reads() - reads < 16, "{} reads", mock.reads() - reads);
+}
+
+// A write dirties the whole huge page it is in, and only that page is read again
+#[test]
+fn soft_dirty_narrows_read_whole_huge_pages() {
+    let mock = MockProcess::new(100);
+    mock.add_region(huge_region()).unwrap();
+    let mut unknown = UnknownScan::start(&mock, 4, &ScanOptions { soft_dirty: true, ..ScanOptions::default() }).unwrap();
+    mock.plant_value(BASE + HUGE + 0x10_0000, 7i32).unwrap();
+    unknown.narrow::<i32>(&mock, |old, new| new != old).unwrap();
+    assert_eq!((unknown.addresses(), unknown.unread), (vec![BASE + HUGE + 0x10_0000], 2 * HUGE));
+}
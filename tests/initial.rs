@@ -0,0 +1,81 @@
+mod common;
+use memory::{InitialValues, MockProcess, Scalar, ScanOptions, ScanStats, find_value, reduce_found_values, reduce_to_initial};
+use common::mock_with_heap;
+
+fn plant<T: Scalar>(mock: &MockProcess, address: usize, value: T) {
+    mock.plant(address, &value.to_bytes()).unwrap();
+}
+
+#[test]
+fn records_the_values_as_they_are() {
+    let mock = mock_with_heap(0x1000);
+    plant(&mock, 0x10010, 50i32);
+    plant(&mock, 0x10000, -7i32);
+    let initial = InitialValues::record::<i32>(&mock, &[0x10010, 0x10000, 0x20000], Default::default());
+    // Whatever the order the results were in, and without what could not be read
+    assert_eq!(initial.len(), 2);
+    assert_eq!((initial.get::<i32>(0x10000), initial.get::<i32>(0x10010), initial.get::<i32>(0x20000)), (Some(-7), Some(50), None));
+    // A type of another size reads nothing rather than half a value
+    assert_eq!(initial.get::<i64>(0x10000), None);
+    assert_eq!(initial.get::<u32>(0x10000), Some(-7i32 as u32));
+}
+
+// Several rescans narrow the results down, then the value is put back as it was
+#[test]
+fn goes_back_to_the_first_values_after_other_rescans() {
+    let mock = mock_with_heap(0x1000);
+    for address in [0x10000, 0x10100, 0x10200] {
+        plant(&mock, address, 100u16);
+    }
+    let options = ScanOptions::default();
+    let (mut found, mut stats) = find_value(&mock, 100u16, &options).unwrap();
+    let initial = InitialValues::record::<u16>(&mock, &found, options.endianness);
+    for address in [0x10000, 0x10100] {
+        plant(&mock, address, 60u16);
+    }
+    reduce_found_values(&mock, &mut found, 60u16, &options, &mut stats).unwrap();
+    assert_eq!(found, [0x10000, 0x10100]);
+    plant(&mock, 0x10000, 100u16);
+    plant(&mock, 0x10200, 60u16);
+    reduce_to_initial::<u16>(&mock, &mut found, &initial, &options, &mut stats).unwrap();
+    assert_eq!((found, stats.matches), (vec![0x10000], 1));
+}
+
+#[test]
+fn floats_may_be_a_little_off() {
+    let mock = mock_with_heap(0x1000);
+    let addresses = [0x10000, 0x10008, 0x10010, 0x10018];
+    for address in addresses {
+        plant(&mock, address, 1234.5f64);
+    }
+    let initial = InitialValues::record::<f64>(&mock, &addresses, Default::default());
+    plant(&mock, 0x10008, f64::from_bits(1234.5f64.to_bits() + 2));
+    plant(&mock, 0x10010, 1234.5001f64);
+    plant(&mock, 0x10018, -1234.5f64);
+    let mut found = addresses.to_vec();
+    reduce_to_initial::<f64>(&mock, &mut found, &initial, &ScanOptions::default(), &mut ScanStats::default()).unwrap();
+    assert_eq!(found, [0x10000, 0x10008]);
+}
+
+// NaN equals nothing, not even a NaN left with the bits it had
+#[test]
+fn a_nan_is_never_the_same_value() {
+    let mock = mock_with_heap(0x1000);
+    plant(&mock, 0x10000, f32::NAN);
+    plant(&mock, 0x10004, 0.0f32);
+    let initial = InitialValues::record::<f32>(&mock, &[0x10000, 0x10004], Default::default());
+    let mut found = vec![0x10000, 0x10004];
+    reduce_to_initial::<f32>(&mock, &mut found, &initial, &ScanOptions::default(), &mut ScanStats::default()).unwrap();
+    assert_eq!(found, [0x10004]);
+}
+
+#[test]
+fn results_never_recorded_are_dropped() {
+    let mock = mock_with_heap(0x1000);
+    let initial = InitialValues::record::<u8>(&mock, &[0x10000], Default::default());
+    let mut found = vec![0x10000, 0x10001];
+    reduce_to_initial::<u8>(&mock, &mut found, &initial, &ScanOptions::default(), &mut ScanStats::default()).unwrap();
+    assert_eq!(found, [0x10000]);
+    let error = reduce_to_initial::<u32>(&mock, &mut found, &initial, &ScanOptions::default(), &mut ScanStats::default()).unwrap_err();
+    assert!(error.to_string().contains("1-byte values, not 4-byte ones"), "{}", error);
+}
@@ -0,0 +1,106 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+mod common;
+use std::{time::Duration};
+use memory::{Endianness, MemBackend, MemoryRegion, Process, ProcessMemory, PtraceSession, make_writable, read_bytes_from_process, remote_alloc_near, read_scalar, write_bytes_to_process};
+use common::Target;
+
+impl Target {
+    fn process(&self) -> Process {
+        Process::with_backend(self.pid(), MemBackend::ProcessVmReadv).unwrap()
+    }
+
+    // The executable's first region matching, by permissions like "r--p"
+    fn region(&self, permissions: &str) -> MemoryRegion {
+        let process = self.process();
+        let executable = process.executable_path().unwrap();
+        process.memory_regions().unwrap().into_iter().find(|x| x.pathname == executable && x.permissions() == permissions).unwrap()
+    }
+
+    fn permissions_at(&self, address: usize) -> String {
+        self.process().memory_regions().unwrap().into_iter().find(|x| x.contains(address)).unwrap().permissions()
+    }
+
+    fn still_running(&mut self) {
+        let process = self.process();
+        let before = read_scalar::<i32>(&process, self.player, Endianness::Native).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(read_scalar::<i32>(&process, self.player, Endianness::Native).unwrap() > before);
+        assert!(self.child.try_wait().unwrap().is_none());
+        let status = std::fs::read_to_string(format!("/proc/{}/status", self.pid())).unwrap();
+        assert_eq!(status.lines().find_map(|x| x.strip_prefix("TracerPid:")).unwrap().trim(), "0");
+    }
+}
+
+// process_vm_writev, unlike /proc/<pid>/mem, goes by the page protection, so it only gets in
+// once the target has called mprotect
+#[test]
+fn makes_a_read_only_page_writable_and_back() {
+    let mut target = Target::spawn();
+    let region = target.region("r--p");
+    let bytes = read_bytes_from_process(target.process(), 16, region.start).unwrap();
+    assert!(write_bytes_to_process(target.process(), region.start, &bytes).is_err());
+    let mut protection = make_writable(target.pid(), region.start + 10, 4).unwrap();
+    assert_eq!((protection.start, protection.len()), (region.start, 4096));
+    assert_eq!(target.permissions_at(region.start), "rw-p");
+    write_bytes_to_process(target.process(), region.start, &bytes).unwrap();
+    target.still_running();
+    protection.restore().unwrap();
+    assert_eq!(target.permissions_at(region.start), "r--p");
+    assert!(write_bytes_to_process(target.process(), region.start, &bytes).is_err());
+    target.still_running();
+}
+
+// Code stays executable, which the running tick thread depends on
+#[test]
+fn keeps_the_rest_of_the_protection() {
+    let mut target = Target::spawn();
+    let region = target.region("r-xp");
+    let protection = make_writable(target.pid(), region.start, region.len()).unwrap();
+    assert_eq!(protection.original.iter().map(|x| (x.start, x.end, x.prot)).collect::<Vec<_>>(), [(region.start, region.end, libc::PROT_READ | libc::PROT_EXEC)]);
+    assert_eq!(target.permissions_at(region.start), "rwxp");
+    target.still_running();
+}
+
+#[test]
+fn restores_on_drop_only_when_asked() {
+    let target = Target::spawn();
+    let region = target.region("r--p");
+    drop(make_writable(target.pid(), region.start, 1).unwrap());
+    assert_eq!(target.permissions_at(region.start), "rw-p");
+    let region = target.region("r--p");
+    let mut protection = make_writable(target.pid(), region.start, 1).unwrap();
+    protection.restore_on_drop = true;
+    drop(protection);
+    assert_eq!(target.permissions_at(region.start), "r--p");
+}
+
+#[test]
+fn refuses_what_it_cannot_change() {
+    let mut target = Target::spawn();
+    assert!(make_writable(target.pid(), 0x1000, 16).unwrap_err().to_string().contains("not mapped"));
+    assert!(make_writable(target.pid(), target.player, 4).unwrap_err().to_string().contains("already writable"));
+    target.still_running();
+}
+
+// More than 2 GiB can never all be within reach of a rel32 jump, so nothing is looked for
+#[test]
+fn refuses_allocations_too_large_to_be_near() {
+    let mut target = Target::spawn();
+    let error = remote_alloc_near(target.pid(), target.player, 1 << 32, libc::PROT_READ).unwrap_err().to_string();
+    assert_eq!(error, format!("0x100000000 bytes cannot all be within 2 GiB of 0x{:x}", target.player));
+    target.still_running();
+}
+
+// The mprotect goes through the session already open rather than failing to trace it again
+#[test]
+fn works_through_a_session_already_open() {
+    let mut target = Target::spawn();
+    let region = target.region("r--p");
+    let session = PtraceSession::seize(target.pid()).unwrap();
+    let mut protection = make_writable(target.pid(), region.start, 1).unwrap();
+    assert_eq!(target.permissions_at(region.start), "rw-p");
+    protection.restore().unwrap();
+    assert_eq!(target.permissions_at(region.start), "r--p");
+    drop(session);
+    target.still_running();
+}
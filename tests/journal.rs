@@ -0,0 +1,198 @@
+mod common;
+use std::{io::Write, sync::Mutex, time::{Duration, UNIX_EPOCH}};
+use memory::{Journal, JournalEntry, JournalFile, JournalRecord, MemoryRegion, ProcessMemory, SavedAddress, SavedWrite, SessionFile};
+use nix::unistd::Pid;
+
+const BASE: usize = 0x10000;
+
+// A single read-write region held in a buffer, standing in for a target's memory
+struct Buffer(Mutex<Vec<u8>>);
+
+impl Buffer {
+    fn bytes(&self) -> Vec<u8> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl ProcessMemory for Buffer {
+    fn pid(&self) -> Pid {
+        Pid::from_raw(0)
+    }
+
+    fn read_at(&self, address: usize, buffer: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        let bytes = self.0.lock().unwrap();
+        let offset = address.checked_sub(BASE).filter(|x| *x < bytes.len()).ok_or("unmapped")?;
+        let len = buffer.len().min(bytes.len() - offset);
+        buffer[..len].copy_from_slice(&bytes[offset..offset + len]);
+        Ok(len)
+    }
+
+    fn write_at(&self, address: usize, data: &[u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut bytes = self.0.lock().unwrap();
+        let offset = address.checked_sub(BASE).filter(|x| *x < bytes.len()).ok_or("unmapped")?;
+        let len = data.len().min(bytes.len() - offset);
+        bytes[offset..offset + len].copy_from_slice(&data[..len]);
+        Ok(len)
+    }
+
+    fn memory_regions(&self) -> Result<Vec<MemoryRegion>, Box<dyn std::error::Error>> {
+        let len = self.0.lock().unwrap().len();
+        Ok(vec![format!("{:x}-{:x} rw-p 00000000 00:00 0", BASE, BASE + len).parse()?])
+    }
+
+    fn executable_path(&self) -> Option<String> {
+        None
+    }
+
+    fn thread_stacks(&self) -> Vec<(Pid, usize)> {
+        Vec::new()
+    }
+}
+
+#[test]
+fn undoes_writes_newest_first() {
+    let buffer = Buffer(Mutex::new((0..8).collect()));
+    let mut journal = Journal::default();
+    journal.write(&buffer, BASE, &[0xaa, 0xbb], "write").unwrap();
+    journal.write(&buffer, BASE + 1, &[0xcc], "poke").unwrap();
+    assert_eq!(buffer.bytes()[..3], [0xaa, 0xcc, 2]);
+    assert_eq!(journal.entries[0].old, [0, 1]);
+    assert_eq!(journal.entries[1].old, [0xbb]);
+
+    let undone = journal.undo(&buffer).unwrap().unwrap();
+    assert_eq!((undone.kind.as_str(), undone.address), ("poke", BASE + 1));
+    assert_eq!(buffer.bytes()[..3], [0xaa, 0xbb, 2]);
+    journal.undo(&buffer).unwrap().unwrap();
+    assert_eq!(buffer.bytes(), (0..8).collect::<Vec<u8>>());
+    assert!(journal.undo(&buffer).is_none());
+}
+
+#[test]
+fn only_journals_what_was_written() {
+    let buffer = Buffer(Mutex::new(vec![0; 4]));
+    let mut journal = Journal::default();
+    assert_eq!(journal.write(&buffer, BASE + 2, &[1, 2, 3, 4], "poke").unwrap(), 2);
+    assert_eq!(journal.entries[0].new, [1, 2]);
+    assert!(journal.write(&buffer, BASE + 8, &[1], "poke").is_err());
+    assert_eq!(journal.len(), 1);
+}
+
+#[test]
+fn keeps_entries_that_could_not_be_undone() {
+    let buffer = Buffer(Mutex::new(vec![0; 4]));
+    let mut journal = Journal::default();
+    journal.record(BASE + 100, vec![1], vec![2], "lock");
+    assert!(journal.undo(&buffer).unwrap().is_err());
+    assert_eq!(journal.len(), 1);
+}
+
+// Ids stay with their writes, so one can be undone ahead of those after it and comes back in place
+#[test]
+fn undoes_by_id_and_redoes() {
+    let buffer = Buffer(Mutex::new(vec![0; 4]));
+    let mut journal = Journal::default();
+    journal.write(&buffer, BASE, &[1], "write").unwrap();
+    journal.write(&buffer, BASE + 1, &[2], "poke").unwrap();
+    journal.write(&buffer, BASE + 2, &[3], "write").unwrap();
+    assert_eq!(journal.undo_id(&buffer, 1).unwrap().unwrap().id, 1);
+    assert_eq!(buffer.bytes(), [1, 0, 3, 0]);
+    assert!(journal.undo_id(&buffer, 1).is_none());
+    assert_eq!(journal.undo(&buffer).unwrap().unwrap().id, 2);
+    assert_eq!(journal.redo(&buffer).unwrap().unwrap().id, 2);
+    assert_eq!(journal.redo(&buffer).unwrap().unwrap().id, 1);
+    assert_eq!(buffer.bytes(), [1, 2, 3, 0]);
+    assert_eq!(journal.entries.iter().map(|x| x.id).collect::<Vec<usize>>(), [0, 1, 2]);
+    assert!(journal.redo(&buffer).is_none());
+}
+
+// A new write leaves nothing to redo, and neither does undoing a lock, which is gone with its entry
+#[test]
+fn only_what_can_be_redone_is_kept() {
+    let buffer = Buffer(Mutex::new(vec![0; 4]));
+    let mut journal = Journal::default();
+    journal.write(&buffer, BASE, &[1], "write").unwrap();
+    journal.undo(&buffer).unwrap().unwrap();
+    journal.write(&buffer, BASE, &[2], "write").unwrap();
+    assert!(journal.undone.is_empty());
+    journal.record(BASE + 1, vec![0], vec![5], "lock");
+    journal.undo(&buffer).unwrap().unwrap();
+    assert!(journal.undone.is_empty() && journal.len() == 1);
+}
+
+// Writes from before the journal go first but take ids after those it has given out
+#[test]
+fn prepended_writes_are_numbered_after() {
+    let buffer = Buffer(Mutex::new(vec![0; 4]));
+    let mut journal = Journal::default();
+    journal.write(&buffer, BASE, &[1], "write").unwrap();
+    let saved = JournalEntry { id: 0, address: BASE + 3, old: vec![9], new: vec![0], time: UNIX_EPOCH, kind: "poke".to_string() };
+    journal.prepend(vec![saved]);
+    assert_eq!(journal.entries.iter().map(|x| (x.id, x.address)).collect::<Vec<(usize, usize)>>(), [(1, BASE + 3), (0, BASE)]);
+    journal.write(&buffer, BASE + 1, &[1], "write").unwrap();
+    assert_eq!(journal.entries[2].id, 2);
+    journal.undo_id(&buffer, 1).unwrap().unwrap();
+    journal.redo(&buffer).unwrap().unwrap();
+    assert_eq!(journal.entries[0].id, 1);
+}
+
+#[test]
+fn saves_and_loads_journaled_writes() {
+    let path = std::env::temp_dir().join(format!("journal-{}.session", std::process::id()));
+    let path = path.to_str().unwrap();
+    let writes = vec![
+        SavedWrite { address: SavedAddress::Module { name: "game".to_string(), offset: 0x10 }, old: vec![0, 1], new: vec![2, 3], time: UNIX_EPOCH + Duration::from_nanos(1_700_000_000_123_456_789), kind: "write".to_string() },
+        SavedWrite { address: SavedAddress::Absolute(0x7f00), old: vec![0xff], new: vec![0], time: UNIX_EPOCH, kind: "lock".to_string() },
+    ];
+    let file = SessionFile { writes, ..SessionFile::default() };
+    file.save(path).unwrap();
+    let loaded = SessionFile::load(path);
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(loaded.unwrap(), file);
+}
+
+#[test]
+fn a_journal_file_survives_being_cut_short() {
+    let path = std::env::temp_dir().join(format!("journal-{}.jsonl", std::process::id()));
+    let record = |action: &str, address| JournalRecord { time: UNIX_EPOCH, pid: 4242, action: action.to_string(), address, absolute: 0x7f00, old: vec![0], new: vec![1], command: "poke game+0x10 01".to_string(), lock: None };
+    let mut file = JournalFile::open(&path).unwrap();
+    file.append(&record("poke", SavedAddress::Module { name: "game".to_string(), offset: 0x10 })).unwrap();
+    // Reopening appends rather than starting over
+    let mut file = JournalFile::open(&path).unwrap();
+    file.append(&record("write", SavedAddress::Absolute(0x7f00))).unwrap();
+    let line = std::fs::read_to_string(&path).unwrap().lines().next().unwrap().to_string();
+    std::fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(&line.as_bytes()[..line.len() / 2]).unwrap();
+    let records = JournalFile::read(&path).unwrap();
+    assert_eq!(records.iter().map(|x| x.action.as_str()).collect::<Vec<&str>>(), ["poke", "write"]);
+    assert_eq!(records[0], record("poke", SavedAddress::Module { name: "game".to_string(), offset: 0x10 }));
+    // Anywhere but at the end, a broken line is an error
+    std::fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(format!("\n{}\n", line).as_bytes()).unwrap();
+    assert!(JournalFile::read(&path).unwrap_err().to_string().contains("line 3"));
+    std::fs::remove_file(path).unwrap();
+}
+
+// The victim's hp is an i32 at the start of its player, 100 to begin with, and only changes on a tick
+#[cfg(target_os = "linux")]
+#[test]
+fn the_cli_undoes_and_redoes_writes() {
+    use std::{io::{BufRead, BufReader}, process::{Command, Stdio}};
+    let path = common::victim_path();
+    let mut victim = Command::new(path).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut lines = BufReader::new(victim.stdout.take().unwrap()).lines();
+    let pid = lines.next().unwrap().unwrap().strip_prefix("pid ").unwrap().to_string();
+    let player = lines.next().unwrap().unwrap();
+    let hp = usize::from_str_radix(player.strip_prefix("player 0x").unwrap().split_once(' ').unwrap().0, 16).unwrap();
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(&pid).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let commands = "scan i32 100\nwrite {hp} 5\nwrite {hp} 6\npoke {gold} 01\nundo #0\nwrite {hp} 7 --force\nundo\nundo\nredo\njournal\nundo #9\nredo\nredo";
+    writeln!(session.stdin.take().unwrap(), "{}", commands.replace("{hp}", &format!("0x{:x}", hp)).replace("{gold}", &format!("0x{:x}", hp + 8))).unwrap();
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    let value = memory::read_scalar::<i32>(Pid::from_raw(pid.parse().unwrap()), hp, memory::Endianness::Native).unwrap();
+    let _ = victim.kill();
+    let _ = victim.wait();
+    // Undoing #0 puts back 100 under the 6 and the 01 written since
+    assert!(stdout.contains("warning: #0 is not the most recent write, and 1 later writes overlap it"), "{}", stdout);
+    assert!(stdout.contains("holds 06 00 00 00 rather than the 05 00 00 00 expected, so the target may have changed it since"), "{}", stdout);
+    assert!(stdout.contains("(undoing #3 write 07 00 00 00)") && stdout.contains("(undoing #2 poke 01)") && stdout.contains("(redoing #2 poke)"), "{}", stdout);
+    assert!(stdout.contains("1 undone, `redo` writes #3 again"), "{}", stdout);
+    assert!(stdout.contains("error: No journaled write #9; `journal` lists them") && stdout.contains("nothing to redo"), "{}", stdout);
+    assert_eq!(value, 7, "{}", stdout);
+}
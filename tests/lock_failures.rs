@@ -0,0 +1,59 @@
+use std::{sync::{Arc, Mutex}, time::{Duration, Instant}};
+use memory::{Errno, LOCK_DEAD_AFTER, LockEntry, LockFailureCallback, LockManager, LockStatus, MockProcess};
+
+// Polls the lock until the check holds of it, for the servicing thread's ticks
+fn wait_until(locks: &LockManager<&'static MockProcess>, address: usize, check: impl Fn(&LockEntry) -> bool) -> LockEntry {
+    let start = Instant::now();
+    loop {
+        let lock = locks.get(address).unwrap();
+        if check(&lock) || start.elapsed() > Duration::from_secs(5) {
+            return lock;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+fn mock() -> &'static MockProcess {
+    let mock: &'static MockProcess = Box::leak(Box::new(MockProcess::new(100)));
+    mock.map("10000-11000 rw-p 00000000 00:00 0 [heap]").unwrap();
+    mock.map("20000-21000 r--p 00000000 08:01 42 /opt/game/game").unwrap();
+    mock
+}
+
+// A lock on a read-only page fails with EACCES every tick, but only the first has the maps read
+#[test]
+fn a_failing_lock_reads_the_maps_once() {
+    let mock = mock();
+    let mut locks = LockManager::new(mock);
+    let before = mock.maps_reads();
+    locks.lock_value(7i32, 0x20008, Duration::from_millis(1));
+    let lock = wait_until(&locks, 0x20008, |x| x.consecutive_failures >= 20);
+    assert!(lock.consecutive_failures >= 20);
+    assert_eq!(lock.last_errno, Some(Errno::EACCES));
+    assert_eq!(mock.maps_reads() - before, 1);
+    locks.remove_all();
+}
+
+// The callback hears of a lock turning failing and then dead once each, not on every failed tick,
+// and a write that goes through again puts it back to running
+#[test]
+fn failures_are_counted_and_reported_once() {
+    let mock = mock();
+    let mut locks = LockManager::new(mock);
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let sink = reported.clone();
+    locks.set_failure_callback(Some(LockFailureCallback(Arc::new(move |address, status, errno| sink.lock().unwrap().push((address, status, errno))))));
+    locks.lock_value(7i32, 0x30000, Duration::from_micros(1));
+    let lock = wait_until(&locks, 0x30000, |x| x.status() == LockStatus::Dead);
+    assert_eq!((lock.status(), lock.consecutive_failures, lock.writes, lock.last_errno), (LockStatus::Dead, LOCK_DEAD_AFTER, 0, Some(Errno::EFAULT)));
+    assert_eq!(*reported.lock().unwrap(), [(0x30000, LockStatus::Failing, Some(Errno::EFAULT)), (0x30000, LockStatus::Dead, Some(Errno::EFAULT))]);
+    // Dead locks are no longer serviced, until resumed
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(locks.get(0x30000).unwrap().failures, LOCK_DEAD_AFTER);
+    mock.map("30000-31000 rw-p 00000000 00:00 0").unwrap();
+    assert!(locks.resume(0x30000));
+    let lock = wait_until(&locks, 0x30000, |x| x.writes > 0);
+    assert_eq!((lock.status(), lock.consecutive_failures, lock.failures), (LockStatus::Running, 0, LOCK_DEAD_AFTER));
+    assert_eq!(reported.lock().unwrap().len(), 2);
+    locks.remove_all();
+}
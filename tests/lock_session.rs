@@ -0,0 +1,61 @@
+#![cfg(target_os = "linux")]
+mod common;
+use std::{io::{BufRead, BufReader, Write}, process::{Child, Command, Stdio}};
+use common::victim_path;
+
+// A victim, with where its player and the static pointing at it are
+struct Victim {
+    child: Child,
+    pid: String,
+    player: usize,
+    pointer: usize,
+}
+
+impl Victim {
+    fn spawn() -> Victim {
+        let mut child = Command::new(victim_path()).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+        let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+        let mut next = |prefix: &str| lines.next().unwrap().unwrap().strip_prefix(prefix).unwrap().to_string();
+        let pid = next("pid ");
+        let player = usize::from_str_radix(next("player 0x").split_once(' ').unwrap().0, 16).unwrap();
+        let pointer = usize::from_str_radix(&next("pointer 0x"), 16).unwrap();
+        Victim { child, pid, player, pointer }
+    }
+
+    fn run(&self, commands: &str) -> String {
+        let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(&self.pid).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+        writeln!(session.stdin.take().unwrap(), "{}", commands).unwrap();
+        String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap()
+    }
+}
+
+impl Drop for Victim {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// The static is in the executable, so its lock is saved module-relative and comes back running in
+// a new instance. The heap only has absolute addresses, so the active lock there is loaded paused
+// with a warning, and the one that was paused stays paused without one
+#[test]
+fn locks_are_saved_and_restored_in_a_new_instance() {
+    let path = std::env::temp_dir().join(format!("rmh-lock-session-{}", std::process::id())).to_string_lossy().into_owned();
+    let before = Victim::spawn();
+    let stdout = before.run(&format!("lock 0x{:x} u64 --interval 50ms\nlock 0x{:x} i32 set 50\nlock 0x{:x} i64 set 1\nlocks pause 0x{:x}\nsave {}", before.pointer, before.player, before.player + 8, before.player + 8, path));
+    assert!(!stdout.contains("error:"), "{}", stdout);
+    let saved = std::fs::read_to_string(&path).unwrap();
+    assert!(saved.contains("\"address\": \"victim+0x") && saved.contains(&format!("\"address\": \"0x{:x}\"", before.player)), "{}", saved);
+    assert!(saved.contains("\"enabled\": false"), "{}", saved);
+    let after = Victim::spawn();
+    let stdout = after.run(&format!("load {}\nlocks", path));
+    std::fs::remove_file(&path).unwrap();
+    assert!(stdout.contains("restored 3 of 3 locks from "), "{}", stdout);
+    assert!(stdout.contains(&format!("warning: lock at 0x{:x} has no module to re-resolve against and was loaded paused", before.player)), "{}", stdout);
+    assert!(!stdout.contains(&format!("warning: lock at 0x{:x} ", before.player + 8)), "{}", stdout);
+    let listed = |address: String| stdout.lines().find(|x| x.contains(&address)).unwrap_or_else(|| panic!("{}", stdout)).to_string();
+    let module = listed(format!("(0x{:x}) u64 = ", after.pointer));
+    assert!(module.contains("victim+0x") && module.ends_with(" every 50ms (running)"), "{}", stdout);
+    assert!(listed(format!("0x{:x} (", before.player)).ends_with("(paused)") && listed(format!("0x{:x} (", before.player + 8)).ends_with("(paused)"), "{}", stdout);
+}
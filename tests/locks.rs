@@ -0,0 +1,212 @@
+mod common;
+use std::time::{Duration, Instant};
+use memory::{Endianness, LockAction, LockManager, LockStatus, MockProcess, Scalar};
+
+// Polls until the read gives the value, for writes made by a lock's servicing thread
+fn wait_for<T: PartialEq>(read: impl Fn() -> T, expected: T) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(5) {
+        if read() == expected {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    false
+}
+
+fn mock() -> &'static MockProcess {
+    let mock: &'static MockProcess = Box::leak(Box::new(MockProcess::new(100)));
+    mock.map("10000-11000 rw-p 00000000 00:00 0 [heap]").unwrap();
+    mock.plant_value(0x10000, 100i32).unwrap();
+    mock
+}
+
+fn value(mock: &MockProcess, address: usize) -> i32 {
+    i32::from_bytes(&mock.bytes(address, 4).unwrap())
+}
+
+// A lock writes on its first tick and then once an interval, which a live lock can have changed
+#[test]
+fn a_live_locks_interval_can_be_changed() {
+    let mock = mock();
+    let mut locks = LockManager::new(mock);
+    locks.lock_value(7i32, 0x10000, Duration::from_secs(3600));
+    assert!(wait_for(|| value(mock, 0x10000), 7));
+    mock.plant_value(0x10000, 100i32).unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!((value(mock, 0x10000), locks.get(0x10000).unwrap().writes), (100, 1));
+    assert!(locks.set_interval(0x10000, Duration::from_millis(1)));
+    assert!(wait_for(|| value(mock, 0x10000), 7));
+    assert_eq!(locks.get(0x10000).unwrap().interval, Duration::from_millis(1));
+    assert!(!locks.set_interval(0x20000, Duration::from_millis(1)));
+    locks.remove_all();
+}
+
+// What is there is what is frozen, and an address that cannot be read makes no lock
+#[test]
+fn locks_the_current_value() {
+    let mock = mock();
+    let mut locks = LockManager::new(mock);
+    mock.plant_value(0x10000, 42i32).unwrap();
+    locks.lock_current_value::<i32>(0x10000, Duration::from_millis(1)).unwrap();
+    let lock = locks.get(0x10000).unwrap();
+    assert_eq!((lock.type_name, lock.value::<i32>(), lock.value::<u32>()), ("i32", Some(42), None));
+    mock.plant_value(0x10000, 1i32).unwrap();
+    assert!(wait_for(|| value(mock, 0x10000), 42));
+    assert!(locks.lock_current_value::<i32>(0x50000, Duration::from_millis(1)).is_err());
+    assert_eq!(locks.len(), 1);
+    locks.remove_all();
+}
+
+// Paused locks stay listed but write nothing, and once the manager is dropped nothing writes at all
+#[test]
+fn locks_are_paused_resumed_and_removed_together() {
+    let mock = mock();
+    let mut locks = LockManager::new(mock);
+    locks.lock_value(7i32, 0x10000, Duration::from_millis(1));
+    locks.lock_value(8i32, 0x10004, Duration::from_millis(1));
+    assert!(wait_for(|| (value(mock, 0x10000), value(mock, 0x10004)), (7, 8)));
+    locks.pause_all();
+    assert_eq!(locks.list().iter().map(|x| (x.address, x.status())).collect::<Vec<(usize, LockStatus)>>(), [(0x10000, LockStatus::Paused), (0x10004, LockStatus::Paused)]);
+    assert_eq!(LockStatus::Paused.to_string(), "paused");
+    mock.plant_value(0x10000, 0i32).unwrap();
+    mock.plant_value(0x10004, 0i32).unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!((value(mock, 0x10000), value(mock, 0x10004)), (0, 0));
+    locks.resume_all();
+    assert!(locks.list().iter().all(|x| x.status() == LockStatus::Running));
+    assert!(wait_for(|| (value(mock, 0x10000), value(mock, 0x10004)), (7, 8)));
+    assert!(locks.unlock_value(0x10000) && !locks.unlock_value(0x10000));
+    assert_eq!(locks.len(), 1);
+    drop(locks);
+    mock.plant_value(0x10004, 0i32).unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(value(mock, 0x10004), 0);
+}
+
+#[test]
+fn actions_apply_to_the_current_value() {
+    assert_eq!(LockAction::Set(1).apply(9), 1);
+    assert_eq!((LockAction::Add(10).apply(5), LockAction::Add(10).apply(i32::MAX - 5)), (15, i32::MAX));
+    assert_eq!(LockAction::Sub(10u8).apply(3), 0);
+    assert_eq!((LockAction::Min(500).apply(600), LockAction::Min(500).apply(400)), (500, 400));
+    assert_eq!((LockAction::Max(500).apply(400), LockAction::Max(500).apply(600)), (500, 600));
+    assert_eq!((LockAction::Add(10).to_string(), LockAction::Max(0.5f32).to_string()), ("add 10".to_string(), "max 0.5".to_string()));
+}
+
+// Every tick reads the value and writes it back changed; a tick whose read fails writes nothing
+#[test]
+fn the_servicing_thread_applies_the_action_each_tick() {
+    let mock = mock();
+    let mut locks = LockManager::new(mock);
+    locks.lock_with_action(LockAction::Add(10), 0x10000, Duration::from_millis(1), Endianness::Native);
+    assert!(wait_for(|| value(mock, 0x10000) >= 150, true));
+    let lock = locks.get(0x10000).unwrap();
+    assert_eq!((lock.action.as_str(), lock.value::<i32>()), ("add 10", Some(10)));
+    locks.lock_with_action(LockAction::Add(1i32), 0x50000, Duration::from_millis(1), Endianness::Native);
+    assert!(wait_for(|| locks.get(0x50000).unwrap().failures >= 5, true));
+    assert_eq!(locks.get(0x50000).unwrap().writes, 0);
+    locks.remove_all();
+}
+
+// The whole buffer is written every tick, listed as bytes
+#[test]
+fn byte_locks_write_the_whole_buffer() {
+    let mock = mock();
+    let mut locks = LockManager::new(mock);
+    locks.lock_bytes(b"PLAYER1\0".to_vec(), 0x10010, Duration::from_millis(1));
+    assert!(wait_for(|| mock.bytes(0x10010, 8).unwrap(), b"PLAYER1\0".to_vec()));
+    mock.plant(0x10012, b"xx").unwrap();
+    assert!(wait_for(|| mock.bytes(0x10010, 8).unwrap(), b"PLAYER1\0".to_vec()));
+    let lock = locks.get(0x10010).unwrap();
+    assert_eq!((lock.type_name, lock.action.as_str(), &*lock.value_bytes), ("bytes", "set", &b"PLAYER1\0"[..]));
+    locks.remove_all();
+}
+
+// Listings show the length and a preview in hex and ASCII
+#[cfg(target_os = "linux")]
+#[test]
+fn the_cli_freezes_strings() {
+    use std::{io::{BufRead, BufReader, Write}, process::{Command, Stdio}};
+    let mut victim = Command::new(common::victim_path()).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut lines = BufReader::new(victim.stdout.take().unwrap()).lines();
+    let pid = lines.next().unwrap().unwrap().strip_prefix("pid ").unwrap().to_string();
+    let player = lines.next().unwrap().unwrap();
+    let player = usize::from_str_radix(player.strip_prefix("player 0x").unwrap().split_once(' ').unwrap().0, 16).unwrap();
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(&pid).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    writeln!(session.stdin.take().unwrap(), "lock 0x{:x} string PLAYER1\nlocks\nquit", player + 0x100).unwrap();
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    let _ = victim.kill();
+    let _ = victim.wait();
+    assert!(stdout.contains(" bytes = 7 bytes 50 4c 41 59 45 52 31 |PLAYER1| every "), "{}", stdout);
+}
+
+// An expired lock stops writing but stays listed until renewed for another run of its duration
+#[test]
+fn locks_expire_and_are_renewed() {
+    let mock = mock();
+    let mut locks = LockManager::new(mock);
+    locks.lock_value(7i32, 0x10000, Duration::from_millis(1));
+    assert!(!locks.renew(0x10000));
+    assert!(locks.set_duration(0x10000, Some(Duration::from_millis(50))));
+    assert!(locks.get(0x10000).unwrap().remaining().is_some_and(|x| x <= Duration::from_millis(50)));
+    assert!(wait_for(|| locks.get(0x10000).unwrap().status(), LockStatus::Expired));
+    assert_eq!(locks.get(0x10000).unwrap().remaining(), Some(Duration::ZERO));
+    mock.plant_value(0x10000, 0i32).unwrap();
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(value(mock, 0x10000), 0);
+    assert!(locks.renew(0x10000));
+    assert_eq!(locks.get(0x10000).unwrap().status(), LockStatus::Running);
+    assert!(wait_for(|| value(mock, 0x10000), 7));
+    assert!(locks.set_duration(0x10000, None) && !locks.set_duration(0x20000, None));
+    assert_eq!(locks.get(0x10000).unwrap().remaining(), None);
+    locks.remove_all();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn the_cli_lists_the_time_left() {
+    use std::{io::{BufRead, BufReader, Write}, process::{Command, Stdio}};
+    let mut victim = Command::new(common::victim_path()).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut lines = BufReader::new(victim.stdout.take().unwrap()).lines();
+    let pid = lines.next().unwrap().unwrap().strip_prefix("pid ").unwrap().to_string();
+    let player = lines.next().unwrap().unwrap();
+    let player = usize::from_str_radix(player.strip_prefix("player 0x").unwrap().split_once(' ').unwrap().0, 16).unwrap();
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(&pid).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    writeln!(session.stdin.take().unwrap(), "lock 0x{:x} i32 99 --for 2m\nlock 0x{:x} i64 1 --for 1h\nlocks\nlock renew 0x{:x}\nquit", player, player + 8, player + 8).unwrap();
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    let _ = victim.kill();
+    let _ = victim.wait();
+    let line = stdout.lines().find(|x| x.contains(" i32 = 99 every ")).unwrap_or_else(|| panic!("{}", stdout));
+    assert!(line.contains(" (running, 119.") || line.contains(" (running, 120.0s left)"), "{}", stdout);
+    assert!(stdout.contains("error: Unknown duration unit 'h', expected ns, us, ms, s or m"), "{}", stdout);
+    assert!(stdout.contains(&format!("error: No lock with a duration at 0x{:x}", player + 8)), "{}", stdout);
+}
+
+// A drift within the tolerance is left alone, and anything further is put back at the value
+#[test]
+fn holds_write_only_past_the_tolerance() {
+    let hold = LockAction::Hold(100.0f32, 0.5);
+    assert!(!hold.needs_write(100.0) && !hold.needs_write(100.5) && !hold.needs_write(99.6));
+    assert_eq!((hold.needs_write(100.6), hold.apply(100.6), hold.apply(100.2)), (true, 100.0, 100.2));
+    assert!(hold.needs_write(f32::NAN) && LockAction::Hold(f32::NAN, 0.5).needs_write(100.0));
+    let exact = LockAction::Hold(100i32, 0);
+    assert!(!exact.needs_write(100) && exact.needs_write(99));
+    assert_eq!(hold.to_string(), "hold 100 0.5");
+}
+
+// Only the corrective writes are counted, as an undisturbed value is only read
+#[test]
+fn holds_count_their_corrective_writes() {
+    let mock = mock();
+    let mut locks = LockManager::new(mock);
+    locks.lock_with_action(LockAction::Hold(100i32, 2), 0x10000, Duration::from_millis(1), Endianness::Native);
+    mock.plant_value(0x10000, 98i32).unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!((value(mock, 0x10000), locks.get(0x10000).unwrap().writes), (98, 0));
+    mock.plant_value(0x10000, 90i32).unwrap();
+    assert!(wait_for(|| value(mock, 0x10000), 100));
+    let lock = locks.get(0x10000).unwrap();
+    assert_eq!((lock.writes, lock.action.as_str()), (1, "hold 100 2"));
+    locks.remove_all();
+}
@@ -0,0 +1,89 @@
+use std::{io::Write, process::{Child, Command, Stdio}};
+use memory::{ErrorCode, MACHINE_VERSION, MachineError, MachineMessage, ResultStatus};
+
+// Something small to attach to, killed when dropped
+struct Target(Child);
+
+impl Target {
+    fn spawn() -> Target {
+        Target(Command::new("sleep").arg("60").spawn().unwrap())
+    }
+}
+
+impl Drop for Target {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+// Runs memory --machine against the target with the commands on standard input, returning every
+// line it wrote parsed back
+fn run(target: &Target, arguments: &[&str], commands: &str) -> Vec<MachineMessage> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_memory")).arg(target.0.id().to_string()).arg("--machine").args(arguments).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    child.stdin.take().unwrap().write_all(commands.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap().lines().map(|x| serde_json::from_str::<MachineMessage>(x).unwrap_or_else(|e| panic!("{}: {}", x, e))).collect()
+}
+
+fn results(messages: &[MachineMessage]) -> Vec<(String, ResultStatus, Option<ErrorCode>)> {
+    messages.iter().filter_map(|x| match x {
+        MachineMessage::Result { command, status, error, .. } => Some((command.clone(), *status, error.as_ref().map(|x| x.code))),
+        _ => None,
+    }).collect()
+}
+
+#[test]
+fn answers_every_command_in_order() {
+    let target = Target::spawn();
+    let messages = run(&target, &[], "maps\nfrobnicate\nscan i32 123456789\nrescan abc\nload /nonexistent/session\n");
+    assert_eq!(messages[0], MachineMessage::Attached { pid: target.0.id() as i32, backend: "process_vm".to_string(), version: MACHINE_VERSION });
+    assert_eq!(results(&messages), [
+        ("maps".to_string(), ResultStatus::Ok, None),
+        ("frobnicate".to_string(), ResultStatus::Error, Some(ErrorCode::Invalid)),
+        ("scan i32 123456789".to_string(), ResultStatus::Ok, None),
+        ("rescan abc".to_string(), ResultStatus::Error, Some(ErrorCode::Invalid)),
+        ("load /nonexistent/session".to_string(), ResultStatus::Error, Some(ErrorCode::Io)),
+    ]);
+    let Some(MachineMessage::Result { output, .. }) = messages.iter().find(|x| matches!(x, MachineMessage::Result { command, .. } if command == "maps")) else {
+        panic!("no result for maps");
+    };
+    assert!(output.iter().any(|x| x.ends_with("[stack]")));
+}
+
+// Anything printed outside a command, here the config file's failure, is a message event
+#[test]
+fn reports_the_rest_as_events() {
+    let target = Target::spawn();
+    let config = std::env::temp_dir().join(format!("rmh-machine-{}", std::process::id()));
+    std::fs::write(&config, "frobnicate\n").unwrap();
+    let messages = run(&target, &["--config", config.to_str().unwrap()], "");
+    std::fs::remove_file(&config).unwrap();
+    assert!(matches!(&messages[1], MachineMessage::Message { text } if text.ends_with(":1: Unknown command 'frobnicate'")), "{:?}", messages);
+    assert!(results(&messages).is_empty());
+}
+
+// The wire format frontends are written against
+#[test]
+fn keeps_the_schema_stable() {
+    let error = MachineMessage::Result { command: "write 0x10 i32:1".to_string(), status: ResultStatus::Error, output: Vec::new(), error: Some(MachineError { code: ErrorCode::Os, message: "Bad address".to_string(), errno: Some(14) }) };
+    assert_eq!(serde_json::to_string(&error).unwrap(), r#"{"type":"result","command":"write 0x10 i32:1","status":"error","output":[],"error":{"code":"os","message":"Bad address","errno":14}}"#);
+    let failure = MachineMessage::LockFailure { address: "0x1000".to_string(), status: "failing".to_string(), error: None };
+    assert_eq!(serde_json::to_string(&failure).unwrap(), r#"{"type":"lock_failure","address":"0x1000","status":"failing"}"#);
+    let progress = MachineMessage::Progress { bytes_scanned: 1, bytes_total: 2, rate: 0.5 };
+    assert_eq!(serde_json::to_string(&progress).unwrap(), r#"{"type":"progress","bytes_scanned":1,"bytes_total":2,"rate":0.5}"#);
+    // Fields added later are ignored by older readers
+    let later = r#"{"type":"message","text":"hello","level":"info"}"#;
+    assert_eq!(serde_json::from_str::<MachineMessage>(later).unwrap(), MachineMessage::Message { text: "hello".to_string() });
+}
+
+#[test]
+fn classifies_errors_by_type() {
+    let errno: Box<dyn std::error::Error> = Box::new(nix::errno::Errno::EPERM);
+    assert_eq!(MachineError::from_error(errno.as_ref()), MachineError { code: ErrorCode::Os, message: errno.to_string(), errno: Some(1) });
+    let parse: Box<dyn std::error::Error> = "x".parse::<u32>().unwrap_err().into();
+    assert_eq!(MachineError::from_error(parse.as_ref()).code, ErrorCode::Invalid);
+    let other: Box<dyn std::error::Error> = "No scan to rescan".into();
+    assert_eq!(MachineError::from_error(other.as_ref()), MachineError { code: ErrorCode::Failed, message: "No scan to rescan".to_string(), errno: None });
+}
@@ -0,0 +1,47 @@
+#![cfg(target_os = "macos")]
+use memory::{Endianness, MemBackend, Pid, Process, ProcessMemory, ScanOptions, find_value, modules_from_regions, read_scalar, reduce_found_values, write_scalar};
+
+// The harness scanning its own memory, which needs no entitlement
+#[test]
+fn scans_its_own_process() {
+    let planted = Box::into_raw(Box::new(0x5eed_cafe_f00d_1234u64));
+    let process = Process::attach(Pid::this()).unwrap();
+    assert_eq!(process.backend(), MemBackend::Mach);
+    let options = ScanOptions { alignment: Some(8), ..ScanOptions::default() };
+    let (mut found, mut stats) = find_value(&process, 0x5eed_cafe_f00d_1234u64, &options).unwrap();
+    assert!(found.contains(&(planted as usize)));
+    write_scalar(&process, planted as usize, 0x0bad_f00du64, Endianness::Native).unwrap();
+    assert_eq!(unsafe { *planted }, 0x0bad_f00d);
+    reduce_found_values(&process, &mut found, 0x0bad_f00du64, &options, &mut stats).unwrap();
+    assert_eq!(found, [planted as usize]);
+    assert_eq!(read_scalar::<u64>(&process, planted as usize, Endianness::Native).unwrap(), 0x0bad_f00d);
+    assert!(process.read_at(0, &mut [0u8; 4]).is_err());
+}
+
+#[test]
+fn maps_regions_and_the_executable() {
+    let planted = Box::into_raw(Box::new(0u64));
+    let process = Process::attach(Pid::this()).unwrap();
+    let regions = process.memory_regions().unwrap();
+    assert!(regions.windows(2).all(|x| x[0].end <= x[1].start));
+    let heap = regions.iter().find(|x| x.contains(planted as usize)).unwrap();
+    assert!(heap.readable && heap.writable && !heap.executable);
+    let executable = process.executable_path().unwrap();
+    assert_eq!(std::fs::canonicalize(&executable).unwrap(), std::fs::canonicalize(std::env::current_exe().unwrap()).unwrap());
+    let modules = modules_from_regions(&regions);
+    let main = modules.iter().find(|x| x.path == executable).unwrap();
+    assert!(main.executable && main.contains(maps_regions_and_the_executable as *const () as usize));
+}
+
+// Refusals say why rather than just failing
+#[test]
+fn explains_what_it_cannot_do() {
+    assert!(Process::attach(Pid::from_raw(i32::MAX)).unwrap_err().to_string().contains("No such process"));
+    // launchd is covered by System Integrity Protection, even for root
+    let refused = Process::attach(Pid::from_raw(1)).unwrap_err().to_string();
+    assert!(refused.contains("task_for_pid refused process 1") && refused.contains("System Integrity Protection"), "{}", refused);
+    for backend in [MemBackend::ProcessVmReadv, MemBackend::ProcMem] {
+        assert!(Process::with_backend(Pid::this(), backend).unwrap_err().to_string().contains("only available on Linux"));
+    }
+    assert!(Process::seize(Pid::this()).is_err());
+}
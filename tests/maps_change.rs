@@ -0,0 +1,65 @@
+use memory::{MOCK_PAGE_SIZE, MapsChange, MapsFingerprint, MockProcess, ScanOptions, ScanStats, find_value, reduce_found_values};
+
+const HEAP: usize = 0x10_0000;
+const ARENA: usize = 0x40_0000;
+const FILE: usize = 0x50_0000;
+const MOVED: usize = 0x60_0000;
+const NEW: usize = 0x70_0000;
+
+// A heap, an anonymous arena and a page of a file, each with a 5 in it, scanned for it
+fn scanned() -> (MockProcess, Vec<usize>, ScanStats) {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", HEAP, HEAP + MOCK_PAGE_SIZE)).unwrap();
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0", ARENA, ARENA + MOCK_PAGE_SIZE)).unwrap();
+    mock.map(&format!("{:x}-{:x} rw-p 00002000 08:01 42 /opt/game/level.dat", FILE, FILE + MOCK_PAGE_SIZE)).unwrap();
+    for address in [HEAP + 8, ARENA + 8, FILE + 8] {
+        mock.plant_value(address, 5u32).unwrap();
+    }
+    let (found, stats) = find_value(&mock, 5u32, &ScanOptions::default()).unwrap();
+    assert_eq!((found.len(), stats.maps.as_ref().map(|x| x.regions.len())), (3, Some(3)));
+    (mock, found, stats)
+}
+
+// The arena is unmapped, the file page moved and a new mapping made, as loading a level might
+fn relayout(mock: &MockProcess) {
+    mock.unmap(ARENA).unwrap();
+    mock.move_region(FILE, MOVED).unwrap();
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0", NEW, NEW + MOCK_PAGE_SIZE)).unwrap();
+}
+
+#[test]
+fn reduces_report_how_the_layout_changed() {
+    let (mock, mut found, mut stats) = scanned();
+    reduce_found_values(&mock, &mut found, 5u32, &ScanOptions::default(), &mut stats).unwrap();
+    assert_eq!(stats.maps_change, None);
+    relayout(&mock);
+    reduce_found_values(&mock, &mut found, 5u32, &ScanOptions::default(), &mut stats).unwrap();
+    let change = MapsChange { appeared: vec![(NEW, NEW + MOCK_PAGE_SIZE)], disappeared: vec![(ARENA, ARENA + MOCK_PAGE_SIZE)], moved: vec![((FILE, FILE + MOCK_PAGE_SIZE), (MOVED, MOVED + MOCK_PAGE_SIZE))] };
+    assert_eq!(stats.maps_change, Some(change));
+    // Only reported, so results that went with their mappings are kept
+    assert_eq!((&found[..], stats.dropped_unmapped), (&[HEAP + 8, ARENA + 8, FILE + 8][..], 0));
+    // The layout is now the new one, so the next reduce finds nothing changed
+    reduce_found_values(&mock, &mut found, 5u32, &ScanOptions::default(), &mut stats).unwrap();
+    assert_eq!(stats.maps_change, None);
+}
+
+#[test]
+fn drop_unmapped_drops_results_no_mapping_holds() {
+    let (mock, mut found, mut stats) = scanned();
+    relayout(&mock);
+    reduce_found_values(&mock, &mut found, 5u32, &ScanOptions { drop_unmapped: true, ..ScanOptions::default() }, &mut stats).unwrap();
+    assert!(stats.maps_change.is_some());
+    assert_eq!((found, stats.dropped_unmapped), (vec![HEAP + 8], 2));
+}
+
+// An anonymous mapping that grew in place counts as moved; one of a file only if its size held
+#[test]
+fn fingerprints_tell_moves_from_replacements() {
+    let before = MapsFingerprint { regions: vec![(0x1000, 0x2000, 0), (0x5000, 0x6000, 42)] };
+    let grown = MapsFingerprint { regions: vec![(0x1000, 0x3000, 0), (0x8000, 0x9000, 42)] };
+    assert_eq!(before.compare(&grown), MapsChange { appeared: vec![], disappeared: vec![], moved: vec![((0x1000, 0x2000), (0x1000, 0x3000)), ((0x5000, 0x6000), (0x8000, 0x9000))] });
+    let replaced = MapsFingerprint { regions: vec![(0x4000, 0x5000, 0), (0x5000, 0x7000, 42)] };
+    let change = before.compare(&replaced);
+    assert_eq!((change.appeared, change.disappeared, change.moved), (vec![(0x4000, 0x5000), (0x5000, 0x7000)], vec![(0x1000, 0x2000), (0x5000, 0x6000)], vec![]));
+    assert!(before.compare(&before).is_empty());
+}
@@ -0,0 +1,55 @@
+use std::{io::Write, path::PathBuf, process::{Command, Stdio}};
+
+fn temp_path(name: &str) -> String {
+    let path: PathBuf = std::env::temp_dir().join(format!("rmh-marks-{}-{}", std::process::id(), name));
+    path.to_string_lossy().into_owned()
+}
+
+// Runs the commands on the capture's scan for 31337, which finds game+0x4010, game+0x4101,
+// game+0x5ffc, heap+0xffffe and 0x121fffc
+fn offline(commands: &str) -> String {
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).args(["--offline", "tests/fixtures/scan_before.cap"]).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    writeln!(session.stdin.take().unwrap(), "scan i32 31337\n{}", commands).unwrap();
+    String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap()
+}
+
+fn listed(stdout: &str) -> Vec<&str> {
+    stdout.lines().map(|x| x.trim_start_matches("> ")).filter(|x| x.starts_with('#')).collect()
+}
+
+#[test]
+fn marks_are_shown_and_listed_on_their_own() {
+    let stdout = offline("tag #2 \"maybe hp\"\nstar #0\nstar #2\nstar #4\nunstar #4\nlist --starred\nlist u16 --starred");
+    assert_eq!(listed(&stdout), [
+        "#0 game+0x4010 (0x404010) 31337 [rw-p] *",
+        "#2 game+0x5ffc (0x405ffc) 31337 [rw-p] * \"maybe hp\"",
+        "#0 game+0x4010 (0x404010) 31337 [rw-p] *",
+        "#2 game+0x5ffc (0x405ffc) 31337 [rw-p] * \"maybe hp\"",
+    ], "{}", stdout);
+}
+
+// Kept by address, so a filter that moves a result to another index keeps its mark
+#[test]
+fn marks_follow_the_address_rather_than_the_index() {
+    let stdout = offline("tag #1 odd\ntag #3 \"in the heap\"\nfilter region heap\nlist");
+    assert!(listed(&stdout).contains(&"#0 0x10ffffe (heap+0xffffe) 31337 [rw-p] \"in the heap\""), "{}", stdout);
+    assert!(stdout.contains("note: marked result game+0x4101 (0x404101) \"odd\" is no longer a result"), "{}", stdout);
+    assert!(!stdout.contains("in the heap\" is no longer"), "{}", stdout);
+}
+
+#[test]
+fn only_results_can_be_marked() {
+    let stdout = offline("tag 0x404000 nothing\nstar\nuntag #0\nlist --starred");
+    assert!(stdout.contains("error: game+0x4000 (0x404000) is not one of the results"), "{}", stdout);
+    assert!(stdout.contains("error: Usage: tag <address>"), "{}", stdout);
+    assert!(stdout.contains("none of the results are starred or tagged") && listed(&stdout).is_empty(), "{}", stdout);
+}
+
+#[test]
+fn marks_are_saved_and_loaded_with_the_results() {
+    let path = temp_path("saved");
+    offline(&format!("tag #2 \"maybe hp\"\nstar #3\nsave {}", path));
+    let stdout = offline(&format!("rescan 0\nload {}\nlist --starred", path));
+    assert_eq!(listed(&stdout), ["#2 game+0x5ffc (0x405ffc) 31337 [rw-p] \"maybe hp\"", "#3 0x10ffffe (heap+0xffffe) 31337 [rw-p] *"], "{}", stdout);
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,69 @@
+mod common;
+use std::{io::Write, process::{Command, Stdio}};
+use memory::{Endianness, Scalar, ScanOptions, find_masked, reduce_found_masked};
+use common::mock_with_heap;
+
+// Only the middle two bytes are compared, so the packed fields either side can hold anything
+#[test]
+fn bits_outside_the_mask_are_ignored() {
+    let mock = mock_with_heap(0x1000);
+    for (address, value) in [(0x10000, 0x00123400u32), (0x10010, 0xab1234cd), (0x10020, 0x00123500), (0x10031, 0x00123400)] {
+        mock.plant(address, &value.to_bytes()).unwrap();
+    }
+    let (found, stats) = find_masked(&mock, 0x00123400, 0x00ffff00, 4, &ScanOptions::default()).unwrap();
+    assert_eq!((found, stats.matches), (vec![0x10000, 0x10010], 2));
+    // Unaligned places are only looked at when asked for
+    let unaligned = ScanOptions { alignment: Some(1), ..ScanOptions::default() };
+    assert!(find_masked(&mock, 0x00123400, 0x00ffff00, 4, &unaligned).unwrap().0.contains(&0x10031));
+}
+
+#[test]
+fn every_width_is_supported() {
+    let mock = mock_with_heap(0x1000);
+    mock.plant(0x10100, &[0b1010_0110]).unwrap();
+    mock.plant(0x10200, &0xbeefu16.to_bytes()).unwrap();
+    mock.plant(0x10300, &0x1122_3344_5566_7788u64.to_bytes()).unwrap();
+    let found = |value, mask, width| find_masked(&mock, value, mask, width, &ScanOptions::default()).unwrap().0;
+    assert!(found(0b0010_0000, 0b0110_0000, 1).contains(&0x10100));
+    assert_eq!(found(0xbe00, 0xff00, 2), [0x10200]);
+    assert_eq!(found(0x1122_0000_0000_0088, 0xffff_0000_0000_00ff, 8), [0x10300]);
+    let error = find_masked(&mock, 1, 1, 3, &ScanOptions::default()).unwrap_err();
+    assert!(error.to_string().contains("1, 2, 4 or 8 bytes"), "{}", error);
+}
+
+// A value with bits the mask leaves out, or a mask leaving out everything, is refused
+#[test]
+fn masks_that_cannot_work_are_refused() {
+    let mock = mock_with_heap(0x1000);
+    let error = |value, mask, width| find_masked(&mock, value, mask, width, &ScanOptions::default()).unwrap_err().to_string();
+    assert!(error(0x10, 0x0f, 1).contains("has bits outside the mask 0xf"));
+    assert!(error(0, 0xff00, 1).contains("leaves no bits of a 1-byte value"));
+    assert!(error(0x1_0000, 0xffff_ffff, 2).contains("outside the mask 0xffff"));
+}
+
+#[test]
+fn rescans_and_byte_order_use_the_same_mask() {
+    let mock = mock_with_heap(0x1000);
+    mock.plant(0x10000, &0x00123400u32.to_bytes_in(Endianness::Big)).unwrap();
+    mock.plant(0x10004, &0x00123400u32.to_bytes()).unwrap();
+    let big = ScanOptions { endianness: Endianness::Big, ..ScanOptions::default() };
+    let (mut found, mut stats) = find_masked(&mock, 0x00123400, 0x00ffff00, 4, &big).unwrap();
+    assert_eq!(found, [0x10000]);
+    mock.plant(0x10000, &0xff1234ffu32.to_bytes_in(Endianness::Big)).unwrap();
+    reduce_found_masked(&mock, &mut found, 0x00123400, 0x00ffff00, 4, &big, &mut stats).unwrap();
+    assert_eq!(found, [0x10000]);
+    reduce_found_masked(&mock, &mut found, 0x00ff0000, 0x00ff0000, 4, &big, &mut stats).unwrap();
+    assert!(found.is_empty());
+}
+
+// The capture's 31337 is 0x7a69, so its second byte is 0x7a
+#[test]
+fn the_cli_scans_and_rescans_by_mask() {
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).args(["--offline", "tests/fixtures/scan_before.cap"]).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    writeln!(session.stdin.take().unwrap(), "rescan masked 1\nscan masked u32 0x7a00 mask 0xff00\nrescan masked 0x7a69 mask 0xffff\nrescan masked 0x7a00\nscan masked f32 1 mask 1").unwrap();
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    let matches = stdout.lines().filter_map(|x| x.trim_start_matches("> ").split(", ").last()?.strip_suffix(" matches")).collect::<Vec<&str>>();
+    assert_eq!(matches, ["44", "5", "5"], "{}", stdout);
+    assert!(stdout.contains("error: rescan masked checks with the mask of the last scan masked"), "{}", stdout);
+    assert!(stdout.contains("error: A mask is over the bits of an integer, not an f32"), "{}", stdout);
+}
@@ -0,0 +1,39 @@
+use memory::{MOCK_PAGE_SIZE, MockProcess, ScanOptions, Snapshot, find_value};
+
+const SMALL: usize = 0x10_0000;
+const BIG: usize = 0x40_0000;
+
+// A one-page heap and a sparse arena four times its size, each with the value planted in it
+fn mock() -> MockProcess {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", SMALL, SMALL + MOCK_PAGE_SIZE)).unwrap();
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0", BIG, BIG + 4 * MOCK_PAGE_SIZE)).unwrap();
+    mock.plant_value(SMALL + 8, 0x600dd00du32).unwrap();
+    mock.plant_value(BIG + 8, 0x600dd00du32).unwrap();
+    mock
+}
+
+#[test]
+fn regions_over_the_limit_are_skipped_and_reported() {
+    let mock = mock();
+    let (found, stats) = find_value(&mock, 0x600dd00du32, &ScanOptions::default()).unwrap();
+    assert_eq!(found, [SMALL + 8, BIG + 8]);
+    assert!(stats.skipped_regions.is_empty());
+    let options = ScanOptions { max_region_size: Some(2 * MOCK_PAGE_SIZE), ..ScanOptions::default() };
+    let (found, stats) = find_value(&mock, 0x600dd00du32, &options).unwrap();
+    assert_eq!(found, [SMALL + 8]);
+    assert_eq!(stats.skipped_regions, [(BIG, BIG + 4 * MOCK_PAGE_SIZE)]);
+    assert_eq!((stats.bytes_skipped(), stats.bytes_scanned), (4 * MOCK_PAGE_SIZE, MOCK_PAGE_SIZE));
+    // The largest region is still the one that was skipped, for the CLI to suggest the option by
+    assert_eq!(stats.largest_region, 4 * MOCK_PAGE_SIZE);
+}
+
+// A region exactly at the limit is still scanned, and snapshots leave out the same regions
+#[test]
+fn the_limit_is_inclusive_and_applies_to_snapshots() {
+    let mock = mock();
+    let (found, _) = find_value(&mock, 0x600dd00du32, &ScanOptions { max_region_size: Some(4 * MOCK_PAGE_SIZE), ..ScanOptions::default() }).unwrap();
+    assert_eq!(found, [SMALL + 8, BIG + 8]);
+    let snapshot = Snapshot::capture(&mock, &ScanOptions { max_region_size: Some(MOCK_PAGE_SIZE), ..ScanOptions::default() }).unwrap();
+    assert_eq!((snapshot.len(), snapshot.chunks.iter().map(|x| x.address).collect::<Vec<usize>>()), (MOCK_PAGE_SIZE, vec![SMALL]));
+}
@@ -0,0 +1,88 @@
+use std::process::{Command, Stdio};
+use memory::{BudgetFallback, MIN_BUDGET_CHUNK, MockProcess, ScanOptions, Snapshot, UnknownScan, find_value, snapshot::ChunkData};
+
+const HEAP: usize = 0x100000;
+const MB: usize = 1 << 20;
+
+// Four MB of heap, zero but for a value at the start of each MB
+fn mock() -> MockProcess {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", HEAP, HEAP + 4 * MB)).unwrap();
+    for i in 0..4 {
+        mock.plant_value(HEAP + i * MB, 31337 + i as i32).unwrap();
+    }
+    mock
+}
+
+fn budget(bytes: usize) -> ScanOptions {
+    ScanOptions { memory_budget: Some(bytes), alignment: Some(4), ..ScanOptions::default() }
+}
+
+// A MB takes the scan's buffers down from a MB each, and leaves less than the 1M zeros need
+#[test]
+fn a_tight_budget_shrinks_chunks_and_truncates_results() {
+    let mock = mock();
+    let (found, stats) = find_value(&mock, 0i32, &budget(MB)).unwrap();
+    assert!(matches!(stats.fallbacks[0], BudgetFallback::SmallerChunks(x) if (MIN_BUDGET_CHUNK..MB).contains(&x)), "{:?}", stats.fallbacks);
+    assert_eq!(stats.fallbacks[1..], [BudgetFallback::TruncatedResults(found.len())]);
+    assert!(stats.partial && found.len() * 8 <= MB);
+    assert!(found.iter().all(|x| !(x - HEAP).is_multiple_of(MB)), "{:?}", found);
+}
+
+#[test]
+fn a_roomy_budget_changes_nothing() {
+    let mock = mock();
+    let (found, stats) = find_value(&mock, 0i32, &budget(1 << 30)).unwrap();
+    assert_eq!((found.len(), stats.fallbacks, stats.partial), (MB - 4, Vec::new(), false));
+    let snapshot = Snapshot::capture(&mock, &budget(1 << 30)).unwrap();
+    assert!(snapshot.fallbacks.is_empty() && snapshot.stored_len() == 4 * MB);
+}
+
+// A budget the workers' buffers already use up leaves no room for any chunk in memory
+#[test]
+fn snapshots_over_budget_spill_to_disk() {
+    let mock = mock();
+    let snapshot = Snapshot::capture(&mock, &budget(1)).unwrap();
+    assert_eq!((snapshot.fallbacks.as_slice(), snapshot.stored_len(), snapshot.len()), (&[BudgetFallback::Spilled(4 * MB)][..], 0, 4 * MB));
+    assert!(snapshot.chunks.iter().all(|x| matches!(x.data, ChunkData::Spilled { .. })));
+    for (i, chunk) in snapshot.chunks.iter().enumerate() {
+        assert_eq!(chunk.bytes().unwrap()[..4], (31337 + i as i32).to_ne_bytes());
+    }
+}
+
+#[test]
+fn spilled_snapshots_save_like_any_other() {
+    let mock = mock();
+    let path = std::env::temp_dir().join(format!("mem_budget_save_{}.cap", std::process::id()));
+    Snapshot::capture(&mock, &budget(1)).unwrap().save(&mock, &path).unwrap();
+    let loaded = Snapshot::load(&path, false).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let expected = Snapshot::capture(&mock, &ScanOptions::default()).unwrap();
+    assert_eq!(loaded.chunks.len(), expected.chunks.len());
+    assert!(loaded.chunks.iter().zip(&expected.chunks).all(|(a, b)| a.address == b.address && a.bytes().unwrap() == b.bytes().unwrap()));
+}
+
+// Narrows keep to the budget too, spilling the chunks they keep
+#[test]
+fn unknown_scans_narrow_within_the_budget() {
+    let mock = mock();
+    let mut unknown = UnknownScan::start(&mock, 4, &budget(1)).unwrap();
+    mock.plant_value(HEAP + 2 * MB, 1i32).unwrap();
+    mock.plant_value(HEAP + 3 * MB + 8, 2i32).unwrap();
+    unknown.narrow::<i32>(&mock, |old, new| old != new).unwrap();
+    assert_eq!(unknown.addresses(), [HEAP + 2 * MB, HEAP + 3 * MB + 8]);
+    assert_eq!(unknown.snapshot().fallbacks, [BudgetFallback::Spilled(2 * MB)]);
+    mock.plant_value(HEAP + 2 * MB, 5i32).unwrap();
+    unknown.narrow::<i32>(&mock, |old, new| new > old).unwrap();
+    assert_eq!(unknown.addresses(), [HEAP + 2 * MB]);
+}
+
+#[test]
+fn the_cli_reports_the_fallbacks() {
+    let session = Command::new(env!("CARGO_BIN_EXE_memory")).args(["--offline", "tests/fixtures/scan_before.cap", "--mem-budget", "64K"]).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    std::io::Write::write_all(&mut session.stdin.as_ref().unwrap(), b"scan i32 100\nsnapshot\nset mem_budget none\nscan i32 100\nsnapshot\n").unwrap();
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    assert!(stdout.contains("warning: read in 64.0 KB chunks rather than 1.0 MB to keep the scan's buffers within the memory budget"), "{}", stdout);
+    assert!(stdout.contains(" of the snapshot on disk to stay within the memory budget"), "{}", stdout);
+    assert_eq!(stdout.matches("warning:").count(), 3, "{}", stdout);
+}
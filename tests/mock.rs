@@ -0,0 +1,155 @@
+use std::sync::Arc;
+use memory::{Endianness, MOCK_PAGE_SIZE, MockProcess, ProcessMemory, RegionCache, ScanOptions, Snapshot, UnknownScan, find_value, read_bytes_from_process, read_cstring, read_scalar, reduce_found_values, resolve_address, scan::SCAN_CHUNK_SIZE, snapshot::SNAPSHOT_CHUNK_SIZE, write_to_process};
+
+const HEAP: usize = 0x10_0000;
+
+// A heap of the given size and nothing else
+fn heap(len: usize) -> MockProcess {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", HEAP, HEAP + len)).unwrap();
+    mock
+}
+
+#[test]
+fn scans_find_values_at_the_edges_of_regions_and_chunks() {
+    let end = HEAP + 2 * SCAN_CHUNK_SIZE;
+    let mock = heap(2 * SCAN_CHUNK_SIZE);
+    // The very first offset, straddling the seam between the two chunks, and the last one a whole
+    // value fits at; the three bytes after that are the start of one cut off by the end
+    let planted = [HEAP, HEAP + SCAN_CHUNK_SIZE - 2, end - 7];
+    for address in planted {
+        mock.plant_value(address, 0x1234_5678i32).unwrap();
+    }
+    mock.plant(end - 3, &0x1234_5678i32.to_ne_bytes()[..3]).unwrap();
+    let (found, stats) = find_value(&mock, 0x1234_5678i32, &ScanOptions::default()).unwrap();
+    assert_eq!(found, planted);
+    assert_eq!((stats.bytes_scanned, stats.coverage()), (2 * SCAN_CHUNK_SIZE, 1.0));
+    // Aligned, the one across the seam is not at a multiple of four
+    let (found, _) = find_value(&mock, 0x1234_5678i32, &ScanOptions { alignment: Some(4), ..ScanOptions::default() }).unwrap();
+    assert_eq!(found, [HEAP]);
+}
+
+#[test]
+fn an_unreadable_page_splits_a_region() {
+    let mock = heap(4 * MOCK_PAGE_SIZE);
+    let hole = HEAP + 2 * MOCK_PAGE_SIZE;
+    mock.mark_unreadable(hole, hole + MOCK_PAGE_SIZE).unwrap();
+    let regions = mock.memory_regions().unwrap();
+    assert_eq!(regions.iter().map(|x| (x.start, x.end, x.permissions())).collect::<Vec<(usize, usize, String)>>(), vec![
+        (HEAP, hole, "rw-p".to_string()),
+        (hole, hole + MOCK_PAGE_SIZE, "---p".to_string()),
+        (hole + MOCK_PAGE_SIZE, HEAP + 4 * MOCK_PAGE_SIZE, "rw-p".to_string()),
+    ]);
+    // The last offset before the hole, inside it, and the first after it
+    for address in [hole - 4, hole + 0x10, hole + MOCK_PAGE_SIZE] {
+        mock.plant_value(address, -9i32).unwrap();
+    }
+    let (found, stats) = find_value(&mock, -9i32, &ScanOptions::default()).unwrap();
+    assert_eq!((found, stats.failed_regions), (vec![hole - 4, hole + MOCK_PAGE_SIZE], vec![]));
+    // A read runs up to the hole and stops there, and one starting inside it fails
+    assert_eq!(read_bytes_from_process(&mock, 16, hole - 8).unwrap().len(), 8);
+    assert!(read_scalar::<i32>(&mock, hole - 2, Endianness::Native).is_err());
+    assert!(write_to_process(&mock, hole, &1i32).is_err());
+}
+
+#[test]
+fn short_reads_leave_the_rest_of_a_chunk_unscanned() {
+    let mock = heap(MOCK_PAGE_SIZE);
+    mock.plant_value(HEAP + 96, 77u32).unwrap();
+    mock.plant_value(HEAP + 200, 77u32).unwrap();
+    mock.limit_reads(Some(100));
+    let (mut found, mut stats) = find_value(&mock, 77u32, &ScanOptions::default()).unwrap();
+    assert_eq!(found, [HEAP + 96]);
+    assert_eq!(stats.bytes_scanned, 100);
+    assert!(stats.coverage() < 0.1);
+    // Reduces read each value on its own, which a limit this size never cuts short
+    mock.plant_value(HEAP + 96, 78u32).unwrap();
+    reduce_found_values(&mock, &mut found, 77u32, &ScanOptions::default(), &mut stats).unwrap();
+    assert!(found.is_empty());
+}
+
+#[test]
+fn regions_that_go_away_mid_scan_are_reported() {
+    let mock = heap(MOCK_PAGE_SIZE);
+    let other = 0x40_0000;
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0", other, other + MOCK_PAGE_SIZE)).unwrap();
+    mock.plant_value(HEAP + 8, 5u64).unwrap();
+    mock.plant_value(other + 8, 5u64).unwrap();
+    // Listed when the scan starts, gone by the time it is read
+    mock.unmap_after(0, other);
+    let (found, stats) = find_value(&mock, 5u64, &ScanOptions::default()).unwrap();
+    assert_eq!((found, stats.failed_regions), (vec![HEAP + 8], vec![(other, other + MOCK_PAGE_SIZE)]));
+    // A result in a region that moved is dropped before reducing, once asked to
+    let mock = heap(MOCK_PAGE_SIZE);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0", other, other + MOCK_PAGE_SIZE)).unwrap();
+    mock.plant_value(HEAP + 8, 5u64).unwrap();
+    mock.plant_value(other + 8, 5u64).unwrap();
+    let options = ScanOptions { drop_unmapped: true, ..ScanOptions::default() };
+    let (mut found, mut stats) = find_value(&mock, 5u64, &options).unwrap();
+    assert_eq!(found, [HEAP + 8, other + 8]);
+    mock.move_region(other, 0x80_0000).unwrap();
+    reduce_found_values(&mock, &mut found, 5u64, &options, &mut stats).unwrap();
+    assert_eq!((found, stats.dropped_unmapped), (vec![HEAP + 8], 1));
+    assert!(stats.maps_change.is_some());
+}
+
+#[test]
+fn pointer_chains_stop_at_the_hop_that_goes_wrong() {
+    let mock = Arc::new(heap(2 * MOCK_PAGE_SIZE));
+    mock.map("400000-401000 r--p 00000000 08:01 1234 /usr/bin/game").unwrap();
+    mock.map("401000-402000 rw-p 00001000 08:01 1234 /usr/bin/game").unwrap();
+    // The base is the last pointer in the executable's data
+    let base = 0x402000 - 8;
+    mock.plant_value(base, HEAP + 0x100).unwrap();
+    mock.plant_value(HEAP + 0x108, HEAP + MOCK_PAGE_SIZE - 8).unwrap();
+    mock.plant_value(HEAP + 2 * MOCK_PAGE_SIZE - 4, 31337i32).unwrap();
+    let mut regions = RegionCache::from_process(mock.clone()).unwrap();
+    let chain = "game+0x1ff8->0x8->0x1004";
+    let address = resolve_address(&*mock, &mut regions, chain).unwrap();
+    assert_eq!(read_scalar::<i32>(&*mock, address, Endianness::Native).unwrap(), 31337);
+    // The last hop now lands in a page that cannot be read
+    mock.mark_unreadable(HEAP + MOCK_PAGE_SIZE, HEAP + 2 * MOCK_PAGE_SIZE).unwrap();
+    regions.refresh().unwrap();
+    let error = resolve_address(&*mock, &mut regions, chain).unwrap_err().to_string();
+    assert!(error.contains(&format!("hop 2: 0x{:x} is not in mapped readable memory", HEAP + 2 * MOCK_PAGE_SIZE - 4)), "{}", error);
+    mock.plant_value(HEAP + 0x108, 0usize).unwrap();
+    let error = resolve_address(&*mock, &mut regions, chain).unwrap_err().to_string();
+    assert!(error.contains(&format!("hop 1: null pointer at 0x{:x}", HEAP + 0x108)), "{}", error);
+}
+
+#[test]
+fn strings_end_at_a_terminator_the_region_or_a_hole() {
+    let mock = Arc::new(heap(3 * MOCK_PAGE_SIZE));
+    let mut regions = RegionCache::from_process(mock.clone()).unwrap();
+    mock.plant(HEAP + 0x10, b"player one\0junk").unwrap();
+    assert_eq!(read_cstring(&*mock, &mut regions, HEAP + 0x10, 64).unwrap().to_str().unwrap(), "player one");
+    // Without a terminator, only what fits before the end of the region
+    let end = HEAP + 3 * MOCK_PAGE_SIZE;
+    mock.plant(end - 5, b"abcde").unwrap();
+    assert_eq!(read_cstring(&*mock, &mut regions, end - 5, 64).unwrap().to_str().unwrap(), "abcde");
+    // or before a page that cannot be read, however long the string was meant to be
+    let hole = HEAP + MOCK_PAGE_SIZE;
+    mock.plant(hole - 3, b"xyzzy\0").unwrap();
+    mock.mark_unreadable(hole, hole + MOCK_PAGE_SIZE).unwrap();
+    regions.refresh().unwrap();
+    assert_eq!(read_cstring(&*mock, &mut regions, hole - 3, 64).unwrap().to_str().unwrap(), "xyz");
+    assert!(read_cstring(&*mock, &mut regions, hole, 64).is_err());
+}
+
+#[test]
+fn snapshots_compare_every_value_including_the_last() {
+    let len = SNAPSHOT_CHUNK_SIZE + MOCK_PAGE_SIZE;
+    let mock = heap(len);
+    let snapshot = Snapshot::capture(&mock, &ScanOptions::default()).unwrap();
+    assert_eq!((snapshot.len(), snapshot.chunks.len()), (len, 2));
+    mock.plant_value(HEAP + len - 4, 3i32).unwrap();
+    mock.plant_value(HEAP + SNAPSHOT_CHUNK_SIZE - 4, 3i32).unwrap();
+    let changed = snapshot.compare::<i32>(&mock, |old, new| new > old).unwrap();
+    assert!(changed.contains(&(HEAP + len - 4)) && changed.contains(&(HEAP + SNAPSHOT_CHUNK_SIZE - 4)), "{:x?}", changed);
+    // An unknown-value scan drops the chunks it can no longer read along with their candidates
+    let mut unknown = UnknownScan::start(&mock, 4, &ScanOptions::default()).unwrap();
+    mock.plant_value(HEAP + len - 4, 4i32).unwrap();
+    mock.mark_unreadable(HEAP, HEAP + MOCK_PAGE_SIZE).unwrap();
+    assert_eq!(unknown.narrow::<i32>(&mock, |old, new| new > old).unwrap(), 1);
+    assert_eq!(unknown.addresses(), [HEAP + len - 4]);
+}
@@ -0,0 +1,117 @@
+mod common;
+use std::{sync::{Arc, mpsc::channel}, time::Duration};
+use memory::{MockProcess, MonitorCallback, MonitorChange, MonitorEntry, ValueMonitor, ValueType};
+
+const HEAP: usize = 0x10000;
+
+// Leaked, as the monitor's thread needs a process that outlives the test's borrow
+fn mock() -> &'static MockProcess {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", HEAP, HEAP + 0x1000)).unwrap();
+    mock.plant_value(HEAP, 100i32).unwrap();
+    mock.plant_value(HEAP + 8, 1000i64).unwrap();
+    Box::leak(Box::new(mock))
+}
+
+fn entry(label: &str, address: usize, value_type: ValueType) -> MonitorEntry {
+    MonitorEntry { label: label.to_string(), address, size: value_type.size().unwrap(), value_type }
+}
+
+fn start(process: &'static MockProcess, entries: Vec<MonitorEntry>) -> (ValueMonitor, std::sync::mpsc::Receiver<MonitorChange>) {
+    let (sender, receiver) = channel();
+    let callback = MonitorCallback(Arc::new(move |change| { let _ = sender.send(change); }));
+    (ValueMonitor::start(process, entries, Duration::from_millis(5), callback).unwrap(), receiver)
+}
+
+fn wait_for_reads(monitor: &ValueMonitor, reads: u64) {
+    while monitor.reads() < reads {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn each_change_is_reported_with_its_label_once() {
+    let mock = mock();
+    let (monitor, changes) = start(mock, vec![entry("hp", HEAP, ValueType::I32), entry("gold", HEAP + 8, ValueType::I64)]);
+    wait_for_reads(&monitor, 1);
+    mock.plant_value(HEAP + 8, 1025i64).unwrap();
+    let change = changes.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!((change.entry.label.as_str(), change.old, change.new), ("gold", 1000i64.to_ne_bytes().to_vec(), 1025i64.to_ne_bytes().to_vec()));
+    let reads = monitor.reads();
+    wait_for_reads(&monitor, reads + 3);
+    assert!(changes.try_recv().is_err());
+    assert_eq!(monitor.changes(), 1);
+}
+
+// What was there before it started is not a change
+#[test]
+fn the_first_read_only_records_the_values() {
+    let mock = mock();
+    let (monitor, changes) = start(mock, vec![entry("hp", HEAP, ValueType::I32)]);
+    wait_for_reads(&monitor, 5);
+    assert!(changes.try_recv().is_err());
+    assert_eq!(monitor.entries(), [entry("hp", HEAP, ValueType::I32)]);
+}
+
+// An entry that cannot be read holds up nothing else, and is not reported until it changes
+#[test]
+fn unreadable_entries_are_skipped() {
+    let mock = mock();
+    let (monitor, changes) = start(mock, vec![entry("gone", 0x90000, ValueType::I32), entry("hp", HEAP, ValueType::I32)]);
+    wait_for_reads(&monitor, 1);
+    mock.plant_value(HEAP, 99i32).unwrap();
+    assert_eq!(changes.recv_timeout(Duration::from_secs(5)).unwrap().entry.label, "hp");
+}
+
+#[test]
+fn stopping_ends_the_reads() {
+    let mock = mock();
+    let (mut monitor, _changes) = start(mock, vec![entry("hp", HEAP, ValueType::I32)]);
+    wait_for_reads(&monitor, 1);
+    monitor.stop();
+    let reads = monitor.reads();
+    std::thread::sleep(Duration::from_millis(30));
+    assert_eq!(monitor.reads(), reads);
+}
+
+#[test]
+fn needs_entries_and_an_interval() {
+    let callback = MonitorCallback(Arc::new(|_| {}));
+    let error = ValueMonitor::start(mock(), Vec::new(), Duration::from_millis(5), callback.clone()).unwrap_err();
+    assert_eq!(error.to_string(), "There is nothing to monitor");
+    let error = ValueMonitor::start(mock(), vec![entry("hp", HEAP, ValueType::I32)], Duration::ZERO, callback).unwrap_err();
+    assert_eq!(error.to_string(), "A monitor needs an interval above zero");
+}
+
+// The victim's tick takes 1 off the locked hp, which is alerted; a write from the session is not
+#[cfg(target_os = "linux")]
+#[test]
+fn the_cli_alerts_only_on_changes_it_did_not_make() {
+    use std::{io::{BufRead, BufReader, Write}, process::{Command, Stdio}};
+    let path = common::victim_path();
+    let mut victim = Command::new(path).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut lines = BufReader::new(victim.stdout.take().unwrap()).lines();
+    let pid = lines.next().unwrap().unwrap().strip_prefix("pid ").unwrap().to_string();
+    let player = lines.next().unwrap().unwrap();
+    let player = usize::from_str_radix(player.strip_prefix("player 0x").unwrap().split_once(' ').unwrap().0, 16).unwrap();
+    lines.next().unwrap().unwrap();
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(&pid).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut stdin = session.stdin.take().unwrap();
+    let pause = || std::thread::sleep(Duration::from_millis(300));
+    writeln!(stdin, "lock 0x{:x} i32 set 50 --interval 10s\nmonitor start --every 10ms", player).unwrap();
+    pause();
+    writeln!(victim.stdin.as_mut().unwrap()).unwrap();
+    assert_eq!(lines.next().unwrap().unwrap(), "ticked");
+    pause();
+    writeln!(stdin, "write 0x{:x} 70", player).unwrap();
+    pause();
+    writeln!(stdin, "monitor\nmonitor stop\nmonitor start --every").unwrap();
+    drop(stdin);
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    let _ = victim.kill();
+    let _ = victim.wait();
+    let alerts = stdout.lines().filter(|x| x.contains("[monitor] lock ")).collect::<Vec<&str>>();
+    assert!(alerts.len() == 1 && alerts[0].contains(": 50 -> 49 at "), "{}", stdout);
+    assert!(stdout.contains("monitoring 1 entries every 10ms") && stdout.contains(" 1 alerted, 1 put down to this session's own writes"), "{}", stdout);
+    assert!(stdout.contains("stopped the monitor after 1 alerts") && stdout.contains("error: Expected a value after --every"), "{}", stdout);
+}
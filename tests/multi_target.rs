@@ -0,0 +1,67 @@
+#![cfg(target_os = "linux")]
+mod common;
+use std::{io::{BufRead, BufReader}, process::{Child, Command, Stdio}};
+use common::victim_path;
+
+// A victim, its pid and where its player is, once it has printed all it prints, as it exits if
+// writing to the closed pipe fails
+fn victim() -> (Child, String, usize) {
+    let mut victim = Command::new(victim_path()).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut lines = BufReader::new(victim.stdout.take().unwrap()).lines();
+    let pid = lines.next().unwrap().unwrap().strip_prefix("pid ").unwrap().to_string();
+    let player = usize::from_str_radix(lines.next().unwrap().unwrap().strip_prefix("player 0x").unwrap().split_once(' ').unwrap().0, 16).unwrap();
+    assert!(lines.next().unwrap().unwrap().starts_with("pointer "));
+    (victim, pid, player)
+}
+
+fn session(arguments: &[&str], commands: &str) -> String {
+    let session = Command::new(env!("CARGO_BIN_EXE_memory")).args(arguments).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    std::io::Write::write_all(&mut session.stdin.as_ref().unwrap(), commands.as_bytes()).unwrap();
+    String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap()
+}
+
+// Each target keeps its own locks, which locks --all lists under it
+#[test]
+fn targets_keep_their_own_locks() {
+    let ((first, first_pid, first_player), (second, second_pid, second_player)) = (victim(), victim());
+    let commands = format!("lock 0x{first_player:x} i32 111\nattach {second_pid}\nlock 0x{second_player:x} i32 222\nlocks\ntargets\nlocks --all\nuse {first_pid}\nlocks\n");
+    let stdout = session(&[&first_pid], &commands);
+    for mut victim in [first, second] {
+        let _ = victim.kill();
+        let _ = victim.wait();
+    }
+    assert!(stdout.contains(&format!("attached to {second_pid} using ")) && stdout.contains(&format!("alongside process {first_pid}")), "{}", stdout);
+    assert!(stdout.contains(&format!("[{second_pid} victim] > ")), "{}", stdout);
+    assert!(stdout.contains(&format!("process {second_pid} (victim), in use: 0 results, 1 locks\nprocess {first_pid} (victim): 0 results, 1 locks")), "{}", stdout);
+    assert!(stdout.contains(&format!("process {second_pid} (victim), in use:\n  0x{second_player:x} (heap+0x")), "{}", stdout);
+    assert!(stdout.contains(&format!("process {first_pid} (victim):\n  0x{first_player:x} (heap+0x")), "{}", stdout);
+    assert!(stdout.contains(&format!("using process {first_pid} (victim)\n[{first_pid} victim] > 0x{first_player:x} (heap+0x")), "{}", stdout);
+    assert!(stdout.contains("i32 = 222 every") && stdout.matches("i32 = 111 every").count() == 2, "{}", stdout);
+}
+
+// Detaching one leaves the other as it was, and the last cannot be detached
+#[test]
+fn detach_drops_only_that_target() {
+    let ((first, first_pid, first_player), (second, second_pid, _)) = (victim(), victim());
+    let commands = format!("lock 0x{first_player:x} i32 111\nattach {second_pid}\nattach {second_pid}\nuse victim\ndetach {second_pid}\nlocks\ntargets\ndetach {first_pid}\n");
+    let stdout = session(&[&first_pid], &commands);
+    for mut victim in [first, second] {
+        let _ = victim.kill();
+        let _ = victim.wait();
+    }
+    assert!(stdout.contains(&format!("error: Process {second_pid} is attached already; use {second_pid} switches to it")), "{}", stdout);
+    assert!(stdout.contains("error: 2 targets are named 'victim', so give the pid"), "{}", stdout);
+    assert!(stdout.contains(&format!("detached from process {second_pid} (victim), stopping its locks and watches\nusing process {first_pid} (victim)")), "{}", stdout);
+    assert!(stdout.contains(&format!("> 0x{first_player:x} (heap+0x")) && stdout.contains("i32 = 111 every"), "{}", stdout);
+    assert!(stdout.contains(&format!("process {first_pid} (victim), in use: 0 results, 1 locks\n")), "{}", stdout);
+    assert!(stdout.contains(&format!("error: Process {first_pid} is the only target; quit ends the session")), "{}", stdout);
+}
+
+#[test]
+fn offline_captures_attach_nothing_more() {
+    let stdout = session(&["--offline", "tests/fixtures/scan_before.cap"], "attach 1\nattach\nuse 1\ndetach nothing\n");
+    assert!(stdout.contains("error: An offline capture has no process to attach alongside"), "{}", stdout);
+    assert!(stdout.contains("error: Usage: attach <pid>"), "{}", stdout);
+    assert!(stdout.contains("error: Process 1 is not attached; attach 1 adds it"), "{}", stdout);
+    assert!(stdout.contains("error: No target is named 'nothing'; targets lists them"), "{}", stdout);
+}
@@ -0,0 +1,88 @@
+mod common;
+use memory::{MockProcess, PairDistance, SCAN_CHUNK_SIZE, Scalar, ScanOptions, TypedValue, find_pair};
+use common::mock_with_heap;
+
+fn options() -> ScanOptions {
+    ScanOptions { alignment: Some(4), ..ScanOptions::default() }
+}
+
+// Two objects with hp 100 and mp 50 in them, and a stray 50 too far from either
+#[test]
+fn pairs_are_the_values_within_the_distance() {
+    let mock = mock_with_heap(0x2000);
+    mock.plant(0x10100, &100i32.to_bytes()).unwrap();
+    mock.plant(0x10108, &50i32.to_bytes()).unwrap();
+    mock.plant(0x10400, &50i32.to_bytes()).unwrap();
+    mock.plant(0x10420, &100i32.to_bytes()).unwrap();
+    mock.plant(0x10800, &50i32.to_bytes()).unwrap();
+    let (pairs, stats) = find_pair(&mock, &TypedValue::I32(100), &TypedValue::I32(50), PairDistance::Within(0x40), &options()).unwrap();
+    assert_eq!(pairs, [(0x10100, 0x10108, 8), (0x10420, 0x10400, -0x20)]);
+    assert_eq!(stats.matches, 2);
+    let (pairs, _) = find_pair(&mock, &TypedValue::I32(100), &TypedValue::I32(50), PairDistance::Within(8), &options()).unwrap();
+    assert_eq!(pairs, [(0x10100, 0x10108, 8)]);
+}
+
+#[test]
+fn an_exact_delta_keeps_only_pairs_that_far_apart() {
+    let mock = mock_with_heap(0x2000);
+    mock.plant(0x10100, &100i32.to_bytes()).unwrap();
+    mock.plant(0x10108, &1000i64.to_bytes()).unwrap();
+    mock.plant(0x10200, &100i32.to_bytes()).unwrap();
+    mock.plant(0x10210, &1000i64.to_bytes()).unwrap();
+    let find = |delta| find_pair(&mock, &TypedValue::I32(100), &TypedValue::I64(1000), PairDistance::Exactly(delta), &options()).unwrap().0;
+    assert_eq!(find(8), [(0x10100, 0x10108, 8)]);
+    assert_eq!(find(0x10), [(0x10200, 0x10210, 0x10)]);
+    let (pairs, _) = find_pair(&mock, &TypedValue::I64(1000), &TypedValue::I32(100), PairDistance::Exactly(-8), &options()).unwrap();
+    assert_eq!(pairs, [(0x10108, 0x10100, -8)]);
+}
+
+// The bytes of one value are not a second field beside it, even when they hold the other
+#[test]
+fn overlapping_values_are_not_paired() {
+    let mock = mock_with_heap(0x2000);
+    mock.plant(0x10100, &50i64.to_bytes()).unwrap();
+    mock.plant(0x10108, &[0xff]).unwrap();
+    let (pairs, _) = find_pair(&mock, &TypedValue::I64(50), &TypedValue::I32(50), PairDistance::Within(16), &options()).unwrap();
+    assert!(pairs.is_empty(), "{:x?}", pairs);
+    // Nor is a value its own pair
+    let (pairs, _) = find_pair(&mock, &TypedValue::I32(50), &TypedValue::I32(50), PairDistance::Within(16), &options()).unwrap();
+    assert!(pairs.is_empty(), "{:x?}", pairs);
+    mock.plant(0x10110, &50i32.to_bytes()).unwrap();
+    let (pairs, _) = find_pair(&mock, &TypedValue::I32(50), &TypedValue::I32(50), PairDistance::Within(16), &options()).unwrap();
+    assert_eq!(pairs, [(0x10100, 0x10110, 0x10), (0x10110, 0x10100, -0x10)]);
+}
+
+// Each value is found in the chunk it starts in, and the pair is joined afterwards
+#[test]
+fn pairs_may_straddle_a_chunk_boundary() {
+    let mock = MockProcess::new(100);
+    let start = 0x1000000;
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0", start, start + 2 * SCAN_CHUNK_SIZE)).unwrap();
+    mock.plant(start + SCAN_CHUNK_SIZE - 4, &7u32.to_bytes()).unwrap();
+    mock.plant(start + SCAN_CHUNK_SIZE + 4, &9u32.to_bytes()).unwrap();
+    let (pairs, _) = find_pair(&mock, &TypedValue::U32(7), &TypedValue::U32(9), PairDistance::Within(64), &options()).unwrap();
+    assert_eq!(pairs, [(start + SCAN_CHUNK_SIZE - 4, start + SCAN_CHUNK_SIZE + 4, 8)]);
+}
+
+// The victim's player has an i32 hp of 100 and, 8 bytes on, an i64 gold of 1000
+#[cfg(target_os = "linux")]
+#[test]
+fn the_cli_seeds_a_struct_scan() {
+    use std::{io::{BufRead, BufReader, Write}, process::{Command, Stdio}};
+    let path = common::victim_path();
+    let mut victim = Command::new(path).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut lines = BufReader::new(victim.stdout.take().unwrap()).lines();
+    let pid = lines.next().unwrap().unwrap().strip_prefix("pid ").unwrap().to_string();
+    let player = lines.next().unwrap().unwrap();
+    let player = usize::from_str_radix(player.strip_prefix("player 0x").unwrap().split_once(' ').unwrap().0, 16).unwrap();
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(&pid).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let commands = "scan pair i64 1000 i32 100 --delta -8\nrescan struct +0x10 f32 1.5\nlist\nscan pair i32 100 i32 100\nscan pair i32 1 i32 2 --within 8 --delta 4";
+    writeln!(session.stdin.take().unwrap(), "{}", commands).unwrap();
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    let _ = victim.kill();
+    let _ = victim.wait();
+    assert!(stdout.contains("the second is at -0x0008 (") && stdout.contains("rescan struct checks both"), "{}", stdout);
+    assert!(stdout.contains(&format!("#0 0x{:x} (heap+", player)) && stdout.contains(") 100 [rw-p]"), "{}", stdout);
+    assert!(stdout.contains("the results are the first values; with --delta <offset>"), "{}", stdout);
+    assert!(stdout.contains("error: --within and --delta both say how far apart the pair is"), "{}", stdout);
+}
@@ -0,0 +1,151 @@
+use memory::{Encoding, MOCK_PAGE_SIZE, MemoryRegion, MockProcess, ScanOptions, TypedValue, ValueType, find_value, parse_hex_bytes};
+use proptest::prelude::*;
+
+// Which pathnames a mapping can have: none, a pseudo-path, or a file whose name may hold spaces
+// and any other printable characters, unicode included. The kernel escapes newlines, so there
+// are none here
+fn pathname() -> impl Strategy<Value = String> {
+    prop_oneof![
+        Just(String::new()),
+        prop::sample::select(vec!["[heap]", "[stack]", "[vdso]", "[anon:scudo:primary]"]).prop_map(|x| x.to_string()),
+        "/[^\n\r]{0,40}",
+    ]
+}
+
+fn region() -> impl Strategy<Value = MemoryRegion> {
+    (any::<usize>(), 1..=usize::MAX, prop::array::uniform4(any::<bool>()), any::<usize>(), (any::<u8>(), any::<u8>()), any::<u64>(), pathname(), any::<bool>())
+        .prop_map(|(start, len, flags, offset, device, inode, pathname, deleted)| MemoryRegion {
+            start: start.min(usize::MAX - len),
+            end: start.min(usize::MAX - len) + len,
+            readable: flags[0],
+            writable: flags[1],
+            executable: flags[2],
+            shared: flags[3],
+            offset,
+            device: format!("{:02x}:{:02x}", device.0, device.1),
+            inode,
+            // Only a file can have been deleted
+            deleted: deleted && pathname.starts_with('/'),
+            pathname,
+            kernel_page_size: None,
+        })
+}
+
+// As the kernel writes it, padding before the pathname to line them up
+fn maps_line(region: &MemoryRegion, padding: usize) -> String {
+    let line = format!("{:x}-{:x} {} {:08x} {} {}", region.start, region.end, region.permissions(), region.offset, region.device, region.inode);
+    match region.pathname.is_empty() {
+        true => line,
+        false => format!("{}{} {}{}", line, " ".repeat(padding), region.pathname, if region.deleted { " (deleted)" } else { "" }),
+    }
+}
+
+fn typed_value() -> impl Strategy<Value = TypedValue> {
+    prop_oneof![
+        any::<i8>().prop_map(TypedValue::I8),
+        any::<i16>().prop_map(TypedValue::I16),
+        any::<i32>().prop_map(TypedValue::I32),
+        any::<i64>().prop_map(TypedValue::I64),
+        any::<u8>().prop_map(TypedValue::U8),
+        any::<u16>().prop_map(TypedValue::U16),
+        any::<u32>().prop_map(TypedValue::U32),
+        any::<u64>().prop_map(TypedValue::U64),
+        // NaN equals nothing, itself included, so could never compare equal after parsing
+        any::<f32>().prop_filter("not NaN", |x| !x.is_nan()).prop_map(TypedValue::F32),
+        any::<f64>().prop_filter("not NaN", |x| !x.is_nan()).prop_map(TypedValue::F64),
+        prop::collection::vec(any::<u8>(), 1..32).prop_map(TypedValue::Bytes),
+        (".*", prop::sample::select(vec![Encoding::Utf8, Encoding::Utf16]), any::<bool>()).prop_map(|(text, encoding, null_terminate)| TypedValue::Str { text, encoding, null_terminate }),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn maps_lines_round_trip(region in region(), padding in 0..20usize) {
+        let line = maps_line(&region, padding);
+        prop_assert_eq!(line.parse::<MemoryRegion>().unwrap(), region, "{}", line);
+    }
+
+    // Whatever the line, the parser returns rather than panicking, and anything it accepts is a
+    // region that can be used: it has a length, and permissions that print back the same
+    #[test]
+    fn any_maps_line_parses_or_is_rejected(line in "[0-9a-f]{0,17}-?[0-9a-f]{0,17} [rwxsp-]{0,5} [0-9a-f]{0,9} [0-9a-f:]{0,6} [0-9]{0,21}( +\\PC{0,20})?") {
+        if let Ok(region) = line.parse::<MemoryRegion>() {
+            prop_assert!(region.end > region.start, "{}", line);
+            prop_assert_eq!(&region.permissions(), line.split(' ').nth(1).unwrap());
+        }
+    }
+
+    #[test]
+    fn arbitrary_text_never_panics_the_parsers(text in "\\PC{0,64}") {
+        let _ = text.parse::<MemoryRegion>();
+        let _ = text.parse::<TypedValue>();
+        let _ = text.parse::<ValueType>();
+        let _ = parse_hex_bytes(&text);
+    }
+
+    #[test]
+    fn typed_values_round_trip(value in typed_value()) {
+        prop_assert_eq!(value.to_string().parse::<TypedValue>().unwrap(), value.clone());
+        prop_assert_eq!(value.value_type().to_string().parse::<ValueType>().unwrap(), value.value_type());
+        prop_assert_eq!(value.value_type().value_from_bytes(&value.to_bytes()).unwrap(), value);
+    }
+
+    // Bytes written as pairs of digits in any grouping, each group an even number of digits
+    #[test]
+    fn hex_bytes_parse_however_they_are_grouped(bytes in prop::collection::vec(any::<u8>(), 1..24), breaks in prop::collection::vec(any::<bool>(), 24), upper in any::<bool>()) {
+        let mut text = String::new();
+        for (index, byte) in bytes.iter().enumerate() {
+            if index > 0 && breaks[index] {
+                text.push(' ');
+            }
+            text += &if upper { format!("{:02X}", byte) } else { format!("{:02x}", byte) };
+        }
+        prop_assert_eq!(parse_hex_bytes(&text).unwrap(), bytes);
+    }
+
+    // A pattern split so that a group has an odd number of digits is one the user mistyped, even
+    // when the digits add up to whole bytes
+    #[test]
+    fn hex_bytes_with_a_split_byte_are_rejected(bytes in prop::collection::vec(any::<u8>(), 1..24), at in any::<prop::sample::Index>()) {
+        let digits = bytes.iter().map(|x| format!("{:02x}", x)).collect::<String>();
+        let split = at.index(digits.len() / 2) * 2 + 1;
+        let split = format!("{} {}", &digits[..split], &digits[split..]);
+        prop_assert!(parse_hex_bytes(&split).is_err(), "{}", split);
+        let signed = format!("+{}", &digits[1..]);
+        prop_assert!(parse_hex_bytes(&signed).is_err(), "{}", signed);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    // A parsed value planted anywhere in a region is found there by a scan for it, up to and
+    // including the last offset it fits at
+    #[test]
+    fn planted_values_are_found_where_they_were_put(value in typed_value().prop_filter("numeric", |x| x.value_type().is_numeric()), offset in 0..MOCK_PAGE_SIZE) {
+        let value = value.to_string().parse::<TypedValue>().unwrap();
+        let bytes = value.to_bytes();
+        let mock = MockProcess::new(100);
+        mock.map(&format!("10000-{:x} rw-p 00000000 00:00 0", 0x10000 + MOCK_PAGE_SIZE)).unwrap();
+        let address = 0x10000 + offset.min(MOCK_PAGE_SIZE - bytes.len());
+        mock.plant(address, &bytes).unwrap();
+        let found = match value {
+            TypedValue::I8(x) => find_value(&mock, x, &ScanOptions::default()),
+            TypedValue::I16(x) => find_value(&mock, x, &ScanOptions::default()),
+            TypedValue::I32(x) => find_value(&mock, x, &ScanOptions::default()),
+            TypedValue::I64(x) => find_value(&mock, x, &ScanOptions::default()),
+            TypedValue::U8(x) => find_value(&mock, x, &ScanOptions::default()),
+            TypedValue::U16(x) => find_value(&mock, x, &ScanOptions::default()),
+            TypedValue::U32(x) => find_value(&mock, x, &ScanOptions::default()),
+            TypedValue::U64(x) => find_value(&mock, x, &ScanOptions::default()),
+            TypedValue::F32(x) => find_value(&mock, x, &ScanOptions::default()),
+            TypedValue::F64(x) => find_value(&mock, x, &ScanOptions::default()),
+            _ => unreachable!(),
+        }.unwrap().0;
+        prop_assert!(found.contains(&address), "{} at 0x{:x}: {:x?}", value, address, found);
+        // Everything else found is the value too, in the zeroes around it or overlapping it
+        for other in found {
+            prop_assert!(value.value_type().value_from_bytes(&mock.bytes(other, bytes.len()).unwrap()).unwrap() == value);
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use memory::{MemBackend, NOP, Process, patch_bytes, patch_nop};
+use nix::unistd::Pid;
+
+// Stands in for a function's code: a page holding `sub [rax+0x30], edx` then `ret`, mapped
+// read-only as code is
+const CODE: [u8; 4] = [0x29, 0x50, 0x30, 0xc3];
+
+fn code_page() -> usize {
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize };
+    let address = unsafe { libc::mmap(std::ptr::null_mut(), page, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, -1, 0) };
+    assert_ne!(address, libc::MAP_FAILED);
+    unsafe {
+        std::ptr::copy_nonoverlapping(CODE.as_ptr(), address as *mut u8, CODE.len());
+        assert_eq!(libc::mprotect(address, page, libc::PROT_READ), 0);
+    }
+    address as usize
+}
+
+fn bytes(address: usize) -> [u8; 4] {
+    unsafe { std::ptr::read_volatile(address as *const [u8; 4]) }
+}
+
+// /proc/<pid>/mem can write read-only mappings, as a debugger would
+fn proc_mem() -> Process {
+    Process::with_backend(Pid::this(), MemBackend::ProcMem).unwrap()
+}
+
+#[test]
+fn nops_and_restores_the_original_bytes() {
+    let address = code_page();
+    let mut patch = patch_nop(proc_mem(), address, 3).unwrap();
+    assert_eq!(patch.original, CODE[..3]);
+    assert_eq!(bytes(address), [NOP, NOP, NOP, 0xc3]);
+    patch.restore().unwrap();
+    assert!(patch.is_restored());
+    assert_eq!(bytes(address), CODE);
+    // Restoring again does nothing
+    patch.restore().unwrap();
+}
+
+#[test]
+fn restores_on_drop_only_when_asked() {
+    let address = code_page();
+    drop(patch_nop(proc_mem(), address, 3).unwrap());
+    assert_eq!(bytes(address), [NOP, NOP, NOP, 0xc3]);
+
+    let address = code_page();
+    let mut patch = patch_nop(proc_mem(), address, 3).unwrap();
+    patch.restore_on_drop = true;
+    drop(patch);
+    assert_eq!(bytes(address), CODE);
+}
+
+#[test]
+fn refuses_to_restore_bytes_that_changed_since() {
+    let address = code_page();
+    let mut patch = patch_nop(proc_mem(), address, 2).unwrap();
+    patch_bytes(proc_mem(), address, &[0xcc]).unwrap();
+    assert!(patch.restore().is_err());
+    assert_eq!(bytes(address)[..2], [0xcc, NOP]);
+}
+
+#[test]
+fn fails_cleanly_where_the_backend_cannot_write() {
+    let address = code_page();
+    let process = Process::with_backend(Pid::this(), MemBackend::ProcessVmReadv).unwrap();
+    assert!(patch_nop(process, address, 3).is_err());
+    assert_eq!(bytes(address), CODE);
+    assert!(patch_nop(proc_mem(), 0x10, 3).is_err());
+}
@@ -0,0 +1,75 @@
+use memory::{MockProcess, Scalar, ScanOptions, UnknownScan, reduce_decreased_by_percent, reduce_increased_by_percent};
+
+// One page holding the values, which tests change before narrowing
+fn planted<T: Scalar>(values: &[T]) -> (MockProcess, UnknownScan) {
+    let mock = MockProcess::new(100);
+    mock.map("10000-11000 rw-p 00000000 00:00 0").unwrap();
+    set(&mock, values);
+    let unknown = UnknownScan::start(&mock, T::SIZE, &ScanOptions::default()).unwrap();
+    (mock, unknown)
+}
+
+fn set<T: Scalar>(mock: &MockProcess, values: &[T]) {
+    mock.plant(0x10000, &values.iter().flat_map(|x| x.to_bytes()).collect::<Vec<u8>>()).unwrap();
+}
+
+// Where the values were planted that are still candidates
+fn kept<T: Scalar>(unknown: &UnknownScan, count: usize) -> Vec<usize> {
+    unknown.addresses().into_iter().filter(|x| *x < 0x10000 + count * T::SIZE).map(|x| (x - 0x10000) / T::SIZE).collect()
+}
+
+#[test]
+fn keeps_values_that_dropped_by_about_the_percentage() {
+    let (mock, mut unknown) = planted::<i32>(&[100, 100, 100, 100, 100, 200]);
+    set::<i32>(&mock, &[75, 74, 72, 50, 100, 150]);
+    reduce_decreased_by_percent::<i32>(&mock, &mut unknown, 25.0, 0.02).unwrap();
+    assert_eq!(kept::<i32>(&unknown, 6), [0, 1, 5]);
+    // Each rescan compares with the values the one before saw
+    set::<i32>(&mock, &[60, 74, 0, 0, 0, 75]);
+    reduce_decreased_by_percent::<i32>(&mock, &mut unknown, 20.0, 0.0).unwrap();
+    assert_eq!(kept::<i32>(&unknown, 6), [0]);
+}
+
+#[test]
+fn keeps_values_that_rose_by_about_the_percentage() {
+    let (mock, mut unknown) = planted::<u16>(&[1000, 1000, 1000, 40000]);
+    set::<u16>(&mock, &[1025, 1100, 975, 41000]);
+    reduce_increased_by_percent::<u16>(&mock, &mut unknown, 2.5, 0.001).unwrap();
+    assert_eq!(kept::<u16>(&unknown, 4), [0, 3]);
+}
+
+#[test]
+fn floats_change_by_percentages_too() {
+    let (mock, mut unknown) = planted::<f32>(&[80.0, 80.0, 0.5, f32::NAN]);
+    set::<f32>(&mock, &[60.0, 59.0, 0.375, f32::NAN]);
+    reduce_decreased_by_percent::<f32>(&mock, &mut unknown, 25.0, 0.0001).unwrap();
+    assert_eq!(kept::<f32>(&unknown, 4), [0, 2]);
+    let (mock, mut unknown) = planted::<f64>(&[1.5, 1.5]);
+    set::<f64>(&mock, &[3.0, 2.0]);
+    reduce_increased_by_percent::<f64>(&mock, &mut unknown, 100.0, 0.0).unwrap();
+    assert_eq!(kept::<f64>(&unknown, 2), [0]);
+}
+
+// A percentage of 0 is nothing, whatever it changed to, and a decrease is towards lower values even
+// when they are negative
+#[test]
+fn zeroes_are_dropped_and_negatives_measured_by_magnitude() {
+    let (mock, mut unknown) = planted::<i64>(&[0, 0, -100, -100]);
+    set::<i64>(&mock, &[0, 5, -125, -75]);
+    reduce_decreased_by_percent::<i64>(&mock, &mut unknown, 25.0, 0.0).unwrap();
+    assert_eq!(kept::<i64>(&unknown, 4), [2]);
+    let (mock, mut unknown) = planted::<i64>(&[0, -100]);
+    set::<i64>(&mock, &[0, -75]);
+    reduce_increased_by_percent::<i64>(&mock, &mut unknown, 0.0, 0.5).unwrap();
+    assert_eq!(kept::<i64>(&unknown, 2), [1]);
+}
+
+#[test]
+fn negative_percentages_are_refused() {
+    let (mock, mut unknown) = planted::<i32>(&[100]);
+    let error = reduce_decreased_by_percent::<i32>(&mock, &mut unknown, -25.0, 0.0).unwrap_err();
+    assert!(error.to_string().contains("at least 0"), "{}", error);
+    assert!(reduce_increased_by_percent::<i32>(&mock, &mut unknown, 25.0, -0.1).is_err());
+    assert!(reduce_increased_by_percent::<i32>(&mock, &mut unknown, f64::NAN, 0.0).is_err());
+    assert_eq!(unknown.generation, 0);
+}
@@ -0,0 +1,82 @@
+use memory::{Endianness, PointerChain, ProcessMemory, RegionCache, SavedAddress, read_chain, resolve_pointer_chain, write_chain};
+use nix::unistd::Pid;
+
+// Like a game's globals: a pointer to a player, which points at its stats further in
+#[repr(C)]
+struct Stats {
+    padding: [u64; 2],
+    hp: i32,
+}
+
+#[repr(C)]
+struct Player {
+    name: [u8; 8],
+    stats: *const Stats,
+}
+
+fn chain(base: usize, offsets: &[isize]) -> PointerChain {
+    PointerChain { base: SavedAddress::Absolute(base), offsets: offsets.to_vec() }
+}
+
+#[test]
+fn follows_each_pointer_and_applies_its_offset() {
+    let stats = Box::new(Stats { padding: [0; 2], hp: 250 });
+    let player = Box::new(Player { name: *b"player\0\0", stats: &*stats });
+    let global: *const Player = &*player;
+    let global = std::hint::black_box(&global);
+    let mut regions = RegionCache::new(Pid::this()).unwrap();
+    // [[global] + 8] + 16
+    let path = chain(global as *const _ as usize, &[8, 16]);
+    assert_eq!(resolve_pointer_chain(Pid::this(), &mut regions, &path).unwrap(), &stats.hp as *const i32 as usize);
+    assert_eq!(read_chain::<i32>(Pid::this(), &mut regions, &path, Endianness::Native).unwrap(), 250);
+    write_chain(Pid::this(), &mut regions, &path, 99i32, Endianness::Native).unwrap();
+    assert_eq!(std::hint::black_box(&stats).hp, 99);
+}
+
+#[test]
+fn applies_negative_offsets() {
+    let stats = Box::new(Stats { padding: [0; 2], hp: 250 });
+    let hp: *const i32 = &stats.hp;
+    let hp = std::hint::black_box(&hp);
+    let mut regions = RegionCache::new(Pid::this()).unwrap();
+    let path = chain(hp as *const _ as usize, &[-16]);
+    assert_eq!(resolve_pointer_chain(Pid::this(), &mut regions, &path).unwrap(), &*stats as *const Stats as usize);
+}
+
+#[test]
+fn names_the_hop_that_was_null() {
+    let player = Box::new(Player { name: [0; 8], stats: std::ptr::null() });
+    let global: *const Player = &*player;
+    let global = std::hint::black_box(&global);
+    let mut regions = RegionCache::new(Pid::this()).unwrap();
+    let error = resolve_pointer_chain(Pid::this(), &mut regions, &chain(global as *const _ as usize, &[8, 16])).unwrap_err();
+    assert!(error.to_string().starts_with("hop 1: null pointer"), "{}", error);
+}
+
+#[test]
+fn names_the_hop_that_left_mapped_memory() {
+    let bogus = std::hint::black_box(0x10usize);
+    let mut regions = RegionCache::new(Pid::this()).unwrap();
+    let error = resolve_pointer_chain(Pid::this(), &mut regions, &chain(&bogus as *const usize as usize, &[0, 4])).unwrap_err();
+    assert!(error.to_string().starts_with("hop 1: 0x10 is not in mapped"), "{}", error);
+    let error = resolve_pointer_chain(Pid::this(), &mut regions, &chain(&bogus as *const usize as usize, &[4])).unwrap_err();
+    assert!(error.to_string().starts_with("hop 1: 0x14 is not in mapped"), "{}", error);
+    let error = resolve_pointer_chain(Pid::this(), &mut regions, &chain(0x10, &[4])).unwrap_err();
+    assert!(error.to_string().starts_with("hop 0:"), "{}", error);
+}
+
+#[test]
+fn parses_and_prints_chains() {
+    for text in ["libgame.so+0x1a2b0->0x10->-0x8", "0x7f0000001000", "0x1000->0x0"] {
+        assert_eq!(text.parse::<PointerChain>().unwrap().to_string(), text);
+    }
+    let chain = "game+0x10->+0x20->-0x4".parse::<PointerChain>().unwrap();
+    assert_eq!(chain.base, SavedAddress::Module { name: "game".to_string(), offset: 0x10 });
+    assert_eq!(chain.offsets, [0x20, -4]);
+    assert!("0x1000->zz".parse::<PointerChain>().is_err());
+}
+
+#[test]
+fn reads_this_process_as_native_width() {
+    assert_eq!(Pid::this().pointer_width(), std::mem::size_of::<usize>());
+}
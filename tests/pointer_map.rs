@@ -0,0 +1,95 @@
+#![cfg(target_os = "linux")]
+mod common;
+use std::{io::{BufRead, BufReader, Write}, path::PathBuf, process::{Command, Stdio}};
+use memory::{ChainSearch, MockProcess, PointerMap, ProcessMemory, ScanOptions, intersect_chains, modules_from_regions};
+use common::victim_path;
+
+const GAME: usize = 0x10000;
+const HEAP: usize = 0x20000;
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("rmh-pointer-map-test-{}-{}", std::process::id(), name))
+}
+
+// The game's static at +0x40 points at an object in the heap, whose field at +0x18 points at
+// another, which holds the value at +0x10. The objects are where the run put them
+fn run(game: usize, first: usize, second: usize) -> MockProcess {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 08:01 42 /usr/bin/game", game, game + 0x1000)).unwrap();
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", HEAP, HEAP + 0x1000)).unwrap();
+    mock.plant_value(game + 0x40, first).unwrap();
+    mock.plant_value(first + 0x18, second).unwrap();
+    mock
+}
+
+fn search() -> ChainSearch {
+    ChainSearch { min_offset: 0, max_offset: 0x100, depth: 3, limit: 100 }
+}
+
+#[test]
+fn chains_are_worked_back_to_a_static() {
+    let mock = run(GAME, HEAP + 0x100, HEAP + 0x800);
+    let (map, _) = PointerMap::build(&mock, &ScanOptions::default()).unwrap();
+    assert_eq!(map.pointers, [(GAME + 0x40, HEAP + 0x100), (HEAP + 0x118, HEAP + 0x800)]);
+    let chains = map.find_chains(HEAP + 0x810, &search()).iter().map(|x| x.to_string()).collect::<Vec<String>>();
+    assert_eq!(chains, ["game+0x40->0x18->0x10"]);
+    // Two pointers deep is not enough with a depth of one
+    assert!(map.find_chains(HEAP + 0x810, &ChainSearch { depth: 1, ..search() }).is_empty());
+}
+
+#[test]
+fn a_saved_map_reads_back_with_its_layout() {
+    let mock = run(GAME, HEAP + 0x100, HEAP + 0x800);
+    let (map, _) = PointerMap::build(&mock, &ScanOptions::default()).unwrap();
+    let path = temp_path("saved");
+    map.save(&path).unwrap();
+    let read = PointerMap::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!((&read.pointers, &read.header.uncaptured), (&map.pointers, &map.header.uncaptured));
+    assert_eq!(read.modules().iter().map(|x| (x.name.as_str(), x.base)).collect::<Vec<(&str, usize)>>(), [("game", GAME)]);
+}
+
+// The game loaded elsewhere the next run; what points into the heap keeps its address
+#[test]
+fn rebasing_moves_what_is_inside_modules() {
+    let mock = run(GAME, HEAP + 0x100, GAME + 0x800);
+    let (mut map, _) = PointerMap::build(&mock, &ScanOptions::default()).unwrap();
+    let moved = run(0x50000, HEAP + 0x100, HEAP + 0x800);
+    map.rebase(&modules_from_regions(&moved.memory_regions().unwrap()));
+    assert_eq!(map.pointers, [(HEAP + 0x118, 0x50800), (0x50040, HEAP + 0x100)]);
+    assert_eq!(map.modules()[0].base, 0x50000);
+}
+
+// Before the restart a second, stale chain also led to the value; after it only the real one does
+#[test]
+fn a_second_map_keeps_the_chains_that_survive_a_restart() {
+    let before = run(GAME, HEAP + 0x100, HEAP + 0x800);
+    before.plant_value(GAME + 0x80, HEAP + 0x800).unwrap();
+    let (before, _) = PointerMap::build(&before, &ScanOptions::default()).unwrap();
+    let chains = before.find_chains(HEAP + 0x810, &search());
+    assert_eq!(chains.iter().map(|x| x.to_string()).collect::<Vec<String>>(), ["game+0x80->0x10", "game+0x40->0x18->0x10"]);
+    let (after, _) = PointerMap::build(run(GAME, HEAP + 0x400, HEAP + 0x200), &ScanOptions::default()).unwrap();
+    let kept = intersect_chains(chains, &after, HEAP + 0x210);
+    assert_eq!(kept.iter().map(|x| x.to_string()).collect::<Vec<String>>(), ["game+0x40->0x18->0x10"]);
+}
+
+// The victim's static points at the player, so a built map gives the chain to its gold
+#[test]
+fn the_cli_builds_a_map_and_scans_it() {
+    let mut victim = Command::new(victim_path()).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut lines = BufReader::new(victim.stdout.take().unwrap()).lines();
+    let pid = lines.next().unwrap().unwrap().strip_prefix("pid ").unwrap().to_string();
+    let player = lines.next().unwrap().unwrap();
+    let player = usize::from_str_radix(player.strip_prefix("player 0x").unwrap().split_once(' ').unwrap().0, 16).unwrap();
+    let path = temp_path("cli");
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(&pid).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    writeln!(session.stdin.take().unwrap(), "pointermap build {}\npointerscan 0x{:x} {} --depth 1\npointerscan 0x{:x} --depth 1 --max-offset 0x8\npointerscan", path.display(), player + 8, path.display(), player + 8).unwrap();
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(stdout.contains(" pointers in ") && stdout.contains(&format!("saved to {}", path.display())), "{}", stdout);
+    let chains = stdout.lines().map(|x| x.trim_start_matches("> ")).filter(|x| x.starts_with("victim+") && x.ends_with("->0x8")).count();
+    assert_eq!(chains, 2, "{}", stdout);
+    assert!(stdout.contains("error: Usage: pointerscan <address>"), "{}", stdout);
+    let _ = victim.kill();
+    let _ = victim.wait();
+}
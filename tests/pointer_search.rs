@@ -0,0 +1,82 @@
+#![cfg(target_os = "linux")]
+mod common;
+use std::{io::{BufRead, BufReader, Write}, process::{Command, Stdio}};
+use memory::{MockProcess, PointerHit, PointerSearch, ScanOptions, find_pointers_to};
+use common::victim_path;
+
+const HEAP: usize = 0x20000;
+const TARGET: usize = HEAP + 0x800;
+
+// A game module with a data page, and a heap, the two pointers at the start of the heap pointing a
+// header 0x10 before the target and 8 past it
+fn mock() -> MockProcess {
+    let mock = MockProcess::new(100);
+    mock.map("10000-11000 rw-p 00000000 08:01 42 /usr/bin/game").unwrap();
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", HEAP, HEAP + 0x1000)).unwrap();
+    mock.plant_value(HEAP, TARGET - 0x10).unwrap();
+    mock.plant_value(HEAP + 0x100, TARGET + 8).unwrap();
+    mock
+}
+
+fn search(min_offset: isize, max_offset: isize) -> PointerSearch {
+    PointerSearch { min_offset, max_offset, ..PointerSearch::default() }
+}
+
+#[test]
+fn offsets_may_be_negative() {
+    let mock = mock();
+    let (hits, _) = find_pointers_to(&mock, TARGET, &PointerSearch::default(), &ScanOptions::default()).unwrap();
+    assert_eq!(hits, [PointerHit { location: HEAP, pointer: TARGET - 0x10, offset: 0x10 }]);
+    let (hits, _) = find_pointers_to(&mock, TARGET, &search(-0x8, 0x10), &ScanOptions::default()).unwrap();
+    assert_eq!(hits.iter().map(|x| (x.location, x.offset)).collect::<Vec<(usize, isize)>>(), [(HEAP, 0x10), (HEAP + 0x100, -0x8)]);
+    // Both ends of the window are included, and nothing outside it
+    let (hits, _) = find_pointers_to(&mock, TARGET, &search(-0x7, 0xf), &ScanOptions::default()).unwrap();
+    assert!(hits.is_empty(), "{:x?}", hits);
+}
+
+#[test]
+fn an_aligned_search_skips_pointers_stored_between_slots() {
+    let mock = mock();
+    mock.plant_value(HEAP + 0x203, TARGET).unwrap();
+    let (hits, _) = find_pointers_to(&mock, TARGET, &search(0, 0), &ScanOptions::default()).unwrap();
+    assert_eq!(hits.iter().map(|x| x.location).collect::<Vec<usize>>(), [HEAP + 0x203]);
+    let (hits, _) = find_pointers_to(&mock, TARGET, &PointerSearch { aligned: true, ..search(0, 0) }, &ScanOptions::default()).unwrap();
+    assert!(hits.is_empty(), "{:x?}", hits);
+}
+
+// Only the pointer in the module's data, whose chain still resolves after a restart
+#[test]
+fn a_static_search_keeps_pointers_stored_in_modules() {
+    let mock = mock();
+    mock.plant_value(0x10040, TARGET - 0x20).unwrap();
+    let (hits, _) = find_pointers_to(&mock, TARGET, &PointerSearch { static_only: true, ..PointerSearch::default() }, &ScanOptions::default()).unwrap();
+    assert_eq!(hits, [PointerHit { location: 0x10040, pointer: TARGET - 0x20, offset: 0x20 }]);
+    let modules = memory::modules_from_regions(&memory::ProcessMemory::memory_regions(&mock).unwrap());
+    assert_eq!(hits[0].chain(&modules).to_string(), "game+0x40->0x20");
+}
+
+#[test]
+fn a_window_the_wrong_way_round_is_an_error() {
+    let error = find_pointers_to(mock(), TARGET, &search(0x10, -0x10), &ScanOptions::default()).unwrap_err();
+    assert_eq!(error.to_string(), "The lowest offset, 16, is above the highest, -16");
+}
+
+// The victim's static points at the player, so points 8 before its gold
+#[test]
+fn the_cli_lists_each_pointer_with_its_chain() {
+    let mut victim = Command::new(victim_path()).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut lines = BufReader::new(victim.stdout.take().unwrap()).lines();
+    let pid = lines.next().unwrap().unwrap().strip_prefix("pid ").unwrap().to_string();
+    let player = lines.next().unwrap().unwrap();
+    let player = usize::from_str_radix(player.strip_prefix("player 0x").unwrap().split_once(' ').unwrap().0, 16).unwrap();
+    let pointer = lines.next().unwrap().unwrap();
+    let pointer = usize::from_str_radix(pointer.strip_prefix("pointer 0x").unwrap(), 16).unwrap();
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(&pid).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    writeln!(session.stdin.take().unwrap(), "pointers 0x{:x} --static --aligned\npointers 0x{:x} --min-offset 0x10 --max-offset -0x10\npointers", player + 8, player).unwrap();
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    let line = stdout.lines().find(|x| x.contains(&format!("(0x{:x}) -> 0x{:x} +0x8  victim+", pointer, player))).unwrap_or_else(|| panic!("{}", stdout));
+    assert!(line.ends_with("->0x8"), "{}", stdout);
+    assert!(stdout.contains("error: The lowest offset, 16, is above the highest, -16") && stdout.contains("error: Usage: pointers <address>"), "{}", stdout);
+    let _ = victim.kill();
+    let _ = victim.wait();
+}
@@ -0,0 +1,73 @@
+#![cfg(target_os = "linux")]
+mod common;
+use std::process::{Command, Stdio};
+use memory::{Pid, ProcessMemory, get_memory_regions, preflight};
+use common::Target;
+
+const CHECKS: [&str; 6] = ["process", "ptrace_scope", "permission", "dumpable", "process_vm_readv", "/proc/<pid>/mem"];
+
+// A pid that was in use a moment ago and no longer is
+fn exited_pid() -> Pid {
+    let mut child = Command::new("true").spawn().unwrap();
+    child.wait().unwrap();
+    Pid::from_raw(child.id() as i32)
+}
+
+#[test]
+fn every_check_passes_for_this_process() {
+    let checks = preflight(Pid::this());
+    assert_eq!(checks.iter().map(|x| x.name).collect::<Vec<&str>>(), CHECKS);
+    for check in &checks {
+        assert!(check.passed && check.suggestion.is_none(), "{:?}", check);
+    }
+}
+
+#[test]
+fn a_missing_process_stops_at_the_first_check() {
+    let pid = exited_pid();
+    let checks = preflight(pid);
+    assert_eq!(checks.len(), 1);
+    assert!(!checks[0].passed && checks[0].name == "process");
+    assert!(checks[0].detail.contains(&format!("no process {}", pid)), "{}", checks[0].detail);
+    assert!(checks[0].suggestion.as_ref().unwrap().contains("pgrep"));
+}
+
+// Whatever this environment allows, the check agrees with what reading the target does
+#[test]
+fn the_read_check_agrees_with_reading() {
+    let target = Target::spawn();
+    let checks = preflight(target.pid());
+    assert_eq!(checks.iter().map(|x| x.name).collect::<Vec<&str>>(), CHECKS);
+    let check = checks.iter().find(|x| x.name == "process_vm_readv").unwrap();
+    let readable = get_memory_regions(target.pid()).ok().and_then(|x| x.into_iter().find(|x| x.readable));
+    let read = readable.is_some_and(|x| target.pid().read_at(x.start, &mut [0u8; 8]).is_ok());
+    assert_eq!(check.passed, read, "{:?}", checks);
+    // Anything that failed says what to do about it
+    for check in checks.iter().filter(|x| !x.passed) {
+        assert!(check.suggestion.is_some(), "{:?}", check);
+    }
+}
+
+#[test]
+fn the_cli_doctor_reports_each_check() {
+    let target = Target::spawn();
+    let output = Command::new(env!("CARGO_BIN_EXE_memory")).args(["--doctor", &target.pid().to_string()]).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let reported = stdout.lines().filter(|x| x.starts_with("pass  ") || x.starts_with("FAIL  ")).count();
+    assert_eq!(reported, CHECKS.len(), "{}", stdout);
+    assert_eq!(output.status.success(), !stdout.contains("FAIL  "), "{}", stdout);
+    let output = Command::new(env!("CARGO_BIN_EXE_memory")).args(["--doctor", &exited_pid().to_string()]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("1 of 1 preflight checks failed"));
+}
+
+#[test]
+fn a_failed_attach_says_why() {
+    let pid = exited_pid();
+    let output = Command::new(env!("CARGO_BIN_EXE_memory")).arg(pid.to_string()).stdin(Stdio::null()).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!output.status.success());
+    assert!(stdout.contains(&format!("could not attach to {}, checking why:", pid)), "{}", stdout);
+    assert!(stdout.contains(&format!("FAIL  process: no process {}", pid)), "{}", stdout);
+    assert!(stdout.contains("      fix: check the pid"), "{}", stdout);
+}
@@ -0,0 +1,75 @@
+#![cfg(target_os = "linux")]
+use std::{process::{Command, Stdio}, sync::{Arc, Mutex}};
+use memory::{MockProcess, ProgressCallback, ScanOptions, ScanPriority, UnknownScan, find_value, priority_error};
+
+const HEAP: usize = 0x100000;
+
+fn mock() -> MockProcess {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", HEAP, HEAP + (1 << 20))).unwrap();
+    mock.plant_value(HEAP + 0x40, 31337i32).unwrap();
+    mock
+}
+
+// The thread's name, scheduling policy and nice value
+fn this_thread() -> (String, i32, i32) {
+    let name = std::thread::current().name().unwrap_or_default().to_string();
+    let nice = unsafe { libc::getpriority(libc::PRIO_PROCESS, libc::gettid() as libc::id_t) };
+    (name, unsafe { libc::sched_getscheduler(0) }, nice)
+}
+
+// Progress is reported from whichever worker gets there, so throttled into reporting a few times
+// it shows what the workers run as
+fn workers(mock: &MockProcess, priority: ScanPriority) -> Vec<(String, i32, i32)> {
+    let seen: Arc<Mutex<Vec<(String, i32, i32)>>> = Arc::default();
+    let recorded = seen.clone();
+    let progress = ProgressCallback(Arc::new(move |_| recorded.lock().unwrap().push(this_thread())));
+    let options = ScanOptions { priority, throttle: Some(4 << 20), progress: Some(progress), ..ScanOptions::default() };
+    assert_eq!(find_value(mock, 31337i32, &options).unwrap().0, [HEAP + 0x40]);
+    let seen = seen.lock().unwrap().clone();
+    assert!(!seen.is_empty());
+    seen
+}
+
+#[test]
+fn idle_scans_run_on_lowered_threads() {
+    let seen = workers(&mock(), ScanPriority::Idle);
+    // SCHED_IDLE where allowed, or failing that nice 10
+    assert!(seen.iter().all(|x| x.0.starts_with("scan-idle-") && (x.1 == libc::SCHED_IDLE || x.2 >= 10)), "{:?}", seen);
+    assert_eq!(priority_error(), None);
+}
+
+#[test]
+fn nice_scans_are_niced() {
+    let seen = workers(&mock(), ScanPriority::Nice);
+    assert!(seen.iter().all(|x| x.0.starts_with("scan-nice-") && x.1 == libc::SCHED_OTHER && x.2 >= 10), "{:?}", seen);
+}
+
+// Nothing but the scans is lowered: not the thread that asked for one, nor a normal scan's workers
+#[test]
+fn the_rest_of_the_process_keeps_its_priority() {
+    let mock = mock();
+    let before = this_thread();
+    workers(&mock, ScanPriority::Batch);
+    let mut unknown = UnknownScan::start(&mock, 4, &ScanOptions { priority: ScanPriority::Batch, ..ScanOptions::default() }).unwrap();
+    unknown.narrow::<i32>(&mock, |old, new| old == new).unwrap();
+    assert_eq!(this_thread(), before);
+    let seen = workers(&mock, ScanPriority::Normal);
+    assert!(seen.iter().all(|x| !x.0.starts_with("scan-") && x.1 == libc::SCHED_OTHER), "{:?}", seen);
+}
+
+#[test]
+fn priorities_parse_by_name() {
+    assert_eq!("batch".parse::<ScanPriority>(), Ok(ScanPriority::Batch));
+    assert_eq!(ScanPriority::Idle.to_string(), "idle");
+    assert_eq!("low".parse::<ScanPriority>().unwrap_err(), "Unknown priority 'low', expected normal, nice, batch or idle");
+}
+
+#[test]
+fn the_cli_takes_priority_per_scan() {
+    let session = Command::new(env!("CARGO_BIN_EXE_memory")).args(["--offline", "tests/fixtures/scan_before.cap", "--priority", "nice"]).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    std::io::Write::write_all(&mut session.stdin.as_ref().unwrap(), b"scan i32 100 --priority idle\nscan i32 100 --priority low\n").unwrap();
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    assert!(stdout.contains("error: Unknown priority 'low', expected normal, nice, batch or idle"), "{}", stdout);
+    assert_eq!(stdout.matches("error:").count() + stdout.matches("warning:").count(), 1, "{}", stdout);
+}
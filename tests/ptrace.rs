@@ -0,0 +1,91 @@
+use std::process::{Child, Command};
+use memory::{MemBackend, Process, ProcessMemory, RegionFilter, ScanOptions, read_bytes_from_process, slow_scan_bytes};
+use nix::unistd::Pid;
+
+struct Target(Child);
+
+impl Target {
+    fn spawn() -> Target {
+        Target(Command::new("sleep").arg("30").spawn().unwrap())
+    }
+
+    fn pid(&self) -> Pid {
+        Pid::from_raw(self.0.id() as i32)
+    }
+
+    fn state(&self) -> String {
+        let status = std::fs::read_to_string(format!("/proc/{}/status", self.pid())).unwrap();
+        status.lines().find_map(|x| x.strip_prefix("State:")).unwrap().trim().to_string()
+    }
+}
+
+impl Drop for Target {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+// Reads at odd addresses and lengths keep only the bytes asked for out of the aligned words
+#[test]
+fn reads_what_process_vm_reads() {
+    let target = Target::spawn();
+    let process = Process::with_backend(target.pid(), MemBackend::Ptrace).unwrap();
+    assert!(process.is_seized());
+    let vm = Process::with_backend(target.pid(), MemBackend::ProcessVmReadv).unwrap();
+    let first = process.memory_ranges().unwrap()[0];
+    for (offset, len) in [(0, 4096), (3, 21), (13, 1), (8, 8)] {
+        assert_eq!(read_bytes_from_process(&process, len, first.0 + offset).unwrap(), read_bytes_from_process(&vm, len, first.0 + offset).unwrap());
+    }
+    // Let go again between transfers
+    let state = target.state();
+    assert!(!state.starts_with(['t', 'T']), "{}", state);
+}
+
+// The words at either end of a write are read first, so the bytes around it are left alone
+#[test]
+fn writes_partial_words_at_the_edges() {
+    let target = Target::spawn();
+    let process = Process::with_backend(target.pid(), MemBackend::Ptrace).unwrap();
+    let vm = Process::with_backend(target.pid(), MemBackend::ProcessVmReadv).unwrap();
+    // The far end of the stack from where it is in use
+    let stack = process.memory_regions().unwrap().into_iter().find(|x| x.pathname == "[stack]").unwrap();
+    let before = read_bytes_from_process(&vm, 32, stack.start).unwrap();
+    assert_eq!(process.write_at(stack.start + 11, &[0xa1, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7, 0xa8, 0xa9]).unwrap(), 9);
+    let mut expected = before.clone();
+    expected[11..20].copy_from_slice(&[0xa1, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7, 0xa8, 0xa9]);
+    assert_eq!(read_bytes_from_process(&vm, 32, stack.start).unwrap(), expected);
+    assert_eq!(process.write_at(stack.start + 1, &[0x5a]).unwrap(), 1);
+    expected[1] = 0x5a;
+    assert_eq!(read_bytes_from_process(&vm, 32, stack.start).unwrap(), expected);
+    process.write_at(stack.start, &before).unwrap();
+}
+
+// A read running off the end of a mapping returns what it got before the fault
+#[test]
+fn stops_short_at_a_fault() {
+    let target = Target::spawn();
+    let process = Process::with_backend(target.pid(), MemBackend::Ptrace).unwrap();
+    let regions = process.memory_regions().unwrap();
+    let last = regions.iter().find(|x| x.readable && !regions.iter().any(|y| y.start == x.end)).unwrap();
+    let mut buffer = [0u8; 16];
+    assert_eq!(process.read_at(last.end - 5, &mut buffer).unwrap(), 5);
+    assert!(process.read_at(last.end, &mut buffer).is_err());
+    drop(target);
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    assert!(process.read_at(last.start, &mut buffer).is_err());
+}
+
+// Only unfiltered scans through the slow backend are worth warning about
+#[test]
+fn warns_before_a_slow_full_scan() {
+    let target = Target::spawn();
+    let process = Process::with_backend(target.pid(), MemBackend::Ptrace).unwrap();
+    assert!(process.is_slow());
+    assert!(slow_scan_bytes(&process, &ScanOptions::default()).unwrap().is_some_and(|x| x > 0));
+    let filtered = ScanOptions { filter: Some("heap".parse::<RegionFilter>().unwrap()), ..ScanOptions::default() };
+    assert_eq!(slow_scan_bytes(&process, &filtered).unwrap(), None);
+    let vm = Process::with_backend(target.pid(), MemBackend::ProcessVmReadv).unwrap();
+    assert_eq!(slow_scan_bytes(&vm, &ScanOptions::default()).unwrap(), None);
+    assert_eq!("ptrace".parse::<MemBackend>().unwrap(), MemBackend::Ptrace);
+}
@@ -0,0 +1,112 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+mod common;
+use std::{sync::Arc, time::{Duration, Instant}};
+use memory::{Endianness, MemBackend, Process, PtraceSession, WatchCallback, WatchKind, Watcher, read_scalar};
+use nix::unistd::Pid;
+use common::Target;
+
+impl Target {
+    // The named line of the thread's /proc status
+    fn status(&self, thread: Pid, name: &str) -> Option<String> {
+        let status = std::fs::read_to_string(format!("/proc/{}/task/{}/status", self.pid(), thread)).ok()?;
+        Some(status.lines().find_map(|x| x.strip_prefix(name)?.strip_prefix(':'))?.trim().to_string())
+    }
+
+    fn tracer(&self) -> String {
+        self.status(self.pid(), "TracerPid").unwrap()
+    }
+
+    // How far hp counts up over 100 milliseconds
+    fn counted(&self) -> i32 {
+        let process = Process::with_backend(self.pid(), MemBackend::ProcessVmReadv).unwrap();
+        let before = read_scalar::<i32>(&process, self.player, Endianness::Native).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        read_scalar::<i32>(&process, self.player, Endianness::Native).unwrap() - before
+    }
+}
+
+// The tracer is the session's own thread, so whether it is ours is told from /proc/self/task
+#[test]
+fn brings_in_threads_started_later() {
+    let target = Target::spawn_with("threads");
+    let session = PtraceSession::seize(target.pid()).unwrap();
+    let before = session.threads();
+    std::thread::sleep(Duration::from_millis(100));
+    let new = session.threads().into_iter().filter(|x| !before.contains(x)).collect::<Vec<Pid>>();
+    assert!(!new.is_empty());
+    let traced = new.iter().filter_map(|&x| target.status(x, "TracerPid")).collect::<Vec<String>>();
+    assert!(!traced.is_empty() && traced.iter().all(|x| std::path::Path::new(&format!("/proc/self/task/{}", x)).exists()), "{:?}", traced);
+    drop(session);
+    let tasks = std::fs::read_dir(format!("/proc/{}/task", target.pid())).unwrap().filter_map(|x| x.ok()?.file_name().to_str()?.parse::<i32>().ok()).map(Pid::from_raw);
+    for thread in tasks {
+        assert!(target.status(thread, "TracerPid").is_none_or(|x| x == "0"));
+    }
+}
+
+// SIGSTOP and SIGCONT work as they would untraced, and SIGTERM still ends it. The target is not
+// waited for, since a wait from any thread of the tracer's process takes the tracee's stops, and
+// here the tracer, being in its parent, reaps it
+#[test]
+fn passes_signals_on() {
+    let target = Target::spawn_with("tick");
+    let session = PtraceSession::seize(target.pid()).unwrap();
+    assert_eq!(unsafe { libc::kill(target.pid().as_raw(), libc::SIGSTOP) }, 0);
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(target.counted(), 0);
+    assert_eq!(unsafe { libc::kill(target.pid().as_raw(), libc::SIGCONT) }, 0);
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(target.counted() > 0);
+    assert_eq!(unsafe { libc::kill(target.pid().as_raw(), libc::SIGTERM) }, 0);
+    let started = Instant::now();
+    while !session.threads().is_empty() {
+        assert!(started.elapsed() < Duration::from_secs(5));
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert!(std::fs::metadata(format!("/proc/{}", target.pid())).is_err());
+    drop(session);
+}
+
+// Every seize in the process, including the Process's own, gets the same session, which lasts
+// until the last of them is dropped
+#[test]
+fn shares_one_session_per_process() {
+    let target = Target::spawn_with("tick");
+    let first = PtraceSession::seize(target.pid()).unwrap();
+    let tracer = target.tracer();
+    let second = PtraceSession::seize(target.pid()).unwrap();
+    let process = Process::seize(target.pid()).unwrap();
+    assert_eq!(process.ptrace_session().unwrap().threads(), first.threads());
+    drop(first);
+    assert_eq!(target.tracer(), tracer);
+    drop(second);
+    assert_eq!(target.tracer(), tracer);
+    drop(process);
+    assert_eq!(target.tracer(), "0");
+    let again = PtraceSession::seize(target.pid()).unwrap();
+    assert_ne!(target.tracer(), "0");
+    drop(again);
+    assert!(target.counted() > 0);
+}
+
+// A callback panicking on the session's thread ends the session, which still takes out what the
+// watcher armed and lets go, so the target carries on
+#[test]
+fn lets_go_when_a_handler_panics() {
+    let mut target = Target::spawn_with("tick");
+    for breakpoint in [false, true] {
+        let watcher = Watcher::start(target.pid(), WatchCallback(Arc::new(|_| panic!("callback")))).unwrap();
+        // The hit can come while arming, which then fails
+        let _ = match breakpoint {
+            false => watcher.watch(target.player, 4, WatchKind::Write),
+            true => watcher.break_at(target.tick.unwrap()),
+        };
+        let started = Instant::now();
+        while target.tracer() != "0" {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(target.counted() > 0);
+        assert!(target.child.try_wait().unwrap().is_none());
+        drop(watcher);
+    }
+}
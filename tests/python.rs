@@ -0,0 +1,90 @@
+#![cfg(feature = "python")]
+use std::ffi::CStr;
+use pyo3::{prelude::*, types::PyDict, wrap_pymodule};
+
+// Written by the module behind the compiler's back, so only touched through volatile reads and writes
+fn planted<T>(value: T) -> *mut T {
+    Box::into_raw(Box::new(value))
+}
+
+// Runs the code with the module imported as rmh, attached to this process as p, and the given
+// globals set, failing with the Python traceback if it raises
+fn run(code: &CStr, globals: &[(&str, usize)]) {
+    Python::initialize();
+    Python::attach(|py| {
+        let scope = PyDict::new(py);
+        scope.set_item("rmh", wrap_pymodule!(memory::python::rust_memory_hack)(py)).unwrap();
+        scope.set_item("pid", std::process::id()).unwrap();
+        for (name, value) in globals {
+            scope.set_item(name, value).unwrap();
+        }
+        py.run(c"p = rmh.Process(pid)", Some(&scope), None).unwrap();
+        if let Err(e) = py.run(code, Some(&scope), None) {
+            panic!("{}", format!("{}{}", e.traceback(py).and_then(|x| x.format().ok()).unwrap_or_default(), e));
+        }
+    });
+}
+
+// An errno becomes the OSError Python picks for it, a bad type or value a ValueError, and
+// anything else a RuntimeError
+#[test]
+fn errors_are_raised_as_python_exceptions() {
+    run(c"
+import errno
+try:
+    p.read_i32(0)
+    raise AssertionError('reading address 0 succeeded')
+except OSError as e:
+    assert e.errno == errno.EFAULT, e
+try:
+    rmh.Process(2**22 + 1)
+    raise AssertionError('attached to a pid past pid_max')
+except ProcessLookupError:
+    pass
+for value_type, value in [('i33', 1), ('i32', 'abc')]:
+    try:
+        p.scan(value_type, value)
+        raise AssertionError(value_type)
+    except ValueError:
+        pass
+try:
+    rmh.Process(pid, backend='procmem', seize=True)
+    raise AssertionError('seized with a backend')
+except RuntimeError as e:
+    assert 'does not go with a backend' in str(e), e
+", &[]);
+}
+
+#[test]
+fn locks_are_listed_and_removed() {
+    let hp = planted(100i32);
+    run(c"
+p.lock_i32(hp, 999, 1)
+import time
+deadline = time.monotonic() + 5
+while p.read_i32(hp) != 999 and time.monotonic() < deadline:
+    time.sleep(0.001)
+assert p.read_i32(hp) == 999
+locks = [(x.address, x.value_type, x.action, x.interval_ms, x.status) for x in p.locks()]
+assert locks == [(hp, 'i32', 'set', 1, 'running')], locks
+assert p.unlock(hp) and not p.unlock(hp)
+assert p.locks() == []
+", &[("hp", hp as usize)]);
+    assert_eq!(unsafe { std::ptr::read_volatile(hp) }, 999);
+}
+
+// A scan keeps its results between calls, so a rescan narrows them
+#[test]
+fn scans_narrow_and_read_back_their_results() {
+    let gold = planted(0x1234_5678_9abc_def0i64);
+    run(c"
+s = p.scan('i64', 0x123456789abcdef0)
+assert gold in s.addresses and s.value_type == 'i64' and s.bytes_scanned > 0, s
+p.write_i64(gold, 0x0fed_cba9_8765_4321)
+s.rescan(0x0fed_cba9_8765_4321)
+assert s.addresses == [gold] and s.values() == [0x0fed_cba9_8765_4321] and len(s) == 1, s.addresses
+assert repr(s) == '<Scan of %d for i64: 1 addresses>' % pid, repr(s)
+assert gold in p.find_i64(0x0fed_cba9_8765_4321)
+", &[("gold", gold as usize)]);
+    assert_eq!(unsafe { std::ptr::read_volatile(gold) }, 0x0fed_cba9_8765_4321);
+}
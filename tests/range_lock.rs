@@ -0,0 +1,81 @@
+#![cfg(target_os = "linux")]
+mod common;
+use std::{io::{BufRead, BufReader, Write}, process::{Command, Stdio}, time::{Duration, Instant}};
+use memory::{Endianness, LockManager, MockProcess, Pid, read_bytes_from_process, read_scalar};
+use common::victim_path;
+
+// Polls until the read gives the value, for writes made by a lock's servicing thread
+fn wait_for<T: PartialEq>(read: impl Fn() -> T, expected: T) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(5) {
+        if read() == expected {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    false
+}
+
+fn mock() -> &'static MockProcess {
+    let mock: &'static MockProcess = Box::leak(Box::new(MockProcess::new(100)));
+    mock.map("10000-11000 rw-p 00000000 00:00 0").unwrap();
+    mock.plant(0x10000, &[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+    mock
+}
+
+#[test]
+fn the_whole_block_is_put_back() {
+    let mock = mock();
+    let mut locks = LockManager::new(mock);
+    locks.lock_range(mock.bytes(0x10000, 8).unwrap(), 0x10000, Duration::from_millis(1), false);
+    let lock = locks.get(0x10000).unwrap();
+    assert_eq!((lock.type_name, lock.action.as_str(), lock.value_bytes.len()), ("bytes", "range", 8));
+    mock.plant(0x10002, &[0, 0]).unwrap();
+    mock.plant(0x10007, &[9]).unwrap();
+    assert!(wait_for(|| mock.bytes(0x10000, 8).unwrap(), vec![1, 2, 3, 4, 5, 6, 7, 8]));
+    locks.remove_all();
+}
+
+// Only ticks that find the block changed write, so the writes count how often it was disturbed
+#[test]
+fn if_changed_writes_only_when_it_differs() {
+    let mock = mock();
+    let mut locks = LockManager::new(mock);
+    locks.lock_range(mock.bytes(0x10000, 8).unwrap(), 0x10000, Duration::from_millis(1), true);
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(locks.get(0x10000).unwrap().writes, 0);
+    mock.plant(0x10004, &[0xff]).unwrap();
+    assert!(wait_for(|| mock.bytes(0x10000, 8).unwrap(), vec![1, 2, 3, 4, 5, 6, 7, 8]));
+    assert_eq!(locks.get(0x10000).unwrap().writes, 1);
+    assert_eq!(locks.get(0x10000).unwrap().action, "range if changed");
+    locks.remove_all();
+}
+
+// The victim's player starts with hp 100 and loses 1 a tick, which the lock puts back
+#[test]
+fn the_cli_freezes_a_struct() {
+    let mut victim = Command::new(victim_path()).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut lines = BufReader::new(victim.stdout.take().unwrap()).lines();
+    let pid = lines.next().unwrap().unwrap().strip_prefix("pid ").unwrap().to_string();
+    let player = lines.next().unwrap().unwrap();
+    let player = usize::from_str_radix(player.strip_prefix("player 0x").unwrap().split_once(' ').unwrap().0, 16).unwrap();
+    lines.next().unwrap().unwrap();
+    let target = Pid::from_raw(pid.parse().unwrap());
+    let before = read_bytes_from_process(target, 16, player).unwrap();
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(&pid).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    let mut stdin = session.stdin.take().unwrap();
+    writeln!(stdin, "lock range 0x{:x} 16 --interval 1ms\nlock range 0x{:x} 8k\nset range_lock_limit 4\nlock range 0x{:x} 8 --if-changed", player, player + 0x100, player + 0x100).unwrap();
+    std::thread::sleep(Duration::from_millis(300));
+    writeln!(victim.stdin.as_mut().unwrap()).unwrap();
+    assert_eq!(lines.next().unwrap().unwrap(), "ticked");
+    assert!(wait_for(|| read_scalar::<i32>(target, player, Endianness::Native).unwrap(), 100));
+    assert_eq!(read_bytes_from_process(target, 16, player).unwrap(), before);
+    writeln!(stdin, "locks\nquit").unwrap();
+    drop(stdin);
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    let _ = victim.kill();
+    let _ = victim.wait();
+    assert!(stdout.contains(" bytes range = 16 bytes ") && stdout.contains(" every 1ms ("), "{}", stdout);
+    assert!(stdout.contains("error: 8192 bytes is more than range_lock_limit (4096) freezes; --force locks it anyway"), "{}", stdout);
+    assert!(stdout.contains("error: 8 bytes is more than range_lock_limit (4) freezes"), "{}", stdout);
+}
@@ -0,0 +1,50 @@
+use memory::{RegionCache, read_cstring, read_string_lossy};
+use nix::unistd::Pid;
+
+// A string ending exactly at the end of a mapping. Making the page read-only keeps the kernel from
+// merging it with a neighbouring read-write mapping
+fn string_at_end_of_mapping(text: &[u8]) -> usize {
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize };
+    let address = unsafe { libc::mmap(std::ptr::null_mut(), page, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, -1, 0) };
+    assert_ne!(address, libc::MAP_FAILED);
+    let start = address as usize + page - text.len();
+    unsafe { std::ptr::copy_nonoverlapping(text.as_ptr(), start as *mut u8, text.len()) };
+    assert_eq!(unsafe { libc::mprotect(address, page, libc::PROT_READ) }, 0);
+    start
+}
+
+#[test]
+fn stops_at_the_first_nul() {
+    let buffer = std::hint::black_box(*b"Player_One\0garbage after\0");
+    let mut regions = RegionCache::new(Pid::this()).unwrap();
+    let name = read_cstring(Pid::this(), &mut regions, buffer.as_ptr() as usize, 64).unwrap();
+    assert_eq!(name.as_bytes(), b"Player_One");
+}
+
+#[test]
+fn stops_at_the_cap() {
+    let buffer = std::hint::black_box([b'x'; 1000]);
+    let mut regions = RegionCache::new(Pid::this()).unwrap();
+    assert_eq!(read_cstring(Pid::this(), &mut regions, buffer.as_ptr() as usize, 600).unwrap().as_bytes().len(), 600);
+    assert_eq!(read_cstring(Pid::this(), &mut regions, buffer.as_ptr() as usize, 0).unwrap().as_bytes(), b"");
+}
+
+#[test]
+fn stops_at_the_end_of_the_region() {
+    let address = string_at_end_of_mapping(b"EDGE!");
+    let mut regions = RegionCache::new(Pid::this()).unwrap();
+    assert_eq!(read_cstring(Pid::this(), &mut regions, address, 256).unwrap().as_bytes(), b"EDGE!");
+}
+
+#[test]
+fn replaces_invalid_utf8_when_lossy() {
+    let buffer = std::hint::black_box(*b"caf\xc3\xa9 \xff!\0");
+    let mut regions = RegionCache::new(Pid::this()).unwrap();
+    assert_eq!(read_string_lossy(Pid::this(), &mut regions, buffer.as_ptr() as usize, 64).unwrap(), "café \u{fffd}!");
+}
+
+#[test]
+fn fails_on_unmapped_addresses() {
+    let mut regions = RegionCache::new(Pid::this()).unwrap();
+    assert!(read_cstring(Pid::this(), &mut regions, 0x10, 64).is_err());
+}
@@ -0,0 +1,100 @@
+use std::{path::PathBuf, process::{Command, Stdio}, time::{Duration, UNIX_EPOCH}};
+use memory::{Arch, Capture, CaptureHeader, Compression, Errno, MOCK_PAGE_SIZE, MemoryRegion, MockProcess, ReadFailure, ScanOptions, find_value, read_error_name, reduce_found_bits, reduce_found_values};
+
+const HEAP: usize = 0x10_0000;
+const OTHER: usize = 0x40_0000;
+
+// A page of heap and a region of `other` bytes, both holding 5
+fn mock(other: usize) -> MockProcess {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", HEAP, HEAP + MOCK_PAGE_SIZE)).unwrap();
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0", OTHER, OTHER + other)).unwrap();
+    mock.plant_value(HEAP + 8, 5u64).unwrap();
+    mock.plant_value(OTHER + 8, 5u64).unwrap();
+    mock
+}
+
+fn failure(region: (usize, usize), error: &str, bytes_lost: usize) -> ReadFailure {
+    ReadFailure { region, error: error.to_string(), bytes_lost }
+}
+
+// Every chunk of the region failed, which is one failure of all its bytes
+#[test]
+fn scans_report_each_region_they_could_not_read() {
+    let mock = mock(3 << 20);
+    mock.unmap_after(0, OTHER);
+    let (found, stats) = find_value(&mock, 5u64, &ScanOptions::default()).unwrap();
+    assert_eq!(found, [HEAP + 8]);
+    assert_eq!(stats.read_failures, [failure((OTHER, OTHER + (3 << 20)), "EFAULT", 3 << 20)]);
+    assert_eq!((stats.bytes_lost(), stats.failed_regions), (3 << 20, vec![(OTHER, OTHER + (3 << 20))]));
+}
+
+// A read that stops at the faulting middle page loses only that page, and the scan picks up
+// again after it
+#[test]
+fn short_reads_lose_only_the_pages_they_stopped_at() {
+    let mock = mock(3 * MOCK_PAGE_SIZE);
+    mock.plant_value(OTHER + 2 * MOCK_PAGE_SIZE + 8, 5u64).unwrap();
+    mock.mark_failing(OTHER + MOCK_PAGE_SIZE, OTHER + 2 * MOCK_PAGE_SIZE).unwrap();
+    let (found, stats) = find_value(&mock, 5u64, &ScanOptions::default()).unwrap();
+    assert_eq!(found, [HEAP + 8, OTHER + 8, OTHER + 2 * MOCK_PAGE_SIZE + 8]);
+    assert_eq!(stats.read_failures, [failure((OTHER, OTHER + 3 * MOCK_PAGE_SIZE), "short read", MOCK_PAGE_SIZE)]);
+    assert_eq!((stats.bytes_scanned, stats.failed_regions), (3 * MOCK_PAGE_SIZE, vec![(OTHER, OTHER + 3 * MOCK_PAGE_SIZE)]));
+}
+
+#[test]
+fn a_clean_scan_has_no_failures() {
+    let (_, stats) = find_value(mock(MOCK_PAGE_SIZE), 5u64, &ScanOptions::default()).unwrap();
+    assert!(stats.read_failures.is_empty() && stats.bytes_lost() == 0);
+}
+
+// The results kept for not being readable are counted by the region they are in, or by
+// themselves once it is gone
+#[test]
+fn reduces_report_the_results_they_could_not_read() {
+    let mock = mock(2 * MOCK_PAGE_SIZE);
+    mock.plant_value(OTHER + MOCK_PAGE_SIZE + 8, 5u64).unwrap();
+    let (mut found, mut stats) = find_value(&mock, 5u64, &ScanOptions::default()).unwrap();
+    mock.mark_unreadable(OTHER + MOCK_PAGE_SIZE, OTHER + 2 * MOCK_PAGE_SIZE).unwrap();
+    reduce_found_values(&mock, &mut found, 5u64, &ScanOptions::default(), &mut stats).unwrap();
+    assert_eq!(found.len(), 3);
+    assert_eq!(stats.read_failures, [failure((OTHER + MOCK_PAGE_SIZE, OTHER + 2 * MOCK_PAGE_SIZE), "EFAULT", 8)]);
+    mock.unmap(OTHER).unwrap();
+    reduce_found_bits(&mock, &mut found, 0, true, &ScanOptions::default(), &mut stats).unwrap();
+    assert_eq!(stats.read_failures, [failure((OTHER + 8, OTHER + 9), "EFAULT", 1), failure((OTHER + MOCK_PAGE_SIZE + 8, OTHER + MOCK_PAGE_SIZE + 9), "EFAULT", 1)]);
+    // A reduce that reads everything leaves none from the one before
+    found.retain(|x| *x < OTHER);
+    reduce_found_values(&mock, &mut found, 5u64, &ScanOptions::default(), &mut stats).unwrap();
+    assert!(stats.read_failures.is_empty());
+}
+
+#[test]
+fn errors_are_named_by_errno() {
+    assert_eq!(read_error_name(&Errno::EIO), "EIO");
+    assert_eq!(read_error_name(&std::io::Error::from_raw_os_error(libc::EFAULT)), "EFAULT");
+    let other: Box<dyn std::error::Error> = "Short read".into();
+    assert_eq!(read_error_name(other.as_ref()), "Short read");
+}
+
+// A capture whose first region is all hole, for the session to fail to read
+fn capture_with_a_hole() -> PathBuf {
+    let path = std::env::temp_dir().join(format!("rmh-read-failures-{}.cap", std::process::id()));
+    let region = |start: usize, pathname: &str| MemoryRegion { start, end: start + 0x1000, readable: true, writable: true, pathname: pathname.to_string(), device: "00:00".to_string(), ..MemoryRegion::default() };
+    let header = CaptureHeader { version: 1, pid: 4242, time: UNIX_EPOCH + Duration::from_secs(1_700_000_000), arch: Arch::X86_64, executable: None, stacks: Vec::new(), uncaptured: Vec::new() };
+    Capture::write(&path, &header, Compression::None, vec![region(0x1000, "[guard]"), region(0x10000, "[heap]")], |address, block| {
+        block[..4].copy_from_slice(&100i32.to_ne_bytes());
+        Ok(if address == 0x1000 { vec![(0x1000, 0x2000)] } else { Vec::new() })
+    }).unwrap();
+    path
+}
+
+#[test]
+fn the_cli_summarises_and_lists_them() {
+    let path = capture_with_a_hole();
+    let session = Command::new(env!("CARGO_BIN_EXE_memory")).arg("--offline").arg(&path).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    std::io::Write::write_all(&mut session.stdin.as_ref().unwrap(), b"scan i32 100\nerrors\n").unwrap();
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(stdout.contains("skipped 1 regions / 4.0 KB: 1x EFAULT; run `errors` for details"), "{}", stdout);
+    assert!(stdout.contains("0x1000-0x2000 [guard]: EFAULT, 4.0 KB lost") && stdout.contains("1 matches"), "{}", stdout);
+}
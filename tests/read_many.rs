@@ -0,0 +1,92 @@
+use memory::{MemBackend, Process, read_many, read_many_bytes};
+use nix::unistd::Pid;
+use std::sync::{Mutex, MutexGuard};
+
+fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+// Tests run on parallel threads, and another test's mapping could otherwise land in the hole
+static MAPPINGS: Mutex<()> = Mutex::new(());
+
+// Three pages of this process with the middle one unmapped, and a marker at the start of each
+// mapped page. Making the pages read-only keeps the kernel from merging them with a neighbour
+fn mapping_with_hole() -> (usize, MutexGuard<'static, ()>) {
+    let guard = MAPPINGS.lock().unwrap_or_else(|x| x.into_inner());
+    let page = page_size();
+    let address = unsafe { libc::mmap(std::ptr::null_mut(), page * 3, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, -1, 0) } as usize;
+    assert_ne!(address as *mut libc::c_void, libc::MAP_FAILED);
+    unsafe {
+        *(address as *mut u32) = 0x1111;
+        *((address + page * 2) as *mut u32) = 0x3333;
+        assert_eq!(libc::munmap((address + page) as *mut libc::c_void, page), 0);
+        assert_eq!(libc::mprotect(address as *mut libc::c_void, page, libc::PROT_READ), 0);
+        assert_eq!(libc::mprotect((address + page * 2) as *mut libc::c_void, page, libc::PROT_READ), 0);
+    }
+    (address, guard)
+}
+
+// Every backend that can read this very process
+fn backends() -> Vec<Process> {
+    let pid = Pid::this();
+    vec![Process::with_backend(pid, MemBackend::ProcessVmReadv).unwrap(), Process::with_backend(pid, MemBackend::ProcMem).unwrap()]
+}
+
+#[test]
+fn keeps_the_order_of_the_addresses() {
+    let values = std::hint::black_box([10u64, 20, 30, 40, 50]);
+    let addresses = [4, 0, 2, 2, 1].map(|x| &values[x] as *const u64 as usize);
+    for process in backends() {
+        let read = read_many::<u64>(&process, &addresses).into_iter().map(|x| x.unwrap()).collect::<Vec<u64>>();
+        assert_eq!(read, [50, 10, 30, 30, 20], "{}", process.backend());
+    }
+}
+
+#[test]
+fn isolates_a_failing_entry() {
+    let (address, _guard) = mapping_with_hole();
+    let page = page_size();
+    for process in backends() {
+        let read = read_many::<u32>(&process, &[address, address + page, address + page * 2]);
+        assert_eq!(read[0].as_ref().unwrap(), &0x1111, "{}", process.backend());
+        assert!(read[1].is_err(), "{}", process.backend());
+        assert_eq!(read[2].as_ref().unwrap(), &0x3333, "{}", process.backend());
+    }
+}
+
+#[test]
+fn fails_entries_that_run_into_unmapped_memory() {
+    let (address, _guard) = mapping_with_hole();
+    let page = page_size();
+    for process in backends() {
+        let read = read_many_bytes(&process, &[(address + page - 4, 8), (address, 4), (address + page * 2, 2)]);
+        assert!(read[0].is_err(), "{}", process.backend());
+        assert_eq!(read[1].as_ref().unwrap(), &0x1111u32.to_ne_bytes(), "{}", process.backend());
+        assert_eq!(read[2].as_ref().unwrap(), &0x3333u32.to_ne_bytes()[..2], "{}", process.backend());
+    }
+}
+
+#[test]
+fn reads_more_entries_than_fit_in_one_call() {
+    let values = std::hint::black_box((0..5000u32).collect::<Vec<u32>>());
+    let (address, _guard) = mapping_with_hole();
+    let mut addresses = values.iter().map(|x| x as *const u32 as usize).collect::<Vec<usize>>();
+    // Failures spread over several batches
+    for index in [0, 1023, 1024, 2500, 4999] {
+        addresses[index] = address + page_size();
+    }
+    for process in backends() {
+        for (index, read) in read_many::<u32>(&process, &addresses).into_iter().enumerate() {
+            match [0, 1023, 1024, 2500, 4999].contains(&index) {
+                true => assert!(read.is_err(), "{} {}", process.backend(), index),
+                false => assert_eq!(read.unwrap(), index as u32, "{}", process.backend()),
+            }
+        }
+    }
+}
+
+#[test]
+fn reads_nothing_for_no_addresses() {
+    assert!(read_many::<u32>(Pid::this(), &[]).is_empty());
+    assert!(read_many_bytes(Pid::this(), &[(0, 0)])[0].as_ref().unwrap().is_empty());
+}
@@ -0,0 +1,64 @@
+use memory::{Endianness, FromBytes, Immutable, IntoBytes, KnownLayout, MockProcess, ScanOptions, field_address, find_value_generic, read_from_process, read_into, read_scalar, read_struct, write_struct, write_to_process};
+use nix::unistd::Pid;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, FromBytes, IntoBytes, Immutable, KnownLayout)]
+struct Vec3 {
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, FromBytes, IntoBytes, Immutable, KnownLayout)]
+struct Player {
+    hp: i32,
+    mp: i32,
+    pos: Vec3,
+    inventory: [u16; 4],
+}
+
+const PLAYER: Player = Player { hp: 100, mp: 50, pos: Vec3 { x: 1.0, y: 2.0, z: 3.0 }, inventory: [1, 2, 3, 4] };
+
+#[test]
+fn reads_and_writes_whole_structs() {
+    let player = Box::new(PLAYER);
+    let address = &*player as *const Player as usize;
+    assert_eq!(read_struct::<Player>(Pid::this(), address).unwrap(), PLAYER);
+    let moved = Player { pos: Vec3 { x: -1.0, ..PLAYER.pos }, hp: 1, ..PLAYER };
+    write_struct(Pid::this(), address, &moved).unwrap();
+    assert_eq!(**std::hint::black_box(&player), moved);
+}
+
+// The generic functions take the same structs, their bounds being what makes them safe
+#[test]
+fn the_generic_functions_take_structs_too() {
+    let mock = MockProcess::new(100);
+    mock.map("10000-11000 rw-p 00000000 00:00 0 [heap]").unwrap();
+    write_to_process(&mock, 0x10100, &PLAYER).unwrap();
+    assert_eq!(read_from_process::<Player>(&mock, 0x10100).unwrap(), PLAYER);
+    assert_eq!(find_value_generic(&mock, PLAYER, &ScanOptions::default()).unwrap().0, [0x10100]);
+    // A read cut short by the end of the region leaves the rest as it was
+    write_to_process(&mock, 0x10ff8, &[PLAYER.pos.x, PLAYER.pos.y]).unwrap();
+    let mut pos = Vec3 { x: 0.0, y: 0.0, z: 9.0 };
+    assert_eq!(read_into(&mock, 0x10ff8, &mut pos).unwrap(), 8);
+    assert_eq!(pos, Vec3 { z: 9.0, ..PLAYER.pos });
+    assert!(read_from_process::<Vec3>(&mock, 0x10ff8).unwrap_err().to_string().contains("Short read"));
+}
+
+#[test]
+fn finds_the_address_of_nested_fields() {
+    let player = std::hint::black_box(PLAYER);
+    let address = &player as *const Player as usize;
+    assert_eq!(field_address!(address, Player, hp), address);
+    assert_eq!(field_address!(address, Player, pos), &player.pos as *const Vec3 as usize);
+    assert_eq!(field_address!(address, Player, pos.z), &player.pos.z as *const f32 as usize);
+    assert_eq!(read_scalar::<f32>(Pid::this(), field_address!(address, Player, pos.y), Endianness::Native).unwrap(), 2.0);
+    assert_eq!(read_scalar::<u16>(Pid::this(), field_address!(address, Player, inventory) + 2, Endianness::Native).unwrap(), 2);
+}
+
+#[test]
+fn fails_on_unmapped_addresses() {
+    assert!(read_struct::<Player>(Pid::this(), 0x10).is_err());
+    assert!(write_struct(Pid::this(), 0x10, &PLAYER).is_err());
+}
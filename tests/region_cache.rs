@@ -0,0 +1,39 @@
+use std::sync::Arc;
+use memory::{MockProcess, RegionCache};
+
+// Three regions with gaps before, between and after them
+fn mock() -> Arc<MockProcess> {
+    let mock = MockProcess::new(100);
+    mock.map("10000-12000 r-xp 00001000 08:01 42 /opt/game/game").unwrap();
+    mock.map("20000-21000 rw-p 00003000 08:01 42 /opt/game/game").unwrap();
+    mock.map("30000-34000 rw-p 00000000 00:00 0 [heap]").unwrap();
+    Arc::new(mock)
+}
+
+#[test]
+fn finds_the_region_holding_an_address() {
+    let mock = mock();
+    let regions = RegionCache::from_process(mock.clone()).unwrap();
+    let start = |address: usize| regions.find(address).map(|x| x.start);
+    assert_eq!([start(0x10000), start(0x11fff), start(0x20800), start(0x33fff)], [Some(0x10000), Some(0x10000), Some(0x20000), Some(0x30000)]);
+    // Ends are exclusive, and the gaps between regions are in none of them
+    assert_eq!([start(0xffff), start(0x12000), start(0x21000), start(0x34000), start(usize::MAX)], [None; 5]);
+    let region = regions.find(0x20010).unwrap();
+    assert_eq!((region.permissions(), region.offset, region.pathname.as_str()), ("rw-p".to_string(), 0x3000, "/opt/game/game"));
+}
+
+// Hits are answered from the cache, and a miss re-reads the maps once in case it was mapped since
+#[test]
+fn only_a_miss_rereads_the_maps() {
+    let mock = mock();
+    let mut regions = RegionCache::from_process(mock.clone()).unwrap();
+    let before = mock.maps_reads();
+    assert_eq!(regions.region_for_address(0x30010).map(|x| x.end), Some(0x34000));
+    assert_eq!(mock.maps_reads(), before);
+    mock.map("40000-41000 rw-p 00000000 00:00 0").unwrap();
+    assert!(regions.find(0x40010).is_none());
+    assert_eq!(regions.region_for_address(0x40010).map(|x| x.start), Some(0x40000));
+    assert_eq!(mock.maps_reads(), before + 1);
+    assert!(regions.region_for_address(0x50000).is_none());
+    assert_eq!(mock.maps_reads(), before + 2);
+}
@@ -0,0 +1,79 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+mod common;
+use std::{io::Write, process::{Command, Stdio}, time::Duration};
+use memory::{Endianness, MemBackend, Process, continue_process, is_stopped, list_threads, read_registers, read_scalar, stop_process};
+use nix::unistd::Pid;
+use common::Target;
+
+impl Target {
+    fn process(&self) -> Process {
+        Process::with_backend(self.pid(), MemBackend::ProcessVmReadv).unwrap()
+    }
+
+    // How far hp counts up over 100 milliseconds
+    fn counted(&self) -> i32 {
+        let before = read_scalar::<i32>(&self.process(), self.player, Endianness::Native).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        read_scalar::<i32>(&self.process(), self.player, Endianness::Native).unwrap() - before
+    }
+
+    fn tracer(&self) -> String {
+        let status = std::fs::read_to_string(format!("/proc/{}/status", self.pid())).unwrap();
+        status.lines().find_map(|x| x.strip_prefix("TracerPid:")).unwrap().trim().to_string()
+    }
+}
+
+#[test]
+fn lists_every_thread() {
+    let target = Target::spawn();
+    let threads = list_threads(target.pid()).unwrap();
+    assert_eq!(threads.len(), 2, "{:?}", threads);
+    assert_eq!(threads[0].tid, target.pid());
+    assert!(threads.iter().all(|x| x.name == "bench_target" && "RS".contains(x.state)), "{:?}", threads);
+    stop_process(target.pid()).unwrap();
+    assert!(list_threads(target.pid()).unwrap().iter().all(|x| x.state == 'T'));
+    continue_process(target.pid()).unwrap();
+    assert!(list_threads(Pid::from_raw(i32::MAX)).is_err());
+}
+
+// Each thread is only interrupted for the read, and the session is gone afterwards
+#[test]
+fn reads_a_running_thread() {
+    let target = Target::spawn();
+    let regions = memory::get_memory_regions(target.pid()).unwrap();
+    for thread in list_threads(target.pid()).unwrap() {
+        let registers = read_registers(target.pid(), thread.tid).unwrap();
+        assert_eq!((registers.thread, registers.get("rip"), registers.get("rsp")), (thread.tid, Some(registers.pc), Some(registers.sp)));
+        assert!(regions.iter().any(|x| x.executable && x.contains(registers.pc as usize)), "0x{:x}", registers.pc);
+        assert!(regions.iter().any(|x| x.writable && x.contains(registers.sp as usize)), "0x{:x}", registers.sp);
+        assert_eq!(registers.values.len(), 21);
+    }
+    assert_eq!(target.tracer(), "0");
+    assert!(target.counted() > 0);
+    assert!(read_registers(target.pid(), Pid::from_raw(1)).unwrap_err().to_string().contains("not a thread"));
+}
+
+// Reading them does not continue a stopped target
+#[test]
+fn reads_a_stopped_target() {
+    let target = Target::spawn();
+    stop_process(target.pid()).unwrap();
+    let first = read_registers(target.pid(), target.pid()).unwrap();
+    assert!(is_stopped(target.pid()));
+    assert_eq!(target.counted(), 0);
+    assert_eq!(read_registers(target.pid(), target.pid()).unwrap(), first);
+    continue_process(target.pid()).unwrap();
+    assert!(target.counted() > 0);
+}
+
+#[test]
+fn the_session_shows_threads_and_registers() {
+    let target = Target::spawn();
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(target.pid().to_string()).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    writeln!(session.stdin.take().unwrap(), "threads\nregs\nregs 1").unwrap();
+    let output = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    assert!(output.contains(&format!("{:>7} S bench_target", target.pid())), "{}", output);
+    assert!(output.lines().any(|x| x.contains("rip ") && x.contains("libc.so.6+0x")), "{}", output);
+    assert!(output.lines().any(|x| x.starts_with("rsp ") && x.ends_with(&format!("stack of tid {}", target.pid()))), "{}", output);
+    assert!(output.contains("error: 1 is not a thread"), "{}", output);
+}
@@ -0,0 +1,50 @@
+use memory::{RegionCache, SavedAddress, resolve_address};
+use nix::unistd::Pid;
+
+static GLOBAL: u64 = 0x1122_3344_5566_7788;
+
+fn global() -> usize {
+    &GLOBAL as *const u64 as usize
+}
+
+#[test]
+fn module_relative_addresses_round_trip() {
+    let mut regions = RegionCache::new(Pid::this()).unwrap();
+    let saved = SavedAddress::from_address(global(), regions.modules());
+    let SavedAddress::Module { name, offset } = &saved else {
+        panic!("expected {} to be inside the test binary", saved);
+    };
+    assert_eq!(saved.to_string(), format!("{}+0x{:x}", name, offset));
+    assert_eq!(resolve_address(Pid::this(), &mut regions, &saved.to_string()).unwrap(), global());
+    assert_eq!(regions.resolve(&saved.to_string().parse().unwrap()).unwrap(), global());
+}
+
+#[test]
+fn absolute_addresses_resolve_to_themselves() {
+    let mut regions = RegionCache::new(Pid::this()).unwrap();
+    assert_eq!(resolve_address(Pid::this(), &mut regions, &format!("0x{:x}", global())).unwrap(), global());
+    assert_eq!(resolve_address(Pid::this(), &mut regions, &format!("{:x}", global())).unwrap(), global());
+}
+
+#[test]
+fn errors_name_the_missing_module() {
+    let mut regions = RegionCache::new(Pid::this()).unwrap();
+    let error = resolve_address(Pid::this(), &mut regions, "libnothere.so+0x10").unwrap_err().to_string();
+    assert_eq!(error, "Module libnothere.so is not loaded");
+    // A name cut short is answered with the loaded modules it could have meant
+    let SavedAddress::Module { name, .. } = SavedAddress::from_address(global(), regions.modules()) else {
+        unreachable!();
+    };
+    let error = resolve_address(Pid::this(), &mut regions, &format!("{}+0x10", &name[..name.len() - 1])).unwrap_err().to_string();
+    assert!(error.ends_with(&format!("is not loaded (loaded: {})", name)), "{}", error);
+}
+
+#[test]
+fn offsets_must_stay_inside_the_module() {
+    let mut regions = RegionCache::new(Pid::this()).unwrap();
+    let SavedAddress::Module { name, .. } = SavedAddress::from_address(global(), regions.modules()) else {
+        unreachable!();
+    };
+    let error = resolve_address(Pid::this(), &mut regions, &format!("{}+0xffffffffffff", name)).unwrap_err().to_string();
+    assert!(error.contains("is outside"), "{}", error);
+}
@@ -0,0 +1,70 @@
+#![cfg(target_os = "linux")]
+mod common;
+use std::{io::{BufRead, BufReader}, process::{Command, Stdio}, sync::Arc};
+use memory::{Encoding, Endianness, MockProcess, RegionCache, StringSlot, read_string_slot};
+use common::victim_path;
+
+const HEAP: usize = 0x100000;
+
+// A page of heap holding the bytes at its start, the rest of it zero
+fn mock(bytes: &[u8]) -> (Arc<MockProcess>, RegionCache) {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", HEAP, HEAP + 0x1000)).unwrap();
+    mock.plant(HEAP, bytes).unwrap();
+    let mock = Arc::new(mock);
+    let regions = RegionCache::from_process(mock.clone()).unwrap();
+    (mock, regions)
+}
+
+fn slot(text: &str, len: usize, terminated: bool, spare: usize, encoding: Encoding) -> StringSlot {
+    StringSlot { text: text.to_string(), len, terminated, spare, encoding }
+}
+
+// A name in a 16-byte field, with other data after it
+#[test]
+fn the_nuls_after_a_string_are_its_room() {
+    let (mock, mut regions) = mock(b"Player One\0\0\0\0\0\0\x05\0\0\0");
+    let found = read_string_slot(&*mock, &mut regions, HEAP, Encoding::Utf8, Endianness::Native, 256).unwrap();
+    assert_eq!(found, slot("Player One", 10, true, 5, Encoding::Utf8));
+    assert_eq!(found.room(), 16);
+}
+
+#[test]
+fn utf16_strings_end_at_a_nul_unit() {
+    let bytes = "Hé".encode_utf16().flat_map(|x| x.to_be_bytes()).chain([0, 0, 0, 0, 0, 0x41]).collect::<Vec<u8>>();
+    let (mock, mut regions) = mock(&bytes);
+    let found = read_string_slot(&*mock, &mut regions, HEAP, Encoding::Utf16, Endianness::Big, 256).unwrap();
+    // The odd zero byte before 0x41 is not a whole unit
+    assert_eq!(found, slot("Hé", 4, true, 2, Encoding::Utf16));
+    assert_eq!(found.room(), 8);
+}
+
+// Nothing past the text is known to be free without a terminator
+#[test]
+fn a_string_without_a_terminator_has_no_room_past_it() {
+    let (mock, mut regions) = mock(b"AAAAAAAAAA");
+    let found = read_string_slot(&*mock, &mut regions, HEAP, Encoding::Utf8, Endianness::Native, 4).unwrap();
+    assert_eq!((found.terminated, found.room()), (false, 4));
+    assert!(read_string_slot(&*mock, &mut regions, 0x5000, Encoding::Utf8, Endianness::Native, 4).unwrap_err().to_string().contains("is not mapped"));
+}
+
+// The victim's name field is 16 bytes at 0x14 into its player, holding "Player One"
+#[test]
+fn the_cli_replaces_the_victims_name() {
+    let mut victim = Command::new(victim_path()).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut lines = BufReader::new(victim.stdout.take().unwrap()).lines();
+    let pid = lines.next().unwrap().unwrap().strip_prefix("pid ").unwrap().to_string();
+    let player = usize::from_str_radix(lines.next().unwrap().unwrap().strip_prefix("player 0x").unwrap().split_once(' ').unwrap().0, 16).unwrap();
+    let name = player + 0x14;
+    let commands = format!("replace-string 0x{name:x} \"A name far too long for it\"\nreplace-string 0x{name:x} \"Hero\"\nreplace-string 0x{name:x} \"Heroine\" --pad none\nreplace-string 0x{name:x} \"Hi\" --pad space\n");
+    let session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(&pid).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    std::io::Write::write_all(&mut session.stdin.as_ref().unwrap(), commands.as_bytes()).unwrap();
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    let _ = victim.kill();
+    let _ = victim.wait();
+    assert!(stdout.contains("error: \"A name far too long for it\" takes 27 bytes, more than the "), "{}", stdout);
+    assert!(stdout.contains(": \"Player One\" -> \"Hero\", 11 bytes written"), "{}", stdout);
+    // Without padding, the NULs "Hero" was padded with keep ending it
+    assert!(stdout.contains(": \"Hero\" -> \"Heroine\", 7 bytes written") && stdout.contains("has to be set to 7 to match"), "{}", stdout);
+    assert!(stdout.contains(": \"Heroine\" -> \"Hi\", 8 bytes written"), "{}", stdout);
+}
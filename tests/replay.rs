@@ -0,0 +1,78 @@
+#![cfg(target_os = "linux")]
+mod common;
+use std::{io::Write, path::PathBuf, process::{Command, Stdio}};
+use memory::{JournalFile, Process, SavedAddress, modules, read_bytes_from_process};
+use common::Target;
+
+impl Target {
+    // Runs the commands in a session on the target, returning what it printed
+    fn session(&self, commands: &str) -> String {
+        let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(self.pid().to_string()).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+        session.stdin.take().unwrap().write_all(commands.as_bytes()).unwrap();
+        let output = session.wait_with_output().unwrap();
+        format!("{}{}", String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap())
+    }
+
+    // Where the ELF header is mapped, which is never run so can be patched safely
+    fn header(&self) -> usize {
+        modules(self.pid()).unwrap().iter().find(|x| x.name == "bench_target").unwrap().base
+    }
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("rmh-replay-{}-{}.jsonl", std::process::id(), name))
+}
+
+// A patch of the executable, a write and a lock on the heap, and the patch restored on exit
+fn journal(name: &str) -> (Target, PathBuf) {
+    let target = Target::spawn();
+    let path = temp_path(name);
+    let output = target.session(&format!("journal file {}\nnop bench_target+0x8 2 --force\nwrite 0x{:x} 7\nlock 0x{:x} i32 500\n", path.display(), target.player, target.player));
+    assert!(output.contains("journaling every change to"), "{}", output);
+    (target, path)
+}
+
+#[test]
+fn journals_every_change_as_it_is_made() {
+    let (target, path) = journal("record");
+    let records = JournalFile::read(&path).unwrap();
+    assert_eq!(records.iter().map(|x| x.action.as_str()).collect::<Vec<&str>>(), ["patch", "write", "lock", "restore"]);
+    assert_eq!(records[3].command, "exit");
+    assert!(records.iter().all(|x| x.pid == target.pid().as_raw()));
+    assert_eq!((&records[0].address, records[0].new.as_slice(), records[0].command.as_str()), (&SavedAddress::Module { name: "bench_target".to_string(), offset: 8 }, &[0x90, 0x90][..], "nop bench_target+0x8 2 --force"));
+    let original = read_bytes_from_process(Process::attach(target.pid()).unwrap(), 2, target.header() + 8).unwrap();
+    assert_eq!((records[0].old.clone(), records[3].new.clone()), (original.clone(), original));
+    assert_eq!((records[1].absolute, records[1].new.as_slice()), (target.player, &7i32.to_ne_bytes()[..]));
+    assert_eq!(records[2].lock.as_ref().unwrap().value_bytes, 500i32.to_ne_bytes());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn stops_at_what_cannot_be_found_in_the_new_instance() {
+    let (old, path) = journal("stop");
+    let target = Target::spawn();
+    let output = target.session(&format!("journal replay {} --dry-run\n", path.display()));
+    assert!(output.contains("#0 would patch bench_target+0x8 ("), "{}", output);
+    assert!(output.contains(&format!("Stopped at #1 write at 0x{:x} (from `write 0x{:x} 7`) after 1 of 4 entries: 0x{:x} is outside any module of process {}", old.player, old.player, old.player, old.pid())), "{}", output);
+    // Nothing was written
+    let header = read_bytes_from_process(Process::attach(target.pid()).unwrap(), 2, target.header() + 8).unwrap();
+    assert_ne!(header, [0x90, 0x90]);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn keeps_going_past_what_cannot_be_found() {
+    let (_old, path) = journal("keep-going");
+    let target = Target::spawn();
+    let replayed = temp_path("keep-going-replayed");
+    let output = target.session(&format!("journal file {}\njournal replay {} --keep-going\n", replayed.display(), path.display()));
+    assert!(output.contains("skipped #1 write") && output.contains("skipped #2 lock"), "{}", output);
+    assert!(output.contains("replayed 2 of 4 entries"), "{}", output);
+    // The replay is journaled in turn, against the new process
+    let records = JournalFile::read(&replayed).unwrap();
+    assert_eq!(records.iter().map(|x| x.action.as_str()).collect::<Vec<&str>>(), ["patch", "restore"]);
+    assert!(records.iter().all(|x| x.pid == target.pid().as_raw() && x.command.starts_with("journal replay ")));
+    for path in [path, replayed] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
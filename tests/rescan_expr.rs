@@ -0,0 +1,89 @@
+use std::process::{Command, Stdio};
+use memory::{Endianness, Expression, InitialValues, MockProcess, ScanOptions, ScanStats, UnknownScan, reduce_by_expression};
+
+const HEAP: usize = 0x100000;
+
+// A page of heap holding each value at its own 8 bytes
+fn mock<T: memory::Scalar>(values: &[T]) -> MockProcess {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", HEAP, HEAP + 0x1000)).unwrap();
+    for (i, value) in values.iter().enumerate() {
+        mock.plant_value(HEAP + 8 * i, *value).unwrap();
+    }
+    mock
+}
+
+fn evaluate(text: &str, old: i128) -> Option<i128> {
+    Expression::parse::<i32>(text).unwrap().evaluate_integer(old)
+}
+
+// Integer division truncates towards zero, whatever the sign
+#[test]
+fn expressions_work_out_as_written() {
+    assert_eq!(evaluate("old * 2", 21), Some(42));
+    assert_eq!(evaluate("old - 7 * 2", 20), Some(6));
+    assert_eq!(evaluate(" ( old - 7 ) * 2", 20), Some(26));
+    assert_eq!(evaluate("-old / 2", 7), Some(-3));
+    assert_eq!(evaluate("old / 2 + 0x10", 7), Some(19));
+    assert_eq!(evaluate("old / (old - 7)", 7), None);
+    assert_eq!(Expression::parse::<f32>("old / 2 + 0.5").unwrap().evaluate_float(7.0), 4.0);
+}
+
+#[test]
+fn bad_expressions_are_refused() {
+    assert_eq!(Expression::parse::<i32>("old *").unwrap_err(), "Expected old, a number or '(' at the end of 'old *'");
+    assert_eq!(Expression::parse::<i32>("(old - 7").unwrap_err(), "Expected ')' at the end of '(old - 7'");
+    assert_eq!(Expression::parse::<i32>("old old").unwrap_err(), "Expected an operator at 'o' in 'old old'");
+    assert_eq!(Expression::parse::<i32>("older * 2").unwrap_err(), "Expected old, a number or '(' rather than 'older' in 'older * 2'");
+    assert_eq!(Expression::parse::<u8>("old * 1.5").unwrap_err(), "1.5 is not a whole number, which an expression for u8 values needs, as they are worked out as integers");
+    assert!(Expression::parse::<f64>("old * 1.5").is_ok());
+}
+
+#[test]
+fn results_are_kept_where_the_expression_holds() {
+    let mock = mock(&[10i32, 11, 12, -7]);
+    let mut found = vec![HEAP, HEAP + 8, HEAP + 16, HEAP + 24];
+    let last = InitialValues::record::<i32>(&mock, &found, Endianness::Native);
+    for (i, value) in [20i32, 5, 24, -3].into_iter().enumerate() {
+        mock.plant_value(HEAP + 8 * i, value).unwrap();
+    }
+    let mut stats = ScanStats::default();
+    reduce_by_expression::<i32>(&mock, &mut found, &last, &Expression::parse::<i32>("old * 2").unwrap(), &ScanOptions::default(), &mut stats).unwrap();
+    assert_eq!(found, [HEAP, HEAP + 16]);
+    // 11 / 2 and -7 / 2 truncate to 5 and -3
+    let mut found = vec![HEAP + 8, HEAP + 24];
+    reduce_by_expression::<i32>(&mock, &mut found, &last, &Expression::parse::<i32>("old / 2").unwrap(), &ScanOptions::default(), &mut stats).unwrap();
+    assert_eq!(found, [HEAP + 8, HEAP + 24]);
+}
+
+// 0.1 * 3 as worked out in f64 is not quite the nearest f32 to 0.3, which still matches
+#[test]
+fn floats_match_within_their_tolerance() {
+    let expression = Expression::parse::<f32>("old * 3").unwrap();
+    assert!(expression.matches(0.1f32, 0.3f32));
+    assert!(!expression.matches(0.1f32, 0.3001f32));
+    assert!(!Expression::parse::<f64>("old / 0").unwrap().matches(0.0f64, 0.0f64));
+    assert!(Expression::parse::<f64>("old / 0").unwrap().matches(1.0f64, f64::INFINITY));
+}
+
+#[test]
+fn unknown_scans_narrow_by_expression() {
+    let mock = mock(&[100u32, 200]);
+    let mut unknown = UnknownScan::start(&mock, 4, &ScanOptions { alignment: Some(8), ..ScanOptions::default() }).unwrap();
+    mock.plant_value(HEAP, 93u32).unwrap();
+    mock.plant_value(HEAP + 8, 207u32).unwrap();
+    let expression = Expression::parse::<u32>("old - 7").unwrap();
+    unknown.narrow::<u32>(&mock, |old, new| expression.matches(old, new)).unwrap();
+    assert_eq!(unknown.addresses(), [HEAP]);
+}
+
+// A bad expression fails without a rescan, so the results are as the scan left them
+#[test]
+fn the_cli_rescans_by_expression() {
+    let session = Command::new(env!("CARGO_BIN_EXE_memory")).args(["--offline", "tests/fixtures/scan_before.cap"]).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    std::io::Write::write_all(&mut session.stdin.as_ref().unwrap(), b"scan i32 31337\nrescan expr \"old * \"\nrescan expr \"old / 2 * 2 + 1\"\nrescan expr \"old * 2\"\nhelp rescan\n").unwrap();
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    assert!(stdout.contains("error: Expected old, a number or '(' at the end of 'old * '"), "{}", stdout);
+    assert_eq!(stdout.matches("5 matches").count(), 2, "{}", stdout);
+    assert!(stdout.contains("0 matches") && stdout.contains("dividing by truncating towards zero"), "{}", stdout);
+}
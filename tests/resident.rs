@@ -0,0 +1,45 @@
+#![cfg(target_os = "linux")]
+use std::process::Command;
+use memory::{Pid, ProcessMemory, Residency, resident_ranges};
+
+// Four pages of this process: the first and third written, the last only read, which maps the
+// shared zero page, and the second never touched
+#[test]
+fn pagemap_gives_the_written_pages() {
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize };
+    let address = unsafe { libc::mmap(std::ptr::null_mut(), 4 * page, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, -1, 0) };
+    assert_ne!(address, libc::MAP_FAILED);
+    let address = address as usize;
+    unsafe {
+        std::ptr::write_volatile(address as *mut u8, 1);
+        std::ptr::write_volatile((address + 2 * page + 8) as *mut u8, 1);
+        std::ptr::read_volatile((address + 3 * page) as *const u8);
+    }
+    let regions = Pid::this().memory_regions().unwrap();
+    let (resident, residency) = resident_ranges(Pid::this(), &[(address + 0x10, address + 4 * page)], &regions);
+    unsafe { libc::munmap(address as *mut libc::c_void, 4 * page) };
+    assert_eq!(residency, Residency::Pagemap);
+    // Cut to the range asked about at the start
+    assert_eq!(resident, [(address + 0x10, address + page), (address + 2 * page, address + 3 * page)]);
+}
+
+// A zombie has no memory left: its pagemap reads short, so smaps is used, and lists no mappings
+#[test]
+fn smaps_is_used_when_pagemap_cannot_be_read() {
+    let mut child = Command::new("true").spawn().unwrap();
+    let stat = format!("/proc/{}/stat", child.id());
+    while !std::fs::read_to_string(&stat).unwrap().contains(") Z ") {
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    let (resident, residency) = resident_ranges(Pid::from_raw(child.id() as i32), &[(0x1000, 0x2000)], &[]);
+    child.wait().unwrap();
+    assert_eq!((resident, residency), (vec![], Residency::Smaps));
+}
+
+// With neither to go by nothing is dropped
+#[test]
+fn everything_is_kept_when_neither_can_be_read() {
+    let ranges = [(0x1000, 0x2000), (0x5000, 0x8000)];
+    assert_eq!(resident_ranges(Pid::from_raw(i32::MAX), &ranges, &[]), (ranges.to_vec(), Residency::Unavailable));
+    assert_eq!(Residency::Smaps.to_string(), "smaps");
+}
@@ -0,0 +1,114 @@
+#![cfg(target_os = "linux")]
+mod common;
+use std::{io::{BufRead, BufReader, Write}, process::{Child, Command, Stdio}, time::Duration};
+use memory::{Pid, PointerChain, RegionCache, SavedAddress, SavedLock, SessionFile, ValueType};
+use common::victim_path;
+
+fn temp_path(name: &str) -> String {
+    std::env::temp_dir().join(format!("rmh-resolve-all-test-{}-{}", std::process::id(), name)).to_string_lossy().into_owned()
+}
+
+// A victim, with where its player is and the chain through its static to the gold
+struct Victim {
+    child: Child,
+    pid: String,
+    player: usize,
+    chain: String,
+}
+
+impl Victim {
+    fn spawn() -> Victim {
+        let mut child = Command::new(victim_path()).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+        let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+        let mut next = |prefix: &str| lines.next().unwrap().unwrap().strip_prefix(prefix).unwrap().to_string();
+        let pid = next("pid ");
+        let player = usize::from_str_radix(next("player 0x").split_once(' ').unwrap().0, 16).unwrap();
+        let pointer = usize::from_str_radix(&next("pointer 0x"), 16).unwrap();
+        let mut regions = RegionCache::new(Pid::from_raw(pid.parse().unwrap())).unwrap();
+        let base = regions.module_for_address(pointer).unwrap().base;
+        Victim { child, pid, player, chain: format!("victim+0x{:x}->0x8", pointer - base) }
+    }
+}
+
+impl Drop for Victim {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn run(victim: &Victim, commands: &str) -> String {
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(&victim.pid).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    writeln!(session.stdin.take().unwrap(), "{}", commands).unwrap();
+    String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap()
+}
+
+#[test]
+fn a_lock_keeps_the_chain_it_was_made_through() {
+    let path = temp_path("chain");
+    let lock = SavedLock { address: SavedAddress::Absolute(0x1000), value_type: ValueType::I64, value_bytes: 9999i64.to_ne_bytes().to_vec(), action: "set 9999".to_string(), interval: Duration::from_millis(100), enabled: true, chain: Some("game+0x40->0x18->0x8".parse::<PointerChain>().unwrap()) };
+    let session = SessionFile { locks: vec![lock], ..SessionFile::default() };
+    session.save(&path).unwrap();
+    assert!(std::fs::read_to_string(&path).unwrap().contains("\"chain\": \"game+0x40->0x18->0x8\""));
+    assert_eq!(SessionFile::load(&path).unwrap(), session);
+    // A lock made without one is saved without the field
+    SessionFile { locks: vec![SavedLock { chain: None, ..session.locks[0].clone() }], ..SessionFile::default() }.save(&path).unwrap();
+    assert!(!std::fs::read_to_string(&path).unwrap().contains("\"chain\""));
+    std::fs::remove_file(path).unwrap();
+}
+
+// The player is somewhere else in the new instance; the chain leads to its gold there, and the
+// lock follows it on load, active as it was
+#[test]
+fn a_chain_lock_finds_the_value_in_a_new_instance() {
+    let path = temp_path("new-instance");
+    let before = Victim::spawn();
+    let stdout = run(&before, &format!("lock {} i64 set 9999\nsave {}", before.chain, path));
+    assert!(!stdout.contains("error:"), "{}", stdout);
+    let after = Victim::spawn();
+    let stdout = run(&after, &format!("load {}\nresolve all", path));
+    std::fs::remove_file(&path).unwrap();
+    let line = stdout.lines().find(|x| x.contains(&format!("lock {} i64 set: 0x{:x}", after.chain, after.player + 8))).unwrap_or_else(|| panic!("{}", stdout));
+    assert!(line.ends_with("= 9999, active"), "{}", stdout);
+    assert!(stdout.contains("resolved 1 of 1 entries") && stdout.contains("1 through pointer chains"), "{}", stdout);
+}
+
+// An absolute lock is where the value was before the restart, so it is loaded paused and flagged
+// until resumed
+#[test]
+fn absolute_locks_are_flagged_and_resumed_on_request() {
+    let path = temp_path("absolute");
+    let before = Victim::spawn();
+    run(&before, &format!("lock 0x{:x} i32 set 50\nsave {}", before.player, path));
+    let after = Victim::spawn();
+    let stdout = run(&after, &format!("load {}\nresolve all\nresolve all --resume", path));
+    std::fs::remove_file(&path).unwrap();
+    let lines = stdout.lines().filter(|x| x.contains(&format!("lock 0x{:x} i32 set: ", before.player))).collect::<Vec<&str>>();
+    assert_eq!(lines.len(), 2, "{}", stdout);
+    assert!(lines[0].ends_with(", paused  (absolute, may be stale)") && lines[1].ends_with(", active  (absolute, may be stale)"), "{}", stdout);
+    assert!(stdout.contains("1 locks were active when saved but are paused; `resolve all --resume` turns them back on"), "{}", stdout);
+    assert!(stdout.contains("1 absolute and possibly stale"), "{}", stdout);
+}
+
+// A chain starting well past the end of the executable no longer resolves, which is reported
+// rather than locked
+#[test]
+fn a_chain_that_breaks_is_reported_as_failed() {
+    let path = temp_path("broken");
+    let victim = Victim::spawn();
+    let chain = "victim+0x100000000->0x8";
+    let lock = SavedLock { address: SavedAddress::Absolute(victim.player), value_type: ValueType::I64, value_bytes: 1i64.to_ne_bytes().to_vec(), action: "set 1".to_string(), interval: Duration::from_millis(100), enabled: true, chain: Some(chain.parse().unwrap()) };
+    SessionFile { locks: vec![lock], ..SessionFile::default() }.save(&path).unwrap();
+    let stdout = run(&victim, &format!("load {}\nresolve all", path));
+    std::fs::remove_file(&path).unwrap();
+    assert!(stdout.contains(&format!("lock {} i64: ", chain)), "{}", stdout);
+    assert!(stdout.contains("resolved 0 of 1 entries") && stdout.contains("1 failed"), "{}", stdout);
+}
+
+#[test]
+fn resolving_needs_a_loaded_session() {
+    let victim = Victim::spawn();
+    let stdout = run(&victim, "resolve all\nresolve all --now");
+    assert!(stdout.contains("error: resolve all finds a loaded session's entries again, so load one first"), "{}", stdout);
+    assert!(stdout.contains("error: Usage: resolve all [--resume]"), "{}", stdout);
+}
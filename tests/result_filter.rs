@@ -0,0 +1,77 @@
+mod common;
+use std::{io::Write, process::{Command, Stdio}};
+use memory::{MemoryRegion, MockProcess, ScanOptions, UnknownScan, within_regions};
+use common::mock_with_heap;
+
+fn mock() -> MockProcess {
+    let mock = mock_with_heap(0x1000);
+    mock.map("20000-21000 rw-p 00000000 00:00 0").unwrap();
+    mock
+}
+
+// Runs the commands on the capture's scan for 31337, which finds game+0x4010, game+0x4101,
+// game+0x5ffc, heap+0xffffe and 0x121fffc, in the mapping after [heap] that counts as heap too
+fn offline(commands: &str) -> String {
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).args(["--offline", "tests/fixtures/scan_before.cap"]).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    writeln!(session.stdin.take().unwrap(), "scan i32 31337\n{}", commands).unwrap();
+    String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap()
+}
+
+#[test]
+fn addresses_are_looked_up_in_the_regions() {
+    let regions = ["1000-2000 rw-p 00000000 00:00 0", "3000-4000 rw-p 00000000 00:00 0"].map(|x| x.parse::<MemoryRegion>().unwrap());
+    let regions = regions.iter().collect::<Vec<&MemoryRegion>>();
+    let inside = [0xfff, 0x1000, 0x1fff, 0x2000, 0x3800, 0x4000].map(|x| within_regions(&regions, x));
+    assert_eq!(inside, [false, true, true, false, true, false]);
+    assert!(!within_regions(&[], 0x1000));
+}
+
+// A filter is a generation like any narrow, even though it reads nothing
+#[test]
+fn unknown_candidates_are_filtered_and_restored() {
+    let mock = mock();
+    let mut unknown = UnknownScan::start(&mock, 4, &ScanOptions::default()).unwrap();
+    let before = unknown.candidates();
+    let candidates = unknown.retain(|x| x >= 0x20000 && x % 16 == 0);
+    assert_eq!((unknown.candidates(), unknown.generation), (0x100, 1));
+    assert!(unknown.addresses().iter().all(|x| *x >= 0x20000 && x % 16 == 0));
+    unknown.restore(candidates).unwrap();
+    assert_eq!((unknown.candidates(), unknown.generation), (before, 0));
+}
+
+#[test]
+fn a_narrow_since_stops_the_filter_being_undone() {
+    let mock = mock();
+    let mut unknown = UnknownScan::start(&mock, 4, &ScanOptions::default()).unwrap();
+    let candidates = unknown.retain(|x| x < 0x10100);
+    unknown.narrow::<u32>(&mock, |old, new| old == new).unwrap();
+    let error = unknown.restore(candidates).unwrap_err();
+    assert!(error.to_string().contains("narrowed since"), "{}", error);
+    assert_eq!(unknown.candidates(), 0x40);
+}
+
+#[test]
+fn results_are_filtered_by_alignment_module_region_and_range() {
+    let stdout = offline("filter align 4\nfilter undo\nfilter module game\nfilter undo\nfilter region heap\nfilter undo\nfilter range game+0x4100..0x1100000\nlist");
+    let kept = stdout.lines().filter_map(|x| x.trim_start_matches("> ").strip_prefix("kept ")).collect::<Vec<&str>>();
+    assert_eq!(kept, ["3 of 5 results", "3 of 5 results", "2 of 5 results", "3 of 5 results"], "{}", stdout);
+    assert!(stdout.contains("#0 game+0x4101 (0x404101)") && stdout.contains("#2 0x10ffffe (heap+0xffffe)"), "{}", stdout);
+}
+
+// Each undo goes back one filter, and none is left once the results are replaced
+#[test]
+fn filters_are_undone_in_turn() {
+    let stdout = offline("filter module game\nfilter align 16\nfilter undo\nfilter undo\nfilter undo\nfilter align 8\nscan i32 31337\nfilter undo");
+    let back = stdout.lines().filter_map(|x| x.trim_start_matches("> ").strip_prefix("back to ")).collect::<Vec<&str>>();
+    assert_eq!(back, ["3 results", "5 results"], "{}", stdout);
+    assert!(stdout.contains("error: There is no filter to undo"), "{}", stdout);
+    assert!(stdout.contains("error: The results have changed since the last filter"), "{}", stdout);
+}
+
+#[test]
+fn bad_filters_are_refused() {
+    let stdout = offline("filter align 0\nfilter module nothing.so\nfilter range 0x2000..0x1000\nfilter region nowhere\nfilter");
+    for error in ["alignment of at least 1", "No module named 'nothing.so'", "0x2000..0x1000 is empty", "Unknown region filter 'nowhere'", "Usage: filter align"] {
+        assert!(stdout.contains(error), "{} in {}", error, stdout);
+    }
+}
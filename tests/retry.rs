@@ -0,0 +1,114 @@
+use std::{sync::{Mutex, atomic::{AtomicU32, Ordering}}, time::Duration};
+use memory::{MemoryRegion, ProcessMemory, Retry, ScanOptions, ScanStats, is_transient, read_from_process, read_from_process_with_retry, reduce_found_values};
+use nix::{errno::Errno, unistd::Pid};
+
+const BASE: usize = 0x10000;
+
+// A buffer whose next few reads fail with the given error, like a target in the middle of moving
+// its memory around
+struct Flaky {
+    bytes: Mutex<Vec<u8>>,
+    failures: AtomicU32,
+    errno: Errno,
+}
+
+impl Flaky {
+    fn new(bytes: &[u8], failures: u32, errno: Errno) -> Flaky {
+        Flaky { bytes: Mutex::new(bytes.to_vec()), failures: AtomicU32::new(failures), errno }
+    }
+}
+
+impl ProcessMemory for Flaky {
+    fn pid(&self) -> Pid {
+        Pid::from_raw(0)
+    }
+
+    fn read_at(&self, address: usize, buffer: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        if self.failures.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |x| x.checked_sub(1)).is_ok() {
+            return Err(Box::new(self.errno));
+        }
+        let bytes = self.bytes.lock().unwrap();
+        let offset = address.checked_sub(BASE).filter(|x| *x < bytes.len()).ok_or("unmapped")?;
+        let len = buffer.len().min(bytes.len() - offset);
+        buffer[..len].copy_from_slice(&bytes[offset..offset + len]);
+        Ok(len)
+    }
+
+    fn write_at(&self, _address: usize, _data: &[u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        Err("read-only".into())
+    }
+
+    fn memory_regions(&self) -> Result<Vec<MemoryRegion>, Box<dyn std::error::Error>> {
+        let len = self.bytes.lock().unwrap().len();
+        Ok(vec![format!("{:x}-{:x} rw-p 00000000 00:00 0", BASE, BASE + len).parse()?])
+    }
+
+    fn executable_path(&self) -> Option<String> {
+        None
+    }
+
+    fn thread_stacks(&self) -> Vec<(Pid, usize)> {
+        Vec::new()
+    }
+}
+
+#[test]
+fn defaults_to_a_single_attempt() {
+    assert_eq!(Retry::default().attempts, 1);
+    assert_eq!(ScanOptions::default().retry, Retry::default());
+    let flaky = Flaky::new(&7u32.to_ne_bytes(), 1, Errno::EFAULT);
+    let (read, retries) = read_from_process_with_retry::<u32>(&flaky, BASE, &Retry::default());
+    assert!(read.is_err());
+    assert_eq!(retries, 0);
+    assert_eq!(read_from_process::<u32>(&flaky, BASE).unwrap(), 7);
+}
+
+#[test]
+fn retries_transient_failures() {
+    let retry = Retry::new(3, Duration::from_millis(1));
+    for errno in [Errno::EFAULT, Errno::EIO] {
+        let flaky = Flaky::new(&7u32.to_ne_bytes(), 2, errno);
+        let (read, retries) = read_from_process_with_retry::<u32>(&flaky, BASE, &retry);
+        assert_eq!(read.unwrap(), 7);
+        assert_eq!(retries, 2);
+    }
+    let flaky = Flaky::new(&7u32.to_ne_bytes(), 3, Errno::EFAULT);
+    let (read, retries) = read_from_process_with_retry::<u32>(&flaky, BASE, &retry);
+    assert!(read.is_err());
+    assert_eq!(retries, 2);
+}
+
+#[test]
+fn never_retries_a_missing_process_or_denied_access() {
+    let retry = Retry::new(5, Duration::ZERO);
+    for errno in [Errno::ESRCH, Errno::EPERM] {
+        let flaky = Flaky::new(&7u32.to_ne_bytes(), 1, errno);
+        let (read, retries) = read_from_process_with_retry::<u32>(&flaky, BASE, &retry);
+        assert_eq!(read.unwrap_err().downcast_ref::<Errno>(), Some(&errno));
+        assert_eq!(retries, 0);
+    }
+}
+
+#[test]
+fn recognises_io_errors_as_well_as_errnos() {
+    assert!(is_transient(&std::io::Error::from_raw_os_error(libc::EIO)));
+    assert!(is_transient(&Errno::EFAULT));
+    assert!(!is_transient(&std::io::Error::from_raw_os_error(libc::ESRCH)));
+    let short: Box<dyn std::error::Error> = "Short read".into();
+    assert!(!is_transient(short.as_ref()));
+}
+
+#[test]
+fn reduces_report_results_that_needed_retries() {
+    let mut bytes = vec![0u8; 16];
+    bytes[..4].copy_from_slice(&5u32.to_ne_bytes());
+    bytes[8..12].copy_from_slice(&6u32.to_ne_bytes());
+    let options = ScanOptions { retry: Retry::new(2, Duration::ZERO), ..ScanOptions::default() };
+    // Only the first result's read fails, and its retry succeeds
+    let flaky = Flaky::new(&bytes, 1, Errno::EFAULT);
+    let mut found = vec![BASE, BASE + 8];
+    let mut stats = ScanStats::default();
+    reduce_found_values(&flaky, &mut found, 5u32, &options, &mut stats).unwrap();
+    assert_eq!(found, [BASE]);
+    assert_eq!(stats.retried, 1);
+}
@@ -0,0 +1,88 @@
+use std::{path::PathBuf, process::{Command, Stdio}, time::{Duration, UNIX_EPOCH}};
+use memory::{Arch, Capture, CaptureHeader, Compression, Endianness, InitialValues, MemoryRegion, MockProcess, ScanOptions, find_rounded, reduce_found_rounded, reduce_rounded_change, rounds_to};
+
+const HEAP: usize = 0x100000;
+
+// A page of heap holding each value at its own 8 bytes
+fn mock<T: memory::Scalar>(values: &[T]) -> MockProcess {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", HEAP, HEAP + 0x1000)).unwrap();
+    for (i, value) in values.iter().enumerate() {
+        mock.plant_value(HEAP + 8 * i, *value).unwrap();
+    }
+    mock
+}
+
+fn aligned() -> ScanOptions {
+    ScanOptions { alignment: Some(8), ..ScanOptions::default() }
+}
+
+// The window is anchored to the integer, not a tolerance either side of it
+#[test]
+fn floats_round_to_the_integer_shown() {
+    assert!(rounds_to(99.5f32, 100) && rounds_to(100.49f32, 100) && rounds_to(100.0f64, 100));
+    assert!(!rounds_to(100.5f32, 100) && !rounds_to(99.49f64, 100));
+    assert!(rounds_to(-0.5f64, -1) && rounds_to(-0.49f32, 0) && !rounds_to(f32::NAN, 0));
+}
+
+#[test]
+fn scans_find_both_widths() {
+    let mock = mock(&[99.5f32, 100.4, 100.5, 99.4, 100.0]);
+    assert_eq!(find_rounded::<f32>(&mock, 100, &aligned()).unwrap().0, [HEAP, HEAP + 8, HEAP + 32]);
+    let mock = self::mock(&[99.5f64, 100.4, 100.5, -3.2]);
+    let (found, stats) = find_rounded::<f64>(&mock, -3, &aligned()).unwrap();
+    assert_eq!(found, [HEAP + 24]);
+    assert!(stats.rounded);
+}
+
+#[test]
+fn integers_are_refused() {
+    let error = find_rounded::<i32>(&mock(&[100i32]), 100, &aligned()).unwrap_err();
+    assert_eq!(error.to_string(), "i32 values are integers already; rounded scans are for the floats a UI shows rounded, so scan f32 or f64");
+}
+
+// 99.7 to 100.2 shows 100 both times, so is unchanged however the float moved
+#[test]
+fn changes_are_judged_on_the_integer_shown() {
+    let mock = mock(&[99.7f32, 100.4, 100.0]);
+    let (mut found, mut stats) = find_rounded::<f32>(&mock, 100, &aligned()).unwrap();
+    let last = InitialValues::record::<f32>(&mock, &found, Endianness::Native);
+    for (i, value) in [100.2f32, 100.6, 99.0].into_iter().enumerate() {
+        mock.plant_value(HEAP + 8 * i, value).unwrap();
+    }
+    let mut increased = found.clone();
+    reduce_rounded_change::<f32>(&mock, &mut increased, &last, |old, new| new > old, &aligned(), &mut stats).unwrap();
+    assert_eq!(increased, [HEAP + 8]);
+    let mut unchanged = found.clone();
+    reduce_rounded_change::<f32>(&mock, &mut unchanged, &last, |old, new| new == old, &aligned(), &mut stats).unwrap();
+    assert_eq!(unchanged, [HEAP]);
+    reduce_found_rounded::<f32>(&mock, &mut found, 99, &aligned(), &mut stats).unwrap();
+    assert_eq!(found, [HEAP + 16]);
+}
+
+// A capture with a page of heap holding 99.7, 100.2 and 101.0 as f32s
+fn capture() -> PathBuf {
+    let path = std::env::temp_dir().join(format!("rmh-rounded-{}.cap", std::process::id()));
+    let region = MemoryRegion { start: 0x10000, end: 0x11000, readable: true, writable: true, pathname: "[heap]".to_string(), device: "00:00".to_string(), ..MemoryRegion::default() };
+    let header = CaptureHeader { version: 1, pid: 4242, time: UNIX_EPOCH + Duration::from_secs(1_700_000_000), arch: Arch::X86_64, executable: None, stacks: Vec::new(), uncaptured: Vec::new() };
+    Capture::write(&path, &header, Compression::None, vec![region], |_, block| {
+        for (i, value) in [99.7f32, 100.2, 101.0].into_iter().enumerate() {
+            block[8 * i..8 * i + 4].copy_from_slice(&value.to_ne_bytes());
+        }
+        Ok(Vec::new())
+    }).unwrap();
+    path
+}
+
+#[test]
+fn the_cli_scans_and_rescans_rounded() {
+    let path = capture();
+    let session = Command::new(env!("CARGO_BIN_EXE_memory")).arg("--offline").arg(&path).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    std::io::Write::write_all(&mut session.stdin.as_ref().unwrap(), b"scan i32 ~100\nscan f32 ~1.5\nscan f32 ~100\nrescan unchanged\nrescan increased\n").unwrap();
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(stdout.contains("error: i32 values are integers already"), "{}", stdout);
+    assert!(stdout.contains("error: Expected the integer shown after ~, got '1.5'"), "{}", stdout);
+    assert_eq!(stdout.matches("2 matches").count(), 2, "{}", stdout);
+    assert!(stdout.contains("> 0 matches"), "{}", stdout);
+}
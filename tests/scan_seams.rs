@@ -0,0 +1,67 @@
+use memory::{MemoryRegion, MockProcess, Pid, ProcessMemory, SCAN_CHUNK_SIZE, ScanOptions, Snapshot, find_value};
+
+const HEAP: usize = 0x10_0000;
+const VALUE: i32 = 0x1234_5678;
+
+// The mock, but with its maps also listing a range that overlaps the heap, as /proc sometimes does
+// while a region is being split or merged
+struct Overlapping(MockProcess, (usize, usize));
+
+impl ProcessMemory for Overlapping {
+    fn pid(&self) -> Pid {
+        self.0.pid()
+    }
+
+    fn read_at(&self, address: usize, buffer: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        self.0.read_at(address, buffer)
+    }
+
+    fn write_at(&self, address: usize, data: &[u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        self.0.write_at(address, data)
+    }
+
+    fn memory_regions(&self) -> Result<Vec<MemoryRegion>, Box<dyn std::error::Error>> {
+        let mut regions = self.0.memory_regions()?;
+        regions.push(format!("{:x}-{:x} rw-p 00000000 00:00 0", self.1.0, self.1.1).parse()?);
+        regions.sort_by_key(|x| x.start);
+        Ok(regions)
+    }
+}
+
+// Right on the seam is the first byte of the second chunk, which only that chunk reports
+#[test]
+fn a_value_on_a_chunk_seam_is_found_once() {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", HEAP, HEAP + 2 * SCAN_CHUNK_SIZE)).unwrap();
+    mock.plant_value(HEAP + SCAN_CHUNK_SIZE, VALUE).unwrap();
+    mock.plant_value(HEAP + SCAN_CHUNK_SIZE - 4, VALUE).unwrap();
+    let (found, stats) = find_value(&mock, VALUE, &ScanOptions::default()).unwrap();
+    assert_eq!(found, [HEAP + SCAN_CHUNK_SIZE - 4, HEAP + SCAN_CHUNK_SIZE]);
+    assert_eq!(stats.matches, 2);
+}
+
+// Regions that touch are read apart, so one at the start of the second is only the second's
+#[test]
+fn a_value_on_a_region_boundary_is_found_once() {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", HEAP, HEAP + 0x1000)).unwrap();
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0", HEAP + 0x1000, HEAP + 0x2000)).unwrap();
+    mock.plant_value(HEAP + 0x1000, VALUE).unwrap();
+    let (found, _) = find_value(&mock, VALUE, &ScanOptions::default()).unwrap();
+    assert_eq!(found, [HEAP + 0x1000]);
+}
+
+#[test]
+fn ranges_listed_twice_give_each_address_once() {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", HEAP, HEAP + 0x2000)).unwrap();
+    mock.plant_value(HEAP + 0x1000, VALUE).unwrap();
+    mock.plant_value(HEAP + 0x1800, VALUE).unwrap();
+    let process = Overlapping(mock, (HEAP + 0x1000, HEAP + 0x2000));
+    let (found, stats) = find_value(&process, VALUE, &ScanOptions::default()).unwrap();
+    assert_eq!(found, [HEAP + 0x1000, HEAP + 0x1800]);
+    assert_eq!(stats.matches, 2);
+    let snapshot = Snapshot::capture(&process, &ScanOptions::default()).unwrap();
+    process.0.plant(HEAP + 0x1800, &[0]).unwrap();
+    assert_eq!(snapshot.compare::<u8>(&process, |old, new| old != new).unwrap(), [HEAP + 0x1800]);
+}
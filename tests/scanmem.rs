@@ -0,0 +1,124 @@
+use memory::{Endianness, Process, ScanOptions, ScanmemSession, read_scalar, write_scalar};
+use nix::unistd::Pid;
+
+// A forked copy of this process that only waits to be killed, so that nothing in it holds copies
+// of the values a transcript plants, the way this process's own stack and scan buffers would
+struct Target {
+    pid: Pid,
+    planted: usize,
+}
+
+impl Target {
+    fn fork() -> Target {
+        let planted = Box::into_raw(Box::new(0i32)) as usize;
+        let pid = unsafe { libc::fork() };
+        assert!(pid >= 0);
+        if pid == 0 {
+            loop {
+                unsafe { libc::pause() };
+            }
+        }
+        Target { pid: Pid::from_raw(pid), planted }
+    }
+}
+
+impl Drop for Target {
+    fn drop(&mut self) {
+        unsafe {
+            libc::kill(self.pid.as_raw(), libc::SIGKILL);
+            libc::waitpid(self.pid.as_raw(), std::ptr::null_mut(), 0);
+        }
+    }
+}
+
+// Replays a recorded session against a forked target. Each line of a transcript is one of
+//     > command        sent to the session
+//     < line           expected on the output
+//     ! line           expected on the error stream
+//     % plant <value>  writes value to the planted i32
+//     % expect <value> checks the planted i32 holds value
+// with {planted} standing for the planted address as list prints it, {pid} for the target and
+// {..} for anything. Every command's output has to match the lines after it exactly
+fn replay(transcript: &str) {
+    let target = Target::fork();
+    let process = Process::attach(target.pid).unwrap();
+    let mut session = ScanmemSession::new(Some(process.clone()), ScanOptions::default());
+    let (mut output, mut errors) = (Vec::new(), Vec::new());
+    let mut expected: (Vec<String>, Vec<String>) = (Vec::new(), Vec::new());
+    let substitute = |x: &str| x.replace("{planted}", &format!("{:12x}", target.planted)).replace("{pid}", &target.pid.to_string());
+    let check = |output: &mut Vec<u8>, errors: &mut Vec<u8>, expected: &mut (Vec<String>, Vec<String>), command: &str| {
+        for (got, want, stream) in [(output, &mut expected.0, "output"), (errors, &mut expected.1, "error stream")] {
+            let got_text = String::from_utf8(std::mem::take(got)).unwrap();
+            let lines = got_text.lines().collect::<Vec<&str>>();
+            assert_eq!(lines.len(), want.len(), "{} of '{}' was {:?}, expected {:?}", stream, command, lines, want);
+            for (line, pattern) in lines.iter().zip(want.iter()) {
+                assert!(matches(line, pattern), "{} of '{}' was '{}', expected '{}'", stream, command, line, pattern);
+            }
+            want.clear();
+        }
+    };
+    let mut last = String::new();
+    for line in transcript.lines().filter(|x| !x.is_empty() && !x.starts_with('#')) {
+        let (kind, rest) = line.split_at(2);
+        match kind {
+            "< " => expected.0.push(substitute(rest)),
+            "! " => expected.1.push(substitute(rest)),
+            _ => {
+                check(&mut output, &mut errors, &mut expected, &last);
+                if kind == "> " {
+                    // Errors come out on the error stream the way ScanmemSession::run prints them
+                    match session.command(rest, &mut output, &mut errors) {
+                        Ok(more) => assert!(more || line == transcript.lines().last().unwrap(), "'{}' ended the session early", rest),
+                        Err(e) => errors.extend(format!("error: {}\n", e).bytes()),
+                    }
+                    last = rest.to_string();
+                }
+                else if let Some(value) = rest.strip_prefix("plant ") {
+                    write_scalar(&process, target.planted, value.parse::<i32>().unwrap(), Endianness::Native).unwrap();
+                }
+                else if let Some(value) = rest.strip_prefix("expect ") {
+                    assert_eq!(read_scalar::<i32>(&process, target.planted, Endianness::Native).unwrap(), value.parse::<i32>().unwrap(), "after '{}'", last);
+                }
+                else {
+                    panic!("unknown transcript line '{}'", line);
+                }
+            }
+        }
+    }
+    check(&mut output, &mut errors, &mut expected, &last);
+}
+
+fn matches(line: &str, pattern: &str) -> bool {
+    let mut parts = pattern.split("{..}");
+    let first = parts.next().unwrap();
+    let Some(mut rest) = line.strip_prefix(first) else {
+        return false;
+    };
+    let parts = parts.collect::<Vec<&str>>();
+    for (i, part) in parts.iter().enumerate() {
+        let found = match i + 1 == parts.len() {
+            true => rest.len().checked_sub(part.len()).filter(|x| rest[*x..] == **part),
+            false => rest.find(part),
+        };
+        let Some(at) = found else {
+            return false;
+        };
+        rest = &rest[at + part.len()..];
+    }
+    rest.is_empty()
+}
+
+#[test]
+fn replays_an_exact_scan_list_and_set() {
+    replay(include_str!("transcripts/scanmem_exact.txt"));
+}
+
+#[test]
+fn replays_comparisons_with_the_last_scan() {
+    replay(include_str!("transcripts/scanmem_compare.txt"));
+}
+
+#[test]
+fn replays_options_and_errors() {
+    replay(include_str!("transcripts/scanmem_options.txt"));
+}
@@ -0,0 +1,86 @@
+#![cfg(feature = "scripting")]
+use std::{cell::RefCell, rc::Rc, time::{Duration, Instant}};
+use memory::{Endianness, Process, script::{ScriptError, ScriptHost, ScriptRequest, run_script}};
+use nix::unistd::Pid;
+
+// Answers like a session with the given results, keeping every command it was sent and failing
+// the ones that start with "fail"
+fn host(results: Vec<usize>, commands: Rc<RefCell<Vec<String>>>) -> ScriptHost {
+    ScriptHost {
+        send: Box::new(move |request| {
+            match request {
+                ScriptRequest::Command(line, reply) => {
+                    let _ = reply.send(if line.starts_with("fail") { Err("no such command".to_string()) } else { Ok(()) });
+                    commands.borrow_mut().push(line);
+                }
+                ScriptRequest::Results(reply) => {
+                    let _ = reply.send(results.clone());
+                }
+            }
+            true
+        }),
+        stopped: Box::new(|| false),
+        log: Box::new(|_| {}),
+    }
+}
+
+fn run(source: &str, results: Vec<usize>) -> (Result<(), ScriptError>, Vec<String>) {
+    let commands = Rc::new(RefCell::new(Vec::new()));
+    let process = Process::attach(Pid::this()).unwrap();
+    let result = run_script(source, process, Endianness::Native, host(results, commands.clone()));
+    (result, commands.take())
+}
+
+#[test]
+fn reads_the_process_directly() {
+    let planted = Box::into_raw(Box::new([0x1234_5678u32, 0xffff_fffe]));
+    let source = format!(r#"
+        let at = results()[0];
+        if proc.read_u32(at) != 0x12345678 {{ throw "read_u32"; }}
+        if proc.read_i32(at + 4) != -2 {{ throw "read_i32"; }}
+        if proc.read_bytes(at, 2) != blob(1, 0x78) + blob(1, 0x56) {{ throw "read_bytes"; }}
+        if proc.resolve("0x{:x}") != at {{ throw "resolve"; }}
+    "#, planted as usize);
+    let (result, commands) = run(&source, vec![planted as usize]);
+    assert_eq!(result, Ok(()));
+    assert!(commands.is_empty());
+}
+
+#[test]
+fn everything_else_goes_through_the_session() {
+    let source = r#"
+        if scan("i32", 100) != 3 { throw "scan"; }
+        rescan("dec");
+        proc.write_i16(0x1000, -5);
+        proc.write_f32(0x2000, 1.5);
+        proc.write_bytes(0x3000, blob(2, 0x90));
+        lock(0x4000, "i32", 999);
+        unlock(0x4000);
+        command("freeze all");
+    "#;
+    let (result, commands) = run(source, vec![1, 2, 3]);
+    assert_eq!(result, Ok(()));
+    assert_eq!(commands, ["scan i32 100", "rescan dec", "write 0x1000 i16:-5", "write 0x2000 f32:1.5", "poke 0x3000 9090", "lock 0x4000 i32 999", "unlock 0x4000", "freeze all"]);
+}
+
+#[test]
+fn failures_end_the_script() {
+    let (result, commands) = run("command(\"fail now\"); command(\"never sent\");", Vec::new());
+    assert!(matches!(result, Err(ScriptError::Failed(ref e)) if e.contains("fail now: no such command")), "{:?}", result);
+    assert_eq!(commands, ["fail now"]);
+    assert!(matches!(run("let x = ;", Vec::new()).0, Err(ScriptError::Failed(ref e)) if e.contains("Syntax error")));
+    assert!(matches!(run("proc.read_u8(-1)", Vec::new()).0, Err(ScriptError::Failed(ref e)) if e.contains("is not an address")));
+}
+
+// Both a busy loop and a long sleep end soon after the host asks
+#[test]
+fn stops_when_asked() {
+    for source in ["loop { }", "sleep(60000);"] {
+        let start = Instant::now();
+        let process = Process::attach(Pid::this()).unwrap();
+        let mut host = host(Vec::new(), Rc::default());
+        host.stopped = Box::new(move || start.elapsed() > Duration::from_millis(100));
+        assert_eq!(run_script(source, process, Endianness::Native, host), Err(ScriptError::Stopped));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
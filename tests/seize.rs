@@ -0,0 +1,84 @@
+use std::{process::{Child, Command, Stdio}, time::Duration};
+use memory::{MemBackend, Process, ProcessMemory, PtraceSession, read_bytes_from_process};
+use nix::unistd::Pid;
+
+struct Target(Child);
+
+impl Target {
+    fn spawn() -> Target {
+        Target(Command::new("sleep").arg("30").spawn().unwrap())
+    }
+
+    fn pid(&self) -> Pid {
+        Pid::from_raw(self.0.id() as i32)
+    }
+
+    // The named line of /proc/<pid>/status, e.g. "S (sleeping)" for State
+    fn status(&self, name: &str) -> String {
+        let status = std::fs::read_to_string(format!("/proc/{}/status", self.pid())).unwrap();
+        status.lines().find_map(|x| x.strip_prefix(name)?.strip_prefix(':')).unwrap().trim().to_string()
+    }
+}
+
+impl Drop for Target {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+#[test]
+fn reads_as_the_tracer_and_detaches_when_dropped() {
+    let target = Target::spawn();
+    let process = Process::seize(target.pid()).unwrap();
+    assert!(process.is_seized());
+    assert_eq!(process.backend(), MemBackend::ProcMem);
+    // The tracer is the thread that seized, so only its process can be checked
+    let tracer = target.status("TracerPid");
+    assert_eq!(std::fs::read_to_string(format!("/proc/{}/status", tracer)).unwrap().lines().find_map(|x| x.strip_prefix("Tgid:")).unwrap().trim(), std::process::id().to_string());
+    let first = process.memory_ranges().unwrap()[0];
+    let expected = read_bytes_from_process(Process::with_backend(target.pid(), MemBackend::ProcessVmReadv).unwrap(), 64, first.0).unwrap();
+    assert_eq!(read_bytes_from_process(&process, 64, first.0).unwrap(), expected);
+
+    let clone = process.clone();
+    drop(process);
+    assert_ne!(target.status("TracerPid"), "0");
+    drop(clone);
+    assert_eq!(target.status("TracerPid"), "0");
+    // Straight after the detach it may still be running rather than back asleep, but not stopped
+    let state = target.status("State");
+    assert!(!state.starts_with(['t', 'T']), "{}", state);
+}
+
+#[test]
+fn keeps_the_target_running_through_signals() {
+    let target = Target::spawn();
+    let _session = PtraceSession::seize(target.pid()).unwrap();
+    // SIGCONT is ignored by sleep, but a tracee stops to have it delivered all the same
+    assert_eq!(unsafe { libc::kill(target.pid().as_raw(), libc::SIGCONT) }, 0);
+    std::thread::sleep(Duration::from_millis(100));
+    let state = target.status("State");
+    assert!(!state.starts_with(['t', 'T']), "{}", state);
+}
+
+// Another process's session, since one in this process would be shared
+#[test]
+fn names_the_tracer_already_attached() {
+    let target = Target::spawn();
+    let mut other = Command::new(env!("CARGO_BIN_EXE_memory")).args([&target.pid().to_string(), "--seize"]).stdin(Stdio::piped()).stdout(Stdio::null()).spawn().unwrap();
+    while target.status("TracerPid") == "0" {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    let error = PtraceSession::seize(target.pid()).unwrap_err().to_string();
+    assert!(error.contains("already being traced by") && error.contains("(memory)"), "{}", error);
+    drop(other.stdin.take());
+    other.wait().unwrap();
+}
+
+#[test]
+fn plain_attach_does_not_trace() {
+    let target = Target::spawn();
+    let process = Process::attach(target.pid()).unwrap();
+    assert!(!process.is_seized());
+    assert_eq!(target.status("TracerPid"), "0");
+}
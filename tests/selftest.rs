@@ -0,0 +1,60 @@
+use std::{process::Command, sync::atomic::{AtomicU64, Ordering}, time::{Duration, Instant}};
+use memory::{Endianness, LockManager, Process, ProcessMemory, ScanOptions, find_value, read_scalar, reduce_found_values, write_to_process};
+
+// These run on the test process itself, so they pass wherever attaching to another process is
+// refused
+fn planted(value: u64) -> (Box<AtomicU64>, usize) {
+    let planted = Box::new(AtomicU64::new(value));
+    let address = planted.as_ptr() as usize;
+    (planted, address)
+}
+
+#[test]
+fn the_current_process_is_this_one() {
+    let process = Process::current().unwrap();
+    assert_eq!(process.pid().as_raw() as u32, std::process::id());
+    let regions = process.memory_regions().unwrap();
+    let (_planted, address) = planted(1);
+    assert!(regions.iter().any(|x| x.contains(address) && x.readable && x.writable));
+}
+
+#[test]
+fn finds_and_narrows_a_value_of_its_own() {
+    let process = Process::current().unwrap();
+    let (planted, address) = planted(0x5e1f_7e57_0000_0001);
+    let (mut found, mut stats) = find_value(&process, 0x5e1f_7e57_0000_0001u64, &ScanOptions::default()).unwrap();
+    assert!(found.contains(&address));
+    planted.store(0x5e1f_7e57_0000_0002, Ordering::SeqCst);
+    reduce_found_values(&process, &mut found, 0x5e1f_7e57_0000_0002u64, &ScanOptions::default(), &mut stats).unwrap();
+    assert!(found.contains(&address));
+    assert_eq!(read_scalar::<u64>(&process, address, Endianness::Native).unwrap(), 0x5e1f_7e57_0000_0002);
+}
+
+#[test]
+fn locks_a_value_it_keeps_writing_over() {
+    let process = Process::current().unwrap();
+    let (planted, address) = planted(10);
+    write_to_process(&process, address, &20u64).unwrap();
+    assert_eq!(planted.load(Ordering::SeqCst), 20);
+    let mut locks = LockManager::new(process);
+    locks.lock_value(30u64, address, Duration::from_millis(1));
+    let started = Instant::now();
+    planted.store(40, Ordering::SeqCst);
+    while planted.load(Ordering::SeqCst) != 30 {
+        assert!(started.elapsed() < Duration::from_secs(10));
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert!(locks.unlock_value(address));
+}
+
+#[test]
+fn the_cli_self_test_passes() {
+    let output = Command::new(env!("CARGO_BIN_EXE_memory")).arg("--self-test").output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}{}", stdout, String::from_utf8_lossy(&output.stderr));
+    assert_eq!(stdout.lines().filter(|x| x.starts_with("pass  ")).count(), 5, "{}", stdout);
+    assert!(stdout.ends_with("self-test passed\n"), "{}", stdout);
+    let output = Command::new(env!("CARGO_BIN_EXE_memory")).args(["--self-test", "--seize"]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--self-test takes no other arguments"));
+}
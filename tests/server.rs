@@ -0,0 +1,137 @@
+use std::{io::{BufRead, BufReader, Write}, net::TcpStream, os::unix::{fs::PermissionsExt, net::UnixStream}};
+use memory::{Process, ScanOptions, ServeAddress, Server};
+use nix::unistd::Pid;
+use serde_json::{Value, json};
+
+// A connection to a server, reading its replies and notifications a line at a time
+struct Client {
+    reader: BufReader<Box<dyn std::io::Read>>,
+    writer: Box<dyn Write>,
+    next_id: u64,
+    notifications: Vec<Value>,
+}
+
+impl Client {
+    fn connect(address: &ServeAddress) -> Client {
+        let (reader, writer): (Box<dyn std::io::Read>, Box<dyn Write>) = match address {
+            ServeAddress::Tcp(address) => {
+                let stream = TcpStream::connect(address).unwrap();
+                (Box::new(stream.try_clone().unwrap()), Box::new(stream))
+            }
+            ServeAddress::Unix(path) => {
+                let stream = UnixStream::connect(path).unwrap();
+                (Box::new(stream.try_clone().unwrap()), Box::new(stream))
+            }
+        };
+        Client { reader: BufReader::new(reader), writer, next_id: 0, notifications: Vec::new() }
+    }
+
+    fn next(&mut self) -> Value {
+        let mut line = String::new();
+        self.reader.read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    // Sends a request and waits for its reply, keeping any notifications that arrive meanwhile
+    fn call(&mut self, method: &str, params: Value) -> Result<Value, String> {
+        self.next_id += 1;
+        writeln!(self.writer, "{}", json!({ "id": self.next_id, "method": method, "params": params })).unwrap();
+        loop {
+            let message = self.next();
+            match message.get("id") {
+                Some(id) if *id == json!(self.next_id) => return match message.get("error") {
+                    Some(error) => Err(error.as_str().unwrap().to_string()),
+                    None => Ok(message["result"].clone()),
+                },
+                _ => self.notifications.push(message),
+            }
+        }
+    }
+}
+
+// Serves this test's own process on a free loopback port
+fn serve_self() -> ServeAddress {
+    let server = Server::bind(&"127.0.0.1:0".parse().unwrap(), false, ScanOptions::default()).unwrap();
+    server.attach(Process::attach(Pid::this()).unwrap()).unwrap();
+    let address = server.local_address().unwrap();
+    std::thread::spawn(move || server.run().is_ok());
+    address
+}
+
+// Written behind the compiler's back by the server, so only touched through volatile reads and writes
+fn planted(value: i32) -> *mut i32 {
+    Box::into_raw(Box::new(value))
+}
+
+fn address_of(value: *mut i32) -> String {
+    format!("0x{:x}", value as usize)
+}
+
+#[test]
+fn refuses_to_serve_beyond_loopback_unless_allowed() {
+    let address = "0.0.0.0:0".parse().unwrap();
+    let error = Server::bind(&address, false, ScanOptions::default()).err().unwrap();
+    assert!(error.to_string().contains("not loopback"));
+    assert!(Server::bind(&address, true, ScanOptions::default()).is_ok());
+    assert!("nonsense".parse::<ServeAddress>().is_err());
+}
+
+#[test]
+fn scans_lists_reads_and_writes() {
+    let planted = planted(0x5e4f_1a2b);
+    let mut client = Client::connect(&serve_self());
+    client.call("scan", json!({ "type": "i32", "value": 0x5e4f_1a2b })).unwrap();
+    unsafe { std::ptr::write_volatile(planted, 0x5e4f_1a2c) };
+    let matches = client.call("rescan", json!({ "value": "1582242348" })).unwrap()["matches"].as_u64().unwrap();
+    assert!(matches >= 1);
+
+    let page = client.call("list_results", json!({ "limit": 10000 })).unwrap();
+    assert_eq!(page["total"], json!(matches));
+    assert!(page["results"].as_array().unwrap().iter().any(|x| x["address"] == json!(address_of(planted)) && x["value"] == json!("1582242348")));
+
+    client.call("write", json!({ "address": address_of(planted), "type": "i32", "value": "-7" })).unwrap();
+    assert_eq!(unsafe { std::ptr::read_volatile(planted) }, -7);
+    let read = client.call("read", json!({ "address": address_of(planted), "type": "u8" })).unwrap();
+    assert_eq!(read["value"], json!("249"));
+    assert!(client.call("read", json!({ "address": 16, "type": "i32" })).is_err());
+}
+
+#[test]
+fn reports_scan_progress_to_subscribers() {
+    let mut client = Client::connect(&serve_self());
+    client.call("subscribe", json!({ "topics": ["progress"] })).unwrap();
+    assert!(client.call("subscribe", json!({ "topics": ["nothing"] })).is_err());
+    let done = client.call("scan", json!({ "type": "u64", "value": "12345678987654321" })).unwrap();
+    assert!(client.notifications.iter().any(|x| x["method"] == json!("progress") && x["params"]["scan_done"] == done));
+    assert!(client.call("rescan", json!({ "value": "not a number" })).is_err());
+}
+
+#[test]
+fn notifies_watchers_of_changes() {
+    let watched = planted(10);
+    let mut client = Client::connect(&serve_self());
+    client.call("subscribe", json!({ "topics": ["watch"] })).unwrap();
+    let id = client.call("watch", json!({ "address": address_of(watched), "type": "i32" })).unwrap()["watch"].clone();
+    assert_eq!(client.next()["params"]["value"], json!("10"));
+    unsafe { std::ptr::write_volatile(watched, 11) };
+    let change = client.next();
+    assert_eq!((&change["params"]["watch"], &change["params"]["value"]), (&id, &json!("11")));
+    assert_eq!(client.call("unwatch", json!({ "watch": id })).unwrap()["removed"], json!(true));
+}
+
+#[test]
+fn serves_on_a_unix_socket_only_its_owner_can_use() {
+    let path = std::env::temp_dir().join(format!("server-{}.sock", std::process::id()));
+    let address = ServeAddress::Unix(path.clone());
+    let server = Server::bind(&address, false, ScanOptions::default()).unwrap();
+    assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    std::thread::spawn(move || server.run().is_ok());
+    assert!(Server::bind(&address, false, ScanOptions::default()).is_err());
+
+    let mut client = Client::connect(&address);
+    assert!(client.call("read", json!({ "address": 16, "type": "i32" })).unwrap_err().contains("attach"));
+    assert!(client.call("frobnicate", json!({})).unwrap_err().contains("Unknown method"));
+    client.call("attach", json!({ "pid": Pid::this().as_raw() })).unwrap();
+    assert!(client.call("maps", json!({})).unwrap().as_array().unwrap().len() > 1);
+    std::fs::remove_file(&path).unwrap();
+}
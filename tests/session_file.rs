@@ -0,0 +1,72 @@
+use std::{path::PathBuf, time::{Duration, UNIX_EPOCH}};
+use memory::{Endianness, SESSION_VERSION, SavedAddress, SavedLock, SavedMark, SavedPatch, SavedResult, SavedWrite, SessionFile, ValueType};
+
+fn temp_path(name: &str) -> String {
+    let path: PathBuf = std::env::temp_dir().join(format!("rmh-session-{}-{}", std::process::id(), name));
+    path.to_string_lossy().into_owned()
+}
+
+fn game(offset: usize) -> SavedAddress {
+    SavedAddress::Module { name: "libgame.so".to_string(), offset }
+}
+
+fn session() -> SessionFile {
+    SessionFile {
+        scan_type: Some(ValueType::F32),
+        endianness: Some(Endianness::Big),
+        results: vec![SavedResult { address: game(0x2a10), value: vec![0x42, 0xc8, 0, 0] }, SavedResult { address: SavedAddress::Absolute(0x7f00_1000), value: Vec::new() }],
+        locks: vec![SavedLock { address: game(0x2a10), value_type: ValueType::F32, value_bytes: 100f32.to_ne_bytes().to_vec(), action: "hold 100 0.5".to_string(), interval: Duration::from_millis(50), enabled: true, chain: None }],
+        patches: vec![SavedPatch { address: game(0x1000), original: vec![0xff, 0x08], patched: vec![0x90, 0x90] }],
+        writes: vec![SavedWrite { address: SavedAddress::Absolute(0x5000), old: vec![1], new: vec![2], time: UNIX_EPOCH + Duration::from_nanos(1_700_000_000_123_456_789), kind: "poke".to_string() }],
+        bindings: [("F7".to_string(), "lock #0 100".to_string())].into_iter().collect(),
+        marks: vec![SavedMark { address: game(0x2a10), tag: Some("maybe hp".to_string()), starred: true }, SavedMark { address: SavedAddress::Absolute(0x7f00_1000), tag: None, starred: true }],
+    }
+}
+
+#[test]
+fn round_trips_everything() {
+    let path = temp_path("round-trip");
+    session().save(&path).unwrap();
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert!(text.contains(&format!("\"version\": {}", SESSION_VERSION)));
+    assert!(text.contains("\"address\": \"libgame.so+0x2a10\""));
+    assert!(text.contains("\"value\": \"42c80000\""));
+    assert!(text.contains("\"tag\": \"maybe hp\""));
+    assert_eq!(SessionFile::load(&path).unwrap(), session());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn refuses_files_from_newer_versions() {
+    let path = temp_path("newer");
+    std::fs::write(&path, format!("{{\"version\": {}, \"results\": []}}", SESSION_VERSION + 1)).unwrap();
+    let error = SessionFile::load(&path).unwrap_err().to_string();
+    assert!(error.contains("saved by a newer version of memory"), "{}", error);
+    std::fs::write(&path, "{\"results\": []}").unwrap();
+    assert!(SessionFile::load(&path).unwrap_err().to_string().ends_with("is not a session file"));
+    std::fs::remove_file(path).unwrap();
+}
+
+// Fields added by later minor changes are ignored and missing ones left empty
+#[test]
+fn tolerates_unknown_and_missing_fields() {
+    let path = temp_path("fields");
+    std::fs::write(&path, "{\"version\": 2, \"scan_type\": \"utf16z\", \"labels\": {\"hp\": \"libgame.so+0x10\"}}").unwrap();
+    let loaded = SessionFile::load(&path).unwrap();
+    assert_eq!(loaded.scan_type, Some("utf16z".parse().unwrap()));
+    assert!(loaded.results.is_empty() && loaded.locks.is_empty() && loaded.bindings.is_empty());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn still_reads_tab_separated_sessions() {
+    let path = temp_path("records");
+    let contents = "memory-session 1\nscan_type\ti32\nlock\tlibgame.so+0x2a10\ti32\t64000000\t100000000\tenabled\tset\nwrite\t0x5000\t01\t02\t1700000000123456789\tpoke\n";
+    std::fs::write(&path, contents).unwrap();
+    let loaded = SessionFile::load(&path).unwrap();
+    assert_eq!(loaded.scan_type, Some(ValueType::I32));
+    assert_eq!(loaded.locks[0].address, game(0x2a10));
+    assert_eq!(loaded.locks[0].interval, Duration::from_millis(100));
+    assert_eq!(loaded.writes, session().writes);
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,48 @@
+mod common;
+// A SIGTERM ends the session as quit would, putting back what it patched, and leaves the session
+// to be loaded on the next start
+#[cfg(target_os = "linux")]
+#[test]
+fn sigterm_cleans_up_and_saves_the_session() {
+    use std::{io::{BufRead, BufReader, Read, Write}, process::{Command, Stdio}};
+    let path = common::victim_path();
+    let state = std::env::temp_dir().join(format!("memory-shutdown-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&state);
+    let mut victim = Command::new(path).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut lines = BufReader::new(victim.stdout.take().unwrap()).lines();
+    let pid = lines.next().unwrap().unwrap().strip_prefix("pid ").unwrap().to_string();
+    let player = lines.next().unwrap().unwrap();
+    let player = usize::from_str_radix(player.strip_prefix("player 0x").unwrap().split_once(' ').unwrap().0, 16).unwrap();
+    let session = || Command::new(env!("CARGO_BIN_EXE_memory")).arg(&pid).env("XDG_STATE_HOME", &state).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut first = session();
+    let mut stdin = first.stdin.take().unwrap();
+    writeln!(stdin, "scan i64 1000\nnop 0x{:x} 4\nlock 0x{:x} set 7\nlocks", player, player + 8).unwrap();
+    // Signalled only once the lock is listed as having written, so there is one to save
+    let mut output = BufReader::new(first.stdout.take().unwrap());
+    let mut stdout = String::new();
+    while !stdout.contains("i64 = 7 every") {
+        assert!(output.read_line(&mut stdout).unwrap() > 0, "{}", stdout);
+    }
+    unsafe {
+        libc::kill(first.id() as libc::pid_t, libc::SIGTERM);
+    }
+    output.read_to_string(&mut stdout).unwrap();
+    let status = first.wait().unwrap();
+    drop(stdin);
+    assert_eq!(status.code(), Some(128 + libc::SIGTERM), "{}", stdout);
+    assert!(stdout.contains("SIGTERM: saving the session and cleaning up as for quit"), "{}", stdout);
+    assert!(stdout.contains("restored 4 bytes at"), "{}", stdout);
+    let saved = state.join("memory/recovered-session.json");
+    assert!(stdout.contains(&format!("1 locks, 1 patches and 1 journaled writes to {}", saved.display())), "{}", stdout);
+    let mut second = session();
+    writeln!(second.stdin.take().unwrap(), "guess 0x{:x}\nsession load --recover\nlist\nsession load --recover", player).unwrap();
+    let stdout = String::from_utf8(second.wait_with_output().unwrap().stdout).unwrap();
+    let _ = victim.kill();
+    let _ = victim.wait();
+    let _ = std::fs::remove_dir_all(&state);
+    assert!(stdout.contains(&format!("recovered session from {}; `session load --recover` to restore", saved.display())), "{}", stdout);
+    // hp is 100 again, and gold is among the results once more
+    assert!(stdout.contains(&format!("0x{:x} (heap+", player)) && stdout.contains("): 64 00 00 00"), "{}", stdout);
+    assert!(stdout.contains(&format!("0x{:x} (heap+", player + 8)), "{}", stdout);
+    assert!(stdout.contains("error: No session was saved by a SIGTERM or SIGHUP to recover") && !saved.exists(), "{}", stdout);
+}
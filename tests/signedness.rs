@@ -0,0 +1,51 @@
+use memory::{MockProcess, Scalar, ScanOptions, TypedValue, UnknownScan, find_value, parse_scalar, reduce_found_values};
+
+fn mock() -> MockProcess {
+    let mock = MockProcess::new(100);
+    mock.map("10000-11000 rw-p 00000000 00:00 0").unwrap();
+    mock
+}
+
+fn plant<T: Scalar>(mock: &MockProcess, address: usize, value: T) {
+    mock.plant(address, &value.to_bytes()).unwrap();
+}
+
+#[test]
+fn negative_values_are_refused_for_unsigned_types() {
+    let error = parse_scalar::<u32>("-1").unwrap_err().to_string();
+    assert_eq!(error, "u32 is unsigned, so it cannot be -1; use i32 for values that can go below 0");
+    assert!("u8:-3".parse::<TypedValue>().unwrap_err().to_string().contains("use i8 for values"));
+    assert_eq!(parse_scalar::<i16>("-5").unwrap(), -5);
+}
+
+#[test]
+fn values_out_of_range_say_what_the_range_is() {
+    assert_eq!(parse_scalar::<u8>("300").unwrap_err().to_string(), "300 does not fit in a u8, which holds 0 to 255");
+    assert_eq!(parse_scalar::<i8>("-129").unwrap_err().to_string(), "-129 does not fit in an i8, which holds -128 to 127");
+    assert!(parse_scalar::<f32>("lots").unwrap_err().to_string().starts_with("'lots' is not an f32"));
+}
+
+// Going from 3 to -1 is a decrease as i32, but as u32 it is 3 to 4294967295
+#[test]
+fn ordering_follows_the_signedness() {
+    let mock = mock();
+    plant(&mock, 0x10000, 3i32);
+    let mut signed = UnknownScan::start(&mock, 4, &ScanOptions::default()).unwrap();
+    let mut unsigned = signed.clone();
+    plant(&mock, 0x10000, -1i32);
+    signed.narrow::<i32>(&mock, |old, new| new < old).unwrap();
+    unsigned.narrow::<u32>(&mock, |old, new| new < old).unwrap();
+    assert_eq!((signed.addresses(), unsigned.addresses()), (vec![0x10000], vec![]));
+}
+
+// A signed value and the unsigned one with the same bits are the same byte pattern
+#[test]
+fn equality_is_the_same_bits_either_way() {
+    let mock = mock();
+    plant(&mock, 0x10010, -2i16);
+    let (found, _) = find_value(&mock, -2i16, &ScanOptions::default()).unwrap();
+    let (mut as_unsigned, mut stats) = find_value(&mock, 65534u16, &ScanOptions::default()).unwrap();
+    assert_eq!(found, as_unsigned);
+    reduce_found_values(&mock, &mut as_unsigned, 65534u16, &ScanOptions::default(), &mut stats).unwrap();
+    assert_eq!(as_unsigned, [0x10010]);
+}
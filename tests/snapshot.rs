@@ -0,0 +1,90 @@
+#![cfg(target_os = "linux")]
+mod common;
+use std::{io::Write, path::PathBuf, process::{Command, Stdio}, time::Duration};
+use memory::{Endianness, Process, ProcessMemory, RegionFilter, ScanOptions, Snapshot, get_memory_regions, is_stopped, read_scalar, snapshot_to_file};
+use common::Target;
+
+impl Target {
+    fn hp(&self) -> i32 {
+        read_scalar::<i32>(&Process::attach(self.pid()).unwrap(), self.player, Endianness::Native).unwrap()
+    }
+
+    // Runs the commands in a session on the target, returning what it printed
+    fn session(&self, commands: &str) -> String {
+        let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(self.pid().to_string()).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+        session.stdin.take().unwrap().write_all(commands.as_bytes()).unwrap();
+        let output = session.wait_with_output().unwrap();
+        format!("{}{}", String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap())
+    }
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("rmh-snapshot-{}-{}", std::process::id(), name))
+}
+
+#[test]
+fn offline_mode_reads_the_snapshot() {
+    let target = Target::spawn();
+    let path = temp_path("offline");
+    let written = snapshot_to_file(Process::attach(target.pid()).unwrap(), &path, &ScanOptions::default(), || false).unwrap();
+    let hp = read_scalar::<i32>(&Process::offline(&path).unwrap(), target.player, Endianness::Native).unwrap();
+    assert!(hp >= 100 && hp <= target.hp(), "{}", hp);
+    // Every mapping is there, those that could not be read without their contents
+    let offline = Process::offline(&path).unwrap().memory_regions().unwrap();
+    let live = get_memory_regions(target.pid()).unwrap();
+    assert_eq!(offline.iter().map(|x| (x.start, x.end)).collect::<Vec<(usize, usize)>>(), live.iter().map(|x| (x.start, x.end)).collect::<Vec<(usize, usize)>>());
+    assert_eq!(offline.iter().filter(|x| x.readable).count(), written.len());
+    assert!(written.iter().all(|x| x.compression == memory::Compression::Lz4));
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn compares_against_the_live_target() {
+    let target = Target::spawn();
+    let path = temp_path("compare");
+    let options = ScanOptions { filter: Some("heap".parse::<RegionFilter>().unwrap()), ..ScanOptions::default() };
+    snapshot_to_file(Process::attach(target.pid()).unwrap(), &path, &options, || false).unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    let snapshot = Snapshot::load(&path, true).unwrap();
+    let increased = snapshot.compare::<i32>(&Process::attach(target.pid()).unwrap(), |old, new| new > old).unwrap();
+    assert!(increased.contains(&target.player), "{:x?}", increased);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn cancelling_removes_the_file() {
+    let target = Target::spawn();
+    let path = temp_path("cancelled");
+    let error = snapshot_to_file(Process::attach(target.pid()).unwrap(), &path, &ScanOptions::default(), || true).unwrap_err();
+    assert!(error.to_string().contains("cancelled"), "{}", error);
+    assert!(!path.exists());
+}
+
+#[test]
+fn the_session_stops_the_target_for_the_snapshot() {
+    let target = Target::spawn();
+    let path = temp_path("session");
+    let output = target.session(&format!("snapshot {} --stop\n", path.display()));
+    assert!(output.contains(&format!("stopped process {}", target.pid())), "{}", output);
+    assert!(output.contains(&format!("continued process {}", target.pid())), "{}", output);
+    assert!(output.contains(&format!("to {}, ", path.display())), "{}", output);
+    assert!(!is_stopped(target.pid()));
+    let before = target.hp();
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(target.hp() > before);
+    assert!(Process::offline(&path).is_ok());
+    std::fs::remove_file(path).unwrap();
+}
+
+// Stopped by someone else, it is left for them to continue
+#[test]
+fn a_stopped_target_is_left_stopped() {
+    let target = Target::spawn();
+    let path = temp_path("stopped");
+    memory::stop_process(target.pid()).unwrap();
+    let output = target.session(&format!("snapshot {} --stop\nquit --leave-stopped\n", path.display()));
+    assert!(!output.contains("stopped process") && !output.contains("continued process"), "{}", output);
+    assert!(is_stopped(target.pid()));
+    memory::continue_process(target.pid()).unwrap();
+    std::fs::remove_file(path).unwrap();
+}
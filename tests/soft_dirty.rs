@@ -0,0 +1,94 @@
+mod common;
+use memory::{MOCK_PAGE_SIZE, MockProcess, ProcessMemory, ScanOptions, UnknownScan, snapshot::SNAPSHOT_CHUNK_SIZE};
+
+const HEAP: usize = 0x10_0000;
+
+// Two chunks of heap with a 5 at the start of each
+fn mock() -> MockProcess {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", HEAP, HEAP + 2 * SNAPSHOT_CHUNK_SIZE)).unwrap();
+    mock.plant_value(HEAP, 5i32).unwrap();
+    mock.plant_value(HEAP + SNAPSHOT_CHUNK_SIZE, 5i32).unwrap();
+    mock
+}
+
+fn options() -> ScanOptions {
+    ScanOptions { soft_dirty: true, ..ScanOptions::default() }
+}
+
+#[test]
+fn the_mock_tracks_pages_written_since_the_bits_were_cleared() {
+    let mock = mock();
+    assert_eq!(mock.dirty_ranges(&[(HEAP, HEAP + 0x2000)]).unwrap(), [(HEAP, HEAP + 0x2000)]);
+    mock.clear_soft_dirty().unwrap();
+    mock.plant(HEAP + MOCK_PAGE_SIZE - 1, &[1, 2]).unwrap();
+    mock.write_at(HEAP + 0x5000, &[1]).unwrap();
+    assert_eq!(mock.dirty_ranges(&[(HEAP + 0x10, HEAP + 0x5800)]).unwrap(), [(HEAP + 0x10, HEAP + 0x2000), (HEAP + 0x5000, HEAP + 0x5800)]);
+    mock.track_soft_dirty(false);
+    assert!(mock.clear_soft_dirty().is_err() && mock.dirty_ranges(&[(HEAP, HEAP + 0x1000)]).is_err());
+}
+
+// Only the page with the change is read again; the rest of both chunks is what was stored
+#[test]
+fn narrows_read_only_the_pages_written_since() {
+    let mock = mock();
+    let mut unknown = UnknownScan::start(&mock, 4, &options()).unwrap();
+    assert!(unknown.soft_dirty);
+    mock.plant_value(HEAP + SNAPSHOT_CHUNK_SIZE, 6i32).unwrap();
+    assert_eq!(unknown.narrow::<i32>(&mock, |old, new| new > old).unwrap(), 0);
+    assert_eq!(unknown.addresses(), [HEAP + SNAPSHOT_CHUNK_SIZE]);
+    assert_eq!(unknown.unread, 2 * SNAPSHOT_CHUNK_SIZE - MOCK_PAGE_SIZE);
+    // The bits were cleared again, so the next narrow reads nothing and sees no change
+    unknown.narrow::<i32>(&mock, |old, new| new == old).unwrap();
+    assert_eq!((unknown.addresses(), unknown.unread), (vec![HEAP + SNAPSHOT_CHUNK_SIZE], SNAPSHOT_CHUNK_SIZE));
+}
+
+#[test]
+fn without_soft_dirty_everything_is_read() {
+    let mock = mock();
+    mock.track_soft_dirty(false);
+    let mut unknown = UnknownScan::start(&mock, 4, &options()).unwrap();
+    assert!(!unknown.soft_dirty);
+    mock.plant_value(HEAP, 4i32).unwrap();
+    unknown.narrow::<i32>(&mock, |old, new| new < old).unwrap();
+    assert_eq!((unknown.addresses(), unknown.unread), (vec![HEAP], 0));
+    // Nor is it used unless asked for
+    let mock = self::mock();
+    assert!(!UnknownScan::start(&mock, 4, &ScanOptions::default()).unwrap().soft_dirty);
+}
+
+// A region that went away has no dirty pages, but its candidates still go with it
+#[test]
+fn chunks_no_longer_mapped_are_dropped_however_clean() {
+    let mock = mock();
+    mock.map("10000-11000 rw-p 00000000 00:00 0").unwrap();
+    let mut unknown = UnknownScan::start(&mock, 4, &options()).unwrap();
+    mock.unmap(0x10000).unwrap();
+    assert_eq!(unknown.narrow::<i32>(&mock, |old, new| new == old).unwrap(), 1);
+    assert!(unknown.soft_dirty && unknown.addresses().iter().all(|x| *x >= HEAP));
+}
+
+// Whether or not this kernel tracks the bits, the rescans find the hp the victim takes 1 off a tick
+#[cfg(target_os = "linux")]
+#[test]
+fn the_cli_narrows_with_soft_dirty_on() {
+    use std::{io::{BufRead, BufReader, Write}, process::{Command, Stdio}, time::Duration};
+    let path = common::victim_path();
+    let mut victim = Command::new(path).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut lines = BufReader::new(victim.stdout.take().unwrap()).lines();
+    let pid = lines.next().unwrap().unwrap().strip_prefix("pid ").unwrap().to_string();
+    let player = lines.next().unwrap().unwrap();
+    let player = usize::from_str_radix(player.strip_prefix("player 0x").unwrap().split_once(' ').unwrap().0, 16).unwrap();
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(&pid).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut stdin = session.stdin.take().unwrap();
+    writeln!(stdin, "set soft_dirty on\nscan unknown i32 --exclude-stacks").unwrap();
+    std::thread::sleep(Duration::from_millis(500));
+    writeln!(victim.stdin.as_mut().unwrap()).unwrap();
+    assert_eq!(lines.nth(1).unwrap().unwrap(), "ticked");
+    writeln!(stdin, "rescan decreased\nrescan 99\nlist").unwrap();
+    drop(stdin);
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    let _ = victim.kill();
+    let _ = victim.wait();
+    assert!(stdout.contains(&format!("0x{:x} (", player)) && stdout.contains(") 99 [rw-p]"), "{}", stdout);
+}
@@ -0,0 +1,67 @@
+#![cfg(target_os = "linux")]
+mod common;
+use std::{io::Write, process::{Command, Stdio}};
+use memory::{RegionCache, list_threads, thread_stack_regions};
+use nix::unistd::Pid;
+use common::Target;
+
+impl Target {
+    // Runs the commands in a session on the target, returning what it printed
+    fn session(&self, commands: &str) -> String {
+        let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(self.pid().to_string()).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+        session.stdin.take().unwrap().write_all(commands.as_bytes()).unwrap();
+        let output = session.wait_with_output().unwrap();
+        format!("{}{}", String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap())
+    }
+}
+
+#[test]
+fn finds_each_threads_stack() {
+    let target = Target::spawn();
+    let stacks = thread_stack_regions(target.pid());
+    let threads = list_threads(target.pid()).unwrap();
+    assert_eq!(threads.len(), 2);
+    for thread in &threads {
+        let (_, region) = stacks.iter().find(|x| x.0 == thread.tid).unwrap_or_else(|| panic!("no stack for {}: {:?}", thread.tid, stacks));
+        assert!(region.readable && region.writable, "{:?}", region);
+        // Only the main thread's is labelled; the other is an anonymous mapping pthreads made
+        let expected = if thread.tid == target.pid() { "[stack]" } else { "" };
+        assert_eq!(region.pathname, expected, "{:?}", region);
+    }
+    assert!(thread_stack_regions(Pid::from_raw(i32::MAX)).is_empty());
+}
+
+#[test]
+fn the_region_cache_names_the_same_stacks() {
+    let target = Target::spawn();
+    let cache = RegionCache::new(target.pid()).unwrap();
+    let known = cache.stacks().into_iter().filter_map(|(tid, region)| Some((tid?, region.start))).collect::<Vec<(Pid, usize)>>();
+    for (tid, region) in thread_stack_regions(target.pid()) {
+        assert!(known.contains(&(tid, region.start)), "{} at 0x{:x} not in {:?}", tid, region.start, known);
+    }
+    let starts = cache.stacks().iter().map(|x| x.1.start).collect::<Vec<usize>>();
+    assert!(starts.is_sorted());
+}
+
+#[test]
+fn scans_only_or_all_but_the_stacks() {
+    let target = Target::spawn();
+    let output = target.session(&format!("scan u64 {} --stacks-only\nlist\nscan u64 {} --exclude-stacks\nlist\n", target.player, target.player));
+    // Each scan's summary ends with its categories, and its listing follows
+    let listings = output.split("by category:").skip(1).collect::<Vec<&str>>();
+    assert_eq!(listings.len(), 2, "{}", output);
+    let results = |listing: &str| listing.lines().filter(|x| x.trim_start_matches("> ").starts_with('#')).map(str::to_string).collect::<Vec<String>>();
+    let (only, excluded) = (results(listings[0]), results(listings[1]));
+    assert!(!only.is_empty() && only.iter().all(|x| x.contains("(stack of tid ")), "{}", output);
+    assert!(!excluded.is_empty() && !excluded.iter().any(|x| x.contains("stack")), "{}", output);
+}
+
+#[test]
+fn the_session_lists_stacks() {
+    let target = Target::spawn();
+    let output = target.session("stacks\nscan u32 1 --stacks\n");
+    for thread in list_threads(target.pid()).unwrap() {
+        assert!(output.lines().any(|x| x.trim_start_matches("> ").starts_with("0x") && x.ends_with(&format!(" tid {}", thread.tid))), "{}", output);
+    }
+    assert!(output.contains("Unknown scan option '--stacks', expected --stacks-only or --exclude-stacks"), "{}", output);
+}
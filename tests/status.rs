@@ -0,0 +1,110 @@
+#![cfg(target_os = "linux")]
+mod common;
+use std::{io::Write, process::{Child, ChildStdin, Command, Stdio}, time::{Duration, Instant}};
+use memory::{AlreadyTraced, Endianness, MachineMessage, Process, PtraceSession, check_not_traced, read_scalar, target_status, tracer_of};
+use nix::unistd::Pid;
+use common::Target;
+
+// Another memory session holding a watch, so the target has a tracer that is not this process
+// for as long as it is kept
+struct OtherTracer {
+    child: Child,
+    _stdin: ChildStdin,
+}
+
+impl OtherTracer {
+    fn attach(target: &Target) -> OtherTracer {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_memory")).arg(target.pid().to_string()).stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::null()).spawn().unwrap();
+        let mut stdin = child.stdin.take().unwrap();
+        writeln!(stdin, "watchwrite 0x{:x} 4", target.player).unwrap();
+        let started = Instant::now();
+        while tracer_of(target.pid()).is_none() {
+            assert!(started.elapsed() < Duration::from_secs(10), "the other session never attached");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        OtherTracer { child, _stdin: stdin }
+    }
+}
+
+impl Drop for OtherTracer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn reads_the_target_status() {
+    let target = Target::spawn();
+    let status = target_status(target.pid()).unwrap();
+    assert_eq!((status.pid, status.name.as_str(), status.tracer.as_ref()), (target.pid(), "bench_target", None));
+    assert!(!status.stopped(), "{}", status.state);
+    assert_eq!(status.uid, target_status(nix::unistd::getpid()).unwrap().uid);
+    assert_eq!(status.dumpable, (status.effective_uid != 0).then_some(true));
+    assert_eq!((status.seccomp, status.seccomp_mode()), (0, "disabled"));
+    assert!(check_not_traced(target.pid()).is_ok());
+    assert!(target_status(Pid::from_raw(i32::MAX)).is_err());
+}
+
+// Tracing it from this process is not being traced by someone else
+#[test]
+fn its_own_tracing_is_not_in_the_way() {
+    let target = Target::spawn();
+    let session = PtraceSession::seize(target.pid()).unwrap();
+    let tracer = tracer_of(target.pid()).unwrap();
+    assert!(tracer.is_us());
+    assert_eq!(tracer.pid.as_raw() as u32, std::process::id());
+    assert!(check_not_traced(target.pid()).is_ok());
+    drop(session);
+    assert!(tracer_of(target.pid()).is_none());
+}
+
+#[test]
+fn names_the_other_tracer() {
+    let target = Target::spawn();
+    let other = OtherTracer::attach(&target);
+    let traced = check_not_traced(target.pid()).unwrap_err();
+    assert_eq!((traced.pid, traced.tracer.pid.as_raw() as u32, traced.tracer.name.as_deref()), (target.pid(), other.child.id(), Some("memory")));
+    assert!(traced.to_string().contains(&format!("already being traced by {} (memory)", other.child.id())), "{}", traced);
+    let error = PtraceSession::seize(target.pid()).err().unwrap();
+    assert!(error.downcast_ref::<AlreadyTraced>().is_some(), "{}", error);
+    let error = memory::read_registers(target.pid(), target.pid()).unwrap_err();
+    assert!(error.to_string().contains("already being traced by"), "{}", error);
+    // Without ptrace, memory is still there to be read
+    let process = Process::attach(target.pid()).unwrap();
+    assert!(read_scalar::<u32>(&process, target.player, Endianness::Native).unwrap() >= 100);
+}
+
+#[test]
+fn the_session_warns_and_shows_the_status() {
+    let target = Target::spawn();
+    let other = OtherTracer::attach(&target);
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(target.pid().to_string()).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    writeln!(session.stdin.take().unwrap(), "status\nregs\ndisasm 0x{:x} 1", target.tick.unwrap()).unwrap();
+    let output = session.wait_with_output().unwrap();
+    let (stdout, stderr) = (String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap());
+    let all = format!("{}{}", stdout, stderr);
+    let named = format!("already being traced by {} (memory)", other.child.id());
+    assert!(all.contains(&format!("warning: Process {} is {}", target.pid(), named)), "{}", all);
+    assert!(stdout.contains(&format!("tracer       {} (memory), so", other.child.id())), "{}", stdout);
+    assert!(stdout.contains("process      ") && stdout.contains("(bench_target)\n"), "{}", stdout);
+    assert!(stdout.contains("seccomp      disabled\n"), "{}", stdout);
+    // regs fails naming the tracer, and reading memory does not
+    assert!(all.matches(&named).count() >= 2, "{}", all);
+    assert!(stdout.contains(&format!("0x{:x}  ", target.tick.unwrap())), "{}", stdout);
+}
+
+#[test]
+fn machine_errors_say_the_target_is_traced() {
+    let target = Target::spawn();
+    let _other = OtherTracer::attach(&target);
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(target.pid().to_string()).arg("--machine").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    writeln!(session.stdin.take().unwrap(), "regs").unwrap();
+    let output = session.wait_with_output().unwrap();
+    let messages = String::from_utf8(output.stdout).unwrap().lines().map(|x| serde_json::from_str::<MachineMessage>(x).unwrap()).collect::<Vec<MachineMessage>>();
+    let error = messages.iter().find_map(|x| match x {
+        MachineMessage::Result { command, error, .. } if command == "regs" => error.clone(),
+        _ => None,
+    });
+    assert_eq!(error.map(|x| x.code), Some(memory::ErrorCode::Traced));
+}
@@ -0,0 +1,40 @@
+use memory::{MockProcess, ScanOptions, find_value, scan::SCAN_CHUNK_SIZE};
+
+const HEAP: usize = 0x100_0000;
+const CHUNKS: usize = 8;
+
+// A heap of whole scan chunks with the value at the start of each
+fn mock() -> MockProcess {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", HEAP, HEAP + CHUNKS * SCAN_CHUNK_SIZE)).unwrap();
+    for chunk in 0..CHUNKS {
+        mock.plant_value(HEAP + chunk * SCAN_CHUNK_SIZE, 0x5707_a97e_u32).unwrap();
+    }
+    mock
+}
+
+// On one thread the chunks are taken in order, so where the scan stops is certain
+fn on_one_thread<R: Send>(work: impl FnOnce() -> R + Send) -> R {
+    rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap().install(work)
+}
+
+#[test]
+fn stops_picking_up_chunks_once_enough_are_found() {
+    let mock = mock();
+    let options = ScanOptions { stop_after: Some(2), ..ScanOptions::default() };
+    let (found, stats) = on_one_thread(|| find_value(&mock, 0x5707_a97e_u32, &options).unwrap());
+    assert_eq!(found, [HEAP, HEAP + SCAN_CHUNK_SIZE]);
+    assert!(stats.partial);
+    assert_eq!((stats.bytes_scanned, stats.bytes_total), (2 * SCAN_CHUNK_SIZE, CHUNKS * SCAN_CHUNK_SIZE));
+    assert_eq!(stats.coverage(), 2.0 / CHUNKS as f64);
+}
+
+// Only a scan that left memory unread is partial, however many it found
+#[test]
+fn a_scan_that_covered_everything_is_not_partial() {
+    let mock = mock();
+    let (found, stats) = on_one_thread(|| find_value(&mock, 0x5707_a97e_u32, &ScanOptions { stop_after: Some(CHUNKS), ..ScanOptions::default() }).unwrap());
+    assert_eq!((found.len(), stats.partial), (CHUNKS, false));
+    let (found, stats) = find_value(&mock, 0x5707_a97e_u32, &ScanOptions { stop_after: Some(100), ..ScanOptions::default() }).unwrap();
+    assert_eq!((found.len(), stats.partial, stats.coverage()), (CHUNKS, false, 1.0));
+}
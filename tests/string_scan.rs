@@ -0,0 +1,144 @@
+mod common;
+use memory::{Endianness, MockProcess, SCAN_CHUNK_SIZE, ScanOptions, StringSearch, find_string};
+use memory::Encoding;
+
+const HEAP: usize = 0x10000;
+
+// Names as a game keeps them, NUL after each, and one that is the tail of a longer name
+fn mock() -> MockProcess {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", HEAP, HEAP + 0x1000)).unwrap();
+    mock.plant(HEAP + 0x100, b"PLAYER_01_NAME=Ann\0").unwrap();
+    mock.plant(HEAP + 0x200, b"PLAYER_02_NAME\0").unwrap();
+    mock.plant(HEAP + 0x300, b"OLD_PLAYER_03_NAME\0").unwrap();
+    mock.plant(HEAP + 0x400, b"PLAYER_4_NAME\0").unwrap();
+    mock.plant(HEAP + 0x500, b"player_05_name\0").unwrap();
+    mock
+}
+
+fn glob() -> StringSearch {
+    StringSearch { glob: true, ..StringSearch::default() }
+}
+
+fn find(mock: &MockProcess, needle: &str, search: &StringSearch) -> Vec<usize> {
+    find_string(mock, needle, search, &ScanOptions::default()).unwrap().0
+}
+
+#[test]
+fn question_marks_take_one_character_each() {
+    let mock = mock();
+    assert_eq!(find(&mock, "PLAYER_??_NAME", &glob()), [HEAP + 0x100, HEAP + 0x200, HEAP + 0x304]);
+    assert_eq!(find(&mock, "PLAYER_?_NAME", &glob()), [HEAP + 0x400]);
+    // Matched as written, ? is just a ?
+    assert!(find(&mock, "PLAYER_??_NAME", &StringSearch::default()).is_empty());
+    mock.plant(HEAP + 0x600, b"WHO?\0").unwrap();
+    assert_eq!(find(&mock, "WHO?", &StringSearch::default()), [HEAP + 0x600]);
+    assert_eq!(find(&mock, "WHO\\?", &glob()), [HEAP + 0x600]);
+}
+
+// A run stops at the string's NUL and at max_run characters
+#[test]
+fn stars_take_a_bounded_run_within_the_string() {
+    let mock = mock();
+    assert_eq!(find(&mock, "PLAYER*NAME", &glob()), [HEAP + 0x100, HEAP + 0x200, HEAP + 0x304, HEAP + 0x400]);
+    assert_eq!(find(&mock, "PLAYER*NAME", &StringSearch { max_run: 3, ..glob() }), [HEAP + 0x400]);
+    assert_eq!(find(&mock, "NAME=*n", &glob()), [HEAP + 0x10a]);
+    // Nothing joins the name at 0x200 to the one at 0x300
+    assert!(find(&mock, "_02_*OLD", &StringSearch { max_run: 0x200, ..glob() }).is_empty());
+}
+
+#[test]
+fn case_is_ignored_by_expanding_letters() {
+    let mock = mock();
+    let search = StringSearch { ignore_case: true, ..glob() };
+    assert_eq!(find(&mock, "player_0?_name", &search), [HEAP + 0x100, HEAP + 0x200, HEAP + 0x304, HEAP + 0x500]);
+    assert_eq!(find(&mock, "Player_05_Name", &StringSearch { ignore_case: true, ..StringSearch::default() }), [HEAP + 0x500]);
+    assert!(find(&mock, "Player_05_Name", &StringSearch::default()).is_empty());
+}
+
+// Only strings that start after a terminator, and only at the alignment
+#[test]
+fn anchors_find_string_starts() {
+    let mock = mock();
+    mock.plant(HEAP + 0x1ff, b"X").unwrap();
+    mock.plant(HEAP + 0x6f8, b"\0PLAYER_06_NAME\0").unwrap();
+    let search = StringSearch { after_nul: true, ..glob() };
+    assert_eq!(find(&mock, "PLAYER_??_NAME", &search), [HEAP + 0x100, HEAP + 0x6f9]);
+    // A leading * takes in whatever the string has before
+    assert_eq!(find(&mock, "*PLAYER_??_NAME", &search), [HEAP + 0x100, HEAP + 0x1ff, HEAP + 0x300, HEAP + 0x6f9]);
+    let options = ScanOptions { alignment: Some(0x10), ..ScanOptions::default() };
+    assert_eq!(find_string(&mock, "PLAYER_??_NAME", &search, &options).unwrap().0, [HEAP + 0x100]);
+    assert_eq!(find_string(&mock, "PLAYER_??_NAME", &glob(), &options).unwrap().0, [HEAP + 0x100, HEAP + 0x200]);
+}
+
+#[test]
+fn utf16_matches_code_units_in_the_byte_order() {
+    let mock = mock();
+    let units = |text: &str, endianness: Endianness| text.encode_utf16().flat_map(|x| match endianness {
+        Endianness::Big => x.to_be_bytes(),
+        _ => x.to_le_bytes(),
+    }).collect::<Vec<u8>>();
+    mock.plant(HEAP + 0x700, &units("\0Ünïcode Näme\0", Endianness::Little)).unwrap();
+    mock.plant(HEAP + 0x800, &units("\0ÜNÏCODE NAME\0", Endianness::Big)).unwrap();
+    let search = StringSearch { encoding: Encoding::Utf16, ignore_case: true, after_nul: true, ..glob() };
+    assert_eq!(find(&mock, "Ünïcode N?me", &search), [HEAP + 0x702]);
+    let big = ScanOptions { endianness: Endianness::Big, ..ScanOptions::default() };
+    assert_eq!(find_string(&mock, "ÜNÏCODE*", &search, &big).unwrap().0, [HEAP + 0x802]);
+    // Only ASCII letters have their case ignored
+    assert!(find(&mock, "ünïcode", &search).is_empty());
+}
+
+// A match is found whole across a chunk seam, and one shorter than the longest a * allows is found
+// at the very end of a region
+#[test]
+fn matches_near_seams_and_region_ends_are_found() {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0", HEAP, HEAP + 2 * SCAN_CHUNK_SIZE)).unwrap();
+    mock.plant(HEAP + SCAN_CHUNK_SIZE - 3, b"\0KEY_VALUE\0").unwrap();
+    mock.plant(HEAP + 2 * SCAN_CHUNK_SIZE - 7, b"XKEY_ab").unwrap();
+    let search = StringSearch { after_nul: true, ..glob() };
+    assert_eq!(find(&mock, "KEY_*", &search), [HEAP + SCAN_CHUNK_SIZE - 2]);
+    assert_eq!(find(&mock, "KEY_*", &glob()), [HEAP + SCAN_CHUNK_SIZE - 2, HEAP + 2 * SCAN_CHUNK_SIZE - 6]);
+}
+
+// Each run is up to 32 characters, so there are 33 to the power of four ways to try *a*a*a*b at
+// every byte of a page of a. The b is found from 131 bytes before it up to 3 before
+#[test]
+fn many_runs_do_not_multiply_the_cost() {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0", HEAP, HEAP + 0x1000)).unwrap();
+    mock.plant(HEAP, &[b'a'; 0x1000]).unwrap();
+    assert!(find(&mock, "*a*a*a*b", &glob()).is_empty());
+    mock.plant(HEAP + 0x800, b"b").unwrap();
+    assert_eq!(find(&mock, "*a*a*a*b", &glob()), (HEAP + 0x800 - 131..=HEAP + 0x800 - 3).collect::<Vec<usize>>());
+}
+
+#[test]
+fn needles_that_match_everywhere_are_refused() {
+    let mock = mock();
+    for (needle, error) in [("", "An empty string would match everywhere"), ("?*", "'?*' is all wildcards, which would match almost everywhere"), ("AB\\", "'AB\\' ends in a \\ with nothing after it to match as itself")] {
+        assert_eq!(find_string(&mock, needle, &glob(), &ScanOptions::default()).unwrap_err().to_string(), error);
+    }
+}
+
+// The victim's player is named "Player One", after the padding that follows its speed
+#[cfg(target_os = "linux")]
+#[test]
+fn the_cli_scans_for_globs() {
+    use std::{io::{BufRead, BufReader, Write}, process::{Command, Stdio}};
+    let path = common::victim_path();
+    let mut victim = Command::new(path).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut lines = BufReader::new(victim.stdout.take().unwrap()).lines();
+    let pid = lines.next().unwrap().unwrap().strip_prefix("pid ").unwrap().to_string();
+    let player = lines.next().unwrap().unwrap();
+    let player = usize::from_str_radix(player.strip_prefix("player 0x").unwrap().split_once(' ').unwrap().0, 16).unwrap();
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(&pid).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let commands = "scan string --glob \"pl?yer o*\" --ignore-case\nlist\nscan string \"pl?yer\"\nscan string \"Player\" --max-run 4";
+    writeln!(session.stdin.take().unwrap(), "{}", commands).unwrap();
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    let _ = victim.kill();
+    let _ = victim.wait();
+    assert!(stdout.contains(&format!("0x{:x} (heap+", player + 0x14)) && stdout.contains("\"Player One\""), "{}", stdout);
+    assert!(stdout.contains(", 0 matches"), "{}", stdout);
+    assert!(stdout.contains("error: --max-run is how far a * reaches, which only --glob has"), "{}", stdout);
+}
@@ -0,0 +1,87 @@
+use memory::{FieldConstraint, MockProcess, Scalar, ScanOptions, ScanStats, StructPattern, TypedValue, ValueType, find_struct, reduce_found_structs};
+
+// Laid out like examples/victim.rs: i32 hp at 0x0, i64 gold at 0x8, f32 speed at 0x10
+fn plant_player(mock: &MockProcess, address: usize, hp: i32, gold: i64, speed: f32) {
+    mock.plant(address, &hp.to_bytes()).unwrap();
+    mock.plant(address + 0x8, &gold.to_bytes()).unwrap();
+    mock.plant(address + 0x10, &speed.to_bytes()).unwrap();
+}
+
+fn mock() -> MockProcess {
+    let mock = MockProcess::new(100);
+    mock.map("10000-12000 rw-p 00000000 00:00 0").unwrap();
+    mock
+}
+
+fn player() -> StructPattern {
+    "+0x0 i32 1..1000, +0x8 i64 *, +0x10 f32 0.5..20".parse().unwrap()
+}
+
+#[test]
+fn every_field_has_to_match() {
+    let mock = mock();
+    plant_player(&mock, 0x10100, 100, 1000, 1.5);
+    plant_player(&mock, 0x10200, 5000, 1000, 1.5);
+    plant_player(&mock, 0x10300, 100, 1000, 50.0);
+    plant_player(&mock, 0x10400, 1, -7, 20.0);
+    let (found, stats) = find_struct(&mock, &player(), &ScanOptions { alignment: Some(8), ..ScanOptions::default() }).unwrap();
+    assert_eq!((found, stats.matches), (vec![0x10100, 0x10400], 2));
+}
+
+// Structs are looked for at every multiple of the alignment and nowhere else
+#[test]
+fn the_base_steps_by_the_alignment() {
+    let mock = mock();
+    plant_player(&mock, 0x10104, 100, 1000, 1.5);
+    let found = |alignment| find_struct(&mock, &player(), &ScanOptions { alignment: Some(alignment), ..ScanOptions::default() }).unwrap().0;
+    assert_eq!(found(8), Vec::<usize>::new());
+    assert_eq!(found(4), [0x10104]);
+    assert_eq!(player().alignment(), 8);
+    assert_eq!(player().size(), 0x14);
+}
+
+#[test]
+fn pointers_have_to_point_into_mapped_memory() {
+    let mock = mock();
+    mock.plant(0x10000, &[0x10800usize.to_bytes(), 0x90000usize.to_bytes(), 0x11ff8usize.to_bytes()].concat()).unwrap();
+    let pattern = StructPattern { fields: vec![(0, FieldConstraint::Pointer)] };
+    let (found, _) = find_struct(&mock, &pattern, &ScanOptions { alignment: Some(8), ..ScanOptions::default() }).unwrap();
+    assert_eq!(found, [0x10000, 0x10010]);
+}
+
+// A rescan checks the same fields again, keeping only the structs that still match
+#[test]
+fn rescans_drop_structs_that_stopped_matching() {
+    let mock = mock();
+    let pattern = "+0 i32 100, +0x14 str Player".parse::<StructPattern>().unwrap();
+    for address in [0x10100, 0x10200] {
+        mock.plant(address, &100i32.to_bytes()).unwrap();
+        mock.plant(address + 0x14, b"Player").unwrap();
+    }
+    let (mut found, mut stats) = find_struct(&mock, &pattern, &ScanOptions { alignment: Some(4), ..ScanOptions::default() }).unwrap();
+    assert_eq!(found, [0x10100, 0x10200]);
+    mock.plant(0x10214, b"Nobody").unwrap();
+    reduce_found_structs(&mock, &mut found, &pattern, &ScanOptions::default(), &mut stats).unwrap();
+    assert_eq!((found, stats.matches), (vec![0x10100], 1));
+}
+
+#[test]
+fn patterns_parse_from_text() {
+    let pattern = "+0x10 i32 1..1000, +24 f32 1.5, +0x0 ptr, +0x20 u8 *".parse::<StructPattern>().unwrap();
+    assert_eq!(pattern.fields, [
+        (0x10, FieldConstraint::Range(TypedValue::I32(1), TypedValue::I32(1000))),
+        (24, FieldConstraint::Equals(TypedValue::F32(1.5))),
+        (0, FieldConstraint::Pointer),
+        (0x20, FieldConstraint::Any(ValueType::U8)),
+    ]);
+    for (text, error) in [("+0 ptr 5", "takes no value"), ("+0 bytes", "needs a value"), ("oops", "Expected '<offset> <type>"), ("", "Expected fields"), ("+0 u8 -1..5", "u8 is unsigned")] {
+        let message = text.parse::<StructPattern>().unwrap_err().to_string();
+        assert!(message.contains(error), "{}: {}", text, message);
+    }
+    let mock = mock();
+    let error = find_struct(&mock, &"+0 i32 *".parse().unwrap(), &ScanOptions::default()).unwrap_err();
+    assert!(error.to_string().contains("not a wildcard"), "{}", error);
+    let error = find_struct(&mock, &"+0 i32 5..1".parse().unwrap(), &ScanOptions::default()).unwrap_err();
+    assert!(error.to_string().contains("5..1 is empty"), "{}", error);
+    assert!(reduce_found_structs(&mock, &mut vec![], &StructPattern::default(), &ScanOptions::default(), &mut ScanStats::default()).is_err());
+}
@@ -0,0 +1,69 @@
+#![cfg(target_os = "linux")]
+mod common;
+use std::{io::Write, process::{Command, Stdio}, sync::Arc};
+use memory::{Module, find_symbol, locate, modules, symbol_table};
+use common::Target;
+
+impl Target {
+    fn modules(&self) -> Vec<Module> {
+        modules(self.pid()).unwrap()
+    }
+}
+
+#[test]
+fn names_addresses_in_modules() {
+    let target = Target::spawn();
+    let modules = target.modules();
+    assert_eq!(locate(&modules, target.tick.unwrap()).to_string(), "bench_target!bench_target::tick+0x0");
+    let inside = locate(&modules, target.tick.unwrap() + 3);
+    assert_eq!(inside.symbol, Some(("bench_target::tick".to_string(), 3)));
+    let base = modules.iter().find(|x| x.name == "bench_target").unwrap().base;
+    assert_eq!(inside.module, Some(("bench_target".to_string(), target.tick.unwrap() + 3 - base)));
+    // The heap is in no module
+    let heap = locate(&modules, target.player);
+    assert_eq!((heap.to_string(), heap.module, heap.symbol), (format!("0x{:x}", target.player), None, None));
+}
+
+#[test]
+fn finds_symbols_by_name() {
+    let target = Target::spawn();
+    let modules = target.modules();
+    let found = find_symbol(&modules, "bench_target::tick");
+    assert_eq!(found.iter().map(|x| x.address).collect::<Vec<usize>>(), [target.tick.unwrap()]);
+    assert_eq!(find_symbol(&modules, "bench_target!bench_target::tick"), found);
+    assert!(find_symbol(&modules, "libc.so.6!bench_target::tick").is_empty());
+    assert!(find_symbol(&modules, "no_such_symbol").is_empty());
+    // libc is stripped of .symtab, so this comes from .dynsym
+    let malloc = find_symbol(&modules, "libc.so.6!malloc");
+    assert_eq!(malloc.len(), 1, "{:?}", malloc);
+    let located = locate(&modules, malloc[0].address);
+    assert_eq!((located.module.unwrap().0, located.symbol.unwrap().1), ("libc.so.6".to_string(), 0));
+}
+
+// A module whose file has no symbols, is not ELF or was replaced on disk is still named by its module
+#[test]
+fn falls_back_to_module_offsets() {
+    let target = Target::spawn();
+    let real = target.modules().into_iter().find(|x| x.name == "bench_target").unwrap();
+    let fake = |name: &str, path: &str, deleted: bool| Module { name: name.to_string(), path: path.to_string(), base: 0x10000, size: 0x1000, executable: true, writable: false, deleted };
+    let modules = [fake("null", "/dev/null", false), fake("missing", "/no/such/file", false), fake("deleted", &real.path, true)];
+    for module in &modules {
+        assert!(symbol_table(module).is_none());
+        assert_eq!(locate(std::slice::from_ref(module), 0x10010).to_string(), format!("{}+0x10", module.name));
+    }
+    // Parsed once per file
+    assert!(Arc::ptr_eq(&symbol_table(&real).unwrap(), &symbol_table(&real).unwrap()));
+}
+
+#[test]
+fn the_session_looks_symbols_up_both_ways() {
+    let target = Target::spawn();
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(target.pid().to_string()).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    writeln!(session.stdin.take().unwrap(), "sym 0x{:x}\naddr bench_target::tick\nsym 0x{:x}\naddr nothing_is_called_this", target.tick.unwrap() + 4, target.player).unwrap();
+    let output = session.wait_with_output().unwrap();
+    let (stdout, stderr) = (String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap());
+    assert!(stdout.contains(&format!("0x{:x} bench_target!bench_target::tick+0x4\n", target.tick.unwrap() + 4)), "{}", stdout);
+    assert!(stdout.contains(&format!("0x{:x} bench_target!bench_target::tick+0x0\n", target.tick.unwrap())), "{}", stdout);
+    assert!(stdout.contains(&format!("0x{:x} heap+0x", target.player)), "{}", stdout);
+    assert!(format!("{}{}", stdout, stderr).contains("No loaded module has a symbol named nothing_is_called_this"), "{}{}", stdout, stderr);
+}
@@ -0,0 +1,58 @@
+// Target for the integration tests: holds a few known structures, reports where they are, and
+// idles until its stdin is closed. The counter goes up by one on every SIGUSR1, so a test can
+// change a value when it chooses to rather than racing a timer
+use std::{io::{Read, Write}, sync::atomic::{AtomicI32, AtomicPtr, Ordering}};
+
+// Must match the constants in tests/harness.rs
+const COUNTER_START: i32 = 0x1ee7_0000;
+const FLOATS: usize = 16;
+const STRING_LEN: usize = 3000;
+const CHAIN_VALUE: i32 = 0x0c4a_1234;
+
+static COUNTER: AtomicI32 = AtomicI32::new(COUNTER_START);
+// The base of the pointer chain, in the executable's .bss so it is module-relative like a game's
+static ROOT: AtomicPtr<Outer> = AtomicPtr::new(std::ptr::null_mut());
+
+#[repr(C)]
+struct Outer {
+    tag: u64,
+    inner: *mut Inner,
+}
+
+#[repr(C)]
+struct Inner {
+    tag: u64,
+    padding: u64,
+    value: i32,
+    // Spelled out so the tail is zeroed, as the last hop reads 8 bytes over value
+    _pad: u32,
+}
+
+#[cfg(unix)]
+extern "C" fn increment(_: libc::c_int) {
+    COUNTER.fetch_add(1, Ordering::SeqCst);
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(unix)]
+    unsafe {
+        libc::signal(libc::SIGUSR1, increment as *const () as libc::sighandler_t);
+    }
+    let floats = (0..FLOATS).map(|x| x as f32 + 0.5).collect::<Vec<f32>>();
+    // Printable and never repeating within a few hundred bytes, so a read that is off shows
+    let string = (0..STRING_LEN).map(|x| (b'a' + (x * 7 % 26) as u8) as char).collect::<String>() + "\0";
+    let inner = Box::into_raw(Box::new(Inner { tag: 0x1111, padding: 0, value: CHAIN_VALUE, _pad: 0 }));
+    ROOT.store(Box::into_raw(Box::new(Outer { tag: 0x2222, inner })), Ordering::SeqCst);
+    let mut stdout = std::io::stdout();
+    writeln!(stdout, "pid {}", std::process::id())?;
+    writeln!(stdout, "counter 0x{:x} {}", &COUNTER as *const AtomicI32 as usize, COUNTER_START)?;
+    writeln!(stdout, "floats 0x{:x} {}", floats.as_ptr() as usize, FLOATS)?;
+    writeln!(stdout, "string 0x{:x} {}", string.as_ptr() as usize, STRING_LEN)?;
+    writeln!(stdout, "chain 0x{:x} 0x{:x} 0x{:x}", &ROOT as *const AtomicPtr<Outer> as usize, std::mem::offset_of!(Outer, inner), std::mem::offset_of!(Inner, value))?;
+    writeln!(stdout, "value 0x{:x}", unsafe { &raw const (*inner).value } as usize)?;
+    stdout.flush()?;
+    // Returns once the test closes the pipe (or dies), so the target never outlives it
+    let _ = std::io::stdin().read_to_end(&mut Vec::new());
+    std::hint::black_box((&floats, &string, ROOT.load(Ordering::SeqCst)));
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use std::{process::Command, sync::{Arc, Mutex}, time::{Duration, Instant}};
+use memory::{MockProcess, ProgressCallback, ScanOptions, ScanProgress, UnknownScan, find_value};
+
+const HEAP: usize = 0x100000;
+const MB: u64 = 1 << 20;
+
+// Two MB of heap with a value at its start and its end
+fn mock() -> MockProcess {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", HEAP, HEAP + 2 * MB as usize)).unwrap();
+    mock.plant_value(HEAP, 31337i32).unwrap();
+    mock.plant_value(HEAP + 2 * MB as usize - 4, 31337i32).unwrap();
+    mock
+}
+
+fn throttled(rate: u64) -> ScanOptions {
+    ScanOptions { throttle: Some(rate), ..ScanOptions::default() }
+}
+
+// At 4 MB/s the two MB take half a second, less the tenth of a second's burst allowed at the start
+#[test]
+fn a_throttled_scan_reads_no_faster_than_the_cap() {
+    let mock = mock();
+    let started = Instant::now();
+    let (found, stats) = find_value(&mock, 31337i32, &throttled(4 * MB)).unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(350), "{:?}", started.elapsed());
+    assert_eq!((found, stats.bytes_scanned), (vec![HEAP, HEAP + 2 * MB as usize - 4], 2 * MB as usize));
+}
+
+// Throttled to 1 MB/s the scan would take nearly two seconds
+#[test]
+fn zero_is_unthrottled() {
+    let mock = mock();
+    let started = Instant::now();
+    assert_eq!(find_value(&mock, 31337i32, &throttled(0)).unwrap().0.len(), 2);
+    assert!(started.elapsed() < Duration::from_millis(1500), "{:?}", started.elapsed());
+}
+
+#[test]
+fn progress_shows_the_cap() {
+    let mock = mock();
+    let reports: Arc<Mutex<Vec<ScanProgress>>> = Arc::default();
+    let seen = reports.clone();
+    let options = ScanOptions { progress: Some(ProgressCallback(Arc::new(move |x| seen.lock().unwrap().push(*x)))), ..throttled(4 * MB) };
+    find_value(&mock, 31337i32, &options).unwrap();
+    let reports = reports.lock().unwrap();
+    assert!(!reports.is_empty());
+    assert!(reports.iter().all(|x| x.throttle == Some(4 * MB)), "{:?}", reports);
+}
+
+// The snapshot an unknown scan starts from, and each narrow after, keep to the throttle
+#[test]
+fn unknown_scans_are_throttled_throughout() {
+    let mock = mock();
+    let started = Instant::now();
+    let mut unknown = UnknownScan::start(&mock, 4, &throttled(4 * MB)).unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(350), "{:?}", started.elapsed());
+    assert_eq!(unknown.throttle, Some(4 * MB));
+    let started = Instant::now();
+    mock.plant_value(HEAP, 1i32).unwrap();
+    unknown.narrow::<i32>(&mock, |old, new| old != new).unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(350), "{:?}", started.elapsed());
+    assert_eq!(unknown.addresses(), [HEAP]);
+}
+
+#[test]
+fn the_cli_takes_throttle_per_scan() {
+    let output = Command::new(env!("CARGO_BIN_EXE_memory")).args(["--offline", "tests/fixtures/scan_before.cap"]).stdin(std::process::Stdio::piped()).stdout(std::process::Stdio::piped()).spawn().unwrap();
+    let mut stdin = output.stdin.as_ref().unwrap();
+    std::io::Write::write_all(&mut stdin, b"scan i32 100 --throttle 64\nscan i32 100 --throttle fast\n").unwrap();
+    let stdout = String::from_utf8(output.wait_with_output().unwrap().stdout).unwrap();
+    assert!(stdout.contains("error: Expected a rate in MB/s, not 'fast'"), "{}", stdout);
+    assert_eq!(stdout.matches("error:").count(), 1, "{}", stdout);
+}
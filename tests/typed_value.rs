@@ -0,0 +1,149 @@
+use memory::{Encoding, Endianness, TypedValue, ValueType, write_typed};
+use nix::unistd::Pid;
+
+const STRING_TYPES: [ValueType; 4] = [
+    ValueType::Str { encoding: Encoding::Utf8, null_terminate: false },
+    ValueType::Str { encoding: Encoding::Utf8, null_terminate: true },
+    ValueType::Str { encoding: Encoding::Utf16, null_terminate: false },
+    ValueType::Str { encoding: Encoding::Utf16, null_terminate: true },
+];
+
+fn all_types() -> Vec<ValueType> {
+    ValueType::NUMERIC.into_iter().chain([ValueType::Bytes]).chain(STRING_TYPES).collect()
+}
+
+// Parsing the formatted value gives back the same bytes, and so does decoding them. Bytes rather than
+// values are compared so that NaN round-trips too
+fn assert_round_trip(value: TypedValue) {
+    let text = value.to_string();
+    let parsed = text.parse::<TypedValue>().unwrap_or_else(|e| panic!("could not parse '{}': {}", text, e));
+    assert_eq!(parsed.value_type(), value.value_type(), "{}", text);
+    assert_eq!(parsed.to_bytes(), value.to_bytes(), "{}", text);
+    assert_eq!(parsed.to_string(), text);
+    let decoded = value.value_type().value_from_bytes(&value.to_bytes()).unwrap();
+    assert_eq!(decoded.to_bytes(), value.to_bytes(), "{}", text);
+    assert_eq!(decoded.to_string(), text);
+}
+
+#[test]
+fn type_names_round_trip() {
+    let names = all_types().iter().map(|x| x.to_string()).collect::<Vec<String>>();
+    assert_eq!(names, ["i8", "i16", "i32", "i64", "u8", "u16", "u32", "u64", "f32", "f64", "bytes", "str", "strz", "utf16", "utf16z"]);
+    for value_type in all_types() {
+        assert_eq!(value_type.to_string().parse::<ValueType>(), Ok(value_type));
+    }
+    assert!("i128".parse::<ValueType>().is_err());
+    assert!("I32".parse::<ValueType>().is_err());
+}
+
+#[test]
+fn sizes_match_encoded_values() {
+    for value_type in ValueType::NUMERIC {
+        let value = value_type.parse_value("1").unwrap();
+        assert_eq!(value_type.size(), Some(value.to_bytes().len()), "{}", value_type);
+        assert!(value_type.is_numeric());
+    }
+    assert_eq!(ValueType::Bytes.size(), None);
+    assert!(!ValueType::Bytes.is_numeric());
+    for value_type in STRING_TYPES {
+        assert_eq!(value_type.size(), None);
+        assert!(!value_type.is_numeric());
+    }
+}
+
+#[test]
+fn integers_round_trip_at_their_bounds() {
+    for value in [
+        TypedValue::I8(i8::MIN), TypedValue::I8(0), TypedValue::I8(i8::MAX),
+        TypedValue::I16(i16::MIN), TypedValue::I16(-1), TypedValue::I16(i16::MAX),
+        TypedValue::I32(i32::MIN), TypedValue::I32(999), TypedValue::I32(i32::MAX),
+        TypedValue::I64(i64::MIN), TypedValue::I64(-999), TypedValue::I64(i64::MAX),
+        TypedValue::U8(0), TypedValue::U8(u8::MAX),
+        TypedValue::U16(0), TypedValue::U16(u16::MAX),
+        TypedValue::U32(0), TypedValue::U32(u32::MAX),
+        TypedValue::U64(0), TypedValue::U64(u64::MAX),
+    ] {
+        assert_round_trip(value);
+    }
+}
+
+#[test]
+fn floats_round_trip_including_special_values() {
+    for x in [0.0, -0.0, 1.5, -1.5, f32::MIN, f32::MAX, f32::MIN_POSITIVE, f32::EPSILON, 1e-45, f32::INFINITY, f32::NEG_INFINITY, f32::NAN] {
+        assert_round_trip(TypedValue::F32(x));
+    }
+    for x in [0.0, -0.0, 1.5, 0.1, f64::MIN, f64::MAX, f64::MIN_POSITIVE, 5e-324, f64::INFINITY, f64::NEG_INFINITY, f64::NAN] {
+        assert_round_trip(TypedValue::F64(x));
+    }
+}
+
+#[test]
+fn bytes_round_trip() {
+    assert_round_trip(TypedValue::Bytes(vec![0x90]));
+    assert_round_trip(TypedValue::Bytes(vec![0x90, 0x90]));
+    assert_round_trip(TypedValue::Bytes((0..=255).collect()));
+    assert_eq!(TypedValue::Bytes(vec![0x90, 0x0a]).to_string(), "bytes:90 0a");
+}
+
+#[test]
+fn strings_round_trip_in_every_encoding() {
+    for value_type in STRING_TYPES {
+        let ValueType::Str { encoding, null_terminate } = value_type else { unreachable!() };
+        for text in ["hello", "", "with spaces", "a:b:c", "héllo wörld", "日本語", "emoji 🦀"] {
+            assert_round_trip(TypedValue::Str { text: text.to_string(), encoding, null_terminate });
+        }
+    }
+}
+
+#[test]
+fn parses_the_documented_forms() {
+    assert_eq!("i32:999".parse::<TypedValue>().unwrap(), TypedValue::I32(999));
+    assert_eq!("f32:1.5".parse::<TypedValue>().unwrap(), TypedValue::F32(1.5));
+    assert_eq!("bytes:90 90".parse::<TypedValue>().unwrap(), TypedValue::Bytes(vec![0x90, 0x90]));
+    assert_eq!("bytes:deadBEEF".parse::<TypedValue>().unwrap(), TypedValue::Bytes(vec![0xde, 0xad, 0xbe, 0xef]));
+    assert_eq!("str:a:b".parse::<TypedValue>().unwrap(), TypedValue::Str { text: "a:b".to_string(), encoding: Encoding::Utf8, null_terminate: false });
+}
+
+#[test]
+fn encodes_strings_as_documented() {
+    assert_eq!("str:hi".parse::<TypedValue>().unwrap().to_bytes(), b"hi");
+    assert_eq!("strz:hi".parse::<TypedValue>().unwrap().to_bytes(), b"hi\0");
+    let utf16 = "hi".encode_utf16().flat_map(|x| x.to_ne_bytes()).collect::<Vec<u8>>();
+    assert_eq!("utf16:hi".parse::<TypedValue>().unwrap().to_bytes(), utf16);
+    assert_eq!("utf16z:hi".parse::<TypedValue>().unwrap().to_bytes(), [utf16, vec![0, 0]].concat());
+}
+
+#[test]
+fn rejects_malformed_text() {
+    for text in [
+        "999", "i32", ":999", "i32:", "i32:abc", "i32:1.5", "i8:128", "u8:-1", "u64:18446744073709551616",
+        "f32:", "f32:one", "bytes:", "bytes:9", "bytes:zz", "bytes:é0", "int:5",
+    ] {
+        assert!(text.parse::<TypedValue>().is_err(), "'{}' should not parse", text);
+    }
+}
+
+#[test]
+fn rejects_bytes_of_the_wrong_shape() {
+    assert!(ValueType::I32.value_from_bytes(&[0; 3]).is_err());
+    assert!(ValueType::F64.value_from_bytes(&[0; 4]).is_err());
+    assert!("strz".parse::<ValueType>().unwrap().value_from_bytes(b"hi").is_err());
+    assert!("utf16z".parse::<ValueType>().unwrap().value_from_bytes(&[b'h', 0, b'i']).is_err());
+    assert!("utf16".parse::<ValueType>().unwrap().value_from_bytes(&[b'h', 0, b'i']).is_err());
+    assert!("str".parse::<ValueType>().unwrap().value_from_bytes(&[0xff, 0xfe]).is_err());
+}
+
+#[test]
+fn write_typed_writes_exactly_the_encoded_bytes() {
+    let mut buffer = [0xaau8; 16];
+    let address = buffer.as_mut_ptr() as usize;
+    for text in ["i32:-2", "u16:65535", "f64:1.5", "bytes:90 90 90", "strz:hey", "utf16:ok"] {
+        let value = text.parse::<TypedValue>().unwrap();
+        let bytes = value.to_bytes();
+        buffer.fill(0xaa);
+        write_typed(Pid::this(), address, &value, Endianness::Native).unwrap();
+        let written = std::hint::black_box(&buffer);
+        assert_eq!(&written[..bytes.len()], bytes.as_slice(), "{}", text);
+        assert!(written[bytes.len()..].iter().all(|x| *x == 0xaa), "{} wrote past its end", text);
+    }
+}
@@ -0,0 +1,73 @@
+#![cfg(target_os = "linux")]
+mod common;
+use std::{io::Write, process::{Command, Stdio}, time::Duration};
+use memory::{Process, RegionFilter, ScanOptions, UnknownScan};
+use common::Target;
+
+impl Target {
+    fn process(&self) -> Process {
+        Process::attach(self.pid()).unwrap()
+    }
+
+    // Runs the commands in a session on the target, returning what it printed
+    fn session(&self, commands: &str) -> String {
+        let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(self.pid().to_string()).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+        session.stdin.take().unwrap().write_all(commands.as_bytes()).unwrap();
+        let output = session.wait_with_output().unwrap();
+        format!("{}{}", String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap())
+    }
+}
+
+fn heap() -> ScanOptions {
+    ScanOptions { filter: Some("heap".parse::<RegionFilter>().unwrap()), ..ScanOptions::default() }
+}
+
+#[test]
+fn narrows_by_how_values_changed() {
+    let target = Target::spawn();
+    let mut unknown = UnknownScan::start(target.process(), 4, &heap()).unwrap();
+    let heap_len = unknown.snapshot().len();
+    assert_eq!((unknown.generation, unknown.candidates()), (0, heap_len / 4));
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(unknown.narrow::<i32>(target.process(), |old, new| new > old).unwrap(), 0);
+    assert_eq!(unknown.generation, 1);
+    assert!(unknown.addresses().contains(&target.player), "{:x?}", unknown.addresses());
+    // Each rescan compares with the one before, and hp never goes down
+    std::thread::sleep(Duration::from_millis(50));
+    unknown.narrow::<i32>(target.process(), |old, new| new < old).unwrap();
+    assert!(!unknown.addresses().contains(&target.player));
+    assert!(unknown.narrow::<i64>(target.process(), |old, new| new != old).is_err());
+}
+
+#[test]
+fn keeps_what_did_not_change() {
+    let target = Target::spawn();
+    let mut unknown = UnknownScan::start(target.process(), 4, &ScanOptions::default()).unwrap();
+    unknown.narrow::<i32>(target.process(), |old, new| new == old).unwrap();
+    let addresses = unknown.addresses();
+    // The block's planted values, every page of it
+    assert!(addresses.contains(&target.block) && addresses.contains(&(target.block + 0x8_0000)));
+    assert!(!addresses.contains(&target.player));
+    assert!(addresses.iter().all(|x| x % 4 == 0));
+}
+
+#[test]
+fn candidates_follow_the_alignment() {
+    let target = Target::spawn();
+    let aligned = UnknownScan::start(target.process(), 4, &heap()).unwrap();
+    let unaligned = UnknownScan::start(target.process(), 4, &ScanOptions { alignment: Some(1), ..heap() }).unwrap();
+    // Within each chunk, every offset a whole value starts at
+    let chunks = unaligned.snapshot().chunks.len();
+    assert_eq!(unaligned.candidates(), unaligned.snapshot().len() - 3 * chunks);
+    assert_eq!(aligned.candidates(), aligned.snapshot().len() / 4);
+}
+
+#[test]
+fn the_session_lists_candidates_once_there_are_few() {
+    let target = Target::spawn();
+    let output = target.session("rescan changed\nscan unknown i32\nlist\nrescan increased\nlist\n");
+    assert!(output.contains("rescan changed compares with the values scan unknown stored"), "{}", output);
+    assert!(output.contains("candidates: ~") && output.contains("(region-level)"), "{}", output);
+    assert!(output.contains("listed once rescans leave at most 1.0M"), "{}", output);
+    assert!(output.contains(&format!("0x{:x} (heap+", target.player)), "{}", output);
+}
@@ -0,0 +1,110 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+mod common;
+use std::{io::Write, process::{Command, Stdio}, sync::{Arc, Mutex, mpsc::channel}, time::Duration};
+use memory::{ElfFile, Location, WatchCallback, WatchEvent, WatchKind, Watcher, backtrace, demangle, executable_path, list_threads};
+use nix::unistd::Pid;
+use common::Target;
+
+impl Target {
+    fn tick_thread(&self) -> Pid {
+        list_threads(self.pid()).unwrap().into_iter().map(|x| x.tid).find(|&x| x != self.pid()).unwrap()
+    }
+
+    // The events of the first few hits on the watcher
+    fn hits(&self, arm: impl FnOnce(&Watcher), depth: usize) -> Vec<WatchEvent> {
+        let (send, hits) = channel();
+        let send = Mutex::new(send);
+        let watcher = Watcher::start(self.pid(), WatchCallback(Arc::new(move |event| {
+            let _ = send.lock().unwrap().send(event.clone());
+        }))).unwrap();
+        watcher.set_backtrace_depth(depth);
+        arm(&watcher);
+        (0..3).map(|_| hits.recv_timeout(Duration::from_secs(5)).unwrap()).collect()
+    }
+}
+
+fn symbol(frame: &Location) -> &str {
+    frame.symbol.as_ref().map(|x| x.0.as_str()).unwrap_or("")
+}
+
+#[test]
+fn demangles_rust_names() {
+    assert_eq!(demangle("_ZN12bench_target4tick17h0123456789abcdefE"), "bench_target::tick");
+    assert_eq!(demangle("_ZN12bench_target4main28_$u7b$$u7b$closure$u7d$$u7d$17h0123456789abcdefE"), "bench_target::main::{{closure}}");
+    assert_eq!(demangle("_ZN4core3ptr42drop_in_place$LT$alloc..string..String$GT$17h0123456789abcdefE"), "core::ptr::drop_in_place<alloc::string::String>");
+    assert_eq!(demangle("_RNvNtNtCsjrHSEGnQ3l9_3std6thread9functions5sleep"), "std::thread::functions::sleep");
+    assert_eq!(demangle("_RNCNvCs1234_12bench_target4main0B3_"), "bench_target::main::{{closure}}");
+    // C++ and v0 generics are left alone
+    for name in ["_ZN3foo3barEv", "_RINvNtCs1_3std2io4readNtB2_5StdinEB2_", "memcpy", "_ZN3foo"] {
+        assert_eq!(demangle(name), name);
+    }
+}
+
+// Symbols are in the file's own addresses, which the loaded image starts from at its base
+#[test]
+fn reads_the_symbols_of_a_module() {
+    let target = Target::spawn();
+    let elf = ElfFile::read(&executable_path(target.pid()).unwrap()).unwrap();
+    let base = memory::modules(target.pid()).unwrap().into_iter().find(|x| x.name == "bench_target").unwrap().base;
+    let (tick, into) = elf.symbol_at(target.tick.unwrap() - base + elf.image_start).unwrap();
+    assert_eq!((demangle(&tick.name).as_str(), into), ("bench_target::tick", 0));
+    assert!(elf.section(".eh_frame").is_some_and(|x| !x.1.is_empty()));
+    assert!(ElfFile::parse(b"\x7fELF\x01\x01".to_vec()).is_err());
+    assert!(ElfFile::parse(b"\x7fELF\x02\x01\x01".to_vec()).is_err());
+}
+
+// The tick thread is asleep in libc, called from std, called from the closure main started it with
+#[test]
+fn unwinds_a_thread_on_demand() {
+    let target = Target::spawn();
+    let frames = backtrace(target.pid(), target.tick_thread(), 16).unwrap();
+    let names = frames.iter().map(symbol).collect::<Vec<&str>>();
+    assert!(frames[0].module.as_ref().is_some_and(|x| x.0.starts_with("libc")), "{:?}", frames);
+    let closure = names.iter().position(|x| *x == "bench_target::main::{{closure}}").unwrap_or_else(|| panic!("{:?}", names));
+    assert!(names[..closure].contains(&"std::thread::functions::sleep"), "{:?}", names);
+    assert!(frames.len() > closure + 1);
+    assert_eq!(backtrace(target.pid(), target.tick_thread(), 2).unwrap(), frames[..2]);
+    assert!(backtrace(target.pid(), Pid::from_raw(1), 16).is_err());
+    let status = std::fs::read_to_string(format!("/proc/{}/status", target.pid())).unwrap();
+    assert!(status.contains("TracerPid:\t0\n"));
+}
+
+// A breakpoint's first frame is the function it is on, so the caller comes next; a watchpoint's
+// is the instruction after the write, inside tick
+#[test]
+fn hits_come_with_a_backtrace() {
+    let target = Target::spawn();
+    for event in target.hits(|x| x.break_at(target.tick.unwrap()).unwrap(), 4) {
+        let WatchEvent::Break(hit) = event else { panic!("{:?}", event) };
+        assert_eq!(hit.backtrace[0].address, target.tick.unwrap());
+        assert_eq!(hit.backtrace[0].symbol, Some(("bench_target::tick".to_string(), 0)));
+        assert_eq!(symbol(&hit.backtrace[1]), "bench_target::main::{{closure}}");
+        assert_eq!(hit.backtrace.len(), 4);
+    }
+    for event in target.hits(|x| x.watch(target.player, 4, WatchKind::Write).unwrap(), 2) {
+        let WatchEvent::Watch(hit) = event else { panic!("{:?}", event) };
+        assert_eq!((hit.backtrace[0].address, symbol(&hit.backtrace[0])), (hit.rip, "bench_target::tick"));
+        assert_eq!(symbol(&hit.backtrace[1]), "bench_target::main::{{closure}}");
+        assert_eq!(hit.backtrace.len(), 2);
+    }
+    for event in target.hits(|x| x.watch(target.player, 4, WatchKind::Write).unwrap(), 0) {
+        let WatchEvent::Watch(hit) = event else { panic!("{:?}", event) };
+        assert!(hit.backtrace.is_empty());
+    }
+}
+
+#[test]
+fn the_session_prints_backtraces() {
+    let target = Target::spawn();
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(target.pid().to_string()).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    let mut stdin = session.stdin.take().unwrap();
+    writeln!(stdin, "bt {}\nbt depth 3\nbreak 0x{:x}", target.tick_thread(), target.tick.unwrap()).unwrap();
+    std::thread::sleep(Duration::from_millis(200));
+    drop(stdin);
+    let output = session.wait_with_output().unwrap();
+    let (stdout, stderr) = (String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap());
+    assert!(stdout.lines().any(|x| x.starts_with('#') && x.contains("!std::thread::functions::sleep+0x")), "{}", stdout);
+    assert!(stdout.contains("hits are shown with up to 3 frames"), "{}", stdout);
+    assert!(stderr.contains("    #0  bench_target!bench_target::tick+0x0\n    #1  bench_target!bench_target::main::{{closure}}+0x"), "{}", stderr);
+    assert!(!stderr.contains("    #3 "), "{}", stderr);
+}
@@ -0,0 +1,135 @@
+#![cfg(target_os = "linux")]
+mod common;
+use std::{io::{BufRead, BufReader, Lines, Write}, process::{Child, ChildStdout, Command, Stdio}};
+use memory::{Endianness, Pid, RegionCache, ScanOptions, ScanStats, find_value, read_bytes_from_process, read_scalar, reduce_found_values, resolve_address, write_scalar};
+use common::victim_path;
+
+// Must match the layout documented in examples/victim.rs
+const HP: usize = 0x0;
+const GOLD: usize = 0x8;
+const SPEED: usize = 0x10;
+const NAME: usize = 0x14;
+
+// The victim, ticking only when told to
+struct Victim {
+    child: Child,
+    lines: Lines<BufReader<ChildStdout>>,
+    pid: Pid,
+    player: usize,
+    pointer: usize,
+}
+
+impl Victim {
+    fn spawn() -> Victim {
+        let mut child = Command::new(victim_path()).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+        let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+        let mut next = |prefix: &str| lines.next().unwrap().unwrap().strip_prefix(prefix).unwrap().to_string();
+        let pid = Pid::from_raw(next("pid ").parse().unwrap());
+        let player = next("player 0x");
+        let player = usize::from_str_radix(player.split_once(' ').unwrap().0, 16).unwrap();
+        let pointer = usize::from_str_radix(&next("pointer 0x"), 16).unwrap();
+        Victim { child, lines, pid, player, pointer }
+    }
+
+    fn tick(&mut self) {
+        writeln!(self.child.stdin.as_mut().unwrap()).unwrap();
+        assert_eq!(self.lines.next().unwrap().unwrap(), "ticked");
+    }
+}
+
+impl Drop for Victim {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn the_fields_are_where_the_layout_says() {
+    let mut victim = Victim::spawn();
+    assert_eq!(read_scalar::<i32>(victim.pid, victim.player + HP, Endianness::Native).unwrap(), 100);
+    assert_eq!(read_scalar::<i64>(victim.pid, victim.player + GOLD, Endianness::Native).unwrap(), 1000);
+    assert_eq!(read_scalar::<f32>(victim.pid, victim.player + SPEED, Endianness::Native).unwrap(), 1.5);
+    assert_eq!(read_bytes_from_process(victim.pid, 16, victim.player + NAME).unwrap(), b"Player One\0\0\0\0\0\0");
+    victim.tick();
+    assert_eq!(read_scalar::<i32>(victim.pid, victim.player + HP, Endianness::Native).unwrap(), 99);
+    assert_eq!(read_scalar::<i64>(victim.pid, victim.player + GOLD, Endianness::Native).unwrap(), 1025);
+    assert_eq!(read_scalar::<f32>(victim.pid, victim.player + SPEED, Endianness::Native).unwrap(), 3.0);
+}
+
+// What a user does first: scan for the value shown, let it change, and narrow to what changed with it
+#[test]
+fn a_scan_and_a_rescan_find_the_hp() {
+    let mut victim = Victim::spawn();
+    let (mut found, _) = find_value(victim.pid, 100i32, &ScanOptions::default()).unwrap();
+    assert!(found.contains(&(victim.player + HP)));
+    victim.tick();
+    reduce_found_values(victim.pid, &mut found, 99i32, &ScanOptions::default(), &mut ScanStats::default()).unwrap();
+    assert!(found.contains(&(victim.player + HP)), "{:x?}", found);
+}
+
+// Ticks change what the attached tool wrote rather than putting back their own copy
+#[test]
+fn a_write_is_what_the_next_tick_starts_from() {
+    let mut victim = Victim::spawn();
+    write_scalar(victim.pid, victim.player + GOLD, 5000i64, Endianness::Native).unwrap();
+    victim.tick();
+    assert_eq!(read_scalar::<i64>(victim.pid, victim.player + GOLD, Endianness::Native).unwrap(), 5025);
+}
+
+// Gold goes from 1000 to 1025 to 1050, up 2.5% and then about 2.44%
+#[test]
+fn a_rescan_by_percent_finds_the_gold() {
+    let mut victim = Victim::spawn();
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(victim.pid.to_string()).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut stdin = session.stdin.take().unwrap();
+    let mut lines = BufReader::new(session.stdout.take().unwrap()).lines();
+    let mut run = |command: &str, last: &str| {
+        writeln!(stdin, "{}", command).unwrap();
+        lines.by_ref().map(|x| x.unwrap()).find(|x| x.contains(last)).unwrap()
+    };
+    run("scan unknown i64", "candidates");
+    victim.tick();
+    run("rescan increased 2.5% 0.01%", "matches");
+    victim.tick();
+    assert!(run("rescan increased 2.44% 0.01%", "matches").contains(" matches"));
+    let listed = run("list", &format!("0x{:x} ", victim.player + GOLD));
+    assert!(listed.ends_with(" 1050 [rw-p]"), "{}", listed);
+    drop(stdin);
+    assert!(session.wait().unwrap().success());
+}
+
+// Loading a save puts the hp back to what it was when the scan started
+#[test]
+fn a_rescan_for_the_initial_value_finds_the_restored_hp() {
+    let mut victim = Victim::spawn();
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(victim.pid.to_string()).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut stdin = session.stdin.take().unwrap();
+    let mut lines = BufReader::new(session.stdout.take().unwrap()).lines();
+    let mut run = |command: &str, last: &str| {
+        writeln!(stdin, "{}", command).unwrap();
+        lines.by_ref().map(|x| x.unwrap()).find(|x| x.contains(last)).unwrap()
+    };
+    run("scan i32 100", "matches");
+    victim.tick();
+    run("rescan 99", "matches");
+    write_scalar(victim.pid, victim.player + HP, 100i32, Endianness::Native).unwrap();
+    run("rescan initial", "matches");
+    let listed = run("list", &format!("0x{:x} ", victim.player + HP));
+    assert!(listed.ends_with(" 100 [rw-p]"), "{}", listed);
+    drop(stdin);
+    assert!(session.wait().unwrap().success());
+}
+
+// The known-correct answer for a pointer scan: the static holding the player's address
+#[test]
+fn the_static_points_at_the_player() {
+    let victim = Victim::spawn();
+    let (found, _) = find_value(victim.pid, victim.player as u64, &ScanOptions::default()).unwrap();
+    assert!(found.contains(&victim.pointer), "0x{:x} in {:x?}", victim.pointer, found);
+    let mut regions = RegionCache::new(victim.pid).unwrap();
+    let module = regions.module_for_address(victim.pointer).unwrap().clone();
+    assert_eq!(module.name, "victim");
+    let chain = format!("victim+0x{:x}->0x{:x}", victim.pointer - module.base, GOLD);
+    assert_eq!(resolve_address(victim.pid, &mut regions, &chain).unwrap(), victim.player + GOLD);
+}
@@ -0,0 +1,193 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+mod common;
+use std::{io::Write, process::{Command, Stdio}, sync::{Arc, mpsc::channel}, time::Duration};
+use memory::{Access, Endianness, Process, ProcessMemory, PtraceSession, WatchCallback, WatchEvent, WatchKind, Watcher, read_scalar};
+use common::Target;
+
+impl Target {
+    fn tracer(&self) -> String {
+        let status = std::fs::read_to_string(format!("/proc/{}/status", self.pid())).unwrap();
+        status.lines().find_map(|x| x.strip_prefix("TracerPid:")).unwrap().trim().to_string()
+    }
+}
+
+fn ignore() -> WatchCallback {
+    WatchCallback(Arc::new(|_| {}))
+}
+
+// The writes come from the tick thread rather than the main one, out of the target's own code
+#[test]
+fn catches_writes_with_the_thread_and_value() {
+    let target = Target::spawn();
+    let (send, hits) = channel();
+    let send = std::sync::Mutex::new(send);
+    let watcher = Watcher::start(target.pid(), WatchCallback(Arc::new(move |event| {
+        if let WatchEvent::Watch(hit) = event {
+            let _ = send.lock().unwrap().send(hit.clone());
+        }
+    }))).unwrap();
+    watcher.watch(target.player, 4, WatchKind::Write).unwrap();
+    let caught = (0..3).map(|_| hits.recv_timeout(Duration::from_secs(5)).unwrap()).collect::<Vec<_>>();
+    let process = Process::attach(target.pid()).unwrap();
+    let executable = process.executable_path().unwrap();
+    let code = process.memory_regions().unwrap().into_iter().filter(|x| x.executable && x.pathname == executable).collect::<Vec<_>>();
+    for pair in caught.windows(2) {
+        let (before, after) = (i32::from_ne_bytes(pair[0].value[..].try_into().unwrap()), i32::from_ne_bytes(pair[1].value[..].try_into().unwrap()));
+        assert_eq!(after, before + 1);
+    }
+    for hit in &caught {
+        assert_eq!(hit.address, target.player);
+        assert_ne!(hit.thread, target.pid());
+        assert!(code.iter().any(|x| x.contains(hit.rip)), "0x{:x}", hit.rip);
+        assert!(hit.code_start <= hit.rip && hit.rip < hit.code_start + hit.code.len());
+    }
+    let watches = watcher.watches();
+    assert_eq!(watches.len(), 1);
+    assert!(watches[0].hits[&(caught[0].rip, Access::Write)] >= 3);
+}
+
+// One watchpoint per debug register, each of a size the hardware has and aligned to it
+#[test]
+fn refuses_what_the_debug_registers_cannot_hold() {
+    let target = Target::spawn();
+    let watcher = Watcher::start(target.pid(), ignore()).unwrap();
+    for offset in [0, 4, 8, 12] {
+        watcher.watch(target.player + offset, 4, WatchKind::Write).unwrap();
+    }
+    assert!(watcher.watch(target.player + 16, 4, WatchKind::Write).unwrap_err().to_string().contains("At most 4 watchpoints"));
+    assert!(watcher.watch(target.player + 2, 2, WatchKind::Write).unwrap_err().to_string().contains("already watched"));
+    watcher.unwatch(target.player + 12).unwrap();
+    assert!(watcher.watch(target.player + 16, 3, WatchKind::Write).unwrap_err().to_string().contains("1, 2, 4 or 8 bytes"));
+    assert!(watcher.watch(target.player + 18, 4, WatchKind::Access).unwrap_err().to_string().contains("multiple of 4"));
+    assert!(watcher.unwatch(target.player + 12).is_err());
+    watcher.watch(target.player + 16, 8, WatchKind::Access).unwrap();
+    assert_eq!(watcher.watches().iter().map(|x| (x.address - target.player, x.size)).collect::<Vec<_>>(), [(0, 4), (4, 4), (8, 4), (16, 8)]);
+}
+
+// Left armed, the next write's SIGTRAP would kill the target once nothing traces it
+#[test]
+fn disarms_and_detaches_when_dropped() {
+    let mut target = Target::spawn();
+    let watcher = Watcher::start(target.pid(), ignore()).unwrap();
+    watcher.watch(target.player, 4, WatchKind::Write).unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    assert_ne!(target.tracer(), "0");
+    drop(watcher);
+    assert_eq!(target.tracer(), "0");
+    let process = Process::attach(target.pid()).unwrap();
+    let before = read_scalar::<i32>(&process, target.player, Endianness::Native).unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(read_scalar::<i32>(&process, target.player, Endianness::Native).unwrap() > before);
+    assert!(target.child.try_wait().unwrap().is_none());
+}
+
+// A thread has one tracer, so the watcher goes through the session already open, and only one
+// watcher can have its traps
+#[test]
+fn shares_the_session_already_open() {
+    let target = Target::spawn();
+    let session = PtraceSession::seize(target.pid()).unwrap();
+    let tracer = target.tracer();
+    let watcher = Watcher::start(target.pid(), ignore()).unwrap();
+    watcher.watch(target.player, 4, WatchKind::Write).unwrap();
+    assert!(Watcher::start(target.pid(), ignore()).unwrap_err().to_string().contains("already being watched"));
+    assert_eq!(target.tracer(), tracer);
+    drop(watcher);
+    assert_eq!(target.tracer(), tracer);
+    Watcher::start(target.pid(), ignore()).unwrap().watch(target.player, 4, WatchKind::Write).unwrap();
+    drop(session);
+    assert_eq!(target.tracer(), "0");
+}
+
+// tick reads hp before writing it back one higher, from two instructions close together
+#[test]
+fn tells_reads_from_writes() {
+    let target = Target::spawn();
+    let (send, hits) = channel();
+    let send = std::sync::Mutex::new(send);
+    let watcher = Watcher::start(target.pid(), WatchCallback(Arc::new(move |event| {
+        if let WatchEvent::Watch(hit) = event {
+            let _ = send.lock().unwrap().send(hit.clone());
+        }
+    }))).unwrap();
+    watcher.watch(target.player, 4, WatchKind::Access).unwrap();
+    let caught = (0..8).map(|_| hits.recv_timeout(Duration::from_secs(5)).unwrap()).collect::<Vec<_>>();
+    let read = caught.iter().find(|x| x.access == Access::Read).unwrap();
+    let write = caught.iter().find(|x| x.access == Access::Write).unwrap();
+    assert!(read.rip < write.rip && write.rip - read.rip < 128, "0x{:x} 0x{:x}", read.rip, write.rip);
+    assert_eq!(i32::from_ne_bytes(write.value[..].try_into().unwrap()), i32::from_ne_bytes(read.value[..].try_into().unwrap()) + 1);
+    // Each (rip, access) is counted on its own
+    let reads = caught.iter().filter(|x| x.access == Access::Read).map(|x| x.count).collect::<Vec<u64>>();
+    assert_eq!(reads, (1..=reads.len() as u64).collect::<Vec<u64>>());
+    let watches = watcher.watches();
+    assert_eq!(watches[0].hits.keys().copied().collect::<Vec<_>>(), [(read.rip, Access::Read), (write.rip, Access::Write)]);
+}
+
+// The log printed as it goes has each instruction once, however often it ran, and the export
+// has the counts
+#[test]
+fn exports_the_hit_log() {
+    let target = Target::spawn();
+    let path = std::env::temp_dir().join(format!("rmh-watches-{}.json", std::process::id()));
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(target.pid().to_string()).stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::piped()).spawn().unwrap();
+    let mut stdin = session.stdin.take().unwrap();
+    writeln!(stdin, "watchaccess 0x{:x} 4", target.player).unwrap();
+    std::thread::sleep(Duration::from_millis(200));
+    writeln!(stdin, "watches export {}", path.display()).unwrap();
+    drop(stdin);
+    let output = session.wait_with_output().unwrap();
+    let log = String::from_utf8(output.stderr).unwrap();
+    let exported: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let watch = &exported[0];
+    assert_eq!((watch["address"].as_u64(), watch["kind"].as_str()), (Some(target.player as u64), Some("access")));
+    let hits = watch["hits"].as_array().unwrap();
+    assert_eq!(hits.iter().map(|x| x["access"].as_str().unwrap()).collect::<Vec<_>>(), ["read", "write"]);
+    assert!(hits.iter().all(|x| x["count"].as_u64().unwrap() > 5 && x["location"].as_str().unwrap().starts_with("bench_target!bench_target::tick+0x")));
+    assert_eq!((log.matches("\nread of ").count(), log.matches("\nwrite to ").count()), (1, 1), "{}", log);
+}
+
+// Only writes that changed the value are kept, the latest up to the limit
+#[test]
+fn keeps_a_bounded_history_of_changes() {
+    let target = Target::spawn();
+    let watcher = Watcher::start(target.pid(), ignore()).unwrap();
+    watcher.set_history_limit(4);
+    watcher.watch(target.player, 4, WatchKind::Access).unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+    let watch = &watcher.watches()[0];
+    assert_eq!(watch.history.len(), 4);
+    assert!(watch.dropped > 0);
+    let value = |x: [u8; 8]| i32::from_ne_bytes(x[..4].try_into().unwrap());
+    for change in &watch.history {
+        assert_eq!(value(change.new), value(change.old) + 1);
+        assert!(watch.hits.contains_key(&(change.rip, Access::Write)));
+    }
+    assert!(watch.history.iter().zip(watch.history.iter().skip(1)).all(|(a, b)| a.time <= b.time && b.old == a.new));
+    watcher.set_history_limit(2);
+    assert_eq!(watcher.watches()[0].history.len(), 2);
+}
+
+#[test]
+fn exports_the_history_as_csv() {
+    let target = Target::spawn();
+    let path = std::env::temp_dir().join(format!("rmh-history-{}.csv", std::process::id()));
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(target.pid().to_string()).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::null()).spawn().unwrap();
+    let mut stdin = session.stdin.take().unwrap();
+    writeln!(stdin, "set watch_history 3\nwatchwrite 0x{:x} 4", target.player).unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+    writeln!(stdin, "history 0x{:x}\nhistory export {} u32", target.player, path.display()).unwrap();
+    drop(stdin);
+    let output = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    assert!(output.contains(&format!("3 changes at 0x{:x}", target.player)) && output.contains("earlier ones"), "{}", output);
+    assert!(output.contains(" by tid ") && output.contains("before bench_target!bench_target::tick+0x"), "{}", output);
+    let csv = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let lines = csv.lines().collect::<Vec<&str>>();
+    assert_eq!((lines[0], lines.len()), ("address,time,thread,rip,old,new", 4));
+    for line in &lines[1..] {
+        let fields = line.split(',').collect::<Vec<&str>>();
+        assert_eq!(fields[0], format!("0x{:x}", target.player));
+        assert_eq!(fields[5].parse::<u32>().unwrap(), fields[4].parse::<u32>().unwrap() + 1);
+    }
+}
@@ -0,0 +1,120 @@
+mod common;
+use std::{sync::{Arc, mpsc::channel}, time::Duration};
+use memory::{AlertCondition, Derived, DerivedValue, Endianness, ExpressionCallback, ExpressionChange, ExpressionMonitor, MockProcess, ValueType, WatchedExpression};
+
+const HEAP: usize = 0x10000;
+
+// Leaked, as the monitor's thread needs a process that outlives the test's borrow
+fn mock() -> &'static MockProcess {
+    let mock = MockProcess::new(100);
+    mock.map(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [heap]", HEAP, HEAP + 0x1000)).unwrap();
+    mock.plant_value(HEAP, 45i32).unwrap();
+    mock.plant_value(HEAP + 4, 60i32).unwrap();
+    mock.plant_value(HEAP + 8, 1.5f32).unwrap();
+    Box::leak(Box::new(mock))
+}
+
+fn watched(name: &str, text: &str, addresses: &[usize]) -> WatchedExpression {
+    WatchedExpression { name: name.to_string(), derived: Derived::parse(text).unwrap(), addresses: addresses.to_vec() }
+}
+
+fn bytes(values: &[i32]) -> Vec<Option<Vec<u8>>> {
+    values.iter().map(|x| Some(x.to_ne_bytes().to_vec())).collect()
+}
+
+#[test]
+fn reads_are_kept_in_order_with_their_types() {
+    let derived = Derived::parse("(read i32 @hp) * 100 / (read u16 libgame.so+0x10)").unwrap();
+    let reads = derived.reads.iter().map(|x| (x.value_type, x.address.as_str())).collect::<Vec<(ValueType, &str)>>();
+    assert_eq!(reads, [(ValueType::I32, "@hp"), (ValueType::U16, "libgame.so+0x10")]);
+    assert_eq!(derived.text, "(read i32 @hp) * 100 / (read u16 libgame.so+0x10)");
+}
+
+// All integers work out as rescan expr's do, truncating; a float anywhere makes it all f64
+#[test]
+fn integers_stay_exact_and_floats_spread() {
+    let derived = Derived::parse("(read i32 a) * 100 / (read i32 b)").unwrap();
+    assert_eq!(derived.evaluate(&bytes(&[45, 60]), Endianness::Native), Some(DerivedValue::Integer(75)));
+    assert_eq!(derived.evaluate(&bytes(&[45, 0]), Endianness::Native), None);
+    let derived = Derived::parse("(read i32 a) / 2.0").unwrap();
+    assert_eq!(derived.evaluate(&bytes(&[45]), Endianness::Native), Some(DerivedValue::Float(22.5)));
+    let derived = Derived::parse("read f32 a * 2").unwrap();
+    assert_eq!(derived.evaluate(&[Some(1.25f32.to_ne_bytes().to_vec())], Endianness::Native), Some(DerivedValue::Float(2.5)));
+}
+
+#[test]
+fn a_missing_read_leaves_it_unavailable() {
+    let derived = Derived::parse("read i32 a + read i32 b").unwrap();
+    assert_eq!(derived.evaluate(&[Some(1i32.to_ne_bytes().to_vec()), None], Endianness::Native), None);
+    assert_eq!(derived.evaluate(&bytes(&[1]), Endianness::Native), None);
+}
+
+#[test]
+fn bad_expressions_are_refused() {
+    assert_eq!(Derived::parse("100 / 2").unwrap_err(), "'100 / 2' reads nothing; read <type> <address> reads a value, e.g. read i32 @hp");
+    assert_eq!(Derived::parse("read string @name").unwrap_err(), "read needs a numeric type such as i32 or f32, rather than 'string' in 'read string @name'");
+    assert_eq!(Derived::parse("read i32").unwrap_err(), "read i32 needs an address after it in 'read i32'");
+    assert!(Derived::parse("old + read i32 @hp").unwrap_err().starts_with("old is what rescan expr compares with"));
+}
+
+#[test]
+fn conditions_compare_the_value() {
+    let condition = AlertCondition::parse("hp_pct <= 20").unwrap();
+    assert_eq!(condition.to_string(), "hp_pct <= 20");
+    assert!(condition.holds(DerivedValue::Integer(20)) && !condition.holds(DerivedValue::Float(20.5)));
+    assert!(AlertCondition::parse("hp_pct<20").unwrap().holds(DerivedValue::Integer(19)));
+    assert!(AlertCondition::parse("hp != 0").unwrap().holds(DerivedValue::Integer(1)));
+    assert!(AlertCondition::parse("hp pct < 20").is_err() && AlertCondition::parse("< 20").is_err() && AlertCondition::parse("hp < low").is_err());
+}
+
+#[test]
+fn the_monitor_reports_what_each_works_out_to() {
+    let mock = mock();
+    let expression = watched("hp_pct", "(read i32 hp) * 100 / (read i32 max)", &[HEAP, HEAP + 4]);
+    assert_eq!(expression.read(mock, Endianness::Native), Some(DerivedValue::Integer(75)));
+    let (sender, receiver) = channel();
+    let callback = ExpressionCallback(Arc::new(move |change| { let _ = sender.send(change); }));
+    let gone = watched("gone", "read i32 nowhere", &[0x90000]);
+    let monitor = ExpressionMonitor::start(mock, vec![expression, gone], vec![Some(DerivedValue::Integer(75)), None], Duration::from_millis(5), Endianness::Native, callback).unwrap();
+    mock.plant_value(HEAP, 6i32).unwrap();
+    let change = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!((change.name.as_str(), change.old, change.new), ("hp_pct", Some(DerivedValue::Integer(75)), Some(DerivedValue::Integer(10))));
+    assert_eq!(monitor.values(), [Some(DerivedValue::Integer(10)), None]);
+    mock.unmap(HEAP).unwrap();
+    let change: ExpressionChange = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!((change.name.as_str(), change.new), ("hp_pct", None));
+}
+
+// The victim's hp starts at 100 of an assumed 100, and each tick takes 1 off it
+#[cfg(target_os = "linux")]
+#[test]
+fn the_cli_watches_expressions_and_alerts_on_them() {
+    use std::{io::{BufRead, BufReader, Write}, process::{Command, Stdio}};
+    let path = common::victim_path();
+    let mut victim = Command::new(path).arg("0").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut lines = BufReader::new(victim.stdout.take().unwrap()).lines();
+    let pid = lines.next().unwrap().unwrap().strip_prefix("pid ").unwrap().to_string();
+    lines.next().unwrap().unwrap();
+    lines.next().unwrap().unwrap();
+    let mut session = Command::new(env!("CARGO_BIN_EXE_memory")).arg(&pid).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    let mut stdin = session.stdin.take().unwrap();
+    let pause = || std::thread::sleep(Duration::from_millis(300));
+    writeln!(stdin, "scan i32 100").unwrap();
+    pause();
+    writeln!(victim.stdin.as_mut().unwrap()).unwrap();
+    assert_eq!(lines.next().unwrap().unwrap(), "ticked");
+    writeln!(stdin, "rescan 99\ntag #0 hp\nwatchexpr hp_pct \"(read i32 @hp) * 100 / 100 - 1\" --every 10ms\nmonitor start --when \"hp_pct < 98\"").unwrap();
+    pause();
+    writeln!(victim.stdin.as_mut().unwrap()).unwrap();
+    assert_eq!(lines.next().unwrap().unwrap(), "ticked");
+    pause();
+    writeln!(stdin, "watchexpr\nunwatchexpr hp_pct\nwatchexpr nothing \"read i32 @nobody\"").unwrap();
+    drop(stdin);
+    let stdout = String::from_utf8(session.wait_with_output().unwrap().stdout).unwrap();
+    let _ = victim.kill();
+    let _ = victim.wait();
+    assert!(stdout.contains("hp_pct = 98, watched every 10ms") && stdout.contains("monitoring 1 entries every 100ms and alerting when hp_pct < 98"), "{}", stdout);
+    assert!(stdout.contains("[watchexpr] hp_pct: 98 -> 97") && stdout.contains("[monitor] hp_pct < 98: 97 at "), "{}", stdout);
+    assert!(stdout.contains("hp_pct = 97  (read i32 @hp) * 100 / 100 - 1") && stdout.contains("unwatched hp_pct"), "{}", stdout);
+    assert!(stdout.contains("error: No result is tagged nobody; tag <address> nobody tags one"), "{}", stdout);
+}
@@ -0,0 +1,38 @@
+#![cfg(windows)]
+use memory::{Endianness, MemBackend, Pid, Process, ProcessMemory, modules_from_regions, read_scalar, write_scalar};
+
+#[test]
+fn reads_and_writes_through_the_handle() {
+    let planted = Box::into_raw(Box::new(0x5eed_1234u32));
+    let process = Process::attach(Pid::this()).unwrap();
+    assert_eq!(process.backend(), MemBackend::Win32);
+    assert!(!process.is_read_only());
+    assert_eq!(read_scalar::<u32>(&process, planted as usize, Endianness::Native).unwrap(), 0x5eed_1234);
+    write_scalar(&process, planted as usize, 0x0bad_f00du32, Endianness::Native).unwrap();
+    assert_eq!(unsafe { *planted }, 0x0bad_f00d);
+    assert!(process.read_at(0, &mut [0u8; 4]).is_err());
+}
+
+#[test]
+fn maps_regions_and_modules() {
+    let planted = Box::into_raw(Box::new(0u64));
+    let process = Process::attach(Pid::this()).unwrap();
+    let regions = process.memory_regions().unwrap();
+    assert!(regions.windows(2).all(|x| x[0].end <= x[1].start));
+    let heap = regions.iter().find(|x| x.contains(planted as usize)).unwrap();
+    assert!(heap.readable && heap.writable && !heap.executable);
+    let executable = process.executable_path().unwrap();
+    let modules = modules_from_regions(&regions);
+    let main = modules.iter().find(|x| x.path == executable).unwrap();
+    assert!(main.executable && main.contains(maps_regions_and_modules as *const () as usize));
+    assert!(modules.iter().any(|x| x.name.eq_ignore_ascii_case("kernel32.dll")));
+}
+
+#[test]
+fn refuses_what_windows_cannot_do() {
+    assert!(Process::attach(Pid::from_raw(-2)).is_err());
+    for backend in [MemBackend::ProcessVmReadv, MemBackend::ProcMem] {
+        assert!(Process::with_backend(Pid::this(), backend).unwrap_err().to_string().contains("only available on Linux"));
+    }
+    assert!(Process::seize(Pid::this()).is_err());
+}
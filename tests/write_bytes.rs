@@ -0,0 +1,118 @@
+use memory::{MemBackend, Process, WRITE_CHUNK_SIZE, write_bytes_to_process};
+use nix::unistd::Pid;
+use std::sync::{Mutex, MutexGuard};
+
+fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+// Tests run on parallel threads, and another test's mapping could otherwise land in a page one of
+// them just unmapped
+static MAPPINGS: Mutex<()> = Mutex::new(());
+
+// An anonymous read-write mapping in this process, with the given pages at its end unmapped again
+// so that whatever follows the mapped part is known to be unmapped
+struct Mapping {
+    address: usize,
+    mapped: usize,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl Mapping {
+    fn new(pages: usize, unmapped_pages: usize) -> Mapping {
+        let guard = MAPPINGS.lock().unwrap_or_else(|x| x.into_inner());
+        let len = pages * page_size();
+        let address = unsafe { libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, -1, 0) };
+        assert_ne!(address, libc::MAP_FAILED);
+        let mapped = (pages - unmapped_pages) * page_size();
+        if unmapped_pages > 0 {
+            assert_eq!(unsafe { libc::munmap((address as usize + mapped) as *mut libc::c_void, len - mapped) }, 0);
+        }
+        Mapping { address: address as usize, mapped, _guard: guard }
+    }
+
+    fn end(&self) -> usize {
+        self.address + self.mapped
+    }
+
+    fn bytes(&self) -> &[u8] {
+        std::hint::black_box(unsafe { std::slice::from_raw_parts(self.address as *const u8, self.mapped) })
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.address as *mut libc::c_void, self.mapped) };
+    }
+}
+
+fn pattern(len: usize) -> Vec<u8> {
+    (0..len).map(|x| (x % 251) as u8 + 1).collect()
+}
+
+// Both live backends, against this very process
+fn backends() -> Vec<Process> {
+    let pid = Pid::this();
+    vec![Process::with_backend(pid, MemBackend::ProcessVmReadv).unwrap(), Process::with_backend(pid, MemBackend::ProcMem).unwrap()]
+}
+
+#[test]
+fn writes_across_a_page_boundary() {
+    for process in backends() {
+        let mapping = Mapping::new(2, 0);
+        let start = mapping.address + page_size() - 32;
+        let data = pattern(64);
+        assert_eq!(write_bytes_to_process(&process, start, &data).unwrap(), 64, "{}", process.backend());
+        let offset = start - mapping.address;
+        assert_eq!(&mapping.bytes()[offset..offset + 64], data.as_slice());
+        assert!(mapping.bytes()[..offset].iter().chain(&mapping.bytes()[offset + 64..]).all(|x| *x == 0));
+    }
+}
+
+#[test]
+fn counts_only_the_bytes_before_an_unmapped_page() {
+    for process in backends() {
+        let mapping = Mapping::new(2, 1);
+        let start = mapping.end() - 40;
+        let data = pattern(100);
+        assert_eq!(write_bytes_to_process(&process, start, &data).unwrap(), 40, "{}", process.backend());
+        assert_eq!(&mapping.bytes()[mapping.mapped - 40..], &data[..40]);
+    }
+}
+
+#[test]
+fn fails_when_nothing_can_be_written() {
+    for process in backends() {
+        let mapping = Mapping::new(2, 1);
+        assert!(write_bytes_to_process(&process, mapping.end(), &pattern(16)).is_err(), "{}", process.backend());
+    }
+}
+
+#[test]
+fn writes_payloads_larger_than_a_chunk() {
+    for process in backends() {
+        let len = WRITE_CHUNK_SIZE * 5 / 2;
+        let mapping = Mapping::new(len.div_ceil(page_size()), 0);
+        let data = pattern(len);
+        assert_eq!(write_bytes_to_process(&process, mapping.address, &data).unwrap(), len, "{}", process.backend());
+        assert_eq!(&mapping.bytes()[..len], data.as_slice());
+    }
+}
+
+#[test]
+fn counts_partial_large_writes_across_chunks() {
+    for process in backends() {
+        let pages = (WRITE_CHUNK_SIZE * 3).div_ceil(page_size());
+        let mapping = Mapping::new(pages, 1);
+        let data = pattern(pages * page_size());
+        assert_eq!(write_bytes_to_process(&process, mapping.address, &data).unwrap(), mapping.mapped, "{}", process.backend());
+        assert_eq!(mapping.bytes(), &data[..mapping.mapped]);
+    }
+}
+
+#[test]
+fn writes_nothing_for_an_empty_payload() {
+    for process in backends() {
+        assert_eq!(write_bytes_to_process(&process, 0, &[]).unwrap(), 0);
+    }
+}
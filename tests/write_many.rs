@@ -0,0 +1,85 @@
+use memory::{MemBackend, Process, write_many};
+use nix::{errno::Errno, unistd::Pid};
+use std::sync::{Mutex, MutexGuard};
+
+fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+// Tests run on parallel threads, and another test's mapping could otherwise land in the hole
+static MAPPINGS: Mutex<()> = Mutex::new(());
+
+// Three pages of this process: read-write, unmapped, then read-only
+fn mixed_mapping() -> (usize, MutexGuard<'static, ()>) {
+    let guard = MAPPINGS.lock().unwrap_or_else(|x| x.into_inner());
+    let page = page_size();
+    let address = unsafe { libc::mmap(std::ptr::null_mut(), page * 3, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, -1, 0) } as usize;
+    assert_ne!(address as *mut libc::c_void, libc::MAP_FAILED);
+    unsafe {
+        assert_eq!(libc::munmap((address + page) as *mut libc::c_void, page), 0);
+        assert_eq!(libc::mprotect((address + page * 2) as *mut libc::c_void, page, libc::PROT_READ), 0);
+    }
+    (address, guard)
+}
+
+fn read_u32(address: usize) -> u32 {
+    unsafe { std::ptr::read_volatile(address as *const u32) }
+}
+
+fn backends() -> Vec<Process> {
+    let pid = Pid::this();
+    vec![Process::with_backend(pid, MemBackend::ProcessVmReadv).unwrap(), Process::with_backend(pid, MemBackend::ProcMem).unwrap()]
+}
+
+#[test]
+fn writes_every_entry_in_place() {
+    let (address, _guard) = mixed_mapping();
+    for process in backends() {
+        let values = [7u32, 8, 9].map(|x| x.to_ne_bytes());
+        let writes = [(address + 8, &values[0][..]), (address, &values[1][..]), (address + 100, &values[2][..])];
+        assert!(write_many(&process, &writes).iter().all(|x| x.is_ok()), "{}", process.backend());
+        assert_eq!([read_u32(address), read_u32(address + 8), read_u32(address + 100)], [8, 7, 9]);
+    }
+}
+
+#[test]
+fn failures_do_not_stop_the_rest() {
+    let (address, _guard) = mixed_mapping();
+    let page = page_size();
+    for process in backends() {
+        let value = 0x5151u32.to_ne_bytes();
+        let results = write_many(&process, &[(address, &value[..]), (address + page, &value[..]), (address + 4, &value[..])]);
+        assert!(results[0].is_ok() && results[2].is_ok(), "{}", process.backend());
+        assert!(results[1].is_err(), "{}", process.backend());
+        assert_eq!([read_u32(address), read_u32(address + 4)], [0x5151, 0x5151]);
+    }
+}
+
+#[test]
+fn read_only_regions_fail_with_a_permissions_error() {
+    let (address, _guard) = mixed_mapping();
+    let process = Process::with_backend(Pid::this(), MemBackend::ProcessVmReadv).unwrap();
+    let value = 1u32.to_ne_bytes();
+    let results = write_many(&process, &[(address + page_size() * 2, &value[..]), (address, &value[..])]);
+    assert_eq!(results[0].as_ref().unwrap_err().downcast_ref::<Errno>(), Some(&Errno::EACCES));
+    assert!(results[1].is_ok());
+}
+
+#[test]
+fn writes_more_entries_than_fit_in_one_call() {
+    let (address, _guard) = mixed_mapping();
+    let page = page_size();
+    let failing = [0, 1023, 1024, 2047];
+    let values = (0..2048u32).map(|x| x.to_ne_bytes()).collect::<Vec<[u8; 4]>>();
+    let writes = values.iter().enumerate().map(|(index, value)| {
+        let target = if failing.contains(&index) { address + page } else { address + (index % 1000) * 4 };
+        (target, &value[..])
+    }).collect::<Vec<(usize, &[u8])>>();
+    for process in backends() {
+        for (index, result) in write_many(&process, &writes).iter().enumerate() {
+            assert_eq!(result.is_err(), failing.contains(&index), "{} {}", process.backend(), index);
+        }
+        // Later entries overwrite earlier ones at the same address
+        assert_eq!(read_u32(address + 46 * 4), 2046);
+    }
+}
@@ -0,0 +1,43 @@
+use memory::{Encoding, Endianness, Pad, StringWriteOptions, encode_string, write_string};
+use nix::unistd::Pid;
+
+fn options(max_len: Option<usize>, pad: Pad, add_terminator: bool) -> StringWriteOptions {
+    StringWriteOptions { max_len, pad, add_terminator, ..StringWriteOptions::default() }
+}
+
+#[test]
+fn pads_over_a_longer_old_name() {
+    let mut name = *b"BARTHOLOMEW\0\0\0\0\0";
+    write_string(Pid::this(), name.as_mut_ptr() as usize, "ALICE", &options(Some(16), Pad::Null, false)).unwrap();
+    assert_eq!(&std::hint::black_box(name), b"ALICE\0\0\0\0\0\0\0\0\0\0\0");
+
+    assert_eq!(encode_string("ALICE", &options(Some(8), Pad::Space, false)).unwrap(), b"ALICE   ");
+    assert_eq!(encode_string("ALICE", &options(Some(8), Pad::Space, true)).unwrap(), b"ALICE\0  ");
+    // Without padding only the text is written
+    assert_eq!(encode_string("ALICE", &options(Some(8), Pad::None, false)).unwrap(), b"ALICE");
+}
+
+#[test]
+fn refuses_text_that_does_not_fit() {
+    let mut buffer = [0xffu8; 8];
+    assert!(write_string(Pid::this(), buffer.as_mut_ptr() as usize, "BARTHOLOMEW", &options(Some(8), Pad::Null, false)).is_err());
+    assert_eq!(std::hint::black_box(buffer), [0xff; 8]);
+    // The terminator counts towards the limit
+    assert!(encode_string("ALICE", &options(Some(5), Pad::Null, false)).is_ok());
+    assert!(encode_string("ALICE", &options(Some(5), Pad::Null, true)).is_err());
+}
+
+#[test]
+fn writes_utf16_in_whole_code_units() {
+    let utf16 = |max_len, pad, endianness| StringWriteOptions { encoding: Encoding::Utf16, endianness, max_len, pad, add_terminator: true };
+    assert_eq!(encode_string("Hé", &utf16(Some(10), Pad::Space, Endianness::Little)).unwrap(), [b'H', 0, 0xe9, 0, 0, 0, b' ', 0, b' ', 0]);
+    assert_eq!(encode_string("Hé", &utf16(Some(8), Pad::Null, Endianness::Big)).unwrap(), [0, b'H', 0, 0xe9, 0, 0, 0, 0]);
+    assert!(encode_string("Hé", &utf16(Some(7), Pad::Null, Endianness::Little)).is_err());
+    assert!(encode_string("Hé", &utf16(Some(4), Pad::Null, Endianness::Little)).is_err());
+}
+
+#[test]
+fn writes_the_text_as_is_without_a_limit() {
+    assert_eq!(encode_string("BARTHOLOMEW", &options(None, Pad::Null, false)).unwrap(), b"BARTHOLOMEW");
+    assert_eq!(encode_string("", &options(None, Pad::Space, true)).unwrap(), b"\0");
+}
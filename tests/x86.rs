@@ -0,0 +1,81 @@
+use memory::decode_instruction;
+
+fn len(code: &[u8]) -> usize {
+    decode_instruction(code).unwrap_or_else(|e| panic!("{:02x?}: {}", code, e)).len
+}
+
+// Prefixes, REX.W, ModRM with SIB and displacement, and each size of immediate
+#[test]
+fn measures_common_instructions() {
+    let cases: &[&[u8]] = &[
+        &[0x48, 0x83, 0xec, 0x48],
+        &[0x48, 0x89, 0x7c, 0x24, 0x08],
+        &[0xbe, 0x04, 0x00, 0x00, 0x00],
+        &[0x48, 0xb8, 1, 2, 3, 4, 5, 6, 7, 8],
+        &[0x66, 0xb8, 0x34, 0x12],
+        &[0x66, 0x81, 0xc1, 0x34, 0x12],
+        &[0x48, 0x81, 0xc1, 0x78, 0x56, 0x34, 0x12],
+        &[0xc7, 0x44, 0x24, 0x10, 0x01, 0x00, 0x00, 0x00],
+        &[0xc7, 0x84, 0x88, 0x00, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00],
+        &[0xf6, 0xc1, 0x01],
+        &[0xf6, 0xd9],
+        &[0x0f, 0x1f, 0x44, 0x00, 0x00],
+        &[0x66, 0x0f, 0x1f, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00],
+        &[0xf3, 0x0f, 0x1e, 0xfa],
+        &[0x0f, 0xb6, 0xc0],
+        &[0x66, 0x0f, 0x3a, 0x0f, 0xc1, 0x08],
+        &[0x66, 0x0f, 0x38, 0x00, 0xc1],
+        &[0xc5, 0xf8, 0x77],
+        &[0xc5, 0xfd, 0x6f, 0x06],
+        &[0xc4, 0xe3, 0x7d, 0x18, 0xc1, 0x01],
+        &[0x62, 0xf1, 0x7c, 0x48, 0x10, 0x46, 0x01],
+        &[0xa1, 1, 2, 3, 4, 5, 6, 7, 8],
+        &[0xc8, 0x10, 0x00, 0x00],
+        &[0x0f, 0x05],
+        &[0xf0, 0x48, 0x0f, 0xb1, 0x0a],
+    ];
+    for code in cases {
+        assert_eq!(len(code), code.len(), "{:02x?}", code);
+        // Trailing bytes belong to the next instruction
+        assert_eq!(len(&[code.to_vec(), vec![0x90; 4]].concat()), code.len(), "{:02x?}", code);
+    }
+}
+
+// The disp32 is where it is after any SIB and before any immediate. An absolute [disp32] needs
+// a SIB byte in 64-bit mode and is not rip-relative
+#[test]
+fn finds_rip_relative_operands() {
+    let lea = decode_instruction(&[0x48, 0x8d, 0x05, 0x10, 0x00, 0x00, 0x00]).unwrap();
+    assert_eq!((lea.len, lea.rip_relative), (7, Some(3)));
+    let store = decode_instruction(&[0xc7, 0x05, 0x10, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]).unwrap();
+    assert_eq!((store.len, store.rip_relative), (10, Some(2)));
+    let absolute = decode_instruction(&[0x48, 0x8b, 0x04, 0x25, 0x10, 0x00, 0x00, 0x00]).unwrap();
+    assert_eq!((absolute.len, absolute.rip_relative), (8, None));
+    let vex = decode_instruction(&[0xc5, 0xfa, 0x10, 0x05, 0x10, 0x00, 0x00, 0x00]).unwrap();
+    assert_eq!((vex.len, vex.rip_relative), (8, Some(4)));
+}
+
+#[test]
+fn flags_branches_and_where_the_code_stops() {
+    let flags = |code: &[u8]| {
+        let instruction = decode_instruction(code).unwrap();
+        (instruction.len, instruction.relative_branch, instruction.ends_flow)
+    };
+    assert_eq!(flags(&[0x74, 0x05]), (2, true, false));
+    assert_eq!(flags(&[0xe8, 0, 0, 0, 0]), (5, true, false));
+    assert_eq!(flags(&[0x0f, 0x84, 0, 0, 0, 0]), (6, true, false));
+    assert_eq!(flags(&[0xc7, 0xf8, 0, 0, 0, 0]), (6, true, false));
+    assert_eq!(flags(&[0xe9, 0, 0, 0, 0]), (5, true, true));
+    assert_eq!(flags(&[0xeb, 0x10]), (2, true, true));
+    assert_eq!(flags(&[0xc3]), (1, false, true));
+    assert_eq!(flags(&[0xff, 0xe0]), (2, false, true));
+    assert_eq!(flags(&[0xff, 0xd0]), (2, false, false));
+}
+
+// A guess would cut an instruction in two
+#[test]
+fn refuses_what_it_does_not_know() {
+    for code in [&[][..], &[0x48, 0x8b], &[0x06], &[0x0f, 0x0f, 0xc1, 0xb4], &[0x8f, 0xe8, 0x78, 0xc2, 0xc1, 0x01], &[0x66; 16]] {
+        assert!(decode_instruction(code).is_err(), "{:02x?}", code);
+    }
+}